@@ -21,7 +21,8 @@ METADATA = {
     "version": "0.1.0",
     "author": "ToruAI",
     "icon": "🐍",
-    "route": "/hello-python"
+    "route": "/hello-python",
+    "protocol_version": 3
 }
 
 # Plugin state
@@ -198,10 +199,10 @@ def handle_message(conn: socket.socket, message: Dict) -> None:
 
             # Send response
             response_message = {
-                "type": "http",
+                "type": "http_response",
                 "timestamp": datetime.now(timezone.utc).isoformat(),
                 "request_id": request_id,
-                "payload": response,
+                "payload": {"type": "http_response", "request_id": request_id, "payload": response},
             }
             write_message(conn, response_message)
 
@@ -212,13 +213,22 @@ def handle_message(conn: socket.socket, message: Dict) -> None:
 
             # Send response
             response_message = {
-                "type": "kv",
+                "type": "kv_response",
                 "timestamp": datetime.now(timezone.utc).isoformat(),
                 "request_id": request_id,
-                "payload": response,
+                "payload": {"type": "kv_response", "request_id": request_id, **response},
             }
             write_message(conn, response_message)
 
+        elif message_type == "ping":
+            # Answer health checks so the supervisor knows we're not hung
+            write_message(conn, {
+                "type": "pong",
+                "timestamp": datetime.now(timezone.utc).isoformat(),
+                "request_id": request_id,
+                "payload": {"type": "pong", "request_id": request_id},
+            })
+
         else:
             print(f"[HelloPlugin] Unknown message type: {message_type}", file=sys.stderr)
 
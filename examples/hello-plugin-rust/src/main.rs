@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 use std::env;
 use toru_plugin_api::{
-    PluginContext, PluginError, HttpMessageResponse, HttpRequest, HttpResponse, KvMessageResponse,
-    KvOp, Message, PluginMetadata, PluginProtocol, ToruPlugin,
+    PluginContext, PluginError, HttpMessageResponse, HttpRequest, HttpResponse,
+    KvOp, KvPage, Message, PluginMetadata, PluginProtocol, PluginTransport, ToruPlugin, TransportKind,
 };
+use toru_plugin_api::transport::SECRET_ENV;
 
 struct HelloPlugin {
     ctx: Option<PluginContext>,
@@ -22,6 +23,13 @@ impl HelloPlugin {
             author: Some("ToruAI".to_string()),
             icon: "🦀".to_string(),
             route: "/hello-rust".to_string(),
+            transport: TransportKind::Unix,
+            limits: Default::default(),
+            websockets: vec!["/echo".to_string()],
+            subscriptions: vec!["hello.*".to_string()],
+            capabilities: vec!["call:*".to_string()],
+            dependencies: Vec::new(),
+            protocol_version: toru_plugin_api::PROTOCOL_VERSION,
         }
     }
 
@@ -46,6 +54,16 @@ impl ToruPlugin for HelloPlugin {
     async fn handle_http(&self, req: HttpRequest) -> Result<HttpResponse, PluginError> {
         eprintln!("[HelloPlugin] HTTP request: {} {}", req.method, req.path);
 
+        if req.path == "/echo" {
+            // Send the request body back byte for byte (binary bodies arrive base64 encoded)
+            return Ok(HttpResponse {
+                status: 200,
+                headers: HashMap::from([("Content-Type".to_string(), "application/octet-stream".to_string())]),
+                body: req.body,
+                body_encoding: req.body_encoding,
+            });
+        }
+
         // Simple routing
         let (status, body) = if req.path == "/bundle.js" {
             // Serve frontend bundle
@@ -58,6 +76,8 @@ impl ToruPlugin for HelloPlugin {
             let response = serde_json::json!({
                 "message": "Hello from Rust plugin!",
                 "instance_id": self.ctx.as_ref().map(|c| &c.instance_id).unwrap_or(&"unknown".to_string()),
+                "instance_name": self.ctx.as_ref().and_then(|c| c.config.instance_name.as_ref()),
+                "settings": self.ctx.as_ref().map(|c| &c.config.settings),
                 "time": chrono::Utc::now().to_rfc3339(),
             });
             (200, Some(serde_json::to_string(&response)?))
@@ -77,6 +97,7 @@ impl ToruPlugin for HelloPlugin {
                 h
             },
             body,
+            body_encoding: Default::default(),
         })
     }
 
@@ -91,7 +112,7 @@ impl ToruPlugin for HelloPlugin {
                     _ => Ok(None),
                 }
             }
-            KvOp::Set { key, value } => {
+            KvOp::Set { key, value, .. } => {
                 eprintln!("[HelloPlugin] Setting {} = {}", key, value);
                 Ok(None)
             }
@@ -99,6 +120,21 @@ impl ToruPlugin for HelloPlugin {
                 eprintln!("[HelloPlugin] Deleting {}", key);
                 Ok(None)
             }
+            KvOp::List { .. } => {
+                let page = KvPage {
+                    keys: vec!["counter".to_string()],
+                    next_cursor: None,
+                };
+                Ok(Some(serde_json::to_string(&page)?))
+            }
+            KvOp::Incr { key, by } => {
+                eprintln!("[HelloPlugin] Incrementing {} by {}", key, by);
+                Ok(Some(by.to_string()))
+            }
+            KvOp::Cas { key, .. } => {
+                eprintln!("[HelloPlugin] Compare-and-swap on {}", key);
+                Ok(Some("false".to_string()))
+            }
         }
     }
 }
@@ -116,37 +152,35 @@ async fn main() {
 
     eprintln!("[HelloPlugin] Starting...");
 
-    // Get socket path from environment or use default
+    // Listen on the address the supervisor passed in, or a default socket path
     let plugin_id = HelloPlugin::metadata().id;
-    let socket_path = env::var("TORU_PLUGIN_SOCKET").unwrap_or_else(|_| {
-        format!("/tmp/toru-plugins/{}.sock", plugin_id)
+    let transport = PluginTransport::from_env().unwrap_or_else(|_| {
+        PluginTransport::Unix(format!("/tmp/toru-plugins/{}.sock", plugin_id).into())
     });
 
-    eprintln!("[HelloPlugin] Socket path: {}", socket_path);
+    eprintln!("[HelloPlugin] Address: {}", transport);
 
-    // Ensure socket directory exists
-    if let Some(parent) = std::path::Path::new(&socket_path).parent() {
-        std::fs::create_dir_all(parent).expect("Failed to create socket directory");
-    }
-
-    // Remove socket file if it exists
-    if std::path::Path::new(&socket_path).exists() {
-        std::fs::remove_file(&socket_path).expect("Failed to remove existing socket");
-    }
+    // TCP connections must present the secret from TORU_PLUGIN_SECRET
+    let listener = transport
+        .bind(env::var(SECRET_ENV).ok())
+        .await
+        .expect("Failed to bind plugin listener");
 
-    // Bind to Unix socket
-    let listener = tokio::net::UnixListener::bind(&socket_path)
-        .expect("Failed to bind to socket");
-
-    eprintln!("[HelloPlugin] Listening on socket...");
+    eprintln!("[HelloPlugin] Listening...");
 
     let mut plugin = HelloPlugin::new();
     let mut protocol = PluginProtocol::new();
+    // Events other plugins published, served on /events
+    let mut received_events: Vec<toru_plugin_api::PluginEventPayload> = Vec::new();
+    // Calls to other plugins in flight: call ID -> ID of the HTTP request waiting for it
+    let mut pending_calls: HashMap<String, String> = HashMap::new();
+    // How often each scheduled job ran, served on /jobs
+    let mut job_runs: HashMap<String, u32> = HashMap::new();
 
     // Accept connections
     loop {
         match listener.accept().await {
-            Ok((mut stream, _)) => {
+            Ok(mut stream) => {
                 eprintln!("[HelloPlugin] Connection accepted");
 
                 // Handle messages
@@ -157,18 +191,120 @@ async fn main() {
 
                             // Handle message
                             match &message.payload {
-                                toru_plugin_api::MessagePayload::Lifecycle { action, .. } => {
+                                toru_plugin_api::MessagePayload::Lifecycle { action, from_version, .. } => {
                                     if action == "init" {
                                         if let Ok(ctx) = parse_init_payload(&message) {
                                             if let Err(e) = plugin.init(ctx).await {
                                                 eprintln!("[HelloPlugin] Init error: {}", e);
                                             }
                                         }
+                                        // Let the host trigger an hourly greeting instead of running a timer
+                                        let jobs = vec![toru_plugin_api::ScheduledJob {
+                                            id: "hourly-greeting".to_string(),
+                                            schedule: "0 * * * *".to_string(),
+                                        }];
+                                        if let Err(e) = protocol.write_message(&mut stream, &Message::new_register_jobs(jobs)).await {
+                                            eprintln!("[HelloPlugin] Failed to register jobs: {}", e);
+                                        }
+                                    } else if action == "migrate" {
+                                        // Upgraded from an older version; nothing stored needs converting
+                                        let from_version = from_version.as_deref().unwrap_or_default();
+                                        let error = plugin.migrate(from_version).await.err().map(|e| e.to_string());
+                                        if let Some(request_id) = message.request_id.clone() {
+                                            if let Err(e) = protocol.write_message(&mut stream, &Message::new_migrated(request_id, error)).await {
+                                                eprintln!("[HelloPlugin] Failed to answer migrate: {}", e);
+                                            }
+                                        }
                                     } else if action == "shutdown" {
                                         eprintln!("[HelloPlugin] Shutdown received");
                                         std::process::exit(0);
                                     }
                                 }
+                                toru_plugin_api::MessagePayload::Http { request_id, payload } if payload.path == "/ticks" => {
+                                    // Streamed response: the head first, then the body in chunks
+                                    if let Err(e) = stream_ticks(&protocol, &mut stream, request_id).await {
+                                        eprintln!("[HelloPlugin] Failed to stream ticks: {}", e);
+                                    }
+                                }
+                                toru_plugin_api::MessagePayload::Http { request_id, payload } if payload.path == "/publish" => {
+                                    // Publish the request body as a hello.published event
+                                    let event = Message::new_event("hello.published", serde_json::json!({ "body": payload.body }));
+                                    let result = match protocol.write_message(&mut stream, &event).await {
+                                        Ok(()) => write_json_response(&protocol, &mut stream, request_id, 202, "{}".to_string()).await,
+                                        Err(e) => Err(e),
+                                    };
+                                    if let Err(e) = result {
+                                        eprintln!("[HelloPlugin] Failed to publish event: {}", e);
+                                    }
+                                }
+                                toru_plugin_api::MessagePayload::Http { request_id, payload } if payload.path == "/events" => {
+                                    let body = serde_json::to_string(&received_events).unwrap();
+                                    if let Err(e) = write_json_response(&protocol, &mut stream, request_id, 200, body).await {
+                                        eprintln!("[HelloPlugin] Failed to write HTTP response: {}", e);
+                                    }
+                                }
+                                toru_plugin_api::MessagePayload::Http { request_id, payload } if payload.path.starts_with("/relay/") => {
+                                    // /relay/<plugin>/<path> calls <path> on another plugin through the host
+                                    let target = payload.path.trim_start_matches("/relay/");
+                                    let (plugin_id, path) = target.split_once('/').unwrap_or((target, ""));
+                                    let call = toru_plugin_api::PluginCall {
+                                        plugin: plugin_id.to_string(),
+                                        request: HttpRequest { path: format!("/{}", path), ..payload.clone() },
+                                        timeout_ms: Some(5000),
+                                    };
+                                    let call_id = format!("call-{}", request_id);
+                                    pending_calls.insert(call_id.clone(), request_id.clone());
+                                    if let Err(e) = protocol.write_message(&mut stream, &Message::new_call_plugin(call_id, call)).await {
+                                        eprintln!("[HelloPlugin] Failed to call plugin: {}", e);
+                                    }
+                                }
+                                toru_plugin_api::MessagePayload::CallResult { request_id, payload } => {
+                                    let Some(http_request_id) = pending_calls.remove(request_id) else {
+                                        continue;
+                                    };
+                                    let (status, body) = match (&payload.response, &payload.error) {
+                                        (Some(response), _) => (response.status, response.body.clone().unwrap_or_default()),
+                                        (None, error) => (502, serde_json::json!({ "error": error }).to_string()),
+                                    };
+                                    if let Err(e) = write_json_response(&protocol, &mut stream, &http_request_id, status, body).await {
+                                        eprintln!("[HelloPlugin] Failed to write HTTP response: {}", e);
+                                    }
+                                }
+                                toru_plugin_api::MessagePayload::Http { request_id, payload } if payload.path == "/jobs" => {
+                                    // POST replaces the registered jobs with the ones in the body; GET shows run counts
+                                    let result = if payload.method == "POST" {
+                                        match serde_json::from_str(payload.body.as_deref().unwrap_or("[]")) {
+                                            Ok(jobs) => match protocol.write_message(&mut stream, &Message::new_register_jobs(jobs)).await {
+                                                Ok(()) => write_json_response(&protocol, &mut stream, request_id, 202, "{}".to_string()).await,
+                                                Err(e) => Err(e),
+                                            },
+                                            Err(e) => {
+                                                let body = serde_json::json!({ "error": e.to_string() }).to_string();
+                                                write_json_response(&protocol, &mut stream, request_id, 400, body).await
+                                            }
+                                        }
+                                    } else {
+                                        let body = serde_json::to_string(&job_runs).unwrap();
+                                        write_json_response(&protocol, &mut stream, request_id, 200, body).await
+                                    };
+                                    if let Err(e) = result {
+                                        eprintln!("[HelloPlugin] Failed to write HTTP response: {}", e);
+                                    }
+                                }
+                                toru_plugin_api::MessagePayload::Ping { request_id } => {
+                                    // Tells the supervisor we're not hung
+                                    if let Err(e) = protocol.write_message(&mut stream, &Message::new_pong(request_id.clone())).await {
+                                        eprintln!("[HelloPlugin] Failed to answer ping: {}", e);
+                                    }
+                                }
+                                toru_plugin_api::MessagePayload::Scheduled { job_id } => {
+                                    eprintln!("[HelloPlugin] Job {} is due", job_id);
+                                    *job_runs.entry(job_id.clone()).or_default() += 1;
+                                }
+                                toru_plugin_api::MessagePayload::Event { payload } => {
+                                    eprintln!("[HelloPlugin] Event {} from {:?}", payload.name, payload.source);
+                                    received_events.push(payload.clone());
+                                }
                                 toru_plugin_api::MessagePayload::Http { request_id, payload } => {
                                     match plugin.handle_http(payload.clone()).await {
                                         Ok(http_response) => {
@@ -176,16 +312,10 @@ async fn main() {
                                                 status: http_response.status,
                                                 headers: http_response.headers,
                                                 body: http_response.body,
+                                                body_encoding: http_response.body_encoding,
+                                                stream: false,
                                             };
-                                            let response_msg = Message::new_http(
-                                                request_id.clone(),
-                                                toru_plugin_api::types::HttpRequest {
-                                                    method: "GET".to_string(),
-                                                    path: "".to_string(),
-                                                    headers: HashMap::new(),
-                                                    body: Some(serde_json::to_string(&http_resp).unwrap()),
-                                                },
-                                            );
+                                            let response_msg = Message::new_http_response(request_id.clone(), http_resp);
                                             if let Err(e) = protocol.write_message(&mut stream, &response_msg).await {
                                                 eprintln!("[HelloPlugin] Failed to write HTTP response: {}", e);
                                             }
@@ -200,16 +330,7 @@ async fn main() {
                                     if let toru_plugin_api::KvMessagePayload::Request(kv_op) = payload {
                                         match plugin.handle_kv(kv_op.clone()).await {
                                             Ok(value) => {
-                                                let kv_resp = KvMessageResponse { value };
-                                                let response_msg = Message::new_http(
-                                                    request_id.clone(),
-                                                    toru_plugin_api::types::HttpRequest {
-                                                        method: "GET".to_string(),
-                                                        path: "".to_string(),
-                                                        headers: HashMap::new(),
-                                                        body: Some(serde_json::to_string(&kv_resp).unwrap()),
-                                                    },
-                                                );
+                                                let response_msg = Message::new_kv_response(request_id.clone(), value);
                                                 if let Err(e) = protocol.write_message(&mut stream, &response_msg).await {
                                                     eprintln!("[HelloPlugin] Failed to write KV response: {}", e);
                                                 }
@@ -220,6 +341,24 @@ async fn main() {
                                         }
                                     }
                                 }
+                                toru_plugin_api::MessagePayload::WsFrame { socket_id, payload } => {
+                                    // Echo text and binary frames back on the same socket
+                                    let echo = match payload.kind {
+                                        toru_plugin_api::WsFrameKind::Text => {
+                                            toru_plugin_api::WsFrame::text(payload.data.as_deref().unwrap_or_default())
+                                        }
+                                        toru_plugin_api::WsFrameKind::Binary => match payload.bytes() {
+                                            Ok(bytes) => toru_plugin_api::WsFrame::binary(&bytes),
+                                            Err(_) => continue,
+                                        },
+                                        _ => continue,
+                                    };
+                                    let echo_msg = Message::new_ws_frame(socket_id.clone(), echo);
+                                    if let Err(e) = protocol.write_message(&mut stream, &echo_msg).await {
+                                        eprintln!("[HelloPlugin] Failed to write WebSocket frame: {}", e);
+                                    }
+                                }
+                                _ => {}
                             }
                         }
                         Err(e) => {
@@ -240,12 +379,17 @@ fn parse_init_payload(message: &Message) -> Result<PluginContext, PluginError> {
     if let toru_plugin_api::MessagePayload::Lifecycle {
         action: _,
         payload,
+        ..
     } = &message.payload
     {
         if let Some(init_payload) = payload {
             return Ok(PluginContext {
                 instance_id: init_payload.instance_id.clone(),
-                config: toru_plugin_api::PluginConfig::default(),
+                config: toru_plugin_api::PluginConfig {
+                    instance_name: init_payload.instance_name.clone(),
+                    settings: init_payload.settings.clone(),
+                    ..Default::default()
+                },
                 kv: Box::new(DummyKvStore),
             });
         }
@@ -269,3 +413,42 @@ impl toru_plugin_api::PluginKvStore for DummyKvStore {
         Ok(())
     }
 }
+
+async fn write_json_response(
+    protocol: &PluginProtocol,
+    stream: &mut toru_plugin_api::PluginStream,
+    request_id: &str,
+    status: u16,
+    body: String,
+) -> Result<(), PluginError> {
+    let response = HttpMessageResponse {
+        status,
+        headers: HashMap::from([("Content-Type".to_string(), "application/json".to_string())]),
+        body: Some(body),
+        body_encoding: Default::default(),
+        stream: false,
+    };
+    protocol.write_message(stream, &Message::new_http_response(request_id.to_string(), response)).await
+}
+
+async fn stream_ticks(
+    protocol: &PluginProtocol,
+    stream: &mut toru_plugin_api::PluginStream,
+    request_id: &str,
+) -> Result<(), PluginError> {
+    let head = HttpMessageResponse {
+        status: 200,
+        headers: HashMap::from([("Content-Type".to_string(), "text/plain".to_string())]),
+        body: None,
+        body_encoding: Default::default(),
+        stream: true,
+    };
+    protocol.write_message(stream, &Message::new_http_response(request_id.to_string(), head)).await?;
+
+    for tick in 1..=3 {
+        let chunk = Message::new_http_chunk(request_id.to_string(), format!("tick {}\n", tick).as_bytes(), tick == 3);
+        protocol.write_message(stream, &chunk).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    Ok(())
+}
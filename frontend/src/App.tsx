@@ -11,10 +11,11 @@ import { Plugins } from './pages/Plugins';
 import { PluginView } from './pages/PluginView';
 import { Login } from './pages/Login';
 import { TooltipProvider } from '@/components/ui/tooltip';
+import { BASE_PATH } from '@/lib/api';
 
 function App() {
   return (
-    <BrowserRouter>
+    <BrowserRouter basename={BASE_PATH || undefined}>
       <AuthProvider>
         <TooltipProvider>
           <Routes>
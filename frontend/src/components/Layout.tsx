@@ -3,6 +3,7 @@ import { Home, Terminal, Settings, Activity, History, LogOut, User, Menu, Plug2
 import { cn } from '../lib/utils';
 import { Separator } from '@/components/ui/separator';
 import { ToruLogo } from './ToruLogo';
+import { PluginNotifications } from './PluginNotifications';
 import { useAuth } from '../contexts/AuthContext';
 import { Button } from '@/components/ui/button';
 import { Badge } from '@/components/ui/badge';
@@ -267,6 +268,8 @@ export function Layout() {
           </div>
         </main>
       </div>
+
+      {user && <PluginNotifications />}
     </div>
   );
 }
@@ -0,0 +1,119 @@
+import { useEffect, useState } from 'react';
+import { AlertCircle, AlertTriangle, CheckCircle2, Info, X } from 'lucide-react';
+import { useWebSocket } from '../hooks/useWebSocket';
+import { wsUrl } from '../lib/api';
+import { Alert, AlertDescription, AlertTitle } from '@/components/ui/alert';
+import { cn } from '../lib/utils';
+
+// How long a toast stays on screen
+const TOAST_DURATION_MS = 8000;
+
+// Toasts shown at once; older ones are dropped
+const MAX_TOASTS = 5;
+
+export interface PluginNotification {
+  type: 'plugin_notification';
+  plugin_id: string;
+  level: 'info' | 'success' | 'warning' | 'error';
+  title: string;
+  body?: string | null;
+  timestamp: string;
+}
+
+interface SystemNotificationMessage {
+  type: 'system_notification';
+  notification: {
+    kind: 'alert' | 'task_failed' | 'plugin_crash' | 'security';
+    level: 'info' | 'warning' | 'error';
+    title: string;
+    message: string;
+    timestamp: string;
+  };
+}
+
+interface Toast extends PluginNotification {
+  key: number;
+}
+
+// System notifications (alerts, failed tasks, plugin crashes, sign-ins) are
+// shown like plugin notifications, with their kind as the source
+function systemNotificationToast(message: SystemNotificationMessage): PluginNotification {
+  const { notification } = message;
+  return {
+    type: 'plugin_notification',
+    plugin_id: notification.kind.replace('_', ' '),
+    level: notification.level,
+    title: notification.title,
+    body: notification.message,
+    timestamp: notification.timestamp,
+  };
+}
+
+const levelStyles = {
+  info: { icon: Info, className: '' },
+  success: { icon: CheckCircle2, className: 'border-green-500/50 [&>svg]:text-green-500' },
+  warning: { icon: AlertTriangle, className: 'border-yellow-500/50 [&>svg]:text-yellow-500' },
+  error: { icon: AlertCircle, className: 'border-destructive/50 [&>svg]:text-destructive' },
+};
+
+// Toasts for the notifications plugins and the server send over the main WebSocket
+export function PluginNotifications() {
+  const { messages, clearMessages } = useWebSocket(wsUrl());
+  const [toasts, setToasts] = useState<Toast[]>([]);
+
+  useEffect(() => {
+    if (messages.length === 0) return;
+    const notifications = (messages as unknown[]).flatMap((m): PluginNotification[] => {
+      switch ((m as { type?: string }).type) {
+        case 'plugin_notification':
+          return [m as PluginNotification];
+        case 'system_notification':
+          return [systemNotificationToast(m as SystemNotificationMessage)];
+        default:
+          return [];
+      }
+    });
+    clearMessages();
+    if (notifications.length === 0) return;
+
+    const now = Date.now();
+    const added = notifications.map((n, i) => ({ ...n, key: now + i }));
+    setToasts((prev) => [...prev, ...added].slice(-MAX_TOASTS));
+    added.forEach((toast) => {
+      window.setTimeout(() => {
+        setToasts((prev) => prev.filter((t) => t.key !== toast.key));
+      }, TOAST_DURATION_MS);
+    });
+  }, [messages, clearMessages]);
+
+  const dismiss = (key: number) => {
+    setToasts((prev) => prev.filter((t) => t.key !== key));
+  };
+
+  if (toasts.length === 0) return null;
+
+  return (
+    <div className="fixed bottom-4 right-4 z-50 flex w-full max-w-sm flex-col gap-2">
+      {toasts.map((toast) => {
+        const { icon: Icon, className } = levelStyles[toast.level] ?? levelStyles.info;
+        return (
+          <Alert key={toast.key} className={cn('bg-card shadow-lg pr-10', className)}>
+            <Icon className="h-4 w-4" />
+            <AlertTitle>{toast.title}</AlertTitle>
+            <AlertDescription>
+              {toast.body && <p>{toast.body}</p>}
+              <p className="text-xs text-muted-foreground">{toast.plugin_id}</p>
+            </AlertDescription>
+            <button
+              className="absolute right-2 top-2 rounded-sm opacity-70 hover:opacity-100"
+              onClick={() => dismiss(toast.key)}
+              aria-label="Dismiss"
+            >
+              <X className="h-4 w-4" />
+            </button>
+          </Alert>
+        );
+      })}
+    </div>
+  );
+}
@@ -20,7 +20,7 @@ export function ToruLogo({ className = "", size = 'md', showText = true }: ToruL
   return (
     <div className={`flex items-center gap-2 ${className}`}>
       <img 
-        src="/favicon.png" 
+        src="favicon.png" 
         alt="Steering Center Logo" 
         className={`${sizeMap[size]} object-contain`}
       />
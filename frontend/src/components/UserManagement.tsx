@@ -278,6 +278,12 @@ export function UserManagement() {
                         <span className="text-xs text-muted-foreground">
                           {user.is_active ? 'Active' : 'Inactive'}
                         </span>
+                        {user.expired && <Badge variant="destructive">Expired</Badge>}
+                        {user.expires_soon && user.expires_at && (
+                          <Badge variant="outline">
+                            Expires {new Date(user.expires_at).toLocaleDateString()}
+                          </Badge>
+                        )}
                       </div>
                     </TableCell>
                     <TableCell className="text-xs text-muted-foreground">
@@ -1,11 +1,20 @@
 import { useEffect, useState } from 'react';
 import type { SystemResources } from '../lib/api';
-import { api } from '../lib/api';
+import { api, wsUrl } from '../lib/api';
+import { useWebSocket } from './useWebSocket';
 
+interface ResourcesMessage {
+  type: 'resources';
+  resources: SystemResources;
+}
+
+// Snapshots are pushed over the WebSocket; the first one is fetched over HTTP so
+// the page doesn't wait for the socket and auth errors surface as usual
 export function useSystemStats(intervalMs: number = 2000) {
   const [stats, setStats] = useState<SystemResources | null>(null);
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState<Error | null>(null);
+  const { connected, messages, send, clearMessages } = useWebSocket(wsUrl());
 
   useEffect(() => {
     const fetchStats = async () => {
@@ -22,12 +31,26 @@ export function useSystemStats(intervalMs: number = 2000) {
     };
 
     fetchStats();
-    const interval = setInterval(fetchStats, intervalMs);
+  }, []);
 
-    return () => {
-      clearInterval(interval);
-    };
-  }, [intervalMs]);
+  // Subscribe again after every reconnect
+  useEffect(() => {
+    if (!connected) return;
+    send({ type: 'subscribe_resources', interval_secs: Math.max(1, Math.round(intervalMs / 1000)) });
+    return () => send({ type: 'unsubscribe_resources' });
+  }, [connected, intervalMs, send]);
+
+  useEffect(() => {
+    if (messages.length === 0) return;
+    const snapshots = (messages as unknown[]).filter(
+      (m): m is ResourcesMessage => (m as { type?: string }).type === 'resources'
+    );
+    clearMessages();
+    if (snapshots.length === 0) return;
+    setStats(snapshots[snapshots.length - 1].resources);
+    setError(null);
+    setLoading(false);
+  }, [messages, clearMessages]);
 
   return { stats, loading, error };
 }
@@ -5,12 +5,19 @@ export interface TaskMessage {
   task_id?: string;
   data?: string;
   code?: number;
+  /** Position in the task's output, for resuming after a reconnect */
+  seq?: number;
 }
 
 export interface ClientMessage {
-  type: 'run' | 'cancel';
+  type: 'run' | 'cancel' | 'resume' | 'subscribe' | 'unsubscribe' | 'subscribe_resources' | 'unsubscribe_resources';
   script?: string;
   task_id?: string;
+  channel?: 'logs';
+  plugin_id?: string;
+  interval_secs?: number;
+  /** Last seq received per task */
+  tasks?: Record<string, number>;
 }
 
 export function useWebSocket(url: string) {
@@ -18,6 +25,8 @@ export function useWebSocket(url: string) {
   const [messages, setMessages] = useState<TaskMessage[]>([]);
   const wsRef = useRef<WebSocket | null>(null);
   const reconnectTimeoutRef = useRef<number | null>(null);
+  // Last seq received per running task, sent on reconnect to get what was missed
+  const lastSeqRef = useRef<Record<string, number>>({});
 
   const connect = useCallback(() => {
     if (wsRef.current?.readyState === WebSocket.OPEN) {
@@ -34,11 +43,23 @@ export function useWebSocket(url: string) {
           clearTimeout(reconnectTimeoutRef.current);
           reconnectTimeoutRef.current = null;
         }
+        if (Object.keys(lastSeqRef.current).length > 0) {
+          ws.send(JSON.stringify({ type: 'resume', tasks: lastSeqRef.current }));
+        }
       };
 
       ws.onmessage = (event) => {
         try {
           const message: TaskMessage = JSON.parse(event.data);
+          if (message.task_id && message.seq !== undefined) {
+            if (message.seq <= (lastSeqRef.current[message.task_id] ?? 0)) {
+              return;
+            }
+            lastSeqRef.current[message.task_id] = message.seq;
+          }
+          if (message.task_id && ['exit', 'error', 'cancelled'].includes(message.type)) {
+            delete lastSeqRef.current[message.task_id];
+          }
           setMessages((prev) => [...prev, message]);
         } catch (err) {
           console.error('Failed to parse WebSocket message:', err);
@@ -1,4 +1,12 @@
-const API_BASE = '/api';
+// Prefix the app is served under (e.g. '/steering'), set by the server in index.html
+export const BASE_PATH =
+  document.querySelector<HTMLMetaElement>('meta[name="toru-base-path"]')?.content ?? '';
+
+export const API_BASE = `${BASE_PATH}/api`;
+
+// URL of the main WebSocket
+export const wsUrl = () =>
+  `${window.location.protocol === 'https:' ? 'wss:' : 'ws:'}//${window.location.host}${API_BASE}/ws`;
 
 export interface CpuCore {
   name: string;
@@ -20,6 +28,33 @@ export interface NetworkInterface {
   transmitted: number;
 }
 
+export interface TemperatureSensor {
+  label: string;
+  temperature_celsius: number;
+  max_celsius: number | null;
+  critical_celsius: number | null;
+}
+
+export interface FanSensor {
+  label: string;
+  rpm: number;
+}
+
+export interface GpuInfo {
+  name: string;
+  vendor: 'nvidia' | 'amd';
+  utilization_percent: number | null;
+  memory_used: number | null;
+  memory_total: number | null;
+  temperature_celsius: number | null;
+}
+
+export interface BatteryInfo {
+  name: string;
+  percent: number | null;
+  status: string;
+}
+
 export interface SystemResources {
   cpu_percent: number;
   cpu_cores: CpuCore[];
@@ -36,8 +71,211 @@ export interface SystemResources {
   kernel_version: string | null;
   os_version: string | null;
   host_name: string | null;
+  temperatures: TemperatureSensor[];
+  fans: FanSensor[];
+  gpus: GpuInfo[];
+  batteries: BatteryInfo[];
+}
+
+export interface ProcessInfo {
+  pid: number;
+  parent_pid: number | null;
+  name: string;
+  command: string;
+  cpu_percent: number;
+  memory_bytes: number;
+  user: string | null;
+  start_time: string | null;
+  status: string;
+}
+
+export interface ProcessPage {
+  total: number;
+  processes: ProcessInfo[];
+}
+
+export interface ContainerInfo {
+  id: string;
+  name: string;
+  image: string;
+  state: string;
+  status: string;
+  created: string | null;
+  ports: string[];
+}
+
+export interface ContainerStats {
+  id: string;
+  name: string;
+  cpu_percent: number;
+  memory_bytes: number;
+  memory_limit: number;
+  network_rx_bytes: number;
+  network_tx_bytes: number;
+  pids: number;
+}
+
+export interface SystemLogEntry {
+  timestamp: string | null;
+  unit: string | null;
+  identifier: string | null;
+  priority: number | null;
+  message: string;
+}
+
+export interface SystemLogPage {
+  source: string;
+  entries: SystemLogEntry[];
+  next_cursor: string | null;
+}
+
+export interface SystemLogQuery {
+  unit?: string;
+  priority?: string;
+  since?: string;
+  until?: string;
+  limit?: number;
+  cursor?: string;
+}
+
+export interface ListeningSocket {
+  protocol: 'tcp' | 'udp';
+  address: string;
+  port: number;
+  pid: number | null;
+  process: string | null;
+  user: string | null;
+}
+
+export interface PackageUpdate {
+  name: string;
+  current_version: string | null;
+  new_version: string;
+  security: boolean;
+}
+
+export interface UpdateCheck {
+  checked_at: string;
+  package_manager: string | null;
+  total: number;
+  security: number;
+  packages: PackageUpdate[];
+  error: string | null;
+}
+
+export interface UpsInfo {
+  name: string;
+  model: string | null;
+  status: string;
+  on_battery: boolean;
+  low_battery: boolean;
+  charge_percent: number | null;
+  runtime_seconds: number | null;
+  load_percent: number | null;
+}
+
+export interface PowerAlert {
+  source: string;
+  kind: 'on_battery' | 'low_charge';
+  level: 'info' | 'warning' | 'error';
+  message: string;
+  resolved: boolean;
+  timestamp: string;
+}
+
+export interface PowerStatus {
+  batteries: BatteryInfo[];
+  ups: UpsInfo | null;
+  ups_error: string | null;
+  alert_threshold: number;
+  alerts: PowerAlert[];
+}
+
+export interface RestoreSummary {
+  backup_created_at: string;
+  backup_version: string;
+  plugin_config_restored: boolean;
+}
+
+export interface ExportedUser extends User {
+  password_hash?: string;
+}
+
+export interface ConfigDocument {
+  format: number;
+  version: string;
+  exported_at: string | null;
+  settings: Record<string, string>;
+  quick_actions: QuickAction[];
+  pipelines: unknown[];
+  scheduled_tasks: unknown[];
+  users: ExportedUser[];
 }
 
+export interface ImportSummary {
+  settings: number;
+  quick_actions: number;
+  pipelines: number;
+  scheduled_tasks: number;
+  users: number;
+  skipped_scheduled_tasks: number;
+  users_without_password: string[];
+}
+
+export interface DbStats {
+  file_size_bytes: number;
+  wal_size_bytes: number;
+  page_size: number;
+  page_count: number;
+  free_pages: number;
+  fragmentation_percent: number;
+  auto_vacuum: 'none' | 'full' | 'incremental';
+  journal_mode: string;
+  tables: { name: string; rows: number }[];
+}
+
+export type LogLevelName = 'trace' | 'debug' | 'info' | 'warn' | 'error';
+
+export interface LoggingConfig {
+  filter: string | null;
+  startup_filter: string | null;
+  saved_filter: string | null;
+  plugin_levels: Record<string, LogLevelName>;
+}
+
+export interface AccessLogEntry {
+  timestamp: string;
+  request_id: string;
+  method: string;
+  path: string;
+  status: number;
+  duration_ms: number;
+  user?: string;
+  ip?: string;
+  user_agent?: string;
+}
+
+export interface MaintenanceReport {
+  integrity_ok: boolean;
+  integrity_errors: string[];
+  freed_pages: number;
+  full_vacuum: boolean;
+  duration_ms: number;
+}
+
+export interface HostSession {
+  user: string;
+  terminal: string;
+  host: string | null;
+  ip: string | null;
+  pid: number;
+  login_time: string | null;
+  duration_seconds: number | null;
+  idle_seconds: number | null;
+}
+
+export type ProcessSort = 'pid' | 'name' | 'cpu' | 'memory' | 'start_time';
+
 export interface TaskHistory {
   id: string;
   script_name: string;
@@ -47,12 +285,88 @@ export interface TaskHistory {
   output: string | null;
 }
 
+export interface TaskSearchHit {
+  id: string;
+  script_name: string;
+  started_at: string;
+  finished_at: string | null;
+  exit_code: number | null;
+  snippet: string;
+}
+
 export interface QuickAction {
   id: string;
   name: string;
   script_path: string;
   icon: string | null;
   display_order: number;
+  env?: Record<string, string>;
+  params?: QuickActionParam[];
+  // Who besides admins sees the action; 'users' means those in allowed_users
+  visibility?: 'everyone' | 'admins' | 'users';
+  allowed_users?: string[];
+  // 'high' or requires_confirmation means the name must be typed to run it
+  danger_level?: 'low' | 'medium' | 'high';
+  requires_confirmation?: boolean;
+  // Over the last runs of the script, only returned by getQuickActions
+  stats?: QuickActionStats;
+}
+
+// Quick actions shared between instances, without IDs or local paths
+export interface ActionPack {
+  format: number;
+  version: string;
+  exported_at: string | null;
+  actions: Record<string, unknown>[];
+}
+
+export interface PackImportSummary {
+  created: string[];
+  replaced: string[];
+  skipped: string[];
+  renamed: [string, string][];
+  missing_scripts: string[];
+}
+
+export interface UserImportRow {
+  username: string;
+  display_name?: string;
+  role?: 'admin' | 'client';
+  password?: string;
+  // Create the account with a generated password, returned once
+  invite?: boolean;
+  is_active?: boolean;
+}
+
+export interface UserImportResult {
+  created: number;
+  failed: number;
+  rows: {
+    row: number;
+    username: string;
+    created: boolean;
+    id?: string;
+    initial_password?: string;
+    error?: string;
+  }[];
+}
+
+export interface QuickActionStats {
+  runs: number;
+  last_run_at: string | null;
+  last_exit_code: number | null;
+  avg_duration_secs: number | null;
+  success_rate: number | null;
+}
+
+// Input asked for when a quick action runs, passed to the script as PARAM_<NAME>
+export interface QuickActionParam {
+  name: string;
+  label?: string | null;
+  type: 'text' | 'select' | 'boolean' | 'secret';
+  required?: boolean;
+  default?: string | null;
+  options?: string[];
 }
 
 export interface User {
@@ -62,8 +376,41 @@ export interface User {
   role: 'admin' | 'client';
   is_active: boolean;
   created_at: string;
+  // After this the account can't sign in; expires_soon warns a week ahead
+  expires_at?: string | null;
+  expired?: boolean;
+  expires_soon?: boolean;
+  // Overrides of the role's session policy; null follows the role
+  session_hours?: number | null;
+  idle_timeout_minutes?: number | null;
+  email?: string | null;
+  phone?: string | null;
+  avatar_version?: string | null;
 }
 
+export interface Profile {
+  id: string;
+  username: string;
+  display_name: string | null;
+  email: string | null;
+  phone: string | null;
+  avatar_version: string | null;
+}
+
+export interface ProfileUpdate {
+  display_name?: string;
+  email?: string;
+  phone?: string;
+  avatar?: File;
+  remove_avatar?: boolean;
+}
+
+// Cache-busting URL of a user's avatar, null without one
+export const avatarUrl = (user: { id: string | null; avatar_version?: string | null }) =>
+  user.id && user.avatar_version
+    ? `${API_BASE}/users/${user.id}/avatar?v=${user.avatar_version}`
+    : null;
+
 export interface LoginResponse {
   success: boolean;
   user: {
@@ -89,11 +436,17 @@ export interface CreateUserPayload {
   username: string;
   password: string;
   display_name?: string;
+  expires_at?: string;
 }
 
 export interface UpdateUserPayload {
   display_name?: string;
   is_active?: boolean;
+  // RFC 3339; empty string removes the expiry
+  expires_at?: string;
+  // 0 follows the role's session policy again
+  session_hours?: number;
+  idle_timeout_minutes?: number;
 }
 
 export interface MeResponse {
@@ -129,6 +482,14 @@ export interface PluginStatus {
   socket_path: string | null;
 }
 
+export interface PluginCrashReport {
+  at: string;
+  reason: 'exited' | 'unhealthy' | 'unresponsive';
+  exit_code: number | null;
+  signal: number | null;
+  stderr: string[];
+}
+
 export interface Plugin {
   id: string;
   name: string;
@@ -138,9 +499,64 @@ export interface Plugin {
   route: string | null;
   enabled: boolean;
   running: boolean;
-  health: 'healthy' | 'unhealthy' | 'disabled';
+  health: 'healthy' | 'unhealthy' | 'disabled' | 'conflict';
   pid: number | null;
   socket_path: string | null;
+  limits: PluginLimits;
+  limit_enforcement: 'cgroup' | 'rlimit' | 'wasm' | 'none';
+  usage: PluginResourceUsage | null;
+  limit_exceeded: 'memory' | 'cpu' | null;
+  uptime_secs: number | null;
+  restart_count: number;
+  last_crash_at: string | null;
+  last_crash: PluginCrashReport | null;
+  dev: boolean;
+  runtime: 'native' | 'wasm';
+  capabilities: string[];
+  granted_capabilities: string[];
+  jobs: PluginJob[];
+  route_conflict: string | null;
+  dependencies: string[];
+  dependency_problems: string[];
+  heartbeat: boolean;
+  circuit_open: boolean;
+  instance_of: string | null;
+  request_timeout_secs: number;
+  protocol_version: number;
+  pending_migration: string | null;
+  kv_quota: PluginKvQuota;
+  kv_usage: PluginKvUsage | null;
+}
+
+export interface PluginJob {
+  id: string;
+  schedule: string;
+  next_run: string | null;
+}
+
+export interface PluginLimits {
+  memory_mb?: number;
+  cpu_percent?: number;
+}
+
+export interface PluginResourceUsage {
+  memory_bytes: number;
+  cpu_percent: number;
+}
+
+export interface PluginAccess {
+  users: string[];
+  roles: ('admin' | 'client')[];
+}
+
+export interface PluginKvQuota {
+  max_bytes: number | null;
+  max_keys: number | null;
+}
+
+export interface PluginKvUsage {
+  keys: number;
+  bytes: number;
 }
 
 export interface PluginLogEntry {
@@ -268,6 +684,29 @@ export const api = {
     if (!res.ok) throw new Error('Failed to delete user');
   },
 
+  // Rows of a CSV (with a header line) or objects; each needs a password or invite
+  importUsers: async (rows: string | UserImportRow[]): Promise<UserImportResult> => {
+    const res =
+      typeof rows === 'string'
+        ? await request('/users/import', {
+            method: 'POST',
+            headers: { 'Content-Type': 'text/csv' },
+            body: rows,
+          })
+        : await jsonRequest('/users/import', 'POST', rows);
+    return handleAuthResponse(res, '/users/import');
+  },
+
+  exportUsersCsv: async (): Promise<string> => {
+    const res = await request('/users/export?format=csv');
+    if (res.status === 401) {
+      onAuthError?.();
+      throw new Error('Session expired');
+    }
+    if (!res.ok) throw new Error('Failed to export users');
+    return res.text();
+  },
+
   resetPassword: async (id: string, password: string): Promise<void> => {
     const res = await jsonRequest(`/users/${id}/password`, 'PUT', { password });
     if (res.status === 401) {
@@ -295,6 +734,37 @@ export const api = {
     }
   },
 
+  getProfile: async (): Promise<Profile> => {
+    const res = await request('/me/profile');
+    return handleAuthResponse(res, '/me/profile');
+  },
+
+  // Fields left out are kept; empty strings clear them
+  updateProfile: async (update: ProfileUpdate): Promise<Profile> => {
+    const form = new FormData();
+    for (const key of ['display_name', 'email', 'phone'] as const) {
+      if (update[key] !== undefined) form.append(key, update[key]);
+    }
+    if (update.avatar) form.append('avatar', update.avatar);
+    if (update.remove_avatar) form.append('remove_avatar', 'true');
+    const res = await request('/me/profile', { method: 'PUT', body: form });
+    return handleAuthResponse(res, '/me/profile');
+  },
+
+  // Per-user settings such as theme, dashboard layout, default page and locale
+  getPreferences: async (): Promise<Record<string, unknown>> => {
+    const res = await request('/me/preferences');
+    return handleAuthResponse(res, '/me/preferences');
+  },
+
+  // Merges into the stored preferences; null removes a key
+  updatePreferences: async (
+    preferences: Record<string, unknown>,
+  ): Promise<Record<string, unknown>> => {
+    const res = await jsonRequest('/me/preferences', 'PUT', preferences);
+    return handleAuthResponse(res, '/me/preferences');
+  },
+
   health: async (): Promise<{ status: string }> => {
     const res = await fetch(`${API_BASE}/health`); // Health can be public/no-auth-cookies if needed, but safe to include
     return handleResponse(res, '/health');
@@ -305,6 +775,92 @@ export const api = {
     return handleAuthResponse(res, '/resources');
   },
 
+  listProcesses: async (
+    params: { sort?: ProcessSort; order?: 'asc' | 'desc'; limit?: number; offset?: number } = {}
+  ): Promise<ProcessPage> => {
+    const query = new URLSearchParams(
+      Object.entries(params)
+        .filter(([, value]) => value !== undefined)
+        .map(([key, value]) => [key, String(value)])
+    );
+    const res = await request(`/system/processes?${query}`);
+    return handleAuthResponse(res, '/system/processes');
+  },
+
+  killProcess: async (pid: number, signal = 'TERM'): Promise<void> => {
+    const res = await jsonRequest(`/system/processes/${pid}/kill`, 'POST', { signal });
+    if (res.status === 401) {
+      onAuthError?.();
+      throw new Error('Session expired');
+    }
+    if (!res.ok) {
+      const data = await res.json().catch(() => ({}));
+      throw new Error(data.error || 'Failed to signal process');
+    }
+  },
+
+  getUpdates: async (): Promise<UpdateCheck | null> => {
+    const res = await request('/system/updates');
+    return handleAuthResponse(res, '/system/updates');
+  },
+
+  checkUpdates: async (): Promise<UpdateCheck> => {
+    const res = await request('/system/updates/check', { method: 'POST' });
+    return handleAuthResponse(res, '/system/updates/check');
+  },
+
+  getPower: async (): Promise<PowerStatus> => {
+    const res = await request('/system/power');
+    return handleAuthResponse(res, '/system/power');
+  },
+
+  listPorts: async (): Promise<ListeningSocket[]> => {
+    const res = await request('/system/ports');
+    return handleAuthResponse(res, '/system/ports');
+  },
+
+  listHostSessions: async (): Promise<HostSession[]> => {
+    const res = await request('/system/sessions');
+    return handleAuthResponse(res, '/system/sessions');
+  },
+
+  getSystemLogs: async (params: SystemLogQuery = {}): Promise<SystemLogPage> => {
+    const query = new URLSearchParams(
+      Object.entries(params)
+        .filter(([, value]) => value !== undefined && value !== '')
+        .map(([key, value]) => [key, String(value)])
+    );
+    const res = await request(`/system/logs?${query}`);
+    return handleAuthResponse(res, '/system/logs');
+  },
+
+  listContainers: async (all = false): Promise<ContainerInfo[]> => {
+    const res = await request(`/docker/containers?all=${all}`);
+    return handleAuthResponse(res, '/docker/containers');
+  },
+
+  getContainerStats: async (id: string): Promise<ContainerStats> => {
+    const res = await request(`/docker/containers/${id}/stats`);
+    return handleAuthResponse(res, `/docker/containers/${id}/stats`);
+  },
+
+  getContainerLogs: async (id: string, tail = 100): Promise<{ lines: string[] }> => {
+    const res = await request(`/docker/containers/${id}/logs?tail=${tail}`);
+    return handleAuthResponse(res, `/docker/containers/${id}/logs`);
+  },
+
+  controlContainer: async (id: string, action: 'start' | 'stop' | 'restart'): Promise<void> => {
+    const res = await request(`/docker/containers/${id}/${action}`, { method: 'POST' });
+    if (res.status === 401) {
+      onAuthError?.();
+      throw new Error('Session expired');
+    }
+    if (!res.ok) {
+      const data = await res.json().catch(() => ({}));
+      throw new Error(data.error || `Failed to ${action} container`);
+    }
+  },
+
   listScripts: async (): Promise<string[]> => {
     const res = await request('/scripts');
     return handleAuthResponse(res, '/scripts');
@@ -325,6 +881,13 @@ export const api = {
     return handleAuthResponse(res, '/history');
   },
 
+  searchHistory: async (q: string, limit?: number): Promise<TaskSearchHit[]> => {
+    const params = new URLSearchParams({ q });
+    if (limit) params.set('limit', String(limit));
+    const res = await request(`/search?${params}`);
+    return handleAuthResponse(res, '/search');
+  },
+
   getQuickActions: async (): Promise<QuickAction[]> => {
     const res = await request('/quick-actions');
     return handleAuthResponse(res, '/quick-actions');
@@ -335,8 +898,41 @@ export const api = {
     return handleAuthResponse(res, '/quick-actions');
   },
 
-  runQuickAction: async (id: string): Promise<{ task_id: string }> => {
-    const res = await request(`/quick-actions/${id}/execute`, { method: 'POST' });
+  // Only the given fields change; an empty icon removes it
+  updateQuickAction: async (id: string, changes: Partial<Omit<QuickAction, 'id'>>): Promise<QuickAction> => {
+    const res = await jsonRequest(`/quick-actions/${id}`, 'PUT', changes);
+    return handleAuthResponse(res, `/quick-actions/${id}`);
+  },
+
+  // New order for drag and drop; actions left out go last
+  reorderQuickActions: async (ids: string[]): Promise<QuickAction[]> => {
+    const res = await jsonRequest('/quick-actions/order', 'PUT', { ids });
+    return handleAuthResponse(res, '/quick-actions/order');
+  },
+
+  exportQuickActions: async (ids: string[] = []): Promise<ActionPack> => {
+    const endpoint = ids.length
+      ? `/quick-actions/export?ids=${encodeURIComponent(ids.join(','))}`
+      : '/quick-actions/export';
+    const res = await request(endpoint);
+    return handleAuthResponse(res, endpoint);
+  },
+
+  importQuickActions: async (
+    pack: ActionPack,
+    onConflict: 'skip' | 'replace' | 'rename' = 'skip',
+  ): Promise<PackImportSummary> => {
+    const endpoint = `/quick-actions/import?on_conflict=${onConflict}`;
+    const res = await jsonRequest(endpoint, 'POST', pack);
+    return handleAuthResponse(res, endpoint);
+  },
+
+  runQuickAction: async (
+    id: string,
+    params: Record<string, string | boolean> = {},
+    confirm?: string,
+  ): Promise<{ task_id: string }> => {
+    const res = await jsonRequest(`/quick-actions/${id}/execute`, 'POST', { params, confirm });
     return handleAuthResponse(res, `/quick-actions/${id}/execute`);
   },
 
@@ -366,14 +962,135 @@ export const api = {
     await handleAuthResponse(res, `/plugins/${id}/disable`);
   },
 
-  getPluginLogs: async (id: string, options?: { page?: number; page_size?: number; level?: string }): Promise<PluginLogsResponse> => {
+  setPluginCapabilities: async (id: string, capabilities: string[]): Promise<void> => {
+    const res = await jsonRequest(`/plugins/${id}/capabilities`, 'PUT', { capabilities });
+    await handleAuthResponse(res, `/plugins/${id}/capabilities`);
+  },
+
+  createPluginInstance: async (id: string, name: string, settings: Record<string, string> = {}): Promise<Plugin> => {
+    const res = await jsonRequest(`/plugins/${id}/instances`, 'POST', { name, settings });
+    return handleAuthResponse(res, `/plugins/${id}/instances`);
+  },
+
+  removePluginInstance: async (id: string, name: string): Promise<void> => {
+    const res = await request(`/plugins/${id}/instances/${name}`, { method: 'DELETE' });
+    await handleAuthResponse(res, `/plugins/${id}/instances/${name}`);
+  },
+
+  getPluginAccess: async (id: string): Promise<PluginAccess> => {
+    const res = await request(`/plugins/${id}/access`);
+    return handleAuthResponse(res, `/plugins/${id}/access`);
+  },
+
+  setPluginAccess: async (id: string, access: PluginAccess): Promise<void> => {
+    const res = await jsonRequest(`/plugins/${id}/access`, 'PUT', access);
+    await handleAuthResponse(res, `/plugins/${id}/access`);
+  },
+
+  // from/to are RFC 3339; q matches message or error text, as a regular expression with regex
+  getPluginLogs: async (
+    id: string,
+    options?: {
+      page?: number;
+      page_size?: number;
+      level?: string;
+      from?: string;
+      to?: string;
+      q?: string;
+      regex?: boolean;
+    },
+  ): Promise<PluginLogsResponse> => {
     const params = new URLSearchParams();
     if (options?.page !== undefined) params.set('page', options.page.toString());
     if (options?.page_size !== undefined) params.set('page_size', options.page_size.toString());
     if (options?.level) params.set('level', options.level);
+    if (options?.from) params.set('from', options.from);
+    if (options?.to) params.set('to', options.to);
+    if (options?.q) params.set('q', options.q);
+    if (options?.regex) params.set('regex', 'true');
 
     const url = `/plugins/${id}/logs${params.toString() ? '?' + params.toString() : ''}`;
     const res = await request(url);
     return handleAuthResponse(res, url);
   },
+
+  // Backup endpoints (admin only)
+  createBackup: async (): Promise<Blob> => {
+    const res = await request('/admin/backup', { method: 'POST' });
+    if (res.status === 401) {
+      onAuthError?.();
+      throw new Error('Session expired');
+    }
+    if (!res.ok) {
+      const data = await res.json().catch(() => ({}));
+      throw new Error(data.error || 'Failed to create backup');
+    }
+    return res.blob();
+  },
+
+  restoreBackup: async (archive: Blob): Promise<RestoreSummary> => {
+    const res = await request('/admin/restore', {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/gzip' },
+      body: archive,
+    });
+    return handleAuthResponse(res, '/admin/restore');
+  },
+
+  exportConfig: async (includePasswordHashes = false): Promise<ConfigDocument> => {
+    const endpoint = `/admin/config/export?include_password_hashes=${includePasswordHashes}`;
+    const res = await request(endpoint);
+    return handleAuthResponse(res, endpoint);
+  },
+
+  importConfig: async (document: ConfigDocument): Promise<ImportSummary> => {
+    const res = await jsonRequest('/admin/config/import', 'POST', document);
+    return handleAuthResponse(res, '/admin/config/import');
+  },
+
+  getDbStats: async (): Promise<DbStats> => {
+    const res = await request('/admin/db/stats');
+    return handleAuthResponse(res, '/admin/db/stats');
+  },
+
+  runDbMaintenance: async (): Promise<MaintenanceReport> => {
+    const res = await request('/admin/db/maintenance', { method: 'POST' });
+    return handleAuthResponse(res, '/admin/db/maintenance');
+  },
+
+  getLogging: async (): Promise<LoggingConfig> => {
+    const res = await request('/admin/logging');
+    return handleAuthResponse(res, '/admin/logging');
+  },
+
+  // An empty filter goes back to RUST_LOG; a null plugin level removes it
+  updateLogging: async (update: {
+    filter?: string;
+    plugin_levels?: Record<string, LogLevelName | null>;
+  }): Promise<LoggingConfig> => {
+    const res = await jsonRequest('/admin/logging', 'PUT', update);
+    return handleAuthResponse(res, '/admin/logging');
+  },
+
+  // Newest first; status is a code or a class such as 5xx
+  getAccessLog: async (options?: {
+    limit?: number;
+    method?: string;
+    path?: string;
+    status?: string;
+    user?: string;
+    ip?: string;
+    request_id?: string;
+    from?: string;
+    to?: string;
+  }): Promise<AccessLogEntry[]> => {
+    const params = new URLSearchParams();
+    for (const [key, value] of Object.entries(options ?? {})) {
+      if (value !== undefined && value !== '') params.set(key, value.toString());
+    }
+
+    const url = `/admin/access-log${params.toString() ? '?' + params.toString() : ''}`;
+    const res = await request(url);
+    return handleAuthResponse(res, url);
+  },
 };
@@ -19,11 +19,20 @@ import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/com
 import { Progress } from '@/components/ui/progress';
 import { Alert, AlertDescription, AlertTitle } from '@/components/ui/alert';
 import { Button } from '@/components/ui/button';
+import { Dialog, DialogContent, DialogDescription, DialogFooter, DialogHeader, DialogTitle } from '@/components/ui/dialog';
+import { Input } from '@/components/ui/input';
+import { Label } from '@/components/ui/label';
+import { Switch } from '@/components/ui/switch';
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from '@/components/ui/select';
 
 export function Dashboard() {
   const { stats, loading, error } = useSystemStats(5000);
   const [quickActions, setQuickActions] = useState<QuickAction[]>([]);
   const [loadingActions, setLoadingActions] = useState(true);
+  // Action whose parameters are being entered, with the values so far
+  const [paramAction, setParamAction] = useState<QuickAction | null>(null);
+  const [paramValues, setParamValues] = useState<Record<string, string>>({});
+  const [paramError, setParamError] = useState<string | null>(null);
   const navigate = useNavigate();
 
   useEffect(() => {
@@ -41,9 +50,44 @@ export function Dashboard() {
   }, []);
 
   const handleRunQuickAction = async (action: QuickAction) => {
+    if (action.params?.length) {
+      const defaults: Record<string, string> = {};
+      for (const param of action.params) {
+        defaults[param.name] = param.default ?? (param.type === 'boolean' ? 'false' : '');
+      }
+      setParamValues(defaults);
+      setParamError(null);
+      setParamAction(action);
+      return;
+    }
+    await runAction(action, {});
+  };
+
+  const handleSubmitParams = async () => {
+    if (!paramAction) return;
+    const missing = paramAction.params?.find((p) => p.required && !paramValues[p.name]);
+    if (missing) {
+      setParamError(`${missing.label || missing.name} is required`);
+      return;
+    }
+    const values: Record<string, string> = {};
+    for (const [name, value] of Object.entries(paramValues)) {
+      if (value !== '') values[name] = value;
+    }
+    setParamAction(null);
+    await runAction(paramAction, values);
+  };
+
+  const runAction = async (action: QuickAction, params: Record<string, string>) => {
+    let confirm: string | undefined;
+    if (action.requires_confirmation || action.danger_level === 'high') {
+      const typed = window.prompt(`This action is dangerous. Type "${action.name}" to run it.`);
+      if (typed === null) return;
+      confirm = typed;
+    }
     try {
       setLoadingActions(true);
-      const result = await api.runQuickAction(action.id);
+      const result = await api.runQuickAction(action.id, params, confirm);
       // Navigate to history page with specific task highlighted
       navigate(`/history?highlight_task=${result.task_id}`);
     } catch (err) {
@@ -247,6 +291,17 @@ export function Dashboard() {
                     <div className="text-xs text-muted-foreground truncate max-w-[150px]">
                       {action.script_path}
                     </div>
+                    {action.stats?.last_run_at && (
+                      <div className="text-xs text-muted-foreground">
+                        {action.stats.last_exit_code === null
+                          ? 'Running'
+                          : action.stats.last_exit_code === 0
+                            ? 'Last run OK'
+                            : `Last run failed (${action.stats.last_exit_code})`}
+                        {action.stats.success_rate !== null &&
+                          ` · ${Math.round(action.stats.success_rate * 100)}% of ${action.stats.runs}`}
+                      </div>
+                    )}
                   </div>
                 </Button>
               ))}
@@ -254,6 +309,73 @@ export function Dashboard() {
           )}
         </CardContent>
       </Card>
+
+      {/* Parameters of the action about to run */}
+      <Dialog open={!!paramAction} onOpenChange={(open) => !open && setParamAction(null)}>
+        <DialogContent>
+          <DialogHeader>
+            <DialogTitle>{paramAction?.name}</DialogTitle>
+            <DialogDescription>{paramAction?.script_path}</DialogDescription>
+          </DialogHeader>
+          <div className="space-y-4">
+            {paramAction?.params?.map((param) => {
+              const id = `param-${param.name}`;
+              const label = `${param.label || param.name}${param.required ? ' *' : ''}`;
+              const value = paramValues[param.name] ?? '';
+              const setValue = (v: string) => setParamValues({ ...paramValues, [param.name]: v });
+              if (param.type === 'boolean') {
+                return (
+                  <div key={param.name} className="flex items-center justify-between">
+                    <Label htmlFor={id}>{label}</Label>
+                    <Switch
+                      id={id}
+                      checked={value === 'true'}
+                      onCheckedChange={(checked) => setValue(checked ? 'true' : 'false')}
+                    />
+                  </div>
+                );
+              }
+              return (
+                <div key={param.name} className="space-y-2">
+                  <Label htmlFor={id}>{label}</Label>
+                  {param.type === 'select' ? (
+                    <Select value={value} onValueChange={setValue}>
+                      <SelectTrigger id={id}>
+                        <SelectValue placeholder="Choose..." />
+                      </SelectTrigger>
+                      <SelectContent>
+                        {param.options?.map((option) => (
+                          <SelectItem key={option} value={option}>
+                            {option}
+                          </SelectItem>
+                        ))}
+                      </SelectContent>
+                    </Select>
+                  ) : (
+                    <Input
+                      id={id}
+                      type={param.type === 'secret' ? 'password' : 'text'}
+                      autoComplete="off"
+                      value={value}
+                      onChange={(e) => setValue(e.target.value)}
+                    />
+                  )}
+                </div>
+              );
+            })}
+            {paramError && <p className="text-sm text-destructive">{paramError}</p>}
+          </div>
+          <DialogFooter>
+            <Button variant="outline" onClick={() => setParamAction(null)}>
+              Cancel
+            </Button>
+            <Button onClick={handleSubmitParams}>
+              <Play className="h-4 w-4 mr-2" />
+              Run
+            </Button>
+          </DialogFooter>
+        </DialogContent>
+      </Dialog>
     </div>
   );
 }
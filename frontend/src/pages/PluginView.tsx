@@ -1,6 +1,7 @@
 import { useEffect, useRef, useState } from 'react';
 import { useNavigate, useParams } from 'react-router-dom';
 import { Loader2, AlertCircle } from 'lucide-react';
+import { API_BASE } from '@/lib/api';
 
 interface PluginMountFunction {
   mount: (container: HTMLElement, api: PluginAPI) => void;
@@ -24,13 +25,51 @@ export function PluginView() {
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState<string | null>(null);
   const [pluginMount, setPluginMount] = useState<PluginMountFunction | null>(null);
+  // Token for this plugin's KV namespace, sent with its bundle
+  const kvGrant = useRef<{ pluginId: string; token: Promise<string> } | null>(null);
+
+  const kvToken = (pluginId: string) => {
+    const grant = kvGrant.current;
+    if (grant?.pluginId === pluginId) return grant.token;
+    const token = window
+      .fetch(`${API_BASE}/plugins/${pluginId}/bundle.js`, { method: 'HEAD' })
+      .then((response) => {
+        const token = response.headers.get('X-Plugin-KV-Token');
+        if (!response.ok || !token) throw new Error('KV access denied');
+        return token;
+      });
+    kvGrant.current = { pluginId, token };
+    // Ask again next time if this grant fails
+    token.catch(() => {
+      kvGrant.current = null;
+    });
+    return token;
+  };
+
+  const kvRequest = async (body: Record<string, unknown>) => {
+    if (!pluginId) throw new Error('Plugin ID not provided');
+    const send = async () =>
+      window.fetch(`${API_BASE}/plugins/${pluginId}/kv`, {
+        method: 'POST',
+        headers: {
+          'Content-Type': 'application/json',
+          'X-Plugin-KV-Token': await kvToken(pluginId),
+        },
+        body: JSON.stringify(body),
+      });
+    const response = await send();
+    if (response.status !== 403) return response;
+    // Grants expire; a fresh one is issued while the user may still see the plugin
+    kvGrant.current = null;
+    return send();
+  };
 
   // Plugin API provided to the plugin
   const pluginAPI: PluginAPI = {
     // Wrap fetch to prepend plugin route prefix
     fetch: (path: string, options?: RequestInit) => {
       // Get plugin metadata to find its route
-      const routePrefix = `/api/plugins/route/${pluginId}`;
+      const routePrefix = `${API_BASE}/plugins/route/${pluginId}`;
       const fullPath = path.startsWith('/') ? `${routePrefix}${path}` : `${routePrefix}/${path}`;
       return window.fetch(fullPath, options);
     },
@@ -45,21 +84,13 @@ export function PluginView() {
     },
     kv: {
       get: async (key: string) => {
-        const response = await window.fetch(`/api/plugins/${pluginId}/kv`, {
-          method: 'POST',
-          headers: { 'Content-Type': 'application/json' },
-          body: JSON.stringify({ action: 'get', key }),
-        });
+        const response = await kvRequest({ action: 'get', key });
         if (!response.ok) throw new Error('Failed to get KV');
         const data = await response.json();
         return data.value ?? null;
       },
       set: async (key: string, value: string) => {
-        const response = await window.fetch(`/api/plugins/${pluginId}/kv`, {
-          method: 'POST',
-          headers: { 'Content-Type': 'application/json' },
-          body: JSON.stringify({ action: 'set', key, value }),
-        });
+        const response = await kvRequest({ action: 'set', key, value });
         if (!response.ok) throw new Error('Failed to set KV');
       },
     },
@@ -80,7 +111,7 @@ export function PluginView() {
         setError(null);
 
         // Load the plugin bundle
-        const scriptUrl = `/api/plugins/${pluginId}/bundle.js`;
+        const scriptUrl = `${API_BASE}/plugins/${pluginId}/bundle.js`;
 
         // Create and load the script
         scriptElement = document.createElement('script');
@@ -16,6 +16,7 @@ import {
 } from '@/components/ui/dialog';
 import { Badge } from '@/components/ui/badge';
 import { useAuth } from '@/contexts/AuthContext';
+import { formatBytes, formatUptime } from '@/lib/utils';
 
 export function Plugins() {
   const { isAdmin } = useAuth();
@@ -159,9 +160,13 @@ export function Plugins() {
               <CardContent className="space-y-4">
                 {/* Status */}
                 <div className="flex items-center justify-between">
-                  <Badge variant={getHealthBadgeVariant(plugin.health)}>
-                    {plugin.health}
-                  </Badge>
+                  <div className="flex items-center gap-2">
+                    <Badge variant={getHealthBadgeVariant(plugin.health)}>
+                      {plugin.health}
+                    </Badge>
+                    {plugin.dev && <Badge variant="outline">dev</Badge>}
+                    {plugin.runtime === 'wasm' && <Badge variant="outline">wasm</Badge>}
+                  </div>
                   {plugin.running && (
                     <span className="text-xs text-muted-foreground">
                       PID: {plugin.pid || 'N/A'}
@@ -169,6 +174,51 @@ export function Plugins() {
                   )}
                 </div>
 
+                {/* Resource usage */}
+                {plugin.running && plugin.usage && (
+                  <div className="grid grid-cols-3 gap-2 text-xs">
+                    <div>
+                      <div className="text-muted-foreground">CPU</div>
+                      <div className={plugin.limit_exceeded === 'cpu' ? 'text-destructive' : ''}>
+                        {plugin.usage.cpu_percent.toFixed(1)}%
+                      </div>
+                    </div>
+                    <div>
+                      <div className="text-muted-foreground">Memory</div>
+                      <div className={plugin.limit_exceeded === 'memory' ? 'text-destructive' : ''}>
+                        {formatBytes(plugin.usage.memory_bytes)}
+                      </div>
+                    </div>
+                    <div>
+                      <div className="text-muted-foreground">Uptime</div>
+                      <div>{plugin.uptime_secs != null ? formatUptime(plugin.uptime_secs) : 'N/A'}</div>
+                    </div>
+                  </div>
+                )}
+                {(plugin.restart_count > 0 || plugin.last_crash_at) && (
+                  <div className="text-xs text-muted-foreground">
+                    Restarts: {plugin.restart_count}
+                    {plugin.last_crash_at && (
+                      <> · Last crash: {new Date(plugin.last_crash_at).toLocaleString()}</>
+                    )}
+                    {plugin.last_crash && (
+                      <>
+                        {' '}({plugin.last_crash.reason}
+                        {plugin.last_crash.exit_code != null && `, exit code ${plugin.last_crash.exit_code}`}
+                        {plugin.last_crash.signal != null && `, signal ${plugin.last_crash.signal}`})
+                      </>
+                    )}
+                  </div>
+                )}
+                {plugin.last_crash && plugin.last_crash.stderr.length > 0 && (
+                  <details className="text-xs">
+                    <summary className="cursor-pointer text-muted-foreground">Last stderr output</summary>
+                    <pre className="mt-1 max-h-40 overflow-auto rounded bg-muted p-2 font-mono whitespace-pre-wrap">
+                      {plugin.last_crash.stderr.join('\n')}
+                    </pre>
+                  </details>
+                )}
+
                 {/* Toggle */}
                 <div className="flex items-center justify-between">
                   <span className="text-sm text-muted-foreground">
@@ -1,7 +1,7 @@
 import { useEffect, useState, useRef } from 'react';
 import { useSearchParams } from 'react-router-dom';
 import { useWebSocket } from '../hooks/useWebSocket';
-import { api } from '../lib/api';
+import { api, wsUrl } from '../lib/api';
 import { Play, Square, Terminal as TerminalIcon, Loader2, WifiOff } from 'lucide-react';
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card';
 import { Button } from '@/components/ui/button';
@@ -19,8 +19,7 @@ export function Scripts() {
   const [, setError] = useState<Error | null>(null);
   const terminalRef = useRef<HTMLDivElement>(null);
   
-  const wsUrl = `${window.location.protocol === 'https:' ? 'wss:' : 'ws:'}//${window.location.host}/api/ws`;
-  const { connected, messages, send, clearMessages } = useWebSocket(wsUrl);
+  const { connected, messages, send, clearMessages } = useWebSocket(wsUrl());
 
   useEffect(() => {
     const fetchScripts = async () => {
@@ -24,6 +24,7 @@ export function Settings() {
   const [saving, setSaving] = useState(false);
   const [newActionName, setNewActionName] = useState('');
   const [newActionScript, setNewActionScript] = useState('');
+  const [newActionVisibility, setNewActionVisibility] = useState<'everyone' | 'admins'>('everyone');
   const [scripts, setScripts] = useState<string[]>([]);
   const [saveSuccess, setSaveSuccess] = useState(false);
 
@@ -88,10 +89,12 @@ export function Settings() {
         script_path: newActionScript,
         icon: null,
         display_order: quickActions.length,
+        visibility: newActionVisibility,
       });
       setQuickActions([...quickActions, newAction]);
       setNewActionName('');
       setNewActionScript('');
+      setNewActionVisibility('everyone');
     } catch (err) {
       alert('Failed to create quick action');
       console.error(err);
@@ -238,6 +241,22 @@ export function Settings() {
                   </Select>
                 </div>
 
+                <div className="space-y-2">
+                  <Label htmlFor="action-visibility">Visible To</Label>
+                  <Select
+                    value={newActionVisibility}
+                    onValueChange={(v) => setNewActionVisibility(v as 'everyone' | 'admins')}
+                  >
+                    <SelectTrigger id="action-visibility">
+                      <SelectValue />
+                    </SelectTrigger>
+                    <SelectContent>
+                      <SelectItem value="everyone">Everyone</SelectItem>
+                      <SelectItem value="admins">Admins only</SelectItem>
+                    </SelectContent>
+                  </Select>
+                </div>
+
                 <Button
                   onClick={handleAddQuickAction}
                   disabled={saving || !newActionName || !newActionScript}
@@ -281,6 +300,9 @@ export function Settings() {
                         <p className="font-medium truncate">{action.name}</p>
                         <p className="text-sm text-muted-foreground truncate">
                           {action.script_path}
+                          {action.visibility === 'admins' && ' · Admins only'}
+                          {action.visibility === 'users' &&
+                            ` · ${action.allowed_users?.length ?? 0} user(s)`}
                         </p>
                       </div>
                       <Button
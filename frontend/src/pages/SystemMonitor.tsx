@@ -11,6 +11,10 @@ import {
   Layers,
   AlertCircle,
   RefreshCw,
+  Thermometer,
+  Fan,
+  Gauge,
+  BatteryMedium,
 } from 'lucide-react';
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card';
 import { Badge } from '@/components/ui/badge';
@@ -394,6 +398,125 @@ export function SystemMonitor() {
         </Card>
       )}
 
+      {/* GPUs */}
+      {stats.gpus.length > 0 && (
+        <Card>
+          <CardHeader>
+            <CardTitle className="flex items-center gap-2">
+              <Gauge className="h-5 w-5" />
+              GPUs
+            </CardTitle>
+            <CardDescription>Utilization and video memory</CardDescription>
+          </CardHeader>
+          <CardContent className="space-y-4">
+            {stats.gpus.map((gpu, idx) => (
+              <div key={idx} className="space-y-2">
+                <div className="flex items-center justify-between">
+                  <div className="flex-1 min-w-0">
+                    <p className="text-sm font-medium truncate">{gpu.name}</p>
+                    <p className="text-xs text-muted-foreground">
+                      {gpu.vendor.toUpperCase()}
+                      {gpu.temperature_celsius !== null && ` • ${gpu.temperature_celsius.toFixed(0)}°C`}
+                    </p>
+                  </div>
+                  {gpu.utilization_percent !== null && (
+                    <Badge variant={getUsageVariant(gpu.utilization_percent)} className="ml-2">
+                      {gpu.utilization_percent.toFixed(0)}%
+                    </Badge>
+                  )}
+                </div>
+                {gpu.utilization_percent !== null && (
+                  <Progress value={gpu.utilization_percent} className="h-2" />
+                )}
+                {gpu.memory_used !== null && gpu.memory_total !== null && (
+                  <div className="flex justify-between text-xs text-muted-foreground">
+                    <span>{formatBytes(gpu.memory_used)} VRAM used</span>
+                    <span>{formatBytes(gpu.memory_total)} total</span>
+                  </div>
+                )}
+                {idx < stats.gpus.length - 1 && <Separator className="mt-4" />}
+              </div>
+            ))}
+          </CardContent>
+        </Card>
+      )}
+
+      {/* Batteries */}
+      {stats.batteries.length > 0 && (
+        <Card>
+          <CardHeader>
+            <CardTitle className="flex items-center gap-2">
+              <BatteryMedium className="h-5 w-5" />
+              Batteries
+            </CardTitle>
+            <CardDescription>Charge and charging state</CardDescription>
+          </CardHeader>
+          <CardContent className="space-y-4">
+            {stats.batteries.map((battery, idx) => (
+              <div key={idx} className="space-y-2">
+                <div className="flex items-center justify-between">
+                  <div className="flex-1 min-w-0">
+                    <p className="text-sm font-medium truncate">{battery.name}</p>
+                    <p className="text-xs text-muted-foreground">{battery.status}</p>
+                  </div>
+                  {battery.percent !== null && (
+                    <Badge
+                      variant={battery.status === 'Discharging' && battery.percent <= 20 ? 'destructive' : 'secondary'}
+                      className="ml-2"
+                    >
+                      {battery.percent.toFixed(0)}%
+                    </Badge>
+                  )}
+                </div>
+                {battery.percent !== null && <Progress value={battery.percent} className="h-2" />}
+                {idx < stats.batteries.length - 1 && <Separator className="mt-4" />}
+              </div>
+            ))}
+          </CardContent>
+        </Card>
+      )}
+
+      {/* Temperatures and Fans */}
+      {(stats.temperatures.length > 0 || stats.fans.length > 0) && (
+        <Card>
+          <CardHeader>
+            <CardTitle className="flex items-center gap-2">
+              <Thermometer className="h-5 w-5" />
+              Sensors
+            </CardTitle>
+            <CardDescription>Temperatures and fan speeds</CardDescription>
+          </CardHeader>
+          <CardContent>
+            <div className="grid gap-3 md:grid-cols-2 lg:grid-cols-3">
+              {stats.temperatures.map((sensor, idx) => (
+                <div key={`temp-${idx}`} className="flex items-center justify-between p-3 rounded-lg border">
+                  <span className="text-sm font-medium truncate">{sensor.label}</span>
+                  <Badge
+                    variant={
+                      sensor.critical_celsius !== null && sensor.temperature_celsius >= sensor.critical_celsius - 10
+                        ? 'destructive'
+                        : 'secondary'
+                    }
+                    className="ml-2"
+                  >
+                    {sensor.temperature_celsius.toFixed(0)}°C
+                  </Badge>
+                </div>
+              ))}
+              {stats.fans.map((fan, idx) => (
+                <div key={`fan-${idx}`} className="flex items-center justify-between p-3 rounded-lg border">
+                  <span className="flex items-center gap-2 text-sm font-medium truncate">
+                    <Fan className="h-4 w-4 text-muted-foreground" />
+                    {fan.label}
+                  </span>
+                  <Badge variant="secondary" className="ml-2">{fan.rpm} RPM</Badge>
+                </div>
+              ))}
+            </div>
+          </CardContent>
+        </Card>
+      )}
+
       {/* Network Interfaces */}
       {stats.network.length > 0 && (
         <Card>
@@ -5,6 +5,9 @@ import path from 'path'
 // https://vite.dev/config/
 export default defineConfig({
   plugins: [react()],
+  // Relative asset URLs, resolved against the <base> the server injects, so the
+  // app works under any base path
+  base: './',
   build: {
     outDir: 'dist',
     emptyOutDir: true,
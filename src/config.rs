@@ -0,0 +1,325 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+/// Environment variable naming the config file when `--config` isn't given
+pub const CONFIG_ENV: &str = "STEERING_CONFIG";
+
+static CONFIG: OnceLock<Arc<Config>> = OnceLock::new();
+
+/// Server configuration, layered: defaults < config file < environment < CLI
+///
+/// Every section and key of the TOML file is optional; unknown keys are
+/// refused so typos don't go unnoticed.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub tls: TlsConfig,
+    pub acme: AcmeConfig,
+    pub paths: PathsConfig,
+    pub plugins: PluginsConfig,
+    pub update: UpdateConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Bind to localhost only by default - use Cloudflare Tunnel or reverse proxy for external access
+    pub host: Ipv4Addr,
+    pub port: u16,
+    /// Prefix the app is served under (e.g. `/steering`), empty for the root
+    pub base_path: String,
+    /// Production mode: Secure cookies, no dev plugins
+    pub production: bool,
+    pub secure_cookies: bool,
+    /// Take client IPs from proxy headers (`CF-Connecting-IP`, `X-Forwarded-For`)
+    pub trust_proxy: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: Ipv4Addr::LOCALHOST,
+            port: 3000,
+            base_path: String::new(),
+            production: false,
+            secure_cookies: false,
+            trust_proxy: false,
+        }
+    }
+}
+
+/// Certificate and key to serve HTTPS with; the settings are used when unset
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: Option<String>,
+    pub key: Option<String>,
+    pub redirect_port: Option<u16>,
+}
+
+/// Domain to get a certificate for from Let's Encrypt; the settings are used when unset
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AcmeConfig {
+    pub domain: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// ACME account and certificates, user avatars
+    pub data_dir: PathBuf,
+    /// Server, access, plugin and task logs
+    pub log_dir: PathBuf,
+    pub plugins_dir: PathBuf,
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("./data"),
+            log_dir: PathBuf::from("./logs"),
+            plugins_dir: PathBuf::from("./plugins"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginsConfig {
+    /// Restart plugins when their `.binary` or `.wasm` is replaced
+    pub hot_reload: bool,
+}
+
+/// Where `self-update` looks for releases
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateConfig {
+    /// GitHub API URL of the latest release (or a server answering the same way)
+    pub releases_url: String,
+    /// Minisign key releases must be signed with; without one only checksums are verified
+    pub public_key: Option<String>,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            releases_url:
+                "https://api.github.com/repos/ToruAI/toru-steering-center/releases/latest"
+                    .to_string(),
+            public_key: None,
+        }
+    }
+}
+
+impl Config {
+    /// Defaults overridden by the config file at `path`, then by the environment
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let mut config: Self = toml::from_str(&content)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.server.base_path = normalize_base_path(&config.server.base_path)?;
+        Ok(config)
+    }
+
+    /// Override values with the environment variables that are set
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(host) = var("STEERING_HOST") {
+            self.server.host = parse_env("STEERING_HOST", &host)?;
+        }
+        if let Some(port) = var("STEERING_PORT") {
+            self.server.port = parse_env("STEERING_PORT", &port)?;
+        }
+        if let Some(base_path) = var("STEERING_BASE_PATH") {
+            self.server.base_path = normalize_base_path(&base_path)?;
+        }
+        if let Some(production) = var("PRODUCTION") {
+            self.server.production = is_true(&production);
+        }
+        if let Some(secure) = var("SECURE_COOKIES") {
+            self.server.secure_cookies = is_true(&secure);
+        }
+        if let Some(trust_proxy) = var("TRUST_PROXY") {
+            self.server.trust_proxy = is_true(&trust_proxy);
+        }
+        if let Some(cert) = var("TORU_TLS_CERT") {
+            self.tls.cert = Some(cert);
+        }
+        if let Some(key) = var("TORU_TLS_KEY") {
+            self.tls.key = Some(key);
+        }
+        if let Some(port) = var("TORU_TLS_REDIRECT_PORT") {
+            self.tls.redirect_port = Some(parse_env("TORU_TLS_REDIRECT_PORT", &port)?);
+        }
+        if let Some(domain) = var("TORU_ACME_DOMAIN") {
+            self.acme.domain = Some(domain);
+        }
+        if let Some(email) = var("TORU_ACME_EMAIL") {
+            self.acme.email = Some(email);
+        }
+        if let Some(dir) = var("TORU_DATA_DIR") {
+            self.paths.data_dir = PathBuf::from(dir);
+        }
+        if let Some(dir) = var("TORU_LOG_DIR") {
+            self.paths.log_dir = PathBuf::from(dir);
+        }
+        if let Some(hot_reload) = var("TORU_PLUGIN_HOT_RELOAD") {
+            self.plugins.hot_reload = is_true(&hot_reload);
+        }
+        Ok(())
+    }
+
+    /// Path cookies are scoped to, so apps sharing the domain don't see them
+    pub fn cookie_path(&self) -> &str {
+        if self.server.base_path.is_empty() {
+            "/"
+        } else {
+            &self.server.base_path
+        }
+    }
+
+    /// Whether cookies are marked Secure (serving HTTPS itself also does)
+    pub fn secure_cookies(&self) -> bool {
+        self.server.production || self.server.secure_cookies
+    }
+}
+
+/// Make `config` the configuration of this process; returns the shared instance
+pub fn init(config: Config) -> Arc<Config> {
+    let config = Arc::new(config);
+    // Only the first call counts; later ones (tests) keep the existing configuration
+    let _ = CONFIG.set(Arc::clone(&config));
+    CONFIG.get().cloned().unwrap_or(config)
+}
+
+/// Configuration for code without access to `AppState` (defaults and environment until `init`)
+pub fn get() -> Arc<Config> {
+    CONFIG
+        .get_or_init(|| {
+            let mut config = Config::default();
+            if let Err(e) = config.apply_env(|name| std::env::var(name).ok()) {
+                tracing::warn!("Ignoring invalid environment configuration: {:#}", e);
+            }
+            Arc::new(config)
+        })
+        .clone()
+}
+
+/// `/steering/` or `steering` as `/steering`; `/` and empty as no prefix
+pub fn normalize_base_path(value: &str) -> Result<String> {
+    let trimmed = value.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    let valid = trimmed.split('/').all(|segment| {
+        !segment.is_empty()
+            && segment != "."
+            && segment != ".."
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~'))
+    });
+    if !valid {
+        bail!("Invalid base path: {}", value);
+    }
+    Ok(format!("/{}", trimmed))
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+    match value.trim().parse() {
+        Ok(v) => Ok(v),
+        Err(_) => bail!("Invalid {}: {}", name, value),
+    }
+}
+
+fn is_true(value: &str) -> bool {
+    value.eq_ignore_ascii_case("true") || value == "1"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_file_layer() {
+        let config: Config = toml::from_str(
+            r#"
+            [server]
+            host = "0.0.0.0"
+            production = true
+
+            [tls]
+            cert = "/etc/steering/cert.pem"
+            key = "/etc/steering/key.pem"
+
+            [paths]
+            log_dir = "/var/log/steering"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.server.host, Ipv4Addr::UNSPECIFIED);
+        assert_eq!(config.server.port, 3000);
+        assert!(config.secure_cookies());
+        assert_eq!(config.tls.cert.as_deref(), Some("/etc/steering/cert.pem"));
+        assert_eq!(config.paths.log_dir, PathBuf::from("/var/log/steering"));
+        assert_eq!(config.paths.data_dir, PathBuf::from("./data"));
+
+        assert!(toml::from_str::<Config>("[server]\nprot = 80").is_err());
+        assert!(toml::from_str::<Config>("[server]\nhost = \"localhost\"").is_err());
+    }
+
+    #[test]
+    fn test_env_layer() {
+        let mut config: Config =
+            toml::from_str("[server]\nport = 8080\ntrust_proxy = true").unwrap();
+        let env: HashMap<&str, &str> = [
+            ("STEERING_PORT", "9000"),
+            ("TRUST_PROXY", "false"),
+            ("TORU_ACME_DOMAIN", "example.com"),
+        ]
+        .into();
+        config
+            .apply_env(|name| env.get(name).map(|v| v.to_string()))
+            .unwrap();
+        assert_eq!(config.server.port, 9000);
+        assert!(!config.server.trust_proxy);
+        assert_eq!(config.acme.domain.as_deref(), Some("example.com"));
+        assert_eq!(config.server.host, Ipv4Addr::LOCALHOST);
+
+        let err = config
+            .apply_env(|name| (name == "STEERING_PORT").then(|| "http".to_string()))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid STEERING_PORT: http");
+    }
+
+    #[test]
+    fn test_normalize_base_path() {
+        assert_eq!(normalize_base_path("").unwrap(), "");
+        assert_eq!(normalize_base_path("/").unwrap(), "");
+        assert_eq!(normalize_base_path("steering").unwrap(), "/steering");
+        assert_eq!(
+            normalize_base_path("/tools/steering/").unwrap(),
+            "/tools/steering"
+        );
+        assert!(normalize_base_path("/a/../b").is_err());
+        assert!(normalize_base_path("/a//b").is_err());
+        assert!(normalize_base_path("/a b").is_err());
+        assert!(normalize_base_path("/a\"x").is_err());
+    }
+}
@@ -1,10 +1,129 @@
 use anyhow::Result;
-use rusqlite::{params, Connection};
+use deadpool::managed::{self, Metrics, RecycleResult};
+use rusqlite::{params, Connection, TransactionBehavior};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-pub type DbPool = Arc<Mutex<Connection>>;
+/// Pool of SQLite connections; each query checks one out with `pool.get().await?`
+pub type DbPool = managed::Pool<ConnectionManager>;
+
+/// Path of the database, relative to the working directory
+const DB_PATH: &str = "steering.db";
+
+/// Most connections open at once
+const POOL_SIZE: usize = 8;
+
+/// Setting with the journal mode, e.g. `WAL` (default) or `DELETE`
+pub const JOURNAL_MODE_SETTING: &str = "db_journal_mode";
+
+/// Setting with the `synchronous` level: `OFF`, `NORMAL` (default), `FULL` or `EXTRA`
+pub const SYNCHRONOUS_SETTING: &str = "db_synchronous";
+
+/// Setting with the milliseconds a connection waits for another one's write lock
+pub const BUSY_TIMEOUT_SETTING: &str = "db_busy_timeout_ms";
+
+/// Setting turning foreign key enforcement off when `false`
+pub const FOREIGN_KEYS_SETTING: &str = "db_foreign_keys";
+
+const JOURNAL_MODES: [&str; 6] = ["WAL", "DELETE", "TRUNCATE", "PERSIST", "MEMORY", "OFF"];
+const SYNCHRONOUS_LEVELS: [&str; 4] = ["OFF", "NORMAL", "FULL", "EXTRA"];
+
+/// Pragmas of the database, read from the `db_*` settings when it is opened
+///
+/// The journal mode is stored in the database file; the others are applied to
+/// every connection of the pool.
+#[derive(Debug, Clone)]
+pub struct Pragmas {
+    pub journal_mode: String,
+    pub synchronous: String,
+    pub busy_timeout: Duration,
+    pub foreign_keys: bool,
+}
+
+impl Default for Pragmas {
+    fn default() -> Self {
+        Self {
+            journal_mode: "WAL".to_string(),
+            synchronous: "NORMAL".to_string(),
+            busy_timeout: Duration::from_secs(5),
+            foreign_keys: true,
+        }
+    }
+}
+
+impl Pragmas {
+    /// Pragmas from the settings table, keeping the default for missing or unknown values
+    fn from_settings(conn: &Connection) -> Self {
+        let setting = |key: &str| {
+            conn.query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                params![key],
+                |row| row.get::<_, String>(0),
+            )
+            .ok()
+            .map(|value| value.trim().to_uppercase())
+        };
+        let defaults = Self::default();
+        Self {
+            journal_mode: setting(JOURNAL_MODE_SETTING)
+                .filter(|mode| JOURNAL_MODES.contains(&mode.as_str()))
+                .unwrap_or(defaults.journal_mode),
+            synchronous: setting(SYNCHRONOUS_SETTING)
+                .filter(|level| SYNCHRONOUS_LEVELS.contains(&level.as_str()))
+                .unwrap_or(defaults.synchronous),
+            busy_timeout: setting(BUSY_TIMEOUT_SETTING)
+                .and_then(|millis| millis.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.busy_timeout),
+            foreign_keys: setting(FOREIGN_KEYS_SETTING)
+                .map(|enabled| enabled != "FALSE")
+                .unwrap_or(defaults.foreign_keys),
+        }
+    }
+
+    /// Apply the per-connection pragmas
+    fn apply(&self, conn: &Connection) -> rusqlite::Result<()> {
+        conn.busy_timeout(self.busy_timeout)?;
+        conn.pragma_update(None, "synchronous", &self.synchronous)?;
+        conn.pragma_update(None, "foreign_keys", self.foreign_keys)?;
+        Ok(())
+    }
+}
+
+/// Opens the connections of a `DbPool`
+#[derive(Debug)]
+pub struct ConnectionManager {
+    path: PathBuf,
+    pragmas: Pragmas,
+}
+
+impl managed::Manager for ConnectionManager {
+    type Type = Connection;
+    type Error = rusqlite::Error;
+
+    async fn create(&self) -> Result<Connection, rusqlite::Error> {
+        let conn = Connection::open(&self.path)?;
+        self.pragmas.apply(&conn)?;
+        Ok(conn)
+    }
+
+    async fn recycle(&self, _conn: &mut Connection, _: &Metrics) -> RecycleResult<rusqlite::Error> {
+        Ok(())
+    }
+}
+
+/// A pool over the database at `path`, which must already have its tables
+pub fn create_pool(path: impl AsRef<Path>, pragmas: Pragmas) -> Result<DbPool> {
+    let manager = ConnectionManager {
+        path: path.as_ref().to_path_buf(),
+        pragmas,
+    };
+    Ok(managed::Pool::builder(manager)
+        .max_size(POOL_SIZE)
+        .build()?)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Setting {
@@ -29,6 +148,254 @@ pub struct QuickAction {
     pub script_path: String,
     pub icon: Option<String>,
     pub display_order: i32,
+    /// CPU time limit in seconds (RLIMIT_CPU), overrides the global setting
+    #[serde(default)]
+    pub cpu_limit_secs: Option<i64>,
+    /// Address space limit in megabytes (RLIMIT_AS), overrides the global setting
+    #[serde(default)]
+    pub memory_limit_mb: Option<i64>,
+    /// Maximum open file descriptors (RLIMIT_NOFILE), overrides the global setting
+    #[serde(default)]
+    pub nofile_limit: Option<i64>,
+    /// What to do when the script is already running
+    #[serde(default)]
+    pub concurrency: ConcurrencyMode,
+    /// Environment variables the script runs with
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Inputs asked for on each run, passed to the script as `PARAM_<NAME>`
+    #[serde(default)]
+    pub params: Vec<QuickActionParam>,
+    /// Who besides admins may see and run the action
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// IDs of the users who may run it when `visibility` is `users`
+    #[serde(default)]
+    pub allowed_users: Vec<String>,
+    /// How much harm a mistaken run can do
+    #[serde(default)]
+    pub danger_level: DangerLevel,
+    /// Ask for the action's name to be typed before every run
+    #[serde(default)]
+    pub requires_confirmation: bool,
+}
+
+impl QuickAction {
+    /// Whether a user may see and run this action; admins may run every action
+    pub fn visible_to(&self, role: UserRole, user_id: Option<&str>) -> bool {
+        match (role, self.visibility) {
+            (UserRole::Admin, _) | (_, Visibility::Everyone) => true,
+            (_, Visibility::Admins) => false,
+            (_, Visibility::Users) => {
+                user_id.is_some_and(|id| self.allowed_users.iter().any(|u| u == id))
+            }
+        }
+    }
+
+    /// Whether a run must be confirmed by typing the action's name; such runs
+    /// are recorded in the action audit log
+    pub fn needs_confirmation(&self) -> bool {
+        self.requires_confirmation || self.danger_level == DangerLevel::High
+    }
+
+    /// Whether `confirm` is the confirmation a run of this action needs
+    pub fn is_confirmed_by(&self, confirm: Option<&str>) -> bool {
+        !self.needs_confirmation() || confirm.is_some_and(|c| c.trim() == self.name)
+    }
+}
+
+/// Recent runs of a quick action's script, from task history
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QuickActionStats {
+    /// Runs counted, at most the window asked for
+    pub runs: u32,
+    pub last_run_at: Option<String>,
+    /// `None` while the last run is still going
+    pub last_exit_code: Option<i32>,
+    /// Mean duration of the finished runs
+    pub avg_duration_secs: Option<f64>,
+    /// Share of the finished runs that exited with 0, from 0 to 1
+    pub success_rate: Option<f64>,
+}
+
+/// How much harm a mistaken run of a quick action can do; `high` always
+/// requires confirmation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DangerLevel {
+    #[default]
+    Low,
+    Medium,
+    High,
+}
+
+impl std::fmt::Display for DangerLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DangerLevel::Low => write!(f, "low"),
+            DangerLevel::Medium => write!(f, "medium"),
+            DangerLevel::High => write!(f, "high"),
+        }
+    }
+}
+
+impl std::str::FromStr for DangerLevel {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "low" => Ok(DangerLevel::Low),
+            "medium" => Ok(DangerLevel::Medium),
+            "high" => Ok(DangerLevel::High),
+            _ => Err(anyhow::anyhow!("Invalid danger level: {}", s)),
+        }
+    }
+}
+
+/// Who may see and run a quick action
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Everyone,
+    Admins,
+    /// Admins and the users listed in `allowed_users`
+    Users,
+}
+
+impl std::fmt::Display for Visibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Visibility::Everyone => write!(f, "everyone"),
+            Visibility::Admins => write!(f, "admins"),
+            Visibility::Users => write!(f, "users"),
+        }
+    }
+}
+
+impl std::str::FromStr for Visibility {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "everyone" => Ok(Visibility::Everyone),
+            "admins" => Ok(Visibility::Admins),
+            "users" => Ok(Visibility::Users),
+            _ => Err(anyhow::anyhow!("Invalid visibility: {}", s)),
+        }
+    }
+}
+
+/// An input of a quick action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuickActionParam {
+    pub name: String,
+    /// Shown in the form instead of the name
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: ParamKind,
+    #[serde(default)]
+    pub required: bool,
+    /// Value used when none is submitted (`true`/`false` for booleans)
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Values a `select` may take
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamKind {
+    Text,
+    Select,
+    Boolean,
+    /// Text entered masked; it has no default
+    Secret,
+}
+
+/// Behaviour when a script is started while a previous run is still in progress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConcurrencyMode {
+    /// Refuse the new run (409 Conflict)
+    #[default]
+    Reject,
+    /// Wait for the running instance to finish, then start
+    Queue,
+}
+
+impl std::fmt::Display for ConcurrencyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConcurrencyMode::Reject => write!(f, "reject"),
+            ConcurrencyMode::Queue => write!(f, "queue"),
+        }
+    }
+}
+
+impl std::str::FromStr for ConcurrencyMode {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reject" => Ok(ConcurrencyMode::Reject),
+            "queue" => Ok(ConcurrencyMode::Queue),
+            _ => Err(anyhow::anyhow!("Invalid concurrency mode: {}", s)),
+        }
+    }
+}
+
+/// A single step of a pipeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStep {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub script_path: String,
+    /// Keep running the following steps even if this one fails
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+impl PipelineStep {
+    /// Name shown in history and events (falls back to the script path)
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.script_path)
+    }
+}
+
+/// An ordered chain of scripts executed as a single task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: Vec<PipelineStep>,
+    /// Environment variables shared by every step
+    pub env: HashMap<String, String>,
+    pub created_at: String,
+}
+
+/// Status of one pipeline step within a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStepRun {
+    pub task_id: String,
+    pub step_index: i32,
+    pub step_name: String,
+    pub status: String, // running, success, failed, skipped, cancelled
+    pub exit_code: Option<i32>,
+    pub updated_at: String,
+}
+
+/// A one-shot task held by the scheduler until its `run_at` time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledTask {
+    pub id: String,
+    pub script_name: String,
+    pub run_at: String,
+    pub status: String, // pending, started, failed, cancelled
+    pub task_id: Option<String>,
+    pub error: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: String,
 }
 
 // Auth types
@@ -69,6 +436,37 @@ pub struct User {
     pub role: UserRole,
     pub is_active: bool,
     pub created_at: String,
+    /// RFC 3339 time after which the account can't sign in
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    /// Hours a session lasts, instead of the policy of the user's role
+    #[serde(default)]
+    pub session_hours: Option<i64>,
+    /// Minutes of inactivity that end a session, instead of the role's policy
+    #[serde(default)]
+    pub idle_timeout_minutes: Option<i64>,
+}
+
+/// Contact details and avatar of a user, kept apart from the account
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserProfile {
+    pub user_id: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    /// Content type of the avatar image, if there is one
+    pub avatar_type: Option<String>,
+    /// Changes whenever the avatar is replaced
+    pub avatar_etag: Option<String>,
+}
+
+impl User {
+    /// Whether the account's expiry date has passed
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .is_some_and(|t| t <= chrono::Utc::now())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -79,6 +477,10 @@ pub struct Session {
     pub username: String,
     pub created_at: String,
     pub expires_at: String,
+    /// Minutes of inactivity after which the session ends, if it can idle out
+    pub idle_timeout_minutes: Option<i64>,
+    /// When the session idles out unless it's used before then
+    pub idle_expires_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,7 +494,11 @@ pub struct LoginAttempt {
 }
 
 pub fn init_db() -> Result<DbPool> {
-    let conn = Connection::open("steering.db")?;
+    let conn = Connection::open(DB_PATH)?;
+
+    // Lets maintenance give unused pages back without rebuilding the file; only
+    // takes effect on a new database (older ones switch on their first maintenance)
+    conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
 
     // Create tables
     conn.execute(
@@ -103,6 +509,40 @@ pub fn init_db() -> Result<DbPool> {
         [],
     )?;
 
+    // WAL lets readers and a writer work at the same time, so pooled connections
+    // don't fail with "database is locked"; it stays set in the file
+    let pragmas = Pragmas::from_settings(&conn);
+    pragmas.apply(&conn)?;
+    let journal_mode: String =
+        conn.pragma_update_and_check(None, "journal_mode", &pragmas.journal_mode, |row| {
+            row.get(0)
+        })?;
+    if !journal_mode.eq_ignore_ascii_case(&pragmas.journal_mode) {
+        tracing::warn!(
+            "SQLite journal mode is {} instead of {}",
+            journal_mode,
+            pragmas.journal_mode
+        );
+    }
+
+    create_schema(&conn)?;
+
+    drop(conn);
+    create_pool(DB_PATH, pragmas)
+}
+
+/// Create missing tables and columns, and the default settings
+///
+/// Also run on a restored backup, which may come from an older version.
+pub(crate) fn create_schema(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT
+        )",
+        [],
+    )?;
+
     conn.execute(
         "CREATE TABLE IF NOT EXISTS task_history (
             id TEXT PRIMARY KEY,
@@ -115,6 +555,41 @@ pub fn init_db() -> Result<DbPool> {
         [],
     )?;
 
+    // Full-text index over task output, kept in step by triggers
+    let search_index_exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'task_history_fts')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS task_history_fts USING fts5(
+            script_name, output,
+            content = 'task_history', content_rowid = 'rowid'
+        );
+        CREATE TRIGGER IF NOT EXISTS task_history_fts_insert AFTER INSERT ON task_history BEGIN
+            INSERT INTO task_history_fts (rowid, script_name, output)
+            VALUES (new.rowid, new.script_name, new.output);
+        END;
+        CREATE TRIGGER IF NOT EXISTS task_history_fts_delete AFTER DELETE ON task_history BEGIN
+            INSERT INTO task_history_fts (task_history_fts, rowid, script_name, output)
+            VALUES ('delete', old.rowid, old.script_name, old.output);
+        END;
+        CREATE TRIGGER IF NOT EXISTS task_history_fts_update AFTER UPDATE ON task_history BEGIN
+            INSERT INTO task_history_fts (task_history_fts, rowid, script_name, output)
+            VALUES ('delete', old.rowid, old.script_name, old.output);
+            INSERT INTO task_history_fts (rowid, script_name, output)
+            VALUES (new.rowid, new.script_name, new.output);
+        END;",
+    )?;
+    if !search_index_exists {
+        rebuild_search_index(conn)?;
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_task_history_script_started
+         ON task_history(script_name, started_at)",
+        [],
+    )?;
+
     conn.execute(
         "CREATE TABLE IF NOT EXISTS quick_actions (
             id TEXT PRIMARY KEY,
@@ -126,6 +601,95 @@ pub fn init_db() -> Result<DbPool> {
         [],
     )?;
 
+    // Per-action resource limits (added after the initial schema)
+    add_column_if_missing(conn, "quick_actions", "cpu_limit_secs", "INTEGER")?;
+    add_column_if_missing(conn, "quick_actions", "memory_limit_mb", "INTEGER")?;
+    add_column_if_missing(conn, "quick_actions", "nofile_limit", "INTEGER")?;
+    add_column_if_missing(
+        conn,
+        "quick_actions",
+        "concurrency",
+        "TEXT NOT NULL DEFAULT 'reject'",
+    )?;
+    add_column_if_missing(conn, "quick_actions", "env", "TEXT NOT NULL DEFAULT '{}'")?;
+    add_column_if_missing(
+        conn,
+        "quick_actions",
+        "params",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )?;
+    add_column_if_missing(
+        conn,
+        "quick_actions",
+        "visibility",
+        "TEXT NOT NULL DEFAULT 'everyone'",
+    )?;
+    add_column_if_missing(
+        conn,
+        "quick_actions",
+        "allowed_users",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )?;
+    add_column_if_missing(
+        conn,
+        "quick_actions",
+        "danger_level",
+        "TEXT NOT NULL DEFAULT 'low'",
+    )?;
+    add_column_if_missing(
+        conn,
+        "quick_actions",
+        "requires_confirmation",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+
+    // Pipelines (steps and env stored as JSON)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pipelines (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT,
+            steps TEXT NOT NULL,
+            env TEXT NOT NULL DEFAULT '{}',
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Per-step status of pipeline runs (task_id references task_history)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pipeline_step_runs (
+            task_id TEXT NOT NULL,
+            step_index INTEGER NOT NULL,
+            step_name TEXT NOT NULL,
+            status TEXT NOT NULL,
+            exit_code INTEGER,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (task_id, step_index)
+        )",
+        [],
+    )?;
+
+    // One-shot delayed tasks (run_at is stored as RFC 3339 UTC so it sorts as text)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scheduled_tasks (
+            id TEXT PRIMARY KEY,
+            script_name TEXT NOT NULL,
+            run_at TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            task_id TEXT,
+            error TEXT,
+            created_by TEXT,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_scheduled_tasks_pending
+         ON scheduled_tasks(status, run_at)",
+        [],
+    )?;
+
     // Users table (for client users, admin is from env)
     conn.execute(
         "CREATE TABLE IF NOT EXISTS users (
@@ -140,6 +704,39 @@ pub fn init_db() -> Result<DbPool> {
         [],
     )?;
 
+    // Optional account expiry (added after the initial schema)
+    add_column_if_missing(conn, "users", "expires_at", "TEXT")?;
+
+    // Per-user session policy (added after the initial schema)
+    add_column_if_missing(conn, "users", "session_hours", "INTEGER")?;
+    add_column_if_missing(conn, "users", "idle_timeout_minutes", "INTEGER")?;
+
+    // Contact details and avatar of users (the image itself is on disk)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS user_profiles (
+            user_id TEXT PRIMARY KEY,
+            email TEXT,
+            phone TEXT,
+            avatar_type TEXT,
+            avatar_etag TEXT,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Per-user preferences (JSON values), keyed by user ID or `admin` for the
+    // admin from the environment
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS user_preferences (
+            user_key TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (user_key, key)
+        )",
+        [],
+    )?;
+
     // Sessions table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS sessions (
@@ -153,6 +750,10 @@ pub fn init_db() -> Result<DbPool> {
         [],
     )?;
 
+    // Idle timeouts (added after the initial schema)
+    add_column_if_missing(conn, "sessions", "idle_timeout_minutes", "INTEGER")?;
+    add_column_if_missing(conn, "sessions", "idle_expires_at", "TEXT")?;
+
     // Login attempts table for security audit and rate limiting
     conn.execute(
         "CREATE TABLE IF NOT EXISTS login_attempts (
@@ -184,6 +785,32 @@ pub fn init_db() -> Result<DbPool> {
         [],
     )?;
 
+    // Optional expiry of KV entries (added after the initial schema)
+    add_column_if_missing(conn, "plugin_kv", "expires_at", "TEXT")?;
+
+    // Grants letting a session's plugin page use that plugin's KV namespace
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS plugin_kv_grants (
+            token TEXT PRIMARY KEY,
+            plugin_id TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    add_column_if_missing(conn, "plugin_kv_grants", "expires_at", "TEXT")?;
+
+    // Users and roles a plugin is restricted to (no rows: everyone)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS plugin_access (
+            plugin_id TEXT NOT NULL,
+            subject_type TEXT NOT NULL,
+            subject TEXT NOT NULL,
+            PRIMARY KEY (plugin_id, subject_type, subject)
+        )",
+        [],
+    )?;
+
     // Plugin events (for observability)
     conn.execute(
         "CREATE TABLE IF NOT EXISTS plugin_events (
@@ -203,43 +830,155 @@ pub fn init_db() -> Result<DbPool> {
         [],
     )?;
 
-    // Insert default settings
+    // Public keys trusted to sign plugin binaries
     conn.execute(
-        "INSERT OR IGNORE INTO settings (key, value) VALUES ('scripts_dir', './scripts')",
+        "CREATE TABLE IF NOT EXISTS plugin_trusted_keys (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            public_key TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL
+        )",
         [],
     )?;
 
-    Ok(Arc::new(Mutex::new(conn)))
-}
-
-pub async fn get_setting(pool: &DbPool, key: &str) -> Result<Option<String>> {
-    let conn = pool.lock().await;
-    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
-    let value: Option<String> = stmt.query_row(params![key], |row| row.get(0)).ok();
-    Ok(value)
-}
+    // Result of the last OS package update check; a single row
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS package_update_checks (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            checked_at TEXT NOT NULL,
+            result TEXT NOT NULL
+        )",
+        [],
+    )?;
 
-pub async fn set_setting(pool: &DbPool, key: &str, value: &str) -> Result<()> {
-    let conn = pool.lock().await;
+    // Outbound webhooks (events is a JSON array of event filters; empty means all)
     conn.execute(
-        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-        params![key, value],
+        "CREATE TABLE IF NOT EXISTS webhooks (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            url TEXT NOT NULL,
+            secret TEXT NOT NULL,
+            events TEXT NOT NULL DEFAULT '[]',
+            enabled INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL
+        )",
+        [],
     )?;
-    Ok(())
-}
 
-pub async fn get_all_settings(pool: &DbPool) -> Result<Vec<Setting>> {
-    let conn = pool.lock().await;
-    let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
-    let rows = stmt.query_map([], |row| {
-        Ok(Setting {
-            key: row.get(0)?,
-            value: row.get(1)?,
-        })
-    })?;
+    // Webhook delivery log and retry queue (next_attempt_at sorts as text, like run_at)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id TEXT PRIMARY KEY,
+            webhook_id TEXT NOT NULL,
+            event TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            response_status INTEGER,
+            error TEXT,
+            next_attempt_at TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook
+         ON webhook_deliveries(webhook_id, created_at)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due
+         ON webhook_deliveries(status, next_attempt_at)",
+        [],
+    )?;
 
-    let mut settings = Vec::new();
-    for row in rows {
+    // File manager operations, for auditing
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS file_audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            action TEXT NOT NULL,
+            root TEXT NOT NULL,
+            path TEXT NOT NULL,
+            success INTEGER NOT NULL,
+            details TEXT,
+            timestamp TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Confirmed runs of dangerous quick actions, for auditing
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS action_audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            action_id TEXT NOT NULL,
+            action_name TEXT NOT NULL,
+            danger_level TEXT NOT NULL,
+            username TEXT NOT NULL,
+            task_id TEXT NOT NULL,
+            timestamp TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Insert default settings
+    conn.execute(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('scripts_dir', './scripts')",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Add a column to an existing table if it is not there yet
+///
+/// `CREATE TABLE IF NOT EXISTS` leaves databases created by older versions
+/// untouched, so columns introduced later are added here.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+pub async fn get_setting(pool: &DbPool, key: &str) -> Result<Option<String>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
+    let value: Option<String> = stmt.query_row(params![key], |row| row.get(0)).ok();
+    Ok(value)
+}
+
+pub async fn set_setting(pool: &DbPool, key: &str, value: &str) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+pub async fn get_all_settings(pool: &DbPool) -> Result<Vec<Setting>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Setting {
+            key: row.get(0)?,
+            value: row.get(1)?,
+        })
+    })?;
+
+    let mut settings = Vec::new();
+    for row in rows {
         settings.push(row?);
     }
     Ok(settings)
@@ -271,7 +1010,7 @@ pub async fn get_or_create_instance_id(pool: &DbPool) -> Result<String> {
 }
 
 pub async fn insert_task_history(pool: &DbPool, task: &TaskHistory) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute(
         "INSERT INTO task_history (id, script_name, started_at, finished_at, exit_code, output) 
          VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
@@ -294,7 +1033,7 @@ pub async fn update_task_history(
     exit_code: i32,
     output: Option<&str>,
 ) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute(
         "UPDATE task_history SET finished_at = ?1, exit_code = ?2, output = ?3 WHERE id = ?4",
         params![finished_at, exit_code, output, id],
@@ -302,8 +1041,27 @@ pub async fn update_task_history(
     Ok(())
 }
 
+/// Mark tasks left unfinished by a previous run (that didn't shut down cleanly) as failed
+///
+/// Their pipeline steps still marked running are recorded as cancelled. Returns
+/// the number of tasks marked.
+pub async fn fail_unfinished_tasks(pool: &DbPool) -> Result<usize> {
+    let conn = pool.get().await?;
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "UPDATE pipeline_step_runs SET status = 'cancelled', exit_code = -1, updated_at = ?1
+         WHERE status = 'running'",
+        params![now],
+    )?;
+    let count = conn.execute(
+        "UPDATE task_history SET finished_at = ?1, exit_code = -1 WHERE finished_at IS NULL",
+        params![now],
+    )?;
+    Ok(count)
+}
+
 pub async fn get_task_history(pool: &DbPool, limit: i32) -> Result<Vec<TaskHistory>> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
         "SELECT id, script_name, started_at, finished_at, exit_code, output 
          FROM task_history 
@@ -328,23 +1086,145 @@ pub async fn get_task_history(pool: &DbPool, limit: i32) -> Result<Vec<TaskHisto
     Ok(history)
 }
 
-pub async fn get_quick_actions(pool: &DbPool) -> Result<Vec<QuickAction>> {
-    let conn = pool.lock().await;
+/// Task counts and run time over the whole task history
+#[derive(Debug, Clone, Default)]
+pub struct TaskStats {
+    /// Started but not finished
+    pub running: u64,
+    pub succeeded: u64,
+    /// Finished with a non-zero or unknown exit code
+    pub failed: u64,
+    /// Total run time of finished tasks, in seconds
+    pub duration_secs: f64,
+}
+
+pub async fn get_task_stats(pool: &DbPool) -> Result<TaskStats> {
+    let conn = pool.get().await?;
+    let stats = conn.query_row(
+        "SELECT
+            COUNT(*) FILTER (WHERE finished_at IS NULL),
+            COUNT(*) FILTER (WHERE finished_at IS NOT NULL AND exit_code = 0),
+            COUNT(*) FILTER (WHERE finished_at IS NOT NULL AND (exit_code IS NULL OR exit_code <> 0)),
+            TOTAL((julianday(finished_at) - julianday(started_at)) * 86400)
+         FROM task_history",
+        [],
+        |row| {
+            Ok(TaskStats {
+                running: row.get::<_, i64>(0)? as u64,
+                succeeded: row.get::<_, i64>(1)? as u64,
+                failed: row.get::<_, i64>(2)? as u64,
+                duration_secs: row.get(3)?,
+            })
+        },
+    )?;
+    Ok(stats)
+}
+
+/// Index the whole task history again
+///
+/// The index refers to task_history by rowid, which VACUUM may renumber, so
+/// this also runs after a restore.
+fn rebuild_search_index(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "INSERT INTO task_history_fts (task_history_fts) VALUES ('rebuild')",
+        [],
+    )?;
+    Ok(())
+}
+
+/// A task whose output matched a search
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskSearchHit {
+    pub id: String,
+    pub script_name: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Excerpt of the output around the match, with matches wrapped in `«` and `»`
+    pub snippet: String,
+}
+
+/// Turn search box input into an FTS5 query matching all of its words
+///
+/// Each word is quoted, so characters FTS5 treats as syntax (`:`, `-`, `*`,
+/// `"`) are searched for literally. `None` if there is nothing to search for.
+pub fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Search task names and output for all words of `input`, best matches first
+pub async fn search_task_history(
+    pool: &DbPool,
+    input: &str,
+    limit: i32,
+) -> Result<Vec<TaskSearchHit>> {
+    let Some(query) = fts_query(input) else {
+        return Ok(Vec::new());
+    };
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
-        "SELECT id, name, script_path, icon, display_order 
-         FROM quick_actions 
-         ORDER BY display_order ASC",
+        "SELECT t.id, t.script_name, t.started_at, t.finished_at, t.exit_code,
+                snippet(task_history_fts, 1, '«', '»', '…', 24)
+         FROM task_history_fts
+         JOIN task_history t ON t.rowid = task_history_fts.rowid
+         WHERE task_history_fts MATCH ?1
+         ORDER BY rank
+         LIMIT ?2",
     )?;
-    let rows = stmt.query_map([], |row| {
-        Ok(QuickAction {
+    let rows = stmt.query_map(params![query, limit], |row| {
+        Ok(TaskSearchHit {
             id: row.get(0)?,
-            name: row.get(1)?,
-            script_path: row.get(2)?,
-            icon: row.get(3)?,
-            display_order: row.get(4)?,
+            script_name: row.get(1)?,
+            started_at: row.get(2)?,
+            finished_at: row.get(3)?,
+            exit_code: row.get(4)?,
+            snippet: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
         })
     })?;
 
+    let mut hits = Vec::new();
+    for row in rows {
+        hits.push(row?);
+    }
+    Ok(hits)
+}
+
+const QUICK_ACTION_COLUMNS: &str = "id, name, script_path, icon, display_order, \
+     cpu_limit_secs, memory_limit_mb, nofile_limit, concurrency, env, params, visibility, \
+     allowed_users, danger_level, requires_confirmation";
+
+fn quick_action_from_row(row: &rusqlite::Row) -> rusqlite::Result<QuickAction> {
+    Ok(QuickAction {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        script_path: row.get(2)?,
+        icon: row.get(3)?,
+        display_order: row.get(4)?,
+        cpu_limit_secs: row.get(5)?,
+        memory_limit_mb: row.get(6)?,
+        nofile_limit: row.get(7)?,
+        concurrency: row.get::<_, String>(8)?.parse().unwrap_or_default(),
+        env: serde_json::from_str(&row.get::<_, String>(9)?).unwrap_or_default(),
+        params: serde_json::from_str(&row.get::<_, String>(10)?).unwrap_or_default(),
+        visibility: row.get::<_, String>(11)?.parse().unwrap_or_default(),
+        allowed_users: serde_json::from_str(&row.get::<_, String>(12)?).unwrap_or_default(),
+        danger_level: row.get::<_, String>(13)?.parse().unwrap_or_default(),
+        requires_confirmation: row.get::<_, i32>(14)? != 0,
+    })
+}
+
+pub async fn get_quick_actions(pool: &DbPool) -> Result<Vec<QuickAction>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM quick_actions ORDER BY display_order ASC",
+        QUICK_ACTION_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], quick_action_from_row)?;
+
     let mut actions = Vec::new();
     for row in rows {
         actions.push(row?);
@@ -352,35 +1232,385 @@ pub async fn get_quick_actions(pool: &DbPool) -> Result<Vec<QuickAction>> {
     Ok(actions)
 }
 
+/// Statistics over the last `window` runs of each quick action's script, by
+/// script path; scripts that never ran are left out
+pub async fn get_quick_action_stats(
+    pool: &DbPool,
+    window: u32,
+) -> Result<HashMap<String, QuickActionStats>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT script_name, started_at, finished_at, exit_code FROM (
+             SELECT script_name, started_at, finished_at, exit_code,
+                    ROW_NUMBER() OVER (PARTITION BY script_name ORDER BY started_at DESC) AS n
+             FROM task_history
+             WHERE script_name IN (SELECT script_path FROM quick_actions)
+         )
+         WHERE n <= ?1
+         ORDER BY script_name, started_at DESC",
+    )?;
+    let rows = stmt.query_map(params![window], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<i32>>(3)?,
+        ))
+    })?;
+
+    let parse = |t: &str| chrono::DateTime::parse_from_rfc3339(t).ok();
+    // (stats, finished runs, successful runs, total seconds, runs with a duration)
+    let mut acc: HashMap<String, (QuickActionStats, u32, u32, f64, u32)> = HashMap::new();
+    for row in rows {
+        let (script, started_at, finished_at, exit_code) = row?;
+        let (stats, finished, succeeded, total_secs, timed) = acc.entry(script).or_default();
+        if stats.runs == 0 {
+            stats.last_run_at = Some(started_at.clone());
+            stats.last_exit_code = exit_code;
+        }
+        stats.runs += 1;
+        let Some(finished_at) = finished_at else {
+            continue;
+        };
+        *finished += 1;
+        if exit_code == Some(0) {
+            *succeeded += 1;
+        }
+        if let (Some(start), Some(end)) = (parse(&started_at), parse(&finished_at)) {
+            *total_secs += (end - start).num_milliseconds() as f64 / 1000.0;
+            *timed += 1;
+        }
+    }
+
+    Ok(acc
+        .into_iter()
+        .map(
+            |(script, (mut stats, finished, succeeded, total_secs, timed))| {
+                stats.success_rate = (finished > 0).then(|| succeeded as f64 / finished as f64);
+                stats.avg_duration_secs = (timed > 0).then(|| total_secs / timed as f64);
+                (script, stats)
+            },
+        )
+        .collect())
+}
+
+pub async fn get_quick_action(pool: &DbPool, id: &str) -> Result<Option<QuickAction>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM quick_actions WHERE id = ?1",
+        QUICK_ACTION_COLUMNS
+    ))?;
+    let action = stmt.query_row(params![id], quick_action_from_row).ok();
+    Ok(action)
+}
+
 pub async fn create_quick_action(pool: &DbPool, action: &QuickAction) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute(
-        "INSERT INTO quick_actions (id, name, script_path, icon, display_order) 
-         VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT INTO quick_actions (id, name, script_path, icon, display_order,
+                                    cpu_limit_secs, memory_limit_mb, nofile_limit, concurrency, env,
+                                    params, visibility, allowed_users, danger_level,
+                                    requires_confirmation) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
         params![
             action.id,
             action.name,
             action.script_path,
             action.icon,
-            action.display_order
+            action.display_order,
+            action.cpu_limit_secs,
+            action.memory_limit_mb,
+            action.nofile_limit,
+            action.concurrency.to_string(),
+            serde_json::to_string(&action.env)?,
+            serde_json::to_string(&action.params)?,
+            action.visibility.to_string(),
+            serde_json::to_string(&action.allowed_users)?,
+            action.danger_level.to_string(),
+            action.requires_confirmation as i32
+        ],
+    )?;
+    Ok(())
+}
+
+/// Save every field of an existing quick action. Returns false if it did not exist.
+pub async fn update_quick_action(pool: &DbPool, action: &QuickAction) -> Result<bool> {
+    let conn = pool.get().await?;
+    let changed = conn.execute(
+        "UPDATE quick_actions SET name = ?1, script_path = ?2, icon = ?3, display_order = ?4,
+                cpu_limit_secs = ?5, memory_limit_mb = ?6, nofile_limit = ?7, concurrency = ?8,
+                env = ?9, params = ?10, visibility = ?11, allowed_users = ?12,
+                danger_level = ?13, requires_confirmation = ?14
+         WHERE id = ?15",
+        params![
+            action.name,
+            action.script_path,
+            action.icon,
+            action.display_order,
+            action.cpu_limit_secs,
+            action.memory_limit_mb,
+            action.nofile_limit,
+            action.concurrency.to_string(),
+            serde_json::to_string(&action.env)?,
+            serde_json::to_string(&action.params)?,
+            action.visibility.to_string(),
+            serde_json::to_string(&action.allowed_users)?,
+            action.danger_level.to_string(),
+            action.requires_confirmation as i32,
+            action.id
         ],
     )?;
+    Ok(changed > 0)
+}
+
+/// Number quick actions in the order of `ids`, in one transaction; those not listed
+/// follow in their current order
+pub async fn reorder_quick_actions(pool: &DbPool, ids: &[String]) -> Result<()> {
+    let mut conn = pool.get().await?;
+    let tx = conn.transaction()?;
+    let current: Vec<String> = {
+        let mut stmt = tx.prepare("SELECT id FROM quick_actions ORDER BY display_order ASC")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+    let rest = current.iter().filter(|id| !ids.contains(id));
+    for (order, id) in ids.iter().chain(rest).enumerate() {
+        tx.execute(
+            "UPDATE quick_actions SET display_order = ?1 WHERE id = ?2",
+            params![order as i32, id],
+        )?;
+    }
+    tx.commit()?;
     Ok(())
 }
 
 pub async fn delete_quick_action(pool: &DbPool, id: &str) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute("DELETE FROM quick_actions WHERE id = ?1", params![id])?;
     Ok(())
 }
 
+// ============ Pipeline functions ============
+
+fn pipeline_from_row(row: &rusqlite::Row) -> rusqlite::Result<Pipeline> {
+    let steps: String = row.get(3)?;
+    let env: String = row.get(4)?;
+    Ok(Pipeline {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        steps: serde_json::from_str(&steps).unwrap_or_default(),
+        env: serde_json::from_str(&env).unwrap_or_default(),
+        created_at: row.get(5)?,
+    })
+}
+
+pub async fn get_pipelines(pool: &DbPool) -> Result<Vec<Pipeline>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, description, steps, env, created_at
+         FROM pipelines ORDER BY name ASC",
+    )?;
+    let rows = stmt.query_map([], pipeline_from_row)?;
+
+    let mut pipelines = Vec::new();
+    for row in rows {
+        pipelines.push(row?);
+    }
+    Ok(pipelines)
+}
+
+pub async fn get_pipeline(pool: &DbPool, id: &str) -> Result<Option<Pipeline>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, description, steps, env, created_at
+         FROM pipelines WHERE id = ?1",
+    )?;
+    let pipeline = stmt.query_row(params![id], pipeline_from_row).ok();
+    Ok(pipeline)
+}
+
+/// Insert or replace a pipeline
+pub async fn save_pipeline(pool: &DbPool, pipeline: &Pipeline) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT OR REPLACE INTO pipelines (id, name, description, steps, env, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            pipeline.id,
+            pipeline.name,
+            pipeline.description,
+            serde_json::to_string(&pipeline.steps)?,
+            serde_json::to_string(&pipeline.env)?,
+            pipeline.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub async fn delete_pipeline(pool: &DbPool, id: &str) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute("DELETE FROM pipelines WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+/// Insert or update the status of a pipeline step within a run
+pub async fn record_pipeline_step(
+    pool: &DbPool,
+    task_id: &str,
+    step_index: usize,
+    step_name: &str,
+    status: &str,
+    exit_code: Option<i32>,
+) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT OR REPLACE INTO pipeline_step_runs
+            (task_id, step_index, step_name, status, exit_code, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            task_id,
+            step_index as i64,
+            step_name,
+            status,
+            exit_code,
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Get per-step status for a pipeline run
+pub async fn get_pipeline_step_runs(pool: &DbPool, task_id: &str) -> Result<Vec<PipelineStepRun>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT task_id, step_index, step_name, status, exit_code, updated_at
+         FROM pipeline_step_runs
+         WHERE task_id = ?1
+         ORDER BY step_index ASC",
+    )?;
+    let rows = stmt.query_map(params![task_id], |row| {
+        Ok(PipelineStepRun {
+            task_id: row.get(0)?,
+            step_index: row.get(1)?,
+            step_name: row.get(2)?,
+            status: row.get(3)?,
+            exit_code: row.get(4)?,
+            updated_at: row.get(5)?,
+        })
+    })?;
+
+    let mut steps = Vec::new();
+    for row in rows {
+        steps.push(row?);
+    }
+    Ok(steps)
+}
+
+// ============ Scheduled task functions ============
+
+fn scheduled_task_from_row(row: &rusqlite::Row) -> rusqlite::Result<ScheduledTask> {
+    Ok(ScheduledTask {
+        id: row.get(0)?,
+        script_name: row.get(1)?,
+        run_at: row.get(2)?,
+        status: row.get(3)?,
+        task_id: row.get(4)?,
+        error: row.get(5)?,
+        created_by: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+pub async fn insert_scheduled_task(pool: &DbPool, task: &ScheduledTask) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT INTO scheduled_tasks (id, script_name, run_at, status, task_id, error, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            task.id,
+            task.script_name,
+            task.run_at,
+            task.status,
+            task.task_id,
+            task.error,
+            task.created_by,
+            task.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Pending scheduled tasks, soonest first
+pub async fn get_pending_scheduled_tasks(pool: &DbPool) -> Result<Vec<ScheduledTask>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT id, script_name, run_at, status, task_id, error, created_by, created_at
+         FROM scheduled_tasks
+         WHERE status = 'pending'
+         ORDER BY run_at ASC",
+    )?;
+    let rows = stmt.query_map([], scheduled_task_from_row)?;
+
+    let mut tasks = Vec::new();
+    for row in rows {
+        tasks.push(row?);
+    }
+    Ok(tasks)
+}
+
+/// Claim pending tasks due at or before `now`, marking them as started
+pub async fn take_due_scheduled_tasks(pool: &DbPool, now: &str) -> Result<Vec<ScheduledTask>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "UPDATE scheduled_tasks SET status = 'started'
+         WHERE status = 'pending' AND run_at <= ?1
+         RETURNING id, script_name, run_at, status, task_id, error, created_by, created_at",
+    )?;
+    let rows = stmt.query_map(params![now], scheduled_task_from_row)?;
+
+    let mut tasks = Vec::new();
+    for row in rows {
+        tasks.push(row?);
+    }
+    tasks.sort_by(|a, b| a.run_at.cmp(&b.run_at));
+    Ok(tasks)
+}
+
+/// Record the outcome of starting a scheduled task
+pub async fn finish_scheduled_task(
+    pool: &DbPool,
+    id: &str,
+    task_id: Option<&str>,
+    error: Option<&str>,
+) -> Result<()> {
+    let status = if error.is_some() { "failed" } else { "started" };
+    let conn = pool.get().await?;
+    conn.execute(
+        "UPDATE scheduled_tasks SET status = ?1, task_id = ?2, error = ?3 WHERE id = ?4",
+        params![status, task_id, error, id],
+    )?;
+    Ok(())
+}
+
+/// Cancel a pending scheduled task. Returns false if it was not pending.
+pub async fn cancel_scheduled_task(pool: &DbPool, id: &str) -> Result<bool> {
+    let conn = pool.get().await?;
+    let changed = conn.execute(
+        "UPDATE scheduled_tasks SET status = 'cancelled' WHERE id = ?1 AND status = 'pending'",
+        params![id],
+    )?;
+    Ok(changed > 0)
+}
+
 // ============ User functions ============
 
 pub async fn create_user(pool: &DbPool, user: &User) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute(
-        "INSERT INTO users (id, username, password_hash, display_name, role, is_active, created_at) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        "INSERT INTO users (id, username, password_hash, display_name, role, is_active, created_at,
+                            expires_at, session_hours, idle_timeout_minutes) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
         params![
             user.id,
             user.username,
@@ -388,81 +1618,62 @@ pub async fn create_user(pool: &DbPool, user: &User) -> Result<()> {
             user.display_name,
             user.role.to_string(),
             user.is_active as i32,
-            user.created_at
+            user.created_at,
+            user.expires_at,
+            user.session_hours,
+            user.idle_timeout_minutes
         ],
     )?;
     Ok(())
 }
 
+const USER_COLUMNS: &str = "id, username, password_hash, display_name, role, is_active, created_at,
+                            expires_at, session_hours, idle_timeout_minutes";
+
+fn user_from_row(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+        display_name: row.get(3)?,
+        role: row.get::<_, String>(4)?.parse().unwrap_or(UserRole::Client),
+        is_active: row.get::<_, i32>(5)? != 0,
+        created_at: row.get(6)?,
+        expires_at: row.get(7)?,
+        session_hours: row.get(8)?,
+        idle_timeout_minutes: row.get(9)?,
+    })
+}
+
 pub async fn get_user_by_username(pool: &DbPool, username: &str) -> Result<Option<User>> {
-    let conn = pool.lock().await;
-    let mut stmt = conn.prepare(
-        "SELECT id, username, password_hash, display_name, role, is_active, created_at 
-         FROM users WHERE username = ?1",
-    )?;
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM users WHERE username = ?1",
+        USER_COLUMNS
+    ))?;
 
-    let user = stmt
-        .query_row(params![username], |row| {
-            let role_str: String = row.get(4)?;
-            Ok(User {
-                id: row.get(0)?,
-                username: row.get(1)?,
-                password_hash: row.get(2)?,
-                display_name: row.get(3)?,
-                role: role_str.parse().unwrap_or(UserRole::Client),
-                is_active: row.get::<_, i32>(5)? != 0,
-                created_at: row.get(6)?,
-            })
-        })
-        .ok();
+    let user = stmt.query_row(params![username], user_from_row).ok();
 
     Ok(user)
 }
 
 pub async fn get_user_by_id(pool: &DbPool, id: &str) -> Result<Option<User>> {
-    let conn = pool.lock().await;
-    let mut stmt = conn.prepare(
-        "SELECT id, username, password_hash, display_name, role, is_active, created_at 
-         FROM users WHERE id = ?1",
-    )?;
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM users WHERE id = ?1", USER_COLUMNS))?;
 
-    let user = stmt
-        .query_row(params![id], |row| {
-            let role_str: String = row.get(4)?;
-            Ok(User {
-                id: row.get(0)?,
-                username: row.get(1)?,
-                password_hash: row.get(2)?,
-                display_name: row.get(3)?,
-                role: role_str.parse().unwrap_or(UserRole::Client),
-                is_active: row.get::<_, i32>(5)? != 0,
-                created_at: row.get(6)?,
-            })
-        })
-        .ok();
+    let user = stmt.query_row(params![id], user_from_row).ok();
 
     Ok(user)
 }
 
 pub async fn get_all_users(pool: &DbPool) -> Result<Vec<User>> {
-    let conn = pool.lock().await;
-    let mut stmt = conn.prepare(
-        "SELECT id, username, password_hash, display_name, role, is_active, created_at 
-         FROM users ORDER BY created_at DESC",
-    )?;
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM users ORDER BY created_at DESC",
+        USER_COLUMNS
+    ))?;
 
-    let rows = stmt.query_map([], |row| {
-        let role_str: String = row.get(4)?;
-        Ok(User {
-            id: row.get(0)?,
-            username: row.get(1)?,
-            password_hash: row.get(2)?,
-            display_name: row.get(3)?,
-            role: role_str.parse().unwrap_or(UserRole::Client),
-            is_active: row.get::<_, i32>(5)? != 0,
-            created_at: row.get(6)?,
-        })
-    })?;
+    let rows = stmt.query_map([], user_from_row)?;
 
     let mut users = Vec::new();
     for row in rows {
@@ -476,17 +1687,33 @@ pub async fn update_user(
     id: &str,
     display_name: Option<&str>,
     is_active: bool,
+    expires_at: Option<&str>,
+) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "UPDATE users SET display_name = ?1, is_active = ?2, expires_at = ?3 WHERE id = ?4",
+        params![display_name, is_active as i32, expires_at, id],
+    )?;
+    Ok(())
+}
+
+/// Set or clear (`None`) a user's own session duration and idle timeout
+pub async fn set_user_session_policy(
+    pool: &DbPool,
+    id: &str,
+    session_hours: Option<i64>,
+    idle_timeout_minutes: Option<i64>,
 ) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute(
-        "UPDATE users SET display_name = ?1, is_active = ?2 WHERE id = ?3",
-        params![display_name, is_active as i32, id],
+        "UPDATE users SET session_hours = ?1, idle_timeout_minutes = ?2 WHERE id = ?3",
+        params![session_hours, idle_timeout_minutes, id],
     )?;
     Ok(())
 }
 
 pub async fn update_user_password(pool: &DbPool, id: &str, password_hash: &str) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute(
         "UPDATE users SET password_hash = ?1 WHERE id = ?2",
         params![password_hash, id],
@@ -497,36 +1724,150 @@ pub async fn update_user_password(pool: &DbPool, id: &str, password_hash: &str)
 }
 
 pub async fn delete_user(pool: &DbPool, id: &str) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     // Also delete user's sessions
     conn.execute("DELETE FROM sessions WHERE user_id = ?1", params![id])?;
+    conn.execute(
+        "DELETE FROM plugin_access WHERE subject_type = 'user' AND subject = ?1",
+        params![id],
+    )?;
+    conn.execute(
+        "DELETE FROM user_preferences WHERE user_key = ?1",
+        params![id],
+    )?;
+    conn.execute("DELETE FROM user_profiles WHERE user_id = ?1", params![id])?;
     conn.execute("DELETE FROM users WHERE id = ?1", params![id])?;
     Ok(())
 }
 
-// ============ Session functions ============
+// ============ User profile functions ============
 
-pub async fn create_session(pool: &DbPool, session: &Session) -> Result<()> {
-    let conn = pool.lock().await;
-    conn.execute(
-        "INSERT INTO sessions (id, user_id, user_role, username, created_at, expires_at) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![
-            session.id,
-            session.user_id,
-            session.user_role.to_string(),
-            session.username,
-            session.created_at,
-            session.expires_at
+fn user_profile_from_row(row: &rusqlite::Row) -> rusqlite::Result<UserProfile> {
+    Ok(UserProfile {
+        user_id: row.get(0)?,
+        email: row.get(1)?,
+        phone: row.get(2)?,
+        avatar_type: row.get(3)?,
+        avatar_etag: row.get(4)?,
+    })
+}
+
+pub async fn get_user_profile(pool: &DbPool, user_id: &str) -> Result<Option<UserProfile>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT user_id, email, phone, avatar_type, avatar_etag
+         FROM user_profiles WHERE user_id = ?1",
+    )?;
+    Ok(stmt.query_row(params![user_id], user_profile_from_row).ok())
+}
+
+/// Every user's profile by user ID
+pub async fn get_user_profiles(pool: &DbPool) -> Result<HashMap<String, UserProfile>> {
+    let conn = pool.get().await?;
+    let mut stmt =
+        conn.prepare("SELECT user_id, email, phone, avatar_type, avatar_etag FROM user_profiles")?;
+    let rows = stmt.query_map([], user_profile_from_row)?;
+
+    let mut profiles = HashMap::new();
+    for row in rows {
+        let profile = row?;
+        profiles.insert(profile.user_id.clone(), profile);
+    }
+    Ok(profiles)
+}
+
+pub async fn save_user_profile(pool: &DbPool, profile: &UserProfile) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT OR REPLACE INTO user_profiles (user_id, email, phone, avatar_type, avatar_etag, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            profile.user_id,
+            profile.email,
+            profile.phone,
+            profile.avatar_type,
+            profile.avatar_etag,
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+// ============ User preference functions ============
+
+/// A user's preferences by key
+pub async fn get_user_preferences(
+    pool: &DbPool,
+    user_key: &str,
+) -> Result<BTreeMap<String, serde_json::Value>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare("SELECT key, value FROM user_preferences WHERE user_key = ?1")?;
+    let rows = stmt.query_map(params![user_key], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    let mut preferences = BTreeMap::new();
+    for row in rows {
+        let (key, value) = row?;
+        preferences.insert(key, serde_json::from_str(&value).unwrap_or_default());
+    }
+    Ok(preferences)
+}
+
+/// Set a user's preferences in one transaction; a `null` value removes the key
+pub async fn set_user_preferences(
+    pool: &DbPool,
+    user_key: &str,
+    preferences: &BTreeMap<String, serde_json::Value>,
+) -> Result<()> {
+    let mut conn = pool.get().await?;
+    let tx = conn.transaction()?;
+    let now = chrono::Utc::now().to_rfc3339();
+    for (key, value) in preferences {
+        if value.is_null() {
+            tx.execute(
+                "DELETE FROM user_preferences WHERE user_key = ?1 AND key = ?2",
+                params![user_key, key],
+            )?;
+        } else {
+            tx.execute(
+                "INSERT OR REPLACE INTO user_preferences (user_key, key, value, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![user_key, key, value.to_string(), now],
+            )?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+// ============ Session functions ============
+
+pub async fn create_session(pool: &DbPool, session: &Session) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT INTO sessions (id, user_id, user_role, username, created_at, expires_at,
+                               idle_timeout_minutes, idle_expires_at) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            session.id,
+            session.user_id,
+            session.user_role.to_string(),
+            session.username,
+            session.created_at,
+            session.expires_at,
+            session.idle_timeout_minutes,
+            session.idle_expires_at
         ],
     )?;
     Ok(())
 }
 
 pub async fn get_session(pool: &DbPool, id: &str) -> Result<Option<Session>> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
-        "SELECT id, user_id, user_role, username, created_at, expires_at 
+        "SELECT id, user_id, user_role, username, created_at, expires_at, idle_timeout_minutes,
+                idle_expires_at
          FROM sessions WHERE id = ?1",
     )?;
 
@@ -540,6 +1881,8 @@ pub async fn get_session(pool: &DbPool, id: &str) -> Result<Option<Session>> {
                 username: row.get(3)?,
                 created_at: row.get(4)?,
                 expires_at: row.get(5)?,
+                idle_timeout_minutes: row.get(6)?,
+                idle_expires_at: row.get(7)?,
             })
         })
         .ok();
@@ -547,16 +1890,60 @@ pub async fn get_session(pool: &DbPool, id: &str) -> Result<Option<Session>> {
     Ok(session)
 }
 
+/// Push back when a session idles out, after it's been used
+pub async fn touch_session(pool: &DbPool, id: &str, idle_expires_at: &str) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "UPDATE sessions SET idle_expires_at = ?1 WHERE id = ?2",
+        params![idle_expires_at, id],
+    )?;
+    Ok(())
+}
+
 pub async fn delete_session(pool: &DbPool, id: &str) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
+    conn.execute(
+        "DELETE FROM plugin_kv_grants WHERE session_id = ?1",
+        params![id],
+    )?;
     Ok(())
 }
 
+/// Sessions that haven't expired, by role
+pub async fn count_active_sessions(pool: &DbPool) -> Result<Vec<(String, u64)>> {
+    let conn = pool.get().await?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut stmt = conn.prepare(
+        "SELECT user_role, COUNT(*) FROM sessions
+         WHERE expires_at >= ?1 AND (idle_expires_at IS NULL OR idle_expires_at >= ?1)
+         GROUP BY user_role",
+    )?;
+    let rows = stmt.query_map(params![now], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+    })?;
+
+    let mut counts = Vec::new();
+    for row in rows {
+        counts.push(row?);
+    }
+    Ok(counts)
+}
+
 pub async fn cleanup_expired_sessions(pool: &DbPool) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let now = chrono::Utc::now().to_rfc3339();
-    conn.execute("DELETE FROM sessions WHERE expires_at < ?1", params![now])?;
+    conn.execute(
+        "DELETE FROM sessions WHERE expires_at < ?1 OR idle_expires_at < ?1",
+        params![now],
+    )?;
+    // KV grants end with their session, or sooner
+    conn.execute(
+        "DELETE FROM plugin_kv_grants
+         WHERE session_id NOT IN (SELECT id FROM sessions)
+            OR expires_at IS NULL OR expires_at < ?1",
+        params![now],
+    )?;
     Ok(())
 }
 
@@ -570,20 +1957,154 @@ pub struct PluginKvEntry {
     pub value: String,
 }
 
+/// Users and roles allowed to see and use a plugin
+///
+/// A plugin with neither is open to every authenticated user. Admins always
+/// have access.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PluginAccess {
+    /// User IDs
+    #[serde(default)]
+    pub users: Vec<String>,
+    #[serde(default)]
+    pub roles: Vec<UserRole>,
+}
+
+impl PluginAccess {
+    /// Whether the plugin is open to everyone
+    pub fn is_open(&self) -> bool {
+        self.users.is_empty() && self.roles.is_empty()
+    }
+
+    /// Whether a user with this ID and role may use the plugin
+    pub fn allows(&self, user_id: Option<&str>, role: UserRole) -> bool {
+        role == UserRole::Admin
+            || self.is_open()
+            || self.roles.contains(&role)
+            || user_id.is_some_and(|id| self.users.iter().any(|user| user == id))
+    }
+}
+
+/// Default cap on the bytes of keys and values a plugin stores
+pub const DEFAULT_KV_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Default cap on the number of keys a plugin stores
+pub const DEFAULT_KV_MAX_KEYS: u64 = 10_000;
+
+/// Storage quota of a plugin's KV namespace; `None` means unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PluginKvQuota {
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    #[serde(default)]
+    pub max_keys: Option<u64>,
+}
+
+impl Default for PluginKvQuota {
+    fn default() -> Self {
+        Self {
+            max_bytes: Some(DEFAULT_KV_MAX_BYTES),
+            max_keys: Some(DEFAULT_KV_MAX_KEYS),
+        }
+    }
+}
+
+/// Live keys and their bytes (key plus value) in a plugin's KV namespace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PluginKvUsage {
+    pub keys: u64,
+    pub bytes: u64,
+}
+
+/// A KV write was refused because it would exceed the plugin's quota
+#[derive(Debug)]
+pub struct KvQuotaExceeded(pub String);
+
+impl std::fmt::Display for KvQuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "KV quota exceeded: {}", self.0)
+    }
+}
+
+impl std::error::Error for KvQuotaExceeded {}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[allow(dead_code)] // Used by plugins, not yet integrated (Phase 5+)
 pub struct PluginEvent {
     pub id: i64,
     pub plugin_id: String,
-    pub event_type: String, // started, stopped, crashed, restarted, disabled
+    pub event_type: String, // started, stopped, crashed, restarted, enabled, disabled, ...
     pub timestamp: String,
     pub details: Option<String>, // JSON
 }
 
+/// Minisign public key trusted to sign plugin binaries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedKey {
+    pub id: String,
+    pub name: String,
+    pub public_key: String, // base64, as in the second line of minisign.pub
+    pub created_at: String,
+}
+
+/// Outbound webhook; `events` holds filters like `task.failed` or `task.*`, empty for all
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub created_at: String,
+}
+
+/// File manager operation, kept in the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileAuditEntry {
+    pub id: i64,
+    pub username: String,
+    pub action: String, // download, upload, rename, delete
+    pub root: String,
+    pub path: String,
+    pub success: bool,
+    pub details: Option<String>, // size, rename target or error
+    pub timestamp: String,
+}
+
+/// Confirmed run of a quick action that needed confirmation, kept in the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionAuditEntry {
+    pub id: i64,
+    pub action_id: String,
+    pub action_name: String,
+    pub danger_level: DangerLevel,
+    /// User who typed the confirmation
+    pub username: String,
+    pub task_id: String,
+    pub timestamp: String,
+}
+
+/// One event sent (or to be sent) to a webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: String,
+    pub payload: String, // JSON body, as sent
+    pub status: String,  // pending, delivered, failed
+    pub attempts: i64,
+    pub response_status: Option<i64>,
+    pub error: Option<String>,
+    pub next_attempt_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
 // ============ Login Attempts functions ============
 
 pub async fn record_login_attempt(pool: &DbPool, attempt: &LoginAttempt) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute(
         "INSERT INTO login_attempts (id, username, ip_address, success, failure_reason, attempted_at) 
          VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
@@ -601,7 +2122,7 @@ pub async fn record_login_attempt(pool: &DbPool, attempt: &LoginAttempt) -> Resu
 
 /// Get recent failed login attempts for rate limiting (by username)
 pub async fn get_recent_failed_attempts(pool: &DbPool, username: &str, since: &str) -> Result<i32> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
         "SELECT COUNT(*) FROM login_attempts 
          WHERE username = ?1 AND success = 0 AND attempted_at > ?2",
@@ -612,7 +2133,7 @@ pub async fn get_recent_failed_attempts(pool: &DbPool, username: &str, since: &s
 
 /// Get recent failed login attempts for rate limiting (by IP)
 pub async fn get_recent_failed_attempts_by_ip(pool: &DbPool, ip: &str, since: &str) -> Result<i32> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
         "SELECT COUNT(*) FROM login_attempts 
          WHERE ip_address = ?1 AND success = 0 AND attempted_at > ?2",
@@ -623,7 +2144,7 @@ pub async fn get_recent_failed_attempts_by_ip(pool: &DbPool, ip: &str, since: &s
 
 /// Get the most recent failed attempt time for a username
 pub async fn get_last_failed_attempt(pool: &DbPool, username: &str) -> Result<Option<String>> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
         "SELECT attempted_at FROM login_attempts 
          WHERE username = ?1 AND success = 0 
@@ -635,7 +2156,7 @@ pub async fn get_last_failed_attempt(pool: &DbPool, username: &str) -> Result<Op
 
 /// Get the most recent failed attempt time for an IP
 pub async fn get_last_failed_attempt_by_ip(pool: &DbPool, ip: &str) -> Result<Option<String>> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
         "SELECT attempted_at FROM login_attempts 
          WHERE ip_address = ?1 AND success = 0 
@@ -647,7 +2168,7 @@ pub async fn get_last_failed_attempt_by_ip(pool: &DbPool, ip: &str) -> Result<Op
 
 /// Get login attempt history (for admin view)
 pub async fn get_login_attempts(pool: &DbPool, limit: i32) -> Result<Vec<LoginAttempt>> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
         "SELECT id, username, ip_address, success, failure_reason, attempted_at 
          FROM login_attempts 
@@ -674,7 +2195,7 @@ pub async fn get_login_attempts(pool: &DbPool, limit: i32) -> Result<Vec<LoginAt
 
 /// Clean up old login attempts (keep last 30 days)
 pub async fn cleanup_old_login_attempts(pool: &DbPool) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let cutoff = (chrono::Utc::now() - chrono::Duration::days(30)).to_rfc3339();
     conn.execute(
         "DELETE FROM login_attempts WHERE attempted_at < ?1",
@@ -685,29 +2206,172 @@ pub async fn cleanup_old_login_attempts(pool: &DbPool) -> Result<()> {
 
 // ============ Plugin KV functions ============
 
-/// Get a value from plugin KV storage
+/// Get a value from plugin KV storage (expired keys read as absent)
 pub async fn plugin_kv_get(pool: &DbPool, plugin_id: &str, key: &str) -> Result<Option<String>> {
-    let conn = pool.lock().await;
-    let mut stmt = conn.prepare("SELECT value FROM plugin_kv WHERE plugin_id = ?1 AND key = ?2")?;
+    let conn = pool.get().await?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut stmt = conn.prepare(
+        "SELECT value FROM plugin_kv
+         WHERE plugin_id = ?1 AND key = ?2 AND (expires_at IS NULL OR expires_at > ?3)",
+    )?;
     let value: Option<String> = stmt
-        .query_row(params![plugin_id, key], |row| row.get(0))
+        .query_row(params![plugin_id, key, now], |row| row.get(0))
         .ok();
     Ok(value)
 }
 
-/// Set a value in plugin KV storage
-pub async fn plugin_kv_set(pool: &DbPool, plugin_id: &str, key: &str, value: &str) -> Result<()> {
-    let conn = pool.lock().await;
-    conn.execute(
-        "INSERT OR REPLACE INTO plugin_kv (plugin_id, key, value) VALUES (?1, ?2, ?3)",
-        params![plugin_id, key, value],
+/// Usage of a plugin's KV namespace, not counting expired keys
+fn kv_usage(conn: &Connection, plugin_id: &str, except_key: Option<&str>) -> Result<PluginKvUsage> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let (keys, bytes): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(length(CAST(key AS BLOB)) + length(CAST(value AS BLOB))), 0)
+         FROM plugin_kv
+         WHERE plugin_id = ?1 AND (?2 IS NULL OR key != ?2)
+           AND (expires_at IS NULL OR expires_at > ?3)",
+        params![plugin_id, except_key, now],
+        |row| Ok((row.get(0)?, row.get(1)?)),
     )?;
+    Ok(PluginKvUsage {
+        keys: keys as u64,
+        bytes: bytes as u64,
+    })
+}
+
+/// Fail with [`KvQuotaExceeded`] if storing `value` at `key` would break `quota`
+fn check_kv_quota(
+    conn: &Connection,
+    plugin_id: &str,
+    key: &str,
+    value: &str,
+    quota: PluginKvQuota,
+) -> Result<()> {
+    if quota.max_bytes.is_none() && quota.max_keys.is_none() {
+        return Ok(());
+    }
+    // Everything but the key being written, which is replaced
+    let usage = kv_usage(conn, plugin_id, Some(key))?;
+    if let Some(max_keys) = quota.max_keys {
+        if usage.keys + 1 > max_keys {
+            return Err(KvQuotaExceeded(format!("{} allows {} keys", plugin_id, max_keys)).into());
+        }
+    }
+    if let Some(max_bytes) = quota.max_bytes {
+        if usage.bytes + (key.len() + value.len()) as u64 > max_bytes {
+            return Err(
+                KvQuotaExceeded(format!("{} allows {} bytes", plugin_id, max_bytes)).into(),
+            );
+        }
+    }
     Ok(())
 }
 
+/// Set a value in plugin KV storage, expiring after `ttl_secs` if given
+///
+/// Fails with [`KvQuotaExceeded`] if the write would break `quota`.
+pub async fn plugin_kv_set(
+    pool: &DbPool,
+    plugin_id: &str,
+    key: &str,
+    value: &str,
+    ttl_secs: Option<u64>,
+    quota: PluginKvQuota,
+) -> Result<()> {
+    let mut conn = pool.get().await?;
+    // The quota holds only if no other write lands between the check and this one
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    check_kv_quota(&tx, plugin_id, key, value, quota)?;
+    let expires_at = ttl_secs
+        .map(|secs| (chrono::Utc::now() + chrono::Duration::seconds(secs as i64)).to_rfc3339());
+    tx.execute(
+        "INSERT OR REPLACE INTO plugin_kv (plugin_id, key, value, expires_at) VALUES (?1, ?2, ?3, ?4)",
+        params![plugin_id, key, value, expires_at],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Atomically add `by` to an integer in plugin KV storage, returning the result
+///
+/// An absent or expired key counts as 0. A live key keeps its expiry.
+pub async fn plugin_kv_incr(
+    pool: &DbPool,
+    plugin_id: &str,
+    key: &str,
+    by: i64,
+    quota: PluginKvQuota,
+) -> Result<i64> {
+    let mut conn = pool.get().await?;
+    // Take the write lock up front: a deferred read can't upgrade under WAL
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let current: Option<(Option<String>, Option<String>)> = tx
+        .query_row(
+            "SELECT value, expires_at FROM plugin_kv
+             WHERE plugin_id = ?1 AND key = ?2 AND (expires_at IS NULL OR expires_at > ?3)",
+            params![plugin_id, key, now],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .ok();
+    let (value, expires_at) = match current {
+        Some((value, expires_at)) => {
+            let value = value.as_deref().unwrap_or_default();
+            let value: i64 = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Value of {} is not an integer", key))?;
+            (value, expires_at)
+        }
+        None => (0, None),
+    };
+    let value = value
+        .checked_add(by)
+        .ok_or_else(|| anyhow::anyhow!("Value of {} overflowed", key))?;
+    check_kv_quota(&tx, plugin_id, key, &value.to_string(), quota)?;
+    tx.execute(
+        "INSERT OR REPLACE INTO plugin_kv (plugin_id, key, value, expires_at) VALUES (?1, ?2, ?3, ?4)",
+        params![plugin_id, key, value.to_string(), expires_at],
+    )?;
+    tx.commit()?;
+    Ok(value)
+}
+
+/// Atomically set a plugin KV value if it currently holds `expected`
+///
+/// `expected: None` requires the key to be absent (or expired). Returns
+/// whether the value was swapped.
+pub async fn plugin_kv_compare_and_swap(
+    pool: &DbPool,
+    plugin_id: &str,
+    key: &str,
+    expected: Option<&str>,
+    new: &str,
+    quota: PluginKvQuota,
+) -> Result<bool> {
+    let mut conn = pool.get().await?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let current: Option<String> = tx
+        .query_row(
+            "SELECT value FROM plugin_kv
+             WHERE plugin_id = ?1 AND key = ?2 AND (expires_at IS NULL OR expires_at > ?3)",
+            params![plugin_id, key, now],
+            |row| row.get(0),
+        )
+        .ok();
+    if current.as_deref() != expected {
+        return Ok(false);
+    }
+    check_kv_quota(&tx, plugin_id, key, new, quota)?;
+    tx.execute(
+        "INSERT OR REPLACE INTO plugin_kv (plugin_id, key, value, expires_at) VALUES (?1, ?2, ?3, NULL)",
+        params![plugin_id, key, new],
+    )?;
+    tx.commit()?;
+    Ok(true)
+}
+
 /// Delete a value from plugin KV storage
 pub async fn plugin_kv_delete(pool: &DbPool, plugin_id: &str, key: &str) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute(
         "DELETE FROM plugin_kv WHERE plugin_id = ?1 AND key = ?2",
         params![plugin_id, key],
@@ -718,10 +2382,14 @@ pub async fn plugin_kv_delete(pool: &DbPool, plugin_id: &str, key: &str) -> Resu
 /// Get all KV entries for a plugin
 #[allow(dead_code)] // Used by plugins, not yet integrated (Phase 5+)
 pub async fn plugin_kv_get_all(pool: &DbPool, plugin_id: &str) -> Result<Vec<PluginKvEntry>> {
-    let conn = pool.lock().await;
-    let mut stmt = conn
-        .prepare("SELECT plugin_id, key, value FROM plugin_kv WHERE plugin_id = ?1 ORDER BY key")?;
-    let rows = stmt.query_map(params![plugin_id], |row| {
+    let conn = pool.get().await?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut stmt = conn.prepare(
+        "SELECT plugin_id, key, value FROM plugin_kv
+         WHERE plugin_id = ?1 AND (expires_at IS NULL OR expires_at > ?2)
+         ORDER BY key",
+    )?;
+    let rows = stmt.query_map(params![plugin_id, now], |row| {
         Ok(PluginKvEntry {
             plugin_id: row.get(0)?,
             key: row.get(1)?,
@@ -736,6 +2404,124 @@ pub async fn plugin_kv_get_all(pool: &DbPool, plugin_id: &str) -> Result<Vec<Plu
     Ok(entries)
 }
 
+/// List live KV keys of a plugin starting with `prefix`, in key order
+///
+/// Returns up to `limit` keys sorting after `after`, for cursor pagination.
+pub async fn plugin_kv_list_keys(
+    pool: &DbPool,
+    plugin_id: &str,
+    prefix: &str,
+    after: Option<&str>,
+    limit: usize,
+) -> Result<Vec<String>> {
+    let conn = pool.get().await?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut stmt = conn.prepare(
+        "SELECT key FROM plugin_kv
+         WHERE plugin_id = ?1 AND substr(key, 1, length(?2)) = ?2 AND (?3 IS NULL OR key > ?3)
+           AND (expires_at IS NULL OR expires_at > ?4)
+         ORDER BY key
+         LIMIT ?5",
+    )?;
+    let rows = stmt.query_map(
+        params![plugin_id, prefix, after, now, limit as i64],
+        |row| row.get(0),
+    )?;
+
+    let mut keys = Vec::new();
+    for row in rows {
+        keys.push(row?);
+    }
+    Ok(keys)
+}
+
+/// Keys and bytes a plugin stores in KV
+pub async fn plugin_kv_usage(pool: &DbPool, plugin_id: &str) -> Result<PluginKvUsage> {
+    let conn = pool.get().await?;
+    kv_usage(&conn, plugin_id, None)
+}
+
+/// Grant a session access to a plugin's KV namespace for `ttl`, returning the token
+///
+/// A session has one grant per plugin: while it is valid it is extended and
+/// its token kept, otherwise it is replaced.
+pub async fn plugin_kv_grant_create(
+    pool: &DbPool,
+    plugin_id: &str,
+    session_id: &str,
+    ttl: chrono::Duration,
+) -> Result<String> {
+    let mut conn = pool.get().await?;
+    let now = chrono::Utc::now();
+    let expires_at = (now + ttl).to_rfc3339();
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let current: Option<String> = tx
+        .query_row(
+            "SELECT token FROM plugin_kv_grants
+             WHERE plugin_id = ?1 AND session_id = ?2 AND expires_at >= ?3",
+            params![plugin_id, session_id, now.to_rfc3339()],
+            |row| row.get(0),
+        )
+        .ok();
+    let token = match current {
+        Some(token) => {
+            tx.execute(
+                "UPDATE plugin_kv_grants SET expires_at = ?1 WHERE token = ?2",
+                params![expires_at, token],
+            )?;
+            token
+        }
+        None => {
+            tx.execute(
+                "DELETE FROM plugin_kv_grants WHERE plugin_id = ?1 AND session_id = ?2",
+                params![plugin_id, session_id],
+            )?;
+            let token = uuid::Uuid::new_v4().to_string();
+            tx.execute(
+                "INSERT INTO plugin_kv_grants (token, plugin_id, session_id, created_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![token, plugin_id, session_id, now.to_rfc3339(), expires_at],
+            )?;
+            token
+        }
+    };
+    tx.commit()?;
+    Ok(token)
+}
+
+/// Whether `token` grants `session_id` access to `plugin_id`'s KV namespace
+pub async fn plugin_kv_grant_valid(
+    pool: &DbPool,
+    token: &str,
+    plugin_id: &str,
+    session_id: &str,
+) -> Result<bool> {
+    let conn = pool.get().await?;
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM plugin_kv_grants
+         WHERE token = ?1 AND plugin_id = ?2 AND session_id = ?3 AND expires_at >= ?4",
+        params![
+            token,
+            plugin_id,
+            session_id,
+            chrono::Utc::now().to_rfc3339()
+        ],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Purge expired plugin KV entries
+pub async fn cleanup_expired_plugin_kv(pool: &DbPool) -> Result<()> {
+    let conn = pool.get().await?;
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "DELETE FROM plugin_kv WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+        params![now],
+    )?;
+    Ok(())
+}
+
 // ============ Plugin Event functions ============
 
 /// Log a plugin event
@@ -745,7 +2531,7 @@ pub async fn plugin_event_log(
     event_type: &str,
     details: Option<&str>,
 ) -> Result<i64> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     conn.execute(
         "INSERT INTO plugin_events (plugin_id, event_type, timestamp, details) VALUES (?1, ?2, ?3, ?4)",
         params![
@@ -759,13 +2545,12 @@ pub async fn plugin_event_log(
 }
 
 /// Get recent events for a plugin
-#[allow(dead_code)] // Used by plugins, not yet integrated (Phase 5+)
 pub async fn plugin_event_get_recent(
     pool: &DbPool,
     plugin_id: &str,
     limit: i32,
 ) -> Result<Vec<PluginEvent>> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
         "SELECT id, plugin_id, event_type, timestamp, details
          FROM plugin_events
@@ -791,9 +2576,8 @@ pub async fn plugin_event_get_recent(
 }
 
 /// Get all recent plugin events (for dashboard)
-#[allow(dead_code)] // Used by plugins, not yet integrated (Phase 5+)
 pub async fn plugin_event_get_all_recent(pool: &DbPool, limit: i32) -> Result<Vec<PluginEvent>> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let mut stmt = conn.prepare(
         "SELECT id, plugin_id, event_type, timestamp, details
          FROM plugin_events
@@ -820,7 +2604,7 @@ pub async fn plugin_event_get_all_recent(pool: &DbPool, limit: i32) -> Result<Ve
 /// Clean up old plugin events (keep last 7 days)
 #[allow(dead_code)] // Used by plugins, not yet integrated (Phase 5+)
 pub async fn cleanup_old_plugin_events(pool: &DbPool) -> Result<()> {
-    let conn = pool.lock().await;
+    let conn = pool.get().await?;
     let cutoff = (chrono::Utc::now() - chrono::Duration::days(7)).to_rfc3339();
     conn.execute(
         "DELETE FROM plugin_events WHERE timestamp < ?1",
@@ -828,3 +2612,1344 @@ pub async fn cleanup_old_plugin_events(pool: &DbPool) -> Result<()> {
     )?;
     Ok(())
 }
+
+// ============ Plugin Access functions ============
+
+/// Users and roles a plugin is restricted to
+pub async fn get_plugin_access(pool: &DbPool, plugin_id: &str) -> Result<PluginAccess> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT subject_type, subject FROM plugin_access WHERE plugin_id = ?1
+         ORDER BY subject_type, subject",
+    )?;
+    let rows = stmt.query_map(params![plugin_id], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    let mut access = PluginAccess::default();
+    for row in rows {
+        let (subject_type, subject) = row?;
+        add_access_subject(&mut access, &subject_type, subject)?;
+    }
+    Ok(access)
+}
+
+/// Access restrictions of every restricted plugin, by plugin ID
+pub async fn get_all_plugin_access(pool: &DbPool) -> Result<HashMap<String, PluginAccess>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT plugin_id, subject_type, subject FROM plugin_access
+         ORDER BY plugin_id, subject_type, subject",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+
+    let mut access: HashMap<String, PluginAccess> = HashMap::new();
+    for row in rows {
+        let (plugin_id, subject_type, subject) = row?;
+        add_access_subject(access.entry(plugin_id).or_default(), &subject_type, subject)?;
+    }
+    Ok(access)
+}
+
+fn add_access_subject(
+    access: &mut PluginAccess,
+    subject_type: &str,
+    subject: String,
+) -> Result<()> {
+    match subject_type {
+        "user" => access.users.push(subject),
+        "role" => access.roles.push(subject.parse()?),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replace the users and roles a plugin is restricted to
+pub async fn set_plugin_access(
+    pool: &DbPool,
+    plugin_id: &str,
+    access: &PluginAccess,
+) -> Result<()> {
+    let mut conn = pool.get().await?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM plugin_access WHERE plugin_id = ?1",
+        params![plugin_id],
+    )?;
+    for user in &access.users {
+        tx.execute(
+            "INSERT OR IGNORE INTO plugin_access (plugin_id, subject_type, subject) VALUES (?1, 'user', ?2)",
+            params![plugin_id, user],
+        )?;
+    }
+    for role in &access.roles {
+        tx.execute(
+            "INSERT OR IGNORE INTO plugin_access (plugin_id, subject_type, subject) VALUES (?1, 'role', ?2)",
+            params![plugin_id, role.to_string()],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+// ============ Plugin Trusted Key functions ============
+
+pub async fn get_trusted_keys(pool: &DbPool) -> Result<Vec<TrustedKey>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, public_key, created_at FROM plugin_trusted_keys ORDER BY created_at",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(TrustedKey {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            public_key: row.get(2)?,
+            created_at: row.get(3)?,
+        })
+    })?;
+
+    let mut keys = Vec::new();
+    for row in rows {
+        keys.push(row?);
+    }
+    Ok(keys)
+}
+
+pub async fn add_trusted_key(pool: &DbPool, key: &TrustedKey) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT INTO plugin_trusted_keys (id, name, public_key, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![key.id, key.name, key.public_key, key.created_at],
+    )?;
+    Ok(())
+}
+
+/// Remove a trusted key. Returns false if it did not exist.
+pub async fn delete_trusted_key(pool: &DbPool, id: &str) -> Result<bool> {
+    let conn = pool.get().await?;
+    let deleted = conn.execute("DELETE FROM plugin_trusted_keys WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+// ============ Package Update Check functions ============
+
+/// Replace the stored package update check with a new one
+///
+/// `result` is the check serialized as JSON.
+pub async fn save_package_update_check(
+    pool: &DbPool,
+    checked_at: &str,
+    result: &str,
+) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT OR REPLACE INTO package_update_checks (id, checked_at, result) VALUES (1, ?1, ?2)",
+        params![checked_at, result],
+    )?;
+    Ok(())
+}
+
+/// The stored package update check as JSON, if a check ever ran
+pub async fn get_package_update_check(pool: &DbPool) -> Result<Option<String>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare("SELECT result FROM package_update_checks WHERE id = 1")?;
+    let result = stmt.query_row([], |row| row.get(0)).ok();
+    Ok(result)
+}
+
+// ============ Webhook functions ============
+
+const WEBHOOK_COLUMNS: &str = "id, name, url, secret, events, enabled, created_at";
+
+fn webhook_from_row(row: &rusqlite::Row) -> rusqlite::Result<Webhook> {
+    let events: String = row.get(4)?;
+    Ok(Webhook {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        url: row.get(2)?,
+        secret: row.get(3)?,
+        events: serde_json::from_str(&events).unwrap_or_default(),
+        enabled: row.get::<_, i32>(5)? != 0,
+        created_at: row.get(6)?,
+    })
+}
+
+pub async fn get_webhooks(pool: &DbPool) -> Result<Vec<Webhook>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM webhooks ORDER BY created_at",
+        WEBHOOK_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], webhook_from_row)?;
+
+    let mut webhooks = Vec::new();
+    for row in rows {
+        webhooks.push(row?);
+    }
+    Ok(webhooks)
+}
+
+pub async fn get_webhook(pool: &DbPool, id: &str) -> Result<Option<Webhook>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM webhooks WHERE id = ?1",
+        WEBHOOK_COLUMNS
+    ))?;
+    let webhook = stmt.query_row(params![id], webhook_from_row).ok();
+    Ok(webhook)
+}
+
+pub async fn create_webhook(pool: &DbPool, webhook: &Webhook) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT INTO webhooks (id, name, url, secret, events, enabled, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            webhook.id,
+            webhook.name,
+            webhook.url,
+            webhook.secret,
+            serde_json::to_string(&webhook.events)?,
+            webhook.enabled as i32,
+            webhook.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Save a webhook's name, URL, secret, events and enabled flag. Returns false if it did not exist.
+pub async fn update_webhook(pool: &DbPool, webhook: &Webhook) -> Result<bool> {
+    let conn = pool.get().await?;
+    let changed = conn.execute(
+        "UPDATE webhooks SET name = ?1, url = ?2, secret = ?3, events = ?4, enabled = ?5
+         WHERE id = ?6",
+        params![
+            webhook.name,
+            webhook.url,
+            webhook.secret,
+            serde_json::to_string(&webhook.events)?,
+            webhook.enabled as i32,
+            webhook.id
+        ],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Remove a webhook and its delivery log. Returns false if it did not exist.
+pub async fn delete_webhook(pool: &DbPool, id: &str) -> Result<bool> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "DELETE FROM webhook_deliveries WHERE webhook_id = ?1",
+        params![id],
+    )?;
+    let deleted = conn.execute("DELETE FROM webhooks WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+const WEBHOOK_DELIVERY_COLUMNS: &str = "id, webhook_id, event, payload, status, attempts, \
+     response_status, error, next_attempt_at, created_at, updated_at";
+
+fn webhook_delivery_from_row(row: &rusqlite::Row) -> rusqlite::Result<WebhookDelivery> {
+    Ok(WebhookDelivery {
+        id: row.get(0)?,
+        webhook_id: row.get(1)?,
+        event: row.get(2)?,
+        payload: row.get(3)?,
+        status: row.get(4)?,
+        attempts: row.get(5)?,
+        response_status: row.get(6)?,
+        error: row.get(7)?,
+        next_attempt_at: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+pub async fn insert_webhook_delivery(pool: &DbPool, delivery: &WebhookDelivery) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        &format!(
+            "INSERT INTO webhook_deliveries ({})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            WEBHOOK_DELIVERY_COLUMNS
+        ),
+        params![
+            delivery.id,
+            delivery.webhook_id,
+            delivery.event,
+            delivery.payload,
+            delivery.status,
+            delivery.attempts,
+            delivery.response_status,
+            delivery.error,
+            delivery.next_attempt_at,
+            delivery.created_at,
+            delivery.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub async fn get_webhook_delivery(pool: &DbPool, id: &str) -> Result<Option<WebhookDelivery>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM webhook_deliveries WHERE id = ?1",
+        WEBHOOK_DELIVERY_COLUMNS
+    ))?;
+    let delivery = stmt.query_row(params![id], webhook_delivery_from_row).ok();
+    Ok(delivery)
+}
+
+/// Most recent deliveries, newest first, of one webhook or of all of them
+pub async fn get_webhook_deliveries(
+    pool: &DbPool,
+    webhook_id: Option<&str>,
+    limit: i32,
+) -> Result<Vec<WebhookDelivery>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM webhook_deliveries
+         WHERE ?1 IS NULL OR webhook_id = ?1
+         ORDER BY created_at DESC LIMIT ?2",
+        WEBHOOK_DELIVERY_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![webhook_id, limit], webhook_delivery_from_row)?;
+
+    let mut deliveries = Vec::new();
+    for row in rows {
+        deliveries.push(row?);
+    }
+    Ok(deliveries)
+}
+
+/// Claim pending deliveries due at or before `now`
+///
+/// Their next attempt is pushed to `lease_until`, so a delivery still in flight
+/// isn't claimed again; recording the attempt replaces it.
+pub async fn take_due_webhook_deliveries(
+    pool: &DbPool,
+    now: &str,
+    lease_until: &str,
+) -> Result<Vec<WebhookDelivery>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "UPDATE webhook_deliveries SET next_attempt_at = ?2
+         WHERE status = 'pending' AND next_attempt_at <= ?1
+         RETURNING {}",
+        WEBHOOK_DELIVERY_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![now, lease_until], webhook_delivery_from_row)?;
+
+    let mut deliveries = Vec::new();
+    for row in rows {
+        deliveries.push(row?);
+    }
+    deliveries.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(deliveries)
+}
+
+/// Record the outcome of a delivery attempt
+///
+/// `next_attempt_at` is set while the delivery is still pending a retry.
+pub async fn record_webhook_attempt(
+    pool: &DbPool,
+    id: &str,
+    status: &str,
+    response_status: Option<i64>,
+    error: Option<&str>,
+    next_attempt_at: Option<&str>,
+) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "UPDATE webhook_deliveries
+         SET status = ?1, attempts = attempts + 1, response_status = ?2, error = ?3,
+             next_attempt_at = ?4, updated_at = ?5
+         WHERE id = ?6",
+        params![
+            status,
+            response_status,
+            error,
+            next_attempt_at,
+            chrono::Utc::now().to_rfc3339(),
+            id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Queue a finished delivery to be sent again, with a fresh set of attempts
+///
+/// Returns the delivery, claimed until `lease_until` like `take_due_webhook_deliveries`
+/// does, or None if it does not exist or is already pending.
+pub async fn requeue_webhook_delivery(
+    pool: &DbPool,
+    id: &str,
+    lease_until: &str,
+) -> Result<Option<WebhookDelivery>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(&format!(
+        "UPDATE webhook_deliveries
+         SET status = 'pending', attempts = 0, next_attempt_at = ?2, updated_at = ?3
+         WHERE id = ?1 AND status != 'pending'
+         RETURNING {}",
+        WEBHOOK_DELIVERY_COLUMNS
+    ))?;
+    let delivery = stmt
+        .query_row(
+            params![id, lease_until, chrono::Utc::now().to_rfc3339()],
+            webhook_delivery_from_row,
+        )
+        .ok();
+    Ok(delivery)
+}
+
+/// Remove finished deliveries older than 30 days
+pub async fn cleanup_old_webhook_deliveries(pool: &DbPool) -> Result<()> {
+    let conn = pool.get().await?;
+    let cutoff = (chrono::Utc::now() - chrono::Duration::days(30)).to_rfc3339();
+    conn.execute(
+        "DELETE FROM webhook_deliveries WHERE status != 'pending' AND created_at < ?1",
+        params![cutoff],
+    )?;
+    Ok(())
+}
+
+// ============ File audit log functions ============
+
+pub async fn record_file_audit(pool: &DbPool, entry: &FileAuditEntry) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT INTO file_audit_log (username, action, root, path, success, details, timestamp)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            entry.username,
+            entry.action,
+            entry.root,
+            entry.path,
+            entry.success as i32,
+            entry.details,
+            entry.timestamp
+        ],
+    )?;
+    Ok(())
+}
+
+pub async fn get_file_audit_log(pool: &DbPool, limit: i32) -> Result<Vec<FileAuditEntry>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT id, username, action, root, path, success, details, timestamp
+         FROM file_audit_log ORDER BY id DESC LIMIT ?1",
+    )?;
+    let rows = stmt.query_map(params![limit], |row| {
+        Ok(FileAuditEntry {
+            id: row.get(0)?,
+            username: row.get(1)?,
+            action: row.get(2)?,
+            root: row.get(3)?,
+            path: row.get(4)?,
+            success: row.get::<_, i32>(5)? != 0,
+            details: row.get(6)?,
+            timestamp: row.get(7)?,
+        })
+    })?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        entries.push(row?);
+    }
+    Ok(entries)
+}
+
+/// Remove audit entries older than 90 days
+pub async fn cleanup_old_file_audit(pool: &DbPool) -> Result<()> {
+    let conn = pool.get().await?;
+    let cutoff = (chrono::Utc::now() - chrono::Duration::days(90)).to_rfc3339();
+    conn.execute(
+        "DELETE FROM file_audit_log WHERE timestamp < ?1",
+        params![cutoff],
+    )?;
+    Ok(())
+}
+
+// ============ Quick action audit log functions ============
+
+/// Record a confirmed run of `action` started by `username`
+pub async fn record_action_audit(
+    pool: &DbPool,
+    action: &QuickAction,
+    username: &str,
+    task_id: &str,
+) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute(
+        "INSERT INTO action_audit_log (action_id, action_name, danger_level, username, task_id, timestamp)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            action.id,
+            action.name,
+            action.danger_level.to_string(),
+            username,
+            task_id,
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+pub async fn get_action_audit_log(pool: &DbPool, limit: i32) -> Result<Vec<ActionAuditEntry>> {
+    let conn = pool.get().await?;
+    let mut stmt = conn.prepare(
+        "SELECT id, action_id, action_name, danger_level, username, task_id, timestamp
+         FROM action_audit_log ORDER BY id DESC LIMIT ?1",
+    )?;
+    let rows = stmt.query_map(params![limit], |row| {
+        Ok(ActionAuditEntry {
+            id: row.get(0)?,
+            action_id: row.get(1)?,
+            action_name: row.get(2)?,
+            danger_level: row.get::<_, String>(3)?.parse().unwrap_or_default(),
+            username: row.get(4)?,
+            task_id: row.get(5)?,
+            timestamp: row.get(6)?,
+        })
+    })?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        entries.push(row?);
+    }
+    Ok(entries)
+}
+
+/// Remove quick action audit entries older than 90 days
+pub async fn cleanup_old_action_audit(pool: &DbPool) -> Result<()> {
+    let conn = pool.get().await?;
+    let cutoff = (chrono::Utc::now() - chrono::Duration::days(90)).to_rfc3339();
+    conn.execute(
+        "DELETE FROM action_audit_log WHERE timestamp < ?1",
+        params![cutoff],
+    )?;
+    Ok(())
+}
+
+// ============ Configuration import ============
+
+/// Configuration written by `import_config`
+pub struct ConfigImport<'a> {
+    pub settings: &'a [Setting],
+    pub quick_actions: &'a [QuickAction],
+    pub pipelines: &'a [Pipeline],
+    pub scheduled_tasks: &'a [ScheduledTask],
+    /// Users matched by username, each with whether to replace the password of
+    /// an existing user with the one given
+    pub users: &'a [(User, bool)],
+}
+
+/// Write imported configuration in one transaction
+///
+/// Settings, quick actions, pipelines and scheduled tasks replace the ones with
+/// the same key or ID; existing users keep their ID.
+pub async fn import_config(pool: &DbPool, config: &ConfigImport<'_>) -> Result<()> {
+    let mut conn = pool.get().await?;
+    let tx = conn.transaction()?;
+    for setting in config.settings {
+        tx.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            params![setting.key, setting.value],
+        )?;
+    }
+    for action in config.quick_actions {
+        tx.execute(
+            "INSERT OR REPLACE INTO quick_actions (id, name, script_path, icon, display_order,
+                                                   cpu_limit_secs, memory_limit_mb, nofile_limit, concurrency, env,
+                                                   params, visibility, allowed_users, danger_level,
+                                                   requires_confirmation)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            params![
+                action.id,
+                action.name,
+                action.script_path,
+                action.icon,
+                action.display_order,
+                action.cpu_limit_secs,
+                action.memory_limit_mb,
+                action.nofile_limit,
+                action.concurrency.to_string(),
+                serde_json::to_string(&action.env)?,
+                serde_json::to_string(&action.params)?,
+                action.visibility.to_string(),
+                serde_json::to_string(&action.allowed_users)?,
+                action.danger_level.to_string(),
+                action.requires_confirmation as i32
+            ],
+        )?;
+    }
+    for pipeline in config.pipelines {
+        tx.execute(
+            "INSERT OR REPLACE INTO pipelines (id, name, description, steps, env, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                pipeline.id,
+                pipeline.name,
+                pipeline.description,
+                serde_json::to_string(&pipeline.steps)?,
+                serde_json::to_string(&pipeline.env)?,
+                pipeline.created_at
+            ],
+        )?;
+    }
+    for task in config.scheduled_tasks {
+        tx.execute(
+            "INSERT OR REPLACE INTO scheduled_tasks (id, script_name, run_at, status, task_id, error, created_by, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                task.id,
+                task.script_name,
+                task.run_at,
+                task.status,
+                task.task_id,
+                task.error,
+                task.created_by,
+                task.created_at
+            ],
+        )?;
+    }
+    for (user, replace_password) in config.users {
+        tx.execute(
+            "INSERT INTO users (id, username, password_hash, display_name, role, is_active, created_at,
+                                expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?9)
+             ON CONFLICT(username) DO UPDATE SET
+                 display_name = excluded.display_name,
+                 role = excluded.role,
+                 is_active = excluded.is_active,
+                 expires_at = excluded.expires_at,
+                 password_hash = CASE WHEN ?8 THEN excluded.password_hash ELSE users.password_hash END",
+            params![
+                user.id,
+                user.username,
+                user.password_hash,
+                user.display_name,
+                user.role.to_string(),
+                user.is_active as i32,
+                user.created_at,
+                replace_password,
+                user.expires_at
+            ],
+        )?;
+        if *replace_password || !user.is_active || user.is_expired() {
+            tx.execute(
+                "DELETE FROM sessions WHERE user_id = (SELECT id FROM users WHERE username = ?1)",
+                params![user.username],
+            )?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+// ============ Backup functions ============
+
+/// Write a consistent copy of the database to `path`, which must not exist
+pub async fn vacuum_into(pool: &DbPool, path: &Path) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+    Ok(())
+}
+
+/// Replace the contents of the database with the database at `path`
+///
+/// The copy goes through SQLite's online backup, so other connections of the
+/// pool see either the old or the new contents.
+pub async fn restore_from(pool: &DbPool, path: &Path) -> Result<()> {
+    // Not read-only: checking the search index's FTS5 table writes to it
+    let source = Connection::open(path)?;
+    let integrity: String = source.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(anyhow::anyhow!("Backup database is damaged: {}", integrity));
+    }
+
+    let mut conn = pool.get().await?;
+    rusqlite::backup::Backup::new(&source, &mut conn)?.run_to_completion(
+        1024,
+        Duration::ZERO,
+        None,
+    )?;
+    create_schema(&conn)?;
+    rebuild_search_index(&conn)
+}
+
+// ============ Maintenance functions ============
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableStats {
+    pub name: String,
+    pub rows: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DbStats {
+    pub file_size_bytes: u64,
+    /// Size of the write-ahead log not yet checkpointed into the file
+    pub wal_size_bytes: u64,
+    pub page_size: u64,
+    pub page_count: u64,
+    /// Unused pages inside the file
+    pub free_pages: u64,
+    /// Share of the file taken by unused pages, in percent
+    pub fragmentation_percent: f64,
+    /// `none`, `full` or `incremental`
+    pub auto_vacuum: String,
+    pub journal_mode: String,
+    pub tables: Vec<TableStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceReport {
+    pub integrity_ok: bool,
+    /// Problems found by the integrity check (at most 100)
+    pub integrity_errors: Vec<String>,
+    /// Unused pages given back to the file system
+    pub freed_pages: u64,
+    /// The file was rebuilt with VACUUM to turn on incremental vacuum
+    pub full_vacuum: bool,
+    pub duration_ms: u64,
+}
+
+fn pragma_u64(conn: &Connection, pragma: &str) -> Result<u64> {
+    let value: i64 = conn.pragma_query_value(None, pragma, |row| row.get(0))?;
+    Ok(value.max(0) as u64)
+}
+
+pub async fn get_db_stats(pool: &DbPool) -> Result<DbStats> {
+    let conn = pool.get().await?;
+    let page_size = pragma_u64(&conn, "page_size")?;
+    let page_count = pragma_u64(&conn, "page_count")?;
+    let free_pages = pragma_u64(&conn, "freelist_count")?;
+    let auto_vacuum = match pragma_u64(&conn, "auto_vacuum")? {
+        1 => "full",
+        2 => "incremental",
+        _ => "none",
+    };
+    let journal_mode: String = conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+
+    let file: String = conn.query_row(
+        "SELECT file FROM pragma_database_list WHERE name = 'main'",
+        [],
+        |row| row.get(0),
+    )?;
+    let file_size = |path: &str| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+
+    // Plain tables only: virtual tables and their shadow tables are left out
+    let names: Vec<String> = conn
+        .prepare(
+            "SELECT name FROM pragma_table_list
+             WHERE schema = 'main' AND type = 'table' AND name NOT LIKE 'sqlite_%'
+             ORDER BY name",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut tables = Vec::with_capacity(names.len());
+    for name in names {
+        let rows: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\"")),
+            [],
+            |row| row.get(0),
+        )?;
+        tables.push(TableStats {
+            name,
+            rows: rows as u64,
+        });
+    }
+
+    Ok(DbStats {
+        file_size_bytes: file_size(&file),
+        wal_size_bytes: file_size(&format!("{}-wal", file)),
+        page_size,
+        page_count,
+        free_pages,
+        fragmentation_percent: if page_count > 0 {
+            free_pages as f64 / page_count as f64 * 100.0
+        } else {
+            0.0
+        },
+        auto_vacuum: auto_vacuum.to_string(),
+        journal_mode,
+        tables,
+    })
+}
+
+/// Check the database, refresh the query planner statistics and give unused
+/// pages back to the file system
+///
+/// A database created before incremental vacuum was turned on is rebuilt with a
+/// full VACUUM once, which blocks writers while it runs. Nothing is changed if
+/// the integrity check finds problems.
+pub async fn run_maintenance(pool: &DbPool) -> Result<MaintenanceReport> {
+    let started = std::time::Instant::now();
+    let conn = pool.get().await?;
+
+    let integrity_errors: Vec<String> = conn
+        .prepare("PRAGMA integrity_check(100)")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|line| line != "ok")
+        .collect();
+    if !integrity_errors.is_empty() {
+        return Ok(MaintenanceReport {
+            integrity_ok: false,
+            integrity_errors,
+            freed_pages: 0,
+            full_vacuum: false,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    conn.execute_batch("ANALYZE")?;
+
+    let free_before = pragma_u64(&conn, "freelist_count")?;
+    let full_vacuum = pragma_u64(&conn, "auto_vacuum")? != 2;
+    if full_vacuum {
+        // Changing auto_vacuum on an existing database only takes effect with VACUUM
+        conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")?;
+        rebuild_search_index(&conn)?;
+    } else {
+        conn.execute_batch("PRAGMA incremental_vacuum")?;
+    }
+    let free_after = pragma_u64(&conn, "freelist_count")?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+
+    Ok(MaintenanceReport {
+        integrity_ok: true,
+        integrity_errors,
+        freed_pages: free_before.saturating_sub(free_after),
+        full_vacuum,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Write the write-ahead log into the database file and truncate it, for a clean exit
+pub async fn checkpoint(pool: &DbPool) -> Result<()> {
+    let conn = pool.get().await?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(fts_query("  "), None);
+        assert_eq!(
+            fts_query("error: exit-code"),
+            Some(r#""error:" "exit-code""#.to_string())
+        );
+        assert_eq!(
+            fts_query(r#"say "hi""#),
+            Some(r#""say" """hi""""#.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_with_search_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+        set_setting(&pool, "theme", "dark").await.unwrap();
+
+        let copy = dir.path().join("copy.db");
+        vacuum_into(&pool, &copy).await.unwrap();
+        set_setting(&pool, "theme", "light").await.unwrap();
+
+        restore_from(&pool, &copy).await.unwrap();
+        assert_eq!(
+            get_setting(&pool, "theme").await.unwrap().as_deref(),
+            Some("dark")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_task_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+
+        for (id, script, output) in [
+            (
+                "t1",
+                "deploy.sh",
+                "Pulling image\nERROR: disk quota exceeded",
+            ),
+            ("t2", "backup.sh", "Done"),
+        ] {
+            let task = TaskHistory {
+                id: id.to_string(),
+                script_name: script.to_string(),
+                started_at: "2024-01-01T00:00:00Z".to_string(),
+                finished_at: None,
+                exit_code: None,
+                output: None,
+            };
+            insert_task_history(&pool, &task).await.unwrap();
+            update_task_history(&pool, id, "2024-01-01T00:01:00Z", 1, Some(output))
+                .await
+                .unwrap();
+        }
+
+        let hits = search_task_history(&pool, "quota exceeded", 10)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "t1");
+        assert!(hits[0].snippet.contains("«quota»"));
+        assert_eq!(
+            search_task_history(&pool, "backup", 10).await.unwrap()[0].id,
+            "t2"
+        );
+
+        // Updated output replaces the indexed one
+        update_task_history(&pool, "t1", "2024-01-01T00:01:00Z", 0, Some("ok"))
+            .await
+            .unwrap();
+        assert!(search_task_history(&pool, "quota", 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fail_unfinished_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+
+        for id in ["done", "orphan"] {
+            let task = TaskHistory {
+                id: id.to_string(),
+                script_name: "deploy.sh".to_string(),
+                started_at: "2024-01-01T00:00:00Z".to_string(),
+                finished_at: None,
+                exit_code: None,
+                output: None,
+            };
+            insert_task_history(&pool, &task).await.unwrap();
+        }
+        update_task_history(&pool, "done", "2024-01-01T00:01:00Z", 0, None)
+            .await
+            .unwrap();
+        record_pipeline_step(&pool, "orphan", 0, "build", "running", None)
+            .await
+            .unwrap();
+
+        assert_eq!(fail_unfinished_tasks(&pool).await.unwrap(), 1);
+        let history = get_task_history(&pool, 10).await.unwrap();
+        let exit_code = |id: &str| history.iter().find(|t| t.id == id).unwrap().exit_code;
+        assert_eq!(exit_code("done"), Some(0));
+        assert_eq!(exit_code("orphan"), Some(-1));
+        let steps = get_pipeline_step_runs(&pool, "orphan").await.unwrap();
+        assert_eq!(steps[0].status, "cancelled");
+        assert_eq!(fail_unfinished_tasks(&pool).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_update_quick_action() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+
+        let mut action = QuickAction {
+            id: "a1".to_string(),
+            name: "Deploy".to_string(),
+            script_path: "deploy.sh".to_string(),
+            icon: None,
+            display_order: 0,
+            cpu_limit_secs: None,
+            memory_limit_mb: None,
+            nofile_limit: None,
+            concurrency: ConcurrencyMode::Reject,
+            env: HashMap::new(),
+            params: Vec::new(),
+            visibility: Visibility::Users,
+            allowed_users: vec!["u1".to_string()],
+            danger_level: DangerLevel::High,
+            requires_confirmation: false,
+        };
+        create_quick_action(&pool, &action).await.unwrap();
+
+        action.name = "Deploy to staging".to_string();
+        action.env = HashMap::from([("TARGET".to_string(), "staging".to_string())]);
+        assert!(update_quick_action(&pool, &action).await.unwrap());
+        let saved = get_quick_action(&pool, "a1").await.unwrap().unwrap();
+        assert_eq!(saved.name, "Deploy to staging");
+        assert_eq!(saved.env["TARGET"], "staging");
+        assert_eq!(saved.visibility, Visibility::Users);
+        assert!(saved.visible_to(UserRole::Client, Some("u1")));
+        assert!(!saved.visible_to(UserRole::Client, Some("u2")));
+        assert!(!saved.visible_to(UserRole::Client, None));
+        assert!(saved.visible_to(UserRole::Admin, None));
+        assert_eq!(saved.danger_level, DangerLevel::High);
+        assert!(saved.needs_confirmation());
+        assert!(!saved.is_confirmed_by(None));
+        assert!(!saved.is_confirmed_by(Some("deploy")));
+        assert!(saved.is_confirmed_by(Some("Deploy to staging")));
+
+        action.id = "missing".to_string();
+        assert!(!update_quick_action(&pool, &action).await.unwrap());
+        assert!(get_quick_action(&pool, "missing").await.unwrap().is_none());
+
+        for id in ["a2", "a3"] {
+            action.id = id.to_string();
+            create_quick_action(&pool, &action).await.unwrap();
+        }
+        reorder_quick_actions(&pool, &["a3".to_string(), "a1".to_string()])
+            .await
+            .unwrap();
+        let order: Vec<(String, i32)> = get_quick_actions(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.id, a.display_order))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("a3".to_string(), 0),
+                ("a1".to_string(), 1),
+                ("a2".to_string(), 2)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+
+        let mut user = User {
+            id: "u1".to_string(),
+            username: "contractor".to_string(),
+            password_hash: String::new(),
+            display_name: None,
+            role: UserRole::Client,
+            is_active: true,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::days(1)).to_rfc3339()),
+            session_hours: None,
+            idle_timeout_minutes: None,
+        };
+        assert!(!user.is_expired());
+        create_user(&pool, &user).await.unwrap();
+        let saved = get_user_by_username(&pool, "contractor")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.expires_at, user.expires_at);
+
+        let past = (chrono::Utc::now() - chrono::Duration::minutes(1)).to_rfc3339();
+        update_user(&pool, "u1", None, true, Some(&past))
+            .await
+            .unwrap();
+        assert!(get_user_by_id(&pool, "u1")
+            .await
+            .unwrap()
+            .unwrap()
+            .is_expired());
+
+        user.expires_at = None;
+        assert!(!user.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_plugin_access_by_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+
+        let notes = PluginAccess {
+            users: vec!["u1".to_string()],
+            roles: vec![UserRole::Client],
+        };
+        let other = PluginAccess {
+            users: vec!["u2".to_string()],
+            roles: vec![],
+        };
+        set_plugin_access(&pool, "notes", &notes).await.unwrap();
+        set_plugin_access(&pool, "other", &other).await.unwrap();
+
+        assert_eq!(get_plugin_access(&pool, "notes").await.unwrap(), notes);
+        assert_eq!(get_plugin_access(&pool, "other").await.unwrap(), other);
+        assert_eq!(
+            get_plugin_access(&pool, "missing").await.unwrap(),
+            PluginAccess::default()
+        );
+        assert_eq!(get_all_plugin_access(&pool).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_kv_grants() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+        let ttl = chrono::Duration::minutes(15);
+
+        let token = plugin_kv_grant_create(&pool, "notes", "s1", ttl)
+            .await
+            .unwrap();
+        assert!(plugin_kv_grant_valid(&pool, &token, "notes", "s1")
+            .await
+            .unwrap());
+        assert!(!plugin_kv_grant_valid(&pool, &token, "notes", "s2")
+            .await
+            .unwrap());
+        assert!(!plugin_kv_grant_valid(&pool, &token, "other", "s1")
+            .await
+            .unwrap());
+
+        // Loading the page again keeps the grant instead of adding one
+        let again = plugin_kv_grant_create(&pool, "notes", "s1", ttl)
+            .await
+            .unwrap();
+        assert_eq!(again, token);
+        let rows: i64 = pool
+            .get()
+            .await
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM plugin_kv_grants", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(rows, 1);
+
+        let expired = plugin_kv_grant_create(&pool, "notes", "s1", -ttl)
+            .await
+            .unwrap();
+        assert!(!plugin_kv_grant_valid(&pool, &expired, "notes", "s1")
+            .await
+            .unwrap());
+        let fresh = plugin_kv_grant_create(&pool, "notes", "s1", ttl)
+            .await
+            .unwrap();
+        assert_ne!(fresh, token);
+    }
+
+    #[tokio::test]
+    async fn test_user_preferences() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+
+        let set = |entries: serde_json::Value| {
+            serde_json::from_value::<BTreeMap<String, serde_json::Value>>(entries).unwrap()
+        };
+        set_user_preferences(
+            &pool,
+            "u1",
+            &set(serde_json::json!({ "theme": "dark", "layout": { "cards": ["cpu", "disk"] } })),
+        )
+        .await
+        .unwrap();
+        set_user_preferences(&pool, "u2", &set(serde_json::json!({ "theme": "light" })))
+            .await
+            .unwrap();
+        set_user_preferences(
+            &pool,
+            "u1",
+            &set(serde_json::json!({ "theme": null, "locale": "pl" })),
+        )
+        .await
+        .unwrap();
+
+        let preferences = get_user_preferences(&pool, "u1").await.unwrap();
+        assert_eq!(
+            preferences,
+            set(serde_json::json!({ "layout": { "cards": ["cpu", "disk"] }, "locale": "pl" }))
+        );
+        assert_eq!(
+            get_user_preferences(&pool, "u2").await.unwrap()["theme"],
+            "light"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quick_action_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+        pool.get()
+            .await
+            .unwrap()
+            .execute(
+                "INSERT INTO quick_actions (id, name, script_path) VALUES ('a1', 'Deploy', 'deploy.sh')",
+                [],
+            )
+            .unwrap();
+
+        for (id, started_at, finished_at, exit_code) in [
+            (
+                "t1",
+                "2025-01-01T00:00:00+00:00",
+                Some("2025-01-01T00:00:10+00:00"),
+                Some(1),
+            ),
+            (
+                "t2",
+                "2025-01-02T00:00:00+00:00",
+                Some("2025-01-02T00:00:20+00:00"),
+                Some(0),
+            ),
+            (
+                "t3",
+                "2025-01-03T00:00:00+00:00",
+                Some("2025-01-03T00:00:30+00:00"),
+                Some(0),
+            ),
+            ("t4", "2025-01-04T00:00:00+00:00", None, None),
+        ] {
+            let task = TaskHistory {
+                id: id.to_string(),
+                script_name: "deploy.sh".to_string(),
+                started_at: started_at.to_string(),
+                finished_at: finished_at.map(str::to_string),
+                exit_code,
+                output: None,
+            };
+            insert_task_history(&pool, &task).await.unwrap();
+        }
+
+        let stats = get_quick_action_stats(&pool, 3).await.unwrap();
+        assert_eq!(
+            stats["deploy.sh"],
+            QuickActionStats {
+                runs: 3,
+                last_run_at: Some("2025-01-04T00:00:00+00:00".to_string()),
+                last_exit_code: None,
+                avg_duration_secs: Some(25.0),
+                success_rate: Some(1.0),
+            }
+        );
+        let stats = get_quick_action_stats(&pool, 10).await.unwrap();
+        assert_eq!(stats["deploy.sh"].success_rate, Some(2.0 / 3.0));
+    }
+
+    #[tokio::test]
+    async fn test_maintenance() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+        for i in 0..200 {
+            set_setting(&pool, &format!("key{}", i), &"x".repeat(1000))
+                .await
+                .unwrap();
+        }
+        pool.get()
+            .await
+            .unwrap()
+            .execute("DELETE FROM settings WHERE key LIKE 'key%'", [])
+            .unwrap();
+
+        let stats = get_db_stats(&pool).await.unwrap();
+        assert!(stats.free_pages > 0);
+        assert!(stats.tables.iter().any(|t| t.name == "task_history"));
+        assert!(!stats
+            .tables
+            .iter()
+            .any(|t| t.name.starts_with("task_history_fts")));
+
+        // Turns on incremental vacuum first, then vacuums incrementally
+        let report = run_maintenance(&pool).await.unwrap();
+        assert!(report.integrity_ok && report.full_vacuum);
+        assert_eq!(
+            get_db_stats(&pool).await.unwrap().auto_vacuum,
+            "incremental"
+        );
+        assert_eq!(get_db_stats(&pool).await.unwrap().free_pages, 0);
+        assert!(!run_maintenance(&pool).await.unwrap().full_vacuum);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_delivery_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        create_schema(&pool.get().await.unwrap()).unwrap();
+
+        let webhook = Webhook {
+            id: "w1".to_string(),
+            name: "Ops".to_string(),
+            url: "https://example.com/hook".to_string(),
+            secret: "secret".to_string(),
+            events: vec!["task.*".to_string()],
+            enabled: true,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+        };
+        create_webhook(&pool, &webhook).await.unwrap();
+        assert_eq!(
+            get_webhook(&pool, "w1").await.unwrap().unwrap().events,
+            webhook.events
+        );
+
+        let delivery = WebhookDelivery {
+            id: "d1".to_string(),
+            webhook_id: "w1".to_string(),
+            event: "task.failed".to_string(),
+            payload: "{}".to_string(),
+            status: "pending".to_string(),
+            attempts: 0,
+            response_status: None,
+            error: None,
+            next_attempt_at: Some("2024-01-01T00:00:10Z".to_string()),
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+        };
+        insert_webhook_delivery(&pool, &delivery).await.unwrap();
+
+        // Not due yet, then claimed once
+        let take = |now: &'static str| {
+            let pool = pool.clone();
+            async move {
+                take_due_webhook_deliveries(&pool, now, "2024-01-01T00:01:10Z")
+                    .await
+                    .unwrap()
+            }
+        };
+        assert!(take("2024-01-01T00:00:05Z").await.is_empty());
+        assert_eq!(take("2024-01-01T00:00:10Z").await.len(), 1);
+        assert!(take("2024-01-01T00:00:20Z").await.is_empty());
+
+        // A pending delivery can't be requeued; a failed one starts over
+        assert!(
+            requeue_webhook_delivery(&pool, "d1", "2024-01-01T00:02:00Z")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        record_webhook_attempt(&pool, "d1", "failed", Some(500), Some("HTTP 500"), None)
+            .await
+            .unwrap();
+        let failed = get_webhook_delivery(&pool, "d1").await.unwrap().unwrap();
+        assert_eq!((failed.attempts, failed.response_status), (1, Some(500)));
+        let requeued = requeue_webhook_delivery(&pool, "d1", "2024-01-01T00:02:00Z")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (requeued.status.as_str(), requeued.attempts),
+            ("pending", 0)
+        );
+
+        assert!(delete_webhook(&pool, "w1").await.unwrap());
+        assert!(get_webhook_deliveries(&pool, None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 // Library exports for testing
 // This allows integration tests to import modules from the main crate
 
+pub mod config;
 pub mod db;
 pub mod services;
 
@@ -1,9 +1,13 @@
+mod config;
 mod db;
 mod routes;
 mod services;
 
+use anyhow::Context;
 use axum::{
-    http::{header, StatusCode, Uri},
+    extract::State,
+    http::{header, HeaderName, StatusCode, Uri},
+    middleware,
     response::IntoResponse,
     routing::get,
     Router,
@@ -15,13 +19,22 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use sysinfo::System;
 use tokio::sync::Mutex;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tower_http::{
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 
 use crate::db::init_db;
 use crate::routes::api::AppState;
 use crate::routes::{
-    create_api_router, create_auth_router, create_plugin_router, handle_websocket,
+    create_admin_router, create_api_router, create_auth_router, create_docker_router,
+    create_files_router, create_health_router, create_metrics_router, create_pipeline_router,
+    create_plugin_router, handle_websocket,
 };
+use crate::services::event_bus::BROWSER_EVENT_BUFFER;
 
 #[derive(RustEmbed)]
 #[folder = "frontend/dist"]
@@ -34,7 +47,9 @@ async fn main() -> anyhow::Result<()> {
 
     // Parse CLI arguments
     let args: Vec<String> = env::args().collect();
-    let (cli_port, cli_host) = parse_args(&args);
+    let cli = parse_args(&args);
+    // Resolved now: once a self-update replaced the file, it names the old one
+    let exe = env::current_exe();
 
     // Show help if requested
     if args.iter().any(|a| a == "--help" || a == "-h") {
@@ -42,53 +57,161 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
+    if args.get(1).map(String::as_str) == Some("install-service") {
+        #[cfg(unix)]
+        return crate::services::systemd::install(&args[2..]).await;
+        #[cfg(not(unix))]
+        anyhow::bail!(
+            "install-service sets up a systemd service, which this platform doesn't have"
+        );
+    }
+
     // Initialize tracing with default level INFO, can be overridden with RUST_LOG env var
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-                tracing_subscriber::EnvFilter::new("steering_center=info,tower_http=debug")
-            }),
-        )
+    // and changed at runtime; the output is also shipped to syslog or Loki once configured
+    tracing_subscriber::registry()
+        .with(crate::services::log_levels::filter_layer())
+        .with(tracing_subscriber::fmt::layer())
+        .with(crate::services::log_shipping::ShippingLayer)
         .init();
 
-    // Check for Secure Cookie capability
-    let is_prod = env::var("PRODUCTION")
-        .map(|v| v.to_lowercase() == "true" || v == "1")
-        .unwrap_or(false);
-    let force_secure = env::var("SECURE_COOKIES")
-        .map(|v| v.to_lowercase() == "true" || v == "1")
-        .unwrap_or(false);
-
-    if !is_prod && !force_secure {
-        tracing::warn!("Running without PRODUCTION/SECURE_COOKIES=true - Cookies will NOT be marked Secure (OK for localhost)");
-    } else {
-        tracing::info!("Secure cookies ENABLED");
+    // Defaults < config file < environment < CLI
+    let config_path = cli
+        .config
+        .clone()
+        .or_else(|| env::var(crate::config::CONFIG_ENV).ok())
+        .map(PathBuf::from);
+    let mut config = crate::config::Config::load(config_path.as_deref())?;
+    cli.apply(&mut config)?;
+    let config = crate::config::init(config);
+    if let Some(path) = &config_path {
+        tracing::info!("Loaded configuration from {}", path.display());
+    }
+
+    if args.get(1).map(String::as_str) == Some("self-update") {
+        let binary = exe.context("Failed to locate the steering-center binary")?;
+        return run_self_update(&config, &binary, args.iter().any(|a| a == "--check")).await;
     }
 
     // Initialize database
     let db = init_db()?;
     tracing::info!("Database initialized");
 
+    // Log levels saved by an admin take over from RUST_LOG
+    let plugin_log_levels = crate::services::log_levels::apply_saved(&db).await;
+
     // Get or create instance ID
     let instance_id = crate::db::get_or_create_instance_id(&db).await?;
     tracing::info!("Instance ID: {}", instance_id);
 
+    // Forward logs to syslog or Loki when log shipping is configured
+    crate::services::log_shipping::spawn_shipper(db.clone());
+
+    // Send events to registered webhooks, retrying failed deliveries; started
+    // early so plugin crashes during startup are sent too
+    crate::services::webhooks::spawn_dispatcher(db.clone());
+
+    let host = config.server.host;
+    let port = config.server.port;
+    let addr = SocketAddr::from((host, port));
+
+    // Terminate TLS ourselves when a certificate and key are configured, or get
+    // the certificate from an ACME CA when a domain is
+    let acme = crate::services::acme::resolve_options(&db, &config).await?;
+    let acme_challenges = crate::services::acme::Challenges::default();
+    let tls = match &acme {
+        Some(acme) => {
+            let redirect_port =
+                crate::services::tls::resolve_redirect_port(&db, config.tls.redirect_port)
+                    .await?
+                    .unwrap_or(crate::services::acme::HTTP_CHALLENGE_PORT);
+            Some(crate::services::tls::TlsOptions {
+                cert: acme.cert_path(),
+                key: acme.key_path(),
+                redirect_port: Some(redirect_port),
+            })
+        }
+        None => crate::services::tls::resolve_options(&db, &config.tls).await?,
+    };
+
+    // Start the HTTP listener first, it answers the ACME challenges
+    if let Some(redirect_port) = tls.as_ref().and_then(|t| t.redirect_port) {
+        let routes = if acme.is_some() {
+            crate::services::acme::challenge_router(acme_challenges.clone())
+        } else {
+            Router::new()
+        };
+        crate::services::tls::spawn_http_redirect(
+            SocketAddr::from((host, redirect_port)),
+            port,
+            routes,
+        );
+    }
+    if let Some(acme) = &acme {
+        if let Err(e) = crate::services::acme::ensure_certificate(acme, &acme_challenges).await {
+            // A certificate still valid for a while can be served until the next renewal
+            if !acme.cert_path().exists() {
+                return Err(e.context(format!(
+                    "Failed to obtain a certificate for {}",
+                    acme.domain
+                )));
+            }
+            tracing::warn!("Failed to renew certificate for {}: {:#}", acme.domain, e);
+        }
+    }
+
+    let tls_config = match &tls {
+        Some(options) => Some(crate::services::tls::load_config(options).await?),
+        None => None,
+    };
+    if let (Some(acme), Some(config)) = (acme, &tls_config) {
+        crate::services::acme::spawn_renewal(acme, acme_challenges, config.clone());
+    }
+
+    // Check for Secure Cookie capability
+    if tls.is_some() {
+        tracing::info!("Secure cookies ENABLED (serving HTTPS)");
+    } else if !config.secure_cookies() {
+        tracing::warn!("Running without PRODUCTION/SECURE_COOKIES=true - Cookies will NOT be marked Secure (OK for localhost)");
+    } else {
+        tracing::info!("Secure cookies ENABLED");
+    }
+
     // Initialize plugin supervisor
-    let log_dir = env::var("TORU_LOG_DIR")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from("./logs"));
+    let log_dir = config.paths.log_dir.clone();
+    // Initialize system monitor, and the sampler whose snapshots are also sent to
+    // plugins asking for system information
+    let sys = Arc::new(Mutex::new(System::new_all()));
+    let resources =
+        crate::services::system::spawn_resource_sampler(db.clone(), Arc::clone(&sys)).await;
+
     let supervisor = match crate::services::plugins::PluginSupervisor::new(
-        "./plugins",
+        &config.paths.plugins_dir,
         10, // max 10 consecutive restarts before disabling
         instance_id.clone(),
-        log_dir,
+        log_dir.clone(),
         db.clone(),
     ) {
         Ok(s) => {
-            let sup = Arc::new(Mutex::new(s));
+            // Plugins run on this machine; an unspecified bind address is reached over loopback
+            let api_host = if host.is_unspecified() {
+                std::net::Ipv4Addr::LOCALHOST
+            } else {
+                host
+            };
+            let scheme = if tls.is_some() { "https" } else { "http" };
+            let api_url = format!(
+                "{}://{}{}/api",
+                scheme,
+                SocketAddr::from((api_host, port)),
+                config.server.base_path
+            );
+            let sup = Arc::new(Mutex::new(
+                s.with_api_url(api_url).with_resources(resources.clone()),
+            ));
             // Initialize and start plugin supervision
             {
                 let mut guard = sup.lock().await;
+                guard.plugin_logger().set_min_levels(plugin_log_levels);
                 match guard.initialize().await {
                     Ok(initialized) => {
                         tracing::info!(
@@ -101,6 +224,17 @@ async fn main() -> anyhow::Result<()> {
                     }
                 }
             }
+            // Restart crashed plugins in the background
+            crate::services::plugins::spawn_health_monitor(sup.clone());
+            // Fan out plugin events and relay calls between plugins
+            crate::services::plugins::spawn_plugin_message_handler(sup.clone());
+            // Trigger the jobs plugins registered
+            crate::services::plugins::spawn_job_scheduler(sup.clone());
+            // Swap in replaced plugin binaries (for plugin development)
+            if config.plugins.hot_reload {
+                tracing::info!("Plugin hot reload enabled");
+                crate::services::plugins::spawn_hot_reload_watcher(sup.clone());
+            }
             Some(sup)
         }
         Err(e) => {
@@ -119,16 +253,37 @@ async fn main() -> anyhow::Result<()> {
     if let Err(e) = crate::db::cleanup_old_plugin_events(&db).await {
         tracing::warn!("Failed to cleanup old plugin events: {}", e);
     }
+    if let Err(e) = crate::db::cleanup_expired_plugin_kv(&db).await {
+        tracing::warn!("Failed to cleanup expired plugin KV entries: {}", e);
+    }
     tracing::info!("Session cleanup completed");
 
-    // Initialize system monitor
-    let sys = Arc::new(Mutex::new(System::new_all()));
+    // Tasks still marked running were cut off when the server last stopped
+    match crate::db::fail_unfinished_tasks(&db).await {
+        Ok(0) => {}
+        Ok(count) => tracing::warn!(
+            "Marked {} tasks interrupted by the last exit as failed",
+            count
+        ),
+        Err(e) => tracing::warn!("Failed to mark interrupted tasks: {}", e),
+    }
 
     // Create app state
+    let (power_alerts, _) = tokio::sync::broadcast::channel(BROWSER_EVENT_BUFFER);
+    let notification_hub =
+        Arc::new(crate::services::system_notifications::NotificationHub::default());
+    crate::services::system_notifications::install(notification_hub.clone());
     let state = AppState {
         db: db.clone(),
         sys,
+        resources,
         supervisor,
+        metrics: Default::default(),
+        power_alerts: power_alerts.clone(),
+        config: config.clone(),
+        task_output: Default::default(),
+        tasks: crate::services::executor::create_task_registry(),
+        notification_hub,
     };
 
     // Spawn background task to clean up expired sessions daily
@@ -151,88 +306,281 @@ async fn main() -> anyhow::Result<()> {
             if let Err(e) = crate::db::cleanup_old_plugin_events(&db_cleanup).await {
                 tracing::warn!("Failed to cleanup old plugin events: {}", e);
             }
+            if let Err(e) = crate::db::cleanup_old_webhook_deliveries(&db_cleanup).await {
+                tracing::warn!("Failed to cleanup old webhook deliveries: {}", e);
+            }
+            if let Err(e) = crate::db::cleanup_old_file_audit(&db_cleanup).await {
+                tracing::warn!("Failed to cleanup old file audit entries: {}", e);
+            }
+            if let Err(e) = crate::db::cleanup_old_action_audit(&db_cleanup).await {
+                tracing::warn!("Failed to cleanup old quick action audit entries: {}", e);
+            }
+            if let Err(e) = crate::db::cleanup_expired_plugin_kv(&db_cleanup).await {
+                tracing::warn!("Failed to cleanup expired plugin KV entries: {}", e);
+            }
         }
     });
 
+    // Start the scheduler for one-shot delayed tasks
+    crate::services::scheduler::spawn_scheduler(db.clone());
+
+    // Check for OS package updates in the background
+    crate::services::updates::spawn_update_checker(db.clone());
+
+    // Write scheduled backups when a backup directory is configured
+    crate::services::backup::spawn_backup_scheduler(db.clone(), config.paths.plugins_dir.clone());
+
+    // Check and compact the database periodically
+    crate::services::db_maintenance::spawn_maintenance_scheduler(db.clone());
+
+    // Watch batteries and the UPS for power alerts
+    crate::services::power::spawn_power_monitor(db.clone(), power_alerts);
+
     // Create API router
     let api_router = create_api_router();
     let auth_router = create_auth_router();
     let plugin_router = create_plugin_router();
+    let pipeline_router = create_pipeline_router();
+    let docker_router = create_docker_router();
+    let admin_router = create_admin_router();
+
+    let supervisor = state.supervisor.clone();
+
+    // Every request gets an ID (kept from X-Request-Id when the client sent one),
+    // returned in the response and written to the access log
+    let request_id_header = HeaderName::from_static(toru_plugin_api::REQUEST_ID_HEADER);
+    let access_logger = Arc::new(crate::services::logging::AccessLogger::new(
+        crate::services::logging::LogConfig {
+            log_dir,
+            ..Default::default()
+        },
+    )?);
 
     // Create main router
-    let app = Router::new()
+    let routes = Router::new()
         .route("/api/ws", get(handle_websocket))
         .nest("/api/auth", auth_router)
         .nest("/api/plugins", plugin_router)
+        .nest("/api/pipelines", pipeline_router)
+        .nest("/api/docker", docker_router)
+        .nest("/api/admin", admin_router)
+        .nest("/api/files", create_files_router())
         .nest("/api", api_router)
-        .fallback(static_handler)
-        .layer(TraceLayer::new_for_http())
+        .merge(create_metrics_router())
+        .merge(create_health_router())
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::routes::metrics::track_requests,
+        ))
+        .fallback(static_handler);
+
+    // Serve everything under the base path when one is configured
+    let routes = if config.server.base_path.is_empty() {
+        routes
+    } else {
+        tracing::info!("Serving under {}", config.server.base_path);
+        // `nest` doesn't match the prefix with a trailing slash, the URL proxies link to
+        Router::new()
+            .route(
+                &format!("{}/", config.server.base_path),
+                get(static_handler),
+            )
+            .nest(&config.server.base_path, routes)
+    };
+
+    let app = routes
+        .layer(middleware::from_fn_with_state(
+            access_logger,
+            crate::routes::access_log::log_access,
+        ))
+        .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+        .layer(TraceLayer::new_for_http().make_span_with(crate::routes::access_log::request_span))
+        .layer(SetRequestIdLayer::new(request_id_header, MakeRequestUuid))
         .layer(CorsLayer::permissive())
         .with_state(state);
 
-    // Start server
-    // Priority: CLI args > env vars > defaults
-    // Bind to localhost only by default - use Cloudflare Tunnel or reverse proxy for external access
-    let host: [u8; 4] = cli_host
-        .or_else(|| env::var("STEERING_HOST").ok())
-        .and_then(|h| parse_host(&h))
-        .unwrap_or([127, 0, 0, 1]);
+    // Start server, until SIGTERM or SIGINT
+    let shutdown_signal = crate::services::shutdown::listen();
+    if let Some(config) = tls_config {
+        tracing::info!("Server listening on https://{}", addr);
+        crate::services::tls::serve(addr, config, app, shutdown_signal).await?;
+    } else {
+        tracing::info!("Server listening on http://{}", addr);
 
-    let port: u16 = cli_port
-        .or_else(|| env::var("STEERING_PORT").ok().and_then(|p| p.parse().ok()))
-        .unwrap_or(3000);
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        // The peer address is the client IP in login history and the access log
+        let server = axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown_signal.clone().received());
+        crate::services::shutdown::serve_until_drained(server, shutdown_signal).await?;
+    }
 
-    let addr = SocketAddr::from((host, port));
-    tracing::info!("Server listening on http://{}", addr);
+    // Finish running tasks and stop plugins instead of leaving them behind
+    crate::services::shutdown::drain(&db, supervisor.as_ref()).await;
+
+    // After a self-update, run the new binary in place of this process
+    if crate::services::shutdown::restart_requested() {
+        let exe = exe.context("Failed to locate the steering-center binary")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            let e = std::process::Command::new(&exe).args(&args[1..]).exec();
+            return Err(
+                anyhow::Error::from(e).context(format!("Failed to restart {}", exe.display()))
+            );
+        }
+        // Without exec, start the new binary and leave it the port by exiting
+        #[cfg(not(unix))]
+        std::process::Command::new(&exe)
+            .args(&args[1..])
+            .spawn()
+            .with_context(|| format!("Failed to restart {}", exe.display()))?;
+    }
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    Ok(())
+}
 
+/// `steering-center self-update [--check]`
+async fn run_self_update(
+    config: &crate::config::Config,
+    binary: &std::path::Path,
+    check_only: bool,
+) -> anyhow::Result<()> {
+    use crate::services::self_update;
+
+    let (_, check) = self_update::check(&config.update).await?;
+    if !check.update_available {
+        println!("steering-center {} is up to date", check.current_version);
+        return Ok(());
+    }
+    println!(
+        "steering-center {} is available (running {})",
+        check.latest_version, check.current_version
+    );
+    if check_only {
+        return Ok(());
+    }
+    if let Some(version) = self_update::update(&config.update, binary).await? {
+        println!(
+            "Installed {} at {}; restart the server to run it",
+            version,
+            binary.display()
+        );
+    }
     Ok(())
 }
 
-fn parse_args(args: &[String]) -> (Option<u16>, Option<String>) {
-    let mut port = None;
-    let mut host = None;
+/// Options given on the command line
+#[derive(Default)]
+struct CliArgs {
+    config: Option<String>,
+    base_path: Option<String>,
+    port: Option<u16>,
+    host: Option<std::net::Ipv4Addr>,
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+    tls_redirect_port: Option<u16>,
+    acme_domain: Option<String>,
+}
+
+impl CliArgs {
+    /// Override the configuration with the options given
+    fn apply(&self, config: &mut crate::config::Config) -> anyhow::Result<()> {
+        if let Some(host) = self.host {
+            config.server.host = host;
+        }
+        if let Some(port) = self.port {
+            config.server.port = port;
+        }
+        if let Some(cert) = &self.tls_cert {
+            config.tls.cert = Some(cert.clone());
+        }
+        if let Some(key) = &self.tls_key {
+            config.tls.key = Some(key.clone());
+        }
+        if let Some(port) = self.tls_redirect_port {
+            config.tls.redirect_port = Some(port);
+        }
+        if let Some(domain) = &self.acme_domain {
+            config.acme.domain = Some(domain.clone());
+        }
+        if let Some(base_path) = &self.base_path {
+            config.server.base_path = crate::config::normalize_base_path(base_path)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_args(args: &[String]) -> CliArgs {
+    let mut cli = CliArgs::default();
 
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
-            "-p" | "--port" => {
-                if i + 1 < args.len() {
-                    port = args[i + 1].parse().ok();
-                    i += 1;
-                }
+            "-p" | "--port" if i + 1 < args.len() => {
+                cli.port = args[i + 1].parse().ok();
+                i += 1;
             }
-            "-H" | "--host" => {
-                if i + 1 < args.len() {
-                    host = Some(args[i + 1].clone());
-                    i += 1;
-                }
+            "-H" | "--host" if i + 1 < args.len() => {
+                cli.host = args[i + 1].parse().ok();
+                i += 1;
+            }
+            "-c" | "--config" if i + 1 < args.len() => {
+                cli.config = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--tls-cert" if i + 1 < args.len() => {
+                cli.tls_cert = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--tls-key" if i + 1 < args.len() => {
+                cli.tls_key = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--tls-redirect-port" if i + 1 < args.len() => {
+                cli.tls_redirect_port = args[i + 1].parse().ok();
+                i += 1;
+            }
+            "--base-path" if i + 1 < args.len() => {
+                cli.base_path = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--acme-domain" if i + 1 < args.len() => {
+                cli.acme_domain = Some(args[i + 1].clone());
+                i += 1;
             }
             arg if arg.starts_with("--port=") => {
-                port = arg.trim_start_matches("--port=").parse().ok();
+                cli.port = arg.trim_start_matches("--port=").parse().ok();
             }
             arg if arg.starts_with("--host=") => {
-                host = Some(arg.trim_start_matches("--host=").to_string());
+                cli.host = arg.trim_start_matches("--host=").parse().ok();
+            }
+            arg if arg.starts_with("--config=") => {
+                cli.config = Some(arg.trim_start_matches("--config=").to_string());
+            }
+            arg if arg.starts_with("--tls-cert=") => {
+                cli.tls_cert = Some(arg.trim_start_matches("--tls-cert=").to_string());
+            }
+            arg if arg.starts_with("--tls-key=") => {
+                cli.tls_key = Some(arg.trim_start_matches("--tls-key=").to_string());
+            }
+            arg if arg.starts_with("--tls-redirect-port=") => {
+                cli.tls_redirect_port = arg.trim_start_matches("--tls-redirect-port=").parse().ok();
+            }
+            arg if arg.starts_with("--base-path=") => {
+                cli.base_path = Some(arg.trim_start_matches("--base-path=").to_string());
+            }
+            arg if arg.starts_with("--acme-domain=") => {
+                cli.acme_domain = Some(arg.trim_start_matches("--acme-domain=").to_string());
             }
             _ => {}
         }
         i += 1;
     }
 
-    (port, host)
-}
-
-fn parse_host(h: &str) -> Option<[u8; 4]> {
-    let parts: Vec<&str> = h.split('.').collect();
-    if parts.len() == 4 {
-        let octets: Result<Vec<u8>, _> = parts.iter().map(|p| p.parse()).collect();
-        if let Ok(o) = octets {
-            return Some([o[0], o[1], o[2], o[3]]);
-        }
-    }
-    None
+    cli
 }
 
 fn print_help() {
@@ -240,28 +588,69 @@ fn print_help() {
     println!();
     println!("USAGE:");
     println!("    steering-center [OPTIONS]");
+    println!("    steering-center install-service [INSTALL OPTIONS]");
+    println!("    steering-center self-update [--check] [--config <FILE>]");
     println!();
     println!("OPTIONS:");
+    println!("    -c, --config <FILE>  TOML configuration file");
     println!("    -p, --port <PORT>    Port to listen on [default: 3000]");
     println!("    -H, --host <HOST>    Host to bind to [default: 127.0.0.1]");
+    println!("    --base-path <PATH>   Serve the app under PATH, e.g. /steering");
+    println!("    --tls-cert <FILE>    PEM certificate chain to serve HTTPS with");
+    println!("    --tls-key <FILE>     PEM private key of the certificate");
+    println!("    --tls-redirect-port <PORT>");
+    println!("                         Redirect plain HTTP on this port to HTTPS");
+    println!("    --acme-domain <DOMAIN>");
+    println!("                         Get a certificate for DOMAIN from Let's Encrypt");
     println!("    -h, --help           Print this help message");
     println!();
+    println!("COMMANDS:");
+    println!("    install-service      Install as a systemd service (see INSTALL OPTIONS)");
+    println!(
+        "    self-update          Install the latest release, or only look for one with --check"
+    );
+    println!();
+    println!("INSTALL OPTIONS:");
+    println!("    --user <USER>        System user to run as [default: steering]");
+    println!("    --state-dir <DIR>    Working directory for the database, logs and plugins");
+    println!("                         [default: /var/lib/steering-center]");
+    println!("    --env-file <FILE>    Environment file with the admin credentials");
+    println!("                         [default: /etc/steering-center/env]");
+    println!("    --config <FILE>      TOML configuration file to start with");
+    println!("    --unit <FILE>        Unit file to write");
+    println!("                         [default: /etc/systemd/system/steering-center.service]");
+    println!("    --binary <FILE>      Binary to run [default: this one]");
+    println!("    --enable             Enable and start the service");
+    println!("    --print              Only print the unit");
+    println!();
     println!("ENVIRONMENT VARIABLES:");
+    println!("    STEERING_CONFIG      TOML configuration file");
     println!("    STEERING_PORT        Port to listen on");
     println!("    STEERING_HOST        Host to bind to");
+    println!("    STEERING_BASE_PATH   Serve the app under this path");
     println!("    RUST_LOG             Log level (e.g., debug, info, warn, error)");
     println!("    TORU_LOG_DIR         Directory for plugin logs [default: ./logs]");
     println!("    PRODUCTION           Set to 'true' for production mode");
     println!("    SECURE_COOKIES       Set to 'true' to mark cookies as Secure");
+    println!("    TRUST_PROXY          Set to 'true' to take client IPs from proxy headers");
+    println!("    TORU_TLS_CERT        PEM certificate chain to serve HTTPS with");
+    println!("    TORU_TLS_KEY         PEM private key of the certificate");
+    println!("    TORU_TLS_REDIRECT_PORT  Redirect plain HTTP on this port to HTTPS");
+    println!("    TORU_ACME_DOMAIN     Get a certificate for this domain from Let's Encrypt");
+    println!("    TORU_ACME_EMAIL      Contact email for the ACME account");
+    println!("    TORU_DATA_DIR        Directory for certificates [default: ./data]");
     println!();
     println!("EXAMPLES:");
     println!("    steering-center                    # Start on localhost:3000");
     println!("    steering-center -p 8080            # Start on localhost:8080");
     println!("    steering-center --host 0.0.0.0     # Bind to all interfaces");
+    println!("    steering-center --config /etc/steering/config.toml");
+    println!("    sudo steering-center install-service --enable  # Run as a systemd service");
+    println!("    steering-center --tls-cert cert.pem --tls-key key.pem  # Serve HTTPS");
     println!();
 }
 
-async fn static_handler(uri: Uri) -> impl IntoResponse {
+async fn static_handler(State(state): State<AppState>, uri: Uri) -> impl IntoResponse {
     let mut path = uri.path().trim_start_matches('/').to_string();
 
     if path.is_empty() {
@@ -269,16 +658,29 @@ async fn static_handler(uri: Uri) -> impl IntoResponse {
     }
 
     match Assets::get(&path) {
+        Some(_) if path == "index.html" => index_html(&state.config.server.base_path),
         Some(content) => {
             let mime = mime_guess::from_path(&path).first_or_octet_stream();
             ([(header::CONTENT_TYPE, mime.as_ref())], content.data).into_response()
         }
-        None => {
-            if let Some(content) = Assets::get("index.html") {
-                ([(header::CONTENT_TYPE, "text/html")], content.data).into_response()
-            } else {
-                (StatusCode::NOT_FOUND, "404 Not Found").into_response()
-            }
-        }
+        None => index_html(&state.config.server.base_path),
     }
 }
+
+/// The frontend's page, told which base path it is served under
+fn index_html(base_path: &str) -> axum::response::Response {
+    let Some(content) = Assets::get("index.html") else {
+        return (StatusCode::NOT_FOUND, "404 Not Found").into_response();
+    };
+    // Assets are linked relative to <base>; the frontend reads the prefix of API
+    // and page URLs from the meta tag
+    let page = String::from_utf8_lossy(&content.data).replacen(
+        "<head>",
+        &format!(
+            "<head><base href=\"{0}/\"><meta name=\"toru-base-path\" content=\"{0}\">",
+            base_path
+        ),
+        1,
+    );
+    ([(header::CONTENT_TYPE, "text/html")], page).into_response()
+}
@@ -0,0 +1,92 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tower_http::request_id::RequestId;
+
+use crate::services::logging::{AccessLogEntry, AccessLogger};
+
+/// Slot in a request's extensions for the user it was made as
+///
+/// `log_access` adds it and the auth extractors fill it in, since the session
+/// is only looked up once a handler asks for it.
+#[derive(Debug, Clone, Default)]
+pub struct AccessLogUser(Arc<Mutex<Option<String>>>);
+
+impl AccessLogUser {
+    pub fn set(&self, username: &str) {
+        *self.0.lock().unwrap() = Some(username.to_string());
+    }
+
+    fn take(&self) -> Option<String> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// ID given to a request by `SetRequestIdLayer`, or the one the client sent
+fn request_id(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or("-")
+        .to_string()
+}
+
+/// Tracing span of a request, so everything logged while handling it carries its ID
+pub fn request_span(request: &Request) -> tracing::Span {
+    tracing::info_span!(
+        "request",
+        request_id = %request_id(request),
+        method = %request.method(),
+        uri = %request.uri(),
+    )
+}
+
+/// Write every request to the access log, with its ID, status, duration,
+/// user and client IP
+pub async fn log_access(
+    State(logger): State<Arc<AccessLogger>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let request_id = request_id(&request);
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let ip = super::auth::get_client_ip(
+        &crate::config::get(),
+        request.headers(),
+        request.extensions().get::<ConnectInfo<SocketAddr>>(),
+    );
+    let user = AccessLogUser::default();
+    request.extensions_mut().insert(user.clone());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let entry = AccessLogEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        request_id,
+        method,
+        path,
+        status: response.status().as_u16(),
+        duration_ms: started.elapsed().as_millis() as u64,
+        user: user.take(),
+        ip,
+        user_agent,
+    };
+    if let Err(e) = logger.log(&entry).await {
+        tracing::warn!("Failed to write access log: {}", e);
+    }
+    response
+}
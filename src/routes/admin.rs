@@ -0,0 +1,696 @@
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
+    routing::{get, post, put},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::db::{self, DbStats, MaintenanceReport, Webhook, WebhookDelivery};
+use crate::routes::api::AppState;
+use crate::routes::auth::AdminUser;
+use crate::services::backup::{self, BackupError, RestoreSummary, MAX_BACKUP_SIZE};
+use crate::services::config_transfer::{self, ConfigDocument, ConfigImportError, ImportSummary};
+use crate::services::db_maintenance;
+use crate::services::log_levels;
+use crate::services::logging::{self, AccessLogEntry, AccessLogFilter, LogLevel};
+use crate::services::notifications::{self, Notification, NotificationKind, NotificationLevel};
+use crate::services::self_update::{self, ReleaseCheck, UpdateError};
+use crate::services::shutdown;
+use crate::services::webhooks;
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn internal_error(e: anyhow::Error) -> ApiError {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({ "error": format!("{:#}", e) })),
+    )
+}
+
+fn backup_error(e: BackupError) -> ApiError {
+    let status = match e {
+        BackupError::Invalid(_) => StatusCode::BAD_REQUEST,
+        BackupError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string() })))
+}
+
+fn update_error(e: UpdateError) -> ApiError {
+    let status = match e {
+        UpdateError::InProgress => StatusCode::CONFLICT,
+        UpdateError::Release(_) => StatusCode::BAD_GATEWAY,
+        UpdateError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string() })))
+}
+
+fn import_error(e: ConfigImportError) -> ApiError {
+    let status = match e {
+        ConfigImportError::Invalid(_) => StatusCode::BAD_REQUEST,
+        ConfigImportError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string() })))
+}
+
+pub fn create_admin_router() -> Router<AppState> {
+    Router::new()
+        .route("/backup", post(create_backup))
+        .route(
+            "/restore",
+            post(restore_backup).layer(DefaultBodyLimit::max(MAX_BACKUP_SIZE)),
+        )
+        .route("/config/export", get(export_config))
+        .route("/config/import", post(import_config))
+        .route("/db/stats", get(db_stats))
+        .route("/db/maintenance", post(run_db_maintenance))
+        .route("/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/webhooks/deliveries", get(list_all_deliveries))
+        .route("/webhooks/deliveries/:id", get(get_delivery))
+        .route("/webhooks/deliveries/:id/retry", post(retry_delivery))
+        .route("/webhooks/:id", put(update_webhook).delete(delete_webhook))
+        .route("/webhooks/:id/deliveries", get(list_deliveries))
+        .route("/webhooks/:id/test", post(test_webhook))
+        .route("/notifications", get(notification_status))
+        .route("/notifications/test", post(test_notification))
+        .route("/update", get(check_update).post(install_update))
+        .route("/logging", get(get_logging).put(update_logging))
+        .route("/access-log", get(get_access_log))
+}
+
+fn bad_request(e: anyhow::Error) -> ApiError {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({ "error": e.to_string() })),
+    )
+}
+
+fn not_found(what: &str) -> ApiError {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({ "error": format!("{} not found", what) })),
+    )
+}
+
+/// Download a backup of the database and plugin configuration
+async fn create_backup(
+    auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (file_name, archive) = backup::create_backup(&state.db, &state.config.paths.plugins_dir)
+        .await
+        .map_err(internal_error)?;
+    tracing::info!("Backup {} downloaded by {}", file_name, auth.0.username);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        archive,
+    ))
+}
+
+/// Restore a backup sent as the request body
+async fn restore_backup(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<Json<RestoreSummary>, ApiError> {
+    let summary = backup::restore_backup(&state.db, &state.config.paths.plugins_dir, body.to_vec())
+        .await
+        .map_err(backup_error)?;
+    tracing::warn!(
+        "Backup from {} restored by {}",
+        summary.backup_created_at,
+        auth.0.username
+    );
+    Ok(Json(summary))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    include_password_hashes: bool,
+}
+
+/// Settings, quick actions, pipelines, scheduled tasks and users as JSON
+async fn export_config(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Json<ConfigDocument>, ApiError> {
+    let document = config_transfer::export_config(&state.db, query.include_password_hashes)
+        .await
+        .map_err(internal_error)?;
+    tracing::info!(
+        "Configuration exported by {} (password hashes: {})",
+        auth.0.username,
+        query.include_password_hashes
+    );
+    Ok(Json(document))
+}
+
+/// Merge an exported configuration into this instance's
+async fn import_config(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Json(document): Json<ConfigDocument>,
+) -> Result<Json<ImportSummary>, ApiError> {
+    let summary = config_transfer::import_config(&state.db, document)
+        .await
+        .map_err(import_error)?;
+    tracing::warn!("Configuration imported by {}", auth.0.username);
+    Ok(Json(summary))
+}
+
+#[derive(Serialize)]
+struct LoggingResponse {
+    /// Tracing filter in effect
+    filter: Option<String>,
+    /// Filter from `RUST_LOG` (or the default) the server started with
+    startup_filter: Option<String>,
+    /// Filter saved by an admin, which replaces the startup filter
+    saved_filter: Option<String>,
+    /// Least severe level logged per plugin
+    plugin_levels: HashMap<String, &'static str>,
+}
+
+async fn logging_response(state: &AppState) -> Result<LoggingResponse, ApiError> {
+    let (saved_filter, plugin_levels) =
+        log_levels::load(&state.db).await.map_err(internal_error)?;
+    Ok(LoggingResponse {
+        filter: log_levels::current_filter(),
+        startup_filter: log_levels::startup_filter(),
+        saved_filter,
+        plugin_levels: plugin_levels
+            .into_iter()
+            .map(|(plugin, level)| (plugin, level.as_str()))
+            .collect(),
+    })
+}
+
+/// The log filter and plugin log levels
+async fn get_logging(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<LoggingResponse>, ApiError> {
+    Ok(Json(logging_response(&state).await?))
+}
+
+#[derive(Deserialize)]
+struct UpdateLoggingRequest {
+    /// New tracing filter; empty to go back to the startup filter
+    filter: Option<String>,
+    /// Minimum level per plugin, merged into the current ones; `null` removes one
+    plugin_levels: Option<HashMap<String, Option<String>>>,
+}
+
+/// Change the log filter and plugin log levels now, keeping them for restarts
+async fn update_logging(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Json(payload): Json<UpdateLoggingRequest>,
+) -> Result<Json<LoggingResponse>, ApiError> {
+    let (_, mut plugin_levels) = log_levels::load(&state.db).await.map_err(internal_error)?;
+    for (plugin, level) in payload.plugin_levels.unwrap_or_default() {
+        match level {
+            Some(level) => {
+                let parsed = LogLevel::parse_level(&level).ok_or_else(|| {
+                    bad_request(anyhow::anyhow!(
+                        "Invalid log level {} for {}",
+                        level,
+                        plugin
+                    ))
+                })?;
+                plugin_levels.insert(plugin, parsed);
+            }
+            None => {
+                plugin_levels.remove(&plugin);
+            }
+        }
+    }
+
+    if let Some(filter) = &payload.filter {
+        let filter = filter.trim();
+        if !filter.is_empty() {
+            log_levels::parse_filter(filter).map_err(bad_request)?;
+        }
+        log_levels::set_filter((!filter.is_empty()).then_some(filter)).map_err(internal_error)?;
+        db::set_setting(&state.db, log_levels::FILTER_SETTING, filter)
+            .await
+            .map_err(internal_error)?;
+    }
+    db::set_setting(
+        &state.db,
+        log_levels::PLUGIN_LEVELS_SETTING,
+        &log_levels::plugin_levels_json(&plugin_levels),
+    )
+    .await
+    .map_err(internal_error)?;
+    if let Some(supervisor) = &state.supervisor {
+        supervisor
+            .lock()
+            .await
+            .plugin_logger()
+            .set_min_levels(plugin_levels);
+    }
+
+    Ok(Json(logging_response(&state).await?))
+}
+
+#[derive(Deserialize)]
+struct AccessLogQuery {
+    limit: Option<usize>,
+    method: Option<String>,
+    /// Start of the request path
+    path: Option<String>,
+    /// Exact status, or a class such as `5xx`
+    status: Option<String>,
+    user: Option<String>,
+    ip: Option<String>,
+    request_id: Option<String>,
+    /// RFC 3339 time of the earliest request to return
+    from: Option<String>,
+    /// RFC 3339 time of the latest request to return
+    to: Option<String>,
+}
+
+/// Recent requests from the access log, newest first
+async fn get_access_log(
+    _auth: AdminUser,
+    Query(query): Query<AccessLogQuery>,
+) -> Result<Json<Vec<AccessLogEntry>>, ApiError> {
+    let timestamp = |t: Option<String>| {
+        t.filter(|t| !t.is_empty())
+            .map(|t| {
+                chrono::DateTime::parse_from_rfc3339(&t)
+                    .map(|t| t.with_timezone(&chrono::Utc))
+                    .map_err(|_| bad_request(anyhow::anyhow!("Invalid time {}", t)))
+            })
+            .transpose()
+    };
+    let given = |v: Option<String>| v.filter(|v| !v.is_empty());
+    let filter = AccessLogFilter {
+        method: given(query.method),
+        path: given(query.path),
+        status: given(query.status),
+        user: given(query.user),
+        ip: given(query.ip),
+        request_id: given(query.request_id),
+        from: timestamp(query.from)?,
+        to: timestamp(query.to)?,
+    };
+    filter.validate().map_err(bad_request)?;
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+
+    let entries = tokio::task::spawn_blocking(move || {
+        logging::read_access_log(&logging::log_dir(), &filter, limit)
+    })
+    .await
+    .map_err(|e| internal_error(e.into()))?
+    .map_err(internal_error)?;
+    Ok(Json(entries))
+}
+
+/// File size, fragmentation and row counts of the database
+async fn db_stats(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<DbStats>, ApiError> {
+    let stats = db::get_db_stats(&state.db).await.map_err(internal_error)?;
+    Ok(Json(stats))
+}
+
+/// Check, analyze and vacuum the database now
+async fn run_db_maintenance(
+    auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<MaintenanceReport>, ApiError> {
+    tracing::info!("Database maintenance started by {}", auth.0.username);
+    let report = db_maintenance::run_maintenance(&state.db)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)]
+struct WebhookRequest {
+    name: String,
+    url: String,
+    /// Event filters; empty for every event
+    #[serde(default)]
+    events: Vec<String>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    /// Generated when creating a webhook without one; kept when updating without one
+    secret: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl WebhookRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.name.trim().is_empty() {
+            return Err(bad_request(anyhow::anyhow!("Webhook name is required")));
+        }
+        if self.secret.as_deref().is_some_and(|s| s.trim().is_empty()) {
+            return Err(bad_request(anyhow::anyhow!(
+                "Webhook secret can't be empty"
+            )));
+        }
+        webhooks::validate(self.url.trim(), &self.events).map_err(bad_request)
+    }
+}
+
+/// A webhook with its secret, shown only when it is created
+#[derive(Serialize)]
+struct CreatedWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+struct DeliveryQuery {
+    limit: Option<i32>,
+}
+
+impl DeliveryQuery {
+    fn limit(&self) -> i32 {
+        self.limit.unwrap_or(50).clamp(1, 500)
+    }
+}
+
+/// Registered webhooks (without their secrets)
+async fn list_webhooks(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Webhook>>, ApiError> {
+    let webhooks = db::get_webhooks(&state.db).await.map_err(internal_error)?;
+    Ok(Json(webhooks))
+}
+
+/// Register a webhook; the response is the only place its secret is shown
+async fn create_webhook(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Json(payload): Json<WebhookRequest>,
+) -> Result<(StatusCode, Json<CreatedWebhook>), ApiError> {
+    payload.validate()?;
+    let webhook = Webhook {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: payload.name.trim().to_string(),
+        url: payload.url.trim().to_string(),
+        secret: payload.secret.unwrap_or_else(webhooks::generate_secret),
+        events: payload.events,
+        enabled: payload.enabled,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    db::create_webhook(&state.db, &webhook)
+        .await
+        .map_err(internal_error)?;
+    tracing::info!("Webhook {} added by {}", webhook.url, auth.0.username);
+
+    let secret = webhook.secret.clone();
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedWebhook { webhook, secret }),
+    ))
+}
+
+/// Change a webhook's name, URL, filters, enabled flag or secret
+async fn update_webhook(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<WebhookRequest>,
+) -> Result<Json<Webhook>, ApiError> {
+    payload.validate()?;
+    let Some(existing) = db::get_webhook(&state.db, &id)
+        .await
+        .map_err(internal_error)?
+    else {
+        return Err(not_found("Webhook"));
+    };
+    let webhook = Webhook {
+        name: payload.name.trim().to_string(),
+        url: payload.url.trim().to_string(),
+        secret: payload.secret.unwrap_or(existing.secret),
+        events: payload.events,
+        enabled: payload.enabled,
+        ..existing
+    };
+    db::update_webhook(&state.db, &webhook)
+        .await
+        .map_err(internal_error)?;
+    tracing::info!("Webhook {} updated by {}", webhook.url, auth.0.username);
+    Ok(Json(webhook))
+}
+
+/// Remove a webhook and its delivery log
+async fn delete_webhook(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = db::delete_webhook(&state.db, &id)
+        .await
+        .map_err(internal_error)?;
+    if !deleted {
+        return Err(not_found("Webhook"));
+    }
+    tracing::info!("Webhook {} deleted by {}", id, auth.0.username);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Send a `ping` event to a webhook, even a disabled one
+async fn test_webhook(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<WebhookDelivery>), ApiError> {
+    let Some(webhook) = db::get_webhook(&state.db, &id)
+        .await
+        .map_err(internal_error)?
+    else {
+        return Err(not_found("Webhook"));
+    };
+    let delivery = webhooks::send_test(&state.db, &webhook)
+        .await
+        .map_err(internal_error)?;
+    Ok((StatusCode::ACCEPTED, Json(delivery)))
+}
+
+/// Recent deliveries of one webhook, newest first
+async fn list_deliveries(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<DeliveryQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, ApiError> {
+    let deliveries = db::get_webhook_deliveries(&state.db, Some(&id), query.limit())
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(deliveries))
+}
+
+/// Recent deliveries of every webhook, newest first
+async fn list_all_deliveries(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Query(query): Query<DeliveryQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, ApiError> {
+    let deliveries = db::get_webhook_deliveries(&state.db, None, query.limit())
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(deliveries))
+}
+
+/// One delivery, with the payload that was sent
+async fn get_delivery(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<WebhookDelivery>, ApiError> {
+    db::get_webhook_delivery(&state.db, &id)
+        .await
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(|| not_found("Delivery"))
+}
+
+/// Send a delivered or failed delivery again
+async fn retry_delivery(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<WebhookDelivery>), ApiError> {
+    match webhooks::redeliver(&state.db, &id)
+        .await
+        .map_err(internal_error)?
+    {
+        Some(delivery) => Ok((StatusCode::ACCEPTED, Json(delivery))),
+        None => match db::get_webhook_delivery(&state.db, &id)
+            .await
+            .map_err(internal_error)?
+        {
+            Some(_) => Err((
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "error": "Delivery is still pending" })),
+            )),
+            None => Err(not_found("Delivery")),
+        },
+    }
+}
+
+#[derive(Serialize)]
+struct ChannelStatus {
+    name: &'static str,
+    configured: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct NotificationStatus {
+    channels: Vec<ChannelStatus>,
+    /// Channels each kind of notification is sent to
+    routes: std::collections::BTreeMap<&'static str, Vec<String>>,
+}
+
+/// Which notification channels are configured, and where each kind of notification goes
+async fn notification_status(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<NotificationStatus>, ApiError> {
+    let mut channels = Vec::new();
+    for &name in notifications::CHANNELS {
+        let (configured, error) = match notifications::load_channel(&state.db, name).await {
+            Ok(channel) => (channel.is_some(), None),
+            Err(e) => (false, Some(format!("{:#}", e))),
+        };
+        channels.push(ChannelStatus {
+            name,
+            configured,
+            error,
+        });
+    }
+
+    let mut routes = std::collections::BTreeMap::new();
+    for kind in NotificationKind::ALL {
+        let preference = db::get_setting(&state.db, &kind.preference_setting())
+            .await
+            .map_err(internal_error)?;
+        let names = notifications::preferred_channels(preference.as_deref())
+            .into_iter()
+            .filter(|name| channels.iter().any(|c| c.name == *name && c.configured))
+            .map(String::from)
+            .collect();
+        routes.insert(kind.as_str(), names);
+    }
+    Ok(Json(NotificationStatus { channels, routes }))
+}
+
+#[derive(Deserialize)]
+struct TestNotificationRequest {
+    channel: String,
+}
+
+/// Send a test message through one channel and report whether it went out
+async fn test_notification(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Json(payload): Json<TestNotificationRequest>,
+) -> Result<StatusCode, ApiError> {
+    let channel = notifications::load_channel(&state.db, &payload.channel)
+        .await
+        .map_err(bad_request)?
+        .ok_or_else(|| {
+            bad_request(anyhow::anyhow!(
+                "Channel {} is not configured",
+                payload.channel
+            ))
+        })?;
+    let notification = Notification::new(
+        NotificationKind::Alert,
+        NotificationLevel::Info,
+        "Test notification",
+        format!(
+            "Sent by {} to check the {} channel.",
+            auth.0.username,
+            channel.name()
+        ),
+    );
+    channel.send(&notification).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(serde_json::json!({ "error": format!("{:#}", e) })),
+        )
+    })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Whether a newer release of the server is available
+async fn check_update(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<ReleaseCheck>, ApiError> {
+    let (_, check) = self_update::check(&state.config.update)
+        .await
+        .map_err(|e| update_error(UpdateError::Release(e)))?;
+    Ok(Json(check))
+}
+
+#[derive(Serialize)]
+struct UpdateResult {
+    updated: bool,
+    version: String,
+}
+
+/// Install the latest release and restart into it
+async fn install_update(
+    auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<UpdateResult>), ApiError> {
+    let binary = std::env::current_exe().map_err(|e| {
+        internal_error(anyhow::Error::from(e).context("Failed to locate the binary"))
+    })?;
+    let Some(version) = self_update::update(&state.config.update, &binary)
+        .await
+        .map_err(update_error)?
+    else {
+        return Ok((
+            StatusCode::OK,
+            Json(UpdateResult {
+                updated: false,
+                version: self_update::CURRENT_VERSION.to_string(),
+            }),
+        ));
+    };
+    tracing::warn!(
+        "Update to {} installed by {}, restarting",
+        version,
+        auth.0.username
+    );
+
+    // Answer before the server goes down
+    tokio::spawn(async {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        shutdown::request_restart();
+    });
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(UpdateResult {
+            updated: true,
+            version,
+        }),
+    ))
+}
@@ -1,27 +1,59 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    body::Bytes,
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
     routing::{delete, get, post, put},
     Router,
 };
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::db::{self, DbPool, QuickAction, TaskHistory, User, UserRole};
+use crate::db::{
+    self, ActionAuditEntry, ConcurrencyMode, DangerLevel, DbPool, PipelineStepRun, QuickAction,
+    QuickActionParam, QuickActionStats, ScheduledTask, TaskHistory, TaskSearchHit, User,
+    UserProfile, UserRole, Visibility,
+};
 use crate::routes::auth::{AdminUser, AuthUser};
-use crate::services::auth::{hash_password, validate_password};
-use crate::services::system::{get_system_resources, SystemResources};
+use crate::services::action_packs::{
+    self, ActionPack, ActionPackError, ConflictPolicy, PackImportSummary,
+};
+use crate::services::action_params;
+use crate::services::auth::{hash_password, validate_password, MAX_SESSION_HOURS};
+use crate::services::avatars;
+use crate::services::executor::{task_log_path, ResourceLimits, ScriptBusy};
+use crate::services::host_sessions::{self, HostSession};
+use crate::services::ports::{self, ListeningSocket};
+use crate::services::power::{self, PowerAlert, PowerStatus};
+use crate::services::processes::{self, KillError, ProcessPage, ProcessSort, SortOrder};
+use crate::services::scheduler;
+use crate::services::scripts::{self, ScriptError};
+use crate::services::system::{ResourceCache, SystemResources};
+use crate::services::system_logs::{self, SystemLogError, SystemLogPage, SystemLogQuery};
+use crate::services::updates::{self, UpdateCheck};
+use crate::services::user_import::{self, ImportRow, RowResult};
 use sysinfo::System;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: DbPool,
     pub sys: Arc<Mutex<System>>,
+    /// Latest snapshot from the resource sampler
+    pub resources: ResourceCache,
     pub supervisor: Option<Arc<Mutex<crate::services::plugins::PluginSupervisor>>>,
+    pub metrics: Arc<crate::services::metrics::HttpMetrics>,
+    /// Power alerts raised and cleared by the power monitor
+    pub power_alerts: tokio::sync::broadcast::Sender<PowerAlert>,
+    pub config: Arc<crate::config::Config>,
+    /// Output of tasks started from WebSockets, kept for clients that reconnect
+    pub task_output: Arc<crate::services::task_output::TaskOutputHub>,
+    /// Processes of tasks run from WebSockets and the REST API, so any session can cancel them
+    pub tasks: crate::services::executor::TaskRegistry,
+    /// System notifications pushed to WebSocket sessions
+    pub notification_hub: Arc<crate::services::system_notifications::NotificationHub>,
 }
 
 pub fn create_api_router() -> Router<AppState> {
@@ -30,23 +62,54 @@ pub fn create_api_router() -> Router<AppState> {
         .route("/health", get(health))
         .route("/resources", get(resources))
         .route("/history", get(get_history))
+        .route("/history/:id/log", get(get_history_log))
+        .route("/history/:id/steps", get(get_history_steps))
+        .route("/search", get(search))
         .route("/quick-actions", get(get_quick_actions))
+        .route("/system/processes", get(list_processes))
+        .route("/system/updates", get(get_updates))
+        .route("/system/power", get(get_power))
         // Admin-only routes
+        .route("/system/processes/:pid/kill", post(kill_process))
+        .route("/system/logs", get(get_system_logs))
+        .route("/system/ports", get(list_ports))
+        .route("/system/sessions", get(list_host_sessions))
+        .route("/system/updates/check", post(check_updates))
         .route("/scripts", get(list_scripts))
+        .route("/scripts", post(upload_script))
+        .route("/scripts/:name", get(get_script))
+        .route("/scripts/:name", put(save_script))
+        .route("/scripts/:name", delete(delete_script))
+        .route("/scripts/:name/rename", post(rename_script))
         .route("/settings", get(get_settings))
         .route("/settings/:key", put(update_setting))
         .route("/quick-actions", post(create_quick_action))
+        .route("/quick-actions/order", put(reorder_quick_actions))
+        .route("/quick-actions/audit", get(get_action_audit_log))
+        .route("/quick-actions/export", get(export_quick_actions))
+        .route("/quick-actions/import", post(import_quick_actions))
+        .route("/quick-actions/:id", put(update_quick_action))
         .route("/quick-actions/:id", delete(delete_quick_action))
         .route("/quick-actions/:id/execute", post(execute_quick_action))
+        .route("/scheduled-tasks", get(list_scheduled_tasks))
+        .route("/scheduled-tasks", post(create_scheduled_task))
+        .route("/scheduled-tasks/:id", delete(cancel_scheduled_task))
         // User management (admin-only)
         .route("/users", get(list_users))
         .route("/users", post(create_user))
+        .route("/users/export", get(export_users))
+        .route("/users/import", post(import_users))
         .route("/users/:id", get(get_user))
         .route("/users/:id", put(update_user))
         .route("/users/:id", delete(delete_user))
         .route("/users/:id/password", put(reset_user_password))
+        .route("/users/:id/avatar", get(get_user_avatar))
         // Self-service password change (any authenticated user)
         .route("/me/password", put(change_own_password))
+        .route("/me/profile", get(get_own_profile))
+        .route("/me/profile", put(update_own_profile))
+        .route("/me/preferences", get(get_own_preferences))
+        .route("/me/preferences", put(update_own_preferences))
 }
 
 async fn health() -> Json<serde_json::Value> {
@@ -57,34 +120,308 @@ async fn resources(
     _auth: AuthUser, // Require any authenticated user
     State(state): State<AppState>,
 ) -> Result<Json<SystemResources>, StatusCode> {
+    state
+        .resources
+        .snapshot()
+        .map(Json)
+        .ok_or(StatusCode::SERVICE_UNAVAILABLE)
+}
+
+#[derive(Deserialize)]
+struct ProcessQuery {
+    #[serde(default)]
+    sort: ProcessSort,
+    #[serde(default)]
+    order: SortOrder,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+}
+
+async fn list_processes(
+    _auth: AdminUser, // Admin only: command lines may carry secrets
+    State(state): State<AppState>,
+    Query(query): Query<ProcessQuery>,
+) -> Json<ProcessPage> {
     let mut sys = state.sys.lock().await;
-    let resources = get_system_resources(&mut sys);
-    Ok(Json(resources))
+    Json(processes::list_processes(
+        &mut sys,
+        query.sort,
+        query.order,
+        query.limit,
+        query.offset,
+    ))
 }
 
-async fn list_scripts(
+#[derive(Deserialize)]
+struct KillProcessRequest {
+    #[serde(default = "default_signal")]
+    signal: String,
+}
+
+fn default_signal() -> String {
+    "TERM".to_string()
+}
+
+fn kill_error(e: KillError) -> (StatusCode, Json<serde_json::Value>) {
+    let status = match e {
+        KillError::InvalidSignal(_) | KillError::Protected(_) => StatusCode::BAD_REQUEST,
+        KillError::NotFound(_) => StatusCode::NOT_FOUND,
+        KillError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+        KillError::Unsupported => StatusCode::NOT_IMPLEMENTED,
+        KillError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string() })))
+}
+
+/// Send a signal (`TERM` by default) to a process on the host
+async fn kill_process(
+    auth: AdminUser, // Admin only
+    Path(pid): Path<u32>,
+    Json(payload): Json<KillProcessRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    processes::kill_process(pid, &payload.signal).map_err(kill_error)?;
+    tracing::info!(
+        "Process {} sent {} by {}",
+        pid,
+        payload.signal,
+        auth.0.username
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The last package update check; `null` until one has run
+async fn get_updates(
+    _auth: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<Option<UpdateCheck>>, StatusCode> {
+    let check = updates::last_check(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(check))
+}
+
+/// Batteries, the configured UPS and active power alerts
+async fn get_power(_auth: AuthUser, State(state): State<AppState>) -> Json<PowerStatus> {
+    Json(power::read_power_status(&state.db).await)
+}
+
+/// Check for package updates now
+async fn check_updates(
     _auth: AdminUser, // Admin only
     State(state): State<AppState>,
-) -> Result<Json<Vec<String>>, StatusCode> {
+) -> Result<Json<UpdateCheck>, StatusCode> {
+    let check = updates::run_check(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(check))
+}
+
+/// Listening TCP and UDP sockets with the processes that own them
+async fn list_ports(
+    _auth: AdminUser, // Admin only
+) -> Result<Json<Vec<ListeningSocket>>, (StatusCode, Json<serde_json::Value>)> {
+    let sockets = tokio::task::spawn_blocking(ports::list_listening_sockets)
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|sockets| sockets)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
+    Ok(Json(sockets))
+}
+
+/// Users logged in to the host (terminals, SSH)
+async fn list_host_sessions(
+    _auth: AdminUser, // Admin only
+) -> Result<Json<Vec<HostSession>>, (StatusCode, Json<serde_json::Value>)> {
+    let sessions = tokio::task::spawn_blocking(host_sessions::list_host_sessions)
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|sessions| sessions)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
+    Ok(Json(sessions))
+}
+
+/// A page of journald or the configured system log file
+async fn get_system_logs(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Query(query): Query<SystemLogQuery>,
+) -> Result<Json<SystemLogPage>, (StatusCode, Json<serde_json::Value>)> {
+    let page = system_logs::read_system_log(&state.db, &query)
+        .await
+        .map_err(|e| {
+            let status = match e {
+                SystemLogError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+                SystemLogError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+                SystemLogError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(serde_json::json!({ "error": e.to_string() })))
+        })?;
+    Ok(Json(page))
+}
+
+/// Resolve the configured scripts directory
+async fn get_scripts_dir(state: &AppState) -> Result<PathBuf, StatusCode> {
     let scripts_dir = db::get_setting(&state.db, "scripts_dir")
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .unwrap_or_else(|| "./scripts".to_string());
+    Ok(PathBuf::from(scripts_dir))
+}
+
+async fn list_scripts(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    let dir = get_scripts_dir(&state).await?;
+    Ok(Json(scripts::list_scripts(&dir)))
+}
 
-    let dir = PathBuf::from(&scripts_dir);
-    let mut scripts = Vec::new();
+fn script_error(e: ScriptError) -> (StatusCode, Json<serde_json::Value>) {
+    let status = match e {
+        ScriptError::InvalidName(_) => StatusCode::BAD_REQUEST,
+        ScriptError::NotFound => StatusCode::NOT_FOUND,
+        ScriptError::AlreadyExists => StatusCode::CONFLICT,
+        ScriptError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        ScriptError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string() })))
+}
 
-    if let Ok(entries) = fs::read_dir(&dir) {
-        for entry in entries.flatten() {
-            if let Some(name) = entry.file_name().to_str() {
-                if name.ends_with(".sh") || name.ends_with(".bash") {
-                    scripts.push(name.to_string());
-                }
+fn internal_error(_: StatusCode) -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({ "error": "Database error" })),
+    )
+}
+
+#[derive(Serialize)]
+struct ScriptContent {
+    name: String,
+    content: String,
+}
+
+async fn get_script(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<ScriptContent>, (StatusCode, Json<serde_json::Value>)> {
+    let dir = get_scripts_dir(&state).await.map_err(internal_error)?;
+    let content = scripts::read_script(&dir, &name).map_err(script_error)?;
+    Ok(Json(ScriptContent { name, content }))
+}
+
+#[derive(Deserialize)]
+struct SaveScriptRequest {
+    content: String,
+    #[serde(default = "default_executable")]
+    executable: bool,
+}
+
+fn default_executable() -> bool {
+    true
+}
+
+/// Create or overwrite a script file
+async fn save_script(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<SaveScriptRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let dir = get_scripts_dir(&state).await.map_err(internal_error)?;
+    scripts::write_script(&dir, &name, payload.content.as_bytes(), payload.executable)
+        .map_err(script_error)?;
+    tracing::info!("Script saved: {}", name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Upload a script file (multipart form with a `file` field and optional `executable`)
+async fn upload_script(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let bad_request = |msg: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": msg })),
+        )
+    };
+
+    let mut file: Option<(String, Vec<u8>)> = None;
+    let mut executable = true;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| bad_request("Invalid multipart body"))?
+    {
+        match field.name() {
+            Some("file") => {
+                let name = field
+                    .file_name()
+                    .map(String::from)
+                    .ok_or_else(|| bad_request("Missing file name"))?;
+                let data = field
+                    .bytes()
+                    .await
+                    .map_err(|_| bad_request("Failed to read file"))?;
+                file = Some((name, data.to_vec()));
             }
+            Some("executable") => {
+                let value = field.text().await.unwrap_or_default();
+                executable = value != "false" && value != "0";
+            }
+            _ => {}
         }
     }
 
-    Ok(Json(scripts))
+    let (name, content) = file.ok_or_else(|| bad_request("Missing 'file' field"))?;
+    let dir = get_scripts_dir(&state).await.map_err(internal_error)?;
+    scripts::write_script(&dir, &name, &content, executable).map_err(script_error)?;
+    tracing::info!("Script uploaded: {}", name);
+
+    Ok(Json(serde_json::json!({ "name": name })))
+}
+
+#[derive(Deserialize)]
+struct RenameScriptRequest {
+    new_name: String,
+}
+
+async fn rename_script(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<RenameScriptRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let dir = get_scripts_dir(&state).await.map_err(internal_error)?;
+    scripts::rename_script(&dir, &name, &payload.new_name).map_err(script_error)?;
+    tracing::info!("Script renamed: {} -> {}", name, payload.new_name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_script(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let dir = get_scripts_dir(&state).await.map_err(internal_error)?;
+    scripts::delete_script(&dir, &name).map_err(script_error)?;
+    tracing::info!("Script deleted: {}", name);
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[derive(Serialize)]
@@ -129,13 +466,97 @@ async fn get_history(
     Ok(Json(history))
 }
 
-async fn get_quick_actions(
+/// Download the full on-disk output of a task (not subject to the capture cap)
+async fn get_history_log(
+    _auth: AuthUser, // Any authenticated user
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    // Task IDs are UUIDs - reject anything else to keep the path inside the log dir
+    if uuid::Uuid::parse_str(&id).is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let content = tokio::fs::read(task_log_path(&id))
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        content,
+    ))
+}
+
+/// Per-step status of a pipeline run (empty for plain script tasks)
+async fn get_history_steps(
     _auth: AuthUser, // Any authenticated user
     State(state): State<AppState>,
-) -> Result<Json<Vec<QuickAction>>, StatusCode> {
+    Path(id): Path<String>,
+) -> Result<Json<Vec<PipelineStepRun>>, StatusCode> {
+    let steps = db::get_pipeline_step_runs(&state.db, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(steps))
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+    #[serde(default)]
+    limit: Option<i32>,
+}
+
+/// Tasks whose name or output contains all the words of `q`
+async fn search(
+    _auth: AuthUser, // Any authenticated user
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<TaskSearchHit>>, StatusCode> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let hits = db::search_task_history(&state.db, &query.q, limit)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(hits))
+}
+
+/// Runs the statistics of a quick action cover unless asked otherwise
+const DEFAULT_STATS_RUNS: u32 = 20;
+
+#[derive(Deserialize)]
+struct QuickActionsQuery {
+    /// Number of recent runs the statistics cover
+    stats_runs: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct QuickActionWithStats {
+    #[serde(flatten)]
+    action: QuickAction,
+    stats: QuickActionStats,
+}
+
+async fn get_quick_actions(
+    auth: AuthUser, // Any authenticated user, who sees the actions visible to them
+    State(state): State<AppState>,
+    Query(query): Query<QuickActionsQuery>,
+) -> Result<Json<Vec<QuickActionWithStats>>, StatusCode> {
     let actions = db::get_quick_actions(&state.db)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let window = query
+        .stats_runs
+        .unwrap_or(DEFAULT_STATS_RUNS)
+        .clamp(1, 1000);
+    let stats = db::get_quick_action_stats(&state.db, window)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let actions = actions
+        .into_iter()
+        .filter(|a| a.visible_to(auth.role, auth.user_id.as_deref()))
+        .map(|action| QuickActionWithStats {
+            stats: stats.get(&action.script_path).cloned().unwrap_or_default(),
+            action,
+        })
+        .collect();
     Ok(Json(actions))
 }
 
@@ -145,6 +566,23 @@ struct CreateQuickActionRequest {
     script_path: String,
     icon: Option<String>,
     display_order: Option<i32>,
+    cpu_limit_secs: Option<i64>,
+    memory_limit_mb: Option<i64>,
+    nofile_limit: Option<i64>,
+    #[serde(default)]
+    concurrency: ConcurrencyMode,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    params: Vec<QuickActionParam>,
+    #[serde(default)]
+    visibility: Visibility,
+    #[serde(default)]
+    allowed_users: Vec<String>,
+    #[serde(default)]
+    danger_level: DangerLevel,
+    #[serde(default)]
+    requires_confirmation: bool,
 }
 
 async fn create_quick_action(
@@ -159,7 +597,18 @@ async fn create_quick_action(
         script_path: payload.script_path,
         icon: payload.icon,
         display_order: payload.display_order.unwrap_or(0),
+        cpu_limit_secs: payload.cpu_limit_secs,
+        memory_limit_mb: payload.memory_limit_mb,
+        nofile_limit: payload.nofile_limit,
+        concurrency: payload.concurrency,
+        env: payload.env,
+        params: payload.params,
+        visibility: payload.visibility,
+        allowed_users: payload.allowed_users,
+        danger_level: payload.danger_level,
+        requires_confirmation: payload.requires_confirmation,
     };
+    validate_quick_action(&action)?;
 
     db::create_quick_action(&state.db, &action)
         .await
@@ -168,52 +617,283 @@ async fn create_quick_action(
     Ok(Json(action))
 }
 
+/// Fields of a quick action to change; those left out keep their value
+#[derive(Deserialize)]
+struct UpdateQuickActionRequest {
+    name: Option<String>,
+    script_path: Option<String>,
+    /// An empty string removes the icon
+    icon: Option<String>,
+    display_order: Option<i32>,
+    /// A value of 0 or less removes the override
+    cpu_limit_secs: Option<i64>,
+    memory_limit_mb: Option<i64>,
+    nofile_limit: Option<i64>,
+    concurrency: Option<ConcurrencyMode>,
+    /// Replaces the whole environment
+    env: Option<HashMap<String, String>>,
+    /// Replaces every parameter
+    params: Option<Vec<QuickActionParam>>,
+    visibility: Option<Visibility>,
+    /// Replaces the list of users
+    allowed_users: Option<Vec<String>>,
+    danger_level: Option<DangerLevel>,
+    requires_confirmation: Option<bool>,
+}
+
+async fn update_quick_action(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateQuickActionRequest>,
+) -> Result<Json<QuickAction>, StatusCode> {
+    let existing = db::get_quick_action(&state.db, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let positive = |value: Option<i64>, current: Option<i64>| match value {
+        Some(v) if v > 0 => Some(v),
+        Some(_) => None,
+        None => current,
+    };
+    let action = QuickAction {
+        name: payload.name.unwrap_or(existing.name),
+        script_path: payload.script_path.unwrap_or(existing.script_path),
+        icon: match payload.icon {
+            Some(icon) if icon.is_empty() => None,
+            Some(icon) => Some(icon),
+            None => existing.icon,
+        },
+        display_order: payload.display_order.unwrap_or(existing.display_order),
+        cpu_limit_secs: positive(payload.cpu_limit_secs, existing.cpu_limit_secs),
+        memory_limit_mb: positive(payload.memory_limit_mb, existing.memory_limit_mb),
+        nofile_limit: positive(payload.nofile_limit, existing.nofile_limit),
+        concurrency: payload.concurrency.unwrap_or(existing.concurrency),
+        env: payload.env.unwrap_or(existing.env),
+        params: payload.params.unwrap_or(existing.params),
+        visibility: payload.visibility.unwrap_or(existing.visibility),
+        allowed_users: payload.allowed_users.unwrap_or(existing.allowed_users),
+        danger_level: payload.danger_level.unwrap_or(existing.danger_level),
+        requires_confirmation: payload
+            .requires_confirmation
+            .unwrap_or(existing.requires_confirmation),
+        id: existing.id,
+    };
+    validate_quick_action(&action)?;
+
+    let updated = db::update_quick_action(&state.db, &action)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !updated {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(action))
+}
+
+#[derive(Deserialize)]
+struct ReorderQuickActionsRequest {
+    /// Quick action IDs in their new order; unlisted ones go last
+    ids: Vec<String>,
+}
+
+async fn reorder_quick_actions(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Json(payload): Json<ReorderQuickActionsRequest>,
+) -> Result<Json<Vec<QuickAction>>, StatusCode> {
+    let actions = db::get_quick_actions(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Every ID must be a quick action, listed once
+    let mut seen = std::collections::HashSet::new();
+    for id in &payload.ids {
+        if !seen.insert(id) || !actions.iter().any(|a| &a.id == id) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    db::reorder_quick_actions(&state.db, &payload.ids)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let actions = db::get_quick_actions(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(actions))
+}
+
+/// Reject quick actions without a name, with an environment variable name the shell
+/// can't use or with invalid parameters
+fn validate_quick_action(action: &QuickAction) -> Result<(), StatusCode> {
+    if action.name.trim().is_empty() || action.script_path.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !action
+        .env
+        .keys()
+        .all(|key| action_params::valid_env_key(key))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    action_params::validate_schema(&action.params).map_err(|_| StatusCode::BAD_REQUEST)?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ExportQuickActionsQuery {
+    /// Comma-separated IDs of the actions to export; all of them if left out
+    ids: Option<String>,
+}
+
+/// Quick actions as a pack another instance can import
+async fn export_quick_actions(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Query(query): Query<ExportQuickActionsQuery>,
+) -> Result<Json<ActionPack>, StatusCode> {
+    let ids: Vec<String> = query
+        .ids
+        .iter()
+        .flat_map(|ids| ids.split(','))
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    let pack = action_packs::export_pack(&state.db, &ids)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(pack))
+}
+
+#[derive(Deserialize)]
+struct ImportQuickActionsQuery {
+    #[serde(default)]
+    on_conflict: ConflictPolicy,
+}
+
+/// Add the actions of an exported pack, resolving name conflicts as asked
+async fn import_quick_actions(
+    AdminUser(auth): AdminUser, // Admin only
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuickActionsQuery>,
+    Json(pack): Json<ActionPack>,
+) -> Result<Json<PackImportSummary>, (StatusCode, Json<serde_json::Value>)> {
+    let scripts_dir = get_scripts_dir(&state).await.map_err(internal_error)?;
+    let summary = action_packs::import_pack(&state.db, pack, query.on_conflict, &scripts_dir)
+        .await
+        .map_err(|e| {
+            let status = match e {
+                ActionPackError::Invalid(_) => StatusCode::BAD_REQUEST,
+                ActionPackError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(serde_json::json!({ "error": e.to_string() })))
+        })?;
+    tracing::info!(
+        "Quick action pack imported by {}: {} created, {} replaced",
+        auth.username,
+        summary.created.len(),
+        summary.replaced.len()
+    );
+    Ok(Json(summary))
+}
+
+#[derive(Deserialize, Default)]
+struct ExecuteQuickActionRequest {
+    /// Values of the action's parameters by name
+    #[serde(default)]
+    params: HashMap<String, serde_json::Value>,
+    /// The action's name, typed to confirm a run that needs confirmation
+    confirm: Option<String>,
+}
+
 async fn execute_quick_action(
-    _auth: AuthUser,
+    auth: AuthUser,
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+    payload: Option<Json<ExecuteQuickActionRequest>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let error = |status, msg: &str| (status, Json(serde_json::json!({ "error": msg })));
+
     // 1. Get Quick Action
     let actions = db::get_quick_actions(&state.db)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
 
     let action = actions
         .into_iter()
         .find(|a| a.id == id)
-        .ok_or(StatusCode::NOT_FOUND)?;
+        .filter(|a| a.visible_to(auth.role, auth.user_id.as_deref()))
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, "Quick action not found"))?;
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+    if !action.is_confirmed_by(payload.confirm.as_deref()) {
+        return Err(error(
+            StatusCode::PRECONDITION_REQUIRED,
+            &format!("Type \"{}\" to confirm this action", action.name),
+        ));
+    }
+    let env = action_params::run_env(&action, &payload.params)
+        .map_err(|e| error(StatusCode::BAD_REQUEST, &e.to_string()))?;
 
     // 2. Prepare paths
     let scripts_dir = db::get_setting(&state.db, "scripts_dir")
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?
         .unwrap_or_else(|| "./scripts".to_string());
 
     let script_path = format!("{}/{}", scripts_dir, action.script_path);
     let task_id = uuid::Uuid::new_v4().to_string();
-    let task_id_clone = task_id.clone();
+    let limits = ResourceLimits::from_settings(&state.db)
+        .await
+        .with_action_overrides(&action);
+
+    if action.needs_confirmation() {
+        if let Err(e) = db::record_action_audit(&state.db, &action, &auth.username, &task_id).await
+        {
+            tracing::warn!("Failed to write quick action audit log: {}", e);
+        }
+    }
 
     // 3. Run safely
-    let db_clone = state.db.clone();
-    // Use a transient registry since we don't support API-based cancellation yet
-    let registry = crate::services::executor::create_task_registry();
-
-    tokio::spawn(async move {
-        let _ = crate::services::executor::run_script_task(
-            script_path,
-            task_id_clone,
-            action.script_path,
-            db_clone,
-            registry,
-            None, // No real-time streaming to caller, just DB updates
-        )
-        .await;
-    });
+    let result = crate::services::executor::run_script_task(
+        script_path,
+        task_id.clone(),
+        action.script_path,
+        state.db.clone(),
+        state.tasks.clone(),
+        limits,
+        action.concurrency,
+        env,
+        None, // No real-time streaming to caller, just DB updates
+    )
+    .await;
+
+    // A start failure is recorded in history; only a busy script is reported here
+    if let Err(e) = result {
+        if let Some(busy) = e.downcast_ref::<ScriptBusy>() {
+            return Err(error(StatusCode::CONFLICT, &busy.to_string()));
+        }
+    }
 
     // 4. Return task_id so frontend can navigate/poll
     Ok(Json(serde_json::json!({ "task_id": task_id })))
 }
 
+#[derive(Deserialize)]
+struct AuditQuery {
+    limit: Option<i32>,
+}
+
+/// Confirmed runs of dangerous quick actions, newest first
+async fn get_action_audit_log(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<ActionAuditEntry>>, StatusCode> {
+    let entries = db::get_action_audit_log(&state.db, query.limit.unwrap_or(100).clamp(1, 1000))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(entries))
+}
+
 async fn delete_quick_action(
     _auth: AdminUser, // Admin only
     State(state): State<AppState>,
@@ -225,6 +905,62 @@ async fn delete_quick_action(
     Ok(StatusCode::NO_CONTENT)
 }
 
+// ============ Scheduled Task Routes (Admin Only) ============
+
+#[derive(Deserialize)]
+struct CreateScheduledTaskRequest {
+    script: String,
+    /// RFC 3339 timestamp, e.g. `2025-01-31T02:00:00Z`
+    run_at: String,
+}
+
+async fn list_scheduled_tasks(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ScheduledTask>>, StatusCode> {
+    let tasks = db::get_pending_scheduled_tasks(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(tasks))
+}
+
+async fn create_scheduled_task(
+    AdminUser(auth): AdminUser, // Admin only
+    State(state): State<AppState>,
+    Json(payload): Json<CreateScheduledTaskRequest>,
+) -> Result<Json<ScheduledTask>, (StatusCode, Json<serde_json::Value>)> {
+    scripts::validate_script_name(&payload.script).map_err(script_error)?;
+
+    let run_at = chrono::DateTime::parse_from_rfc3339(&payload.run_at)
+        .map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "run_at must be an RFC 3339 timestamp" })),
+            )
+        })?
+        .with_timezone(&chrono::Utc);
+
+    let task = scheduler::schedule_task(&state.db, &payload.script, run_at, Some(&auth.username))
+        .await
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(task))
+}
+
+async fn cancel_scheduled_task(
+    _auth: AdminUser, // Admin only
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let cancelled = db::cancel_scheduled_task(&state.db, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if cancelled {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
 // ============ User Management Routes (Admin Only) ============
 
 #[derive(Serialize)]
@@ -235,10 +971,32 @@ struct UserResponse {
     role: UserRole,
     is_active: bool,
     created_at: String,
+    expires_at: Option<String>,
+    session_hours: Option<i64>,
+    idle_timeout_minutes: Option<i64>,
+    /// The account has expired and can't sign in
+    expired: bool,
+    /// The account expires within `EXPIRY_WARNING_DAYS`
+    expires_soon: bool,
+    email: Option<String>,
+    phone: Option<String>,
+    /// Changes with the avatar, for `/users/:id/avatar?v=`; none without one
+    avatar_version: Option<String>,
 }
 
+/// Days before an account expires from which the users list warns about it
+const EXPIRY_WARNING_DAYS: i64 = 7;
+
 impl From<User> for UserResponse {
     fn from(u: User) -> Self {
+        let expired = u.is_expired();
+        let expires_soon = !expired
+            && u.expires_at
+                .as_deref()
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                .is_some_and(|t| {
+                    t <= chrono::Utc::now() + chrono::Duration::days(EXPIRY_WARNING_DAYS)
+                });
         UserResponse {
             id: u.id,
             username: u.username,
@@ -246,10 +1004,41 @@ impl From<User> for UserResponse {
             role: u.role,
             is_active: u.is_active,
             created_at: u.created_at,
+            expires_at: u.expires_at,
+            session_hours: u.session_hours,
+            idle_timeout_minutes: u.idle_timeout_minutes,
+            expired,
+            expires_soon,
+            email: None,
+            phone: None,
+            avatar_version: None,
         }
     }
 }
 
+impl UserResponse {
+    fn with_profile(mut self, profile: Option<&UserProfile>) -> Self {
+        if let Some(profile) = profile {
+            self.email = profile.email.clone();
+            self.phone = profile.phone.clone();
+            self.avatar_version = profile.avatar_etag.clone();
+        }
+        self
+    }
+}
+
+/// Check that an account expiry is an RFC 3339 timestamp
+fn validate_expires_at(expires_at: &str) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    chrono::DateTime::parse_from_rfc3339(expires_at)
+        .map(|_| ())
+        .map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "expires_at must be an RFC 3339 timestamp" })),
+            )
+        })
+}
+
 async fn list_users(
     _auth: AdminUser,
     State(state): State<AppState>,
@@ -257,7 +1046,18 @@ async fn list_users(
     let users = db::get_all_users(&state.db)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(users.into_iter().map(UserResponse::from).collect()))
+    let profiles = db::get_user_profiles(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(
+        users
+            .into_iter()
+            .map(|u| {
+                let profile = profiles.get(&u.id);
+                UserResponse::from(u).with_profile(profile)
+            })
+            .collect(),
+    ))
 }
 
 #[derive(Deserialize)]
@@ -265,6 +1065,8 @@ struct CreateUserRequest {
     username: String,
     password: String,
     display_name: Option<String>,
+    /// RFC 3339 time after which the account can't sign in
+    expires_at: Option<String>,
 }
 
 async fn create_user(
@@ -280,6 +1082,10 @@ async fn create_user(
         ));
     }
 
+    if let Some(expires_at) = &payload.expires_at {
+        validate_expires_at(expires_at)?;
+    }
+
     // Check if username already exists
     if let Ok(Some(_)) = db::get_user_by_username(&state.db, &payload.username).await {
         return Err((
@@ -303,6 +1109,9 @@ async fn create_user(
         role: UserRole::Client,
         is_active: true,
         created_at: chrono::Utc::now().to_rfc3339(),
+        expires_at: payload.expires_at,
+        session_hours: None,
+        idle_timeout_minutes: None,
     };
 
     db::create_user(&state.db, &user).await.map_err(|_| {
@@ -315,6 +1124,92 @@ async fn create_user(
     Ok(Json(UserResponse::from(user)))
 }
 
+#[derive(Deserialize)]
+struct ExportUsersQuery {
+    /// `json` (the default) or `csv`
+    format: Option<String>,
+}
+
+/// Every user without password hashes, as JSON or CSV
+async fn export_users(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Query(query): Query<ExportUsersQuery>,
+) -> Result<axum::response::Response, StatusCode> {
+    let users = db::get_all_users(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    match query.format.as_deref().unwrap_or("json") {
+        "json" => Ok(Json(
+            users
+                .into_iter()
+                .map(UserResponse::from)
+                .collect::<Vec<_>>(),
+        )
+        .into_response()),
+        "csv" => Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"users.csv\"",
+                ),
+            ],
+            user_import::users_csv(&users),
+        )
+            .into_response()),
+        _ => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+#[derive(Serialize)]
+struct ImportUsersResponse {
+    created: usize,
+    failed: usize,
+    rows: Vec<RowResult>,
+}
+
+/// Create users from a CSV (`Content-Type: text/csv`) or a JSON array of rows
+async fn import_users(
+    AdminUser(auth): AdminUser,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ImportUsersResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let bad_request = |msg: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": msg })),
+        )
+    };
+    let is_csv = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/csv"));
+    let rows: Vec<ImportRow> = if is_csv {
+        let text =
+            std::str::from_utf8(&body).map_err(|_| bad_request("CSV must be UTF-8".to_string()))?;
+        user_import::parse_csv(text).map_err(bad_request)?
+    } else {
+        serde_json::from_slice(&body).map_err(|e| bad_request(e.to_string()))?
+    };
+    if rows.len() > user_import::MAX_ROWS {
+        return Err(bad_request(format!(
+            "At most {} users can be imported at once",
+            user_import::MAX_ROWS
+        )));
+    }
+
+    let rows = user_import::import_users(&state.db, rows).await;
+    let created = rows.iter().filter(|r| r.created).count();
+    tracing::info!("{} users imported by {}", created, auth.username);
+    Ok(Json(ImportUsersResponse {
+        created,
+        failed: rows.len() - created,
+        rows,
+    }))
+}
+
 async fn get_user(
     _auth: AdminUser,
     State(state): State<AppState>,
@@ -324,13 +1219,24 @@ async fn get_user(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
-    Ok(Json(UserResponse::from(user)))
+    let profile = db::get_user_profile(&state.db, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(
+        UserResponse::from(user).with_profile(profile.as_ref()),
+    ))
 }
 
 #[derive(Deserialize)]
 struct UpdateUserRequest {
     display_name: Option<String>,
     is_active: Option<bool>,
+    /// RFC 3339 time after which the account can't sign in; empty to never expire
+    expires_at: Option<String>,
+    /// Hours the user's new sessions last; `0` to follow their role's policy
+    session_hours: Option<i64>,
+    /// Minutes of inactivity that end the user's new sessions; `0` to follow their role's policy
+    idle_timeout_minutes: Option<i64>,
 }
 
 async fn update_user(
@@ -338,11 +1244,17 @@ async fn update_user(
     State(state): State<AppState>,
     Path(id): Path<String>,
     Json(payload): Json<UpdateUserRequest>,
-) -> Result<Json<UserResponse>, StatusCode> {
+) -> Result<Json<UserResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "User not found" })),
+        )
+    };
     let user = db::get_user_by_id(&state.db, &id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(not_found)?;
 
     let is_active = payload.is_active.unwrap_or(user.is_active);
 
@@ -353,14 +1265,51 @@ async fn update_user(
         None => user.display_name.as_deref(),  // Not provided = keep existing
     };
 
-    db::update_user(&state.db, &id, display_name, is_active)
+    // Same as display_name: None = keep, Some("") = never expire
+    let expires_at = match &payload.expires_at {
+        Some(t) if t.is_empty() => None,
+        Some(t) => {
+            validate_expires_at(t)?;
+            Some(t.as_str())
+        }
+        None => user.expires_at.as_deref(),
+    };
+
+    // None = keep, 0 = follow the role's policy
+    let policy_value = |value: Option<i64>, current: Option<i64>, max: i64, name: &str| match value
+    {
+        Some(0) => Ok(None),
+        Some(v) if (1..=max).contains(&v) => Ok(Some(v)),
+        Some(_) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": format!("{} must be between 0 and {}", name, max) })),
+        )),
+        None => Ok(current),
+    };
+    let session_hours = policy_value(
+        payload.session_hours,
+        user.session_hours,
+        MAX_SESSION_HOURS,
+        "session_hours",
+    )?;
+    let idle_timeout_minutes = policy_value(
+        payload.idle_timeout_minutes,
+        user.idle_timeout_minutes,
+        MAX_SESSION_HOURS * 60,
+        "idle_timeout_minutes",
+    )?;
+
+    db::update_user(&state.db, &id, display_name, is_active, expires_at)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
+    db::set_user_session_policy(&state.db, &id, session_hours, idle_timeout_minutes)
+        .await
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
 
     let updated_user = db::get_user_by_id(&state.db, &id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(not_found)?;
 
     Ok(Json(UserResponse::from(updated_user)))
 }
@@ -373,6 +1322,10 @@ async fn delete_user(
     db::delete_user(&state.db, &id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let avatar_dir = avatars::avatar_dir(&state.config.paths.data_dir);
+    if let Err(e) = avatars::delete_avatar(&avatar_dir, &id).await {
+        tracing::warn!("Failed to delete avatar of user {}: {}", id, e);
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -500,3 +1453,307 @@ async fn change_own_password(
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+// ============ User Profile (any authenticated user) ============
+
+/// Longest display name, e-mail address and phone number
+const MAX_DISPLAY_NAME_LEN: usize = 100;
+const MAX_EMAIL_LEN: usize = 254;
+const MAX_PHONE_LEN: usize = 32;
+
+#[derive(Serialize)]
+struct ProfileResponse {
+    id: String,
+    username: String,
+    display_name: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    /// Changes with the avatar, for `/users/:id/avatar?v=`; none without one
+    avatar_version: Option<String>,
+}
+
+fn profile_response(user: User, profile: UserProfile) -> ProfileResponse {
+    ProfileResponse {
+        id: user.id,
+        username: user.username,
+        display_name: user.display_name,
+        email: profile.email,
+        phone: profile.phone,
+        avatar_version: profile.avatar_etag,
+    }
+}
+
+/// The signed-in user and their profile; the admin from the environment has none
+async fn own_user(
+    auth: &AuthUser,
+    state: &AppState,
+) -> Result<(User, UserProfile), (StatusCode, Json<serde_json::Value>)> {
+    let Some(user_id) = &auth.user_id else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "The admin from the environment has no profile" })),
+        ));
+    };
+    let user = db::get_user_by_id(&state.db, user_id)
+        .await
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "User not found" })),
+        ))?;
+    let profile = db::get_user_profile(&state.db, user_id)
+        .await
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?
+        .unwrap_or_else(|| UserProfile {
+            user_id: user_id.clone(),
+            ..Default::default()
+        });
+    Ok((user, profile))
+}
+
+async fn get_own_profile(
+    auth: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<ProfileResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let (user, profile) = own_user(&auth, &state).await?;
+    Ok(Json(profile_response(user, profile)))
+}
+
+/// Change the signed-in user's display name, contact fields and avatar
+///
+/// A multipart form with any of `display_name`, `email` and `phone` (empty to
+/// clear), an `avatar` image and `remove_avatar=true`. Fields left out are kept.
+async fn update_own_profile(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Json<ProfileResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let bad_request = |msg: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": msg })),
+        )
+    };
+    let (user, mut profile) = own_user(&auth, &state).await?;
+    let mut display_name = user.display_name.clone();
+    let mut avatar: Option<Vec<u8>> = None;
+    let mut remove_avatar = false;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| bad_request("Invalid multipart body".to_string()))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "avatar" {
+            let data = field
+                .bytes()
+                .await
+                .map_err(|_| bad_request("Failed to read avatar".to_string()))?;
+            avatar = Some(data.to_vec());
+            continue;
+        }
+        let value = field.text().await.unwrap_or_default().trim().to_string();
+        let optional = |max_len: usize| {
+            if value.chars().count() > max_len || value.chars().any(char::is_control) {
+                Err(bad_request(format!("Invalid {}", name)))
+            } else {
+                Ok((!value.is_empty()).then(|| value.clone()))
+            }
+        };
+        match name.as_str() {
+            "display_name" => display_name = optional(MAX_DISPLAY_NAME_LEN)?,
+            "email" => {
+                let email = optional(MAX_EMAIL_LEN)?;
+                let valid = email.as_deref().is_none_or(|e| {
+                    e.split_once('@').is_some_and(|(local, domain)| {
+                        !local.is_empty()
+                            && domain.contains('.')
+                            && !e.contains(char::is_whitespace)
+                    })
+                });
+                if !valid {
+                    return Err(bad_request("Invalid email".to_string()));
+                }
+                profile.email = email;
+            }
+            "phone" => {
+                let phone = optional(MAX_PHONE_LEN)?;
+                let valid = phone.as_deref().is_none_or(|p| {
+                    p.chars()
+                        .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | ' ' | '(' | ')'))
+                });
+                if !valid {
+                    return Err(bad_request("Invalid phone".to_string()));
+                }
+                profile.phone = phone;
+            }
+            "remove_avatar" => remove_avatar = value == "true",
+            _ => {}
+        }
+    }
+
+    let avatar_dir = avatars::avatar_dir(&state.config.paths.data_dir);
+    if let Some(data) = avatar {
+        let (content_type, etag) =
+            avatars::check_avatar(&data).map_err(|e| bad_request(e.to_string()))?;
+        avatars::save_avatar(&avatar_dir, &user.id, &data)
+            .await
+            .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
+        profile.avatar_type = Some(content_type.to_string());
+        profile.avatar_etag = Some(etag);
+    } else if remove_avatar {
+        avatars::delete_avatar(&avatar_dir, &user.id)
+            .await
+            .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
+        profile.avatar_type = None;
+        profile.avatar_etag = None;
+    }
+
+    db::update_user(
+        &state.db,
+        &user.id,
+        display_name.as_deref(),
+        user.is_active,
+        user.expires_at.as_deref(),
+    )
+    .await
+    .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
+    db::save_user_profile(&state.db, &profile)
+        .await
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    Ok(Json(profile_response(
+        User {
+            display_name,
+            ..user
+        },
+        profile,
+    )))
+}
+
+/// Seconds browsers may reuse an avatar before checking it again
+const AVATAR_MAX_AGE_SECS: u32 = 24 * 60 * 60;
+
+/// A user's avatar image, for any signed-in user
+async fn get_user_avatar(
+    _auth: AuthUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, StatusCode> {
+    let profile = db::get_user_profile(&state.db, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (Some(content_type), Some(etag)) = (profile.avatar_type, profile.avatar_etag) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let etag = format!("\"{}\"", etag);
+    let cache_control = format!("private, max-age={}", AVATAR_MAX_AGE_SECS);
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control)],
+        )
+            .into_response());
+    }
+
+    let avatar_dir = avatars::avatar_dir(&state.config.paths.data_dir);
+    let data = avatars::read_avatar(&avatar_dir, &id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+// ============ User Preferences (any authenticated user) ============
+
+/// Longest preference key
+const MAX_PREFERENCE_KEY_LEN: usize = 64;
+/// Largest preference value, as JSON
+const MAX_PREFERENCE_VALUE_LEN: usize = 16 * 1024;
+/// Most preferences a user may keep
+const MAX_PREFERENCES: usize = 100;
+
+/// Key a user's preferences are stored under; the admin from the environment
+/// has no user ID
+fn preferences_key(auth: &AuthUser) -> &str {
+    auth.user_id.as_deref().unwrap_or("admin")
+}
+
+/// The signed-in user's preferences, e.g. theme, dashboard layout, default page
+/// and locale
+async fn get_own_preferences(
+    auth: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<BTreeMap<String, serde_json::Value>>, StatusCode> {
+    let preferences = db::get_user_preferences(&state.db, preferences_key(&auth))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(preferences))
+}
+
+/// Merge the given preferences into the signed-in user's; `null` removes one
+async fn update_own_preferences(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Json(payload): Json<BTreeMap<String, serde_json::Value>>,
+) -> Result<Json<BTreeMap<String, serde_json::Value>>, (StatusCode, Json<serde_json::Value>)> {
+    let bad_request = |msg: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": msg })),
+        )
+    };
+    for (key, value) in &payload {
+        let valid_key = !key.is_empty()
+            && key.len() <= MAX_PREFERENCE_KEY_LEN
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !valid_key {
+            return Err(bad_request(format!("Invalid preference key: {}", key)));
+        }
+        if value.to_string().len() > MAX_PREFERENCE_VALUE_LEN {
+            return Err(bad_request(format!("Preference {} is too large", key)));
+        }
+    }
+
+    let user_key = preferences_key(&auth);
+    let mut preferences = db::get_user_preferences(&state.db, user_key)
+        .await
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
+    for (key, value) in &payload {
+        if value.is_null() {
+            preferences.remove(key);
+        } else {
+            preferences.insert(key.clone(), value.clone());
+        }
+    }
+    if preferences.len() > MAX_PREFERENCES {
+        return Err(bad_request(format!(
+            "At most {} preferences can be kept",
+            MAX_PREFERENCES
+        )));
+    }
+
+    db::set_user_preferences(&state.db, user_key, &payload)
+        .await
+        .map_err(|_| internal_error(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(Json(preferences))
+}
@@ -11,12 +11,17 @@ use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 
-use crate::db::{LoginAttempt, UserRole};
+use crate::config::Config;
+use crate::db::{LoginAttempt, Session, UserRole};
+use crate::routes::access_log::AccessLogUser;
 use crate::routes::api::AppState;
 use crate::services::auth::{
     authenticate_admin, authenticate_user, create_user_session, validate_session,
     SESSION_DURATION_DAYS,
 };
+use crate::services::notifications::{self, Notification, NotificationKind, NotificationLevel};
+use crate::services::system_notifications::{Audience, SystemNotification};
+use crate::services::webhooks;
 
 pub const SESSION_COOKIE_NAME: &str = "session_id";
 const ADMIN_DISPLAY_NAME_DEFAULT: &str = "Administrator";
@@ -37,25 +42,22 @@ pub fn create_auth_router() -> Router<AppState> {
         .route("/login-history", get(get_login_history))
 }
 
-/// Helper to check if running in production/secure mode
-fn is_secure_mode() -> bool {
-    let prod = std::env::var("PRODUCTION")
-        .map(|v| v.to_lowercase() == "true" || v == "1")
-        .unwrap_or(false);
-    let secure = std::env::var("SECURE_COOKIES")
-        .map(|v| v.to_lowercase() == "true" || v == "1")
-        .unwrap_or(false);
-    prod || secure
+/// Helper to check if running in production/secure mode, or serving HTTPS itself
+fn is_secure_mode(config: &Config) -> bool {
+    crate::services::tls::is_active() || config.secure_cookies()
 }
 
-/// Build a session cookie with proper security flags
-fn build_session_cookie(session_id: String) -> Cookie<'static> {
-    Cookie::build((SESSION_COOKIE_NAME, session_id))
-        .path("/")
+/// Build a session cookie with proper security flags, kept as long as the session lasts
+fn build_session_cookie(config: &Config, session: &Session) -> Cookie<'static> {
+    let max_age = chrono::DateTime::parse_from_rfc3339(&session.expires_at)
+        .map(|t| (t.with_timezone(&Utc) - Utc::now()).num_seconds().max(0))
+        .unwrap_or(SESSION_DURATION_DAYS * 24 * 60 * 60);
+    Cookie::build((SESSION_COOKIE_NAME, session.id.clone()))
+        .path(config.cookie_path().to_string())
         .http_only(true)
-        .secure(is_secure_mode())
+        .secure(is_secure_mode(config))
         .same_site(axum_extra::extract::cookie::SameSite::Lax)
-        .max_age(time::Duration::days(SESSION_DURATION_DAYS))
+        .max_age(time::Duration::seconds(max_age))
         .build()
 }
 
@@ -156,6 +158,74 @@ async fn record_attempt(
         attempted_at: Utc::now().to_rfc3339(),
     };
     let _ = crate::db::record_login_attempt(pool, &attempt).await;
+
+    let event = if success {
+        "login.succeeded"
+    } else {
+        "login.failed"
+    };
+    webhooks::emit(
+        event,
+        serde_json::json!({
+            "username": attempt.username,
+            "ip_address": attempt.ip_address,
+            "failure_reason": attempt.failure_reason,
+        }),
+    );
+}
+
+/// Tell the admins connected to the UI about a sign-in
+fn announce_sign_in(state: &AppState, username: &str, ip: Option<&str>) {
+    state.notification_hub.publish(SystemNotification::new(
+        NotificationKind::Security,
+        NotificationLevel::Info,
+        format!("{} signed in", username),
+        format!(
+            "{} signed in from {}.",
+            username,
+            ip.unwrap_or("an unknown address")
+        ),
+        Audience::Admins,
+    ));
+}
+
+/// Send a security notification when a failed attempt starts a lockout tier
+async fn notify_if_locked_out(pool: &crate::db::DbPool, username: &str, ip: Option<&str>) {
+    let one_hour_ago = (Utc::now() - Duration::hours(1)).to_rfc3339();
+    let failed_attempts_user = crate::db::get_recent_failed_attempts(pool, username, &one_hour_ago)
+        .await
+        .unwrap_or(0);
+    let failed_attempts_ip = match ip {
+        Some(ip_addr) => crate::db::get_recent_failed_attempts_by_ip(pool, ip_addr, &one_hour_ago)
+            .await
+            .unwrap_or(0),
+        None => 0,
+    };
+    let failed_attempts = std::cmp::max(failed_attempts_user, failed_attempts_ip);
+
+    if !RATE_LIMIT_TIERS
+        .iter()
+        .any(|&(threshold, _)| threshold == failed_attempts)
+    {
+        return;
+    }
+    let minutes = get_lockout_duration(failed_attempts).unwrap_or(0);
+    notifications::notify(
+        pool,
+        Notification::new(
+            NotificationKind::Security,
+            NotificationLevel::Warning,
+            format!("Sign-in locked for {}", username),
+            format!(
+                "{} failed sign-in attempts for {} from {} in the last hour; \
+                 sign-in is refused for {} minute(s).",
+                failed_attempts,
+                username,
+                ip.unwrap_or("an unknown address"),
+                minutes
+            ),
+        ),
+    );
 }
 
 #[derive(Deserialize)]
@@ -182,16 +252,13 @@ struct UserInfo {
 }
 
 /// Helper to get client IP, respecting proxy headers if configured
-fn get_client_ip(
+pub(crate) fn get_client_ip(
+    config: &Config,
     headers: &HeaderMap,
     connect_info: Option<&ConnectInfo<SocketAddr>>,
 ) -> Option<String> {
     // Check if we trust proxy headers
-    let trust_proxy = std::env::var("TRUST_PROXY")
-        .map(|v| v.to_lowercase() == "true" || v == "1")
-        .unwrap_or(false);
-
-    if trust_proxy {
+    if config.server.trust_proxy {
         // Try Cloudflare header first
         if let Some(ip) = headers
             .get("cf-connecting-ip")
@@ -222,7 +289,7 @@ async fn login(
     connect_info: Option<ConnectInfo<SocketAddr>>,
     Json(payload): Json<LoginRequest>,
 ) -> impl IntoResponse {
-    let ip = get_client_ip(&headers, connect_info.as_ref());
+    let ip = get_client_ip(&state.config, &headers, connect_info.as_ref());
 
     // Check rate limiting
     if let Some(remaining_seconds) =
@@ -281,11 +348,12 @@ async fn login(
         };
 
         // Record successful login
+        announce_sign_in(&state, &payload.username, ip.as_deref());
         record_attempt(&state.db, &payload.username, ip, true, None).await;
 
         return (
             StatusCode::OK,
-            jar.add(build_session_cookie(session.id)),
+            jar.add(build_session_cookie(&state.config, &session)),
             Json(LoginResponse {
                 success: true,
                 user: Some(UserInfo {
@@ -325,11 +393,12 @@ async fn login(
             };
 
         // Record successful login
+        announce_sign_in(&state, &payload.username, ip.as_deref());
         record_attempt(&state.db, &payload.username, ip, true, None).await;
 
         return (
             StatusCode::OK,
-            jar.add(build_session_cookie(session.id)),
+            jar.add(build_session_cookie(&state.config, &session)),
             Json(LoginResponse {
                 success: true,
                 user: Some(UserInfo {
@@ -348,11 +417,12 @@ async fn login(
     record_attempt(
         &state.db,
         &payload.username,
-        ip,
+        ip.clone(),
         false,
         Some("Invalid credentials"),
     )
     .await;
+    notify_if_locked_out(&state.db, &payload.username, ip.as_deref()).await;
 
     (
         StatusCode::UNAUTHORIZED,
@@ -372,9 +442,9 @@ async fn logout(State(state): State<AppState>, jar: CookieJar) -> impl IntoRespo
     }
 
     let cookie = Cookie::build((SESSION_COOKIE_NAME, ""))
-        .path("/")
+        .path(state.config.cookie_path().to_string())
         .http_only(true)
-        .secure(is_secure_mode())
+        .secure(is_secure_mode(&state.config))
         .same_site(axum_extra::extract::cookie::SameSite::Lax)
         .max_age(time::Duration::seconds(0))
         .build();
@@ -452,6 +522,7 @@ async fn me(State(state): State<AppState>, jar: CookieJar) -> Json<MeResponse> {
 /// Authenticated user info extracted from session
 #[derive(Debug, Clone)]
 pub struct AuthUser {
+    pub session_id: String,
     pub user_id: Option<String>,
     #[allow(dead_code)]
     pub username: String,
@@ -459,7 +530,6 @@ pub struct AuthUser {
 }
 
 impl AuthUser {
-    #[allow(dead_code)]
     pub fn is_admin(&self) -> bool {
         self.role == UserRole::Admin
     }
@@ -502,11 +572,17 @@ impl FromRequestParts<AppState> for AuthUser {
         };
 
         match validate_session(&state.db, &session_id).await {
-            Some(session) => Ok(AuthUser {
-                user_id: session.user_id,
-                username: session.username,
-                role: session.user_role,
-            }),
+            Some(session) => {
+                if let Some(slot) = parts.extensions.get::<AccessLogUser>() {
+                    slot.set(&session.username);
+                }
+                Ok(AuthUser {
+                    session_id,
+                    user_id: session.user_id,
+                    username: session.username,
+                    role: session.user_role,
+                })
+            }
             None => Err((
                 StatusCode::UNAUTHORIZED,
                 Json(serde_json::json!({ "error": "Session expired or invalid" })),
@@ -0,0 +1,138 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use bollard::Docker;
+use serde::{Deserialize, Serialize};
+
+use crate::routes::api::AppState;
+use crate::routes::auth::AdminUser;
+use crate::services::docker::{
+    self, ContainerAction, ContainerInfo, ContainerStats, DockerError, DEFAULT_LOG_TAIL,
+};
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn docker_error(e: DockerError) -> ApiError {
+    let status = match e {
+        DockerError::Disabled => StatusCode::NOT_IMPLEMENTED,
+        DockerError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        DockerError::NotFound(_) => StatusCode::NOT_FOUND,
+        DockerError::Rejected(_) => StatusCode::CONFLICT,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string() })))
+}
+
+pub fn create_docker_router() -> Router<AppState> {
+    Router::new()
+        .route("/containers", get(list_containers))
+        .route("/containers/:id/stats", get(get_container_stats))
+        .route("/containers/:id/logs", get(get_container_logs))
+        .route("/containers/:id/start", post(start_container))
+        .route("/containers/:id/stop", post(stop_container))
+        .route("/containers/:id/restart", post(restart_container))
+}
+
+async fn connect(state: &AppState) -> Result<Docker, ApiError> {
+    docker::connect(&state.db).await.map_err(docker_error)
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    /// Include stopped containers
+    #[serde(default)]
+    all: bool,
+}
+
+async fn list_containers(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<ContainerInfo>>, ApiError> {
+    let docker = connect(&state).await?;
+    let containers = docker::list_containers(&docker, query.all)
+        .await
+        .map_err(docker_error)?;
+    Ok(Json(containers))
+}
+
+async fn get_container_stats(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ContainerStats>, ApiError> {
+    let docker = connect(&state).await?;
+    let stats = docker::container_stats(&docker, &id)
+        .await
+        .map_err(docker_error)?;
+    Ok(Json(stats))
+}
+
+#[derive(Deserialize)]
+struct LogsQuery {
+    #[serde(default = "default_tail")]
+    tail: usize,
+}
+
+fn default_tail() -> usize {
+    DEFAULT_LOG_TAIL
+}
+
+#[derive(Serialize)]
+struct LogsResponse {
+    lines: Vec<String>,
+}
+
+async fn get_container_logs(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> Result<Json<LogsResponse>, ApiError> {
+    let docker = connect(&state).await?;
+    let lines = docker::container_logs(&docker, &id, query.tail)
+        .await
+        .map_err(docker_error)?;
+    Ok(Json(LogsResponse { lines }))
+}
+
+async fn control(
+    auth: AdminUser,
+    state: AppState,
+    id: String,
+    action: ContainerAction,
+) -> Result<StatusCode, ApiError> {
+    let docker = connect(&state).await?;
+    docker::control_container(&docker, &id, action)
+        .await
+        .map_err(docker_error)?;
+    tracing::info!("Container {}: {} by {}", id, action, auth.0.username);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn start_container(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    control(auth, state, id, ContainerAction::Start).await
+}
+
+async fn stop_container(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    control(auth, state, id, ContainerAction::Stop).await
+}
+
+async fn restart_container(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    control(auth, state, id, ContainerAction::Restart).await
+}
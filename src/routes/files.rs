@@ -0,0 +1,251 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
+    routing::{get, post},
+    Router,
+};
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+
+use crate::db::{self, FileAuditEntry};
+use crate::routes::api::AppState;
+use crate::routes::auth::AdminUser;
+use crate::services::files::{self, DirListing, FileError, FileRoot};
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn file_error(e: &FileError) -> ApiError {
+    let status = match e {
+        FileError::Invalid(_) => StatusCode::BAD_REQUEST,
+        FileError::NotFound(_) => StatusCode::NOT_FOUND,
+        FileError::Forbidden(_) => StatusCode::FORBIDDEN,
+        FileError::Conflict(_) => StatusCode::CONFLICT,
+        FileError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        FileError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string() })))
+}
+
+/// File manager over the roots in the `file_manager_roots` setting (admin only)
+pub fn create_files_router() -> Router<AppState> {
+    Router::new()
+        .route("/roots", get(list_roots))
+        .route("/roots/:root", get(list_dir).delete(delete_entry))
+        .route("/roots/:root/download", get(download))
+        .route("/roots/:root/upload", post(upload))
+        .route("/roots/:root/rename", post(rename))
+        .route("/audit", get(audit_log))
+}
+
+#[derive(Deserialize)]
+struct PathQuery {
+    #[serde(default)]
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct UploadQuery {
+    path: String,
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(Deserialize)]
+struct DeleteQuery {
+    path: String,
+    #[serde(default)]
+    recursive: bool,
+}
+
+#[derive(Deserialize)]
+struct RenameRequest {
+    from: String,
+    to: String,
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    limit: Option<i32>,
+}
+
+async fn find_root(state: &AppState, name: &str) -> Result<FileRoot, ApiError> {
+    files::find_root(&state.db, name)
+        .await
+        .map_err(|e| file_error(&e))
+}
+
+/// Configured roots
+async fn list_roots(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<FileRoot>>, ApiError> {
+    let roots = files::load_roots(&state.db)
+        .await
+        .map_err(|e| file_error(&e))?;
+    Ok(Json(roots))
+}
+
+/// Entries of a directory (`path`, the root by default)
+async fn list_dir(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(root): Path<String>,
+    Query(query): Query<PathQuery>,
+) -> Result<Json<DirListing>, ApiError> {
+    let root = find_root(&state, &root).await?;
+    let listing = files::list_dir(&root, &query.path)
+        .await
+        .map_err(|e| file_error(&e))?;
+    Ok(Json(listing))
+}
+
+/// Download a file as an attachment
+async fn download(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Path(root): Path<String>,
+    Query(query): Query<PathQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let root = find_root(&state, &root).await?;
+    let result = files::open_file(&root, &query.path).await;
+    files::audit(
+        &state.db,
+        &auth.0.username,
+        "download",
+        &root.name,
+        &query.path,
+        result
+            .as_ref()
+            .map(|(_, size, _)| Some(format!("{} bytes", size))),
+    )
+    .await;
+    let (file, size, name) = result.map_err(|e| file_error(&e))?;
+
+    // Read in chunks so large files aren't held in memory
+    let body = Body::from_stream(futures::stream::unfold(file, |mut file| async move {
+        let mut buffer = vec![0u8; 64 * 1024];
+        match file.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buffer.truncate(n);
+                Some((Ok(buffer), file))
+            }
+            Err(e) => Some((Err(e), file)),
+        }
+    }));
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                mime_guess::from_path(&name)
+                    .first_or_octet_stream()
+                    .to_string(),
+            ),
+            (header::CONTENT_LENGTH, size.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}\"",
+                    name.replace(['"', '\\'], "_")
+                ),
+            ),
+        ],
+        body,
+    ))
+}
+
+/// Upload the request body to `path`, replacing an existing file only with `overwrite=true`
+async fn upload(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Path(root): Path<String>,
+    Query(query): Query<UploadQuery>,
+    body: Body,
+) -> Result<StatusCode, ApiError> {
+    let root = find_root(&state, &root).await?;
+    let max_bytes = files::max_upload_bytes(&state.db).await;
+    let result = files::upload(
+        &root,
+        &query.path,
+        query.overwrite,
+        max_bytes,
+        body.into_data_stream(),
+    )
+    .await;
+    files::audit(
+        &state.db,
+        &auth.0.username,
+        "upload",
+        &root.name,
+        &query.path,
+        result.as_ref().map(|size| Some(format!("{} bytes", size))),
+    )
+    .await;
+    result.map_err(|e| file_error(&e))?;
+    Ok(StatusCode::CREATED)
+}
+
+/// Rename or move a file or directory within its root
+async fn rename(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Path(root): Path<String>,
+    Json(payload): Json<RenameRequest>,
+) -> Result<StatusCode, ApiError> {
+    let root = find_root(&state, &root).await?;
+    let result = files::rename(&root, &payload.from, &payload.to).await;
+    files::audit(
+        &state.db,
+        &auth.0.username,
+        "rename",
+        &root.name,
+        &payload.from,
+        result.as_ref().map(|_| Some(format!("to {}", payload.to))),
+    )
+    .await;
+    result.map_err(|e| file_error(&e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Delete a file or an empty directory; `recursive=true` deletes a directory tree
+async fn delete_entry(
+    auth: AdminUser,
+    State(state): State<AppState>,
+    Path(root): Path<String>,
+    Query(query): Query<DeleteQuery>,
+) -> Result<StatusCode, ApiError> {
+    let root = find_root(&state, &root).await?;
+    let result = files::delete(&root, &query.path, query.recursive).await;
+    files::audit(
+        &state.db,
+        &auth.0.username,
+        "delete",
+        &root.name,
+        &query.path,
+        result
+            .as_ref()
+            .map(|_| query.recursive.then(|| "recursive".to_string())),
+    )
+    .await;
+    result.map_err(|e| file_error(&e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Recent file manager operations, newest first
+async fn audit_log(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<FileAuditEntry>>, ApiError> {
+    let entries = db::get_file_audit_log(&state.db, query.limit.unwrap_or(100).clamp(1, 1000))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
+    Ok(Json(entries))
+}
@@ -0,0 +1,85 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+
+use crate::routes::api::AppState;
+use crate::routes::plugins::PluginStatus;
+use crate::services::health::{self, ComponentHealth, HealthStatus, Readiness};
+
+/// Probes for orchestrators and load balancers; they need no authentication
+pub fn create_health_router() -> Router<AppState> {
+    Router::new()
+        .route("/healthz", get(liveness))
+        .route("/readyz", get(readiness))
+}
+
+/// The process is up and answering requests
+async fn liveness() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Whether the server can serve requests, with the status of each component
+///
+/// Answers 503 when a component is failing.
+async fn readiness(State(state): State<AppState>) -> Response {
+    let components = vec![
+        health::check_database(&state.db).await,
+        check_plugins(&state).await,
+        health::check_log_disk(&state.db, &state.config.paths.log_dir).await,
+    ];
+    let readiness = Readiness::new(components);
+    let status = if readiness.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness)).into_response()
+}
+
+/// Whether the plugin supervisor answers, and enabled plugins are healthy
+///
+/// Plugins aren't needed for the server to work, so problems with them only
+/// degrade it.
+async fn check_plugins(state: &AppState) -> ComponentHealth {
+    let Some(supervisor) = &state.supervisor else {
+        return ComponentHealth::new("plugins", HealthStatus::Degraded)
+            .with_message("Plugin supervisor failed to start");
+    };
+    let Ok(supervisor) = tokio::time::timeout(health::CHECK_TIMEOUT, supervisor.lock()).await
+    else {
+        return ComponentHealth::new("plugins", HealthStatus::Degraded).with_message(format!(
+            "Plugin supervisor busy for over {:?}",
+            health::CHECK_TIMEOUT
+        ));
+    };
+
+    let plugins: Vec<PluginStatus> = supervisor
+        .get_all_plugins()
+        .values()
+        .map(|process| PluginStatus::new(process, &supervisor, None))
+        .collect();
+    let enabled = plugins.iter().filter(|p| p.enabled).count();
+    let mut unhealthy: Vec<String> = plugins
+        .iter()
+        .filter(|p| p.health == "unhealthy" || p.health == "conflict")
+        .map(|p| p.id.clone())
+        .collect();
+    unhealthy.sort();
+
+    let details = serde_json::json!({
+        "total": plugins.len(),
+        "enabled": enabled,
+        "unhealthy": unhealthy,
+    });
+    if unhealthy.is_empty() {
+        ComponentHealth::new("plugins", HealthStatus::Ok).with_details(details)
+    } else {
+        ComponentHealth::new("plugins", HealthStatus::Degraded)
+            .with_message(format!("{} plugins unhealthy", unhealthy.len()))
+            .with_details(details)
+    }
+}
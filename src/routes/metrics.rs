@@ -0,0 +1,233 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use std::time::Instant;
+use subtle::ConstantTimeEq;
+
+use crate::db;
+use crate::routes::api::AppState;
+use crate::routes::plugins::PluginStatus;
+use crate::services::metrics::{self, MetricsWriter, METRICS_TOKEN_SETTING};
+use crate::services::plugins::PluginSupervisor;
+
+pub fn create_metrics_router() -> Router<AppState> {
+    Router::new().route("/metrics", get(get_metrics))
+}
+
+/// Count a request and its duration under the route it matched
+///
+/// Added with `route_layer`, so requests no route matched (static files) aren't
+/// counted.
+pub async fn track_requests(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+    if let Some(route) = route {
+        state.metrics.record(
+            &method,
+            &route,
+            response.status().as_u16(),
+            started.elapsed(),
+        );
+    }
+    response
+}
+
+/// Metrics in the Prometheus text format
+///
+/// Needs `Authorization: Bearer <token>` when the `metrics_token` setting is set.
+async fn get_metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    check_token(&state, &headers).await?;
+
+    let mut out = MetricsWriter::default();
+    if let Some(resources) = state.resources.snapshot() {
+        metrics::write_system(&mut out, &resources);
+    }
+
+    if let Some(supervisor) = &state.supervisor {
+        let mut supervisor = supervisor.lock().await;
+        supervisor.sample_resource_usage();
+        write_plugins(&mut out, &supervisor);
+    }
+
+    let tasks = db::get_task_stats(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    out.single(
+        "toru_tasks_running",
+        "gauge",
+        "Script tasks started and not finished",
+        tasks.running as f64,
+    );
+    out.family(
+        "toru_tasks_finished_total",
+        "counter",
+        "Script tasks finished, by result",
+    );
+    out.sample(
+        "toru_tasks_finished_total",
+        &[("result", "success")],
+        tasks.succeeded as f64,
+    );
+    out.sample(
+        "toru_tasks_finished_total",
+        &[("result", "failure")],
+        tasks.failed as f64,
+    );
+    out.family(
+        "toru_task_duration_seconds",
+        "summary",
+        "Run time of finished script tasks",
+    );
+    out.sample("toru_task_duration_seconds_sum", &[], tasks.duration_secs);
+    out.sample(
+        "toru_task_duration_seconds_count",
+        &[],
+        (tasks.succeeded + tasks.failed) as f64,
+    );
+
+    let sessions = db::count_active_sessions(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    out.family(
+        "toru_sessions_active",
+        "gauge",
+        "Logged-in sessions that haven't expired, by role",
+    );
+    for (role, count) in &sessions {
+        out.sample("toru_sessions_active", &[("role", role)], *count as f64);
+    }
+
+    state.metrics.write(&mut out);
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        out.finish(),
+    )
+        .into_response())
+}
+
+async fn check_token(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let token = db::get_setting(&state.db, METRICS_TOKEN_SETTING)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter(|token| !token.is_empty());
+    let Some(token) = token else {
+        return Ok(());
+    };
+
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if bool::from(given.as_bytes().ct_eq(token.as_bytes())) {
+        Ok(())
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+fn write_plugins(out: &mut MetricsWriter, supervisor: &PluginSupervisor) {
+    let mut plugins: Vec<PluginStatus> = supervisor
+        .get_all_plugins()
+        .values()
+        .map(|process| PluginStatus::new(process, supervisor, None))
+        .collect();
+    plugins.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let flag = |value: bool| if value { 1.0 } else { 0.0 };
+    out.family(
+        "toru_plugin_enabled",
+        "gauge",
+        "Whether the plugin is enabled",
+    );
+    for plugin in &plugins {
+        out.sample(
+            "toru_plugin_enabled",
+            &[("plugin", &plugin.id)],
+            flag(plugin.enabled),
+        );
+    }
+    out.family(
+        "toru_plugin_running",
+        "gauge",
+        "Whether the plugin is running",
+    );
+    for plugin in &plugins {
+        out.sample(
+            "toru_plugin_running",
+            &[("plugin", &plugin.id)],
+            flag(plugin.running),
+        );
+    }
+    out.family(
+        "toru_plugin_healthy",
+        "gauge",
+        "Whether the plugin is running and reachable",
+    );
+    for plugin in &plugins {
+        out.sample(
+            "toru_plugin_healthy",
+            &[("plugin", &plugin.id)],
+            flag(plugin.health == "healthy"),
+        );
+    }
+    out.family(
+        "toru_plugin_restarts",
+        "gauge",
+        "Restarts after crashes since the plugin last ran stably",
+    );
+    for plugin in &plugins {
+        out.sample(
+            "toru_plugin_restarts",
+            &[("plugin", &plugin.id)],
+            plugin.restart_count.into(),
+        );
+    }
+    out.family(
+        "toru_plugin_memory_bytes",
+        "gauge",
+        "Resident memory of the plugin process",
+    );
+    for plugin in &plugins {
+        if let Some(usage) = &plugin.usage {
+            out.sample(
+                "toru_plugin_memory_bytes",
+                &[("plugin", &plugin.id)],
+                usage.memory_bytes as f64,
+            );
+        }
+    }
+    out.family(
+        "toru_plugin_cpu_percent",
+        "gauge",
+        "CPU usage of the plugin process, in percent of one core",
+    );
+    for plugin in &plugins {
+        if let Some(usage) = &plugin.usage {
+            out.sample(
+                "toru_plugin_cpu_percent",
+                &[("plugin", &plugin.id)],
+                usage.cpu_percent.into(),
+            );
+        }
+    }
+}
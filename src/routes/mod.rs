@@ -1,9 +1,22 @@
+pub mod access_log;
+pub mod admin;
 pub mod api;
 pub mod auth;
+pub mod docker;
+pub mod files;
+pub mod health;
+pub mod metrics;
+pub mod pipelines;
 pub mod plugins;
 pub mod ws;
 
+pub use admin::create_admin_router;
 pub use api::create_api_router;
 pub use auth::create_auth_router;
+pub use docker::create_docker_router;
+pub use files::create_files_router;
+pub use health::create_health_router;
+pub use metrics::create_metrics_router;
+pub use pipelines::create_pipeline_router;
 pub use plugins::create_plugin_router;
 pub use ws::handle_websocket;
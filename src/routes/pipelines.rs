@@ -0,0 +1,187 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{delete, get, post, put},
+    Router,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::db::{self, Pipeline, PipelineStep};
+use crate::routes::api::AppState;
+use crate::routes::auth::AdminUser;
+use crate::services::executor::{self, ResourceLimits};
+use crate::services::scripts::validate_script_name;
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn api_error(status: StatusCode, msg: &str) -> ApiError {
+    (status, Json(serde_json::json!({ "error": msg })))
+}
+
+fn db_error<E>(_: E) -> ApiError {
+    api_error(StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+}
+
+pub fn create_pipeline_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_pipelines))
+        .route("/", post(create_pipeline))
+        .route("/:id", get(get_pipeline))
+        .route("/:id", put(update_pipeline))
+        .route("/:id", delete(delete_pipeline))
+        .route("/:id/run", post(run_pipeline))
+}
+
+#[derive(Deserialize)]
+struct PipelineRequest {
+    name: String,
+    description: Option<String>,
+    steps: Vec<PipelineStep>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+}
+
+/// Validate a pipeline definition before saving it
+fn validate_pipeline(payload: &PipelineRequest) -> Result<(), ApiError> {
+    if payload.name.trim().is_empty() {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "Pipeline name is required",
+        ));
+    }
+    if payload.steps.is_empty() {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "Pipeline must have at least one step",
+        ));
+    }
+    for step in &payload.steps {
+        validate_script_name(&step.script_path)
+            .map_err(|e| api_error(StatusCode::BAD_REQUEST, &e.to_string()))?;
+    }
+    for key in payload.env.keys() {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                &format!("Invalid environment variable name: {}", key),
+            ));
+        }
+    }
+    Ok(())
+}
+
+async fn list_pipelines(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Pipeline>>, ApiError> {
+    let pipelines = db::get_pipelines(&state.db).await.map_err(db_error)?;
+    Ok(Json(pipelines))
+}
+
+async fn get_pipeline(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Pipeline>, ApiError> {
+    let pipeline = db::get_pipeline(&state.db, &id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, "Pipeline not found"))?;
+    Ok(Json(pipeline))
+}
+
+async fn create_pipeline(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Json(payload): Json<PipelineRequest>,
+) -> Result<Json<Pipeline>, ApiError> {
+    validate_pipeline(&payload)?;
+
+    let pipeline = Pipeline {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: payload.name,
+        description: payload.description,
+        steps: payload.steps,
+        env: payload.env,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    db::save_pipeline(&state.db, &pipeline)
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(pipeline))
+}
+
+async fn update_pipeline(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<PipelineRequest>,
+) -> Result<Json<Pipeline>, ApiError> {
+    validate_pipeline(&payload)?;
+
+    let existing = db::get_pipeline(&state.db, &id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, "Pipeline not found"))?;
+
+    let pipeline = Pipeline {
+        id: existing.id,
+        name: payload.name,
+        description: payload.description,
+        steps: payload.steps,
+        env: payload.env,
+        created_at: existing.created_at,
+    };
+    db::save_pipeline(&state.db, &pipeline)
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(pipeline))
+}
+
+async fn delete_pipeline(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    db::delete_pipeline(&state.db, &id)
+        .await
+        .map_err(db_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Start a pipeline run and return its task ID (progress is recorded in history)
+async fn run_pipeline(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let pipeline = db::get_pipeline(&state.db, &id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, "Pipeline not found"))?;
+
+    let scripts_dir = db::get_setting(&state.db, "scripts_dir")
+        .await
+        .map_err(db_error)?
+        .unwrap_or_else(|| "./scripts".to_string());
+
+    let task_id = uuid::Uuid::new_v4().to_string();
+    let limits = ResourceLimits::from_settings(&state.db).await;
+    executor::run_pipeline_task(
+        pipeline,
+        scripts_dir,
+        task_id.clone(),
+        state.db.clone(),
+        state.tasks.clone(),
+        limits,
+        None, // No real-time streaming to caller, just DB updates
+    )
+    .await
+    .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(serde_json::json!({ "task_id": task_id })))
+}
@@ -1,20 +1,36 @@
 use axum::{
     body::Body,
-    extract::{Path, Query, State},
-    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    extract::{
+        ws::{Message as WsMessage, WebSocket},
+        DefaultBodyLimit, Multipart, Path, Query, State, WebSocketUpgrade,
+    },
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri},
     response::{IntoResponse, Json, Response},
-    routing::{any, get, post},
+    routing::{any, delete, get, post, put},
     Router,
 };
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::sync::Arc;
+use toru_plugin_api::{KvOp, KvPage, Message, WsFrame, WsFrameKind};
 
+use crate::db::{KvQuotaExceeded, PluginEvent, PluginKvQuota, PluginKvUsage, TrustedKey};
 use crate::routes::api::AppState;
 use crate::routes::auth::{AdminUser, AuthUser};
-use crate::services::logging::LogLevel;
-use crate::services::plugins::PluginProcess;
+use crate::services::logging::{LogFilter, LogLevel, TextFilter};
+use crate::services::plugin_assets::{self, ASSET_MAX_AGE_SECS};
+use crate::services::plugin_breaker::CircuitOpen;
+use crate::services::plugin_connection::{PluginConnection, RequestTimeout};
+use crate::services::plugin_crash::CrashReport;
+use crate::services::plugin_deps::DependencyProblem;
+use crate::services::plugin_limits::{LimitExceeded, ResourceUsage};
+use crate::services::plugin_package::{PluginPackage, MAX_PACKAGE_SIZE};
+use crate::services::plugin_registry::{self, RegistryEntry, RegistryIndex, REGISTRY_URL_SETTING};
+use crate::services::plugin_signing;
+use crate::services::plugins::{InstallError, PluginProcess, PluginSupervisor, INSTANCE_SEPARATOR};
 
 /// Plugin status information
 #[derive(Serialize, Clone)]
@@ -26,18 +42,57 @@ pub struct PluginStatus {
     pub icon: String,
     pub enabled: bool,
     pub running: bool,
-    pub health: String, // "healthy", "unhealthy", "disabled"
+    pub health: String, // "healthy", "unhealthy", "disabled", "conflict"
     pub pid: Option<u32>,
     pub socket_path: Option<String>,
+    pub limits: toru_plugin_api::PluginLimits,
+    pub limit_enforcement: String, // "cgroup", "rlimit", "wasm", "none"
+    pub usage: Option<ResourceUsage>,
+    pub limit_exceeded: Option<LimitExceeded>,
+    pub uptime_secs: Option<u64>,
+    pub restart_count: u32,
+    pub last_crash_at: Option<String>,
+    pub last_crash: Option<CrashReport>, // Exit status and last stderr lines of the last crash
+    pub dev: bool,       // Registered through /dev/register, run outside the supervisor
+    pub runtime: String, // "native" or "wasm"
+    pub capabilities: Vec<String>, // Requested in the plugin metadata
+    pub granted_capabilities: Vec<String>,
+    pub jobs: Vec<PluginJobStatus>,
+    pub route_conflict: Option<String>, // Why the plugin wasn't started, if its route is taken
+    pub dependencies: Vec<String>,      // Declared in the plugin metadata
+    pub dependency_problems: Vec<String>, // Missing, disabled or outdated dependencies
+    pub heartbeat: bool,                // Answers the health monitor's pings
+    pub circuit_open: bool,             // Requests paused after repeated timeouts
+    pub instance_of: Option<String>,    // Plugin this is a named instance of
+    pub request_timeout_secs: u64,
+    pub protocol_version: u32,
+    pub pending_migration: Option<String>, // Version upgraded from, until the plugin has migrated
+    pub kv_quota: PluginKvQuota,
+    pub kv_usage: Option<PluginKvUsage>,
 }
 
-impl From<&PluginProcess> for PluginStatus {
-    fn from(process: &PluginProcess) -> Self {
-        let health = if !process.enabled {
+/// A scheduled job a plugin registered
+#[derive(Serialize, Clone)]
+pub struct PluginJobStatus {
+    pub id: String,
+    pub schedule: String,
+    pub next_run: Option<String>,
+}
+
+impl PluginStatus {
+    pub(crate) fn new(
+        process: &PluginProcess,
+        supervisor: &PluginSupervisor,
+        kv_usage: Option<PluginKvUsage>,
+    ) -> Self {
+        let route_conflict = supervisor.route_conflict(&process.id).map(str::to_string);
+        let health = if route_conflict.is_some() {
+            "conflict".to_string()
+        } else if !process.enabled {
             "disabled".to_string()
-        } else if process.process.is_some()
-            && !process.socket_path.is_empty()
-            && PathBuf::from(&process.socket_path).exists()
+        } else if (process.is_running() || process.dev)
+            && process.endpoint_ready()
+            && !process.breaker.is_open()
         {
             "healthy".to_string()
         } else {
@@ -63,7 +118,7 @@ impl From<&PluginProcess> for PluginStatus {
                 .map(|m| m.icon.clone())
                 .unwrap_or_default(),
             enabled: process.enabled,
-            running: process.process.is_some(),
+            running: process.is_running() || (process.dev && process.endpoint_ready()),
             health,
             pid: process.pid,
             socket_path: if process.socket_path.is_empty() {
@@ -71,6 +126,58 @@ impl From<&PluginProcess> for PluginStatus {
             } else {
                 Some(process.socket_path.clone())
             },
+            limits: process.limits,
+            limit_enforcement: process.enforcement.as_str().to_string(),
+            usage: process.usage,
+            limit_exceeded: process.limit_exceeded,
+            uptime_secs: process.uptime().map(|uptime| uptime.as_secs()),
+            restart_count: supervisor.get_restart_count(&process.id),
+            last_crash_at: process.last_crash_at.map(|at| at.to_rfc3339()),
+            last_crash: process.last_crash.clone(),
+            dev: process.dev,
+            runtime: if process.wasm.is_some() || process.socket_path.ends_with(".wasm") {
+                "wasm".to_string()
+            } else {
+                "native".to_string()
+            },
+            capabilities: process
+                .metadata
+                .as_ref()
+                .map(|m| m.capabilities.clone())
+                .unwrap_or_default(),
+            granted_capabilities: supervisor.granted_capabilities(&process.id),
+            dependencies: process
+                .metadata
+                .as_ref()
+                .map(|m| m.dependencies.clone())
+                .unwrap_or_default(),
+            dependency_problems: supervisor.dependency_problems(&process.id),
+            jobs: supervisor
+                .plugin_jobs(&process.id)
+                .iter()
+                .map(|job| PluginJobStatus {
+                    id: job.id.clone(),
+                    schedule: job.schedule.clone(),
+                    next_run: job.next_run.map(|at| at.to_rfc3339()),
+                })
+                .collect(),
+            route_conflict,
+            heartbeat: supervisor.answers_pings(&process.id),
+            circuit_open: process.breaker.is_open(),
+            instance_of: process
+                .id
+                .split_once(INSTANCE_SEPARATOR)
+                .map(|(plugin, _)| plugin.to_string()),
+            request_timeout_secs: supervisor.request_timeout(&process.id).as_secs(),
+            protocol_version: process
+                .metadata
+                .as_ref()
+                .map_or(toru_plugin_api::MIN_PROTOCOL_VERSION, |m| {
+                    m.protocol_version
+                }),
+            pending_migration: supervisor.pending_migration(&process.id),
+            kv_quota: supervisor.kv_quota(&process.id),
+            kv_usage,
         }
     }
 }
@@ -79,11 +186,41 @@ pub fn create_plugin_router() -> Router<AppState> {
     // Admin routes router
     let admin_router = Router::new()
         .route("/", get(list_plugins))
+        .route("/events", get(get_all_plugin_events))
+        .route(
+            "/install",
+            post(install_plugin).layer(DefaultBodyLimit::max(MAX_PACKAGE_SIZE)),
+        )
+        .route("/install-from-url", post(install_plugin_from_url))
+        .route("/registry", get(list_registry_plugins))
+        .route("/registry/:id/install", post(install_registry_plugin))
+        .route("/trusted-keys", get(list_trusted_keys))
+        .route("/trusted-keys", post(add_trusted_key))
+        .route("/trusted-keys/:id", delete(delete_trusted_key))
+        .route("/dev/register", post(register_dev_plugin))
+        .route("/dev/:id", delete(unregister_dev_plugin))
+        .route("/:id/instances", post(create_plugin_instance))
+        .route("/:id/instances/:name", delete(remove_plugin_instance))
         .route("/:id", get(get_plugin))
+        .route(
+            "/:id/upgrade",
+            post(upgrade_plugin).layer(DefaultBodyLimit::max(MAX_PACKAGE_SIZE)),
+        )
         .route("/:id/enable", post(enable_plugin))
         .route("/:id/disable", post(disable_plugin))
+        .route("/:id/shutdown-timeout", put(set_shutdown_timeout))
+        .route("/:id/request-timeout", put(set_request_timeout))
+        .route("/:id/limits", put(set_resource_limits))
+        .route("/:id/capabilities", put(set_capabilities))
+        .route("/:id/kv-quota", put(set_kv_quota))
+        .route("/:id/env", get(get_plugin_env))
+        .route("/:id/env", put(set_plugin_env))
+        .route("/:id/access", get(get_plugin_access))
+        .route("/:id/access", put(set_plugin_access))
         .route("/:id/bundle.js", get(get_plugin_bundle))
+        .route("/:id/assets/*path", get(get_plugin_asset))
         .route("/:id/logs", get(get_plugin_logs))
+        .route("/:id/events", get(get_plugin_events))
         .route("/:id/kv", post(plugin_kv_handler));
 
     // Dynamic plugin routes (separate path prefix to avoid conflicts)
@@ -111,10 +248,18 @@ pub fn create_plugin_router() -> Router<AppState> {
 /// - Path: "hello-plugin/some/path"
 /// - Plugin route: "/hello-plugin"
 /// - Plugin path: "/some/path?query=1"
+///
+/// WebSocket upgrades of `/api/plugins/route/hello-plugin/ws/echo` are relayed
+/// to the plugin when it declares `/echo` in its `websockets` metadata.
+///
+/// The request's headers are passed on, including the `X-Request-Id` it was
+/// given, so the plugin can log it alongside its own entries.
+#[allow(clippy::too_many_arguments)]
 async fn forward_to_plugin(
-    _auth: AuthUser, // Require authentication (any role)
+    auth: AuthUser, // Require authentication and access to the plugin
     State(state): State<AppState>,
     Path(path): Path<String>,
+    ws: Option<WebSocketUpgrade>,
     method: Method,
     uri: Uri,
     headers: HeaderMap,
@@ -128,17 +273,47 @@ async fn forward_to_plugin(
         return Err(StatusCode::BAD_REQUEST);
     }
 
-    // Check if this path matches an enabled plugin's route
-    let supervisor = state
-        .supervisor
-        .as_ref()
-        .ok_or(StatusCode::NOT_IMPLEMENTED)?
-        .lock()
-        .await;
+    // Check if this path matches an enabled plugin's route and grab its connection.
+    // The supervisor lock is released before talking to the plugin so requests
+    // to plugins don't serialize behind each other.
+    let (plugin_id, target, websockets) = {
+        let supervisor = state
+            .supervisor
+            .as_ref()
+            .ok_or(StatusCode::NOT_IMPLEMENTED)?
+            .lock()
+            .await;
 
-    let plugin_id = supervisor
-        .get_plugin_for_route(&format!("/{}", plugin_route))
-        .ok_or(StatusCode::NOT_FOUND)?;
+        let plugin_id = supervisor
+            .get_plugin_for_route(&format!("/{}", plugin_route))
+            .ok_or(StatusCode::NOT_FOUND)?;
+
+        let target = supervisor.http_target(&plugin_id).await.map_err(|e| {
+            tracing::error!("Failed to connect to plugin {}: {}", plugin_id, e);
+            StatusCode::BAD_GATEWAY
+        })?;
+        let websockets = supervisor
+            .get_plugin_status(&plugin_id)
+            .and_then(|process| process.metadata.as_ref())
+            .map(|metadata| metadata.websockets.clone())
+            .unwrap_or_default();
+        (plugin_id, target, websockets)
+    };
+    // Restricted plugins look absent to users without access
+    if !has_plugin_access(&state, &auth, &plugin_id).await {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // WebSocket upgrades are relayed frame by frame instead of forwarded as HTTP
+    if let (Some(ws), Some(socket_path)) = (ws, remaining.strip_prefix("ws/")) {
+        let socket_path = format!("/{}", socket_path);
+        if !websockets.contains(&socket_path) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        return Ok(ws.on_upgrade(move |socket| {
+            relay_plugin_socket(socket, target.connection, plugin_id, socket_path)
+        }));
+    }
 
     // Build the path to send to plugin
     let plugin_path = if remaining.is_empty() {
@@ -161,89 +336,523 @@ async fn forward_to_plugin(
             plugin_headers.insert(name.to_string(), value_str.to_string());
         }
     }
+    // Where the plugin is reached from the browser (replacing a client-sent value),
+    // so it can build links and redirects under a base path
+    plugin_headers.insert(
+        toru_plugin_api::FORWARDED_PREFIX_HEADER.to_string(),
+        format!(
+            "{}/api/plugins/route/{}",
+            state.config.server.base_path, plugin_route
+        ),
+    );
 
     // Read request body
     let body_bytes = axum::body::to_bytes(body, usize::MAX)
         .await
         .map_err(|_| StatusCode::BAD_REQUEST)?;
-    let body_str = if body_bytes.is_empty() {
-        None
-    } else {
-        String::from_utf8(body_bytes.to_vec()).ok()
-    };
 
-    // Build HTTP request for plugin
-    let http_request = toru_plugin_api::HttpRequest {
+    // Build HTTP request for plugin (binary bodies are sent base64 encoded)
+    let mut http_request = toru_plugin_api::HttpRequest {
         method: method.to_string(),
         path: full_path,
         headers: plugin_headers,
-        body: body_str,
+        body: None,
+        body_encoding: Default::default(),
     };
+    if !body_bytes.is_empty() {
+        http_request.set_body_bytes(&body_bytes);
+    }
 
     // Forward to plugin
-    let response = supervisor
-        .forward_http_request(&plugin_id, &http_request)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to forward request to plugin {}: {}", plugin_id, e);
+    // A plugin that keeps timing out is answered for with 503 until its breaker closes
+    let (response, stream) = target.open(&http_request).await.map_err(|e| {
+        tracing::error!("Failed to forward request to plugin {}: {}", plugin_id, e);
+        if e.downcast_ref::<CircuitOpen>().is_some() {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else if e.downcast_ref::<RequestTimeout>().is_some() {
+            StatusCode::GATEWAY_TIMEOUT
+        } else {
             StatusCode::BAD_GATEWAY
-        })?;
+        }
+    })?;
 
     // Build Axum response from plugin response
     let mut builder = Response::builder().status(response.status);
 
     // Set headers
-    for (name, value) in response.headers {
-        if let Ok(header_value) = HeaderValue::from_str(&value) {
+    for (name, value) in &response.headers {
+        if let Ok(header_value) = HeaderValue::from_str(value) {
             if let Ok(header_name) = name.parse::<axum::http::HeaderName>() {
                 builder = builder.header(header_name, header_value);
             }
         }
     }
 
-    // Set body
+    // Set body, passing a streamed body on chunk by chunk
+    let body = match stream {
+        Some(stream) => Body::from_stream(futures::stream::unfold(stream, |mut stream| async {
+            match stream.next_chunk().await {
+                Ok(Some(data)) => Some((Ok(data), stream)),
+                Ok(None) => None,
+                // Fails the response so the client sees a truncated body, not a complete one
+                Err(e) => Some((Err(std::io::Error::other(e.to_string())), stream)),
+            }
+        })),
+        None => Body::from(
+            response
+                .body_bytes()
+                .map_err(|e| {
+                    tracing::error!("Plugin {} sent an invalid body: {}", plugin_id, e);
+                    StatusCode::BAD_GATEWAY
+                })?
+                .unwrap_or_default(),
+        ),
+    };
     let response = builder
-        .body(axum::body::Body::from(response.body.unwrap_or_default()))
+        .body(body)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(response)
 }
 
-/// List all plugins (available to all authenticated users)
+/// Relay frames between a browser WebSocket and the plugin until either side closes
+async fn relay_plugin_socket(
+    socket: WebSocket,
+    connection: Arc<PluginConnection>,
+    plugin_id: String,
+    path: String,
+) {
+    let socket_id = uuid::Uuid::new_v4().to_string();
+    let mut plugin_socket = connection.open_socket(&socket_id);
+    let send_frame = |frame: WsFrame| {
+        let message = Message::new_ws_frame(socket_id.clone(), frame);
+        let connection = Arc::clone(&connection);
+        async move { connection.send(&message).await }
+    };
+
+    if let Err(e) = send_frame(WsFrame::open(&path)).await {
+        tracing::error!(
+            "Failed to open WebSocket {} on plugin {}: {}",
+            path,
+            plugin_id,
+            e
+        );
+        return;
+    }
+
+    let (mut sender, mut receiver) = socket.split();
+    // Whether the plugin still expects a close frame from us
+    let mut plugin_open = true;
+    loop {
+        tokio::select! {
+            frame = plugin_socket.recv() => {
+                // The plugin closed the socket or its connection went away
+                let Some(frame) = frame else {
+                    plugin_open = false;
+                    break;
+                };
+                let message = match frame.kind {
+                    WsFrameKind::Text => WsMessage::Text(frame.data.unwrap_or_default()),
+                    WsFrameKind::Binary => match frame.bytes() {
+                        Ok(bytes) => WsMessage::Binary(bytes),
+                        Err(e) => {
+                            tracing::warn!("Plugin {} sent an invalid WebSocket frame: {}", plugin_id, e);
+                            continue;
+                        }
+                    },
+                    WsFrameKind::Close => {
+                        plugin_open = false;
+                        break;
+                    }
+                    WsFrameKind::Open => continue,
+                };
+                if sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+            message = receiver.next() => {
+                let frame = match message {
+                    Some(Ok(WsMessage::Text(text))) => WsFrame::text(&text),
+                    Some(Ok(WsMessage::Binary(bytes))) => WsFrame::binary(&bytes),
+                    // axum answers pings itself
+                    Some(Ok(WsMessage::Ping(_) | WsMessage::Pong(_))) => continue,
+                    Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                };
+                if send_frame(frame).await.is_err() {
+                    plugin_open = false;
+                    break;
+                }
+            }
+        }
+    }
+
+    if plugin_open {
+        let _ = send_frame(WsFrame::close()).await;
+    }
+    let _ = sender.send(WsMessage::Close(None)).await;
+}
+
+/// Whether `auth` may see and use plugin `id`
+async fn has_plugin_access(state: &AppState, auth: &AuthUser, id: &str) -> bool {
+    auth.is_admin()
+        || crate::db::get_plugin_access(&state.db, id)
+            .await
+            .is_ok_and(|access| access.allows(auth.user_id.as_deref(), auth.role))
+}
+
+/// List the plugins the user has access to (all of them for admins)
 async fn list_plugins(
-    _auth: AuthUser, // Changed from AdminUser to AuthUser
+    auth: AuthUser,
     State(state): State<AppState>,
 ) -> Result<Json<Vec<PluginStatus>>, StatusCode> {
-    let supervisor = state
+    let access = crate::db::get_all_plugin_access(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut supervisor = state
         .supervisor
         .as_ref()
         .ok_or(StatusCode::NOT_IMPLEMENTED)?
         .lock()
         .await;
+    supervisor.sample_resource_usage();
     let plugins = supervisor.get_all_plugins();
 
-    let plugin_statuses: Vec<PluginStatus> = plugins.values().map(PluginStatus::from).collect();
+    let mut plugin_statuses = Vec::with_capacity(plugins.len());
+    for p in plugins.values() {
+        let allowed = access
+            .get(&p.id)
+            .is_none_or(|access| access.allows(auth.user_id.as_deref(), auth.role));
+        if !allowed {
+            continue;
+        }
+        let kv_usage = crate::db::plugin_kv_usage(&state.db, &p.id).await.ok();
+        plugin_statuses.push(PluginStatus::new(p, &supervisor, kv_usage));
+    }
 
     Ok(Json(plugin_statuses))
 }
 
-/// Get plugin details (available to all authenticated users)
+/// Get plugin details (available to users with access to the plugin)
 async fn get_plugin(
-    _auth: AuthUser, // Changed from AdminUser to AuthUser
+    auth: AuthUser,
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> Result<Json<PluginStatus>, StatusCode> {
-    let supervisor = state
+    if !has_plugin_access(&state, &auth, &id).await {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let mut supervisor = state
         .supervisor
         .as_ref()
         .ok_or(StatusCode::NOT_IMPLEMENTED)?
         .lock()
         .await;
+    supervisor.sample_resource_usage();
     let plugin = supervisor
         .get_plugin_status(&id)
         .ok_or(StatusCode::NOT_FOUND)?;
+    let kv_usage = crate::db::plugin_kv_usage(&state.db, &id).await.ok();
+
+    Ok(Json(PluginStatus::new(plugin, &supervisor, kv_usage)))
+}
+
+/// Install a plugin from an uploaded `.binary` or `.tar` package
+///
+/// Multipart fields: `file` (binary or tar archive with `<id>.binary` and optional
+/// `bundle.js` and `<id>.binary.minisig`), plus optional `bundle` and `signature`
+/// when uploading a bare binary.
+async fn install_plugin(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let package = read_package_upload(multipart).await?;
+    install_package(&state, package).await
+}
+
+/// Upgrade an installed plugin from an uploaded package with a newer version
+///
+/// Takes the same multipart fields as an install. Fails with 409 when the
+/// package isn't newer, and with 502 when the new version failed to start or
+/// migrate its data and the previous version was restored.
+async fn upgrade_plugin(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    multipart: Multipart,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let package = read_package_upload(multipart).await?;
+    let mut supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
+        .lock()
+        .await;
+
+    let metadata = supervisor
+        .upgrade_plugin(&id, package)
+        .await
+        .map_err(install_error_response)?;
+
+    Ok(Json(
+        serde_json::json!({ "success": true, "plugin": metadata }),
+    ))
+}
+
+/// Read a plugin package from the multipart fields of an install or upgrade
+async fn read_package_upload(
+    mut multipart: Multipart,
+) -> Result<PluginPackage, (StatusCode, Json<serde_json::Value>)> {
+    let bad_request = |msg: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": msg })),
+        )
+    };
+
+    let mut file: Option<Vec<u8>> = None;
+    let mut bundle: Option<Vec<u8>> = None;
+    let mut signature: Option<Vec<u8>> = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| bad_request("Invalid multipart body"))?
+    {
+        let name = field.name().map(String::from);
+        let data = field
+            .bytes()
+            .await
+            .map_err(|_| bad_request("Failed to read upload"))?;
+        match name.as_deref() {
+            Some("file") => file = Some(data.to_vec()),
+            Some("bundle") => bundle = Some(data.to_vec()),
+            Some("signature") => signature = Some(data.to_vec()),
+            _ => {}
+        }
+    }
+
+    let file = file.ok_or_else(|| bad_request("Missing 'file' field"))?;
+    PluginPackage::from_upload(file, bundle, signature).map_err(|e| bad_request(&e.to_string()))
+}
+
+/// Hand a package to the supervisor and map install errors to responses
+async fn install_package(
+    state: &AppState,
+    package: PluginPackage,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let mut supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
+        .lock()
+        .await;
+
+    let metadata = supervisor
+        .install_plugin(package)
+        .await
+        .map_err(install_error_response)?;
+
+    Ok(Json(
+        serde_json::json!({ "success": true, "plugin": metadata }),
+    ))
+}
+
+fn install_error_response(e: InstallError) -> (StatusCode, Json<serde_json::Value>) {
+    let status = match e {
+        InstallError::Invalid(_) => StatusCode::BAD_REQUEST,
+        InstallError::AlreadyInstalled(_) | InstallError::NotNewer { .. } => StatusCode::CONFLICT,
+        InstallError::NotInstalled(_) => StatusCode::NOT_FOUND,
+        InstallError::RolledBack { .. } => StatusCode::BAD_GATEWAY,
+        InstallError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string() })))
+}
+
+#[derive(Deserialize)]
+struct InstallFromUrlRequest {
+    url: String,
+    /// Hex-encoded SHA-256 of the package, verified before anything is written
+    sha256: String,
+}
+
+/// Download a plugin package, verify its checksum and install it
+async fn install_plugin_from_url(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Json(payload): Json<InstallFromUrlRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    download_and_install(&state, &payload.url, &payload.sha256).await
+}
+
+async fn download_and_install(
+    state: &AppState,
+    url: &str,
+    sha256: &str,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let error = |status: StatusCode, e: anyhow::Error| {
+        (status, Json(serde_json::json!({ "error": e.to_string() })))
+    };
+
+    plugin_registry::validate_url(url).map_err(|e| error(StatusCode::BAD_REQUEST, e))?;
+    let data = plugin_registry::download_package(url, sha256)
+        .await
+        .map_err(|e| error(StatusCode::BAD_GATEWAY, e))?;
+    let package = PluginPackage::from_upload(data, None, None)
+        .map_err(|e| error(StatusCode::BAD_REQUEST, e))?;
+
+    install_package(state, package).await
+}
+
+/// Registry entry annotated with local install state
+#[derive(Serialize)]
+struct RegistryPlugin {
+    #[serde(flatten)]
+    entry: RegistryEntry,
+    installed: bool,
+    installed_version: Option<String>,
+}
+
+/// Fetch the configured registry index
+async fn fetch_registry(
+    state: &AppState,
+) -> Result<RegistryIndex, (StatusCode, Json<serde_json::Value>)> {
+    let url = crate::db::get_setting(&state.db, REGISTRY_URL_SETTING)
+        .await
+        .ok()
+        .flatten()
+        .filter(|url| !url.is_empty())
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "No plugin registry configured" })),
+        ))?;
+
+    plugin_registry::fetch_index(&url).await.map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+    })
+}
+
+/// List plugins available in the registry
+async fn list_registry_plugins(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<RegistryPlugin>>, (StatusCode, Json<serde_json::Value>)> {
+    let index = fetch_registry(&state).await?;
+
+    let installed: HashMap<String, Option<String>> = match state.supervisor.as_ref() {
+        Some(supervisor) => supervisor
+            .lock()
+            .await
+            .get_all_plugins()
+            .iter()
+            .map(|(id, p)| (id.clone(), p.metadata.as_ref().map(|m| m.version.clone())))
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    let plugins = index
+        .plugins
+        .into_iter()
+        .map(|entry| RegistryPlugin {
+            installed: installed.contains_key(&entry.id),
+            installed_version: installed.get(&entry.id).cloned().flatten(),
+            entry,
+        })
+        .collect();
+
+    Ok(Json(plugins))
+}
+
+/// Install a plugin listed in the registry
+async fn install_registry_plugin(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let index = fetch_registry(&state).await?;
+    let entry = index.plugins.into_iter().find(|p| p.id == id).ok_or((
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({ "error": "Plugin not found in registry" })),
+    ))?;
+
+    download_and_install(&state, &entry.url, &entry.sha256).await
+}
+
+/// List public keys trusted to sign plugin binaries
+async fn list_trusted_keys(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<TrustedKey>>, StatusCode> {
+    let keys = crate::db::get_trusted_keys(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(keys))
+}
+
+#[derive(Deserialize)]
+struct TrustedKeyRequest {
+    name: String,
+    /// Minisign public key: the base64 key or the whole `minisign.pub` file
+    public_key: String,
+}
+
+/// Trust a minisign public key for plugin signatures
+async fn add_trusted_key(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Json(payload): Json<TrustedKeyRequest>,
+) -> Result<Json<TrustedKey>, (StatusCode, Json<serde_json::Value>)> {
+    let error = |status: StatusCode, msg: &str| (status, Json(serde_json::json!({ "error": msg })));
+
+    if payload.name.trim().is_empty() {
+        return Err(error(StatusCode::BAD_REQUEST, "Key name is required"));
+    }
+    let public_key = plugin_signing::normalize_public_key(&payload.public_key)
+        .map_err(|e| error(StatusCode::BAD_REQUEST, &e.to_string()))?;
+
+    let existing = crate::db::get_trusted_keys(&state.db)
+        .await
+        .map_err(|_| error(StatusCode::INTERNAL_SERVER_ERROR, "Database error"))?;
+    if existing.iter().any(|k| k.public_key == public_key) {
+        return Err(error(StatusCode::CONFLICT, "Key is already trusted"));
+    }
+
+    let key = TrustedKey {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: payload.name.trim().to_string(),
+        public_key,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    crate::db::add_trusted_key(&state.db, &key)
+        .await
+        .map_err(|_| error(StatusCode::INTERNAL_SERVER_ERROR, "Database error"))?;
+
+    Ok(Json(key))
+}
 
-    Ok(Json(PluginStatus::from(plugin)))
+/// Stop trusting a plugin signing key
+async fn delete_trusted_key(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let deleted = crate::db::delete_trusted_key(&state.db, &id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
 }
 
 /// Enable a plugin
@@ -271,8 +880,13 @@ async fn enable_plugin(
     }
 
     supervisor.enable_plugin(&id).await.map_err(|e| {
+        let status = if e.is::<DependencyProblem>() {
+            StatusCode::CONFLICT
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            status,
             Json(serde_json::json!({ "error": format!("Failed to enable plugin: {}", e) })),
         )
     })?;
@@ -314,60 +928,682 @@ async fn disable_plugin(
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
-/// Get plugin frontend bundle (available to all authenticated users)
-async fn get_plugin_bundle(
-    _auth: AuthUser, // Changed from AdminUser to AuthUser
+#[derive(Deserialize)]
+struct ShutdownTimeoutRequest {
+    seconds: u64,
+}
+
+/// Longest grace period a plugin may be given to shut down
+const MAX_SHUTDOWN_TIMEOUT_SECS: u64 = 300;
+
+/// Set how long a plugin gets to exit after the shutdown message before SIGTERM
+async fn set_shutdown_timeout(
+    _auth: AdminUser,
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<impl IntoResponse, StatusCode> {
-    // Security: Validate plugin ID to prevent path traversal attacks
-    if id.contains("..") || id.contains('/') || id.contains('\\') {
-        return Err(StatusCode::BAD_REQUEST);
+    Json(payload): Json<ShutdownTimeoutRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if payload.seconds > MAX_SHUTDOWN_TIMEOUT_SECS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!("Shutdown timeout cannot exceed {}s", MAX_SHUTDOWN_TIMEOUT_SECS)
+            })),
+        ));
     }
 
     let supervisor = state
         .supervisor
         .as_ref()
-        .ok_or(StatusCode::NOT_IMPLEMENTED)?
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
         .lock()
         .await;
-    let plugin = supervisor
-        .get_plugin_status(&id)
-        .ok_or(StatusCode::NOT_FOUND)?;
-
-    // Check if plugin is enabled
-    if !plugin.enabled {
-        return Err(StatusCode::NOT_FOUND);
-    }
-
-    // Get plugin bundle path from plugins directory
-    let plugins_dir = supervisor.get_plugins_dir();
-    let bundle_path = plugins_dir.join(&id).join("bundle.js");
 
-    if !bundle_path.exists() {
-        return Err(StatusCode::NOT_FOUND);
+    if supervisor.get_plugin_status(&id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Plugin not found" })),
+        ));
     }
 
-    let content =
-        fs::read_to_string(&bundle_path).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    supervisor
+        .set_shutdown_timeout(&id, std::time::Duration::from_secs(payload.seconds))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
 
-    Ok((
-        [
-            (header::CONTENT_TYPE, "application/javascript"),
-            (header::CACHE_CONTROL, "no-store, no-cache, must-revalidate"),
-        ],
-        content,
+    Ok(Json(
+        serde_json::json!({ "success": true, "seconds": payload.seconds }),
     ))
 }
 
-#[derive(Deserialize)]
-struct LogQuery {
-    #[serde(default)]
-    page: usize,
-    #[serde(default = "default_page_size")]
-    page_size: usize,
-    #[serde(default)]
+/// Environment variables an admin defined for a plugin
+async fn get_plugin_env(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<HashMap<String, String>>, StatusCode> {
+    let supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or(StatusCode::NOT_IMPLEMENTED)?
+        .lock()
+        .await;
+    if supervisor.get_plugin_status(&id).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(supervisor.plugin_env(&id)))
+}
+
+/// Replace a plugin's environment variables; they apply from its next start
+async fn set_plugin_env(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(env): Json<HashMap<String, String>>,
+) -> Result<Json<HashMap<String, String>>, (StatusCode, Json<serde_json::Value>)> {
+    let supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
+        .lock()
+        .await;
+
+    if supervisor.get_plugin_status(&id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Plugin not found" })),
+        ));
+    }
+
+    supervisor
+        .set_plugin_env(&id, env.clone())
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
+    Ok(Json(env))
+}
+
+#[derive(Deserialize)]
+struct RequestTimeoutRequest {
+    seconds: u64,
+}
+
+/// Longest time a plugin may be given to answer an HTTP request
+const MAX_REQUEST_TIMEOUT_SECS: u64 = 600;
+
+/// Set how long a plugin gets to answer an HTTP request before it times out
+async fn set_request_timeout(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<RequestTimeoutRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if payload.seconds == 0 || payload.seconds > MAX_REQUEST_TIMEOUT_SECS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!("Request timeout must be between 1 and {}s", MAX_REQUEST_TIMEOUT_SECS)
+            })),
+        ));
+    }
+
+    let supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
+        .lock()
+        .await;
+
+    if supervisor.get_plugin_status(&id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Plugin not found" })),
+        ));
+    }
+
+    supervisor
+        .set_request_timeout(&id, std::time::Duration::from_secs(payload.seconds))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
+
+    Ok(Json(
+        serde_json::json!({ "success": true, "seconds": payload.seconds }),
+    ))
+}
+
+/// Override a plugin's memory/CPU limits; they apply from its next start
+async fn set_resource_limits(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(limits): Json<toru_plugin_api::PluginLimits>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if limits.memory_mb == Some(0) || limits.cpu_percent == Some(0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "Limits must be greater than zero" })),
+        ));
+    }
+
+    let supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
+        .lock()
+        .await;
+
+    if supervisor.get_plugin_status(&id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Plugin not found" })),
+        ));
+    }
+
+    supervisor
+        .set_resource_limits(&id, limits)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
+
+    Ok(Json(
+        serde_json::json!({ "success": true, "limits": limits }),
+    ))
+}
+
+/// Override a plugin's KV storage quota; `null` fields are unlimited
+async fn set_kv_quota(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(quota): Json<PluginKvQuota>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if quota.max_bytes == Some(0) || quota.max_keys == Some(0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "Quotas must be greater than zero" })),
+        ));
+    }
+
+    let supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
+        .lock()
+        .await;
+
+    if supervisor.get_plugin_status(&id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Plugin not found" })),
+        ));
+    }
+
+    supervisor.set_kv_quota(&id, quota).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+    })?;
+
+    Ok(Json(
+        serde_json::json!({ "success": true, "kv_quota": quota }),
+    ))
+}
+
+/// Users and roles a plugin is restricted to
+async fn get_plugin_access(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<crate::db::PluginAccess>, (StatusCode, Json<serde_json::Value>)> {
+    crate::db::get_plugin_access(&state.db, &id)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })
+}
+
+/// Restrict a plugin to some users and roles; empty lists open it to everyone
+async fn set_plugin_access(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(access): Json<crate::db::PluginAccess>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(supervisor) = &state.supervisor {
+        if supervisor.lock().await.get_plugin_status(&id).is_none() {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "error": "Plugin not found" })),
+            ));
+        }
+    }
+
+    for user_id in &access.users {
+        let exists = crate::db::get_user_by_id(&state.db, user_id)
+            .await
+            .ok()
+            .flatten()
+            .is_some();
+        if !exists {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": format!("Unknown user: {}", user_id) })),
+            ));
+        }
+    }
+
+    crate::db::set_plugin_access(&state.db, &id, &access)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
+
+    Ok(Json(
+        serde_json::json!({ "success": true, "access": access }),
+    ))
+}
+
+#[derive(Deserialize)]
+struct CapabilitiesRequest {
+    capabilities: Vec<String>,
+}
+
+/// Grant a plugin capabilities (e.g. `call:metrics`), replacing its previous grant
+///
+/// Only capabilities the plugin also asks for in its metadata take effect.
+async fn set_capabilities(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<CapabilitiesRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if payload.capabilities.iter().any(|c| c.trim().is_empty()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "Capabilities cannot be empty" })),
+        ));
+    }
+
+    let supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
+        .lock()
+        .await;
+
+    if supervisor.get_plugin_status(&id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Plugin not found" })),
+        ));
+    }
+
+    supervisor
+        .set_granted_capabilities(&id, &payload.capabilities)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        })?;
+
+    Ok(Json(
+        serde_json::json!({ "success": true, "capabilities": payload.capabilities }),
+    ))
+}
+
+#[derive(Deserialize)]
+struct DevRegisterRequest {
+    /// Unix socket path or `tcp://127.0.0.1:PORT`
+    socket_path: String,
+    /// Handshake secret, for TCP plugins
+    secret: Option<String>,
+    metadata: toru_plugin_api::PluginMetadata,
+}
+
+/// Attach a plugin the developer runs by hand (e.g. `cargo run`) without spawning it
+async fn register_dev_plugin(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Json(payload): Json<DevRegisterRequest>,
+) -> Result<Json<PluginStatus>, (StatusCode, Json<serde_json::Value>)> {
+    // Dev plugins attach arbitrary sockets, so they are refused in production
+    if state.config.server.production {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": "Dev plugins are disabled in production" })),
+        ));
+    }
+
+    let mut supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
+        .lock()
+        .await;
+
+    let plugin_id = payload.metadata.id.clone();
+    supervisor
+        .register_dev_plugin(&payload.socket_path, payload.secret, payload.metadata)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": format!("{:#}", e) })),
+            )
+        })?;
+
+    let process = supervisor.get_plugin_status(&plugin_id).ok_or((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({ "error": "Dev plugin disappeared after registering" })),
+    ))?;
+    let kv_usage = crate::db::plugin_kv_usage(&state.db, &plugin_id).await.ok();
+    Ok(Json(PluginStatus::new(process, &supervisor, kv_usage)))
+}
+
+#[derive(Deserialize)]
+struct CreateInstanceRequest {
+    name: String,
+    #[serde(default)]
+    settings: HashMap<String, String>,
+}
+
+/// Start another instance of an installed plugin, under `<id>@<name>`
+async fn create_plugin_instance(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(payload): Json<CreateInstanceRequest>,
+) -> Result<(StatusCode, Json<PluginStatus>), (StatusCode, Json<serde_json::Value>)> {
+    let mut supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or((
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Plugin supervisor not initialized" })),
+        ))?
+        .lock()
+        .await;
+
+    if supervisor.get_plugin_status(&id).is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Plugin not found" })),
+        ));
+    }
+
+    let instance = supervisor
+        .create_instance(&id, &payload.name, payload.settings)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": format!("{:#}", e) })),
+            )
+        })?;
+
+    let instance_id = instance.id();
+    let process = supervisor.get_plugin_status(&instance_id).ok_or((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({ "error": "Instance disappeared after starting" })),
+    ))?;
+    let kv_usage = crate::db::plugin_kv_usage(&state.db, &instance_id)
+        .await
+        .ok();
+    Ok((
+        StatusCode::CREATED,
+        Json(PluginStatus::new(process, &supervisor, kv_usage)),
+    ))
+}
+
+/// Stop an instance of a plugin and delete it; its KV data is kept
+async fn remove_plugin_instance(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path((id, name)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let mut supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or(StatusCode::NOT_IMPLEMENTED)?
+        .lock()
+        .await;
+
+    let instance_id = format!("{}{}{}", id, INSTANCE_SEPARATOR, name);
+    if supervisor.plugin_instance(&instance_id).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    supervisor
+        .remove_instance(&instance_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to remove plugin instance {}: {}", instance_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Detach a dev plugin (its process keeps running)
+async fn unregister_dev_plugin(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let mut supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or(StatusCode::NOT_IMPLEMENTED)?
+        .lock()
+        .await;
+
+    if supervisor.unregister_dev_plugin(&id).await {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Get plugin frontend bundle (available to all authenticated users)
+///
+/// The response carries a KV grant for the caller's session in
+/// [`KV_GRANT_HEADER`].
+async fn get_plugin_bundle(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    // Security: Validate plugin ID to prevent path traversal attacks
+    if id.contains("..") || id.contains('/') || id.contains('\\') {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !has_plugin_access(&state, &auth, &id).await {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let supervisor = state
+        .supervisor
+        .as_ref()
+        .ok_or(StatusCode::NOT_IMPLEMENTED)?
+        .lock()
+        .await;
+    let plugin = supervisor
+        .get_plugin_status(&id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Check if plugin is enabled
+    if !plugin.enabled {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // Get plugin bundle path from plugins directory
+    let bundle_path = supervisor.plugin_files_dir(&id).join("bundle.js");
+
+    if !bundle_path.exists() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let content =
+        fs::read_to_string(&bundle_path).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    drop(supervisor);
+
+    // The page that loads the bundle may use the plugin's KV namespace
+    let token = crate::db::plugin_kv_grant_create(
+        &state.db,
+        &id,
+        &auth.session_id,
+        chrono::Duration::minutes(KV_GRANT_TTL_MINUTES),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/javascript".to_string()),
+            (
+                header::CACHE_CONTROL,
+                "no-store, no-cache, must-revalidate".to_string(),
+            ),
+            (HeaderName::from_static(KV_GRANT_HEADER), token),
+        ],
+        content,
+    ))
+}
+
+/// Get a static asset of a plugin frontend (available to all authenticated users)
+///
+/// Files come from `<plugins_dir>/<id>/assets/`. Responses carry an ETag and
+/// Last-Modified, and a matching `If-None-Match` gets 304 Not Modified.
+async fn get_plugin_asset(
+    auth: AuthUser,
+    State(state): State<AppState>,
+    Path((id, path)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    // Security: Validate plugin ID to prevent path traversal attacks
+    if id.contains("..") || id.contains('/') || id.contains('\\') {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !has_plugin_access(&state, &auth, &id).await {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let plugin_dir = {
+        let supervisor = state
+            .supervisor
+            .as_ref()
+            .ok_or(StatusCode::NOT_IMPLEMENTED)?
+            .lock()
+            .await;
+        let plugin = supervisor
+            .get_plugin_status(&id)
+            .ok_or(StatusCode::NOT_FOUND)?;
+        if !plugin.enabled {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        supervisor.plugin_files_dir(&id)
+    };
+
+    let asset = plugin_assets::read_asset(&plugin_dir, &path)
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let cache_control = format!("private, max-age={}", ASSET_MAX_AGE_SECS);
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == asset.etag));
+    if not_modified {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [
+                (header::ETAG, asset.etag),
+                (header::CACHE_CONTROL, cache_control),
+            ],
+        )
+            .into_response());
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, asset.content_type),
+            (header::ETAG, asset.etag),
+            (header::LAST_MODIFIED, asset.last_modified),
+            (header::CACHE_CONTROL, cache_control),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        asset.content,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct LogQuery {
+    #[serde(default)]
+    page: usize,
+    #[serde(default = "default_page_size")]
+    page_size: usize,
+    #[serde(default)]
     level: Option<String>,
+    /// RFC 3339 time of the earliest entry to return
+    #[serde(default)]
+    from: Option<String>,
+    /// RFC 3339 time of the latest entry to return
+    #[serde(default)]
+    to: Option<String>,
+    /// Text the message or error must contain, case-insensitively
+    #[serde(default)]
+    q: Option<String>,
+    /// Treat `q` as a regular expression
+    #[serde(default)]
+    regex: bool,
 }
 
 fn default_page_size() -> usize {
@@ -395,12 +1631,28 @@ async fn get_plugin_logs(
 
     let plugin_logger = supervisor.plugin_logger();
 
-    // Parse log level filter
-    let filter_level = query.level.as_ref().and_then(|l| LogLevel::parse_level(l));
+    // Parse the filters
+    let timestamp = |t: &Option<String>| {
+        t.as_deref()
+            .map(|t| DateTime::parse_from_rfc3339(t).map(|t| t.with_timezone(&Utc)))
+            .transpose()
+            .map_err(|_| StatusCode::BAD_REQUEST)
+    };
+    let text = match query.q.as_deref().filter(|q| !q.is_empty()) {
+        Some(q) if query.regex => Some(TextFilter::regex(q).map_err(|_| StatusCode::BAD_REQUEST)?),
+        Some(q) => Some(TextFilter::substring(q)),
+        None => None,
+    };
+    let filter = LogFilter {
+        level: query.level.as_ref().and_then(|l| LogLevel::parse_level(l)),
+        from: timestamp(&query.from)?,
+        to: timestamp(&query.to)?,
+        text,
+    };
 
     // Read logs with pagination and filtering
     let logs = plugin_logger
-        .read_plugin_logs(&id, filter_level, query.page, query.page_size)
+        .read_plugin_logs(&id, &filter, query.page, query.page_size)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -418,32 +1670,143 @@ struct LogsResponse {
     page_size: usize,
 }
 
+#[derive(Deserialize)]
+struct EventsQuery {
+    #[serde(default = "default_events_limit")]
+    limit: i32,
+}
+
+fn default_events_limit() -> i32 {
+    100
+}
+
+/// Maximum number of events returned by a single request
+const MAX_EVENTS_LIMIT: i32 = 1000;
+
+/// Get recent lifecycle events of a plugin
+async fn get_plugin_events(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Json<Vec<PluginEvent>>, StatusCode> {
+    let limit = query.limit.clamp(1, MAX_EVENTS_LIMIT);
+    let events = crate::db::plugin_event_get_recent(&state.db, &id, limit)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(events))
+}
+
+/// Get recent lifecycle events of all plugins (for the dashboard)
+async fn get_all_plugin_events(
+    _auth: AdminUser,
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Json<Vec<PluginEvent>>, StatusCode> {
+    let limit = query.limit.clamp(1, MAX_EVENTS_LIMIT);
+    let events = crate::db::plugin_event_get_all_recent(&state.db, limit)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(events))
+}
+
 /// KV operation request
 #[derive(Deserialize)]
 struct KvOperation {
     action: String, // "get", "set", "delete"
     key: String,
     value: Option<String>,
+    /// Seconds until a set key expires
+    ttl_secs: Option<u64>,
+    /// Key prefix, cursor and page size of a list
+    #[serde(default)]
+    prefix: String,
+    cursor: Option<String>,
+    limit: Option<usize>,
+    /// Amount to add in an incr (default 1)
+    by: Option<i64>,
+    /// Current and new value of a cas
+    expected: Option<String>,
+    new: Option<String>,
 }
 
 /// KV operation response
 #[derive(Serialize)]
 struct KvResponse {
     value: Option<String>,
+    /// Keys and next cursor of a list
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    page: Option<KvPage>,
 }
 
+/// Header carrying a KV grant token, sent with the plugin's bundle
+const KV_GRANT_HEADER: &str = "x-plugin-kv-token";
+
+/// How long a KV grant lasts; serving the bundle again extends it
+const KV_GRANT_TTL_MINUTES: i64 = 15;
+
 /// Handle KV storage operations for plugins
+///
+/// Admins may use any namespace; other users need a grant token for the
+/// plugin in [`KV_GRANT_HEADER`]. Every operation is recorded as a plugin event.
 async fn plugin_kv_handler(
-    _auth: AuthUser,
+    auth: AuthUser,
     State(state): State<AppState>,
     Path(id): Path<String>,
+    headers: HeaderMap,
     Json(op): Json<KvOperation>,
 ) -> Result<Json<KvResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let granted = match headers
+        .get(KV_GRANT_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(token) => crate::db::plugin_kv_grant_valid(&state.db, token, &id, &auth.session_id)
+            .await
+            .unwrap_or(false),
+        None => false,
+    };
+    let allowed = auth.is_admin() || (granted && has_plugin_access(&state, &auth, &id).await);
+
+    let action = op.action.clone();
+    let key = op.key.clone();
+    let result = if allowed {
+        run_kv_operation(&state, &id, op).await
+    } else {
+        Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": "No KV access to this plugin" })),
+        ))
+    };
+
+    let details = serde_json::json!({
+        "user": auth.username,
+        "action": action,
+        "key": key,
+        "status": result.as_ref().map_or_else(|(status, _)| status.as_u16(), |_| 200),
+    });
+    let event_type = if allowed { "kv_access" } else { "kv_denied" };
+    let _ =
+        crate::db::plugin_event_log(&state.db, &id, event_type, Some(&details.to_string())).await;
+
+    result.map(Json)
+}
+
+/// Run a KV operation on plugin `id`'s namespace
+async fn run_kv_operation(
+    state: &AppState,
+    id: &str,
+    op: KvOperation,
+) -> Result<KvResponse, (StatusCode, Json<serde_json::Value>)> {
+    let quota = match &state.supervisor {
+        Some(supervisor) => supervisor.lock().await.kv_quota(id),
+        None => PluginKvQuota::default(),
+    };
+
     // Validate action
     match op.action.as_str() {
         "get" => {
             // Get value from database
-            let value = crate::db::plugin_kv_get(&state.db, &id, &op.key)
+            let value = crate::db::plugin_kv_get(&state.db, id, &op.key)
                 .await
                 .map_err(|e| {
                     (
@@ -451,7 +1814,7 @@ async fn plugin_kv_handler(
                         Json(serde_json::json!({ "error": format!("Failed to get KV: {}", e) })),
                     )
                 })?;
-            Ok(Json(KvResponse { value }))
+            Ok(KvResponse { value, page: None })
         }
         "set" => {
             // Set value in database
@@ -460,20 +1823,18 @@ async fn plugin_kv_handler(
                 Json(serde_json::json!({ "error": "Missing 'value' field for set operation" })),
             ))?;
 
-            crate::db::plugin_kv_set(&state.db, &id, &op.key, &value)
+            crate::db::plugin_kv_set(&state.db, id, &op.key, &value, op.ttl_secs, quota)
                 .await
-                .map_err(|e| {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(serde_json::json!({ "error": format!("Failed to set KV: {}", e) })),
-                    )
-                })?;
+                .map_err(|e| kv_write_error("set", e))?;
 
-            Ok(Json(KvResponse { value: Some(value) }))
+            Ok(KvResponse {
+                value: Some(value),
+                page: None,
+            })
         }
         "delete" => {
             // Delete value from database
-            crate::db::plugin_kv_delete(&state.db, &id, &op.key)
+            crate::db::plugin_kv_delete(&state.db, id, &op.key)
                 .await
                 .map_err(|e| {
                     (
@@ -482,7 +1843,65 @@ async fn plugin_kv_handler(
                     )
                 })?;
 
-            Ok(Json(KvResponse { value: None }))
+            Ok(KvResponse {
+                value: None,
+                page: None,
+            })
+        }
+        "list" => {
+            let limit = KvOp::list_limit(op.limit);
+            let keys = crate::db::plugin_kv_list_keys(
+                &state.db,
+                id,
+                &op.prefix,
+                op.cursor.as_deref(),
+                limit + 1,
+            )
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({ "error": format!("Failed to list KV: {}", e) })),
+                )
+            })?;
+
+            Ok(KvResponse {
+                value: None,
+                page: Some(KvPage::from_keys(keys, limit)),
+            })
+        }
+        "incr" => {
+            let value =
+                crate::db::plugin_kv_incr(&state.db, id, &op.key, op.by.unwrap_or(1), quota)
+                    .await
+                    .map_err(|e| kv_write_error("increment", e))?;
+
+            Ok(KvResponse {
+                value: Some(value.to_string()),
+                page: None,
+            })
+        }
+        "cas" => {
+            let new = op.new.ok_or((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "Missing 'new' field for cas operation" })),
+            ))?;
+
+            let swapped = crate::db::plugin_kv_compare_and_swap(
+                &state.db,
+                id,
+                &op.key,
+                op.expected.as_deref(),
+                &new,
+                quota,
+            )
+            .await
+            .map_err(|e| kv_write_error("swap", e))?;
+
+            Ok(KvResponse {
+                value: Some(swapped.to_string()),
+                page: None,
+            })
         }
         _ => Err((
             StatusCode::BAD_REQUEST,
@@ -490,3 +1909,16 @@ async fn plugin_kv_handler(
         )),
     }
 }
+
+/// Error response of a failed KV write; 507 when the plugin is over its quota
+fn kv_write_error(action: &str, e: anyhow::Error) -> (StatusCode, Json<serde_json::Value>) {
+    let status = if e.downcast_ref::<KvQuotaExceeded>().is_some() {
+        StatusCode::INSUFFICIENT_STORAGE
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    (
+        status,
+        Json(serde_json::json!({ "error": format!("Failed to {} KV: {}", action, e) })),
+    )
+}
@@ -1,26 +1,102 @@
 use axum::{
-    extract::{ws::Message, State, WebSocketUpgrade},
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 use axum_extra::extract::cookie::CookieJar;
-use futures::{SinkExt, StreamExt};
+use futures::{stream::SplitSink, SinkExt, StreamExt};
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::Instant;
 use uuid::Uuid;
 
-use crate::db::{self, UserRole};
+use crate::db::{self, ConcurrencyMode, UserRole};
 use crate::routes::api::AppState;
 use crate::routes::auth::SESSION_COOKIE_NAME;
+use crate::services::action_params;
 use crate::services::auth::validate_session;
 use crate::services::executor::{self, TaskMessage};
+use crate::services::logging::LogEntry;
+use crate::services::task_output::{SequencedMessage, TaskOutputHub};
+
+type WsSender = Arc<Mutex<SplitSink<WebSocket, Message>>>;
 
 #[derive(Deserialize)]
 struct ClientMessage {
     r#type: String,
     script: Option<String>,
     task_id: Option<String>,
+    pipeline_id: Option<String>,
+    /// Subscription channel, e.g. `logs`
+    channel: Option<String>,
+    plugin_id: Option<String>,
+    /// Seconds between pushes for `subscribe_resources`
+    interval_secs: Option<u64>,
+    /// Last `seq` received per task, for `resume`
+    tasks: Option<HashMap<String, u64>>,
+    /// Parameter values of the quick action `run` starts
+    params: Option<HashMap<String, serde_json::Value>>,
+    /// The quick action's name, typed to confirm a `run` that needs confirmation
+    confirm: Option<String>,
+}
+
+/// Seconds between resource snapshots unless the client asks otherwise
+const DEFAULT_RESOURCE_INTERVAL_SECS: u64 = 2;
+
+/// Shortest and longest interval a client may ask for
+const MIN_RESOURCE_INTERVAL_SECS: u64 = 1;
+const MAX_RESOURCE_INTERVAL_SECS: u64 = 60;
+
+/// Setting with the seconds between pings sent to the client
+pub const PING_INTERVAL_SETTING: &str = "ws_ping_interval_secs";
+/// Setting with the seconds without any frame from the client after which it's disconnected
+pub const IDLE_TIMEOUT_SETTING: &str = "ws_idle_timeout_secs";
+/// Setting with the seconds a connection may stay open (`0` for no limit); clients reconnect
+pub const MAX_LIFETIME_SETTING: &str = "ws_max_lifetime_secs";
+
+const DEFAULT_PING_INTERVAL_SECS: u64 = 30;
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 90;
+const DEFAULT_MAX_LIFETIME_SECS: u64 = 24 * 60 * 60;
+
+/// How long sending a ping or close frame may take
+const CONTROL_SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a connection detects dead peers and how long it may live
+struct Heartbeat {
+    ping_interval: Duration,
+    idle_timeout: Duration,
+    max_lifetime: Option<Duration>,
+}
+
+impl Heartbeat {
+    async fn from_settings(pool: &db::DbPool) -> Self {
+        let secs = |key, default| async move {
+            db::get_setting(pool, key)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let ping_interval = secs(PING_INTERVAL_SETTING, DEFAULT_PING_INTERVAL_SECS)
+            .await
+            .max(1);
+        let idle_timeout = secs(IDLE_TIMEOUT_SETTING, DEFAULT_IDLE_TIMEOUT_SECS)
+            .await
+            .max(ping_interval);
+        let max_lifetime = secs(MAX_LIFETIME_SETTING, DEFAULT_MAX_LIFETIME_SECS).await;
+        Self {
+            ping_interval: Duration::from_secs(ping_interval),
+            idle_timeout: Duration::from_secs(idle_timeout),
+            max_lifetime: (max_lifetime > 0).then(|| Duration::from_secs(max_lifetime)),
+        }
+    }
 }
 
 pub async fn handle_websocket(
@@ -43,22 +119,43 @@ pub async fn handle_websocket(
         }
     };
 
-    let is_admin = session.user_role == UserRole::Admin;
-    let session_id = session.id.clone();
-
-    ws.on_upgrade(move |socket| handle_socket(socket, state, session_id, is_admin))
+    ws.on_upgrade(move |socket| handle_socket(socket, state, session))
 }
 
-async fn handle_socket(
-    socket: axum::extract::ws::WebSocket,
-    state: AppState,
-    session_id: String,
-    is_admin: bool,
-) {
+async fn handle_socket(socket: WebSocket, state: AppState, session: db::Session) {
+    let is_admin = session.user_role == UserRole::Admin;
+    let session_id = session.id.clone();
     let (sender, mut receiver) = socket.split();
     let sender = Arc::new(Mutex::new(sender));
-    let registry = executor::create_task_registry();
+    let registry = state.tasks.clone();
     let mut session_check_interval = tokio::time::interval(std::time::Duration::from_secs(300)); // 5 minutes
+    let mut plugin_events = match &state.supervisor {
+        Some(supervisor) => Some(supervisor.lock().await.subscribe_events()),
+        None => None,
+    };
+    let mut plugin_notifications = match &state.supervisor {
+        Some(supervisor) => Some(supervisor.lock().await.subscribe_notifications()),
+        None => None,
+    };
+    let mut power_alerts = Some(state.power_alerts.subscribe());
+    let mut system_notifications = Some(state.notification_hub.subscribe());
+    // Plugins whose log is tailed; the receiver only exists while there are some
+    let mut tailed_plugins: HashSet<String> = HashSet::new();
+    let mut plugin_logs: Option<broadcast::Receiver<LogEntry>> = None;
+    // Set while the client is subscribed to resource snapshots
+    let mut resource_interval: Option<tokio::time::Interval> = None;
+    // Tasks whose output goes to this client, with the last `seq` sent
+    let mut task_output = state.task_output.subscribe();
+    let mut followed: HashMap<String, u64> = HashMap::new();
+    // Any frame from the client (pongs included) shows it's still there
+    let heartbeat = Heartbeat::from_settings(&state.db).await;
+    let mut ping_interval = tokio::time::interval_at(
+        Instant::now() + heartbeat.ping_interval,
+        heartbeat.ping_interval,
+    );
+    let mut last_seen = Instant::now();
+    let lifetime = next_deadline(heartbeat.max_lifetime.map(|max| Instant::now() + max));
+    tokio::pin!(lifetime);
 
     loop {
         tokio::select! {
@@ -71,6 +168,7 @@ async fn handle_socket(
                         task_id: None,
                         data: Some("Session expired".to_string()),
                         code: None,
+                        step: None,
                      };
                      let mut s = sender.lock().await;
                      let _ = s.send(Message::Text(
@@ -80,12 +178,150 @@ async fn handle_socket(
                  }
              }
 
+             _ = ping_interval.tick() => {
+                 if last_seen.elapsed() > heartbeat.idle_timeout {
+                     tracing::debug!("WebSocket client stopped answering, closing");
+                     close(&sender, close_code::AWAY, "Idle timeout").await;
+                     break;
+                 }
+                 // A dead peer can leave the send stuck on a full socket buffer
+                 let mut s = sender.lock().await;
+                 let ping = tokio::time::timeout(CONTROL_SEND_TIMEOUT, s.send(Message::Ping(Vec::new())));
+                 if !matches!(ping.await, Ok(Ok(()))) {
+                     break;
+                 }
+             }
+
+             _ = &mut lifetime => {
+                 // The client reconnects, and resumes its tasks' output
+                 close(&sender, close_code::AWAY, "Connection lifetime reached").await;
+                 break;
+             }
+
+             event = next_broadcast(&mut plugin_events, "plugin events") => {
+                 // Like notifications, events only reach users with access to the plugin
+                 let allowed = db::get_plugin_access(&state.db, &event.plugin_id)
+                     .await
+                     .is_ok_and(|access| access.allows(session.user_id.as_deref(), session.user_role));
+                 if !allowed {
+                     continue;
+                 }
+                 let event_msg = serde_json::json!({
+                     "type": "plugin_event",
+                     "plugin_id": event.plugin_id,
+                     "name": event.name,
+                     "data": event.data,
+                     "timestamp": event.timestamp,
+                 });
+                 let mut s = sender.lock().await;
+                 if s.send(Message::Text(event_msg.to_string())).await.is_err() {
+                     break;
+                 }
+             }
+
+             notification = next_broadcast(&mut plugin_notifications, "plugin notifications") => {
+                 // Only users with access to the plugin see its notifications
+                 let allowed = db::get_plugin_access(&state.db, &notification.plugin_id)
+                     .await
+                     .is_ok_and(|access| access.allows(session.user_id.as_deref(), session.user_role));
+                 if !allowed {
+                     continue;
+                 }
+                 let notification_msg = serde_json::json!({
+                     "type": "plugin_notification",
+                     "plugin_id": notification.plugin_id,
+                     "level": notification.level,
+                     "title": notification.title,
+                     "body": notification.body,
+                     "timestamp": notification.timestamp,
+                 });
+                 let mut s = sender.lock().await;
+                 if s.send(Message::Text(notification_msg.to_string())).await.is_err() {
+                     break;
+                 }
+             }
+
+             alert = next_broadcast(&mut power_alerts, "power alerts") => {
+                 let alert_msg = serde_json::json!({
+                     "type": "power_alert",
+                     "alert": alert,
+                 });
+                 let mut s = sender.lock().await;
+                 if s.send(Message::Text(alert_msg.to_string())).await.is_err() {
+                     break;
+                 }
+             }
+
+             notification = next_broadcast(&mut system_notifications, "system notifications") => {
+                 if !notification.audience.includes(session.user_role) {
+                     continue;
+                 }
+                 let notification_msg = serde_json::json!({
+                     "type": "system_notification",
+                     "notification": notification,
+                 });
+                 let mut s = sender.lock().await;
+                 if s.send(Message::Text(notification_msg.to_string())).await.is_err() {
+                     break;
+                 }
+             }
+
+             entry = next_broadcast(&mut plugin_logs, "plugin log entries") => {
+                 let Some(plugin_id) = entry.plugin.clone() else { continue };
+                 if !tailed_plugins.contains(&plugin_id) {
+                     continue;
+                 }
+                 let log_msg = serde_json::json!({
+                     "type": "plugin_log",
+                     "plugin_id": plugin_id,
+                     "entry": entry,
+                 });
+                 let mut s = sender.lock().await;
+                 if s.send(Message::Text(log_msg.to_string())).await.is_err() {
+                     break;
+                 }
+             }
+
+             result = task_output.recv() => {
+                 let messages = match result {
+                     Ok(message) => vec![message],
+                     // Fell behind the live output: take what was missed from the buffers
+                     Err(broadcast::error::RecvError::Lagged(_)) => followed
+                         .iter()
+                         .filter_map(|(task_id, last_seq)| {
+                             state.task_output.replay(task_id, *last_seq, session.user_id.as_deref(), is_admin)
+                         })
+                         .flat_map(|replay| replay.messages)
+                         .collect(),
+                     Err(broadcast::error::RecvError::Closed) => break,
+                 };
+                 if !send_task_messages(&sender, &mut followed, messages).await {
+                     break;
+                 }
+             }
+
+             _ = next_tick(&mut resource_interval) => {
+                 let Some(resources) = state.resources.snapshot() else { continue };
+                 let resources_msg = serde_json::json!({
+                     "type": "resources",
+                     "resources": resources,
+                 });
+                 let mut s = sender.lock().await;
+                 if s.send(Message::Text(resources_msg.to_string())).await.is_err() {
+                     break;
+                 }
+             }
+
              msg = receiver.next() => {
                 let msg = match msg {
                     Some(Ok(msg)) => msg,
                     Some(Err(_)) => break,
                     None => break,
                 };
+                last_seen = Instant::now();
+                if matches!(msg, Message::Close(_)) {
+                    break;
+                }
 
                 let text = match msg.to_text() {
                     Ok(text) => text,
@@ -101,14 +337,13 @@ async fn handle_socket(
                     "run" => {
                         if let Some(script_name) = client_msg.script {
                             // Check permissions
-                            let mut allowed = is_admin;
-                            if !allowed {
-                                // Check if it's a quick action
-                                let actions = db::get_quick_actions(&state.db).await.unwrap_or_default();
-                                if actions.iter().any(|a| a.script_path == script_name) {
-                                    allowed = true; // Allowed if it matches a registered quick action
-                                }
-                            }
+                            let actions = db::get_quick_actions(&state.db).await.unwrap_or_default();
+                            let quick_action = actions.iter().find(|a| {
+                                a.script_path == script_name
+                                    && a.visible_to(session.user_role, session.user_id.as_deref())
+                            });
+                            // Non-admins may only run scripts of quick actions visible to them
+                            let allowed = is_admin || quick_action.is_some();
 
                             if !allowed {
                                 let error_msg = TaskMessage {
@@ -116,6 +351,7 @@ async fn handle_socket(
                                     task_id: None,
                                     data: Some("Admin access required to run this script".to_string()),
                                     code: None,
+                                    step: None,
                                 };
                                 let mut s = sender.lock().await;
                                 let _ = s.send(Message::Text(
@@ -132,20 +368,50 @@ async fn handle_socket(
                             let script_path = format!("{}/{}", scripts_dir, script_name);
                             let task_id = Uuid::new_v4().to_string();
 
-                            // Create channel for streaming output back to WS
-                            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-                            let sender_clone = sender.clone();
-
-                            // Bridge task: MPSC -> WebSocket
-                            tokio::spawn(async move {
-                                while let Some(msg) = rx.recv().await {
-                                    let text = serde_json::to_string(&msg).unwrap();
-                                    let mut s = sender_clone.lock().await;
-                                    if s.send(Message::Text(text)).await.is_err() {
-                                        break;
+                            // Global limits, overridden by the matching quick action if any
+                            let mut limits = executor::ResourceLimits::from_settings(&state.db).await;
+                            let mut concurrency = ConcurrencyMode::default();
+                            let mut env = HashMap::new();
+                            if let Some(action) = quick_action {
+                                if !action.is_confirmed_by(client_msg.confirm.as_deref()) {
+                                    let error_msg = TaskMessage {
+                                        r#type: "error".to_string(),
+                                        task_id: None,
+                                        data: Some(format!("Type \"{}\" to confirm this action", action.name)),
+                                        code: None,
+                                        step: None,
+                                    };
+                                    let mut s = sender.lock().await;
+                                    let _ = s.send(Message::Text(
+                                        serde_json::to_string(&error_msg).unwrap(),
+                                    )).await;
+                                    continue;
+                                }
+                                limits = limits.with_action_overrides(action);
+                                concurrency = action.concurrency;
+                                env = match action_params::run_env(action, &client_msg.params.unwrap_or_default()) {
+                                    Ok(env) => env,
+                                    Err(e) => {
+                                        let error_msg = TaskMessage::event("error", &task_id)
+                                            .with_data(&e.to_string());
+                                        let mut s = sender.lock().await;
+                                        let _ = s.send(Message::Text(
+                                            serde_json::to_string(&error_msg).unwrap(),
+                                        )).await;
+                                        continue;
                                     }
+                                };
+                            }
+
+                            if let Some(action) = quick_action.filter(|a| a.needs_confirmation()) {
+                                if let Err(e) = db::record_action_audit(&state.db, action, &session.username, &task_id).await {
+                                    tracing::warn!("Failed to write quick action audit log: {}", e);
                                 }
-                            });
+                            }
+
+                            // Output reaches this client through the task output hub
+                            followed.insert(task_id.clone(), 0);
+                            let tx = task_event_sender(state.task_output.clone(), session.user_id.clone());
 
                             // Run the task (detached)
                             let _ = executor::run_script_task(
@@ -154,18 +420,77 @@ async fn handle_socket(
                                 script_name,
                                 state.db.clone(),
                                 registry.clone(),
+                                limits,
+                                concurrency,
+                                env,
                                 Some(tx) // Pass the sender to stream output
                             ).await;
                         }
                     }
+                    "run_pipeline" => {
+                        if let Some(pipeline_id) = client_msg.pipeline_id {
+                            let pipeline = if is_admin {
+                                db::get_pipeline(&state.db, &pipeline_id).await.ok().flatten()
+                            } else {
+                                None
+                            };
+
+                            let Some(pipeline) = pipeline else {
+                                let error = if is_admin {
+                                    "Pipeline not found"
+                                } else {
+                                    "Admin access required to run pipelines"
+                                };
+                                let error_msg = TaskMessage {
+                                    r#type: "error".to_string(),
+                                    task_id: None,
+                                    data: Some(error.to_string()),
+                                    code: None,
+                                    step: None,
+                                };
+                                let mut s = sender.lock().await;
+                                let _ = s.send(Message::Text(
+                                    serde_json::to_string(&error_msg).unwrap(),
+                                )).await;
+                                continue;
+                            };
+
+                            let scripts_dir = db::get_setting(&state.db, "scripts_dir")
+                                .await
+                                .unwrap_or_else(|_| Some("./scripts".to_string()))
+                                .unwrap_or_else(|| "./scripts".to_string());
+
+                            let task_id = Uuid::new_v4().to_string();
+                            let limits = executor::ResourceLimits::from_settings(&state.db).await;
+                            followed.insert(task_id.clone(), 0);
+                            let tx = task_event_sender(state.task_output.clone(), session.user_id.clone());
+
+                            // Run the pipeline (detached)
+                            let _ = executor::run_pipeline_task(
+                                pipeline,
+                                scripts_dir,
+                                task_id,
+                                state.db.clone(),
+                                registry.clone(),
+                                limits,
+                                Some(tx),
+                            ).await;
+                        }
+                    }
                     "cancel" => {
                         if let Some(task_id) = client_msg.task_id {
+                            // Admins may cancel any task, others only the ones they follow
+                            if !is_admin && !followed.contains_key(&task_id) {
+                                continue;
+                            }
+                            let registry = executor::active_task_registry(&task_id).unwrap_or_else(|| registry.clone());
                             if executor::cancel_task(&task_id, &registry).await.unwrap_or(false) {
                                 let cancelled_msg = TaskMessage {
                                     r#type: "cancelled".to_string(),
                                     task_id: Some(task_id.clone()),
                                     data: None,
                                     code: None,
+                                    step: None,
                                 };
                                 let mut s = sender.lock().await;
                                 let _ = s.send(Message::Text(
@@ -177,9 +502,183 @@ async fn handle_socket(
                             }
                         }
                     }
+                    "resume" => {
+                        let mut messages = Vec::new();
+                        for (task_id, last_seq) in client_msg.tasks.unwrap_or_default() {
+                            let replay = state.task_output.replay(
+                                &task_id,
+                                last_seq,
+                                session.user_id.as_deref(),
+                                is_admin,
+                            );
+                            // Unknown, expired or started by another user
+                            let Some(replay) = replay else {
+                                let error_msg = TaskMessage::event("error", &task_id)
+                                    .with_data("Task output is no longer available");
+                                let mut s = sender.lock().await;
+                                let _ = s.send(Message::Text(
+                                    serde_json::to_string(&error_msg).unwrap(),
+                                )).await;
+                                continue;
+                            };
+                            if replay.dropped > 0 {
+                                let truncated_msg = TaskMessage::event("truncated", &task_id).with_data(&format!(
+                                    "[{} messages missed while disconnected, full log on disk]",
+                                    replay.dropped
+                                ));
+                                let mut s = sender.lock().await;
+                                let _ = s.send(Message::Text(
+                                    serde_json::to_string(&truncated_msg).unwrap(),
+                                )).await;
+                            }
+                            followed.insert(task_id, last_seq);
+                            messages.extend(replay.messages);
+                        }
+                        if !send_task_messages(&sender, &mut followed, messages).await {
+                            break;
+                        }
+                    }
+                    "subscribe" | "unsubscribe" => {
+                        let subscribe = client_msg.r#type == "subscribe";
+                        let error = match (client_msg.channel.as_deref(), client_msg.plugin_id) {
+                            (Some("logs"), Some(_)) if !is_admin => {
+                                Some("Admin access required to tail plugin logs")
+                            }
+                            (Some("logs"), Some(_)) if state.supervisor.is_none() => {
+                                Some("Plugin system not available")
+                            }
+                            (Some("logs"), Some(plugin_id)) => {
+                                if subscribe {
+                                    tailed_plugins.insert(plugin_id);
+                                } else {
+                                    tailed_plugins.remove(&plugin_id);
+                                }
+                                if tailed_plugins.is_empty() {
+                                    plugin_logs = None;
+                                } else if plugin_logs.is_none() {
+                                    if let Some(supervisor) = &state.supervisor {
+                                        plugin_logs = Some(supervisor.lock().await.subscribe_logs());
+                                    }
+                                }
+                                None
+                            }
+                            (Some("logs"), None) => Some("plugin_id is required for the logs channel"),
+                            _ => Some("Unknown subscription channel"),
+                        };
+
+                        if let Some(error) = error {
+                            let error_msg = TaskMessage {
+                                r#type: "error".to_string(),
+                                task_id: None,
+                                data: Some(error.to_string()),
+                                code: None,
+                                step: None,
+                            };
+                            let mut s = sender.lock().await;
+                            let _ = s.send(Message::Text(
+                                serde_json::to_string(&error_msg).unwrap(),
+                            )).await;
+                        }
+                    }
+                    "subscribe_resources" => {
+                        let secs = client_msg
+                            .interval_secs
+                            .unwrap_or(DEFAULT_RESOURCE_INTERVAL_SECS)
+                            .clamp(MIN_RESOURCE_INTERVAL_SECS, MAX_RESOURCE_INTERVAL_SECS);
+                        // The first tick is immediate, so the client gets a snapshot right away
+                        let mut interval =
+                            tokio::time::interval(std::time::Duration::from_secs(secs));
+                        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                        resource_interval = Some(interval);
+                    }
+                    "unsubscribe_resources" => {
+                        resource_interval = None;
+                    }
                     _ => {}
                 }
              }
         }
     }
 }
+
+/// Next value from a broadcast channel; never resolves when there is no receiver
+/// (e.g. no plugin supervisor)
+async fn next_broadcast<T: Clone>(receiver: &mut Option<broadcast::Receiver<T>>, what: &str) -> T {
+    if let Some(receiver) = receiver {
+        loop {
+            match receiver.recv().await {
+                Ok(value) => return value,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("WebSocket client missed {} {}", missed, what);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+    std::future::pending().await
+}
+
+/// Sleep until `deadline`; never resolves when there is none
+async fn next_deadline(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Send a close frame; the connection ends either way
+async fn close(sender: &WsSender, code: u16, reason: &'static str) {
+    let frame = CloseFrame {
+        code,
+        reason: reason.into(),
+    };
+    let mut s = sender.lock().await;
+    let _ = tokio::time::timeout(CONTROL_SEND_TIMEOUT, s.send(Message::Close(Some(frame)))).await;
+}
+
+/// Next tick of an optional interval; never resolves when there is none
+async fn next_tick(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Send the messages of followed tasks the client hasn't had yet; `false` once the socket is closed
+async fn send_task_messages(
+    sender: &WsSender,
+    followed: &mut HashMap<String, u64>,
+    messages: Vec<SequencedMessage>,
+) -> bool {
+    for message in messages {
+        let Some(last_seq) = followed.get_mut(message.task_id()) else {
+            continue;
+        };
+        if message.seq <= *last_seq {
+            continue;
+        }
+        *last_seq = message.seq;
+        if message.is_final() {
+            followed.remove(message.task_id());
+        }
+        let text = serde_json::to_string(&message).unwrap();
+        let mut s = sender.lock().await;
+        if s.send(Message::Text(text)).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Event channel of a task started by `owner`; its messages go to the task output hub
+fn task_event_sender(hub: Arc<TaskOutputHub>, owner: Option<String>) -> executor::EventSender {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<TaskMessage>();
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            hub.publish(owner.as_deref(), msg);
+        }
+    });
+    tx
+}
@@ -0,0 +1,414 @@
+//! Certificates obtained and renewed automatically from an ACME CA
+//!
+//! When a domain is configured (`--acme-domain`, `TORU_ACME_DOMAIN` or the
+//! `acme_domain` setting) the server gets a certificate for it from Let's
+//! Encrypt, or the CA whose directory is in `acme_directory`, and serves HTTPS
+//! with it. Domain ownership is proven with HTTP-01 challenges, answered by the
+//! plain HTTP listener (the TLS redirect port, 80 by default), which the CA
+//! must be able to reach on port 80.
+//!
+//! The account and the certificates are kept under `<data dir>/acme`. A
+//! certificate is renewed once it expires within `RENEW_BEFORE_DAYS`, and the
+//! running server switches to the new one without restarting.
+
+use anyhow::{anyhow, bail, Context, Result};
+use axum::{extract::Path as UrlPath, http::StatusCode, routing::get, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, LetsEncrypt,
+    NewAccount, NewOrder, OrderStatus,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::db::{self, DbPool};
+
+/// Setting with the domain to get a certificate for; unset turns ACME off
+pub const ACME_DOMAIN_SETTING: &str = "acme_domain";
+
+/// Setting with the contact email given to the CA
+pub const ACME_EMAIL_SETTING: &str = "acme_email";
+
+/// Setting with the ACME directory URL [default: Let's Encrypt]
+pub const ACME_DIRECTORY_SETTING: &str = "acme_directory";
+
+/// Port the CA sends HTTP-01 challenges to
+pub const HTTP_CHALLENGE_PORT: u16 = 80;
+
+/// Days before expiry a certificate is renewed
+const RENEW_BEFORE_DAYS: i64 = 30;
+
+/// How often the renewal task looks whether the certificate is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// How many times an order is polled while the CA validates the challenges
+const MAX_ORDER_POLLS: u32 = 10;
+
+const ACCOUNT_FILE: &str = "account.json";
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+/// Where and from which CA certificates for the domain are obtained
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcmeOptions {
+    pub domain: String,
+    pub email: Option<String>,
+    pub directory_url: String,
+    /// Directory holding the account and the certificates
+    pub dir: PathBuf,
+}
+
+impl AcmeOptions {
+    pub fn cert_path(&self) -> PathBuf {
+        self.dir.join(&self.domain).join(CERT_FILE)
+    }
+
+    pub fn key_path(&self) -> PathBuf {
+        self.dir.join(&self.domain).join(KEY_FILE)
+    }
+
+    fn account_path(&self) -> PathBuf {
+        self.dir.join(ACCOUNT_FILE)
+    }
+}
+
+/// ACME options from the configuration, or the settings for the values it leaves unset
+///
+/// Returns `None` when no domain is configured.
+pub async fn resolve_options(db: &DbPool, config: &Config) -> Result<Option<AcmeOptions>> {
+    let domain = match config.acme.domain.clone() {
+        Some(domain) => Some(domain),
+        None => db::get_setting(db, ACME_DOMAIN_SETTING).await?,
+    };
+    let Some(domain) = domain
+        .map(|d| d.trim().to_lowercase())
+        .filter(|d| !d.is_empty())
+    else {
+        return Ok(None);
+    };
+    if !is_valid_domain(&domain) {
+        bail!("Invalid ACME domain: {}", domain);
+    }
+    let email = match config.acme.email.clone() {
+        Some(email) => Some(email),
+        None => db::get_setting(db, ACME_EMAIL_SETTING).await?,
+    }
+    .filter(|e| !e.trim().is_empty());
+    let directory_url = db::get_setting(db, ACME_DIRECTORY_SETTING)
+        .await?
+        .filter(|d| !d.trim().is_empty())
+        .unwrap_or_else(|| LetsEncrypt::Production.url().to_string());
+
+    Ok(Some(AcmeOptions {
+        domain,
+        email,
+        directory_url,
+        dir: config.paths.data_dir.join("acme"),
+    }))
+}
+
+/// Whether `domain` is a DNS name a certificate can be issued for (and safe as a directory name)
+fn is_valid_domain(domain: &str) -> bool {
+    domain.len() <= 253
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// Key authorizations of the pending HTTP-01 challenges, by token
+#[derive(Clone, Default)]
+pub struct Challenges(Arc<RwLock<HashMap<String, String>>>);
+
+impl Challenges {
+    fn insert(&self, token: String, key_authorization: String) {
+        self.0.write().unwrap().insert(token, key_authorization);
+    }
+
+    fn remove(&self, token: &str) {
+        self.0.write().unwrap().remove(token);
+    }
+
+    fn get(&self, token: &str) -> Option<String> {
+        self.0.read().unwrap().get(token).cloned()
+    }
+}
+
+/// Routes answering HTTP-01 challenges, for the plain HTTP listener
+pub fn challenge_router(challenges: Challenges) -> Router {
+    Router::new().route(
+        "/.well-known/acme-challenge/:token",
+        get(move |UrlPath(token): UrlPath<String>| async move {
+            challenges.get(&token).ok_or(StatusCode::NOT_FOUND)
+        }),
+    )
+}
+
+/// Get a certificate when there is none or it is due for renewal
+///
+/// Returns whether a new certificate was written.
+pub async fn ensure_certificate(options: &AcmeOptions, challenges: &Challenges) -> Result<bool> {
+    if let Ok(cert) = tokio::fs::read(options.cert_path()).await {
+        match expires_at(&cert) {
+            Ok(expires) if !is_due(expires, Utc::now()) => return Ok(false),
+            Ok(expires) => tracing::info!(
+                "Certificate for {} expires {}, renewing",
+                options.domain,
+                expires
+            ),
+            Err(e) => tracing::warn!(
+                "Failed to read certificate for {}, requesting a new one: {}",
+                options.domain,
+                e
+            ),
+        }
+    }
+
+    let (cert, key) = issue_certificate(options, challenges).await?;
+    let domain_dir = options.dir.join(&options.domain);
+    tokio::fs::create_dir_all(&domain_dir).await?;
+    write_private(&options.key_path(), key.as_bytes()).await?;
+    tokio::fs::write(options.cert_path(), cert.as_bytes()).await?;
+    tracing::info!("Obtained certificate for {}", options.domain);
+    Ok(true)
+}
+
+/// Renew the certificate in the background, switching the server to each new one
+pub fn spawn_renewal(options: AcmeOptions, challenges: Challenges, config: RustlsConfig) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        // The certificate was just checked at startup
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match ensure_certificate(&options, &challenges).await {
+                Ok(true) => {
+                    if let Err(e) = config
+                        .reload_from_pem_file(options.cert_path(), options.key_path())
+                        .await
+                    {
+                        tracing::warn!("Failed to load renewed certificate: {}", e);
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => tracing::warn!(
+                    "Failed to renew certificate for {}: {:#}",
+                    options.domain,
+                    e
+                ),
+            }
+        }
+    });
+}
+
+/// Order a certificate, returning the PEM certificate chain and private key
+async fn issue_certificate(
+    options: &AcmeOptions,
+    challenges: &Challenges,
+) -> Result<(String, String)> {
+    // The ACME client makes its HTTPS requests with the default crypto provider
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let account = load_or_create_account(options).await?;
+
+    let identifiers = [Identifier::Dns(options.domain.clone())];
+    let mut order = account
+        .new_order(&NewOrder {
+            identifiers: &identifiers,
+        })
+        .await
+        .context("Failed to create ACME order")?;
+
+    let mut tokens = Vec::new();
+    let result = async {
+        for authz in order.authorizations().await? {
+            match authz.status {
+                AuthorizationStatus::Pending => {}
+                AuthorizationStatus::Valid => continue,
+                status => bail!("Authorization for {} is {:?}", options.domain, status),
+            }
+            let challenge = authz
+                .challenges
+                .iter()
+                .find(|c| c.r#type == ChallengeType::Http01)
+                .ok_or_else(|| anyhow!("The CA offered no HTTP-01 challenge"))?;
+            let key_authorization = order.key_authorization(challenge);
+            challenges.insert(
+                challenge.token.clone(),
+                key_authorization.as_str().to_string(),
+            );
+            tokens.push(challenge.token.clone());
+            order.set_challenge_ready(&challenge.url).await?;
+        }
+
+        let mut delay = Duration::from_secs(1);
+        let mut polls = 0;
+        loop {
+            tokio::time::sleep(delay).await;
+            let state = order.refresh().await?;
+            match state.status {
+                OrderStatus::Ready | OrderStatus::Valid => break,
+                OrderStatus::Invalid => bail!(
+                    "The CA could not validate {} (is port {} reachable?)",
+                    options.domain,
+                    HTTP_CHALLENGE_PORT
+                ),
+                _ => {}
+            }
+            polls += 1;
+            if polls >= MAX_ORDER_POLLS {
+                bail!(
+                    "Timed out waiting for the CA to validate {}",
+                    options.domain
+                );
+            }
+            delay = (delay * 2).min(Duration::from_secs(30));
+        }
+        Ok(())
+    }
+    .await;
+    for token in &tokens {
+        challenges.remove(token);
+    }
+    result?;
+
+    let key = rcgen::KeyPair::generate()?;
+    let mut params = rcgen::CertificateParams::new(vec![options.domain.clone()])?;
+    params.distinguished_name = rcgen::DistinguishedName::new();
+    let csr = params.serialize_request(&key)?;
+    order
+        .finalize(csr.der())
+        .await
+        .context("Failed to finalize ACME order")?;
+
+    let mut polls = 0;
+    let cert = loop {
+        if let Some(cert) = order.certificate().await? {
+            break cert;
+        }
+        polls += 1;
+        if polls >= MAX_ORDER_POLLS {
+            bail!(
+                "Timed out waiting for the certificate for {}",
+                options.domain
+            );
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    };
+    Ok((cert, key.serialize_pem()))
+}
+
+/// Account credentials, with the directory they were created in
+#[derive(Serialize, Deserialize)]
+struct StoredAccount {
+    directory_url: String,
+    credentials: AccountCredentials,
+}
+
+async fn load_or_create_account(options: &AcmeOptions) -> Result<Account> {
+    let path = options.account_path();
+    if let Ok(json) = tokio::fs::read(&path).await {
+        let stored: StoredAccount =
+            serde_json::from_slice(&json).context("Failed to read ACME account")?;
+        if stored.directory_url == options.directory_url {
+            return Ok(Account::from_credentials(stored.credentials).await?);
+        }
+        tracing::info!("ACME directory changed, creating a new account");
+    }
+
+    let contact = options.email.as_ref().map(|e| format!("mailto:{}", e));
+    let contact: Vec<&str> = contact.iter().map(String::as_str).collect();
+    let (account, credentials) = Account::create(
+        &NewAccount {
+            contact: &contact,
+            terms_of_service_agreed: true,
+            only_return_existing: false,
+        },
+        &options.directory_url,
+        None,
+    )
+    .await
+    .context("Failed to create ACME account")?;
+
+    let stored = StoredAccount {
+        directory_url: options.directory_url.clone(),
+        credentials,
+    };
+    tokio::fs::create_dir_all(&options.dir).await?;
+    write_private(&path, &serde_json::to_vec_pretty(&stored)?).await?;
+    Ok(account)
+}
+
+/// Write a file only its owner can read
+#[cfg(unix)]
+async fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    tokio::fs::write(path, contents).await?;
+    tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await?;
+    Ok(())
+}
+
+/// Write a file, relying on the ACME directory's ACL to keep it private
+#[cfg(not(unix))]
+async fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    tokio::fs::write(path, contents).await?;
+    Ok(())
+}
+
+/// When the first certificate of a PEM chain expires
+fn expires_at(pem: &[u8]) -> Result<DateTime<Utc>> {
+    let der = rustls_pemfile::certs(&mut &pem[..])
+        .next()
+        .ok_or_else(|| anyhow!("No certificate found"))??;
+    let (_, cert) = x509_parser::parse_x509_certificate(&der)?;
+    DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)
+        .ok_or_else(|| anyhow!("Invalid expiry date"))
+}
+
+/// Whether a certificate expiring at `expires` should be renewed
+fn is_due(expires: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    expires - now < ChronoDuration::days(RENEW_BEFORE_DAYS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_domain() {
+        assert!(is_valid_domain("steering.example.com"));
+        assert!(is_valid_domain("my-host.example.org"));
+        assert!(!is_valid_domain("localhost"));
+        assert!(!is_valid_domain("../etc.example.com"));
+        assert!(!is_valid_domain("-bad.example.com"));
+        assert!(!is_valid_domain("a..example.com"));
+    }
+
+    #[test]
+    fn test_is_due() {
+        let now = Utc::now();
+        assert!(!is_due(now + ChronoDuration::days(60), now));
+        assert!(is_due(now + ChronoDuration::days(10), now));
+        assert!(is_due(now - ChronoDuration::days(1), now));
+    }
+
+    #[test]
+    fn test_expires_at_reads_certificate() {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["example.com".into()]).unwrap();
+        params.not_after = rcgen::date_time_ymd(2031, 5, 17);
+        let cert = params.self_signed(&key).unwrap();
+
+        let expires = expires_at(cert.pem().as_bytes()).unwrap();
+        assert_eq!(expires.format("%Y-%m-%d").to_string(), "2031-05-17");
+        assert!(expires_at(b"not a certificate").is_err());
+    }
+}
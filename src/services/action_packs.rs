@@ -0,0 +1,333 @@
+//! Sharing quick actions between instances as JSON packs
+//!
+//! A pack holds quick actions with their parameters, limits and metadata but
+//! nothing tied to the instance that exported it: no IDs, order or user IDs,
+//! and each script is named by its file name in the scripts directory. The
+//! scripts themselves are not included; an import reports the ones missing.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::{action_params, scripts};
+use crate::db::{
+    self, ConcurrencyMode, ConfigImport, DangerLevel, DbPool, QuickAction, QuickActionParam,
+    Visibility,
+};
+
+/// Version of the pack layout
+const FORMAT_VERSION: u32 = 1;
+
+/// Errors returned when importing a pack
+#[derive(Debug)]
+pub enum ActionPackError {
+    Invalid(String),
+    Failed(anyhow::Error),
+}
+
+impl std::fmt::Display for ActionPackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActionPackError::Invalid(e) => write!(f, "Invalid action pack: {}", e),
+            ActionPackError::Failed(e) => write!(f, "Import failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ActionPackError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionPack {
+    pub format: u32,
+    /// Version of the Steering Center that exported it
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub exported_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub actions: Vec<PackedAction>,
+}
+
+/// A quick action without what belongs to the exporting instance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackedAction {
+    pub name: String,
+    /// File name of the script in the scripts directory
+    pub script: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub cpu_limit_secs: Option<i64>,
+    #[serde(default)]
+    pub memory_limit_mb: Option<i64>,
+    #[serde(default)]
+    pub nofile_limit: Option<i64>,
+    #[serde(default)]
+    pub concurrency: ConcurrencyMode,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub params: Vec<QuickActionParam>,
+    /// `users` is exported as `admins`, since user IDs differ between instances
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(default)]
+    pub danger_level: DangerLevel,
+    #[serde(default)]
+    pub requires_confirmation: bool,
+}
+
+/// What to do with an imported action named like an existing one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Keep the existing action and leave the imported one out
+    #[default]
+    Skip,
+    /// Overwrite the existing action, keeping its ID, order and users
+    Replace,
+    /// Import it under a free name such as `Deploy (2)`
+    Rename,
+}
+
+/// What an import changed, by action name
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PackImportSummary {
+    pub created: Vec<String>,
+    pub replaced: Vec<String>,
+    pub skipped: Vec<String>,
+    /// Original name and the name it was imported under
+    pub renamed: Vec<(String, String)>,
+    /// Scripts the imported actions run that aren't in the scripts directory
+    pub missing_scripts: Vec<String>,
+}
+
+impl From<&QuickAction> for PackedAction {
+    fn from(action: &QuickAction) -> Self {
+        let script = Path::new(&action.script_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| action.script_path.clone());
+        PackedAction {
+            name: action.name.clone(),
+            script,
+            icon: action.icon.clone(),
+            cpu_limit_secs: action.cpu_limit_secs,
+            memory_limit_mb: action.memory_limit_mb,
+            nofile_limit: action.nofile_limit,
+            concurrency: action.concurrency,
+            env: action.env.clone(),
+            params: action.params.clone(),
+            visibility: match action.visibility {
+                Visibility::Users => Visibility::Admins,
+                visibility => visibility,
+            },
+            danger_level: action.danger_level,
+            requires_confirmation: action.requires_confirmation,
+        }
+    }
+}
+
+/// Pack the quick actions with the given IDs, or all of them if none are given
+pub async fn export_pack(db: &DbPool, ids: &[String]) -> Result<ActionPack> {
+    let actions = db::get_quick_actions(db)
+        .await?
+        .iter()
+        .filter(|action| ids.is_empty() || ids.contains(&action.id))
+        .map(PackedAction::from)
+        .collect();
+    Ok(ActionPack {
+        format: FORMAT_VERSION,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: Some(Utc::now()),
+        actions,
+    })
+}
+
+fn validate(action: &PackedAction) -> Result<(), ActionPackError> {
+    let invalid = |e: String| ActionPackError::Invalid(format!("{}: {}", action.name, e));
+    if action.name.trim().is_empty() {
+        return Err(ActionPackError::Invalid(
+            "action without a name".to_string(),
+        ));
+    }
+    scripts::validate_script_name(&action.script).map_err(|e| invalid(e.to_string()))?;
+    if let Some(key) = action
+        .env
+        .keys()
+        .find(|key| !action_params::valid_env_key(key))
+    {
+        return Err(invalid(format!("invalid environment variable {}", key)));
+    }
+    action_params::validate_schema(&action.params).map_err(|e| invalid(e.to_string()))
+}
+
+/// `name` followed by the first free number, e.g. `Deploy (2)`
+fn free_name(name: &str, taken: &HashMap<String, QuickAction>) -> String {
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !taken.contains_key(candidate))
+        .unwrap()
+}
+
+/// Add the actions of a pack to this instance's quick actions in one transaction
+pub async fn import_pack(
+    db: &DbPool,
+    pack: ActionPack,
+    policy: ConflictPolicy,
+    scripts_dir: &Path,
+) -> Result<PackImportSummary, ActionPackError> {
+    if pack.format > FORMAT_VERSION {
+        return Err(ActionPackError::Invalid(format!(
+            "exported by a newer version ({})",
+            pack.version
+        )));
+    }
+    for action in &pack.actions {
+        validate(action)?;
+    }
+
+    let existing = db::get_quick_actions(db)
+        .await
+        .map_err(ActionPackError::Failed)?;
+    let mut by_name: HashMap<String, QuickAction> = existing
+        .iter()
+        .map(|action| (action.name.clone(), action.clone()))
+        .collect();
+    let mut next_order = existing
+        .iter()
+        .map(|action| action.display_order + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut summary = PackImportSummary::default();
+    let mut actions = Vec::with_capacity(pack.actions.len());
+    for packed in pack.actions {
+        let mut action = QuickAction {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: packed.name,
+            script_path: packed.script,
+            icon: packed.icon,
+            display_order: next_order,
+            cpu_limit_secs: packed.cpu_limit_secs,
+            memory_limit_mb: packed.memory_limit_mb,
+            nofile_limit: packed.nofile_limit,
+            concurrency: packed.concurrency,
+            env: packed.env,
+            params: packed.params,
+            visibility: packed.visibility,
+            allowed_users: Vec::new(),
+            danger_level: packed.danger_level,
+            requires_confirmation: packed.requires_confirmation,
+        };
+        match by_name.get(&action.name) {
+            None => summary.created.push(action.name.clone()),
+            Some(_) if policy == ConflictPolicy::Skip => {
+                summary.skipped.push(action.name);
+                continue;
+            }
+            Some(_) if policy == ConflictPolicy::Rename => {
+                let name = free_name(&action.name, &by_name);
+                summary.renamed.push((action.name, name.clone()));
+                summary.created.push(name.clone());
+                action.name = name;
+            }
+            Some(current) => {
+                action.id = current.id.clone();
+                action.display_order = current.display_order;
+                action.allowed_users = current.allowed_users.clone();
+                // Also replaces an action earlier in the same pack
+                actions.retain(|a: &QuickAction| a.id != action.id);
+                summary.replaced.push(action.name.clone());
+            }
+        }
+        if action.display_order == next_order {
+            next_order += 1;
+        }
+        if !scripts_dir.join(&action.script_path).is_file()
+            && !summary.missing_scripts.contains(&action.script_path)
+        {
+            summary.missing_scripts.push(action.script_path.clone());
+        }
+        by_name.insert(action.name.clone(), action.clone());
+        actions.push(action);
+    }
+
+    db::import_config(
+        db,
+        &ConfigImport {
+            settings: &[],
+            quick_actions: &actions,
+            pipelines: &[],
+            scheduled_tasks: &[],
+            users: &[],
+        },
+    )
+    .await
+    .map_err(ActionPackError::Failed)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_import_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        db::create_schema(&pool.get().await.unwrap()).unwrap();
+        std::fs::write(dir.path().join("deploy.sh"), "#!/bin/sh\n").unwrap();
+        let pack: ActionPack = serde_json::from_str(
+            r#"{"format": 1, "actions": [
+                {"name": "Deploy", "script": "deploy.sh", "danger_level": "high"},
+                {"name": "Backup", "script": "backup.sh", "visibility": "admins"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let summary = import_pack(&pool, pack.clone(), ConflictPolicy::Skip, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(summary.created, ["Deploy", "Backup"]);
+        assert_eq!(summary.missing_scripts, ["backup.sh"]);
+
+        let summary = import_pack(&pool, pack.clone(), ConflictPolicy::Skip, dir.path())
+            .await
+            .unwrap();
+        assert!(summary.created.is_empty());
+        assert_eq!(summary.skipped, ["Deploy", "Backup"]);
+
+        let summary = import_pack(&pool, pack.clone(), ConflictPolicy::Rename, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(
+            summary.renamed[0],
+            ("Deploy".to_string(), "Deploy (2)".to_string())
+        );
+
+        let summary = import_pack(&pool, pack, ConflictPolicy::Replace, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(summary.replaced, ["Deploy", "Backup"]);
+        let actions = db::get_quick_actions(&pool).await.unwrap();
+        assert_eq!(actions.len(), 4);
+        assert_eq!(actions[0].name, "Deploy");
+        assert_eq!(actions[0].danger_level, DangerLevel::High);
+
+        let exported = export_pack(&pool, &[actions[1].id.clone()]).await.unwrap();
+        assert_eq!(exported.actions.len(), 1);
+        assert_eq!(exported.actions[0].visibility, Visibility::Admins);
+
+        let pack: ActionPack = serde_json::from_str(
+            r#"{"format": 1, "actions": [{"name": "Bad", "script": "../etc/passwd"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            import_pack(&pool, pack, ConflictPolicy::Skip, dir.path()).await,
+            Err(ActionPackError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+//! Input parameters of quick actions
+//!
+//! A quick action may declare typed parameters (text, select, boolean, secret).
+//! The values submitted for a run are checked against them and passed to the
+//! script as environment variables: parameter `service` becomes
+//! `PARAM_SERVICE`, next to the action's own environment.
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::db::{ParamKind, QuickAction, QuickActionParam};
+
+/// Longest value a parameter may take
+pub const MAX_VALUE_LEN: usize = 4096;
+
+/// Environment variable a parameter is passed as
+pub fn env_name(param: &QuickActionParam) -> String {
+    format!("PARAM_{}", param.name.to_ascii_uppercase())
+}
+
+/// Whether `key` can be used as an environment variable name by the shell
+pub fn valid_env_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Check parameter definitions before they are saved
+pub fn validate_schema(params: &[QuickActionParam]) -> Result<()> {
+    let mut names = HashSet::new();
+    for param in params {
+        let valid_name = !param.name.is_empty()
+            && !param.name.starts_with(|c: char| c.is_ascii_digit())
+            && param
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            bail!("Invalid parameter name: {:?}", param.name);
+        }
+        // Names differing only in case would be the same variable
+        if !names.insert(env_name(param)) {
+            bail!("Duplicate parameter: {}", param.name);
+        }
+        if param.kind == ParamKind::Select && param.options.is_empty() {
+            bail!("Select parameter {} has no options", param.name);
+        }
+        if param.kind != ParamKind::Select && !param.options.is_empty() {
+            bail!("Only select parameters have options ({})", param.name);
+        }
+        if let Some(default) = &param.default {
+            if param.kind == ParamKind::Secret {
+                bail!("Secret parameter {} can't have a default", param.name);
+            }
+            check_value(param, default)?;
+        }
+    }
+    Ok(())
+}
+
+/// The environment a run of `action` gets with the submitted `values`
+///
+/// Unknown parameters, values of the wrong type and missing required values
+/// are refused. Optional parameters without a value or default are not set.
+pub fn run_env(
+    action: &QuickAction,
+    values: &HashMap<String, Value>,
+) -> Result<HashMap<String, String>> {
+    if let Some(unknown) = values
+        .keys()
+        .find(|name| !action.params.iter().any(|p| &p.name == *name))
+    {
+        bail!("Unknown parameter: {}", unknown);
+    }
+
+    let mut env = action.env.clone();
+    for param in &action.params {
+        let value = match values.get(&param.name) {
+            None | Some(Value::Null) => match &param.default {
+                Some(default) => default.clone(),
+                None if param.required => bail!("Missing parameter: {}", param.name),
+                None => continue,
+            },
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Bool(b)) if param.kind == ParamKind::Boolean => b.to_string(),
+            Some(_) => bail!("Parameter {} must be a string", param.name),
+        };
+        if param.required && value.is_empty() {
+            bail!("Missing parameter: {}", param.name);
+        }
+        check_value(param, &value)?;
+        env.insert(env_name(param), value);
+    }
+    Ok(env)
+}
+
+fn check_value(param: &QuickActionParam, value: &str) -> Result<()> {
+    if value.len() > MAX_VALUE_LEN || value.contains('\0') {
+        bail!("Invalid value for parameter {}", param.name);
+    }
+    match param.kind {
+        ParamKind::Boolean if value != "true" && value != "false" => {
+            bail!("Parameter {} must be true or false", param.name)
+        }
+        ParamKind::Select if !param.options.iter().any(|o| o == value) => {
+            bail!("Parameter {} must be one of its options", param.name)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ConcurrencyMode;
+    use serde_json::json;
+
+    fn param(name: &str, kind: ParamKind) -> QuickActionParam {
+        QuickActionParam {
+            name: name.to_string(),
+            label: None,
+            kind,
+            required: false,
+            default: None,
+            options: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_run_env() {
+        let mut service = param("service", ParamKind::Select);
+        service.required = true;
+        service.options = vec!["nginx".to_string(), "postgres".to_string()];
+        let mut force = param("force", ParamKind::Boolean);
+        force.default = Some("false".to_string());
+        let params = vec![service, force, param("token", ParamKind::Secret)];
+        validate_schema(&params).unwrap();
+
+        let action = QuickAction {
+            id: "a".to_string(),
+            name: "Restart service".to_string(),
+            script_path: "restart.sh".to_string(),
+            icon: None,
+            display_order: 0,
+            cpu_limit_secs: None,
+            memory_limit_mb: None,
+            nofile_limit: None,
+            concurrency: ConcurrencyMode::Reject,
+            env: HashMap::from([("MODE".to_string(), "safe".to_string())]),
+            params,
+            visibility: Default::default(),
+            allowed_users: Vec::new(),
+            danger_level: Default::default(),
+            requires_confirmation: false,
+        };
+        let values = |v: Value| serde_json::from_value::<HashMap<String, Value>>(v).unwrap();
+
+        let env = run_env(&action, &values(json!({"service": "nginx"}))).unwrap();
+        assert_eq!(env["PARAM_SERVICE"], "nginx");
+        assert_eq!(env["PARAM_FORCE"], "false");
+        assert_eq!(env["MODE"], "safe");
+        assert!(!env.contains_key("PARAM_TOKEN"));
+
+        let env = run_env(
+            &action,
+            &values(json!({"service": "postgres", "force": true, "token": "s3cret"})),
+        )
+        .unwrap();
+        assert_eq!(env["PARAM_FORCE"], "true");
+        assert_eq!(env["PARAM_TOKEN"], "s3cret");
+
+        assert!(run_env(&action, &HashMap::new()).is_err());
+        assert!(run_env(&action, &values(json!({"service": "redis"}))).is_err());
+        assert!(run_env(
+            &action,
+            &values(json!({"service": "nginx", "force": "yes"}))
+        )
+        .is_err());
+        assert!(run_env(&action, &values(json!({"service": "nginx", "other": "x"}))).is_err());
+        assert!(run_env(&action, &values(json!({"service": 1}))).is_err());
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        assert!(validate_schema(&[param("1st", ParamKind::Text)]).is_err());
+        assert!(validate_schema(&[param("a-b", ParamKind::Text)]).is_err());
+        assert!(validate_schema(&[
+            param("host", ParamKind::Text),
+            param("HOST", ParamKind::Text)
+        ])
+        .is_err());
+        assert!(validate_schema(&[param("env", ParamKind::Select)]).is_err());
+
+        let mut secret = param("token", ParamKind::Secret);
+        secret.default = Some("x".to_string());
+        assert!(validate_schema(&[secret]).is_err());
+
+        let mut flag = param("dry_run", ParamKind::Boolean);
+        flag.default = Some("maybe".to_string());
+        assert!(validate_schema(&[flag]).is_err());
+    }
+}
@@ -8,9 +8,22 @@ use subtle::ConstantTimeEq;
 
 use crate::db::{DbPool, Session, User, UserRole};
 
-/// Session duration in days
+/// Session duration in days, unless a setting says otherwise
 pub const SESSION_DURATION_DAYS: i64 = 7;
 
+/// Setting with the hours a session lasts; `session_hours_admin` and
+/// `session_hours_client` set it per role
+pub const SESSION_HOURS_SETTING: &str = "session_hours";
+/// Setting with the minutes of inactivity that end a session (`0` or unset for
+/// none); `session_idle_timeout_minutes_admin` and `_client` set it per role
+pub const IDLE_TIMEOUT_SETTING: &str = "session_idle_timeout_minutes";
+
+/// Longest session a policy may ask for
+pub const MAX_SESSION_HOURS: i64 = 365 * 24;
+
+/// How often a session's idle deadline is pushed back while it's in use
+const IDLE_TOUCH_INTERVAL_SECS: i64 = 60;
+
 /// Minimum password length
 pub const MIN_PASSWORD_LENGTH: usize = 8;
 
@@ -59,15 +72,64 @@ pub fn validate_password(password: &str) -> Result<(), &'static str> {
     Ok(())
 }
 
-/// Create a new session for a user
+/// How long sessions of a user last and how long they may sit unused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionPolicy {
+    pub duration: Duration,
+    pub idle_timeout: Option<Duration>,
+}
+
+/// A number from a setting, its per-role variant first
+async fn policy_setting(pool: &DbPool, key: &str, role: UserRole) -> Option<i64> {
+    for key in [format!("{}_{}", key, role), key.to_string()] {
+        let value = crate::db::get_setting(pool, &key)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|v| v.trim().parse::<i64>().ok());
+        if let Some(value) = value {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// The session policy of a user, or of the admin from the environment: the
+/// user's own, else their role's, else the global settings
+pub async fn session_policy(pool: &DbPool, user: Option<&User>, role: UserRole) -> SessionPolicy {
+    let hours = match user.and_then(|u| u.session_hours) {
+        Some(hours) => Some(hours),
+        None => policy_setting(pool, SESSION_HOURS_SETTING, role).await,
+    };
+    let idle_minutes = match user.and_then(|u| u.idle_timeout_minutes) {
+        Some(minutes) => Some(minutes),
+        None => policy_setting(pool, IDLE_TIMEOUT_SETTING, role).await,
+    };
+    SessionPolicy {
+        duration: hours
+            .filter(|h| *h > 0)
+            .map(|h| Duration::hours(h.min(MAX_SESSION_HOURS)))
+            .unwrap_or_else(|| Duration::days(SESSION_DURATION_DAYS)),
+        idle_timeout: idle_minutes
+            .filter(|m| *m > 0)
+            .map(|m| Duration::minutes(m.min(MAX_SESSION_HOURS * 60))),
+    }
+}
+
+/// Create a new session for a user, lasting as long as their session policy says
 pub async fn create_user_session(
     pool: &DbPool,
     user_id: Option<String>,
     username: &str,
     role: UserRole,
 ) -> anyhow::Result<Session> {
+    let user = match &user_id {
+        Some(id) => crate::db::get_user_by_id(pool, id).await?,
+        None => None,
+    };
+    let policy = session_policy(pool, user.as_ref(), role).await;
     let now = Utc::now();
-    let expires_at = now + Duration::days(SESSION_DURATION_DAYS);
+    let expires_at = now + policy.duration;
 
     let session = Session {
         id: generate_session_token(),
@@ -76,6 +138,8 @@ pub async fn create_user_session(
         username: username.to_string(),
         created_at: now.to_rfc3339(),
         expires_at: expires_at.to_rfc3339(),
+        idle_timeout_minutes: policy.idle_timeout.map(|t| t.num_minutes()),
+        idle_expires_at: policy.idle_timeout.map(|t| (now + t).to_rfc3339()),
     };
 
     crate::db::create_session(pool, &session).await?;
@@ -86,18 +150,23 @@ pub async fn create_user_session(
 pub async fn validate_session(pool: &DbPool, session_id: &str) -> Option<Session> {
     let session = crate::db::get_session(pool, session_id).await.ok()??;
 
-    // Check if session is expired
+    // Check if session is expired or has been left unused for too long
+    let now = Utc::now();
     let expires_at = chrono::DateTime::parse_from_rfc3339(&session.expires_at).ok()?;
-    if expires_at < Utc::now() {
+    let idle_expires_at = session
+        .idle_expires_at
+        .as_deref()
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok());
+    if expires_at < now || idle_expires_at.is_some_and(|t| t < now) {
         // Clean up expired session
         let _ = crate::db::delete_session(pool, session_id).await;
         return None;
     }
 
-    // For client users, verify the user still exists and is active
+    // For client users, verify the user still exists, is active and hasn't expired
     if let Some(ref user_id) = session.user_id {
         if let Ok(Some(user)) = crate::db::get_user_by_id(pool, user_id).await {
-            if !user.is_active {
+            if !user.is_active || user.is_expired() {
                 let _ = crate::db::delete_session(pool, session_id).await;
                 return None;
             }
@@ -108,6 +177,16 @@ pub async fn validate_session(pool: &DbPool, session_id: &str) -> Option<Session
         }
     }
 
+    // Being used keeps the session from idling out; written at most once a minute
+    if let (Some(minutes), Some(idle_expires_at)) = (session.idle_timeout_minutes, idle_expires_at)
+    {
+        let next = now + Duration::minutes(minutes);
+        if next - idle_expires_at.with_timezone(&Utc) >= Duration::seconds(IDLE_TOUCH_INTERVAL_SECS)
+        {
+            let _ = crate::db::touch_session(pool, session_id, &next.to_rfc3339()).await;
+        }
+    }
+
     Some(session)
 }
 
@@ -133,7 +212,7 @@ pub async fn authenticate_user(pool: &DbPool, username: &str, password: &str) ->
         .await
         .ok()??;
 
-    if !user.is_active {
+    if !user.is_active || user.is_expired() {
         return None;
     }
 
@@ -143,3 +222,63 @@ pub async fn authenticate_user(pool: &DbPool, username: &str, password: &str) ->
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[tokio::test]
+    async fn test_session_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        db::create_schema(&pool.get().await.unwrap()).unwrap();
+
+        let policy = session_policy(&pool, None, UserRole::Admin).await;
+        assert_eq!(policy.duration, Duration::days(SESSION_DURATION_DAYS));
+        assert_eq!(policy.idle_timeout, None);
+
+        db::set_setting(&pool, SESSION_HOURS_SETTING, "720")
+            .await
+            .unwrap();
+        db::set_setting(&pool, "session_hours_admin", "8")
+            .await
+            .unwrap();
+        db::set_setting(&pool, IDLE_TIMEOUT_SETTING, "30")
+            .await
+            .unwrap();
+        let policy = session_policy(&pool, None, UserRole::Admin).await;
+        assert_eq!(policy.duration, Duration::hours(8));
+        assert_eq!(policy.idle_timeout, Some(Duration::minutes(30)));
+
+        let mut user = User {
+            id: "u1".to_string(),
+            username: "alice".to_string(),
+            password_hash: String::new(),
+            display_name: None,
+            role: UserRole::Client,
+            is_active: true,
+            created_at: Utc::now().to_rfc3339(),
+            expires_at: None,
+            session_hours: None,
+            idle_timeout_minutes: Some(0),
+        };
+        let policy = session_policy(&pool, Some(&user), UserRole::Client).await;
+        assert_eq!(policy.duration, Duration::hours(720));
+        assert_eq!(policy.idle_timeout, None);
+
+        user.session_hours = Some(2);
+        user.idle_timeout_minutes = None;
+        db::create_user(&pool, &user).await.unwrap();
+        let session = create_user_session(&pool, Some("u1".to_string()), "alice", user.role)
+            .await
+            .unwrap();
+        assert_eq!(session.idle_timeout_minutes, Some(30));
+        assert!(validate_session(&pool, &session.id).await.is_some());
+
+        // Left unused past its idle deadline
+        let idle = (Utc::now() - Duration::minutes(1)).to_rfc3339();
+        db::touch_session(&pool, &session.id, &idle).await.unwrap();
+        assert!(validate_session(&pool, &session.id).await.is_none());
+    }
+}
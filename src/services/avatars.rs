@@ -0,0 +1,111 @@
+//! User avatar images
+//!
+//! Avatars are kept as files named by user ID under `<data_dir>/avatars`; the
+//! database holds their content type and a tag that changes with the image, so
+//! they can be cached by browsers. Only PNG, JPEG, GIF and WebP images up to
+//! `MAX_AVATAR_SIZE` are accepted, recognised by their content rather than the
+//! name or type the browser sent.
+
+use anyhow::{bail, Result};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Largest avatar accepted, in bytes
+pub const MAX_AVATAR_SIZE: usize = 1024 * 1024;
+
+/// Directory avatars are kept in
+pub fn avatar_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("avatars")
+}
+
+/// Content type of an image from its first bytes, if it's an accepted format
+pub fn image_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// Check an uploaded avatar, returning its content type and tag
+pub fn check_avatar(data: &[u8]) -> Result<(&'static str, String)> {
+    if data.is_empty() {
+        bail!("Avatar is empty");
+    }
+    if data.len() > MAX_AVATAR_SIZE {
+        bail!("Avatar must be at most {} KB", MAX_AVATAR_SIZE / 1024);
+    }
+    let Some(content_type) = image_type(data) else {
+        bail!("Avatar must be a PNG, JPEG, GIF or WebP image");
+    };
+    let digest = Sha256::digest(data);
+    let etag = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    Ok((content_type, etag))
+}
+
+fn avatar_path(dir: &Path, user_id: &str) -> Result<PathBuf> {
+    // User IDs are UUIDs; anything else could name a file elsewhere
+    uuid::Uuid::parse_str(user_id)?;
+    Ok(dir.join(user_id))
+}
+
+/// Write a checked avatar, replacing the user's previous one
+pub async fn save_avatar(dir: &Path, user_id: &str, data: &[u8]) -> Result<()> {
+    let path = avatar_path(dir, user_id)?;
+    tokio::fs::create_dir_all(dir).await?;
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, data).await?;
+    tokio::fs::rename(&tmp, &path).await?;
+    Ok(())
+}
+
+pub async fn read_avatar(dir: &Path, user_id: &str) -> Result<Option<Vec<u8>>> {
+    let path = avatar_path(dir, user_id)?;
+    match tokio::fs::read(&path).await {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn delete_avatar(dir: &Path, user_id: &str) -> Result<()> {
+    let path = avatar_path(dir, user_id)?;
+    match tokio::fs::remove_file(&path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_avatars() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let (content_type, etag) = check_avatar(png).unwrap();
+        assert_eq!(content_type, "image/png");
+        assert_eq!(etag.len(), 16);
+        assert_eq!(image_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert!(check_avatar(b"<svg onload=alert(1)>").is_err());
+        assert!(check_avatar(&vec![0xff; MAX_AVATAR_SIZE + 1]).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let id = uuid::Uuid::new_v4().to_string();
+        save_avatar(dir.path(), &id, png).await.unwrap();
+        assert_eq!(
+            read_avatar(dir.path(), &id).await.unwrap().as_deref(),
+            Some(&png[..])
+        );
+        delete_avatar(dir.path(), &id).await.unwrap();
+        assert!(read_avatar(dir.path(), &id).await.unwrap().is_none());
+        delete_avatar(dir.path(), &id).await.unwrap();
+        assert!(read_avatar(dir.path(), "../secret").await.is_err());
+    }
+}
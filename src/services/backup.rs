@@ -0,0 +1,498 @@
+//! Backups of the Steering Center's state, and restoring them
+//!
+//! A backup is a gzipped tar archive holding a consistent copy of the database
+//! (made with `VACUUM INTO`), the plugin configuration and, for reading outside
+//! the Steering Center, the quick actions as JSON. Backups are made on demand
+//! and, when the `backup_dir` setting names a directory, every
+//! `backup_interval_hours` into that directory, keeping the newest `backup_keep`.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::db::{self, DbPool};
+
+/// Setting naming the directory scheduled backups are written to; unset turns them off
+pub const BACKUP_DIR_SETTING: &str = "backup_dir";
+
+/// Setting with the hours between scheduled backups
+pub const BACKUP_INTERVAL_SETTING: &str = "backup_interval_hours";
+
+/// Setting with how many scheduled backups are kept
+pub const BACKUP_KEEP_SETTING: &str = "backup_keep";
+
+const DEFAULT_BACKUP_INTERVAL_HOURS: u64 = 24;
+const DEFAULT_BACKUP_KEEP: usize = 7;
+
+/// Largest backup accepted for restoring (256 MB)
+pub const MAX_BACKUP_SIZE: usize = 256 * 1024 * 1024;
+
+/// Maximum size of the files unpacked from a backup, so a small gzipped
+/// archive can't expand without bound
+const MAX_UNPACKED_SIZE: u64 = 4 * MAX_BACKUP_SIZE as u64;
+
+/// Maximum number of entries in a backup; it holds a handful of files
+const MAX_ENTRIES: usize = 64;
+
+/// How often the scheduler looks whether a backup is due
+const POLL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Version of the archive layout, stored in its manifest
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "steering.db";
+const QUICK_ACTIONS_ENTRY: &str = "quick_actions.json";
+const PLUGIN_CONFIG_ENTRY: &str = "plugins/config.json";
+
+/// Prefix and suffix of the file names of scheduled backups
+const FILE_PREFIX: &str = "steering-backup-";
+const FILE_SUFFIX: &str = ".tar.gz";
+
+/// Errors returned when restoring a backup
+#[derive(Debug)]
+pub enum BackupError {
+    /// The upload isn't a backup this version can restore
+    Invalid(String),
+    Failed(anyhow::Error),
+}
+
+impl std::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupError::Invalid(e) => write!(f, "Invalid backup: {}", e),
+            BackupError::Failed(e) => write!(f, "Restore failed: {:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format: u32,
+    /// Version of the Steering Center that made the backup
+    pub version: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What a restore brought back
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreSummary {
+    pub backup_created_at: DateTime<Utc>,
+    pub backup_version: String,
+    pub plugin_config_restored: bool,
+}
+
+/// The files of a backup archive
+struct BackupContents {
+    manifest: BackupManifest,
+    database: Vec<u8>,
+    plugin_config: Option<Vec<u8>>,
+}
+
+/// Plugin configuration (enabled state, limits, ...) kept by the plugin supervisor
+fn plugin_config_path(plugins_dir: &Path) -> PathBuf {
+    plugins_dir.join(".metadata").join("config.json")
+}
+
+/// Make a backup, returning the archive and the file name to save it as
+pub async fn create_backup(db: &DbPool, plugins_dir: &Path) -> Result<(String, Vec<u8>)> {
+    let created_at = Utc::now();
+    let database = TempFile::new("db");
+    db::vacuum_into(db, &database.0).await?;
+    let database_bytes = tokio::fs::read(&database.0).await?;
+    drop(database);
+
+    let quick_actions = serde_json::to_vec_pretty(&db::get_quick_actions(db).await?)?;
+    let plugin_config = match tokio::fs::read(plugin_config_path(plugins_dir)).await {
+        Ok(config) => Some(config),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let manifest = BackupManifest {
+        format: FORMAT_VERSION,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at,
+    };
+
+    let archive = tokio::task::spawn_blocking(move || {
+        pack(
+            &manifest,
+            &database_bytes,
+            &quick_actions,
+            plugin_config.as_deref(),
+        )
+    })
+    .await??;
+    Ok((backup_file_name(created_at), archive))
+}
+
+/// Restore a backup made by `create_backup`
+///
+/// The database is replaced while running. Plugins pick up the restored
+/// configuration when they are next started.
+pub async fn restore_backup(
+    db: &DbPool,
+    plugins_dir: &Path,
+    archive: Vec<u8>,
+) -> Result<RestoreSummary, BackupError> {
+    let contents = tokio::task::spawn_blocking(move || unpack(&archive))
+        .await
+        .map_err(|e| BackupError::Failed(e.into()))??;
+
+    let database = TempFile::new("db");
+    tokio::fs::write(&database.0, &contents.database)
+        .await
+        .map_err(|e| BackupError::Failed(e.into()))?;
+    db::restore_from(db, &database.0)
+        .await
+        .map_err(BackupError::Failed)?;
+
+    if let Some(config) = &contents.plugin_config {
+        let path = plugin_config_path(plugins_dir);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(|e| BackupError::Failed(e.into()))?;
+        }
+        tokio::fs::write(&path, config)
+            .await
+            .map_err(|e| BackupError::Failed(e.into()))?;
+    }
+
+    Ok(RestoreSummary {
+        backup_created_at: contents.manifest.created_at,
+        backup_version: contents.manifest.version,
+        plugin_config_restored: contents.plugin_config.is_some(),
+    })
+}
+
+/// Make backups into the `backup_dir` directory on the configured interval
+pub fn spawn_backup_scheduler(db: DbPool, plugins_dir: PathBuf) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(dir) = db::get_setting(&db, BACKUP_DIR_SETTING)
+                .await
+                .ok()
+                .flatten()
+                .filter(|dir| !dir.trim().is_empty())
+            else {
+                continue;
+            };
+            if let Err(e) = run_scheduled_backup(&db, &plugins_dir, Path::new(dir.trim())).await {
+                tracing::warn!("Scheduled backup to {} failed: {:#}", dir, e);
+            }
+        }
+    });
+}
+
+async fn run_scheduled_backup(db: &DbPool, plugins_dir: &Path, dir: &Path) -> Result<()> {
+    let hours = db::get_setting(db, BACKUP_INTERVAL_SETTING)
+        .await?
+        .and_then(|hours| hours.trim().parse().ok())
+        .unwrap_or(DEFAULT_BACKUP_INTERVAL_HOURS)
+        .max(1);
+    let keep = db::get_setting(db, BACKUP_KEEP_SETTING)
+        .await?
+        .and_then(|keep| keep.trim().parse().ok())
+        .unwrap_or(DEFAULT_BACKUP_KEEP)
+        .max(1);
+
+    tokio::fs::create_dir_all(dir).await?;
+    let backups = list_backups(dir)?;
+    let due = match backups.last() {
+        Some((_, modified)) => modified
+            .elapsed()
+            .map(|age| age >= Duration::from_secs(hours * 60 * 60))
+            .unwrap_or(true),
+        None => true,
+    };
+    if !due {
+        return Ok(());
+    }
+
+    let (file_name, archive) = create_backup(db, plugins_dir).await?;
+    let path = dir.join(&file_name);
+    tokio::fs::write(&path, archive).await?;
+    tracing::info!("Backup written to {}", path.display());
+
+    let mut backups = list_backups(dir)?;
+    let excess = backups.len().saturating_sub(keep);
+    for (old, _) in backups.drain(..excess) {
+        if let Err(e) = std::fs::remove_file(&old) {
+            tracing::warn!("Failed to remove old backup {}: {}", old.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Scheduled backups in `dir`, oldest first
+fn list_backups(dir: &Path) -> Result<Vec<(PathBuf, SystemTime)>> {
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with(FILE_PREFIX) || !name.ends_with(FILE_SUFFIX) {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        backups.push((entry.path(), modified));
+    }
+    backups.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+    Ok(backups)
+}
+
+pub fn backup_file_name(created_at: DateTime<Utc>) -> String {
+    format!(
+        "{}{}{}",
+        FILE_PREFIX,
+        created_at.format("%Y%m%d-%H%M%S"),
+        FILE_SUFFIX
+    )
+}
+
+fn pack(
+    manifest: &BackupManifest,
+    database: &[u8],
+    quick_actions: &[u8],
+    plugin_config: Option<&[u8]>,
+) -> Result<Vec<u8>> {
+    let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let mut append = |name: &str, data: &[u8]| -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(manifest.created_at.timestamp().max(0) as u64);
+        header.set_cksum();
+        archive.append_data(&mut header, name, data)?;
+        Ok(())
+    };
+    append(MANIFEST_ENTRY, &serde_json::to_vec_pretty(manifest)?)?;
+    append(DATABASE_ENTRY, database)?;
+    append(QUICK_ACTIONS_ENTRY, quick_actions)?;
+    if let Some(config) = plugin_config {
+        append(PLUGIN_CONFIG_ENTRY, config)?;
+    }
+    Ok(archive.into_inner()?.finish()?)
+}
+
+fn unpack(archive: &[u8]) -> Result<BackupContents, BackupError> {
+    let invalid = |e: std::io::Error| BackupError::Invalid(e.to_string());
+    let mut manifest = None;
+    let mut database = None;
+    let mut plugin_config = None;
+    let mut unpacked = 0;
+
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    for (index, entry) in archive.entries().map_err(invalid)?.enumerate() {
+        if index >= MAX_ENTRIES {
+            return Err(BackupError::Invalid(format!(
+                "more than {} entries",
+                MAX_ENTRIES
+            )));
+        }
+        let mut entry = entry.map_err(invalid)?;
+        // Skipped entries are decompressed too, so they count towards the limit
+        unpacked += entry.size();
+        if unpacked > MAX_UNPACKED_SIZE {
+            return Err(BackupError::Invalid(format!(
+                "unpacks to more than {} MB",
+                MAX_UNPACKED_SIZE / 1024 / 1024
+            )));
+        }
+        let name = entry
+            .path()
+            .map_err(invalid)?
+            .to_string_lossy()
+            .into_owned();
+        let target = match name.as_str() {
+            MANIFEST_ENTRY => &mut manifest,
+            DATABASE_ENTRY => &mut database,
+            PLUGIN_CONFIG_ENTRY => &mut plugin_config,
+            _ => continue,
+        };
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(invalid)?;
+        *target = Some(data);
+    }
+
+    let manifest: BackupManifest = manifest
+        .ok_or_else(|| BackupError::Invalid(format!("{} is missing", MANIFEST_ENTRY)))
+        .and_then(|manifest| {
+            serde_json::from_slice(&manifest)
+                .map_err(|e| BackupError::Invalid(format!("{}: {}", MANIFEST_ENTRY, e)))
+        })?;
+    if manifest.format > FORMAT_VERSION {
+        return Err(BackupError::Invalid(format!(
+            "made by a newer version ({})",
+            manifest.version
+        )));
+    }
+    let database =
+        database.ok_or_else(|| BackupError::Invalid(format!("{} is missing", DATABASE_ENTRY)))?;
+    if let Some(config) = &plugin_config {
+        serde_json::from_slice::<serde_json::Value>(config)
+            .map_err(|e| BackupError::Invalid(format!("{}: {}", PLUGIN_CONFIG_ENTRY, e)))?;
+    }
+
+    Ok(BackupContents {
+        manifest,
+        database,
+        plugin_config,
+    })
+}
+
+/// A path in the temporary directory, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(extension: &str) -> Self {
+        Self(std::env::temp_dir().join(format!(
+            "steering-backup-{}.{}",
+            uuid::Uuid::new_v4(),
+            extension
+        )))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let manifest = BackupManifest {
+            format: FORMAT_VERSION,
+            version: "1.2.3".to_string(),
+            created_at: Utc::now(),
+        };
+        let archive = pack(&manifest, b"SQLite", b"[]", Some(br#"{"plugins":{}}"#)).unwrap();
+
+        let contents = unpack(&archive).unwrap();
+        assert_eq!(contents.manifest.version, "1.2.3");
+        assert_eq!(contents.database, b"SQLite");
+        assert_eq!(
+            contents.plugin_config.as_deref(),
+            Some(&br#"{"plugins":{}}"#[..])
+        );
+
+        assert!(matches!(
+            unpack(b"not an archive"),
+            Err(BackupError::Invalid(_))
+        ));
+        let newer = BackupManifest {
+            format: FORMAT_VERSION + 1,
+            ..manifest
+        };
+        let archive = pack(&newer, b"SQLite", b"[]", None).unwrap();
+        assert!(matches!(unpack(&archive), Err(BackupError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_unpack_limits() {
+        let gzip = |tar: Vec<u8>| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            std::io::Write::write_all(&mut encoder, &tar).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        let mut builder = tar::Builder::new(Vec::new());
+        for i in 0..=MAX_ENTRIES {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(0);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("extra-{}", i), &[][..])
+                .unwrap();
+        }
+        let archive = gzip(builder.into_inner().unwrap());
+        assert!(matches!(unpack(&archive), Err(BackupError::Invalid(e)) if e.contains("entries")));
+
+        // Only the header is needed: the size is checked before the data is read
+        let mut header = tar::Header::new_gnu();
+        header.set_path(DATABASE_ENTRY).unwrap();
+        header.set_size(MAX_UNPACKED_SIZE + 1);
+        header.set_cksum();
+        let archive = gzip(header.as_bytes().to_vec());
+        assert!(matches!(unpack(&archive), Err(BackupError::Invalid(e)) if e.contains("MB")));
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("steering.db");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT);
+             INSERT INTO settings VALUES ('theme', 'dark');",
+        )
+        .unwrap();
+        drop(conn);
+        let pool = db::create_pool(&path, Default::default()).unwrap();
+
+        let copy = dir.path().join("copy.db");
+        db::vacuum_into(&pool, &copy).await.unwrap();
+        db::set_setting(&pool, "theme", "light").await.unwrap();
+
+        db::restore_from(&pool, &copy).await.unwrap();
+        assert_eq!(
+            db::get_setting(&pool, "theme").await.unwrap().as_deref(),
+            Some("dark")
+        );
+        // Tables of newer versions are added to the restored database
+        assert!(db::get_quick_actions(&pool).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_backup_uses_plugins_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        db::create_schema(&pool.get().await.unwrap()).unwrap();
+        let plugins_dir = dir.path().join("custom-plugins");
+        std::fs::create_dir_all(plugins_dir.join(".metadata")).unwrap();
+        std::fs::write(plugin_config_path(&plugins_dir), r#"{"notes":{}}"#).unwrap();
+
+        let (_, archive) = create_backup(&pool, &plugins_dir).await.unwrap();
+        let restored_dir = dir.path().join("restored-plugins");
+        let summary = restore_backup(&pool, &restored_dir, archive).await.unwrap();
+        assert!(summary.plugin_config_restored);
+        assert_eq!(
+            std::fs::read_to_string(plugin_config_path(&restored_dir)).unwrap(),
+            r#"{"notes":{}}"#
+        );
+    }
+
+    #[test]
+    fn test_list_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("steering-backup-20260101-000000.tar.gz"),
+            "",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        let backups = list_backups(dir.path()).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            backup_file_name("2026-01-01T00:00:00Z".parse().unwrap()),
+            "steering-backup-20260101-000000.tar.gz"
+        );
+    }
+}
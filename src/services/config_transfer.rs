@@ -0,0 +1,254 @@
+//! Exporting the configuration as JSON and importing it on another instance
+//!
+//! The document holds settings, quick actions, pipelines, pending scheduled
+//! tasks and users, so an instance can be set up like another one. Unlike a
+//! backup it leaves out history, sessions and plugin data, and importing merges
+//! into the existing configuration instead of replacing it.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::auth::hash_password;
+use crate::db::{
+    self, ConfigImport, DbPool, Pipeline, QuickAction, ScheduledTask, Setting, User, UserRole,
+};
+
+/// Version of the document layout
+const FORMAT_VERSION: u32 = 1;
+
+/// Settings that belong to one instance and are neither exported nor imported
+const INSTANCE_SETTINGS: [&str; 1] = ["instance_id"];
+
+/// Errors returned when importing a configuration
+#[derive(Debug)]
+pub enum ConfigImportError {
+    Invalid(String),
+    Failed(anyhow::Error),
+}
+
+impl std::fmt::Display for ConfigImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigImportError::Invalid(e) => write!(f, "Invalid configuration: {}", e),
+            ConfigImportError::Failed(e) => write!(f, "Import failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigImportError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigDocument {
+    pub format: u32,
+    /// Version of the Steering Center that exported it
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub exported_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+    #[serde(default)]
+    pub quick_actions: Vec<QuickAction>,
+    #[serde(default)]
+    pub pipelines: Vec<Pipeline>,
+    /// Only tasks that haven't run yet
+    #[serde(default)]
+    pub scheduled_tasks: Vec<ScheduledTask>,
+    #[serde(default)]
+    pub users: Vec<ExportedUser>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedUser {
+    pub id: String,
+    pub username: String,
+    /// Left out unless the export asked for password hashes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+    pub display_name: Option<String>,
+    pub role: UserRole,
+    pub is_active: bool,
+    pub created_at: String,
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+/// What an import changed
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+    pub settings: usize,
+    pub quick_actions: usize,
+    pub pipelines: usize,
+    pub scheduled_tasks: usize,
+    pub users: usize,
+    /// Scheduled tasks left out because their time has passed
+    pub skipped_scheduled_tasks: usize,
+    /// New users imported without a password hash; they can't sign in until
+    /// an admin sets a password
+    pub users_without_password: Vec<String>,
+}
+
+pub async fn export_config(db: &DbPool, include_password_hashes: bool) -> Result<ConfigDocument> {
+    let settings = db::get_all_settings(db)
+        .await?
+        .into_iter()
+        .filter(|setting| !INSTANCE_SETTINGS.contains(&setting.key.as_str()))
+        .map(|setting| (setting.key, setting.value))
+        .collect();
+    let users = db::get_all_users(db)
+        .await?
+        .into_iter()
+        .map(|user| ExportedUser {
+            id: user.id,
+            username: user.username,
+            password_hash: include_password_hashes.then_some(user.password_hash),
+            display_name: user.display_name,
+            role: user.role,
+            is_active: user.is_active,
+            created_at: user.created_at,
+            expires_at: user.expires_at,
+        })
+        .collect();
+
+    Ok(ConfigDocument {
+        format: FORMAT_VERSION,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: Some(Utc::now()),
+        settings,
+        quick_actions: db::get_quick_actions(db).await?,
+        pipelines: db::get_pipelines(db).await?,
+        scheduled_tasks: db::get_pending_scheduled_tasks(db).await?,
+        users,
+    })
+}
+
+/// Merge a configuration document into this instance's configuration
+pub async fn import_config(
+    db: &DbPool,
+    document: ConfigDocument,
+) -> Result<ImportSummary, ConfigImportError> {
+    if document.format > FORMAT_VERSION {
+        return Err(ConfigImportError::Invalid(format!(
+            "exported by a newer version ({})",
+            document.version
+        )));
+    }
+    if let Some(user) = document
+        .users
+        .iter()
+        .find(|user| user.username.trim().is_empty())
+    {
+        return Err(ConfigImportError::Invalid(format!(
+            "user {} has no username",
+            user.id
+        )));
+    }
+
+    let settings: Vec<Setting> = document
+        .settings
+        .into_iter()
+        .filter(|(key, _)| !INSTANCE_SETTINGS.contains(&key.as_str()))
+        .map(|(key, value)| Setting { key, value })
+        .collect();
+
+    // A task whose time has passed would run as soon as it is imported
+    let now = Utc::now();
+    let (scheduled_tasks, past): (Vec<ScheduledTask>, Vec<ScheduledTask>) = document
+        .scheduled_tasks
+        .into_iter()
+        .filter(|task| task.status == "pending")
+        .partition(|task| {
+            DateTime::parse_from_rfc3339(&task.run_at).is_ok_and(|run_at| run_at > now)
+        });
+
+    let mut users = Vec::with_capacity(document.users.len());
+    let mut users_without_password = Vec::new();
+    for user in document.users {
+        let replace_password = user.password_hash.is_some();
+        let password_hash = match user.password_hash {
+            Some(hash) => hash,
+            None => {
+                let existing = db::get_user_by_username(db, &user.username)
+                    .await
+                    .map_err(ConfigImportError::Failed)?;
+                if existing.is_none() {
+                    users_without_password.push(user.username.clone());
+                }
+                // Unusable until an admin sets a password; kept for existing users
+                hash_password(&uuid::Uuid::new_v4().to_string())
+                    .map_err(|e| ConfigImportError::Failed(anyhow::anyhow!(e.to_string())))?
+            }
+        };
+        users.push((
+            User {
+                id: user.id,
+                username: user.username,
+                password_hash,
+                display_name: user.display_name,
+                role: user.role,
+                is_active: user.is_active,
+                created_at: user.created_at,
+                expires_at: user.expires_at,
+                session_hours: None,
+                idle_timeout_minutes: None,
+            },
+            replace_password,
+        ));
+    }
+
+    db::import_config(
+        db,
+        &ConfigImport {
+            settings: &settings,
+            quick_actions: &document.quick_actions,
+            pipelines: &document.pipelines,
+            scheduled_tasks: &scheduled_tasks,
+            users: &users,
+        },
+    )
+    .await
+    .map_err(ConfigImportError::Failed)?;
+
+    Ok(ImportSummary {
+        settings: settings.len(),
+        quick_actions: document.quick_actions.len(),
+        pipelines: document.pipelines.len(),
+        scheduled_tasks: scheduled_tasks.len(),
+        users: users.len(),
+        skipped_scheduled_tasks: past.len(),
+        users_without_password,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_import_validation() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+
+        // Sections left out of a hand-written document default to empty
+        let document: ConfigDocument =
+            serde_json::from_str(r#"{"format": 2, "version": "9.0.0"}"#).unwrap();
+        assert!(document.settings.is_empty() && document.users.is_empty());
+        assert!(matches!(
+            import_config(&pool, document).await,
+            Err(ConfigImportError::Invalid(_))
+        ));
+
+        let document: ConfigDocument = serde_json::from_str(
+            r#"{"format": 1, "users": [{"id": "u1", "username": " ", "display_name": null,
+                "role": "client", "is_active": true, "created_at": "2024-01-01T00:00:00Z"}]}"#,
+        )
+        .unwrap();
+        assert!(document.users[0].password_hash.is_none());
+        assert!(matches!(
+            import_config(&pool, document).await,
+            Err(ConfigImportError::Invalid(_))
+        ));
+    }
+}
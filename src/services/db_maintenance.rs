@@ -0,0 +1,65 @@
+//! Periodic database maintenance
+//!
+//! Every `db_maintenance_interval_hours` the database is checked for
+//! corruption, its query planner statistics are refreshed and unused pages are
+//! given back to the file system. Admins can also run it on demand.
+
+use anyhow::Result;
+use std::time::Duration;
+
+use crate::db::{self, DbPool, MaintenanceReport};
+
+/// Setting with the hours between maintenance runs; `0` turns them off
+pub const MAINTENANCE_INTERVAL_SETTING: &str = "db_maintenance_interval_hours";
+
+const DEFAULT_MAINTENANCE_INTERVAL_HOURS: u64 = 24;
+
+/// How long to wait before looking at the setting again while runs are off
+const DISABLED_POLL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Run maintenance now and log the outcome
+pub async fn run_maintenance(db: &DbPool) -> Result<MaintenanceReport> {
+    let report = db::run_maintenance(db).await?;
+    if report.integrity_ok {
+        tracing::info!(
+            "Database maintenance finished in {} ms, {} pages freed{}",
+            report.duration_ms,
+            report.freed_pages,
+            if report.full_vacuum {
+                " (full vacuum)"
+            } else {
+                ""
+            }
+        );
+    } else {
+        tracing::error!(
+            "Database integrity check failed, maintenance skipped: {}",
+            report.integrity_errors.join("; ")
+        );
+    }
+    Ok(report)
+}
+
+/// Run maintenance on the configured interval, the first time one interval
+/// after startup
+pub fn spawn_maintenance_scheduler(db: DbPool) {
+    tokio::spawn(async move {
+        loop {
+            let hours = db::get_setting(&db, MAINTENANCE_INTERVAL_SETTING)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|hours| hours.trim().parse().ok())
+                .unwrap_or(DEFAULT_MAINTENANCE_INTERVAL_HOURS);
+            if hours == 0 {
+                tokio::time::sleep(DISABLED_POLL_INTERVAL).await;
+                continue;
+            }
+
+            tokio::time::sleep(Duration::from_secs(hours * 60 * 60)).await;
+            if let Err(e) = run_maintenance(&db).await {
+                tracing::warn!("Database maintenance failed: {:#}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,376 @@
+//! Containers on the local Docker daemon, for monitoring and control
+//!
+//! The integration is off until the `docker_enabled` setting is `true`. The
+//! daemon is reached through `DOCKER_HOST` or the default local socket.
+
+use bollard::container::{
+    ListContainersOptions, LogsOptions, MemoryStatsStats, RestartContainerOptions, Stats,
+    StatsOptions, StopContainerOptions,
+};
+use bollard::models::{ContainerSummary, Port};
+use bollard::Docker;
+use futures::StreamExt;
+use serde::Serialize;
+
+use crate::db::{self, DbPool};
+
+/// Setting that turns the Docker endpoints on
+pub const DOCKER_ENABLED_SETTING: &str = "docker_enabled";
+
+/// Log lines returned when the request doesn't say how many
+pub const DEFAULT_LOG_TAIL: usize = 100;
+
+/// Most log lines returned at once
+pub const MAX_LOG_TAIL: usize = 5000;
+
+/// Seconds a container gets to exit on stop/restart before it is killed
+const STOP_TIMEOUT_SECS: i64 = 10;
+
+/// Errors returned by the Docker integration
+#[derive(Debug)]
+pub enum DockerError {
+    Disabled,
+    /// The daemon can't be reached
+    Unavailable(String),
+    NotFound(String),
+    /// The daemon refused the request, e.g. starting a running container
+    Rejected(String),
+}
+
+impl std::fmt::Display for DockerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DockerError::Disabled => write!(
+                f,
+                "Docker integration is disabled (set {} to true)",
+                DOCKER_ENABLED_SETTING
+            ),
+            DockerError::Unavailable(e) => write!(f, "Docker daemon unavailable: {}", e),
+            DockerError::NotFound(id) => write!(f, "Container {} not found", id),
+            DockerError::Rejected(e) => write!(f, "Docker refused the request: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DockerError {}
+
+impl DockerError {
+    fn from_api(container: &str, e: bollard::errors::Error) -> Self {
+        match e {
+            bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            } => DockerError::NotFound(container.to_string()),
+            bollard::errors::Error::DockerResponseServerError { message, .. } => {
+                DockerError::Rejected(message)
+            }
+            e => DockerError::Unavailable(e.to_string()),
+        }
+    }
+}
+
+/// Whether the `docker_enabled` setting is on
+pub async fn docker_enabled(db: &DbPool) -> bool {
+    matches!(
+        db::get_setting(db, DOCKER_ENABLED_SETTING).await,
+        Ok(Some(value)) if value == "true"
+    )
+}
+
+/// Client for the local daemon, if the integration is enabled
+///
+/// Connecting doesn't talk to the daemon yet; a missing daemon shows up as
+/// `Unavailable` on the first request.
+pub async fn connect(db: &DbPool) -> Result<Docker, DockerError> {
+    if !docker_enabled(db).await {
+        return Err(DockerError::Disabled);
+    }
+    Docker::connect_with_local_defaults().map_err(|e| DockerError::Unavailable(e.to_string()))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContainerInfo {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    /// `running`, `exited`, `paused`, ...
+    pub state: String,
+    /// Human-readable status, e.g. `Up 3 hours`
+    pub status: String,
+    /// RFC 3339
+    pub created: Option<String>,
+    /// Published ports, e.g. `0.0.0.0:8080->80/tcp`
+    pub ports: Vec<String>,
+}
+
+impl From<ContainerSummary> for ContainerInfo {
+    fn from(summary: ContainerSummary) -> Self {
+        let id = summary.id.unwrap_or_default();
+        let name = summary
+            .names
+            .and_then(|names| names.into_iter().next())
+            .map(|name| name.trim_start_matches('/').to_string())
+            .unwrap_or_else(|| id.chars().take(12).collect());
+        Self {
+            id,
+            name,
+            image: summary.image.unwrap_or_default(),
+            state: summary.state.unwrap_or_default(),
+            status: summary.status.unwrap_or_default(),
+            created: summary
+                .created
+                .and_then(|created| chrono::DateTime::from_timestamp(created, 0))
+                .map(|at| at.to_rfc3339()),
+            ports: summary
+                .ports
+                .unwrap_or_default()
+                .iter()
+                .map(format_port)
+                .collect(),
+        }
+    }
+}
+
+fn format_port(port: &Port) -> String {
+    let protocol = port
+        .typ
+        .map(|typ| typ.to_string())
+        .unwrap_or_else(|| "tcp".to_string());
+    match port.public_port {
+        Some(public) => format!(
+            "{}:{}->{}/{}",
+            port.ip.as_deref().unwrap_or("0.0.0.0"),
+            public,
+            port.private_port,
+            protocol
+        ),
+        None => format!("{}/{}", port.private_port, protocol),
+    }
+}
+
+/// List containers, including stopped ones when `all` is set
+pub async fn list_containers(
+    docker: &Docker,
+    all: bool,
+) -> Result<Vec<ContainerInfo>, DockerError> {
+    let options = ListContainersOptions::<String> {
+        all,
+        ..Default::default()
+    };
+    let mut containers: Vec<ContainerInfo> = docker
+        .list_containers(Some(options))
+        .await
+        .map_err(|e| DockerError::from_api("", e))?
+        .into_iter()
+        .map(ContainerInfo::from)
+        .collect();
+    containers.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(containers)
+}
+
+/// Resource usage of a running container
+#[derive(Debug, Clone, Serialize)]
+pub struct ContainerStats {
+    pub id: String,
+    pub name: String,
+    /// In percent of one core, like `docker stats`
+    pub cpu_percent: f64,
+    /// Memory in use, not counting inactive page cache
+    pub memory_bytes: u64,
+    pub memory_limit: u64,
+    pub network_rx_bytes: u64,
+    pub network_tx_bytes: u64,
+    pub pids: u64,
+}
+
+impl From<Stats> for ContainerStats {
+    fn from(stats: Stats) -> Self {
+        let cpu = &stats.cpu_stats;
+        let precpu = &stats.precpu_stats;
+        let cpus = cpu
+            .online_cpus
+            .or_else(|| cpu.cpu_usage.percpu_usage.as_ref().map(|v| v.len() as u64))
+            .unwrap_or(1);
+        let cpu_percent = cpu_percent(
+            cpu.cpu_usage
+                .total_usage
+                .saturating_sub(precpu.cpu_usage.total_usage),
+            cpu.system_cpu_usage
+                .unwrap_or(0)
+                .saturating_sub(precpu.system_cpu_usage.unwrap_or(0)),
+            cpus,
+        );
+
+        let inactive_file = match stats.memory_stats.stats {
+            Some(MemoryStatsStats::V1(v1)) => v1.total_inactive_file,
+            Some(MemoryStatsStats::V2(v2)) => v2.inactive_file,
+            None => 0,
+        };
+        let (network_rx_bytes, network_tx_bytes) = stats
+            .networks
+            .iter()
+            .flat_map(|networks| networks.values())
+            .fold((0, 0), |(rx, tx), network| {
+                (rx + network.rx_bytes, tx + network.tx_bytes)
+            });
+
+        Self {
+            name: stats.name.trim_start_matches('/').to_string(),
+            id: stats.id,
+            cpu_percent,
+            memory_bytes: stats
+                .memory_stats
+                .usage
+                .unwrap_or(0)
+                .saturating_sub(inactive_file),
+            memory_limit: stats.memory_stats.limit.unwrap_or(0),
+            network_rx_bytes,
+            network_tx_bytes,
+            pids: stats.pids_stats.current.unwrap_or(0),
+        }
+    }
+}
+
+/// CPU usage between two samples, computed the way `docker stats` does
+///
+/// # Arguments
+/// * `cpu_delta` - Container CPU time used between the samples
+/// * `system_delta` - Host CPU time (all cores) between the samples
+/// * `cpus` - Cores available to the container
+fn cpu_percent(cpu_delta: u64, system_delta: u64, cpus: u64) -> f64 {
+    if system_delta == 0 {
+        return 0.0;
+    }
+    cpu_delta as f64 / system_delta as f64 * cpus as f64 * 100.0
+}
+
+/// Sample a container's resource usage
+///
+/// Takes about a second: the daemon waits for a second sample to measure CPU.
+pub async fn container_stats(docker: &Docker, id: &str) -> Result<ContainerStats, DockerError> {
+    let options = StatsOptions {
+        stream: false,
+        one_shot: false,
+    };
+    match docker.stats(id, Some(options)).next().await {
+        Some(Ok(stats)) => Ok(stats.into()),
+        Some(Err(e)) => Err(DockerError::from_api(id, e)),
+        None => Err(DockerError::NotFound(id.to_string())),
+    }
+}
+
+/// The last `tail` lines a container wrote to stdout and stderr, oldest first
+pub async fn container_logs(
+    docker: &Docker,
+    id: &str,
+    tail: usize,
+) -> Result<Vec<String>, DockerError> {
+    let options = LogsOptions::<String> {
+        stdout: true,
+        stderr: true,
+        timestamps: true,
+        tail: tail.clamp(1, MAX_LOG_TAIL).to_string(),
+        ..Default::default()
+    };
+    let mut logs = docker.logs(id, Some(options));
+    let mut lines = Vec::new();
+    while let Some(output) = logs.next().await {
+        let output = output.map_err(|e| DockerError::from_api(id, e))?;
+        lines.extend(output.to_string().lines().map(String::from));
+    }
+    Ok(lines)
+}
+
+/// What to do with a container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerAction {
+    Start,
+    Stop,
+    Restart,
+}
+
+impl std::fmt::Display for ContainerAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContainerAction::Start => write!(f, "start"),
+            ContainerAction::Stop => write!(f, "stop"),
+            ContainerAction::Restart => write!(f, "restart"),
+        }
+    }
+}
+
+pub async fn control_container(
+    docker: &Docker,
+    id: &str,
+    action: ContainerAction,
+) -> Result<(), DockerError> {
+    let result = match action {
+        ContainerAction::Start => docker.start_container::<String>(id, None).await,
+        ContainerAction::Stop => {
+            docker
+                .stop_container(
+                    id,
+                    Some(StopContainerOptions {
+                        t: STOP_TIMEOUT_SECS,
+                    }),
+                )
+                .await
+        }
+        ContainerAction::Restart => {
+            docker
+                .restart_container(
+                    id,
+                    Some(RestartContainerOptions {
+                        t: STOP_TIMEOUT_SECS as isize,
+                    }),
+                )
+                .await
+        }
+    };
+    result.map_err(|e| DockerError::from_api(id, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bollard::models::PortTypeEnum;
+
+    #[test]
+    fn test_cpu_percent() {
+        assert_eq!(cpu_percent(50, 100, 4), 200.0);
+        assert_eq!(cpu_percent(50, 0, 4), 0.0);
+    }
+
+    #[test]
+    fn test_container_info() {
+        let summary = ContainerSummary {
+            id: Some("0123456789abcdef".to_string()),
+            names: Some(vec!["/web".to_string()]),
+            image: Some("nginx:latest".to_string()),
+            state: Some("running".to_string()),
+            ports: Some(vec![
+                Port {
+                    ip: Some("0.0.0.0".to_string()),
+                    private_port: 80,
+                    public_port: Some(8080),
+                    typ: Some(PortTypeEnum::TCP),
+                },
+                Port {
+                    ip: None,
+                    private_port: 53,
+                    public_port: None,
+                    typ: Some(PortTypeEnum::UDP),
+                },
+            ]),
+            ..Default::default()
+        };
+        let info = ContainerInfo::from(summary);
+        assert_eq!(info.name, "web");
+        assert_eq!(info.ports, ["0.0.0.0:8080->80/tcp", "53/udp"]);
+
+        let unnamed = ContainerInfo::from(ContainerSummary {
+            id: Some("0123456789abcdef".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(unnamed.name, "0123456789ab");
+    }
+}
@@ -0,0 +1,107 @@
+use serde::Serialize;
+
+use toru_plugin_api::{
+    Message, MessagePayload, NotifyLevel, PluginEventPayload, PluginNotification,
+};
+
+/// Longest event name accepted from a plugin
+pub const MAX_EVENT_NAME_LEN: usize = 128;
+
+/// How many events a browser connection may fall behind before it misses some
+pub const BROWSER_EVENT_BUFFER: usize = 256;
+
+/// Longest notification title accepted from a plugin
+pub const MAX_NOTIFICATION_TITLE_LEN: usize = 200;
+
+/// Longest notification body accepted from a plugin
+pub const MAX_NOTIFICATION_BODY_LEN: usize = 4096;
+
+/// An event a plugin published on the event bus
+#[derive(Debug, Clone, Serialize)]
+pub struct PublishedEvent {
+    pub plugin_id: String,
+    pub name: String,
+    pub data: serde_json::Value,
+    pub timestamp: String,
+}
+
+impl PublishedEvent {
+    /// The event as published by `plugin_id`; a `source` the plugin set itself is ignored
+    pub fn new(plugin_id: &str, payload: &PluginEventPayload) -> Self {
+        Self {
+            plugin_id: plugin_id.to_string(),
+            name: payload.name.clone(),
+            data: payload.data.clone(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Why the event is rejected, if it is
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("Event name is empty".to_string());
+        }
+        if self.name.len() > MAX_EVENT_NAME_LEN {
+            return Err(format!(
+                "Event name is longer than {} characters",
+                MAX_EVENT_NAME_LEN
+            ));
+        }
+        Ok(())
+    }
+
+    /// Message forwarding the event to a subscribed plugin
+    pub fn to_message(&self) -> Message {
+        let mut message = Message::new_event(&self.name, self.data.clone());
+        if let MessagePayload::Event { payload } = &mut message.payload {
+            payload.source = Some(self.plugin_id.clone());
+        }
+        message
+    }
+}
+
+/// A notification a plugin sent for the users of the UI
+#[derive(Debug, Clone, Serialize)]
+pub struct BrowserNotification {
+    pub plugin_id: String,
+    pub level: NotifyLevel,
+    pub title: String,
+    pub body: Option<String>,
+    pub timestamp: String,
+}
+
+impl BrowserNotification {
+    pub fn new(plugin_id: &str, payload: PluginNotification) -> Self {
+        Self {
+            plugin_id: plugin_id.to_string(),
+            level: payload.level,
+            title: payload.title,
+            body: payload.body,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Why the notification is rejected, if it is
+    pub fn validate(&self) -> Result<(), String> {
+        if self.title.trim().is_empty() {
+            return Err("Notification title is empty".to_string());
+        }
+        if self.title.len() > MAX_NOTIFICATION_TITLE_LEN {
+            return Err(format!(
+                "Notification title is longer than {} characters",
+                MAX_NOTIFICATION_TITLE_LEN
+            ));
+        }
+        if self
+            .body
+            .as_ref()
+            .is_some_and(|body| body.len() > MAX_NOTIFICATION_BODY_LEN)
+        {
+            return Err(format!(
+                "Notification body is longer than {} characters",
+                MAX_NOTIFICATION_BODY_LEN
+            ));
+        }
+        Ok(())
+    }
+}
@@ -1,13 +1,18 @@
-use crate::db::{self, DbPool, TaskHistory};
-use anyhow::Result;
+use crate::db::{self, ConcurrencyMode, DbPool, Pipeline, QuickAction, TaskHistory};
+use crate::services::notifications::{self, Notification, NotificationKind, NotificationLevel};
+use crate::services::webhooks;
+use anyhow::{bail, Result};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command as TokioCommand;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OwnedMutexGuard};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskMessage {
@@ -18,6 +23,138 @@ pub struct TaskMessage {
     pub data: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<i32>,
+    /// Pipeline step index (only set for pipeline step events)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step: Option<usize>,
+}
+
+impl TaskMessage {
+    /// Create an event message for a task
+    pub fn event(r#type: &str, task_id: &str) -> Self {
+        Self {
+            r#type: r#type.to_string(),
+            task_id: Some(task_id.to_string()),
+            data: None,
+            code: None,
+            step: None,
+        }
+    }
+
+    /// Set the data payload
+    pub fn with_data(mut self, data: &str) -> Self {
+        self.data = Some(data.to_string());
+        self
+    }
+
+    /// Set the exit code
+    pub fn with_code(mut self, code: i32) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Set the pipeline step index
+    pub fn with_step(mut self, step: usize) -> Self {
+        self.step = Some(step);
+        self
+    }
+}
+
+/// Channel used to stream task events to a client
+pub type EventSender = tokio::sync::mpsc::UnboundedSender<TaskMessage>;
+
+/// Default cap on output captured in memory and stored in task history (1 MiB)
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Marker appended to captured output once the cap is reached
+pub const OUTPUT_TRUNCATED_MARKER: &str = "[output truncated, full log on disk]";
+
+/// Directory holding the full on-disk output of every task
+pub fn task_log_dir() -> PathBuf {
+    crate::services::logging::log_dir().join("tasks")
+}
+
+/// Path of the full output log for a task
+pub fn task_log_path(task_id: &str) -> PathBuf {
+    task_log_dir().join(format!("{}.log", task_id))
+}
+
+/// Read the output cap from the `task_output_max_bytes` setting
+pub async fn max_output_bytes(pool: &DbPool) -> usize {
+    db::get_setting(pool, "task_output_max_bytes")
+        .await
+        .ok()
+        .flatten()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_OUTPUT_BYTES)
+}
+
+/// Captures task output: everything goes to the on-disk log, while the
+/// in-memory buffer (later stored in task history) stops at `max_bytes`.
+struct OutputCapture {
+    buffer: String,
+    max_bytes: usize,
+    truncated: bool,
+    log_file: Option<tokio::fs::File>,
+}
+
+impl OutputCapture {
+    /// Capture output, writing all of it to `log_path`
+    async fn new(log_path: &Path, max_bytes: usize) -> Self {
+        let log_file = async {
+            if let Some(dir) = log_path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::File::create(log_path).await
+        }
+        .await
+        .map_err(|e| tracing::warn!("Failed to create task log {}: {}", log_path.display(), e))
+        .ok();
+
+        Self {
+            buffer: String::new(),
+            max_bytes,
+            truncated: false,
+            log_file,
+        }
+    }
+
+    /// Record a line. Returns `Captured` while under the cap, `Truncated` for the
+    /// line that crosses it and `Dropped` for every line after that.
+    async fn push(&mut self, line: &str) -> CaptureResult {
+        if let Some(ref mut file) = self.log_file {
+            if file.write_all(line.as_bytes()).await.is_err() {
+                self.log_file = None;
+            }
+        }
+
+        if self.truncated {
+            return CaptureResult::Dropped;
+        }
+
+        if self.buffer.len() + line.len() > self.max_bytes {
+            self.truncated = true;
+            self.buffer.push_str(OUTPUT_TRUNCATED_MARKER);
+            self.buffer.push('\n');
+            return CaptureResult::Truncated;
+        }
+
+        self.buffer.push_str(line);
+        CaptureResult::Captured
+    }
+
+    async fn finish(mut self) -> String {
+        if let Some(ref mut file) = self.log_file {
+            let _ = file.flush().await;
+        }
+        self.buffer
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum CaptureResult {
+    Captured,
+    Truncated,
+    Dropped,
 }
 
 /// Stores the child process handle for cancellation
@@ -27,18 +164,192 @@ pub fn create_task_registry() -> TaskRegistry {
     Arc::new(Mutex::new(HashMap::new()))
 }
 
+/// Resource limits applied to a script process before exec
+///
+/// `None` means "no limit" (inherit from the server process).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// CPU time in seconds (RLIMIT_CPU)
+    pub cpu_secs: Option<u64>,
+    /// Address space in megabytes (RLIMIT_AS)
+    pub memory_mb: Option<u64>,
+    /// Maximum open file descriptors (RLIMIT_NOFILE)
+    pub nofile: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Load the global limits from the settings table
+    ///
+    /// Keys: `script_cpu_limit_secs`, `script_memory_limit_mb`, `script_nofile_limit`.
+    /// Missing, empty or non-numeric values mean "no limit".
+    pub async fn from_settings(pool: &DbPool) -> Self {
+        async fn read(pool: &DbPool, key: &str) -> Option<u64> {
+            db::get_setting(pool, key)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|v| v.trim().parse().ok())
+                .filter(|v| *v > 0)
+        }
+
+        Self {
+            cpu_secs: read(pool, "script_cpu_limit_secs").await,
+            memory_mb: read(pool, "script_memory_limit_mb").await,
+            nofile: read(pool, "script_nofile_limit").await,
+        }
+    }
+
+    /// Apply per-quick-action overrides on top of these limits
+    pub fn with_action_overrides(self, action: &QuickAction) -> Self {
+        let positive = |v: Option<i64>| v.filter(|v| *v > 0).map(|v| v as u64);
+        Self {
+            cpu_secs: positive(action.cpu_limit_secs).or(self.cpu_secs),
+            memory_mb: positive(action.memory_limit_mb).or(self.memory_mb),
+            nofile: positive(action.nofile_limit).or(self.nofile),
+        }
+    }
+
+    /// Install the limits on a command so they are applied in the child before exec
+    #[cfg(unix)]
+    fn apply(&self, command: &mut TokioCommand) {
+        if *self == Self::default() {
+            return;
+        }
+
+        let limits = *self;
+        // SAFETY: the closure runs between fork and exec and only calls setrlimit,
+        // which is async-signal-safe. No allocation happens inside it.
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(secs) = limits.cpu_secs {
+                    // SIGXCPU comes at the soft limit, SIGKILL only at the hard one
+                    set_rlimit(
+                        libc::RLIMIT_CPU,
+                        secs,
+                        secs.saturating_add(CPU_LIMIT_GRACE_SECS),
+                    )?;
+                }
+                if let Some(mb) = limits.memory_mb {
+                    let bytes = mb.saturating_mul(1024 * 1024);
+                    set_rlimit(libc::RLIMIT_AS, bytes, bytes)?;
+                }
+                if let Some(n) = limits.nofile {
+                    set_rlimit(libc::RLIMIT_NOFILE, n, n)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn apply(&self, _command: &mut TokioCommand) {
+        if *self != Self::default() {
+            tracing::warn!("Script resource limits are only supported on Unix, ignoring");
+        }
+    }
+}
+
+// glibc declares the resource argument of setrlimit with its own type
+#[cfg(all(unix, target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type RlimitResource = libc::c_int;
+
+/// Seconds a script gets past its CPU limit to handle SIGXCPU before it is killed
+#[cfg(unix)]
+const CPU_LIMIT_GRACE_SECS: u64 = 5;
+
+/// Lower a resource limit of the current process to `soft`, and its hard
+/// limit to `hard`; neither is raised above the hard limit in place
+#[cfg(unix)]
+pub(crate) fn set_rlimit(resource: RlimitResource, soft: u64, hard: u64) -> std::io::Result<()> {
+    let mut current = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `current` is a valid rlimit struct for getrlimit to fill in
+    if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let rlim_max = (hard as libc::rlim_t).min(current.rlim_max);
+    let limit = libc::rlimit {
+        rlim_cur: (soft as libc::rlim_t).min(rlim_max),
+        rlim_max,
+    };
+    // SAFETY: `limit` is a valid, initialized rlimit struct
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
 /// Spawns a script and returns stdout/stderr handles separately.
 /// The Child is wrapped for safe cancellation while streaming.
-pub async fn execute_script(script_path: &str) -> Result<tokio::process::Child> {
-    let child = TokioCommand::new("sh")
-        .arg(script_path)
+pub async fn execute_script(
+    script_path: &str,
+    limits: &ResourceLimits,
+    env: &HashMap<String, String>,
+) -> Result<tokio::process::Child> {
+    let mut command = script_command(script_path);
+    command
+        .envs(env)
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
+        .stderr(Stdio::piped());
+    limits.apply(&mut command);
+
+    let child = command.spawn()?;
 
     Ok(child)
 }
 
+/// PowerShell executable (Windows PowerShell on Windows, PowerShell Core elsewhere)
+#[cfg(windows)]
+const POWERSHELL: &str = "powershell";
+#[cfg(not(windows))]
+const POWERSHELL: &str = "pwsh";
+
+/// Build the command that runs a script, choosing the interpreter from its extension
+///
+/// `.ps1` runs under PowerShell, `.bat`/`.cmd` under `cmd /C`, `.bash` under bash and
+/// everything else under `sh`.
+fn script_command(script_path: &str) -> TokioCommand {
+    let extension = std::path::Path::new(script_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("ps1") => {
+            let mut command = TokioCommand::new(POWERSHELL);
+            command.args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-ExecutionPolicy",
+                "Bypass",
+                "-File",
+                script_path,
+            ]);
+            command
+        }
+        Some("bat") | Some("cmd") => {
+            // cmd.exe does not accept forward slashes in the script path
+            let mut command = TokioCommand::new("cmd");
+            command.arg("/C").arg(script_path.replace('/', "\\"));
+            command
+        }
+        Some("bash") => {
+            let mut command = TokioCommand::new("bash");
+            command.arg(script_path);
+            command
+        }
+        _ => {
+            let mut command = TokioCommand::new("sh");
+            command.arg(script_path);
+            command
+        }
+    }
+}
+
 /// Stores task handle in registry for cancellation support
 pub async fn store_task(task_id: String, child: tokio::process::Child, registry: &TaskRegistry) {
     let mut reg = registry.lock().await;
@@ -78,15 +389,317 @@ pub async fn cancel_task(task_id: &str, registry: &TaskRegistry) -> Result<bool>
     Ok(false)
 }
 
+/// Forward an output line to the event channel, honouring the capture cap
+fn stream_line(tx: &EventSender, task_id: &str, stream: &str, line: &str, result: CaptureResult) {
+    let (msg_type, data) = match result {
+        CaptureResult::Captured => (stream, line.trim_end()),
+        CaptureResult::Truncated => ("truncated", OUTPUT_TRUNCATED_MARKER),
+        CaptureResult::Dropped => return,
+    };
+    let _ = tx.send(TaskMessage::event(msg_type, task_id).with_data(data));
+}
+
+/// Longest piece of output read as one line; longer lines are split, so output
+/// without newlines isn't buffered whole
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// Read up to and including the next newline into `line`, stopping once it holds
+/// `MAX_LINE_BYTES`. Bytes read before a cancellation stay in `line`.
+async fn read_capped_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    line: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    let limit = MAX_LINE_BYTES.saturating_sub(line.len()) as u64;
+    reader.take(limit).read_until(b'\n', line).await
+}
+
+/// Stream a child's stdout/stderr line by line into the capture and the event channel
+async fn stream_output(
+    stdout: tokio::process::ChildStdout,
+    stderr: tokio::process::ChildStderr,
+    capture: &mut OutputCapture,
+    task_id: &str,
+    event_sender: &Option<EventSender>,
+) {
+    let mut stdout_reader = BufReader::new(stdout);
+    let mut stderr_reader = BufReader::new(stderr);
+    let mut stdout_line = Vec::new();
+    let mut stderr_line = Vec::new();
+    let mut stdout_done = false;
+    let mut stderr_done = false;
+
+    while !stdout_done || !stderr_done {
+        tokio::select! {
+            result = read_capped_line(&mut stdout_reader, &mut stdout_line), if !stdout_done => {
+                match result {
+                    Ok(0) => stdout_done = true,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&stdout_line);
+                        let result = capture.push(&line).await;
+                        if let Some(ref tx) = event_sender {
+                            stream_line(tx, task_id, "stdout", &line, result);
+                        }
+                        stdout_line.clear();
+                    }
+                    Err(_) => stdout_done = true,
+                }
+            }
+            result = read_capped_line(&mut stderr_reader, &mut stderr_line), if !stderr_done => {
+                match result {
+                    Ok(0) => stderr_done = true,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&stderr_line);
+                        let result = capture.push(&line).await;
+                        if let Some(ref tx) = event_sender {
+                            stream_line(tx, task_id, "stderr", &line, result);
+                        }
+                        stderr_line.clear();
+                    }
+                    Err(_) => stderr_done = true,
+                }
+            }
+        }
+    }
+}
+
+/// Wait for the registered child of a task to exit
+///
+/// Returns `None` if the task was cancelled (its handle was killed or removed).
+async fn wait_for_exit(task_id: &str, registry: &TaskRegistry) -> Option<i32> {
+    let handle = get_task(task_id, registry).await?;
+    let mut child_opt = handle.lock().await;
+    let child = child_opt.as_mut()?;
+    let status = child.wait().await;
+    Some(status.ok().and_then(|s| s.code()).unwrap_or(-1))
+}
+
+/// Error returned when a script is already running and its concurrency mode is `Reject`
+#[derive(Debug)]
+pub struct ScriptBusy(pub String);
+
+impl std::fmt::Display for ScriptBusy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Script '{}' is already running", self.0)
+    }
+}
+
+impl std::error::Error for ScriptBusy {}
+
+/// Tasks started and not yet recorded as finished, with the registry holding their process
+static ACTIVE_TASKS: OnceLock<std::sync::Mutex<HashMap<String, TaskRegistry>>> = OnceLock::new();
+
+/// Set once the server shuts down; no new tasks are started after it
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Set when running tasks are cancelled on shutdown; pipelines start no further steps
+static CANCELLING: AtomicBool = AtomicBool::new(false);
+
+/// How long cancelled tasks get to record their result
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+fn active_tasks() -> std::sync::MutexGuard<'static, HashMap<String, TaskRegistry>> {
+    ACTIVE_TASKS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Track a task from its history row being written until it is finished
+fn track_task(task_id: &str, registry: &TaskRegistry) -> Result<()> {
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        bail!("The server is shutting down");
+    }
+    active_tasks().insert(task_id.to_string(), registry.clone());
+    Ok(())
+}
+
+fn untrack_task(task_id: &str) {
+    active_tasks().remove(task_id);
+}
+
+/// Send `task.succeeded` or `task.failed` to webhooks, and notify about failures
+fn notify_task_finished(db: &DbPool, task: &TaskHistory, finished_at: &str, exit_code: i32) {
+    let event = if exit_code == 0 {
+        "task.succeeded"
+    } else {
+        "task.failed"
+    };
+    webhooks::emit(
+        event,
+        serde_json::json!({
+            "task_id": task.id,
+            "script_name": task.script_name,
+            "started_at": task.started_at,
+            "finished_at": finished_at,
+            "exit_code": exit_code,
+        }),
+    );
+
+    if exit_code != 0 {
+        notifications::notify(
+            db,
+            Notification::new(
+                NotificationKind::TaskFailed,
+                NotificationLevel::Error,
+                format!("Task failed: {}", task.script_name),
+                format!(
+                    "{} exited with code {} at {} (task {}).",
+                    task.script_name, exit_code, finished_at, task.id
+                ),
+            ),
+        );
+    }
+}
+
+/// Registry holding the process of a running task, whoever started it
+pub fn active_task_registry(task_id: &str) -> Option<TaskRegistry> {
+    active_tasks().get(task_id).cloned()
+}
+
+/// Number of tasks running (across every caller)
+pub fn running_task_count() -> usize {
+    active_tasks().len()
+}
+
+/// Stop starting tasks and wait up to `timeout` for the running ones to finish,
+/// cancelling those that don't
+///
+/// Returns the number of tasks that were cancelled.
+pub async fn drain_tasks(timeout: Duration) -> usize {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    if wait_for_tasks(timeout).await {
+        return 0;
+    }
+
+    CANCELLING.store(true, Ordering::SeqCst);
+    let remaining: Vec<(String, TaskRegistry)> = active_tasks()
+        .iter()
+        .map(|(id, registry)| (id.clone(), registry.clone()))
+        .collect();
+    for (task_id, registry) in &remaining {
+        tracing::info!("Cancelling task {}", task_id);
+        if let Err(e) = cancel_task(task_id, registry).await {
+            tracing::warn!("Failed to cancel task {}: {}", task_id, e);
+        }
+    }
+    // Cancelled tasks record themselves as finished with exit code -1
+    if !wait_for_tasks(CANCEL_GRACE).await {
+        tracing::warn!(
+            "{} tasks did not finish after being cancelled",
+            running_task_count()
+        );
+    }
+    remaining.len()
+}
+
+/// Wait up to `timeout` for every task to finish; returns whether they did
+async fn wait_for_tasks(timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while running_task_count() > 0 {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    true
+}
+
+type ScriptLock = Arc<Mutex<()>>;
+
+/// Per-script locks shared by every caller (API, WebSocket, pipelines)
+static SCRIPT_LOCKS: OnceLock<std::sync::Mutex<HashMap<String, ScriptLock>>> = OnceLock::new();
+
+/// Get the lock guarding concurrent runs of a script
+fn script_lock(script_path: &str) -> ScriptLock {
+    let locks = SCRIPT_LOCKS.get_or_init(Default::default);
+    let mut locks = locks.lock().unwrap_or_else(|e| e.into_inner());
+    locks.entry(script_path.to_string()).or_default().clone()
+}
+
 /// Runs a script, monitors output, updates DB, and optionally streams events to a channel
+///
+/// Only one run of a given script is allowed at a time. If the script is busy, the
+/// call fails with [`ScriptBusy`] in `Reject` mode; in `Queue` mode a `queued`
+/// event is sent and the run starts once the previous one has finished.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_script_task(
     script_path: String,
     task_id: String,
     script_name: String,
     db: DbPool,
     registry: TaskRegistry,
-    event_sender: Option<tokio::sync::mpsc::UnboundedSender<TaskMessage>>,
+    limits: ResourceLimits,
+    concurrency: ConcurrencyMode,
+    env: HashMap<String, String>,
+    event_sender: Option<EventSender>,
 ) -> Result<()> {
+    let lock = script_lock(&script_path);
+    match lock.clone().try_lock_owned() {
+        Ok(guard) => {
+            start_script_task(
+                script_path,
+                task_id,
+                script_name,
+                db,
+                registry,
+                limits,
+                env,
+                guard,
+                event_sender,
+            )
+            .await
+        }
+        Err(_) if concurrency == ConcurrencyMode::Queue => {
+            if let Some(ref tx) = event_sender {
+                let _ = tx.send(TaskMessage::event("queued", &task_id));
+            }
+            tokio::spawn(async move {
+                let guard = lock.lock_owned().await;
+                let _ = start_script_task(
+                    script_path,
+                    task_id,
+                    script_name,
+                    db,
+                    registry,
+                    limits,
+                    env,
+                    guard,
+                    event_sender,
+                )
+                .await;
+            });
+            Ok(())
+        }
+        Err(_) => {
+            let busy = ScriptBusy(script_name);
+            if let Some(ref tx) = event_sender {
+                let _ = tx.send(TaskMessage::event("error", &task_id).with_data(&busy.to_string()));
+            }
+            Err(busy.into())
+        }
+    }
+}
+
+/// Start a script whose lock is held; the guard is released when the run finishes
+#[allow(clippy::too_many_arguments)]
+async fn start_script_task(
+    script_path: String,
+    task_id: String,
+    script_name: String,
+    db: DbPool,
+    registry: TaskRegistry,
+    limits: ResourceLimits,
+    env: HashMap<String, String>,
+    lock_guard: OwnedMutexGuard<()>,
+    event_sender: Option<EventSender>,
+) -> Result<()> {
+    if let Err(e) = track_task(&task_id, &registry) {
+        if let Some(ref tx) = event_sender {
+            let _ = tx.send(TaskMessage::event("error", &task_id).with_data(&e.to_string()));
+        }
+        return Err(e);
+    }
+
     // 1. Create task history entry
     let task_history = TaskHistory {
         id: task_id.clone(),
@@ -104,30 +717,22 @@ pub async fn run_script_task(
 
     // 2. Notify started
     if let Some(ref tx) = event_sender {
-        let _ = tx.send(TaskMessage {
-            r#type: "started".to_string(),
-            task_id: Some(task_id.clone()),
-            data: None,
-            code: None,
-        });
+        let _ = tx.send(TaskMessage::event("started", &task_id));
     }
 
     // 3. Execute script
-    let mut child = match execute_script(&script_path).await {
+    let mut child = match execute_script(&script_path, &limits, &env).await {
         Ok(c) => c,
         Err(e) => {
             let err_msg = format!("Failed to start script: {}", e);
             if let Some(ref tx) = event_sender {
-                let _ = tx.send(TaskMessage {
-                    r#type: "error".to_string(),
-                    task_id: Some(task_id.clone()),
-                    data: Some(err_msg.clone()),
-                    code: None,
-                });
+                let _ = tx.send(TaskMessage::event("error", &task_id).with_data(&err_msg));
             }
             // Update DB with failure
             let finished_at = Utc::now().to_rfc3339();
             let _ = db::update_task_history(&db, &task_id, &finished_at, -1, Some(&err_msg)).await;
+            untrack_task(&task_id);
+            notify_task_finished(&db, &task_history, &finished_at, -1);
             return Err(e);
         }
     };
@@ -140,94 +745,322 @@ pub async fn run_script_task(
     store_task(task_id.clone(), child, &registry).await;
 
     // 6. Spawn monitoring task
+    let max_output = max_output_bytes(&db).await;
     tokio::spawn(async move {
-        let mut stdout_reader = BufReader::new(stdout);
-        let mut stderr_reader = BufReader::new(stderr);
-        let mut output_buffer = String::new();
-        let mut stdout_line = String::new();
-        let mut stderr_line = String::new();
-        let mut stdout_done = false;
-        let mut stderr_done = false;
+        let mut capture = OutputCapture::new(&task_log_path(&task_id), max_output).await;
 
         // Stream output
-        while !stdout_done || !stderr_done {
-            tokio::select! {
-                result = stdout_reader.read_line(&mut stdout_line), if !stdout_done => {
-                    match result {
-                        Ok(0) => stdout_done = true,
-                        Ok(_) => {
-                            let line = stdout_line.clone();
-                            output_buffer.push_str(&line);
-                            if let Some(ref tx) = event_sender {
-                                let _ = tx.send(TaskMessage {
-                                    r#type: "stdout".to_string(),
-                                    task_id: Some(task_id.clone()),
-                                    data: Some(line.trim_end().to_string()),
-                                    code: None,
-                                });
-                            }
-                            stdout_line.clear();
-                        }
-                        Err(_) => stdout_done = true,
-                    }
-                }
-                result = stderr_reader.read_line(&mut stderr_line), if !stderr_done => {
-                    match result {
-                        Ok(0) => stderr_done = true,
-                        Ok(_) => {
-                            let line = stderr_line.clone();
-                            output_buffer.push_str(&line);
-                            if let Some(ref tx) = event_sender {
-                                let _ = tx.send(TaskMessage {
-                                    r#type: "stderr".to_string(),
-                                    task_id: Some(task_id.clone()),
-                                    data: Some(line.trim_end().to_string()),
-                                    code: None,
-                                });
-                            }
-                            stderr_line.clear();
-                        }
-                        Err(_) => stderr_done = true,
-                    }
-                }
-            }
-        }
+        stream_output(stdout, stderr, &mut capture, &task_id, &event_sender).await;
 
         // Wait for exit
-        let exit_code = if let Some(handle) = get_task(&task_id, &registry).await {
-            let mut child_opt = handle.lock().await;
-            if let Some(ref mut child) = *child_opt {
-                let status = child.wait().await;
-                status.ok().and_then(|s| s.code()).unwrap_or(-1)
-            } else {
-                -1
-            }
-        } else {
-            -1
-        };
+        let exit_code = wait_for_exit(&task_id, &registry).await.unwrap_or(-1);
 
         // Remove from registry
         remove_task(&task_id, &registry).await;
 
         // Update DB
         let finished_at = Utc::now().to_rfc3339();
+        let output_buffer = capture.finish().await;
         let output_str = if output_buffer.is_empty() {
             None
         } else {
             Some(output_buffer.as_str())
         };
         let _ = db::update_task_history(&db, &task_id, &finished_at, exit_code, output_str).await;
+        untrack_task(&task_id);
+        notify_task_finished(&db, &task_history, &finished_at, exit_code);
+
+        // Release the script lock before notifying, so a follow-up run can start
+        drop(lock_guard);
 
         // Notify exit
         if let Some(ref tx) = event_sender {
-            let _ = tx.send(TaskMessage {
-                r#type: "exit".to_string(),
-                task_id: Some(task_id),
-                data: None,
-                code: Some(exit_code),
-            });
+            let _ = tx.send(TaskMessage::event("exit", &task_id).with_code(exit_code));
         }
     });
 
     Ok(())
 }
+
+/// Runs a pipeline as a single task: steps execute in order with the pipeline's
+/// shared environment, and each step's status is recorded in `pipeline_step_runs`.
+///
+/// A failing step stops the pipeline unless it is marked `continue_on_error`;
+/// remaining steps are then recorded as `skipped`.
+pub async fn run_pipeline_task(
+    pipeline: Pipeline,
+    scripts_dir: String,
+    task_id: String,
+    db: DbPool,
+    registry: TaskRegistry,
+    limits: ResourceLimits,
+    event_sender: Option<EventSender>,
+) -> Result<()> {
+    if let Err(e) = track_task(&task_id, &registry) {
+        if let Some(ref tx) = event_sender {
+            let _ = tx.send(TaskMessage::event("error", &task_id).with_data(&e.to_string()));
+        }
+        return Err(e);
+    }
+
+    let task_history = TaskHistory {
+        id: task_id.clone(),
+        script_name: format!("pipeline:{}", pipeline.name),
+        started_at: Utc::now().to_rfc3339(),
+        finished_at: None,
+        exit_code: None,
+        output: None,
+    };
+
+    if let Err(e) = db::insert_task_history(&db, &task_history).await {
+        tracing::error!("Failed to insert task history: {}", e);
+    }
+
+    if let Some(ref tx) = event_sender {
+        let _ = tx.send(TaskMessage::event("started", &task_id).with_data(&pipeline.name));
+    }
+
+    let max_output = max_output_bytes(&db).await;
+    tokio::spawn(async move {
+        let mut capture = OutputCapture::new(&task_log_path(&task_id), max_output).await;
+        let total = pipeline.steps.len();
+        let mut exit_code = 0;
+        let mut stopped = false;
+
+        for (index, step) in pipeline.steps.iter().enumerate() {
+            let step_name = step.display_name().to_string();
+
+            // Steps not started yet are skipped when tasks are cancelled on shutdown
+            if !stopped && CANCELLING.load(Ordering::SeqCst) {
+                exit_code = -1;
+                stopped = true;
+            }
+            if stopped {
+                let _ = db::record_pipeline_step(&db, &task_id, index, &step_name, "skipped", None)
+                    .await;
+                continue;
+            }
+
+            let header = format!("==> [{}/{}] {}\n", index + 1, total, step_name);
+            let _ = capture.push(&header).await;
+            let _ =
+                db::record_pipeline_step(&db, &task_id, index, &step_name, "running", None).await;
+            if let Some(ref tx) = event_sender {
+                let _ = tx.send(
+                    TaskMessage::event("step_started", &task_id)
+                        .with_step(index)
+                        .with_data(&step_name),
+                );
+            }
+
+            let mut env = pipeline.env.clone();
+            env.insert("TORU_PIPELINE_ID".to_string(), pipeline.id.clone());
+            env.insert("TORU_PIPELINE_STEP".to_string(), index.to_string());
+
+            // Steps wait for any other run of the same script to finish
+            let script_path = format!("{}/{}", scripts_dir, step.script_path);
+            let _lock_guard = script_lock(&script_path).lock_owned().await;
+            let step_exit = match execute_script(&script_path, &limits, &env).await {
+                Ok(mut child) => {
+                    let stdout = child.stdout.take().expect("stdout not captured");
+                    let stderr = child.stderr.take().expect("stderr not captured");
+                    store_task(task_id.clone(), child, &registry).await;
+                    stream_output(stdout, stderr, &mut capture, &task_id, &event_sender).await;
+                    wait_for_exit(&task_id, &registry).await
+                }
+                Err(e) => {
+                    let err_msg = format!("Failed to start script: {}\n", e);
+                    let _ = capture.push(&err_msg).await;
+                    Some(-1)
+                }
+            };
+
+            let (status, code) = match step_exit {
+                None => ("cancelled", -1),
+                Some(0) => ("success", 0),
+                Some(code) => ("failed", code),
+            };
+            let _ = db::record_pipeline_step(&db, &task_id, index, &step_name, status, Some(code))
+                .await;
+            if let Some(ref tx) = event_sender {
+                let _ = tx.send(
+                    TaskMessage::event("step_finished", &task_id)
+                        .with_step(index)
+                        .with_data(status)
+                        .with_code(code),
+                );
+            }
+
+            if step_exit.is_none() {
+                exit_code = -1;
+                stopped = true;
+            } else if code != 0 && !step.continue_on_error {
+                exit_code = code;
+                stopped = true;
+            }
+        }
+
+        remove_task(&task_id, &registry).await;
+
+        let finished_at = Utc::now().to_rfc3339();
+        let output_buffer = capture.finish().await;
+        let output_str = if output_buffer.is_empty() {
+            None
+        } else {
+            Some(output_buffer.as_str())
+        };
+        let _ = db::update_task_history(&db, &task_id, &finished_at, exit_code, output_str).await;
+        untrack_task(&task_id);
+        notify_task_finished(&db, &task_history, &finished_at, exit_code);
+
+        if let Some(ref tx) = event_sender {
+            let _ = tx.send(TaskMessage::event("exit", &task_id).with_code(exit_code));
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_nofile_limit_applied_to_script() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script = temp_dir.path().join("ulimit.sh");
+        std::fs::write(&script, "ulimit -n\n").unwrap();
+
+        let limits = ResourceLimits {
+            nofile: Some(64),
+            ..Default::default()
+        };
+        let child = execute_script(script.to_str().unwrap(), &limits, &HashMap::new())
+            .await
+            .unwrap();
+        let output = child.wait_with_output().await.unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "64");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cpu_limit_leaves_hard_limit_headroom() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script = temp_dir.path().join("ulimit.sh");
+        std::fs::write(&script, "ulimit -St\nulimit -Ht\n").unwrap();
+
+        let limits = ResourceLimits {
+            cpu_secs: Some(10),
+            ..Default::default()
+        };
+        let child = execute_script(script.to_str().unwrap(), &limits, &HashMap::new())
+            .await
+            .unwrap();
+        let output = child.wait_with_output().await.unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("10\n{}\n", 10 + CPU_LIMIT_GRACE_SECS)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_output_capture_truncates_but_keeps_disk_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("tasks/test.log");
+        let mut capture = OutputCapture::new(&log_path, 10).await;
+
+        assert_eq!(capture.push("12345\n").await, CaptureResult::Captured);
+        assert_eq!(capture.push("67890\n").await, CaptureResult::Truncated);
+        assert_eq!(capture.push("more\n").await, CaptureResult::Dropped);
+
+        let captured = capture.finish().await;
+        assert_eq!(captured, format!("12345\n{}\n", OUTPUT_TRUNCATED_MARKER));
+
+        let on_disk = std::fs::read_to_string(&log_path).unwrap();
+        assert_eq!(on_disk, "12345\n67890\nmore\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_capped_line() {
+        let data = [vec![b'x'; MAX_LINE_BYTES + 10], b"\nnext\n".to_vec()].concat();
+        let mut reader = BufReader::new(&data[..]);
+        let mut line = Vec::new();
+
+        assert_eq!(
+            read_capped_line(&mut reader, &mut line).await.unwrap(),
+            MAX_LINE_BYTES
+        );
+        line.clear();
+        read_capped_line(&mut reader, &mut line).await.unwrap();
+        assert_eq!(line, [vec![b'x'; 10], b"\n".to_vec()].concat());
+        line.clear();
+        read_capped_line(&mut reader, &mut line).await.unwrap();
+        assert_eq!(line, b"next\n");
+        line.clear();
+        assert_eq!(read_capped_line(&mut reader, &mut line).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_script_command_picks_interpreter() {
+        let program = |path: &str| {
+            script_command(path)
+                .as_std()
+                .get_program()
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        assert_eq!(program("./scripts/backup.sh"), "sh");
+        assert_eq!(program("./scripts/backup.bash"), "bash");
+        assert_eq!(program("./scripts/backup.BAT"), "cmd");
+        assert_eq!(program("./scripts/backup.ps1"), POWERSHELL);
+
+        let cmd = script_command("./scripts/backup.cmd");
+        let args: Vec<_> = cmd.as_std().get_args().collect();
+        assert_eq!(args, ["/C", ".\\scripts\\backup.cmd"]);
+    }
+
+    #[tokio::test]
+    async fn test_script_lock_is_shared_per_path() {
+        let guard = script_lock("/tmp/locked.sh").try_lock_owned().unwrap();
+        assert!(script_lock("/tmp/locked.sh").try_lock_owned().is_err());
+        assert!(script_lock("/tmp/other.sh").try_lock_owned().is_ok());
+
+        drop(guard);
+        assert!(script_lock("/tmp/locked.sh").try_lock_owned().is_ok());
+    }
+
+    #[test]
+    fn test_action_overrides_global_limits() {
+        let global = ResourceLimits {
+            cpu_secs: Some(60),
+            memory_mb: Some(512),
+            nofile: None,
+        };
+        let action = QuickAction {
+            id: "a".to_string(),
+            name: "Backup".to_string(),
+            script_path: "backup.sh".to_string(),
+            icon: None,
+            display_order: 0,
+            cpu_limit_secs: Some(300),
+            memory_limit_mb: None,
+            nofile_limit: Some(0), // non-positive values do not override
+            concurrency: ConcurrencyMode::Reject,
+            env: HashMap::new(),
+            params: Vec::new(),
+            visibility: Default::default(),
+            allowed_users: Vec::new(),
+            danger_level: Default::default(),
+            requires_confirmation: false,
+        };
+
+        let merged = global.with_action_overrides(&action);
+        assert_eq!(merged.cpu_secs, Some(300));
+        assert_eq!(merged.memory_mb, Some(512));
+        assert_eq!(merged.nofile, None);
+    }
+}
@@ -0,0 +1,501 @@
+//! File manager for directories an admin opened up
+//!
+//! The `file_manager_roots` setting is a JSON array of roots, like
+//! `[{"name": "backups", "path": "/var/backups/toru"}, {"name": "config",
+//! "path": "/etc/toru", "read_only": true}]`. Paths are given relative to a
+//! root and every one is checked to stay inside it, symlinks included. Nothing
+//! is reachable until roots are configured.
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+use crate::db::{self, DbPool};
+
+/// Setting with the roots, as a JSON array of `{name, path, read_only}`
+pub const ROOTS_SETTING: &str = "file_manager_roots";
+
+/// Setting with the largest upload accepted, in MB
+pub const MAX_UPLOAD_SETTING: &str = "file_manager_max_upload_mb";
+
+const DEFAULT_MAX_UPLOAD_MB: u64 = 100;
+
+/// Most entries returned when listing a directory
+pub const MAX_LIST_ENTRIES: usize = 5000;
+
+/// Errors of file manager operations
+#[derive(Debug)]
+pub enum FileError {
+    /// The request is malformed, e.g. a path leaving its root
+    Invalid(String),
+    NotFound(String),
+    /// The root is read-only, or the path leads outside it
+    Forbidden(String),
+    /// The target already exists
+    Conflict(String),
+    TooLarge(u64),
+    Failed(anyhow::Error),
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileError::Invalid(e) | FileError::Forbidden(e) => write!(f, "{}", e),
+            FileError::NotFound(path) => write!(f, "{} not found", path),
+            FileError::Conflict(path) => write!(f, "{} already exists", path),
+            FileError::TooLarge(max) => write!(f, "Upload exceeds {} MB", max),
+            FileError::Failed(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+impl From<std::io::Error> for FileError {
+    fn from(e: std::io::Error) -> Self {
+        FileError::Failed(e.into())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileRoot {
+    pub name: String,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileEntry {
+    pub name: String,
+    /// `file`, `dir`, `symlink` or `other`
+    pub kind: &'static str,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirListing {
+    pub root: String,
+    pub path: String,
+    pub entries: Vec<FileEntry>,
+    /// More than `MAX_LIST_ENTRIES` entries; the rest were left out
+    pub truncated: bool,
+}
+
+/// The configured roots
+///
+/// Roots with an invalid name or a relative path are skipped with a warning.
+pub async fn load_roots(db: &DbPool) -> Result<Vec<FileRoot>, FileError> {
+    let Some(value) = db::get_setting(db, ROOTS_SETTING)
+        .await
+        .map_err(FileError::Failed)?
+    else {
+        return Ok(Vec::new());
+    };
+    if value.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let roots: Vec<FileRoot> = serde_json::from_str(&value)
+        .map_err(|e| FileError::Failed(anyhow::anyhow!("Invalid {}: {}", ROOTS_SETTING, e)))?;
+    Ok(roots
+        .into_iter()
+        .filter(|root| {
+            let valid = is_valid_root_name(&root.name) && root.path.is_absolute();
+            if !valid {
+                tracing::warn!("Ignoring file manager root {:?}", root.name);
+            }
+            valid
+        })
+        .collect())
+}
+
+fn is_valid_root_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// A configured root by name
+pub async fn find_root(db: &DbPool, name: &str) -> Result<FileRoot, FileError> {
+    load_roots(db)
+        .await?
+        .into_iter()
+        .find(|root| root.name == name)
+        .ok_or_else(|| FileError::NotFound(format!("Root {}", name)))
+}
+
+/// Largest upload accepted, in bytes
+pub async fn max_upload_bytes(db: &DbPool) -> u64 {
+    db::get_setting(db, MAX_UPLOAD_SETTING)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_UPLOAD_MB)
+        * 1024
+        * 1024
+}
+
+/// The parts of a root-relative path; `..`, and anything else leaving the root, is refused
+fn relative_path(path: &str) -> Result<PathBuf, FileError> {
+    let mut relative = PathBuf::new();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => return Err(FileError::Invalid(format!("Path {} leaves its root", path))),
+        }
+    }
+    Ok(relative)
+}
+
+impl FileRoot {
+    async fn canonical_path(&self) -> Result<PathBuf, FileError> {
+        tokio::fs::canonicalize(&self.path).await.map_err(|e| {
+            FileError::Failed(anyhow::anyhow!(
+                "Root {} ({}) is unavailable: {}",
+                self.name,
+                self.path.display(),
+                e
+            ))
+        })
+    }
+
+    fn check_writable(&self) -> Result<(), FileError> {
+        if self.read_only {
+            return Err(FileError::Forbidden(format!(
+                "Root {} is read-only",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// An existing path inside the root, with symlinks followed
+    async fn resolve_existing(&self, path: &str) -> Result<PathBuf, FileError> {
+        let root = self.canonical_path().await?;
+        let full = tokio::fs::canonicalize(root.join(relative_path(path)?))
+            .await
+            .map_err(|_| FileError::NotFound(path.to_string()))?;
+        if !full.starts_with(&root) {
+            return Err(FileError::Forbidden(format!(
+                "{} leads outside root {}",
+                path, self.name
+            )));
+        }
+        Ok(full)
+    }
+
+    /// An entry inside the root, which may not exist, without following it if it's a symlink
+    ///
+    /// Its directory must exist inside the root. The root itself is refused.
+    async fn resolve_entry(&self, path: &str) -> Result<PathBuf, FileError> {
+        let relative = relative_path(path)?;
+        let (Some(parent), Some(name)) = (relative.parent(), relative.file_name()) else {
+            return Err(FileError::Invalid(
+                "The root itself can't be changed".into(),
+            ));
+        };
+        let parent = self.resolve_existing(&parent.to_string_lossy()).await?;
+        Ok(parent.join(name))
+    }
+}
+
+/// Entries of a directory, directories first
+pub async fn list_dir(root: &FileRoot, path: &str) -> Result<DirListing, FileError> {
+    let dir = root.resolve_existing(path).await?;
+    if !tokio::fs::metadata(&dir).await?.is_dir() {
+        return Err(FileError::Invalid(format!("{} is not a directory", path)));
+    }
+
+    let mut entries = Vec::new();
+    let mut truncated = false;
+    let mut read_dir = tokio::fs::read_dir(&dir).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        if entries.len() == MAX_LIST_ENTRIES {
+            truncated = true;
+            break;
+        }
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        let kind = if metadata.is_symlink() {
+            "symlink"
+        } else if metadata.is_dir() {
+            "dir"
+        } else if metadata.is_file() {
+            "file"
+        } else {
+            "other"
+        };
+        entries.push(FileEntry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            kind,
+            size: metadata.len(),
+            modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+        });
+    }
+    entries.sort_by(|a, b| (a.kind != "dir", &a.name).cmp(&(b.kind != "dir", &b.name)));
+
+    Ok(DirListing {
+        root: root.name.clone(),
+        path: relative_path(path)?.to_string_lossy().into_owned(),
+        entries,
+        truncated,
+    })
+}
+
+/// Open a file for download; returns it with its size and name
+pub async fn open_file(
+    root: &FileRoot,
+    path: &str,
+) -> Result<(tokio::fs::File, u64, String), FileError> {
+    let full = root.resolve_existing(path).await?;
+    let metadata = tokio::fs::metadata(&full).await?;
+    if !metadata.is_file() {
+        return Err(FileError::Invalid(format!("{} is not a file", path)));
+    }
+    let name = full
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok((tokio::fs::File::open(&full).await?, metadata.len(), name))
+}
+
+/// Write an uploaded file, refusing it once it grows past `max_bytes`
+///
+/// The upload goes to a temporary file next to the target, which replaces the
+/// target only once complete. Returns the size written.
+pub async fn upload<S, B, E>(
+    root: &FileRoot,
+    path: &str,
+    overwrite: bool,
+    max_bytes: u64,
+    mut body: S,
+) -> Result<u64, FileError>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    root.check_writable()?;
+    let target = root.resolve_entry(path).await?;
+    match tokio::fs::symlink_metadata(&target).await {
+        Ok(metadata) if metadata.is_dir() => {
+            return Err(FileError::Conflict(format!("Directory {}", path)))
+        }
+        Ok(_) if !overwrite => return Err(FileError::Conflict(path.to_string())),
+        _ => {}
+    }
+
+    let temp = target.with_file_name(format!(
+        ".{}.upload-{}",
+        target
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        uuid::Uuid::new_v4()
+    ));
+    let result = async {
+        let mut file = tokio::fs::File::create(&temp).await?;
+        let mut written = 0u64;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk
+                .map_err(|e| FileError::Failed(anyhow::anyhow!("Upload interrupted: {}", e)))?;
+            let chunk = chunk.as_ref();
+            written += chunk.len() as u64;
+            if written > max_bytes {
+                return Err(FileError::TooLarge(max_bytes / (1024 * 1024)));
+            }
+            file.write_all(chunk).await?;
+        }
+        file.sync_all().await?;
+        tokio::fs::rename(&temp, &target).await?;
+        Ok(written)
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp).await;
+    }
+    result
+}
+
+/// Rename or move an entry within its root; the target must not exist
+pub async fn rename(root: &FileRoot, from: &str, to: &str) -> Result<(), FileError> {
+    root.check_writable()?;
+    let source = root.resolve_entry(from).await?;
+    if tokio::fs::symlink_metadata(&source).await.is_err() {
+        return Err(FileError::NotFound(from.to_string()));
+    }
+    let target = root.resolve_entry(to).await?;
+    if tokio::fs::symlink_metadata(&target).await.is_ok() {
+        return Err(FileError::Conflict(to.to_string()));
+    }
+    if target.starts_with(&source) {
+        return Err(FileError::Invalid(format!(
+            "{} can't be moved into itself",
+            from
+        )));
+    }
+    tokio::fs::rename(&source, &target).await?;
+    Ok(())
+}
+
+/// Delete a file, a symlink or an empty directory; non-empty directories need `recursive`
+pub async fn delete(root: &FileRoot, path: &str, recursive: bool) -> Result<(), FileError> {
+    root.check_writable()?;
+    let target = root.resolve_entry(path).await?;
+    let metadata = tokio::fs::symlink_metadata(&target)
+        .await
+        .map_err(|_| FileError::NotFound(path.to_string()))?;
+    if !metadata.is_dir() {
+        tokio::fs::remove_file(&target).await?;
+    } else if recursive {
+        tokio::fs::remove_dir_all(&target).await?;
+    } else {
+        tokio::fs::remove_dir(&target).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::DirectoryNotEmpty {
+                FileError::Conflict(format!("Non-empty directory {}", path))
+            } else {
+                e.into()
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// Record a file manager operation in the audit log
+pub async fn audit(
+    db: &DbPool,
+    username: &str,
+    action: &str,
+    root: &str,
+    path: &str,
+    result: Result<Option<String>, &FileError>,
+) {
+    let (success, details) = match result {
+        Ok(details) => (true, details),
+        Err(e) => (false, Some(e.to_string())),
+    };
+    let entry = db::FileAuditEntry {
+        id: 0,
+        username: username.to_string(),
+        action: action.to_string(),
+        root: root.to_string(),
+        path: path.to_string(),
+        success,
+        details,
+        timestamp: Utc::now().to_rfc3339(),
+    };
+    if let Err(e) = db::record_file_audit(db, &entry).await {
+        tracing::warn!("Failed to write file manager audit log: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(path: &Path, read_only: bool) -> FileRoot {
+        FileRoot {
+            name: "test".to_string(),
+            path: path.to_path_buf(),
+            read_only,
+        }
+    }
+
+    fn body(data: &'static [u8]) -> impl Stream<Item = Result<&'static [u8], String>> + Unpin {
+        futures::stream::iter(vec![Ok(data)])
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(relative_path("/a/./b").unwrap(), PathBuf::from("a/b"));
+        assert_eq!(relative_path("").unwrap(), PathBuf::new());
+        assert!(relative_path("a/../../etc").is_err());
+        assert!(relative_path("..").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stays_inside_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "x").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("escape")).unwrap();
+        let root = root(dir.path(), false);
+
+        assert!(matches!(
+            root.resolve_existing("escape/secret").await,
+            Err(FileError::Forbidden(_))
+        ));
+        assert!(matches!(
+            upload(&root, "escape/new", false, 100, body(b"x")).await,
+            Err(FileError::Forbidden(_))
+        ));
+        assert!(matches!(
+            delete(&root, "", true).await,
+            Err(FileError::Invalid(_))
+        ));
+
+        // The link itself can go; its target stays
+        delete(&root, "escape", false).await.unwrap();
+        assert!(outside.path().join("secret").exists());
+    }
+
+    #[tokio::test]
+    async fn test_file_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let root = root(dir.path(), false);
+
+        assert_eq!(
+            upload(&root, "sub/a.txt", false, 100, body(b"hello"))
+                .await
+                .unwrap(),
+            5
+        );
+        assert!(matches!(
+            upload(&root, "sub/a.txt", false, 100, body(b"again")).await,
+            Err(FileError::Conflict(_))
+        ));
+        assert!(matches!(
+            upload(&root, "big.txt", false, 3, body(b"hello")).await,
+            Err(FileError::TooLarge(_))
+        ));
+        assert!(!dir.path().join("big.txt").exists());
+
+        let listing = list_dir(&root, "/").await.unwrap();
+        let names: Vec<_> = listing.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["sub"]);
+        assert_eq!(list_dir(&root, "sub").await.unwrap().entries[0].size, 5);
+
+        rename(&root, "sub/a.txt", "b.txt").await.unwrap();
+        assert_eq!(std::fs::read(dir.path().join("b.txt")).unwrap(), b"hello");
+        assert!(matches!(
+            rename(&root, "sub", "sub/inner").await,
+            Err(FileError::Invalid(_))
+        ));
+
+        std::fs::write(dir.path().join("sub/c.txt"), "c").unwrap();
+        assert!(matches!(
+            delete(&root, "sub", false).await,
+            Err(FileError::Conflict(_))
+        ));
+        delete(&root, "sub", true).await.unwrap();
+        delete(&root, "b.txt", false).await.unwrap();
+        assert!(list_dir(&root, "").await.unwrap().entries.is_empty());
+
+        let read_only = FileRoot {
+            read_only: true,
+            ..root
+        };
+        assert!(matches!(
+            upload(&read_only, "x", false, 100, body(b"x")).await,
+            Err(FileError::Forbidden(_))
+        ));
+    }
+}
@@ -0,0 +1,196 @@
+//! Component checks behind the liveness and readiness probes
+//!
+//! Each check reports `ok`, `degraded` (working, but needs attention) or
+//! `failing`. The server is ready unless a component is failing.
+
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::db::{self, DbPool};
+
+/// Setting with the free space (in MB) the log directory needs for the server to be ready
+pub const MIN_FREE_DISK_SETTING: &str = "health_min_free_disk_mb";
+
+const DEFAULT_MIN_FREE_DISK_MB: u64 = 100;
+
+/// How long a component check may take before it counts as failing
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+    Failing,
+}
+
+/// Result of checking one component
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ComponentHealth {
+    pub fn new(name: &str, status: HealthStatus) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            message: None,
+            details: None,
+        }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+/// Status of the server and of each component checked
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    pub status: HealthStatus,
+    pub components: Vec<ComponentHealth>,
+}
+
+impl Readiness {
+    /// Overall status: the worst of the components
+    pub fn new(components: Vec<ComponentHealth>) -> Self {
+        let status = components
+            .iter()
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(HealthStatus::Ok);
+        Self { status, components }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.status != HealthStatus::Failing
+    }
+}
+
+/// Whether the database answers a query
+pub async fn check_database(db: &DbPool) -> ComponentHealth {
+    let query = async {
+        let conn = db.get().await?;
+        conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))?;
+        anyhow::Ok(())
+    };
+    match tokio::time::timeout(CHECK_TIMEOUT, query).await {
+        Ok(Ok(())) => ComponentHealth::new("database", HealthStatus::Ok),
+        Ok(Err(e)) => ComponentHealth::new("database", HealthStatus::Failing)
+            .with_message(format!("Query failed: {}", e)),
+        Err(_) => ComponentHealth::new("database", HealthStatus::Failing)
+            .with_message(format!("No answer within {:?}", CHECK_TIMEOUT)),
+    }
+}
+
+/// Whether the disk holding the logs has the free space the `health_min_free_disk_mb` setting asks for
+pub async fn check_log_disk(db: &DbPool, log_dir: &Path) -> ComponentHealth {
+    let min_free_mb = db::get_setting(db, MIN_FREE_DISK_SETTING)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_MIN_FREE_DISK_MB);
+    disk_health(log_dir, min_free_mb)
+}
+
+fn disk_health(log_dir: &Path, min_free_mb: u64) -> ComponentHealth {
+    let free = match free_space(log_dir) {
+        Ok(free) => free,
+        Err(e) => {
+            let message = format!("Failed to read free space of {}: {}", log_dir.display(), e);
+            return ComponentHealth::new("log_disk", HealthStatus::Failing).with_message(message);
+        }
+    };
+    let free_mb = free / (1024 * 1024);
+    let details = serde_json::json!({
+        "path": log_dir,
+        "free_mb": free_mb,
+        "min_free_mb": min_free_mb,
+    });
+    if free_mb < min_free_mb {
+        ComponentHealth::new("log_disk", HealthStatus::Failing)
+            .with_message(format!("Only {} MB free", free_mb))
+            .with_details(details)
+    } else {
+        ComponentHealth::new("log_disk", HealthStatus::Ok).with_details(details)
+    }
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`
+#[cfg(unix)]
+fn free_space(path: &Path) -> std::io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_path: &Path) -> std::io::Result<u64> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Free space is only read on Unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_takes_worst_status() {
+        let readiness = Readiness::new(vec![
+            ComponentHealth::new("a", HealthStatus::Ok),
+            ComponentHealth::new("b", HealthStatus::Degraded),
+        ]);
+        assert_eq!(readiness.status, HealthStatus::Degraded);
+        assert!(readiness.is_ready());
+
+        let readiness = Readiness::new(vec![
+            ComponentHealth::new("a", HealthStatus::Failing),
+            ComponentHealth::new("b", HealthStatus::Degraded),
+        ]);
+        assert_eq!(readiness.status, HealthStatus::Failing);
+        assert!(!readiness.is_ready());
+    }
+
+    #[test]
+    fn test_disk_health() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(disk_health(dir.path(), 0).status, HealthStatus::Ok);
+        assert_eq!(
+            disk_health(dir.path(), u64::MAX).status,
+            HealthStatus::Failing
+        );
+        assert_eq!(
+            disk_health(&dir.path().join("missing"), 0).status,
+            HealthStatus::Failing
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        assert_eq!(check_database(&pool).await.status, HealthStatus::Ok);
+    }
+}
@@ -0,0 +1,150 @@
+//! Users logged in to the host, as `who` shows them
+//!
+//! Read from the utmp database on Linux: local terminals, SSH sessions and the
+//! like. These are sessions on the machine itself, not Steering Center logins.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HostSession {
+    pub user: String,
+    /// Terminal, e.g. `pts/0` or `tty1`
+    pub terminal: String,
+    /// Where the session comes from, as recorded by the login program (often a
+    /// hostname or IP for SSH, or an X display)
+    pub host: Option<String>,
+    /// Remote address, when recorded
+    pub ip: Option<String>,
+    pub pid: u32,
+    pub login_time: Option<DateTime<Utc>>,
+    pub duration_seconds: Option<u64>,
+    /// Time since the terminal last received input
+    pub idle_seconds: Option<u64>,
+}
+
+/// Sessions in the utmp database, oldest first
+#[cfg(target_os = "linux")]
+pub fn list_host_sessions() -> Result<Vec<HostSession>> {
+    use std::sync::Mutex;
+
+    // getutxent iterates over a process-wide cursor
+    static UTMP_LOCK: Mutex<()> = Mutex::new(());
+    let _guard = UTMP_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let now = Utc::now();
+    let mut sessions = Vec::new();
+    unsafe {
+        libc::setutxent();
+        loop {
+            let entry = libc::getutxent();
+            if entry.is_null() {
+                break;
+            }
+            let entry = &*entry;
+            if entry.ut_type != libc::USER_PROCESS {
+                continue;
+            }
+
+            let terminal = c_chars_to_string(&entry.ut_line);
+            let login_time = DateTime::from_timestamp(entry.ut_tv.tv_sec as i64, 0);
+            sessions.push(HostSession {
+                user: c_chars_to_string(&entry.ut_user),
+                host: Some(c_chars_to_string(&entry.ut_host)).filter(|host| !host.is_empty()),
+                ip: utmp_address(entry.ut_addr_v6).map(|ip| ip.to_string()),
+                pid: entry.ut_pid as u32,
+                login_time,
+                duration_seconds: login_time.map(|login| (now - login).num_seconds().max(0) as u64),
+                idle_seconds: idle_seconds(&terminal),
+                terminal,
+            });
+        }
+        libc::endutxent();
+    }
+
+    sessions.sort_by_key(|session| session.login_time);
+    Ok(sessions)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn list_host_sessions() -> Result<Vec<HostSession>> {
+    Err(anyhow::anyhow!(
+        "Listing login sessions is only supported on Linux"
+    ))
+}
+
+/// A fixed-size, NUL-padded utmp field as a string
+fn c_chars_to_string(chars: &[libc::c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// The `ut_addr_v6` field: an IPv4 address in the first word, or an IPv6 address,
+/// in network byte order
+fn utmp_address(words: [i32; 4]) -> Option<IpAddr> {
+    if words == [0; 4] {
+        return None;
+    }
+    if words[1..] == [0; 3] {
+        return Some(IpAddr::V4(Ipv4Addr::from(words[0].to_ne_bytes())));
+    }
+    let mut bytes = [0u8; 16];
+    for (chunk, word) in bytes.chunks_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    Some(IpAddr::V6(Ipv6Addr::from(bytes)))
+}
+
+/// Seconds since the terminal device was last read from
+#[cfg(target_os = "linux")]
+fn idle_seconds(terminal: &str) -> Option<u64> {
+    if terminal.is_empty() || terminal.contains("..") {
+        return None;
+    }
+    let accessed = std::fs::metadata(format!("/dev/{}", terminal))
+        .and_then(|metadata| metadata.accessed())
+        .ok()?;
+    Some(accessed.elapsed().map(|idle| idle.as_secs()).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_chars_to_string() {
+        let mut field = [0 as libc::c_char; 8];
+        for (c, b) in field.iter_mut().zip(b"pts/0") {
+            *c = *b as libc::c_char;
+        }
+        assert_eq!(c_chars_to_string(&field), "pts/0");
+    }
+
+    #[test]
+    fn test_utmp_address() {
+        assert_eq!(utmp_address([0; 4]), None);
+
+        let v4 = i32::from_ne_bytes([192, 168, 1, 20]);
+        assert_eq!(
+            utmp_address([v4, 0, 0, 0]),
+            Some("192.168.1.20".parse().unwrap())
+        );
+
+        let v6: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let octets = v6.octets();
+        let words: Vec<i32> = octets
+            .chunks(4)
+            .map(|chunk| i32::from_ne_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(
+            utmp_address(words.try_into().unwrap()),
+            Some(IpAddr::V6(v6))
+        );
+    }
+}
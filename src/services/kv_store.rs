@@ -1,9 +1,11 @@
-use crate::db::DbPool;
-use toru_plugin_api::{PluginError, PluginResult};
+use crate::db::{DbPool, PluginKvQuota};
+use std::time::Duration;
+use toru_plugin_api::{KvPage, PluginError, PluginResult};
 
 /// Sqlite-backed key-value store for plugins
 ///
-/// Each plugin gets its own isolated namespace in the plugin_kv table.
+/// Each plugin gets its own isolated namespace in the plugin_kv table,
+/// limited by a [`PluginKvQuota`].
 /// This implements the PluginKvStore trait from toru-plugin-api.
 // TODO: Integrate KV store into plugin API when plugins need persistent storage
 #[allow(dead_code)]
@@ -11,6 +13,7 @@ use toru_plugin_api::{PluginError, PluginResult};
 pub struct SqliteKvStore {
     pool: DbPool,
     plugin_id: String,
+    quota: PluginKvQuota,
 }
 
 #[allow(dead_code)]
@@ -21,7 +24,17 @@ impl SqliteKvStore {
     /// * `pool` - Database connection pool
     /// * `plugin_id` - Plugin ID for namespace isolation
     pub fn new(pool: DbPool, plugin_id: String) -> Self {
-        Self { pool, plugin_id }
+        Self {
+            pool,
+            plugin_id,
+            quota: PluginKvQuota::default(),
+        }
+    }
+
+    /// Limit the namespace to `quota` instead of the default quota
+    pub fn with_quota(mut self, quota: PluginKvQuota) -> Self {
+        self.quota = quota;
+        self
     }
 
     /// Get the plugin ID
@@ -51,9 +64,77 @@ impl toru_plugin_api::PluginKvStore for SqliteKvStore {
     /// * `key` - Key to set
     /// * `value` - Value to store
     async fn set(&self, key: &str, value: &str) -> PluginResult<()> {
-        crate::db::plugin_kv_set(&self.pool, &self.plugin_id, key, value)
+        self.set_with_ttl(key, value, None).await
+    }
+
+    /// Set a value that expires after `ttl`
+    ///
+    /// Expired keys read as absent and are purged by the daily cleanup.
+    async fn set_with_ttl(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: Option<Duration>,
+    ) -> PluginResult<()> {
+        let ttl_secs = ttl.map(|ttl| ttl.as_secs());
+        crate::db::plugin_kv_set(
+            &self.pool,
+            &self.plugin_id,
+            key,
+            value,
+            ttl_secs,
+            self.quota,
+        )
+        .await
+        .map_err(|e| PluginError::Internal(format!("Failed to set value: {}", e)))
+    }
+
+    /// List keys of the plugin's KV namespace
+    ///
+    /// # Arguments
+    /// * `prefix` - Only keys starting with this
+    /// * `cursor` - `next_cursor` of the previous page
+    /// * `limit` - Page size
+    async fn list(&self, prefix: &str, cursor: Option<&str>, limit: usize) -> PluginResult<KvPage> {
+        crate::db::plugin_kv_list_keys(&self.pool, &self.plugin_id, prefix, cursor, limit + 1)
+            .await
+            .map(|keys| KvPage::from_keys(keys, limit))
+            .map_err(|e| PluginError::Internal(format!("Failed to list keys: {}", e)))
+    }
+
+    /// Atomically add to an integer in the plugin's KV namespace
+    ///
+    /// # Arguments
+    /// * `key` - Key of the counter (absent counts as 0)
+    /// * `by` - Amount to add
+    async fn incr(&self, key: &str, by: i64) -> PluginResult<i64> {
+        crate::db::plugin_kv_incr(&self.pool, &self.plugin_id, key, by, self.quota)
             .await
-            .map_err(|e| PluginError::Internal(format!("Failed to set value: {}", e)))
+            .map_err(|e| PluginError::Internal(format!("Failed to increment value: {}", e)))
+    }
+
+    /// Atomically replace a value in the plugin's KV namespace
+    ///
+    /// # Arguments
+    /// * `key` - Key to swap
+    /// * `expected` - Value the key must hold, or `None` if it must be absent
+    /// * `new` - Value to store
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&str>,
+        new: &str,
+    ) -> PluginResult<bool> {
+        crate::db::plugin_kv_compare_and_swap(
+            &self.pool,
+            &self.plugin_id,
+            key,
+            expected,
+            new,
+            self.quota,
+        )
+        .await
+        .map_err(|e| PluginError::Internal(format!("Failed to swap value: {}", e)))
     }
 
     /// Delete a value from the plugin's KV namespace
@@ -119,4 +200,128 @@ mod tests {
             Some("value-b".to_string())
         );
     }
+
+    #[tokio::test]
+    async fn test_kv_store_expiry() {
+        let pool = crate::db::init_db().unwrap();
+        let kv = SqliteKvStore::new(pool.clone(), "test-plugin-ttl".to_string());
+
+        kv.set_with_ttl("cached", "stale", Some(Duration::ZERO))
+            .await
+            .unwrap();
+        kv.set_with_ttl("fresh", "value", Some(Duration::from_secs(3600)))
+            .await
+            .unwrap();
+        assert_eq!(kv.get("cached").await.unwrap(), None);
+        assert_eq!(kv.get("fresh").await.unwrap(), Some("value".to_string()));
+
+        // Cleanup purges the expired row but keeps live ones
+        crate::db::cleanup_expired_plugin_kv(&pool).await.unwrap();
+        let keys: Vec<_> = crate::db::plugin_kv_get_all(&pool, "test-plugin-ttl")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect();
+        assert_eq!(keys, ["fresh"]);
+        let conn = pool.get().await.unwrap();
+        let rows: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM plugin_kv WHERE plugin_id = 'test-plugin-ttl'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+
+    #[tokio::test]
+    async fn test_kv_store_list() {
+        let pool = crate::db::init_db().unwrap();
+        let kv = SqliteKvStore::new(pool.clone(), "test-plugin-list".to_string());
+        for key in ["cache:b", "cache:a", "cache:c", "setting"] {
+            kv.set(key, "1").await.unwrap();
+        }
+        kv.set_with_ttl("cache:old", "1", Some(Duration::ZERO))
+            .await
+            .unwrap();
+        // Other plugins' keys stay out of the listing
+        SqliteKvStore::new(pool, "test-plugin-other".to_string())
+            .set("cache:x", "1")
+            .await
+            .unwrap();
+
+        let first = kv.list("cache:", None, 2).await.unwrap();
+        assert_eq!(first.keys, ["cache:a", "cache:b"]);
+        let rest = kv
+            .list("cache:", first.next_cursor.as_deref(), 2)
+            .await
+            .unwrap();
+        assert_eq!(rest.keys, ["cache:c"]);
+        assert_eq!(rest.next_cursor, None);
+
+        let all = kv.list("", None, 10).await.unwrap();
+        assert_eq!(all.keys.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_kv_store_atomic_ops() {
+        let pool = crate::db::init_db().unwrap();
+        let kv = SqliteKvStore::new(pool, "test-plugin-atomic".to_string());
+        kv.delete("hits").await.unwrap();
+        kv.delete("lock").await.unwrap();
+
+        // Concurrent increments are never lost
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let kv = kv.clone();
+                tokio::spawn(async move { kv.incr("hits", 1).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        assert_eq!(kv.get("hits").await.unwrap(), Some("10".to_string()));
+        assert_eq!(kv.incr("hits", -3).await.unwrap(), 7);
+
+        kv.set("name", "toru").await.unwrap();
+        assert!(kv.incr("name", 1).await.is_err());
+
+        assert!(kv.compare_and_swap("lock", None, "a").await.unwrap());
+        assert!(!kv.compare_and_swap("lock", None, "b").await.unwrap());
+        assert!(!kv.compare_and_swap("lock", Some("b"), "c").await.unwrap());
+        assert!(kv.compare_and_swap("lock", Some("a"), "c").await.unwrap());
+        assert_eq!(kv.get("lock").await.unwrap(), Some("c".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_kv_store_quota() {
+        let pool = crate::db::init_db().unwrap();
+        for key in ["a", "b", "c"] {
+            crate::db::plugin_kv_delete(&pool, "test-plugin-quota", key)
+                .await
+                .unwrap();
+        }
+        let quota = PluginKvQuota {
+            max_bytes: Some(20),
+            max_keys: Some(2),
+        };
+        let kv =
+            SqliteKvStore::new(pool.clone(), "test-plugin-quota".to_string()).with_quota(quota);
+
+        kv.set("a", "123456789").await.unwrap();
+        kv.set("b", "1").await.unwrap();
+        // Replacing a key doesn't count it twice
+        kv.set("a", "12345678").await.unwrap();
+
+        let err = kv.set("c", "1").await.unwrap_err();
+        assert!(err.to_string().contains("quota"), "{}", err);
+        assert!(kv.set("b", "1234567890123").await.is_err());
+        assert!(kv.incr("c", 1).await.is_err());
+
+        let usage = crate::db::plugin_kv_usage(&pool, "test-plugin-quota")
+            .await
+            .unwrap();
+        assert_eq!(usage, crate::db::PluginKvUsage { keys: 2, bytes: 11 });
+    }
 }
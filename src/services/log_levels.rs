@@ -0,0 +1,143 @@
+//! Changing what gets logged without a restart
+//!
+//! The server's tracing filter, in `RUST_LOG` syntax, and a minimum level per
+//! plugin are kept in the `log_filter` and `log_plugin_levels` settings. They
+//! are applied at startup and again whenever an admin changes them; an empty
+//! filter goes back to `RUST_LOG` (or the default) the server started with.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use super::logging::LogLevel;
+use crate::db::{self, DbPool};
+
+/// Tracing filter in `RUST_LOG` syntax, e.g. `steering_center=debug,tower_http=warn`
+pub const FILTER_SETTING: &str = "log_filter";
+/// JSON object of plugin IDs and the least severe level logged for each
+pub const PLUGIN_LEVELS_SETTING: &str = "log_plugin_levels";
+
+/// Filter used when `RUST_LOG` isn't set
+pub const DEFAULT_FILTER: &str = "steering_center=info,tower_http=debug";
+
+struct Reloadable {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// The filter the server started with
+    startup: String,
+}
+
+static FILTER: OnceLock<Reloadable> = OnceLock::new();
+
+/// The tracing filter layer, starting from `RUST_LOG` or `DEFAULT_FILTER`
+pub fn filter_layer() -> reload::Layer<EnvFilter, Registry> {
+    let startup = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|filter| EnvFilter::try_new(filter).is_ok())
+        .unwrap_or_else(|| DEFAULT_FILTER.to_string());
+    let (layer, handle) = reload::Layer::new(EnvFilter::new(&startup));
+    let _ = FILTER.set(Reloadable { handle, startup });
+    layer
+}
+
+/// The filter the server started with
+pub fn startup_filter() -> Option<String> {
+    FILTER.get().map(|filter| filter.startup.clone())
+}
+
+/// The filter in effect
+pub fn current_filter() -> Option<String> {
+    FILTER
+        .get()
+        .and_then(|filter| filter.handle.with_current(|f| f.to_string()).ok())
+}
+
+/// Check a filter, returning it parsed
+pub fn parse_filter(filter: &str) -> Result<EnvFilter> {
+    EnvFilter::try_new(filter).map_err(|e| anyhow!("Invalid log filter: {}", e))
+}
+
+/// Switch to a filter, or back to the startup one for `None`
+pub fn set_filter(filter: Option<&str>) -> Result<()> {
+    let reloadable = FILTER
+        .get()
+        .context("The log filter can't be changed in this process")?;
+    let filter = parse_filter(filter.unwrap_or(&reloadable.startup))?;
+    reloadable
+        .handle
+        .reload(filter)
+        .context("Failed to change the log filter")
+}
+
+/// Plugin levels from their setting
+pub fn parse_plugin_levels(json: &str) -> Result<HashMap<String, LogLevel>> {
+    let levels: HashMap<String, String> =
+        serde_json::from_str(json).context("Invalid plugin log levels")?;
+    levels
+        .into_iter()
+        .map(|(plugin, level)| {
+            let parsed = LogLevel::parse_level(&level)
+                .ok_or_else(|| anyhow!("Invalid log level {} for {}", level, plugin))?;
+            Ok((plugin, parsed))
+        })
+        .collect()
+}
+
+/// Plugin levels as stored in their setting
+pub fn plugin_levels_json(levels: &HashMap<String, LogLevel>) -> String {
+    let levels: HashMap<&str, &str> = levels
+        .iter()
+        .map(|(plugin, level)| (plugin.as_str(), level.as_str()))
+        .collect();
+    serde_json::to_string(&levels).unwrap_or_default()
+}
+
+/// The saved filter, if any, and plugin levels
+pub async fn load(db: &DbPool) -> Result<(Option<String>, HashMap<String, LogLevel>)> {
+    let filter = db::get_setting(db, FILTER_SETTING)
+        .await?
+        .filter(|filter| !filter.trim().is_empty());
+    let plugin_levels = match db::get_setting(db, PLUGIN_LEVELS_SETTING).await? {
+        Some(json) if !json.trim().is_empty() => parse_plugin_levels(&json)?,
+        _ => HashMap::new(),
+    };
+    Ok((filter, plugin_levels))
+}
+
+/// Apply the saved filter at startup; returns the plugin levels for the plugin logger
+pub async fn apply_saved(db: &DbPool) -> HashMap<String, LogLevel> {
+    match load(db).await {
+        Ok((filter, plugin_levels)) => {
+            if let Some(filter) = filter {
+                if let Err(e) = set_filter(Some(&filter)) {
+                    tracing::warn!("Ignoring saved log filter: {:#}", e);
+                }
+            }
+            plugin_levels
+        }
+        Err(e) => {
+            tracing::warn!("Ignoring saved log levels: {:#}", e);
+            HashMap::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_levels() {
+        let levels = parse_plugin_levels(r#"{"db": "warn", "web": "DEBUG"}"#).unwrap();
+        assert_eq!(levels["db"], LogLevel::Warn);
+        assert_eq!(levels["web"], LogLevel::Debug);
+        assert_eq!(
+            parse_plugin_levels(&plugin_levels_json(&levels)).unwrap(),
+            levels
+        );
+        assert!(parse_plugin_levels(r#"{"db": "loud"}"#).is_err());
+
+        assert!(parse_filter("steering_center=debug,tower_http=warn").is_ok());
+        assert!(parse_filter("steering_center=loud").is_err());
+    }
+}
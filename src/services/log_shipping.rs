@@ -0,0 +1,489 @@
+//! Forwarding logs to a remote syslog server or Grafana Loki
+//!
+//! Off until `log_shipping_target` is `syslog` or `loki`. Plugin and supervisor
+//! log entries and the server's own tracing output at or above
+//! `log_shipping_min_level` are queued and sent in batches by a background
+//! task. When the queue is full, because the target is down or slow, new
+//! records are dropped rather than holding up logging. The settings are read
+//! again every `SETTINGS_REFRESH`, so changes apply without a restart.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+
+use super::logging::{LogEntry, LogLevel};
+use super::notifications::CurlConfig;
+use super::plugin_registry::validate_url;
+use crate::db::{self, DbPool};
+
+/// `syslog`, `loki`, or empty for no shipping
+pub const TARGET_SETTING: &str = "log_shipping_target";
+/// Least severe level shipped: `trace`, `debug`, `info` (default), `warn` or `error`
+pub const MIN_LEVEL_SETTING: &str = "log_shipping_min_level";
+/// `udp://host:514` or `tcp://host:601`
+pub const SYSLOG_URL_SETTING: &str = "log_shipping_syslog_url";
+/// Base URL of Loki, e.g. `http://loki:3100`; logs are pushed to `/loki/api/v1/push`
+pub const LOKI_URL_SETTING: &str = "log_shipping_loki_url";
+pub const LOKI_USERNAME_SETTING: &str = "log_shipping_loki_username";
+pub const LOKI_PASSWORD_SETTING: &str = "log_shipping_loki_password";
+
+/// Records waiting to be sent before new ones are dropped
+const QUEUE_SIZE: usize = 10_000;
+/// Records sent at once
+const BATCH_SIZE: usize = 500;
+/// Longest a record waits for its batch to fill
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+/// How often the settings are read again
+const SETTINGS_REFRESH: Duration = Duration::from_secs(30);
+/// Time a target gets to accept a batch
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Name the logs are sent under: syslog APP-NAME and Loki `job` label
+const APP_NAME: &str = "steering-center";
+/// Syslog facility `local0`
+const SYSLOG_FACILITY: u8 = 16;
+/// Longest syslog message sent over UDP, as RFC 5426 suggests
+const MAX_UDP_MESSAGE: usize = 2048;
+
+/// Severity of the least severe level shipped; `SHIPPING_OFF` ships nothing
+static MIN_SEVERITY: AtomicU8 = AtomicU8::new(SHIPPING_OFF);
+const SHIPPING_OFF: u8 = u8::MAX;
+
+static QUEUE: OnceLock<mpsc::Sender<ShippedLog>> = OnceLock::new();
+
+/// A log record on its way out
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShippedLog {
+    pub timestamp: DateTime<Utc>,
+    pub level: LogLevel,
+    /// `app`, `supervisor` or `plugin`
+    pub source: &'static str,
+    pub plugin: Option<String>,
+    pub message: String,
+}
+
+/// Whether records of a level are shipped at the moment
+pub fn enabled(level: &LogLevel) -> bool {
+    level.severity() >= MIN_SEVERITY.load(Ordering::Relaxed)
+}
+
+/// Queue a record for shipping
+///
+/// Does nothing until the shipper is running, or when shipping is off.
+pub fn ship(log: ShippedLog) {
+    if !enabled(&log.level) {
+        return;
+    }
+    if let Some(queue) = QUEUE.get() {
+        let _ = queue.try_send(log);
+    }
+}
+
+/// Queue a plugin or supervisor log entry for shipping
+pub fn ship_entry(entry: &LogEntry, source: &'static str) {
+    let level = LogLevel::parse_level(&entry.level).unwrap_or(LogLevel::Info);
+    if !enabled(&level) {
+        return;
+    }
+    let timestamp = DateTime::parse_from_rfc3339(&entry.timestamp)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+    let message = match &entry.error {
+        Some(error) => format!("{}: {}", entry.message, error),
+        None => entry.message.clone(),
+    };
+    ship(ShippedLog {
+        timestamp,
+        level,
+        source,
+        plugin: entry.plugin.clone(),
+        message,
+    });
+}
+
+/// Tracing layer shipping the server's own log output
+pub struct ShippingLayer;
+
+impl<S: tracing::Subscriber> Layer<S> for ShippingLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: LayerContext<'_, S>) {
+        let metadata = event.metadata();
+        let level = match *metadata.level() {
+            tracing::Level::ERROR => LogLevel::Error,
+            tracing::Level::WARN => LogLevel::Warn,
+            tracing::Level::INFO => LogLevel::Info,
+            tracing::Level::DEBUG => LogLevel::Debug,
+            tracing::Level::TRACE => LogLevel::Trace,
+        };
+        // The shipper's own complaints would otherwise feed back into it
+        if !enabled(&level) || metadata.target().starts_with(module_path!()) {
+            return;
+        }
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+        ship(ShippedLog {
+            timestamp: Utc::now(),
+            level,
+            source: "app",
+            plugin: None,
+            message: format!("{}: {}", metadata.target(), visitor.0),
+        });
+    }
+}
+
+/// An event's message followed by its other fields as `name=value`
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{:?}", value));
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Where records go
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Syslog {
+        tcp: bool,
+        address: String,
+    },
+    Loki {
+        push_url: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+}
+
+/// `udp://host:port` or `tcp://host:port`, port 514 if left out
+fn parse_syslog_url(url: &str) -> Result<(bool, String)> {
+    let (tcp, address) = if let Some(address) = url.strip_prefix("udp://") {
+        (false, address)
+    } else if let Some(address) = url.strip_prefix("tcp://") {
+        (true, address)
+    } else {
+        bail!("Syslog URL must start with udp:// or tcp://");
+    };
+    let address = address.trim_end_matches('/');
+    if address.is_empty() || address.contains(['/', ' ']) {
+        bail!("Invalid syslog address {}", address);
+    }
+    // A bracketed IPv6 address or a host without a port
+    let has_port = address
+        .rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && !port.contains(']'));
+    if has_port {
+        Ok((tcp, address.to_string()))
+    } else {
+        Ok((tcp, format!("{}:514", address)))
+    }
+}
+
+/// The target and minimum level from the settings; no target when shipping is off
+async fn load_settings(db: &DbPool) -> Result<(Option<Target>, LogLevel)> {
+    let setting = |key| async move {
+        db::get_setting(db, key)
+            .await
+            .map(|v| v.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()))
+    };
+    let min_level = match setting(MIN_LEVEL_SETTING).await? {
+        Some(level) => LogLevel::parse_level(&level)
+            .ok_or_else(|| anyhow!("Invalid {}: {}", MIN_LEVEL_SETTING, level))?,
+        None => LogLevel::Info,
+    };
+    let target = match setting(TARGET_SETTING).await?.as_deref() {
+        None | Some("none") => None,
+        Some("syslog") => {
+            let url = setting(SYSLOG_URL_SETTING)
+                .await?
+                .with_context(|| format!("{} is not set", SYSLOG_URL_SETTING))?;
+            let (tcp, address) = parse_syslog_url(&url)?;
+            Some(Target::Syslog { tcp, address })
+        }
+        Some("loki") => {
+            let url = setting(LOKI_URL_SETTING)
+                .await?
+                .with_context(|| format!("{} is not set", LOKI_URL_SETTING))?;
+            validate_url(&url)?;
+            Some(Target::Loki {
+                push_url: format!("{}/loki/api/v1/push", url.trim_end_matches('/')),
+                username: setting(LOKI_USERNAME_SETTING).await?,
+                password: setting(LOKI_PASSWORD_SETTING).await?,
+            })
+        }
+        Some(other) => bail!("Unknown {}: {}", TARGET_SETTING, other),
+    };
+    Ok((target, min_level))
+}
+
+fn host_name() -> String {
+    sysinfo::System::host_name().unwrap_or_else(|| "-".to_string())
+}
+
+/// A record as an RFC 5424 syslog message
+fn syslog_message(log: &ShippedLog, host: &str) -> String {
+    let severity = match log.level {
+        LogLevel::Error => 3,
+        LogLevel::Warn => 4,
+        LogLevel::Info => 6,
+        LogLevel::Debug | LogLevel::Trace => 7,
+    };
+    // MSGID: up to 32 printable characters
+    let msg_id: String = log
+        .plugin
+        .as_deref()
+        .unwrap_or(log.source)
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .take(32)
+        .collect();
+    format!(
+        "<{}>1 {} {} {} {} {} - {}",
+        SYSLOG_FACILITY * 8 + severity,
+        log.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+        host,
+        APP_NAME,
+        std::process::id(),
+        msg_id,
+        log.message
+    )
+}
+
+/// Source, plugin and level of a Loki stream
+type StreamKey<'a> = (&'a str, Option<&'a str>, &'static str);
+
+/// Body of a Loki push: one stream per source, plugin and level
+fn loki_payload(logs: &[ShippedLog], host: &str) -> serde_json::Value {
+    let mut streams: BTreeMap<StreamKey, Vec<[String; 2]>> = BTreeMap::new();
+    for log in logs {
+        let nanos = log
+            .timestamp
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_string();
+        streams
+            .entry((log.source, log.plugin.as_deref(), log.level.as_str()))
+            .or_default()
+            .push([nanos, log.message.clone()]);
+    }
+    let streams: Vec<serde_json::Value> = streams
+        .into_iter()
+        .map(|((source, plugin, level), values)| {
+            let mut labels = serde_json::json!({
+                "job": APP_NAME,
+                "host": host,
+                "source": source,
+                "level": level,
+            });
+            if let Some(plugin) = plugin {
+                labels["plugin"] = plugin.into();
+            }
+            serde_json::json!({ "stream": labels, "values": values })
+        })
+        .collect();
+    serde_json::json!({ "streams": streams })
+}
+
+async fn send(target: &Target, logs: &[ShippedLog], host: &str) -> Result<()> {
+    match target {
+        Target::Syslog {
+            tcp: false,
+            address,
+        } => {
+            let socket = UdpSocket::bind(if address.starts_with('[') {
+                "[::]:0"
+            } else {
+                "0.0.0.0:0"
+            })
+            .await?;
+            socket.connect(address.as_str()).await?;
+            for log in logs {
+                let mut message = syslog_message(log, host).into_bytes();
+                message.truncate(MAX_UDP_MESSAGE);
+                socket.send(&message).await?;
+            }
+        }
+        Target::Syslog { tcp: true, address } => {
+            // Octet-counted framing (RFC 6587), so messages may span lines
+            let mut frames = Vec::new();
+            for log in logs {
+                let message = syslog_message(log, host);
+                frames.extend_from_slice(format!("{} {}", message.len(), message).as_bytes());
+            }
+            let mut stream = TcpStream::connect(address.as_str()).await?;
+            stream.write_all(&frames).await?;
+            stream.shutdown().await?;
+        }
+        Target::Loki {
+            push_url,
+            username,
+            password,
+        } => {
+            let mut config = CurlConfig::new(push_url);
+            config.option("header", "Content-Type: application/json");
+            if let Some(username) = username {
+                let password = password.as_deref().unwrap_or_default();
+                config.option("user", &format!("{}:{}", username, password));
+            }
+            config.option("data-binary", &loki_payload(logs, host).to_string());
+            config.run().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Send queued records to the configured target in the background
+pub fn spawn_shipper(db: DbPool) {
+    let (tx, mut rx) = mpsc::channel(QUEUE_SIZE);
+    if QUEUE.set(tx).is_err() {
+        tracing::warn!("Log shipper is already running");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let host = host_name();
+        let mut target = None;
+        let mut refreshed: Option<Instant> = None;
+        let mut failing = false;
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                Some(log) = rx.recv() => {
+                    batch.push(log);
+                    if batch.len() < BATCH_SIZE {
+                        continue;
+                    }
+                }
+                _ = interval.tick() => {}
+            }
+
+            if refreshed.is_none_or(|at| at.elapsed() >= SETTINGS_REFRESH) {
+                refreshed = Some(Instant::now());
+                let (new_target, min_level) = load_settings(&db).await.unwrap_or_else(|e| {
+                    tracing::warn!("Log shipping is off: {:#}", e);
+                    (None, LogLevel::Info)
+                });
+                let min_severity = match new_target {
+                    Some(_) => min_level.severity(),
+                    None => SHIPPING_OFF,
+                };
+                MIN_SEVERITY.store(min_severity, Ordering::Relaxed);
+                target = new_target;
+            }
+
+            let Some(target) = &target else {
+                batch.clear();
+                continue;
+            };
+            if batch.is_empty() {
+                continue;
+            }
+            let logs = std::mem::take(&mut batch);
+            match tokio::time::timeout(SEND_TIMEOUT, send(target, &logs, &host)).await {
+                Ok(Ok(())) => {
+                    if failing {
+                        tracing::info!("Log shipping recovered");
+                        failing = false;
+                    }
+                }
+                // Warn once until it works again; the records are dropped
+                result => {
+                    if !failing {
+                        let error = match result {
+                            Ok(Err(e)) => format!("{:#}", e),
+                            _ => "timed out".to_string(),
+                        };
+                        tracing::warn!("Log shipping failed, dropping logs: {}", error);
+                        failing = true;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(level: LogLevel, source: &'static str, plugin: Option<&str>) -> ShippedLog {
+        ShippedLog {
+            timestamp: "2026-01-05T10:00:00.5Z".parse().unwrap(),
+            level,
+            source,
+            plugin: plugin.map(str::to_string),
+            message: "Plugin db: crashed".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_syslog_message() {
+        let message = syslog_message(&log(LogLevel::Error, "plugin", Some("db")), "box");
+        let pid = std::process::id();
+        assert_eq!(
+            message,
+            format!(
+                "<131>1 2026-01-05T10:00:00.500Z box steering-center {} db - Plugin db: crashed",
+                pid
+            )
+        );
+
+        assert_eq!(
+            parse_syslog_url("udp://logs.local").unwrap(),
+            (false, "logs.local:514".to_string())
+        );
+        assert_eq!(
+            parse_syslog_url("tcp://[::1]:601").unwrap(),
+            (true, "[::1]:601".to_string())
+        );
+        assert!(parse_syslog_url("logs.local:514").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_syslog_udp() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = Target::Syslog {
+            tcp: false,
+            address: receiver.local_addr().unwrap().to_string(),
+        };
+        send(&target, &[log(LogLevel::Warn, "app", None)], "box")
+            .await
+            .unwrap();
+
+        let mut buf = [0u8; MAX_UDP_MESSAGE];
+        let len = receiver.recv(&mut buf).await.unwrap();
+        let message = String::from_utf8_lossy(&buf[..len]);
+        assert!(message.starts_with("<132>1 "));
+        assert!(message.ends_with(" app - Plugin db: crashed"));
+    }
+
+    #[test]
+    fn test_loki_payload() {
+        let logs = [
+            log(LogLevel::Error, "plugin", Some("db")),
+            log(LogLevel::Info, "app", None),
+            log(LogLevel::Error, "plugin", Some("db")),
+        ];
+        let payload = loki_payload(&logs, "box");
+        let streams = payload["streams"].as_array().unwrap();
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0]["stream"]["source"], "app");
+        assert!(streams[0]["stream"].get("plugin").is_none());
+        assert_eq!(streams[1]["stream"]["plugin"], "db");
+        assert_eq!(streams[1]["stream"]["level"], "error");
+        assert_eq!(streams[1]["values"][0][0], "1767607200500000000");
+        assert_eq!(streams[1]["values"].as_array().unwrap().len(), 2);
+    }
+}
@@ -1,14 +1,27 @@
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::{self, File, OpenOptions};
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
+
+/// Entries kept for live log subscribers that fall behind
+const LOG_TAIL_BUFFER: usize = 256;
+
+/// Directory the server writes its logs to (`paths.log_dir`)
+pub fn log_dir() -> PathBuf {
+    crate::config::get().paths.log_dir.clone()
+}
 
 /// Log levels for plugin and supervisor logging
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum LogLevel {
     Trace,
     Debug,
@@ -30,6 +43,17 @@ impl LogLevel {
         }
     }
 
+    /// Lowercase name, as accepted by `parse_level`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+
     /// Get level for filtering (higher values = more severe)
     pub fn severity(&self) -> u8 {
         match self {
@@ -42,6 +66,80 @@ impl LogLevel {
     }
 }
 
+/// Largest compiled search pattern, so a pattern can't use up memory
+const MAX_PATTERN_SIZE: usize = 1024 * 1024;
+
+/// Text a log entry's message or error must contain
+#[derive(Debug, Clone)]
+pub enum TextFilter {
+    /// Case-insensitive substring
+    Substring(String),
+    Regex(Regex),
+}
+
+impl TextFilter {
+    /// A case-insensitive substring filter
+    pub fn substring(text: &str) -> Self {
+        TextFilter::Substring(text.to_lowercase())
+    }
+
+    /// A regular expression filter, failing if the pattern is invalid
+    pub fn regex(pattern: &str) -> Result<Self> {
+        let regex = RegexBuilder::new(pattern)
+            .size_limit(MAX_PATTERN_SIZE)
+            .build()
+            .context("Invalid regular expression")?;
+        Ok(TextFilter::Regex(regex))
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            TextFilter::Substring(needle) => text.to_lowercase().contains(needle.as_str()),
+            TextFilter::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// Which plugin log entries to read; the default matches all of them
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Least severe level to include
+    pub level: Option<LogLevel>,
+    /// Earliest entry to include
+    pub from: Option<DateTime<Utc>>,
+    /// Latest entry to include
+    pub to: Option<DateTime<Utc>>,
+    pub text: Option<TextFilter>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if let Some(level) = &self.level {
+            match LogLevel::parse_level(&entry.level) {
+                Some(entry_level) if entry_level.severity() >= level.severity() => {}
+                _ => return false,
+            }
+        }
+        if self.from.is_some() || self.to.is_some() {
+            let Ok(timestamp) = DateTime::parse_from_rfc3339(&entry.timestamp) else {
+                return false;
+            };
+            if self.from.is_some_and(|from| timestamp < from)
+                || self.to.is_some_and(|to| timestamp > to)
+            {
+                return false;
+            }
+        }
+        if let Some(text) = &self.text {
+            let in_error = entry.error.as_deref().is_some_and(|e| text.matches(e));
+            if !text.matches(&entry.message) && !in_error {
+                return false;
+            }
+        }
+        true
+    }
+}
+
 /// Structured log entry (JSON format)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
@@ -106,6 +204,10 @@ pub struct LogConfig {
     pub max_rotated_files: usize,
     /// Base directory for logs
     pub log_dir: PathBuf,
+    /// Gzip log files once they are rotated
+    pub compress_rotated: bool,
+    /// Gzip level of rotated files, from 0 (fastest) to 9 (smallest)
+    pub compression_level: u32,
 }
 
 impl Default for LogConfig {
@@ -114,10 +216,128 @@ impl Default for LogConfig {
             max_file_size: 10 * 1024 * 1024, // 10 MB
             max_rotated_files: 5,
             log_dir: PathBuf::from("/var/log/toru"),
+            compress_rotated: true,
+            compression_level: 6,
         }
     }
 }
 
+/// Timestamp added to the name of a rotated log file
+const ROTATED_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Whether `name` is a rotated copy of the `stem.ext` log, compressed or not
+fn is_rotated_log(name: &str, stem: &str, ext: &str) -> bool {
+    let Some(rest) = name.strip_prefix(stem).and_then(|r| r.strip_prefix('-')) else {
+        return false;
+    };
+    let rest = rest.strip_suffix(".gz").unwrap_or(rest);
+    let Some(timestamp) = rest.strip_suffix(ext).and_then(|r| r.strip_suffix('.')) else {
+        return false;
+    };
+    // Not the log of another plugin whose ID starts with `stem-`
+    timestamp.len() == 15
+        && timestamp
+            .char_indices()
+            .all(|(i, c)| if i == 8 { c == '-' } else { c.is_ascii_digit() })
+}
+
+/// Rotated copies of a log file, newest first
+fn rotated_logs(log_path: &Path) -> Result<Vec<PathBuf>> {
+    let (Some(stem), Some(ext)) = (
+        log_path.file_stem().and_then(|s| s.to_str()),
+        log_path.extension().and_then(|s| s.to_str()),
+    ) else {
+        return Ok(Vec::new());
+    };
+    let dir = log_path.parent().unwrap_or_else(|| Path::new("."));
+    let mut rotated: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| is_rotated_log(name, stem, ext))
+        })
+        .collect();
+    // The timestamp in the name sorts them
+    rotated.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
+    Ok(rotated)
+}
+
+/// Contents of a log file, decompressing a gzipped one
+fn read_log_file(path: &Path) -> Result<String> {
+    let mut content = String::new();
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        GzDecoder::new(File::open(path)?)
+            .read_to_string(&mut content)
+            .context("Failed to decompress log file")?;
+    } else {
+        content = fs::read_to_string(path).context("Failed to read log file")?;
+    }
+    Ok(content)
+}
+
+/// Gzip a rotated log file next to it, removing the original
+fn compress_log(path: &Path, level: u32) -> Result<PathBuf> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    let gz_path = PathBuf::from(name);
+    let tmp_path = gz_path.with_extension("gz.tmp");
+
+    let mut encoder = GzEncoder::new(
+        File::create(&tmp_path).context("Failed to create compressed log")?,
+        Compression::new(level.min(9)),
+    );
+    std::io::copy(&mut File::open(path)?, &mut encoder)
+        .and_then(|_| encoder.finish())
+        .context("Failed to compress log file")
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&tmp_path);
+        })?;
+    fs::rename(&tmp_path, &gz_path)?;
+    fs::remove_file(path)?;
+    Ok(gz_path)
+}
+
+/// Rotate a log file (rename with timestamp, compress), keeping the newest
+/// `max_rotated_files` rotated copies
+fn rotate_log(log_path: &Path, config: &LogConfig) -> Result<()> {
+    // Get current timestamp for rotation
+    let timestamp = Utc::now().format(ROTATED_TIMESTAMP_FORMAT);
+
+    // Construct rotated filename
+    let stem = log_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("log");
+    let ext = log_path
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("log");
+    let parent = log_path.parent().unwrap_or_else(|| Path::new("."));
+    let rotated_path = parent.join(format!("{}-{}.{}", stem, timestamp, ext));
+
+    // Rename current log file to rotated name
+    fs::rename(log_path, &rotated_path).context("Failed to rename log file for rotation")?;
+
+    if config.compress_rotated {
+        // An uncompressed copy is still readable, so a failure only costs space
+        if let Err(e) = compress_log(&rotated_path, config.compression_level) {
+            tracing::warn!("Failed to compress {}: {:#}", rotated_path.display(), e);
+        }
+    }
+
+    // Remove old rotated files beyond the max_rotated_files limit
+    for old_file in rotated_logs(log_path)?
+        .iter()
+        .skip(config.max_rotated_files)
+    {
+        fs::remove_file(old_file).ok(); // Ignore errors
+    }
+
+    Ok(())
+}
+
 /// Plugin logger for writing structured JSON logs
 #[derive(Debug)]
 pub struct PluginLogger {
@@ -125,7 +345,11 @@ pub struct PluginLogger {
     // Per-plugin log files: HashMap<plugin_id, log_file_path>
     // TODO: Integrate file handle caching for improved performance
     #[allow(dead_code)]
-    log_files: Arc<Mutex<std::collections::HashMap<String, PathBuf>>>,
+    log_files: Arc<Mutex<HashMap<String, PathBuf>>>,
+    // Every written plugin entry, for live tails
+    tail: broadcast::Sender<LogEntry>,
+    // Least severe level written for a plugin, where one is set
+    min_levels: std::sync::RwLock<HashMap<String, LogLevel>>,
 }
 
 impl PluginLogger {
@@ -140,7 +364,9 @@ impl PluginLogger {
 
         Ok(Self {
             config,
-            log_files: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            log_files: Arc::new(Mutex::new(HashMap::new())),
+            tail: broadcast::channel(LOG_TAIL_BUFFER).0,
+            min_levels: Default::default(),
         })
     }
 
@@ -177,6 +403,14 @@ impl PluginLogger {
             .clone()
             .ok_or_else(|| anyhow::anyhow!("Log entry must have plugin_id"))?;
 
+        // Below the plugin's minimum level, if it has one
+        if let Some(min_level) = self.min_level(&plugin_id) {
+            let level = LogLevel::parse_level(&entry.level).unwrap_or(LogLevel::Info);
+            if level.severity() < min_level.severity() {
+                return Ok(());
+            }
+        }
+
         let log_path = self.get_plugin_log_path(&plugin_id);
 
         // Check if rotation is needed
@@ -193,55 +427,72 @@ impl PluginLogger {
         let json = entry.to_json()?;
         writeln!(file, "{}", json).context("Failed to write log entry")?;
 
+        super::log_shipping::ship_entry(&entry, "plugin");
+
+        // No receivers just means nobody is tailing
+        let _ = self.tail.send(entry);
+
         Ok(())
     }
 
-    /// Read logs for a plugin with optional filtering and pagination
+    /// Replace the minimum levels of plugins; the others log everything
+    pub fn set_min_levels(&self, levels: HashMap<String, LogLevel>) {
+        *self.min_levels.write().unwrap_or_else(|e| e.into_inner()) = levels;
+    }
+
+    fn min_level(&self, plugin_id: &str) -> Option<LogLevel> {
+        self.min_levels
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(plugin_id)
+            .cloned()
+    }
+
+    /// Receive plugin log entries as they are written, for every plugin
+    pub fn subscribe(&self) -> broadcast::Receiver<LogEntry> {
+        self.tail.subscribe()
+    }
+
+    /// Read logs for a plugin matching a filter, newest first, a page at a time
+    ///
+    /// Rotated files are read, and decompressed, only once the newer ones have
+    /// run out of matching entries.
     pub async fn read_plugin_logs(
         &self,
         plugin_id: &str,
-        filter_level: Option<LogLevel>,
+        filter: &LogFilter,
         page: usize,
         page_size: usize,
     ) -> Result<Vec<LogEntry>> {
         let log_path = self.get_plugin_log_path(plugin_id);
-
-        if !log_path.exists() {
-            return Ok(Vec::new());
+        let mut files = Vec::new();
+        if log_path.exists() {
+            files.push(log_path.clone());
         }
-
-        let content = fs::read_to_string(&log_path).context("Failed to read log file")?;
-
-        // Parse all log entries
-        let mut logs: Vec<LogEntry> = content
-            .lines()
-            .filter_map(|line| serde_json::from_str::<LogEntry>(line).ok())
-            .collect();
-
-        // Filter by log level if specified
-        if let Some(level) = filter_level {
-            let min_severity = level.severity();
-            logs.retain(|entry| {
-                if let Some(entry_level) = LogLevel::parse_level(&entry.level) {
-                    entry_level.severity() >= min_severity
+        files.extend(rotated_logs(&log_path)?);
+
+        let mut skip = page * page_size;
+        let mut logs = Vec::new();
+        for file in files {
+            let content = read_log_file(&file)?;
+
+            // Parse the log entries, newest first, keeping the ones that match
+            for entry in content
+                .lines()
+                .rev()
+                .filter_map(|line| serde_json::from_str::<LogEntry>(line).ok())
+                .filter(|entry| filter.matches(entry))
+            {
+                if skip > 0 {
+                    skip -= 1;
+                } else if logs.len() < page_size {
+                    logs.push(entry);
                 } else {
-                    false
+                    return Ok(logs);
                 }
-            });
-        }
-
-        // Reverse to show newest first
-        logs.reverse();
-
-        // Apply pagination
-        let start = page * page_size;
-        let end = start + page_size;
-        if start < logs.len() {
-            logs.truncate(end);
-            Ok(logs[start..].to_vec())
-        } else {
-            Ok(Vec::new())
+            }
         }
+        Ok(logs)
     }
 
     /// Check if log file needs rotation and rotate if necessary
@@ -252,73 +503,7 @@ impl PluginLogger {
 
         let metadata = fs::metadata(log_path)?;
         if metadata.len() >= self.config.max_file_size {
-            self.rotate_log(log_path)?;
-        }
-
-        Ok(())
-    }
-
-    /// Rotate a log file (rename with timestamp, create new)
-    fn rotate_log(&self, log_path: &Path) -> Result<()> {
-        // Get current timestamp for rotation
-        let timestamp = Utc::now().format("%Y%m%d-%H%M%S");
-
-        // Construct rotated filename
-        let stem = log_path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("log");
-        let ext = log_path
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("log");
-        let parent = log_path.parent().unwrap_or_else(|| Path::new("."));
-
-        // Rotate existing files (archive -> archive.1 -> archive.2 -> ...)
-        let rotated_path = parent.join(format!("{}-{}.{}", stem, timestamp, ext));
-
-        // Rename current log file to rotated name
-        fs::rename(log_path, &rotated_path).context("Failed to rename log file for rotation")?;
-
-        // Clean up old rotated files
-        self.cleanup_old_rotated_logs(parent, stem, ext)?;
-
-        Ok(())
-    }
-
-    /// Remove old rotated log files beyond max_rotated_files limit
-    fn cleanup_old_rotated_logs(&self, dir: &Path, stem: &str, ext: &str) -> Result<()> {
-        // Read directory and find all rotated log files
-        let mut rotated_files: Vec<PathBuf> = fs::read_dir(dir)?
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.path())
-            .filter(|path| {
-                path.file_name()
-                    .and_then(|name| name.to_str())
-                    .map(|name| name.starts_with(stem) && name.ends_with(ext))
-                    .unwrap_or(false)
-            })
-            .collect();
-
-        // Sort by modification time (oldest first)
-        rotated_files.sort_by(|a, b| {
-            let a_time = a
-                .metadata()
-                .and_then(|m| m.modified())
-                .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-            let b_time = b
-                .metadata()
-                .and_then(|m| m.modified())
-                .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-            a_time.cmp(&b_time)
-        });
-
-        // Remove files beyond max_rotated_files limit
-        let keep_count = self.config.max_rotated_files;
-        if rotated_files.len() > keep_count {
-            for old_file in rotated_files.iter().take(rotated_files.len() - keep_count) {
-                fs::remove_file(old_file).ok(); // Ignore errors
-            }
+            rotate_log(log_path, &self.config)?;
         }
 
         Ok(())
@@ -326,53 +511,67 @@ impl PluginLogger {
 }
 
 /// Supervisor logger for core plugin system logs
+///
+/// Rotates `plugin-supervisor.log` like plugin logs once it reaches the
+/// configured size.
 #[derive(Debug)]
 pub struct SupervisorLogger {
+    config: LogConfig,
+    log_path: PathBuf,
     log_file: Arc<Mutex<File>>,
 }
 
 impl SupervisorLogger {
-    /// Create a new supervisor logger
-    pub fn new(log_dir: &Path) -> Result<Self> {
-        fs::create_dir_all(log_dir).context("Failed to create log directory")?;
+    /// Create a new supervisor logger writing into `config.log_dir`
+    pub fn new(config: LogConfig) -> Result<Self> {
+        fs::create_dir_all(&config.log_dir).context("Failed to create log directory")?;
 
-        let log_path = log_dir.join("plugin-supervisor.log");
-
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&log_path)
-            .context("Failed to open supervisor log file")?;
+        let log_path = config.log_dir.join("plugin-supervisor.log");
+        let file = Self::open(&log_path)?;
 
         Ok(Self {
+            config,
+            log_path,
             log_file: Arc::new(Mutex::new(file)),
         })
     }
 
-    /// Log a message
-    // TODO: Integrate in general supervisor logging
-    #[allow(dead_code)]
-    pub async fn log(&self, level: LogLevel, message: &str) -> Result<()> {
-        let entry = LogEntry::new(level, message);
+    fn open(log_path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)
+            .context("Failed to open supervisor log file")
+    }
+
+    /// Write an entry, rotating the file first when it is full
+    async fn write(&self, entry: &LogEntry) -> Result<()> {
         let json = entry.to_json()?;
 
         let mut file = self.log_file.lock().await;
+        if file.metadata().map(|m| m.len()).unwrap_or(0) >= self.config.max_file_size {
+            rotate_log(&self.log_path, &self.config)?;
+            *file = Self::open(&self.log_path)?;
+        }
         writeln!(file, "{}", json).context("Failed to write supervisor log")?;
+        super::log_shipping::ship_entry(entry, "supervisor");
 
         Ok(())
     }
 
+    /// Log a message
+    // TODO: Integrate in general supervisor logging
+    #[allow(dead_code)]
+    pub async fn log(&self, level: LogLevel, message: &str) -> Result<()> {
+        self.write(&LogEntry::new(level, message)).await
+    }
+
     /// Log error with details
     // TODO: Integrate in error handling paths
     #[allow(dead_code)]
     pub async fn log_error(&self, message: &str, error: &str) -> Result<()> {
-        let entry = LogEntry::new(LogLevel::Error, message).with_error(error);
-        let json = entry.to_json()?;
-
-        let mut file = self.log_file.lock().await;
-        writeln!(file, "{}", json).context("Failed to write supervisor log")?;
-
-        Ok(())
+        self.write(&LogEntry::new(LogLevel::Error, message).with_error(error))
+            .await
     }
 
     /// Log plugin event (spawn, kill, crash, restart, etc.)
@@ -389,11 +588,194 @@ impl SupervisorLogger {
             format!("Plugin {}: {}", plugin_id, event)
         };
 
-        let entry = LogEntry::new(level, &message).with_plugin(plugin_id);
-        let json = entry.to_json()?;
+        self.write(&LogEntry::new(level, &message).with_plugin(plugin_id))
+            .await
+    }
+}
+
+/// One HTTP request in the access log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessLogEntry {
+    pub timestamp: String,
+    pub request_id: String,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub duration_ms: u64,
+    /// Username the request was authenticated as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Client address, from proxy headers when `server.trust_proxy` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+}
+
+/// Which access log entries to return
+#[derive(Debug, Clone, Default)]
+pub struct AccessLogFilter {
+    pub method: Option<String>,
+    /// Start of the path
+    pub path: Option<String>,
+    /// Exact status, or a class such as `5xx`
+    pub status: Option<String>,
+    pub user: Option<String>,
+    pub ip: Option<String>,
+    pub request_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl AccessLogFilter {
+    /// Check the filter's status is a number or class like `4xx`
+    pub fn validate(&self) -> Result<()> {
+        if let Some(status) = &self.status {
+            let valid = match status.strip_suffix("xx") {
+                Some(class) => matches!(class, "1" | "2" | "3" | "4" | "5"),
+                None => status.parse::<u16>().is_ok(),
+            };
+            if !valid {
+                anyhow::bail!(
+                    "Invalid status {:?}, expected a code or a class like 5xx",
+                    status
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, entry: &AccessLogEntry) -> bool {
+        if self
+            .method
+            .as_ref()
+            .is_some_and(|method| !method.eq_ignore_ascii_case(&entry.method))
+        {
+            return false;
+        }
+        if self
+            .path
+            .as_ref()
+            .is_some_and(|path| !entry.path.starts_with(path.as_str()))
+        {
+            return false;
+        }
+        if let Some(status) = &self.status {
+            let matched = match status.strip_suffix("xx") {
+                Some(class) => class == (entry.status / 100).to_string(),
+                None => status == &entry.status.to_string(),
+            };
+            if !matched {
+                return false;
+            }
+        }
+        if self.user.is_some() && self.user != entry.user {
+            return false;
+        }
+        if self.ip.is_some() && self.ip != entry.ip {
+            return false;
+        }
+        if self
+            .request_id
+            .as_ref()
+            .is_some_and(|id| id != &entry.request_id)
+        {
+            return false;
+        }
+        if self.from.is_some() || self.to.is_some() {
+            let Ok(timestamp) = DateTime::parse_from_rfc3339(&entry.timestamp) else {
+                return false;
+            };
+            let timestamp = timestamp.with_timezone(&Utc);
+            if self.from.is_some_and(|from| timestamp < from)
+                || self.to.is_some_and(|to| timestamp > to)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Path of the access log in `log_dir`
+pub fn access_log_path(log_dir: &Path) -> PathBuf {
+    log_dir.join("access.log")
+}
+
+/// Most recent access log entries matching `filter`, newest first, reading
+/// back through rotated files as needed
+pub fn read_access_log(
+    log_dir: &Path,
+    filter: &AccessLogFilter,
+    limit: usize,
+) -> Result<Vec<AccessLogEntry>> {
+    let log_path = access_log_path(log_dir);
+    let mut files = Vec::new();
+    if log_path.exists() {
+        files.push(log_path.clone());
+    }
+    files.extend(rotated_logs(&log_path)?);
+
+    let mut entries = Vec::new();
+    for file in files {
+        let content = read_log_file(&file)?;
+        for entry in content
+            .lines()
+            .rev()
+            .filter_map(|line| serde_json::from_str::<AccessLogEntry>(line).ok())
+            .filter(|entry| filter.matches(entry))
+        {
+            if entries.len() == limit {
+                return Ok(entries);
+            }
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Access logger writing one JSON line per HTTP request to `access.log`
+///
+/// Rotates like plugin logs once the file reaches the configured size.
+#[derive(Debug)]
+pub struct AccessLogger {
+    config: LogConfig,
+    log_path: PathBuf,
+    log_file: Mutex<File>,
+}
+
+impl AccessLogger {
+    /// Create an access logger writing into `config.log_dir`
+    pub fn new(config: LogConfig) -> Result<Self> {
+        fs::create_dir_all(&config.log_dir).context("Failed to create log directory")?;
+        let log_path = access_log_path(&config.log_dir);
+        let file = Self::open(&log_path)?;
+
+        Ok(Self {
+            config,
+            log_path,
+            log_file: Mutex::new(file),
+        })
+    }
+
+    fn open(log_path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)
+            .context("Failed to open access log file")
+    }
+
+    /// Write an entry, rotating the file first when it is full
+    pub async fn log(&self, entry: &AccessLogEntry) -> Result<()> {
+        let json = serde_json::to_string(entry).context("Failed to serialize access log entry")?;
 
         let mut file = self.log_file.lock().await;
-        writeln!(file, "{}", json).context("Failed to write supervisor log")?;
+        if file.metadata().map(|m| m.len()).unwrap_or(0) >= self.config.max_file_size {
+            rotate_log(&self.log_path, &self.config).context("Failed to rotate access log")?;
+            *file = Self::open(&self.log_path)?;
+        }
+        writeln!(file, "{}", json).context("Failed to write access log")?;
 
         Ok(())
     }
@@ -422,6 +804,225 @@ mod tests {
         assert!(LogLevel::Trace.severity() == 0);
     }
 
+    #[tokio::test]
+    async fn test_subscribe_receives_written_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = PluginLogger::from_directory(dir.path()).unwrap();
+        let mut tail = logger.subscribe();
+
+        let entry = LogEntry::new(LogLevel::Info, "hello").with_plugin("tailed");
+        logger.log_plugin(entry).await.unwrap();
+
+        let received = tail.try_recv().unwrap();
+        assert_eq!(received.plugin.as_deref(), Some("tailed"));
+        assert_eq!(received.message, "hello");
+    }
+
+    #[tokio::test]
+    async fn test_plugin_min_level() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = PluginLogger::from_directory(dir.path()).unwrap();
+        logger.set_min_levels(HashMap::from([("quiet".to_string(), LogLevel::Warn)]));
+        for plugin in ["quiet", "chatty"] {
+            for level in [LogLevel::Debug, LogLevel::Error] {
+                logger
+                    .log_plugin(LogEntry::new(level, "hello").with_plugin(plugin))
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let count = |plugin| {
+            let logger = &logger;
+            async move {
+                logger
+                    .read_plugin_logs(plugin, &LogFilter::default(), 0, 10)
+                    .await
+                    .unwrap()
+                    .len()
+            }
+        };
+        assert_eq!(count("quiet").await, 1);
+        assert_eq!(count("chatty").await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_access_log_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = AccessLogger::new(LogConfig {
+            log_dir: dir.path().to_path_buf(),
+            max_file_size: 100,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut entry = AccessLogEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            request_id: "req-1".to_string(),
+            method: "GET".to_string(),
+            path: "/api/resources".to_string(),
+            status: 200,
+            duration_ms: 3,
+            user: Some("alice".to_string()),
+            ip: Some("10.0.0.1".to_string()),
+            user_agent: None,
+        };
+
+        logger.log(&entry).await.unwrap();
+        let line = fs::read_to_string(dir.path().join("access.log")).unwrap();
+        let logged: AccessLogEntry = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(logged.request_id, "req-1");
+        assert_eq!(logged.status, 200);
+        assert_eq!(logged.user.as_deref(), Some("alice"));
+
+        entry.request_id = "req-2".to_string();
+        entry.status = 503;
+        entry.user = None;
+        logger.log(&entry).await.unwrap();
+        let rotated = rotated_logs(&dir.path().join("access.log")).unwrap();
+        assert_eq!(rotated.len(), 1);
+        assert!(rotated[0].to_string_lossy().ends_with(".log.gz"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("access.log"))
+                .unwrap()
+                .lines()
+                .count(),
+            1
+        );
+
+        let all = read_access_log(dir.path(), &AccessLogFilter::default(), 10).unwrap();
+        let ids: Vec<&str> = all.iter().map(|e| e.request_id.as_str()).collect();
+        assert_eq!(ids, ["req-2", "req-1"]);
+
+        let errors = AccessLogFilter {
+            status: Some("5xx".to_string()),
+            ..Default::default()
+        };
+        let found = read_access_log(dir.path(), &errors, 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].request_id, "req-2");
+
+        let by_user = AccessLogFilter {
+            user: Some("alice".to_string()),
+            path: Some("/api/".to_string()),
+            ..Default::default()
+        };
+        let found = read_access_log(dir.path(), &by_user, 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].request_id, "req-1");
+        assert_eq!(read_access_log(dir.path(), &by_user, 0).unwrap().len(), 0);
+
+        let invalid = AccessLogFilter {
+            status: Some("6xx".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+        assert!(errors.validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_read_plugin_logs_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = PluginLogger::from_directory(dir.path()).unwrap();
+        let lines = [
+            ("2024-05-01T10:00:00Z", "Info", "Started worker"),
+            (
+                "2024-05-01T11:00:00Z",
+                "Error",
+                "Connection refused (port 5432)",
+            ),
+            ("2024-05-01T12:00:00Z", "Warn", "Slow query: 1200ms"),
+            ("2024-05-02T09:00:00Z", "Error", "Connection reset"),
+        ];
+        let content: String = lines
+            .iter()
+            .map(|(timestamp, level, message)| {
+                format!(
+                    "{{\"timestamp\":\"{}\",\"level\":\"{}\",\"message\":\"{}\"}}\n",
+                    timestamp, level, message
+                )
+            })
+            .collect();
+        fs::write(logger.get_plugin_log_path("db"), content).unwrap();
+        let logger = &logger;
+        let messages = |filter: LogFilter| async move {
+            logger
+                .read_plugin_logs("db", &filter, 0, 10)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.message)
+                .collect::<Vec<_>>()
+        };
+
+        let all = messages(LogFilter::default()).await;
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0], "Connection reset");
+
+        let errors_on_first = LogFilter {
+            level: Some(LogLevel::Warn),
+            to: Some("2024-05-01T23:59:59Z".parse().unwrap()),
+            text: Some(TextFilter::substring("CONNECTION")),
+            ..Default::default()
+        };
+        assert_eq!(
+            messages(errors_on_first).await,
+            ["Connection refused (port 5432)"]
+        );
+
+        let slow = LogFilter {
+            from: Some("2024-05-01T10:30:00Z".parse().unwrap()),
+            text: Some(TextFilter::regex(r"\d{4}ms$").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(messages(slow).await, ["Slow query: 1200ms"]);
+        assert!(TextFilter::regex("(unclosed").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rotated_logs_are_compressed_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = PluginLogger::new(LogConfig {
+            log_dir: dir.path().to_path_buf(),
+            max_file_size: 1,
+            max_rotated_files: 2,
+            ..Default::default()
+        })
+        .unwrap();
+        // A plugin whose ID starts like the other's keeps its own log
+        logger
+            .log_plugin(LogEntry::new(LogLevel::Info, "other").with_plugin("db-admin"))
+            .await
+            .unwrap();
+        for message in ["first", "second"] {
+            logger
+                .log_plugin(LogEntry::new(LogLevel::Info, message).with_plugin("db"))
+                .await
+                .unwrap();
+        }
+
+        let log_path = logger.get_plugin_log_path("db");
+        let rotated = rotated_logs(&log_path).unwrap();
+        assert_eq!(rotated.len(), 1);
+        assert!(rotated[0].to_string_lossy().ends_with(".log.gz"));
+
+        let messages: Vec<String> = logger
+            .read_plugin_logs("db", &LogFilter::default(), 0, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, ["second", "first"]);
+        let second_page = logger
+            .read_plugin_logs("db", &LogFilter::default(), 1, 1)
+            .await
+            .unwrap();
+        assert_eq!(second_page[0].message, "first");
+
+        assert!(is_rotated_log("db-20240501-100000.log", "db", "log"));
+        assert!(!is_rotated_log("db-admin.log", "db", "log"));
+    }
+
     #[test]
     fn test_log_level_parse_level() {
         assert_eq!(LogLevel::parse_level("info"), Some(LogLevel::Info));
@@ -0,0 +1,347 @@
+//! Metrics for external monitoring, served by `GET /metrics` in the Prometheus
+//! text exposition format
+//!
+//! Host resources, plugins, tasks and sessions are read when the page is
+//! scraped; HTTP requests are counted as they are served.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::system::SystemResources;
+
+/// Setting holding the bearer token scrapers must send; `/metrics` is open without it
+pub const METRICS_TOKEN_SETTING: &str = "metrics_token";
+
+/// Upper bounds of the request duration buckets, in seconds
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Builds a page in the Prometheus text exposition format
+#[derive(Debug, Default)]
+pub struct MetricsWriter {
+    out: String,
+}
+
+impl MetricsWriter {
+    /// Start a metric family
+    ///
+    /// # Arguments
+    /// * `name` - Metric name, without `_bucket`/`_sum`/`_count` suffixes
+    /// * `kind` - `gauge`, `counter`, `histogram` or `summary`
+    /// * `help` - One line describing the metric
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+    }
+
+    /// Add a sample to the current family
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            let _ = write!(self.out, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.out, " {}", format_value(value));
+    }
+
+    /// A family with a single unlabelled sample
+    pub fn single(&mut self, name: &str, kind: &str, help: &str, value: f64) {
+        self.family(name, kind, help);
+        self.sample(name, &[], value);
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+/// Escape a label value: backslash, double quote and newline
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Request durations of one route
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Requests at or under each bound of `DURATION_BUCKETS`
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+}
+
+#[derive(Debug, Default)]
+struct HttpStats {
+    /// Requests by method, route and status
+    requests: BTreeMap<(String, String, u16), u64>,
+    /// Durations by method and route
+    durations: BTreeMap<(String, String), Histogram>,
+}
+
+/// Requests the API served since startup
+///
+/// Routes are recorded as declared (`/api/plugins/:id`), so plugin IDs and other
+/// path parameters don't create a series each.
+#[derive(Debug, Default)]
+pub struct HttpMetrics {
+    stats: Mutex<HttpStats>,
+}
+
+impl HttpMetrics {
+    pub fn record(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        *stats
+            .requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default() += 1;
+        stats
+            .durations
+            .entry((method.to_string(), route.to_string()))
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn write(&self, out: &mut MetricsWriter) {
+        let stats = self.stats.lock().unwrap();
+
+        out.family(
+            "toru_http_requests_total",
+            "counter",
+            "HTTP requests served, by method, route and status",
+        );
+        for ((method, route, status), count) in &stats.requests {
+            let status = status.to_string();
+            out.sample(
+                "toru_http_requests_total",
+                &[("method", method), ("route", route), ("status", &status)],
+                *count as f64,
+            );
+        }
+
+        out.family(
+            "toru_http_request_duration_seconds",
+            "histogram",
+            "Time taken to serve HTTP requests",
+        );
+        for ((method, route), histogram) in &stats.durations {
+            for (bound, count) in DURATION_BUCKETS.iter().zip(histogram.buckets) {
+                let le = bound.to_string();
+                out.sample(
+                    "toru_http_request_duration_seconds_bucket",
+                    &[("method", method), ("route", route), ("le", &le)],
+                    count as f64,
+                );
+            }
+            let labels = [("method", method.as_str()), ("route", route.as_str())];
+            out.sample(
+                "toru_http_request_duration_seconds_bucket",
+                &[labels[0], labels[1], ("le", "+Inf")],
+                histogram.count as f64,
+            );
+            out.sample(
+                "toru_http_request_duration_seconds_sum",
+                &labels,
+                histogram.sum,
+            );
+            out.sample(
+                "toru_http_request_duration_seconds_count",
+                &labels,
+                histogram.count as f64,
+            );
+        }
+    }
+}
+
+/// Write the host's resource usage
+pub fn write_system(out: &mut MetricsWriter, resources: &SystemResources) {
+    out.single(
+        "toru_cpu_usage_percent",
+        "gauge",
+        "CPU usage averaged over all cores",
+        resources.cpu_percent.into(),
+    );
+    out.family(
+        "toru_cpu_core_usage_percent",
+        "gauge",
+        "CPU usage of each core",
+    );
+    for (i, core) in resources.cpu_cores.iter().enumerate() {
+        out.sample(
+            "toru_cpu_core_usage_percent",
+            &[("core", &i.to_string())],
+            core.usage.into(),
+        );
+    }
+
+    out.single(
+        "toru_memory_used_bytes",
+        "gauge",
+        "Memory in use",
+        resources.memory_used as f64,
+    );
+    out.single(
+        "toru_memory_total_bytes",
+        "gauge",
+        "Installed memory",
+        resources.memory_total as f64,
+    );
+    out.single(
+        "toru_swap_used_bytes",
+        "gauge",
+        "Swap in use",
+        resources.swap_used as f64,
+    );
+    out.single(
+        "toru_swap_total_bytes",
+        "gauge",
+        "Swap space",
+        resources.swap_total as f64,
+    );
+    out.single(
+        "toru_uptime_seconds",
+        "gauge",
+        "Time since the host booted",
+        resources.uptime_seconds as f64,
+    );
+    out.single(
+        "toru_processes",
+        "gauge",
+        "Processes running on the host",
+        resources.process_count as f64,
+    );
+
+    out.family(
+        "toru_disk_total_bytes",
+        "gauge",
+        "Size of each mounted disk",
+    );
+    for disk in &resources.disks {
+        out.sample(
+            "toru_disk_total_bytes",
+            &[("mount", &disk.mount_point)],
+            disk.total_space as f64,
+        );
+    }
+    out.family(
+        "toru_disk_available_bytes",
+        "gauge",
+        "Free space on each mounted disk",
+    );
+    for disk in &resources.disks {
+        out.sample(
+            "toru_disk_available_bytes",
+            &[("mount", &disk.mount_point)],
+            disk.available_space as f64,
+        );
+    }
+
+    out.family(
+        "toru_network_received_bytes_total",
+        "counter",
+        "Bytes received on each network interface",
+    );
+    for interface in &resources.network {
+        out.sample(
+            "toru_network_received_bytes_total",
+            &[("interface", &interface.name)],
+            interface.received as f64,
+        );
+    }
+    out.family(
+        "toru_network_transmitted_bytes_total",
+        "counter",
+        "Bytes sent on each network interface",
+    );
+    for interface in &resources.network {
+        out.sample(
+            "toru_network_transmitted_bytes_total",
+            &[("interface", &interface.name)],
+            interface.transmitted as f64,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_format() {
+        let mut out = MetricsWriter::default();
+        out.family("toru_plugin_running", "gauge", "Whether the plugin runs");
+        out.sample("toru_plugin_running", &[("plugin", "a\"b\\c\nd")], 1.0);
+        out.single("toru_cpu_usage_percent", "gauge", "CPU", 12.5);
+        assert_eq!(
+            out.finish(),
+            "# HELP toru_plugin_running Whether the plugin runs\n\
+             # TYPE toru_plugin_running gauge\n\
+             toru_plugin_running{plugin=\"a\\\"b\\\\c\\nd\"} 1\n\
+             # HELP toru_cpu_usage_percent CPU\n\
+             # TYPE toru_cpu_usage_percent gauge\n\
+             toru_cpu_usage_percent 12.5\n"
+        );
+    }
+
+    #[test]
+    fn test_request_histogram() {
+        let metrics = HttpMetrics::default();
+        metrics.record("GET", "/api/health", 200, Duration::from_millis(3));
+        metrics.record("GET", "/api/health", 200, Duration::from_millis(300));
+        metrics.record("GET", "/api/health", 500, Duration::from_secs(20));
+
+        let mut out = MetricsWriter::default();
+        metrics.write(&mut out);
+        let page = out.finish();
+        let labels = "method=\"GET\",route=\"/api/health\"";
+        assert!(page.contains(&format!(
+            "toru_http_requests_total{{{},status=\"200\"}} 2\n",
+            labels
+        )));
+        assert!(page.contains(&format!(
+            "toru_http_request_duration_seconds_bucket{{{},le=\"0.005\"}} 1\n",
+            labels
+        )));
+        assert!(page.contains(&format!(
+            "toru_http_request_duration_seconds_bucket{{{},le=\"0.5\"}} 2\n",
+            labels
+        )));
+        assert!(page.contains(&format!(
+            "toru_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 3\n",
+            labels
+        )));
+        assert!(page.contains(&format!(
+            "toru_http_request_duration_seconds_count{{{}}} 3\n",
+            labels
+        )));
+    }
+}
@@ -1,6 +1,51 @@
+pub mod acme;
+pub mod action_packs;
+pub mod action_params;
 pub mod auth;
+pub mod avatars;
+pub mod backup;
+pub mod config_transfer;
+pub mod db_maintenance;
+pub mod docker;
+pub mod event_bus;
 pub mod executor;
+pub mod files;
+pub mod health;
+pub mod host_sessions;
 pub mod kv_store;
+pub mod log_levels;
+pub mod log_shipping;
 pub mod logging;
+pub mod metrics;
+pub mod notifications;
+pub mod plugin_assets;
+pub mod plugin_breaker;
+pub mod plugin_connection;
+pub mod plugin_crash;
+pub mod plugin_deps;
+pub mod plugin_exec;
+pub mod plugin_jobs;
+pub mod plugin_limits;
+pub mod plugin_package;
+pub mod plugin_registry;
+pub mod plugin_signing;
 pub mod plugins;
+pub mod ports;
+pub mod power;
+pub mod processes;
+pub mod scheduler;
+pub mod scripts;
+pub mod self_update;
+pub mod sensors;
+pub mod shutdown;
 pub mod system;
+pub mod system_logs;
+pub mod system_notifications;
+#[cfg(unix)]
+pub mod systemd;
+pub mod task_output;
+pub mod tls;
+pub mod updates;
+pub mod user_import;
+pub mod wasm_runtime;
+pub mod webhooks;
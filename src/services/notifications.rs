@@ -0,0 +1,524 @@
+//! Notifications to people: email, Telegram, ntfy and Slack
+//!
+//! Each channel is configured by `notify_<channel>_*` settings and is off until
+//! its required settings are set. Alerts, failed tasks, plugin crashes and
+//! security events are sent to the channels listed in `notify_events_<kind>`
+//! (comma-separated, `none` for none); without that setting they go to every
+//! configured channel. Every channel talks to its service through `curl`, with
+//! the options on stdin so tokens and passwords stay out of the process list.
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Serialize;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::db::{self, DbPool};
+use crate::services::system_notifications;
+
+pub const EMAIL_SMTP_URL_SETTING: &str = "notify_email_smtp_url";
+pub const EMAIL_FROM_SETTING: &str = "notify_email_from";
+/// Comma-separated recipients
+pub const EMAIL_TO_SETTING: &str = "notify_email_to";
+pub const EMAIL_USERNAME_SETTING: &str = "notify_email_username";
+pub const EMAIL_PASSWORD_SETTING: &str = "notify_email_password";
+pub const TELEGRAM_BOT_TOKEN_SETTING: &str = "notify_telegram_bot_token";
+pub const TELEGRAM_CHAT_ID_SETTING: &str = "notify_telegram_chat_id";
+/// Topic URL, e.g. `https://ntfy.sh/my-server`
+pub const NTFY_URL_SETTING: &str = "notify_ntfy_url";
+pub const NTFY_TOKEN_SETTING: &str = "notify_ntfy_token";
+pub const SLACK_WEBHOOK_URL_SETTING: &str = "notify_slack_webhook_url";
+
+/// Names of the channels, as used in `notify_events_<kind>`
+pub const CHANNELS: &[&str] = &["email", "telegram", "ntfy", "slack"];
+
+/// Time a service gets to accept a notification
+const SEND_TIMEOUT_SECS: u64 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Alert,
+    TaskFailed,
+    PluginCrash,
+    Security,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 4] = [
+        NotificationKind::Alert,
+        NotificationKind::TaskFailed,
+        NotificationKind::PluginCrash,
+        NotificationKind::Security,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotificationKind::Alert => "alert",
+            NotificationKind::TaskFailed => "task_failed",
+            NotificationKind::PluginCrash => "plugin_crash",
+            NotificationKind::Security => "security",
+        }
+    }
+
+    /// Setting listing the channels this kind is sent to
+    pub fn preference_setting(self) -> String {
+        format!("notify_events_{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub level: NotificationLevel,
+    pub title: String,
+    pub message: String,
+}
+
+impl Notification {
+    pub fn new(
+        kind: NotificationKind,
+        level: NotificationLevel,
+        title: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            level,
+            title: title.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// A way of reaching people
+#[async_trait]
+pub trait Channel: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Send a notification, in the background, to the channels its kind is routed to
+/// (and to the connected WebSocket sessions)
+pub fn notify(db: &DbPool, notification: Notification) {
+    system_notifications::publish((&notification).into());
+    let db = db.clone();
+    tokio::spawn(async move {
+        let channels = match routed_channels(&db, notification.kind).await {
+            Ok(channels) => channels,
+            Err(e) => {
+                tracing::warn!("Failed to load notification channels: {:#}", e);
+                return;
+            }
+        };
+        for channel in channels {
+            if let Err(e) = channel.send(&notification).await {
+                tracing::warn!(
+                    "Failed to send {} notification via {}: {:#}",
+                    notification.kind.as_str(),
+                    channel.name(),
+                    e
+                );
+            }
+        }
+    });
+}
+
+/// Load a channel from its settings; None if it isn't configured
+pub async fn load_channel(db: &DbPool, name: &str) -> Result<Option<Box<dyn Channel>>> {
+    let channel: Option<Box<dyn Channel>> = match name {
+        "email" => EmailChannel::from_settings(db)
+            .await?
+            .map(|c| Box::new(c) as _),
+        "telegram" => TelegramChannel::from_settings(db)
+            .await?
+            .map(|c| Box::new(c) as _),
+        "ntfy" => NtfyChannel::from_settings(db)
+            .await?
+            .map(|c| Box::new(c) as _),
+        "slack" => SlackChannel::from_settings(db)
+            .await?
+            .map(|c| Box::new(c) as _),
+        _ => bail!("Unknown notification channel: {}", name),
+    };
+    Ok(channel)
+}
+
+/// Channels a kind of notification goes to
+///
+/// Channels listed in the preference but not configured are skipped.
+async fn routed_channels(db: &DbPool, kind: NotificationKind) -> Result<Vec<Box<dyn Channel>>> {
+    let preference = db::get_setting(db, &kind.preference_setting()).await?;
+    let mut channels = Vec::new();
+    for name in preferred_channels(preference.as_deref()) {
+        match load_channel(db, name).await {
+            Ok(Some(channel)) => channels.push(channel),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Skipping notification channel {}: {:#}", name, e),
+        }
+    }
+    Ok(channels)
+}
+
+/// Channel names a `notify_events_<kind>` value asks for; every channel when unset
+pub fn preferred_channels(preference: Option<&str>) -> Vec<&str> {
+    let Some(preference) = preference else {
+        return CHANNELS.to_vec();
+    };
+    preference
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && !name.eq_ignore_ascii_case("none"))
+        .collect()
+}
+
+async fn setting(db: &DbPool, key: &str) -> Result<Option<String>> {
+    Ok(db::get_setting(db, key)
+        .await?
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty()))
+}
+
+/// Email sent through an SMTP server
+///
+/// `smtps://` URLs use TLS from the start. On `smtp://` STARTTLS is required
+/// when a username is set, so the password never goes out in the clear, and
+/// used when offered otherwise.
+pub struct EmailChannel {
+    smtp_url: String,
+    from: String,
+    to: Vec<String>,
+    credentials: Option<(String, String)>,
+}
+
+impl EmailChannel {
+    async fn from_settings(db: &DbPool) -> Result<Option<Self>> {
+        let (Some(smtp_url), Some(from), Some(to)) = (
+            setting(db, EMAIL_SMTP_URL_SETTING).await?,
+            setting(db, EMAIL_FROM_SETTING).await?,
+            setting(db, EMAIL_TO_SETTING).await?,
+        ) else {
+            return Ok(None);
+        };
+        if !smtp_url.starts_with("smtp://") && !smtp_url.starts_with("smtps://") {
+            bail!(
+                "{} must be an smtp:// or smtps:// URL",
+                EMAIL_SMTP_URL_SETTING
+            );
+        }
+        let to = to
+            .split(',')
+            .map(|address| address.trim().to_string())
+            .filter(|address| !address.is_empty())
+            .collect();
+        let credentials = match setting(db, EMAIL_USERNAME_SETTING).await? {
+            Some(username) => Some((
+                username,
+                setting(db, EMAIL_PASSWORD_SETTING)
+                    .await?
+                    .unwrap_or_default(),
+            )),
+            None => None,
+        };
+        Ok(Some(Self {
+            smtp_url,
+            from,
+            to,
+            credentials,
+        }))
+    }
+
+    fn message(&self, notification: &Notification) -> String {
+        let header = |value: &str| value.replace(['\r', '\n'], " ");
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: [Steering Center] {}\r\nDate: {}\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            header(&self.from),
+            header(&self.to.join(", ")),
+            header(&notification.title),
+            Utc::now().to_rfc2822(),
+            notification.message.replace('\n', "\r\n"),
+        )
+    }
+}
+
+#[async_trait]
+impl Channel for EmailChannel {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        // curl reads the options on stdin, so the message goes through a private file
+        let path = std::env::temp_dir().join(format!("toru-mail-{}.eml", uuid::Uuid::new_v4()));
+        write_private(&path, self.message(notification).as_bytes()).await?;
+
+        let mut config = CurlConfig::new(&self.smtp_url);
+        config.option("mail-from", &self.from);
+        for to in &self.to {
+            config.option("mail-rcpt", to);
+        }
+        if let Some((username, password)) = &self.credentials {
+            config.option("user", &format!("{}:{}", username, password));
+            config.flag("ssl-reqd");
+        } else {
+            config.flag("ssl");
+        }
+        config.option("upload-file", &path.to_string_lossy());
+
+        let result = config.run().await;
+        let _ = tokio::fs::remove_file(&path).await;
+        result
+    }
+}
+
+/// Message from a Telegram bot to a chat
+pub struct TelegramChannel {
+    bot_token: String,
+    chat_id: String,
+}
+
+impl TelegramChannel {
+    async fn from_settings(db: &DbPool) -> Result<Option<Self>> {
+        let (Some(bot_token), Some(chat_id)) = (
+            setting(db, TELEGRAM_BOT_TOKEN_SETTING).await?,
+            setting(db, TELEGRAM_CHAT_ID_SETTING).await?,
+        ) else {
+            return Ok(None);
+        };
+        Ok(Some(Self { bot_token, chat_id }))
+    }
+}
+
+#[async_trait]
+impl Channel for TelegramChannel {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+        let body = serde_json::json!({
+            "chat_id": self.chat_id,
+            "text": format!("{}\n\n{}", notification.title, notification.message),
+            "disable_web_page_preview": true,
+        });
+        let mut config = CurlConfig::new(&url);
+        config.option("header", "Content-Type: application/json");
+        config.option("data-binary", &body.to_string());
+        config.run().await
+    }
+}
+
+/// Push notification through an ntfy topic
+pub struct NtfyChannel {
+    url: String,
+    token: Option<String>,
+}
+
+impl NtfyChannel {
+    async fn from_settings(db: &DbPool) -> Result<Option<Self>> {
+        let Some(url) = setting(db, NTFY_URL_SETTING).await? else {
+            return Ok(None);
+        };
+        crate::services::plugin_registry::validate_url(&url)?;
+        Ok(Some(Self {
+            url,
+            token: setting(db, NTFY_TOKEN_SETTING).await?,
+        }))
+    }
+}
+
+#[async_trait]
+impl Channel for NtfyChannel {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let (priority, tag) = match notification.level {
+            NotificationLevel::Info => ("default", "information_source"),
+            NotificationLevel::Warning => ("high", "warning"),
+            NotificationLevel::Error => ("urgent", "rotating_light"),
+        };
+        let mut config = CurlConfig::new(&self.url);
+        config.option(
+            "header",
+            &format!("Title: {}", notification.title.replace(['\r', '\n'], " ")),
+        );
+        config.option("header", &format!("Priority: {}", priority));
+        config.option("header", &format!("Tags: {}", tag));
+        if let Some(token) = &self.token {
+            config.option("header", &format!("Authorization: Bearer {}", token));
+        }
+        config.option("data-binary", &notification.message);
+        config.run().await
+    }
+}
+
+/// Message through a Slack incoming webhook
+pub struct SlackChannel {
+    webhook_url: String,
+}
+
+impl SlackChannel {
+    async fn from_settings(db: &DbPool) -> Result<Option<Self>> {
+        let Some(webhook_url) = setting(db, SLACK_WEBHOOK_URL_SETTING).await? else {
+            return Ok(None);
+        };
+        crate::services::plugin_registry::validate_url(&webhook_url)?;
+        Ok(Some(Self { webhook_url }))
+    }
+}
+
+#[async_trait]
+impl Channel for SlackChannel {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let body = serde_json::json!({
+            "text": format!("*{}*\n{}", notification.title, notification.message),
+        });
+        let mut config = CurlConfig::new(&self.webhook_url);
+        config.option("header", "Content-Type: application/json");
+        config.option("data-binary", &body.to_string());
+        config.run().await
+    }
+}
+
+/// Options for a `curl` run, written as a curl config file
+pub(crate) struct CurlConfig(String);
+
+impl CurlConfig {
+    pub(crate) fn new(url: &str) -> Self {
+        let mut config = Self(String::new());
+        config.option("url", url);
+        config
+    }
+
+    fn flag(&mut self, name: &str) {
+        self.0.push_str(name);
+        self.0.push('\n');
+    }
+
+    pub(crate) fn option(&mut self, name: &str, value: &str) {
+        self.0.push_str(&format!("{} = {}\n", name, quote(value)));
+    }
+
+    pub(crate) async fn run(self) -> Result<()> {
+        let mut child = Command::new("curl")
+            .args([
+                "--fail",
+                "--silent",
+                "--show-error",
+                "--proto",
+                "=http,https,smtp,smtps",
+                "--max-time",
+                &SEND_TIMEOUT_SECS.to_string(),
+                "--output",
+                "/dev/null",
+                "--config",
+                "-",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run curl")?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(self.0.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A value as a double-quoted curl config string
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+async fn write_private(path: &std::path::Path, contents: &[u8]) -> Result<()> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    file.write_all(contents).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preferred_channels() {
+        assert_eq!(preferred_channels(None), CHANNELS.to_vec());
+        assert_eq!(
+            preferred_channels(Some(" slack, ntfy ,")),
+            vec!["slack", "ntfy"]
+        );
+        assert!(preferred_channels(Some("none")).is_empty());
+        assert!(preferred_channels(Some("")).is_empty());
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain"), r#""plain""#);
+        assert_eq!(quote("a \"b\"\\\nc"), r#""a \"b\"\\\nc""#);
+    }
+
+    #[test]
+    fn test_email_message() {
+        let channel = EmailChannel {
+            smtp_url: "smtp://mail.example.com".to_string(),
+            from: "toru@example.com".to_string(),
+            to: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+            credentials: None,
+        };
+        let notification = Notification::new(
+            NotificationKind::Security,
+            NotificationLevel::Warning,
+            "Locked\r\nBcc: x@example.com",
+            "line 1\nline 2",
+        );
+        let message = channel.message(&notification);
+        assert!(message.contains("To: a@example.com, b@example.com\r\n"));
+        assert!(message.contains("Subject: [Steering Center] Locked  Bcc: x@example.com\r\n"));
+        assert!(message.ends_with("\r\n\r\nline 1\r\nline 2\r\n"));
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Directory next to a plugin's `bundle.js` whose files are served to browsers
+pub const ASSETS_DIR: &str = "assets";
+
+/// How long browsers may reuse an asset before revalidating it
+pub const ASSET_MAX_AGE_SECS: u64 = 300;
+
+/// A file from a plugin's asset directory, ready to be served
+#[derive(Debug)]
+pub struct PluginAsset {
+    pub content: Vec<u8>,
+    pub content_type: String,
+    /// Changes whenever the file is replaced or modified
+    pub etag: String,
+    /// `Last-Modified` value (IMF-fixdate)
+    pub last_modified: String,
+}
+
+/// Check a relative asset path from a URL or package and normalize it
+///
+/// Rejects empty, absolute and hidden paths, `.`/`..` components and
+/// backslashes, so the result can only name a file below the asset directory.
+pub fn sanitize_path(path: &str) -> Result<String> {
+    let path = path.trim_start_matches('/');
+    if path.is_empty() {
+        return Err(anyhow!("Empty asset path"));
+    }
+    let valid = path.split('/').all(|segment| {
+        !segment.is_empty()
+            && !segment.starts_with('.')
+            && !segment.contains(['\\', '\0'])
+            && !segment.ends_with(':')
+    });
+    if !valid {
+        return Err(anyhow!("Invalid asset path: {}", path));
+    }
+    Ok(path.to_string())
+}
+
+/// Content type for an asset, by extension
+pub fn content_type(path: &Path) -> String {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        // Source maps are JSON; mime_guess doesn't know them
+        Some("map") => "application/json".to_string(),
+        _ => mime_guess::from_path(path)
+            .first_or_octet_stream()
+            .to_string(),
+    }
+}
+
+/// Read an asset of a plugin
+///
+/// Symlinks are followed only as long as the target stays inside the asset
+/// directory.
+///
+/// # Arguments
+/// * `plugin_dir` - The plugin's directory (`<plugins_dir>/<id>`)
+/// * `path` - Asset path relative to the asset directory
+///
+/// # Returns
+/// None if there is no such file
+pub fn read_asset(plugin_dir: &Path, path: &str) -> Result<Option<PluginAsset>> {
+    let path = sanitize_path(path)?;
+    let Ok(root) = plugin_dir.join(ASSETS_DIR).canonicalize() else {
+        return Ok(None);
+    };
+    let Ok(file) = root.join(&path).canonicalize() else {
+        return Ok(None);
+    };
+    if !file.starts_with(&root) || !file.is_file() {
+        return Ok(None);
+    }
+
+    let modified = fs::metadata(&file)
+        .and_then(|meta| meta.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let content = fs::read(&file).with_context(|| format!("Failed to read asset {}", path))?;
+    let modified: DateTime<Utc> = modified.into();
+
+    Ok(Some(PluginAsset {
+        etag: format!(
+            "\"{:x}-{:x}\"",
+            content.len(),
+            modified.timestamp_nanos_opt().unwrap_or_default()
+        ),
+        last_modified: modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        content_type: content_type(&file),
+        content,
+    }))
+}
+
+/// Replace the asset directory of a plugin with the given files
+///
+/// # Arguments
+/// * `plugin_dir` - The plugin's directory (`<plugins_dir>/<id>`)
+/// * `assets` - Relative paths and contents, as read from a package
+pub fn write_assets(plugin_dir: &Path, assets: &[(String, Vec<u8>)]) -> Result<()> {
+    let root: PathBuf = plugin_dir.join(ASSETS_DIR);
+    if root.exists() {
+        fs::remove_dir_all(&root).context("Failed to remove old plugin assets")?;
+    }
+    for (path, content) in assets {
+        let file = root.join(sanitize_path(path)?);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).context("Failed to create plugin asset directory")?;
+        }
+        fs::write(&file, content).with_context(|| format!("Failed to write asset {}", path))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_path() {
+        assert_eq!(sanitize_path("css/app.css").unwrap(), "css/app.css");
+        assert_eq!(sanitize_path("/logo.png").unwrap(), "logo.png");
+        for path in [
+            "",
+            "../hello.binary",
+            "css/../../bundle.js",
+            "./app.css",
+            "css//app.css",
+            ".metadata/config.json",
+            "..\\hello.binary",
+            "C:/windows",
+        ] {
+            assert!(sanitize_path(path).is_err(), "{:?} was accepted", path);
+        }
+    }
+
+    #[test]
+    fn test_read_asset() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("secret.txt");
+        fs::write(&outside, "secret").unwrap();
+        write_assets(
+            dir.path(),
+            &[
+                ("app.css".to_string(), b"body {}".to_vec()),
+                ("js/app.js.map".to_string(), b"{}".to_vec()),
+                ("plugin.wasm".to_string(), b"\0asm".to_vec()),
+            ],
+        )
+        .unwrap();
+
+        let css = read_asset(dir.path(), "app.css").unwrap().unwrap();
+        assert_eq!(css.content, b"body {}");
+        assert_eq!(css.content_type, "text/css");
+        assert!(css.etag.starts_with('"'));
+        let map = read_asset(dir.path(), "js/app.js.map").unwrap().unwrap();
+        assert_eq!(map.content_type, "application/json");
+        let wasm = read_asset(dir.path(), "plugin.wasm").unwrap().unwrap();
+        assert_eq!(wasm.content_type, "application/wasm");
+
+        assert!(read_asset(dir.path(), "missing.css").unwrap().is_none());
+        assert!(read_asset(dir.path(), "js").unwrap().is_none());
+        assert!(read_asset(dir.path(), "../secret.txt").is_err());
+
+        // A symlink pointing out of the asset directory isn't followed
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&outside, dir.path().join("assets/link.txt")).unwrap();
+            assert!(read_asset(dir.path(), "link.txt").unwrap().is_none());
+        }
+    }
+}
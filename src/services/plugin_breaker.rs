@@ -0,0 +1,124 @@
+//! Circuit breaker for the HTTP requests forwarded to a plugin
+//!
+//! After `FAILURE_THRESHOLD` timeouts in a row the breaker opens: requests fail
+//! fast with `CircuitOpen` instead of waiting out the timeout, and the plugin is
+//! reported unhealthy. Once `OPEN_PERIOD` has passed requests go through again;
+//! an answer closes the breaker, another timeout opens it for a new period.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Timeouts in a row after which the breaker opens
+pub const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an open breaker rejects requests
+pub const OPEN_PERIOD: Duration = Duration::from_secs(30);
+
+/// Error of a request rejected because the plugin's breaker is open
+#[derive(Debug)]
+pub struct CircuitOpen(pub String);
+
+impl std::fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Plugin {} keeps timing out; requests are paused", self.0)
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    open_period: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    timeouts: u32,
+    open_until: Option<Instant>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(FAILURE_THRESHOLD, OPEN_PERIOD)
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, open_period: Duration) -> Self {
+        Self {
+            threshold,
+            open_period,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Whether requests are currently rejected
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    /// The plugin answered; close the breaker
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    /// Count a request the plugin didn't answer in time
+    ///
+    /// # Returns
+    /// true if this timeout opened the breaker
+    pub fn record_timeout(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.timeouts += 1;
+        if state.timeouts < self.threshold {
+            return false;
+        }
+        let now = Instant::now();
+        let was_open = state.open_until.is_some_and(|open_until| now < open_until);
+        state.open_until = Some(now + self.open_period);
+        !was_open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        assert!(!breaker.record_timeout());
+        assert!(!breaker.record_timeout());
+        assert!(!breaker.is_open());
+
+        assert!(breaker.record_timeout());
+        assert!(breaker.is_open());
+        // Requests that were already in flight don't reopen it
+        assert!(!breaker.record_timeout());
+    }
+
+    #[test]
+    fn test_success_resets_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.record_timeout();
+        breaker.record_success();
+        assert!(!breaker.record_timeout());
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_reopens_after_period() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        assert!(breaker.record_timeout());
+        // The period is over: requests go through, and one more timeout reopens it
+        assert!(!breaker.is_open());
+        assert!(breaker.record_timeout());
+
+        breaker.record_success();
+        assert!(!breaker.is_open());
+    }
+}
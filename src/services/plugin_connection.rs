@@ -0,0 +1,557 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use toru_plugin_api::{
+    HttpBodyChunk, Message, MessagePayload, PluginProtocol, WireFormat, WsFrame, WsFrameKind,
+};
+
+type PendingRequests = Arc<Mutex<HashMap<String, oneshot::Sender<Message>>>>;
+type OpenStreams = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<HttpBodyChunk>>>>;
+type OpenSockets = Arc<Mutex<HashMap<String, mpsc::UnboundedSender<WsFrame>>>>;
+type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Channel for the messages plugins send on their own (events, calls to other
+/// plugins), tagged with the ID of the sending plugin
+pub type PluginMessageSender = mpsc::UnboundedSender<(String, Message)>;
+
+/// Error of a request the plugin didn't answer within its timeout
+#[derive(Debug)]
+pub struct RequestTimeout(pub Duration);
+
+impl std::fmt::Display for RequestTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Plugin response timeout after {}s", self.0.as_secs())
+    }
+}
+
+impl std::error::Error for RequestTimeout {}
+
+/// Long-lived connection to a plugin socket
+///
+/// Requests are written as they come and a background reader matches each
+/// response to its waiting caller by request ID, so several requests can be
+/// in flight on the same connection at once. Body chunks of streamed responses
+/// are passed on to the `BodyStream` of their request, WebSocket frames to
+/// the `PluginSocket` they belong to, and messages the plugin sends on its own
+/// to the supervisor.
+///
+/// Messages are written in JSON until the plugin switches the connection to
+/// another wire format it was offered in `init`.
+pub struct PluginConnection {
+    writer: tokio::sync::Mutex<BoxedWriter>,
+    format: Arc<Mutex<WireFormat>>,
+    pending: PendingRequests,
+    streams: OpenStreams,
+    sockets: OpenSockets,
+    closed: Arc<AtomicBool>,
+    reader: JoinHandle<()>,
+}
+
+impl PluginConnection {
+    /// Wrap a connected stream and start reading responses from it
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        Self::start(stream, None)
+    }
+
+    /// Like `new`, passing the messages the plugin sends on its own to `incoming`
+    pub fn with_incoming<S>(stream: S, plugin_id: &str, incoming: PluginMessageSender) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        Self::start(stream, Some((plugin_id.to_string(), incoming)))
+    }
+
+    fn start<S>(stream: S, incoming: Option<(String, PluginMessageSender)>) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (mut read_half, write_half) = tokio::io::split(stream);
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let streams: OpenStreams = Arc::new(Mutex::new(HashMap::new()));
+        let sockets: OpenSockets = Arc::new(Mutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        let format = Arc::new(Mutex::new(WireFormat::Json));
+
+        let reader = {
+            let format = Arc::clone(&format);
+            let pending = Arc::clone(&pending);
+            let streams = Arc::clone(&streams);
+            let sockets = Arc::clone(&sockets);
+            let closed = Arc::clone(&closed);
+            tokio::spawn(async move {
+                let mut protocol = PluginProtocol::new();
+                loop {
+                    let message = match protocol.read_message(&mut read_half).await {
+                        Ok(message) => message,
+                        Err(e) => {
+                            debug!("Plugin connection closed: {}", e);
+                            break;
+                        }
+                    };
+
+                    if let MessagePayload::HttpChunk {
+                        request_id,
+                        payload,
+                    } = &message.payload
+                    {
+                        let mut streams = streams.lock().unwrap();
+                        let done = payload.done;
+                        // A receiver that went away (client disconnected) just drops the rest
+                        if let Some(sink) = streams.get(request_id) {
+                            let _ = sink.send(payload.clone());
+                        }
+                        if done {
+                            streams.remove(request_id);
+                        }
+                        continue;
+                    }
+
+                    if let MessagePayload::WsFrame { socket_id, payload } = &message.payload {
+                        let mut sockets = sockets.lock().unwrap();
+                        if let Some(sink) = sockets.get(socket_id) {
+                            let _ = sink.send(payload.clone());
+                        }
+                        if payload.kind == WsFrameKind::Close {
+                            sockets.remove(socket_id);
+                        }
+                        continue;
+                    }
+
+                    if let MessagePayload::Lifecycle {
+                        action,
+                        format: Some(chosen),
+                        ..
+                    } = &message.payload
+                    {
+                        if action == "wire_format" {
+                            debug!("Plugin switched the connection to {:?}", chosen);
+                            *format.lock().unwrap() = *chosen;
+                            continue;
+                        }
+                    }
+
+                    let from_plugin = match &message.payload {
+                        // `migrated` answers a request of ours
+                        MessagePayload::Lifecycle { action, .. } => action != "migrated",
+                        MessagePayload::Event { .. }
+                        | MessagePayload::CallPlugin { .. }
+                        | MessagePayload::Exec { .. }
+                        | MessagePayload::SystemInfo { .. }
+                        | MessagePayload::Notify { .. } => true,
+                        _ => false,
+                    };
+                    if from_plugin {
+                        match &incoming {
+                            Some((plugin_id, sink)) => {
+                                let _ = sink.send((plugin_id.clone(), message));
+                            }
+                            None => debug!("Dropping plugin message: {}", message.message_type),
+                        }
+                        continue;
+                    }
+
+                    let waiter = response_request_id(&message)
+                        .and_then(|id| pending.lock().unwrap().remove(id));
+                    match waiter {
+                        Some(waiter) => {
+                            let _ = waiter.send(message);
+                        }
+                        None => warn!(
+                            "Dropping unsolicited plugin message: {}",
+                            message.message_type
+                        ),
+                    }
+                }
+
+                // Dropping the senders wakes every caller still waiting
+                closed.store(true, Ordering::SeqCst);
+                pending.lock().unwrap().clear();
+                streams.lock().unwrap().clear();
+                sockets.lock().unwrap().clear();
+            })
+        };
+
+        Self {
+            writer: tokio::sync::Mutex::new(Box::new(write_half) as BoxedWriter),
+            format,
+            pending,
+            streams,
+            sockets,
+            closed,
+            reader,
+        }
+    }
+
+    /// Whether the plugin side has closed the connection
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Format messages to the plugin are written in
+    pub fn format(&self) -> WireFormat {
+        *self.format.lock().unwrap()
+    }
+
+    /// Send a message that expects no response (e.g. lifecycle messages)
+    pub async fn send(&self, message: &Message) -> Result<()> {
+        if self.is_closed() {
+            return Err(anyhow!("Plugin connection is closed"));
+        }
+        let mut writer = self.writer.lock().await;
+        PluginProtocol::with_format(self.format())
+            .write_message(&mut *writer, message)
+            .await
+            .context("Failed to write message to plugin")
+    }
+
+    /// Send a request and wait for the response carrying the same request ID
+    pub async fn request(&self, message: &Message, timeout: Duration) -> Result<Message> {
+        let request_id = message
+            .request_id
+            .clone()
+            .context("Request message has no request ID")?;
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id.clone(), tx);
+
+        if let Err(e) = self.send(message).await {
+            self.pending.lock().unwrap().remove(&request_id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(anyhow!("Plugin connection closed before responding")),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&request_id);
+                Err(RequestTimeout(timeout).into())
+            }
+        }
+    }
+
+    /// Send a request whose response body may be streamed after the response
+    ///
+    /// Chunks are collected from the moment the request is sent, so none are lost
+    /// between the response and the caller starting to read. Only `timeout` for the
+    /// response itself applies; the stream can stay open indefinitely (e.g. SSE).
+    pub async fn request_stream(
+        &self,
+        message: &Message,
+        timeout: Duration,
+    ) -> Result<(Message, BodyStream)> {
+        let request_id = message
+            .request_id
+            .clone()
+            .context("Request message has no request ID")?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.streams.lock().unwrap().insert(request_id.clone(), tx);
+        let body = BodyStream {
+            request_id,
+            receiver: rx,
+            streams: Arc::clone(&self.streams),
+            done: false,
+        };
+
+        let response = self.request(message, timeout).await?;
+        Ok((response, body))
+    }
+
+    /// Start receiving the frames the plugin sends on a WebSocket
+    ///
+    /// Frames from the browser are sent with `send` as `ws_frame` messages; the
+    /// first one should be `open`.
+    pub fn open_socket(&self, socket_id: &str) -> PluginSocket {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.sockets
+            .lock()
+            .unwrap()
+            .insert(socket_id.to_string(), tx);
+        PluginSocket {
+            socket_id: socket_id.to_string(),
+            receiver: rx,
+            sockets: Arc::clone(&self.sockets),
+        }
+    }
+}
+
+/// Body chunks of one streamed response, in order
+///
+/// Dropping it stops collecting chunks for the request.
+pub struct BodyStream {
+    request_id: String,
+    receiver: mpsc::UnboundedReceiver<HttpBodyChunk>,
+    streams: OpenStreams,
+    done: bool,
+}
+
+impl BodyStream {
+    /// Next piece of the body
+    ///
+    /// # Returns
+    /// `Ok(None)` after the last chunk, an error if the connection closed before it
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.done {
+            return Ok(None);
+        }
+        match self.receiver.recv().await {
+            Some(chunk) => {
+                self.done = chunk.done;
+                Ok(Some(chunk.bytes()?))
+            }
+            None => {
+                self.done = true;
+                Err(anyhow!(
+                    "Plugin connection closed before the body was complete"
+                ))
+            }
+        }
+    }
+
+    /// Read the rest of the body
+    pub async fn collect(mut self) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        while let Some(data) = self.next_chunk().await? {
+            body.extend_from_slice(&data);
+        }
+        Ok(body)
+    }
+}
+
+impl Drop for BodyStream {
+    fn drop(&mut self) {
+        self.streams.lock().unwrap().remove(&self.request_id);
+    }
+}
+
+/// Frames a plugin sends on one relayed WebSocket
+///
+/// Dropping it stops collecting frames for the socket.
+pub struct PluginSocket {
+    socket_id: String,
+    receiver: mpsc::UnboundedReceiver<WsFrame>,
+    sockets: OpenSockets,
+}
+
+impl PluginSocket {
+    /// Next frame from the plugin
+    ///
+    /// # Returns
+    /// `None` once the plugin closed the socket or its connection
+    pub async fn recv(&mut self) -> Option<WsFrame> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for PluginSocket {
+    fn drop(&mut self) {
+        self.sockets.lock().unwrap().remove(&self.socket_id);
+    }
+}
+
+impl Drop for PluginConnection {
+    fn drop(&mut self) {
+        self.reader.abort();
+        // Without the reader no more chunks or frames arrive; end the open bodies and sockets
+        self.streams.lock().unwrap().clear();
+        self.sockets.lock().unwrap().clear();
+    }
+}
+
+impl std::fmt::Debug for PluginConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginConnection")
+            .field("closed", &self.is_closed())
+            .field("format", &self.format())
+            .field("pending", &self.pending.lock().unwrap().len())
+            .field("streams", &self.streams.lock().unwrap().len())
+            .field("sockets", &self.sockets.lock().unwrap().len())
+            .finish()
+    }
+}
+
+/// Request ID a response message answers
+fn response_request_id(message: &Message) -> Option<&str> {
+    message.request_id.as_deref().or(match &message.payload {
+        MessagePayload::Http { request_id, .. }
+        | MessagePayload::Kv { request_id, .. }
+        | MessagePayload::HttpChunk { request_id, .. }
+        | MessagePayload::Pong { request_id }
+        | MessagePayload::HttpResponse { request_id, .. }
+        | MessagePayload::KvResponse { request_id, .. } => Some(request_id.as_str()),
+        MessagePayload::Lifecycle { .. }
+        | MessagePayload::WsFrame { .. }
+        | MessagePayload::Event { .. }
+        | MessagePayload::CallPlugin { .. }
+        | MessagePayload::CallResult { .. }
+        | MessagePayload::Exec { .. }
+        | MessagePayload::ExecResult { .. }
+        | MessagePayload::SystemInfo { .. }
+        | MessagePayload::SystemInfoResult { .. }
+        | MessagePayload::Notify { .. }
+        | MessagePayload::Scheduled { .. }
+        | MessagePayload::Ping { .. } => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toru_plugin_api::HttpRequest;
+
+    fn http_message(request_id: &str, body: &str) -> Message {
+        Message::new_http(
+            request_id.to_string(),
+            HttpRequest {
+                method: "GET".to_string(),
+                path: "/".to_string(),
+                headers: HashMap::new(),
+                body: Some(body.to_string()),
+                body_encoding: Default::default(),
+            },
+        )
+    }
+
+    fn body(message: &Message) -> Option<&str> {
+        match &message.payload {
+            MessagePayload::Http { payload, .. } => payload.body.as_deref(),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_responses_matched_out_of_order() {
+        let (host, mut plugin) = tokio::io::duplex(64 * 1024);
+        let connection = PluginConnection::new(host);
+
+        // Fake plugin: read two requests, answer them in reverse order
+        tokio::spawn(async move {
+            let mut protocol = PluginProtocol::new();
+            let first = protocol.read_message(&mut plugin).await.unwrap();
+            let second = protocol.read_message(&mut plugin).await.unwrap();
+            for request in [second, first] {
+                let id = request.request_id.unwrap();
+                let reply = http_message(&id, &format!("reply to {}", id));
+                protocol.write_message(&mut plugin, &reply).await.unwrap();
+            }
+        });
+
+        let timeout = Duration::from_secs(5);
+        let (request_a, request_b) = (http_message("a", ""), http_message("b", ""));
+        let (a, b) = tokio::join!(
+            connection.request(&request_a, timeout),
+            connection.request(&request_b, timeout),
+        );
+        assert_eq!(body(&a.unwrap()), Some("reply to a"));
+        assert_eq!(body(&b.unwrap()), Some("reply to b"));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_body_chunks_follow_response() {
+        let (host, mut plugin) = tokio::io::duplex(64 * 1024);
+        let connection = PluginConnection::new(host);
+
+        // Fake plugin: answer, then send the body in chunks right away
+        tokio::spawn(async move {
+            let mut protocol = PluginProtocol::new();
+            let request = protocol.read_message(&mut plugin).await.unwrap();
+            let id = request.request_id.unwrap();
+            let head = http_message(&id, r#"{"status":200,"headers":{},"stream":true}"#);
+            protocol.write_message(&mut plugin, &head).await.unwrap();
+            for (data, done) in [("a", false), ("b", false), ("c", true)] {
+                let chunk = Message::new_http_chunk(id.clone(), data.as_bytes(), done);
+                protocol.write_message(&mut plugin, &chunk).await.unwrap();
+            }
+        });
+
+        let (response, chunks) = connection
+            .request_stream(&http_message("s", ""), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(body(&response).unwrap().contains("\"stream\":true"));
+        assert_eq!(chunks.collect().await.unwrap(), b"abc");
+        assert!(connection.streams.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_socket_frames_routed_by_socket_id() {
+        let (host, mut plugin) = tokio::io::duplex(64 * 1024);
+        let connection = PluginConnection::new(host);
+        let mut socket = connection.open_socket("s1");
+
+        // Fake plugin: a frame for an unknown socket, then two for ours
+        tokio::spawn(async move {
+            let protocol = PluginProtocol::new();
+            for (socket_id, frame) in [
+                ("other", WsFrame::text("lost")),
+                ("s1", WsFrame::text("hi")),
+                ("s1", WsFrame::close()),
+            ] {
+                let message = Message::new_ws_frame(socket_id.to_string(), frame);
+                protocol.write_message(&mut plugin, &message).await.unwrap();
+            }
+        });
+
+        let frame = socket.recv().await.unwrap();
+        assert_eq!(frame.data.as_deref(), Some("hi"));
+        assert_eq!(socket.recv().await.unwrap().kind, WsFrameKind::Close);
+        // The close frame ends the socket
+        assert!(socket.recv().await.is_none());
+        assert!(connection.sockets.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_closed_connection_fails_pending_requests() {
+        let (host, plugin) = tokio::io::duplex(1024);
+        let connection = PluginConnection::new(host);
+        drop(plugin);
+
+        let result = connection
+            .request(&http_message("a", ""), Duration::from_secs(5))
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_plugin_switches_wire_format() {
+        let (host, mut plugin) = tokio::io::duplex(64 * 1024);
+        let connection = PluginConnection::new(host);
+
+        // Fake plugin: switch to MessagePack, then echo one request
+        tokio::spawn(async move {
+            let mut protocol = PluginProtocol::new();
+            let switch = Message::new_wire_format(WireFormat::MessagePack);
+            protocol.write_message(&mut plugin, &switch).await.unwrap();
+            protocol.set_format(WireFormat::MessagePack);
+
+            let request = protocol.read_message(&mut plugin).await.unwrap();
+            let id = request.request_id.unwrap();
+            let reply = http_message(&id, "packed");
+            protocol.write_message(&mut plugin, &reply).await.unwrap();
+        });
+
+        for _ in 0..50 {
+            if connection.format() == WireFormat::MessagePack {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(connection.format(), WireFormat::MessagePack);
+
+        let response = connection
+            .request(&http_message("a", ""), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(body(&response), Some("packed"));
+    }
+}
@@ -0,0 +1,136 @@
+//! What a crashed plugin left behind: how it exited and the end of its stderr
+//!
+//! The stderr reader of every spawned plugin keeps the last `STDERR_TAIL_LINES`
+//! lines. When the health monitor finds the plugin crashed, they are saved with
+//! the exit status in a `CrashReport`, shown in the plugin status and attached to
+//! the `crashed` event.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::process::ExitStatus;
+use std::sync::Mutex;
+
+/// Lines of stderr kept for a crash report
+pub const STDERR_TAIL_LINES: usize = 50;
+
+/// Longer stderr lines are cut to this many bytes
+const MAX_LINE_LEN: usize = 1024;
+
+/// The last lines a plugin wrote to stderr
+#[derive(Debug)]
+pub struct StderrTail {
+    capacity: usize,
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl Default for StderrTail {
+    fn default() -> Self {
+        Self::new(STDERR_TAIL_LINES)
+    }
+}
+
+impl StderrTail {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Add output read from stderr; the oldest lines make room for new ones
+    pub fn push(&self, output: &str) {
+        let mut lines = self.lines.lock().unwrap();
+        for line in output.lines().filter(|line| !line.trim().is_empty()) {
+            if lines.len() == self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(truncate(line, MAX_LINE_LEN).to_string());
+        }
+    }
+
+    /// The kept lines, oldest first
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Why and how a plugin stopped, as last seen by the health monitor
+#[derive(Debug, Clone, Serialize)]
+pub struct CrashReport {
+    pub at: DateTime<Utc>,
+    /// `exited`, `unhealthy` (its socket went away) or `unresponsive` (missed pings)
+    pub reason: String,
+    pub exit_code: Option<i32>,
+    /// Signal that killed the process, on Unix
+    pub signal: Option<i32>,
+    /// Last lines of stderr, oldest first
+    pub stderr: Vec<String>,
+}
+
+impl CrashReport {
+    pub fn new(reason: &str, status: Option<ExitStatus>, stderr: &StderrTail) -> Self {
+        Self {
+            at: Utc::now(),
+            reason: reason.to_string(),
+            exit_code: status.and_then(|status| status.code()),
+            signal: status.and_then(exit_signal),
+            stderr: stderr.lines(),
+        }
+    }
+
+    /// Details of the `crashed` event
+    pub fn event_details(&self) -> serde_json::Value {
+        serde_json::json!({
+            "reason": self.reason,
+            "exit_code": self.exit_code,
+            "signal": self.signal,
+            "stderr": self.stderr,
+        })
+    }
+}
+
+#[cfg(unix)]
+fn exit_signal(status: ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Cut a string to at most `max` bytes on a character boundary
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_last_lines() {
+        let tail = StderrTail::new(3);
+        tail.push("one\ntwo\n\n");
+        tail.push("three\nfour\n");
+        assert_eq!(tail.lines(), ["two", "three", "four"]);
+    }
+
+    #[test]
+    fn test_long_lines_are_cut() {
+        let tail = StderrTail::new(1);
+        tail.push(&"é".repeat(MAX_LINE_LEN));
+        let line = &tail.lines()[0];
+        assert!(line.len() <= MAX_LINE_LEN);
+        assert!(line.chars().all(|c| c == 'é'));
+    }
+}
@@ -0,0 +1,173 @@
+//! Dependencies plugins declare on other plugins
+//!
+//! A dependency is written `<plugin>` or `<plugin>>=<version>` in the metadata.
+//! Plugins start after the plugins they depend on; a plugin whose dependencies
+//! are missing, disabled or too old is not started.
+
+use anyhow::{anyhow, Result};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Error of a plugin kept from starting by its dependencies
+#[derive(Debug)]
+pub struct DependencyProblem {
+    pub plugin_id: String,
+    pub problems: Vec<String>,
+}
+
+impl std::fmt::Display for DependencyProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Plugin {} can't start: {}",
+            self.plugin_id,
+            self.problems.join("; ")
+        )
+    }
+}
+
+impl std::error::Error for DependencyProblem {}
+
+/// A plugin another plugin needs, and the oldest version it works with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub id: String,
+    pub min_version: Option<String>,
+}
+
+impl Dependency {
+    /// Parse `metrics` or `metrics>=1.2.0`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (id, min_version) = match spec.split_once(">=") {
+            Some((id, version)) => (id.trim(), Some(version.trim())),
+            None => (spec.trim(), None),
+        };
+        if id.is_empty() || min_version == Some("") {
+            return Err(anyhow!("Invalid dependency: {:?}", spec));
+        }
+        Ok(Self {
+            id: id.to_string(),
+            min_version: min_version.map(String::from),
+        })
+    }
+}
+
+/// Compare dotted version numbers, e.g. `1.10.0` > `1.9`
+///
+/// Missing parts count as 0 and a `-pre` or `+build` suffix is ignored; parts
+/// that aren't numbers count as 0 as well.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn parts(version: &str) -> Vec<u64> {
+        let core = version.trim().trim_start_matches('v');
+        let core = core.split(['-', '+']).next().unwrap_or_default();
+        core.split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    }
+
+    let (a, b) = (parts(a), parts(b));
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| {
+            let x = a.get(i).copied().unwrap_or(0);
+            let y = b.get(i).copied().unwrap_or(0);
+            x.cmp(&y)
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Order plugins so each starts after the plugins it depends on
+///
+/// `plugins` maps each plugin to the IDs it depends on; dependencies outside the
+/// map are ignored. Plugins without an order between them start alphabetically.
+///
+/// # Returns
+/// The start order and, separately, the plugins that can't start because they
+/// are in a dependency cycle or depend on one
+pub fn start_order(plugins: &BTreeMap<String, Vec<String>>) -> (Vec<String>, Vec<String>) {
+    let mut waiting_on: BTreeMap<&str, BTreeSet<&str>> = plugins
+        .iter()
+        .map(|(id, dependencies)| {
+            let known = dependencies
+                .iter()
+                .map(String::as_str)
+                .filter(|dependency| plugins.contains_key(*dependency))
+                .collect();
+            (id.as_str(), known)
+        })
+        .collect();
+
+    let mut order = Vec::with_capacity(plugins.len());
+    while let Some(next) = waiting_on
+        .iter()
+        .find(|(_, dependencies)| dependencies.is_empty())
+        .map(|(id, _)| *id)
+    {
+        waiting_on.remove(next);
+        for dependencies in waiting_on.values_mut() {
+            dependencies.remove(next);
+        }
+        order.push(next.to_string());
+    }
+
+    let cyclic = waiting_on.into_keys().map(String::from).collect();
+    (order, cyclic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dependency() {
+        assert_eq!(
+            Dependency::parse("metrics").unwrap(),
+            Dependency {
+                id: "metrics".to_string(),
+                min_version: None
+            }
+        );
+        assert_eq!(
+            Dependency::parse("metrics >= 1.2.0").unwrap().min_version,
+            Some("1.2.0".to_string())
+        );
+        assert!(Dependency::parse("").is_err());
+        assert!(Dependency::parse("metrics>=").is_err());
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2", "1.2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("v2.0.0-beta", "2.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.9.9", "1.0.0"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_start_order() {
+        let plugins = BTreeMap::from([
+            ("alerts".to_string(), vec!["metrics".to_string()]),
+            ("dashboard".to_string(), vec!["alerts".to_string()]),
+            ("metrics".to_string(), vec!["missing".to_string()]),
+            ("zeta".to_string(), Vec::new()),
+        ]);
+        let (order, cyclic) = start_order(&plugins);
+        assert_eq!(order, ["metrics", "alerts", "dashboard", "zeta"]);
+        assert!(cyclic.is_empty());
+    }
+
+    #[test]
+    fn test_start_order_cycle() {
+        let plugins = BTreeMap::from([
+            ("a".to_string(), vec!["b".to_string()]),
+            ("b".to_string(), vec!["a".to_string()]),
+            ("c".to_string(), vec!["a".to_string()]),
+            ("d".to_string(), Vec::new()),
+        ]);
+        let (order, cyclic) = start_order(&plugins);
+        assert_eq!(order, ["d"]);
+        // `c` waits on the cycle, so it can't start either
+        assert_eq!(cyclic, ["a", "b", "c"]);
+    }
+}
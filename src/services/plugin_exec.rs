@@ -0,0 +1,134 @@
+//! Quick actions and scripts run on behalf of plugins
+//!
+//! A plugin needs the `exec:<script>` capability for the script it runs, whether
+//! it names the script directly or through a quick action. The task runs like one
+//! started from the dashboard: it gets the quick action's limits, concurrency
+//! mode and environment and is recorded in the task history.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+use toru_plugin_api::{PluginExec, PluginExecResult};
+
+use crate::db::{self, ConcurrencyMode, DbPool, QuickAction};
+use crate::services::action_params;
+use crate::services::executor::{self, ResourceLimits};
+
+/// A script an `exec` request resolved to
+#[derive(Debug, Clone)]
+pub struct ExecTarget {
+    /// File name in the scripts directory
+    pub script_name: String,
+    /// The quick action that was named, or the one registered for the script
+    pub action: Option<QuickAction>,
+}
+
+impl ExecTarget {
+    /// Capability a plugin needs to run this script
+    pub fn capability(&self) -> String {
+        format!("exec:{}", self.script_name)
+    }
+}
+
+/// Find the script an `exec` request names
+pub async fn resolve(db: &DbPool, exec: &PluginExec) -> Result<ExecTarget> {
+    let actions = db::get_quick_actions(db).await?;
+    match (&exec.action, &exec.script) {
+        (Some(id), None) => {
+            let action = actions
+                .into_iter()
+                .find(|a| &a.id == id)
+                .ok_or_else(|| anyhow!("Quick action {} not found", id))?;
+            Ok(ExecTarget {
+                script_name: action.script_path.clone(),
+                action: Some(action),
+            })
+        }
+        (None, Some(script)) => {
+            validate_script_name(script)?;
+            Ok(ExecTarget {
+                script_name: script.clone(),
+                action: actions.into_iter().find(|a| &a.script_path == script),
+            })
+        }
+        _ => Err(anyhow!("Exactly one of action and script must be given")),
+    }
+}
+
+/// Start the script and report the task it runs as
+///
+/// The result's status is `queued` when the quick action queues runs and the
+/// script is already running; a busy script in reject mode is an error.
+pub async fn start(db: &DbPool, target: ExecTarget) -> Result<PluginExecResult> {
+    let scripts_dir = db::get_setting(db, "scripts_dir")
+        .await?
+        .unwrap_or_else(|| "./scripts".to_string());
+
+    let mut limits = ResourceLimits::from_settings(db).await;
+    let mut concurrency = ConcurrencyMode::default();
+    let mut env = HashMap::new();
+    if let Some(ref action) = target.action {
+        limits = limits.with_action_overrides(action);
+        concurrency = action.concurrency;
+        // Plugins can't submit parameters; they get the defaults
+        env = action_params::run_env(action, &HashMap::new())?;
+    }
+
+    // The first event tells whether the run started or was queued
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let task_id = uuid::Uuid::new_v4().to_string();
+    executor::run_script_task(
+        format!("{}/{}", scripts_dir, target.script_name),
+        task_id.clone(),
+        target.script_name,
+        db.clone(),
+        executor::create_task_registry(),
+        limits,
+        concurrency,
+        env,
+        Some(tx),
+    )
+    .await?;
+
+    let status = match rx.try_recv() {
+        Ok(event) if event.r#type == "queued" => "queued",
+        _ => "started",
+    };
+    Ok(PluginExecResult {
+        task_id: Some(task_id),
+        status: Some(status.to_string()),
+        error: None,
+    })
+}
+
+/// Scripts are named by file name; paths could leave the scripts directory
+fn validate_script_name(script: &str) -> Result<()> {
+    if script.is_empty() || script.starts_with('.') || script.contains('/') || script.contains('\\')
+    {
+        return Err(anyhow!("Invalid script name: {}", script));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_script_name() {
+        assert!(validate_script_name("backup.sh").is_ok());
+        assert!(validate_script_name("").is_err());
+        assert!(validate_script_name("../etc/passwd").is_err());
+        assert!(validate_script_name("sub/backup.sh").is_err());
+        assert!(validate_script_name(".hidden").is_err());
+    }
+
+    #[test]
+    fn test_capability_names_script() {
+        let target = ExecTarget {
+            script_name: "backup.sh".to_string(),
+            action: None,
+        };
+        assert_eq!(target.capability(), "exec:backup.sh");
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Duration;
+
+use toru_plugin_api::ScheduledJob;
+
+/// How often the job scheduler checks for due plugin jobs
+pub const JOB_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most jobs a single plugin may register
+pub const MAX_JOBS_PER_PLUGIN: usize = 32;
+
+/// Longest job ID a plugin may register
+const MAX_JOB_ID_LEN: usize = 64;
+
+/// A job a plugin registered and when it is due next
+#[derive(Debug, Clone)]
+pub struct PluginJob {
+    pub id: String,
+    pub schedule: String,
+    cron: Schedule,
+    /// `None` once the schedule has no more runs
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+impl PluginJob {
+    /// Whether the job is due at `now`; a due job moves on to its next run
+    ///
+    /// Runs missed while the scheduler was busy are skipped, not caught up.
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> bool {
+        match self.next_run {
+            Some(next_run) if next_run <= now => {
+                self.next_run = self.cron.after(&now).next();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Validate the jobs a plugin registers and work out their first runs
+pub fn parse_jobs(jobs: &[ScheduledJob], now: DateTime<Utc>) -> Result<Vec<PluginJob>> {
+    if jobs.len() > MAX_JOBS_PER_PLUGIN {
+        return Err(anyhow!(
+            "A plugin may register at most {} jobs",
+            MAX_JOBS_PER_PLUGIN
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    jobs.iter()
+        .map(|job| {
+            if job.id.is_empty() || job.id.len() > MAX_JOB_ID_LEN {
+                return Err(anyhow!(
+                    "Job IDs must be 1 to {} characters",
+                    MAX_JOB_ID_LEN
+                ));
+            }
+            if !seen.insert(job.id.as_str()) {
+                return Err(anyhow!("Job {} is registered twice", job.id));
+            }
+            let cron = parse_schedule(&job.schedule)
+                .with_context(|| format!("Invalid schedule for job {}", job.id))?;
+            Ok(PluginJob {
+                id: job.id.clone(),
+                schedule: job.schedule.clone(),
+                next_run: cron.after(&now).next(),
+                cron,
+            })
+        })
+        .collect()
+}
+
+/// Parse a cron expression, reading five fields as `min hour day month weekday`
+fn parse_schedule(expression: &str) -> Result<Schedule> {
+    let expression = expression.trim();
+    let expression = match expression.split_whitespace().count() {
+        5 => format!("0 {}", expression),
+        6 | 7 => expression.to_string(),
+        _ => return Err(anyhow!("Expected 5 to 7 fields in {:?}", expression)),
+    };
+    Schedule::from_str(&expression).map_err(|e| anyhow!("{}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn job(id: &str, schedule: &str) -> ScheduledJob {
+        ScheduledJob {
+            id: id.to_string(),
+            schedule: schedule.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_jobs() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 30).unwrap();
+        let jobs = parse_jobs(
+            &[job("hourly", "0 * * * *"), job("fast", "*/10 * * * * *")],
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            jobs[0].next_run,
+            Some(Utc.with_ymd_and_hms(2026, 1, 1, 13, 0, 0).unwrap())
+        );
+        assert_eq!(
+            jobs[1].next_run,
+            Some(Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 40).unwrap())
+        );
+
+        assert!(parse_jobs(&[job("bad", "every minute")], now).is_err());
+        assert!(parse_jobs(&[job("", "0 * * * *")], now).is_err());
+        assert!(parse_jobs(&[job("a", "0 * * * *"), job("a", "5 * * * *")], now).is_err());
+    }
+
+    #[test]
+    fn test_take_due_moves_to_next_run() {
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+        let mut jobs = parse_jobs(&[job("minutely", "* * * * *")], start).unwrap();
+        let job = &mut jobs[0];
+
+        assert!(!job.take_due(start));
+        // Three minutes late: runs once and skips the missed runs
+        let late = Utc.with_ymd_and_hms(2026, 1, 1, 12, 3, 30).unwrap();
+        assert!(job.take_due(late));
+        assert!(!job.take_due(late));
+        assert_eq!(
+            job.next_run,
+            Some(Utc.with_ymd_and_hms(2026, 1, 1, 12, 4, 0).unwrap())
+        );
+    }
+}
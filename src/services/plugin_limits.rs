@@ -0,0 +1,230 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use sysinfo::{Pid, ProcessRefreshKind, System};
+use tokio::process::Command;
+
+use toru_plugin_api::PluginLimits;
+
+/// Parent of the per-plugin cgroups (cgroup v2 unified hierarchy)
+const CGROUP_ROOT: &str = "/sys/fs/cgroup/toru-plugins";
+
+/// Period the CPU quota in `cpu.max` is measured against (the kernel default)
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Which limit a plugin went over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitExceeded {
+    Memory,
+    Cpu,
+}
+
+/// Resources a plugin process used at the last sample
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ResourceUsage {
+    /// Resident memory in bytes
+    pub memory_bytes: u64,
+    /// CPU usage in percent of one core since the previous sample
+    pub cpu_percent: f32,
+}
+
+/// How the limits of a running plugin are enforced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Enforcement {
+    /// The process was moved into a cgroup with `memory.max` and `cpu.max` set
+    Cgroup(PathBuf),
+    /// Memory is capped with RLIMIT_AS; CPU is only watched by the health monitor
+    Rlimit,
+    /// The plugin is a WASM module: memory is capped by its store, calls by fuel
+    Wasm,
+    /// No limits configured, or none could be applied
+    None,
+}
+
+impl Enforcement {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Enforcement::Cgroup(_) => "cgroup",
+            Enforcement::Rlimit => "rlimit",
+            Enforcement::Wasm => "wasm",
+            Enforcement::None => "none",
+        }
+    }
+}
+
+/// The limit a usage sample is over, if any (memory is checked first)
+pub fn exceeded(limits: &PluginLimits, usage: &ResourceUsage) -> Option<LimitExceeded> {
+    if limits
+        .memory_mb
+        .is_some_and(|mb| usage.memory_bytes > mb.saturating_mul(1024 * 1024))
+    {
+        return Some(LimitExceeded::Memory);
+    }
+    if limits
+        .cpu_percent
+        .is_some_and(|pct| usage.cpu_percent > pct as f32)
+    {
+        return Some(LimitExceeded::Cpu);
+    }
+    None
+}
+
+/// Create (or reset) the cgroup for a plugin and write its limits
+///
+/// Fails when cgroup v2 isn't mounted or the supervisor may not create groups,
+/// in which case the caller falls back to rlimits.
+pub fn create_cgroup(plugin_id: &str, limits: &PluginLimits) -> Result<PathBuf> {
+    let root = Path::new(CGROUP_ROOT);
+    if !root
+        .parent()
+        .is_some_and(|p| p.join("cgroup.controllers").exists())
+    {
+        return Err(anyhow!("cgroup v2 is not available"));
+    }
+
+    fs::create_dir_all(root).context("Failed to create plugin cgroup root")?;
+    // Controllers must be enabled on the parent before children get their files
+    fs::write(root.join("cgroup.subtree_control"), "+memory +cpu")
+        .context("Failed to enable memory and cpu controllers")?;
+
+    let cgroup = root.join(plugin_id);
+    // A group left by the previous run still holds its OOM counters
+    if cgroup.exists() {
+        fs::remove_dir(&cgroup).ok();
+    }
+    fs::create_dir_all(&cgroup).context("Failed to create plugin cgroup")?;
+
+    if let Some(mb) = limits.memory_mb {
+        fs::write(
+            cgroup.join("memory.max"),
+            mb.saturating_mul(1024 * 1024).to_string(),
+        )
+        .context("Failed to set memory.max")?;
+    }
+    if let Some(pct) = limits.cpu_percent {
+        fs::write(cgroup.join("cpu.max"), cpu_max(pct)).context("Failed to set cpu.max")?;
+    }
+
+    Ok(cgroup)
+}
+
+/// Move a process into a cgroup
+pub fn add_to_cgroup(cgroup: &Path, pid: u32) -> Result<()> {
+    fs::write(cgroup.join("cgroup.procs"), pid.to_string())
+        .context("Failed to move plugin into its cgroup")
+}
+
+/// Remove a plugin cgroup once its processes are gone
+pub fn remove_cgroup(cgroup: &Path) {
+    if let Err(e) = fs::remove_dir(cgroup) {
+        tracing::debug!("Failed to remove cgroup {:?}: {}", cgroup, e);
+    }
+}
+
+/// Whether the kernel OOM-killed a process in the cgroup
+pub fn oom_killed(cgroup: &Path) -> bool {
+    fs::read_to_string(cgroup.join("memory.events"))
+        .ok()
+        .and_then(|events| {
+            events.lines().find_map(|line| {
+                line.strip_prefix("oom_kill ")
+                    .and_then(|n| n.trim().parse::<u64>().ok())
+            })
+        })
+        .is_some_and(|kills| kills > 0)
+}
+
+/// Value for `cpu.max` giving `percent` of one core
+fn cpu_max(percent: u32) -> String {
+    let quota = (percent as u64 * CPU_PERIOD_US / 100).max(1000);
+    format!("{} {}", quota, CPU_PERIOD_US)
+}
+
+/// Cap the address space of the child before exec (used without cgroups)
+#[cfg(unix)]
+pub fn apply_rlimits(command: &mut Command, limits: &PluginLimits) {
+    let Some(mb) = limits.memory_mb else {
+        return;
+    };
+    let bytes = mb.saturating_mul(1024 * 1024);
+    // SAFETY: the closure runs between fork and exec and only calls setrlimit,
+    // which is async-signal-safe. No allocation happens inside it.
+    unsafe {
+        command.pre_exec(move || super::executor::set_rlimit(libc::RLIMIT_AS, bytes, bytes));
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply_rlimits(_command: &mut Command, limits: &PluginLimits) {
+    if !limits.is_empty() {
+        tracing::warn!("Plugin resource limits are only supported on Unix, ignoring");
+    }
+}
+
+/// Samples memory and CPU usage of plugin processes
+///
+/// CPU usage is measured between two samples of the same process, so the first
+/// sample after a spawn reports 0%.
+#[derive(Debug, Default)]
+pub struct UsageSampler {
+    system: System,
+}
+
+impl UsageSampler {
+    pub fn sample(&mut self, pid: u32) -> Option<ResourceUsage> {
+        let pid = Pid::from_u32(pid);
+        let refresh = ProcessRefreshKind::new().with_cpu().with_memory();
+        if !self.system.refresh_process_specifics(pid, refresh) {
+            return None;
+        }
+        self.system.process(pid).map(|process| ResourceUsage {
+            memory_bytes: process.memory(),
+            cpu_percent: process.cpu_usage(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exceeded() {
+        let limits = PluginLimits {
+            memory_mb: Some(64),
+            cpu_percent: Some(50),
+        };
+        let usage = |mb: u64, cpu: f32| ResourceUsage {
+            memory_bytes: mb * 1024 * 1024,
+            cpu_percent: cpu,
+        };
+
+        assert_eq!(exceeded(&limits, &usage(32, 10.0)), None);
+        assert_eq!(
+            exceeded(&limits, &usage(65, 10.0)),
+            Some(LimitExceeded::Memory)
+        );
+        assert_eq!(
+            exceeded(&limits, &usage(65, 90.0)),
+            Some(LimitExceeded::Memory)
+        );
+        assert_eq!(
+            exceeded(&limits, &usage(32, 90.0)),
+            Some(LimitExceeded::Cpu)
+        );
+        assert_eq!(
+            exceeded(&PluginLimits::default(), &usage(4096, 400.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cpu_max() {
+        assert_eq!(cpu_max(50), "50000 100000");
+        assert_eq!(cpu_max(200), "200000 100000");
+        // The kernel rejects quotas below 1ms
+        assert_eq!(cpu_max(0), "1000 100000");
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Context, Result};
+use flate2::read::GzDecoder;
+use std::io::Read;
+
+use super::plugin_assets;
+
+/// Maximum size of an uploaded plugin package (64 MB)
+pub const MAX_PACKAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Maximum size of the files unpacked from a package, so a small gzipped
+/// archive can't expand without bound
+const MAX_UNPACKED_SIZE: u64 = 4 * MAX_PACKAGE_SIZE as u64;
+
+/// Files making up a plugin: the executable, its optional frontend bundle and
+/// static assets, and optional detached minisign signature of the executable
+#[derive(Debug, Default)]
+pub struct PluginPackage {
+    pub binary: Vec<u8>,
+    pub bundle: Option<Vec<u8>>,
+    pub signature: Option<Vec<u8>>,
+    /// Files below `assets/`, by path relative to it
+    pub assets: Vec<(String, Vec<u8>)>,
+}
+
+impl PluginPackage {
+    /// Build a package from an upload, unpacking it if it is a tar archive
+    pub fn from_upload(
+        data: Vec<u8>,
+        bundle: Option<Vec<u8>>,
+        signature: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        if is_gzip(&data) || is_tar(&data) {
+            let mut package = Self::from_tar(&data)?;
+            // Explicitly uploaded files win over the ones in the archive
+            if bundle.is_some() {
+                package.bundle = bundle;
+            }
+            if signature.is_some() {
+                package.signature = signature;
+            }
+            Ok(package)
+        } else {
+            Ok(Self {
+                binary: data,
+                bundle,
+                signature,
+                assets: Vec::new(),
+            })
+        }
+    }
+
+    /// Read a plugin package from a tar archive, gzipped or not
+    ///
+    /// The archive must contain exactly one `*.binary` file and may contain a
+    /// `bundle.js`, a `*.binary.minisig` signature and an `assets/` directory;
+    /// other directories in entry names are ignored, other files are skipped.
+    pub fn from_tar(data: &[u8]) -> Result<Self> {
+        if is_gzip(data) {
+            Self::read_tar(GzDecoder::new(data))
+        } else {
+            Self::read_tar(data)
+        }
+    }
+
+    fn read_tar(reader: impl Read) -> Result<Self> {
+        let mut binary = None;
+        let mut bundle = None;
+        let mut signature = None;
+        let mut assets = Vec::new();
+        let mut unpacked = 0u64;
+
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries().context("Invalid tar archive")? {
+            let mut entry = entry.context("Invalid tar archive")?;
+            // Regular files only (directories and links are skipped)
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry
+                .path()
+                .context("Invalid tar entry name")?
+                .to_str()
+                .ok_or_else(|| anyhow!("Tar entry names must be UTF-8"))?
+                .to_string();
+            let file_name = name.rsplit('/').next().unwrap_or_default();
+            let asset = asset_path(&name)
+                .map(plugin_assets::sanitize_path)
+                .transpose()?;
+            if asset.is_none()
+                && !file_name.ends_with(".binary")
+                && file_name != "bundle.js"
+                && !file_name.ends_with(".binary.minisig")
+            {
+                continue;
+            }
+
+            unpacked += entry.size();
+            if unpacked > MAX_UNPACKED_SIZE {
+                return Err(anyhow!(
+                    "Archive unpacks to more than {} MB",
+                    MAX_UNPACKED_SIZE / 1024 / 1024
+                ));
+            }
+            let mut content = Vec::new();
+            entry
+                .read_to_end(&mut content)
+                .context("Truncated tar archive")?;
+
+            if let Some(path) = asset {
+                assets.push((path, content));
+            } else if file_name.ends_with(".binary") {
+                if binary.is_some() {
+                    return Err(anyhow!("Archive contains more than one .binary file"));
+                }
+                binary = Some(content);
+            } else if file_name == "bundle.js" {
+                bundle = Some(content);
+            } else {
+                signature = Some(content);
+            }
+        }
+
+        let binary = binary.ok_or_else(|| anyhow!("Archive does not contain a .binary file"))?;
+        Ok(Self {
+            binary,
+            bundle,
+            signature,
+            assets,
+        })
+    }
+}
+
+/// Path below the first `assets/` directory of an entry name, if it has one
+fn asset_path(name: &str) -> Option<&str> {
+    let (_, path) = name
+        .split_once(&format!("{}/", plugin_assets::ASSETS_DIR))
+        .filter(|(prefix, _)| prefix.is_empty() || prefix.ends_with('/'))?;
+    Some(path)
+}
+
+/// Returns true if the data starts like a gzip stream
+fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&[0x1f, 0x8b])
+}
+
+/// Returns true if the data looks like a ustar (or GNU tar) archive
+fn is_tar(data: &[u8]) -> bool {
+    data.len() >= 512 && &data[257..262] == b"ustar"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut archive = tar::Builder::new(Vec::new());
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            archive.append_data(&mut header, name, *content).unwrap();
+        }
+        archive.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_package_from_tar() {
+        let archive = tar(&[
+            ("./my-plugin.binary", b"\x7fELF..."),
+            ("frontend/bundle.js", b"export default {}"),
+            ("./my-plugin.binary.minisig", b"untrusted comment"),
+            ("README.md", b"ignored"),
+            ("frontend/assets/css/app.css", b"body {}"),
+        ]);
+
+        let package = PluginPackage::from_upload(archive, None, None).unwrap();
+        assert_eq!(package.binary, b"\x7fELF...");
+        assert_eq!(package.bundle.as_deref(), Some(&b"export default {}"[..]));
+        assert_eq!(
+            package.signature.as_deref(),
+            Some(&b"untrusted comment"[..])
+        );
+        assert_eq!(
+            package.assets,
+            [("css/app.css".to_string(), b"body {}".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_package_from_tar_rejects_asset_traversal() {
+        let mut archive = tar(&[("my-plugin.binary", b"\x7fELF...")]);
+        // The tar crate refuses to write `..`, so patch it into the header
+        let mut entry = tar(&[("assets/xx/xx/escape.sh", b"#!/bin/sh")]);
+        entry[7..13].copy_from_slice(b"../../");
+        let mut header = tar::Header::new_old();
+        header.as_mut_bytes().copy_from_slice(&entry[..512]);
+        header.set_cksum();
+        entry[..512].copy_from_slice(header.as_bytes());
+        archive.truncate(archive.len() - 1024);
+        archive.extend(entry);
+        assert!(PluginPackage::from_tar(&archive).is_err());
+    }
+
+    #[test]
+    fn test_package_from_tar_requires_binary() {
+        let archive = tar(&[("bundle.js", b"export default {}")]);
+        assert!(PluginPackage::from_tar(&archive).is_err());
+    }
+
+    #[test]
+    fn test_package_from_gzipped_tar_with_long_names() {
+        let long_asset = format!("assets/{}/logo.svg", "icons".repeat(30));
+        let archive = tar(&[
+            ("my-plugin.binary", b"\x7fELF..."),
+            (long_asset.as_str(), b"<svg/>"),
+        ]);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &archive).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let package = PluginPackage::from_upload(gzipped, None, None).unwrap();
+        assert_eq!(package.binary, b"\x7fELF...");
+        assert_eq!(package.assets[0].0, long_asset["assets/".len()..]);
+    }
+
+    #[test]
+    fn test_package_from_tar_checks_header_checksum() {
+        let mut archive = tar(&[("my-plugin.binary", b"\x7fELF...")]);
+        archive[0] = b'M';
+        assert!(PluginPackage::from_tar(&archive).is_err());
+    }
+
+    #[test]
+    fn test_raw_binary_upload() {
+        let package = PluginPackage::from_upload(b"#!/bin/sh".to_vec(), None, None).unwrap();
+        assert_eq!(package.binary, b"#!/bin/sh");
+        assert!(package.bundle.is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::process::Stdio;
+use tokio::process::Command;
+
+use super::plugin_package::MAX_PACKAGE_SIZE;
+
+/// Settings key holding the URL of the plugin registry index
+pub const REGISTRY_URL_SETTING: &str = "plugin_registry_url";
+
+/// Maximum size of a registry index (1 MB)
+const MAX_INDEX_SIZE: usize = 1024 * 1024;
+
+/// Timeout for a single download
+const DOWNLOAD_TIMEOUT_SECS: u64 = 120;
+
+/// A registry index: JSON document listing installable plugins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryIndex {
+    pub plugins: Vec<RegistryEntry>,
+}
+
+/// One installable plugin in a registry index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    /// Download URL of the `.binary` or `.tar` package
+    pub url: String,
+    /// Hex-encoded SHA-256 of the package
+    pub sha256: String,
+}
+
+/// Hex-encoded SHA-256 digest of some data
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Check data against an expected hex-encoded SHA-256 digest
+pub fn verify_checksum(data: &[u8], expected: &str) -> Result<()> {
+    let actual = sha256_hex(data);
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Checksum mismatch: expected {}, got {}",
+            expected.trim(),
+            actual
+        ))
+    }
+}
+
+/// Only plain http(s) URLs may be downloaded
+pub fn validate_url(url: &str) -> Result<()> {
+    let valid = (url.starts_with("https://") || url.starts_with("http://"))
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control());
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!("URL must be an http:// or https:// URL"))
+    }
+}
+
+/// Download a URL into memory using `curl`, refusing bodies larger than `max_bytes`
+pub async fn download(url: &str, max_bytes: usize) -> Result<Vec<u8>> {
+    validate_url(url)?;
+
+    let output = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--proto",
+            "=http,https",
+            "--max-time",
+            &DOWNLOAD_TIMEOUT_SECS.to_string(),
+            "--max-filesize",
+            &max_bytes.to_string(),
+            "--",
+            url,
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .context("Failed to run curl")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Download failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    // --max-filesize is only enforced when the server sends a length
+    if output.stdout.len() > max_bytes {
+        return Err(anyhow!("Download exceeds {} bytes", max_bytes));
+    }
+    Ok(output.stdout)
+}
+
+/// Download a plugin package and verify its checksum
+pub async fn download_package(url: &str, sha256: &str) -> Result<Vec<u8>> {
+    let data = download(url, MAX_PACKAGE_SIZE).await?;
+    verify_checksum(&data, sha256)?;
+    Ok(data)
+}
+
+/// Fetch and parse a registry index
+pub async fn fetch_index(url: &str) -> Result<RegistryIndex> {
+    let data = download(url, MAX_INDEX_SIZE).await?;
+    serde_json::from_slice(&data).context("Invalid registry index")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_checksum() {
+        let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(sha256_hex(b"hello"), digest);
+        assert!(verify_checksum(b"hello", &digest.to_uppercase()).is_ok());
+        assert!(verify_checksum(b"hello!", digest).is_err());
+    }
+
+    #[test]
+    fn test_validate_url() {
+        assert!(validate_url("https://plugins.example.com/index.json").is_ok());
+        assert!(validate_url("file:///etc/passwd").is_err());
+        assert!(validate_url("https://example.com/a b").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::{anyhow, Context, Result};
+use minisign_verify::{PublicKey, Signature};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::db::{self, DbPool, TrustedKey};
+
+/// Settings key that makes the supervisor refuse unsigned plugin binaries
+pub const REQUIRE_SIGNED_SETTING: &str = "require_signed_plugins";
+
+/// Path of the detached minisign signature for a plugin binary (`<binary>.minisig`)
+pub fn signature_path(binary_path: &Path) -> PathBuf {
+    let mut path = binary_path.as_os_str().to_owned();
+    path.push(".minisig");
+    PathBuf::from(path)
+}
+
+/// Parse a public key given either as the bare base64 key or as a whole `minisign.pub` file
+pub fn parse_public_key(text: &str) -> Result<PublicKey> {
+    let text = text.trim();
+    let key = if text.lines().count() > 1 {
+        PublicKey::decode(text)
+    } else {
+        PublicKey::from_base64(text)
+    };
+    key.map_err(|e| anyhow!("Invalid minisign public key: {}", e))
+}
+
+/// Validate a public key and return it in the bare base64 form stored in the database
+pub fn normalize_public_key(text: &str) -> Result<String> {
+    parse_public_key(text)?;
+    Ok(text
+        .trim()
+        .lines()
+        .last()
+        .unwrap_or_default()
+        .trim()
+        .to_string())
+}
+
+/// Verify data against a minisign signature made by any of the trusted keys
+///
+/// # Returns
+/// The key that produced the signature
+pub fn verify_signature<'a>(
+    data: &[u8],
+    signature: &str,
+    keys: &'a [TrustedKey],
+) -> Result<&'a TrustedKey> {
+    if keys.is_empty() {
+        return Err(anyhow!("No trusted signing keys configured"));
+    }
+
+    let signature =
+        Signature::decode(signature).map_err(|e| anyhow!("Invalid plugin signature: {}", e))?;
+
+    keys.iter()
+        .find(|key| {
+            parse_public_key(&key.public_key)
+                .map(|pk| pk.verify(data, &signature, true).is_ok())
+                .unwrap_or(false)
+        })
+        .ok_or_else(|| anyhow!("Plugin signature does not match any trusted key"))
+}
+
+/// Whether the `require_signed_plugins` setting is enabled
+pub async fn signing_required(db: &DbPool) -> bool {
+    matches!(
+        db::get_setting(db, REQUIRE_SIGNED_SETTING).await,
+        Ok(Some(value)) if value == "true"
+    )
+}
+
+/// Check the detached signature of a plugin binary on disk
+///
+/// Does nothing unless signing is required; otherwise the binary must have a
+/// `.minisig` file next to it made by one of the trusted keys.
+pub async fn verify_plugin_binary(db: &DbPool, binary_path: &Path) -> Result<()> {
+    if !signing_required(db).await {
+        return Ok(());
+    }
+
+    let sig_path = signature_path(binary_path);
+    let signature = fs::read_to_string(&sig_path)
+        .with_context(|| format!("Plugin binary is not signed (missing {:?})", sig_path))?;
+    let data = fs::read(binary_path).context("Failed to read plugin binary")?;
+    let keys = db::get_trusted_keys(db).await?;
+
+    let key = verify_signature(&data, &signature, &keys)?;
+    tracing::debug!(
+        "Plugin {:?} signed by trusted key {}",
+        binary_path,
+        key.name
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vector from the minisign reference implementation
+    const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RWQf6LRCGA9i59SLOFxz6NxvASXDJeRtuZykwQepbDEGt87ig1BNpWaVWuNrm73YiIiJbq71Wi+dP9eKL8OC351vwIasSSbXxwA=
+trusted comment: timestamp:1555779966\tfile:test
+QtKMXWyYcwdpZAlPF7tE2ENJkRd1ujvKjlj1m9RtHTBnZPa5WKU5uWRs5GoP5M/VqE81QFuMKI5k/SfNQUaOAA==";
+
+    fn trusted_key(public_key: &str) -> TrustedKey {
+        TrustedKey {
+            id: "key".to_string(),
+            name: "release".to_string(),
+            public_key: public_key.to_string(),
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let keys = vec![trusted_key(PUBLIC_KEY)];
+        assert!(verify_signature(b"test", SIGNATURE, &keys).is_ok());
+        assert!(verify_signature(b"Test", SIGNATURE, &keys).is_err());
+        assert!(verify_signature(b"test", SIGNATURE, &[]).is_err());
+    }
+
+    #[test]
+    fn test_parse_public_key_file() {
+        let file = format!(
+            "untrusted comment: minisign public key E7620F1842B4E81F\n{}\n",
+            PUBLIC_KEY
+        );
+        let key = parse_public_key(&file).unwrap();
+        assert_eq!(
+            key.untrusted_comment(),
+            Some("untrusted comment: minisign public key E7620F1842B4E81F")
+        );
+        assert_eq!(normalize_public_key(&file).unwrap(), PUBLIC_KEY);
+        assert!(parse_public_key("not a key").is_err());
+        assert_eq!(
+            signature_path(Path::new("plugins/hello.binary")),
+            PathBuf::from("plugins/hello.binary.minisig")
+        );
+    }
+}
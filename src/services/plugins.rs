@@ -1,27 +1,293 @@
 use anyhow::{Context, Result};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
-use tokio::net::UnixStream;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::process::Child;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 
-use toru_plugin_api::{HttpMessageResponse, HttpRequest, Message, PluginMetadata};
+use toru_plugin_api::env::{
+    API_URL_ENV, DATA_DIR_ENV, INSTANCE_ID_ENV, LOG_PATH_ENV, PLUGIN_ID_ENV, RESERVED_PREFIX,
+};
+use toru_plugin_api::transport::{ADDRESS_ENV, SECRET_ENV, SOCKET_ENV};
+use toru_plugin_api::{
+    HttpMessageResponse, HttpRequest, Message, MessagePayload, NotifyLevel, PluginCall,
+    PluginCallResult, PluginExec, PluginExecResult, PluginLimits, PluginMetadata,
+    PluginSystemInfoResult, PluginTransport, ScheduledJob, TransportKind, WireFormat,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+
+use super::event_bus::{BrowserNotification, PublishedEvent, BROWSER_EVENT_BUFFER};
+use super::logging::{LogEntry, LogLevel, PluginLogger, SupervisorLogger};
+use super::notifications::{self, Notification, NotificationKind, NotificationLevel};
+use super::plugin_assets;
+use super::plugin_breaker::{CircuitBreaker, CircuitOpen};
+use super::plugin_connection::{BodyStream, PluginConnection, PluginMessageSender, RequestTimeout};
+use super::plugin_crash::{CrashReport, StderrTail};
+use super::plugin_deps::{self, Dependency, DependencyProblem};
+use super::plugin_exec;
+use super::plugin_jobs::{self, PluginJob, JOB_TICK_INTERVAL};
+use super::plugin_limits::{self, Enforcement, LimitExceeded, ResourceUsage, UsageSampler};
+use super::plugin_package::PluginPackage;
+use super::plugin_signing;
+use super::system::ResourceCache;
+use super::wasm_runtime::{self, WasmPlugin};
+use crate::db::{DbPool, PluginKvQuota};
+
+/// How often the health monitor checks running plugins
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time a freshly spawned plugin gets to create its socket before it is health-checked
+const STARTUP_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Uptime after which a restarted plugin is considered stable and its restart count is reset
+const STABLE_PERIOD: Duration = Duration::from_secs(300);
+
+/// How long the health monitor waits for a plugin to answer a ping
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Protocol version that introduced pings; older plugins aren't pinged
+const PING_PROTOCOL_VERSION: u32 = 2;
+
+/// Protocol version that introduced `migrate`; older plugins aren't asked to migrate
+const MIGRATE_PROTOCOL_VERSION: u32 = 4;
+
+/// How long an upgraded plugin gets to migrate its data
+const MIGRATE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Pings in a row a plugin may miss before it is considered hung and restarted
+pub const MAX_MISSED_PINGS: u32 = 3;
+
+/// Default time a plugin gets to answer an HTTP request
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default time a plugin gets to exit after the shutdown message
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time a plugin gets to exit after SIGTERM before it is killed
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the hot reload watcher checks plugin binaries for changes
+pub const HOT_RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest route a plugin may declare, including the leading slash
+const MAX_ROUTE_LEN: usize = 64;
+
+/// Top-level paths of the built-in API and dashboard that plugins may not take
+const RESERVED_ROUTES: &[&str] = &[
+    "api",
+    "assets",
+    "auth",
+    "health",
+    "history",
+    "login",
+    "me",
+    "pipelines",
+    "plugin",
+    "plugins",
+    "quick-actions",
+    "resources",
+    "route",
+    "scheduled-tasks",
+    "scripts",
+    "settings",
+    "system-monitor",
+    "users",
+    "ws",
+];
+
+/// Separates the plugin ID and the instance name in the ID of a plugin instance
+pub const INSTANCE_SEPARATOR: char = '@';
+
+/// Longest name a plugin instance may have
+const MAX_INSTANCE_NAME_LEN: usize = 32;
+
+/// Modification time and size of a plugin binary
+type BinaryStamp = (SystemTime, u64);
+
+/// How a running plugin answers the health monitor's pings
+#[derive(Debug, Clone, Copy, Default)]
+struct Heartbeat {
+    /// Plugins that never answered may predate pings; their misses don't count
+    answered: bool,
+    missed: u32,
+}
+
+/// Errors returned when installing or upgrading a plugin package
+#[derive(Debug)]
+pub enum InstallError {
+    /// The upload is not a usable plugin (bad archive, `--metadata` failed, ...)
+    Invalid(String),
+    /// A plugin with the same ID is already installed
+    AlreadyInstalled(String),
+    /// The plugin to upgrade isn't installed
+    NotInstalled(String),
+    /// The upgrade isn't newer than the installed version
+    NotNewer {
+        plugin_id: String,
+        installed: String,
+        version: String,
+    },
+    /// The upgraded plugin failed to start and the previous binary was restored
+    RolledBack {
+        plugin_id: String,
+        error: String,
+    },
+    Failed(anyhow::Error),
+}
+
+impl std::fmt::Display for InstallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallError::Invalid(reason) => write!(f, "Invalid plugin package: {}", reason),
+            InstallError::AlreadyInstalled(id) => write!(f, "Plugin {} is already installed", id),
+            InstallError::NotInstalled(id) => write!(f, "Plugin {} is not installed", id),
+            InstallError::NotNewer {
+                plugin_id,
+                installed,
+                version,
+            } => write!(
+                f,
+                "Plugin {} {} is not newer than the installed version {}",
+                plugin_id, version, installed
+            ),
+            InstallError::RolledBack { plugin_id, error } => write!(
+                f,
+                "Upgrade of plugin {} failed and was rolled back: {}",
+                plugin_id, error
+            ),
+            InstallError::Failed(e) => write!(f, "Plugin installation failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for InstallError {}
+
+impl From<anyhow::Error> for InstallError {
+    fn from(e: anyhow::Error) -> Self {
+        InstallError::Failed(e)
+    }
+}
+
+/// A validated plugin binary waiting under a temporary name
+struct StagedBinary {
+    path: PathBuf,
+    signature_path: PathBuf,
+    signed: bool,
+    metadata: PluginMetadata,
+}
+
+impl StagedBinary {
+    /// Move the binary to `binary_path`
+    ///
+    /// The signature goes first so the binary never appears unsigned.
+    fn install(&self, binary_path: &Path) -> std::io::Result<()> {
+        let signature_path = plugin_signing::signature_path(binary_path);
+        if self.signed {
+            fs::rename(&self.signature_path, &signature_path)?;
+        } else {
+            fs::remove_file(&signature_path).ok();
+        }
+        fs::rename(&self.path, binary_path)
+    }
+
+    fn discard(&self) {
+        fs::remove_file(&self.path).ok();
+        fs::remove_file(&self.signature_path).ok();
+    }
+}
+
+/// A named instance of an installed plugin
+///
+/// Each instance runs the plugin's binary as a plugin of its own, under the ID
+/// `<plugin>@<name>`: it has its own process, socket, KV namespace and logs, and
+/// is served at the plugin's route with `-<name>` appended.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginInstance {
+    /// ID of the plugin whose binary the instance runs
+    pub plugin: String,
+    pub name: String,
+    /// Passed to the instance in `init`
+    #[serde(default)]
+    pub settings: HashMap<String, String>,
+}
+
+impl PluginInstance {
+    /// ID the instance runs under
+    pub fn id(&self) -> String {
+        format!("{}{}{}", self.plugin, INSTANCE_SEPARATOR, self.name)
+    }
 
-use super::logging::{LogLevel, PluginLogger, SupervisorLogger};
-use crate::db::DbPool;
+    /// The plugin's metadata, with the instance's ID, name and route
+    pub fn metadata(&self, mut metadata: PluginMetadata) -> PluginMetadata {
+        metadata.id = self.id();
+        metadata.name = format!("{} ({})", metadata.name, self.name);
+        metadata.route = format!("{}-{}", metadata.route, self.name);
+        metadata
+    }
+}
 
 /// Represents a running plugin process
 #[derive(Debug)]
 pub struct PluginProcess {
     pub id: String,
     pub process: Option<Child>,
-    pub socket_path: String,
+    pub socket_path: String, // Socket path, tcp:// address, or module path for WASM plugins
+    // Where a native plugin listens; None for WASM plugins
+    pub transport: Option<PluginTransport>,
+    // Shared secret TCP connections present to the plugin
+    pub secret: Option<String>,
     pub enabled: bool,
     pub metadata: Option<PluginMetadata>,
     pub pid: Option<u32>,
+    pub started_at: Instant,
+    // Persistent socket connection, opened on first use
+    pub connection: tokio::sync::Mutex<Option<Arc<PluginConnection>>>,
+    // Limits in effect for this run (host config, else metadata)
+    pub limits: PluginLimits,
+    pub enforcement: Enforcement,
+    // Last usage sample taken by the health monitor
+    pub usage: Option<ResourceUsage>,
+    // Set when the plugin went over a limit; kept across restarts until re-enabled
+    pub limit_exceeded: Option<LimitExceeded>,
+    // When the plugin was last detected as crashed; kept across restarts
+    pub last_crash_at: Option<chrono::DateTime<chrono::Utc>>,
+    // Exit status and stderr of the last crash; kept across restarts
+    pub last_crash: Option<CrashReport>,
+    // Last lines the current process wrote to stderr
+    pub stderr_tail: Arc<StderrTail>,
+    // Run by a developer outside the supervisor; never spawned or restarted
+    pub dev: bool,
+    // Module of a WASM plugin, run inside the supervisor; taken when stopped
+    pub wasm: Option<Arc<WasmPlugin>>,
+    // Opens after repeated request timeouts; a new one for every start
+    pub breaker: Arc<CircuitBreaker>,
+}
+
+impl PluginProcess {
+    /// Whether the plugin's endpoint can be connected to (its socket file exists;
+    /// TCP plugins are assumed listening once spawned, WASM plugins while loaded)
+    pub fn endpoint_ready(&self) -> bool {
+        match &self.transport {
+            Some(PluginTransport::Unix(socket_path)) => socket_path.exists(),
+            Some(PluginTransport::Tcp(_)) => true,
+            None => self.wasm.is_some(),
+        }
+    }
+
+    /// Whether the supervisor is running the plugin (as a child process or a WASM instance)
+    pub fn is_running(&self) -> bool {
+        self.process.is_some() || self.wasm.is_some()
+    }
+
+    /// How long the current process has been running
+    pub fn uptime(&self) -> Option<Duration> {
+        self.is_running().then(|| self.started_at.elapsed())
+    }
 }
 
 /// Manages plugin lifecycle, including spawning, monitoring, and restarting plugins
@@ -30,6 +296,13 @@ pub struct PluginSupervisor {
     plugins: HashMap<String, PluginProcess>,
     // Used for tracking crash recovery and exponential backoff
     restart_counts: HashMap<String, u32>,
+    // Plugins the health monitor is currently restarting
+    restarting: HashSet<String>,
+    usage_sampler: UsageSampler,
+    // Binaries of spawned plugins and their stamp at spawn time, for hot reload
+    binary_stamps: HashMap<String, (PathBuf, BinaryStamp)>,
+    // Changed binaries waiting one more check to be sure they are fully written
+    pending_reloads: HashMap<String, BinaryStamp>,
     plugins_dir: PathBuf,
     metadata_dir: PathBuf,
     sockets_dir: PathBuf,
@@ -39,6 +312,27 @@ pub struct PluginSupervisor {
     plugin_logger: Arc<PluginLogger>,
     supervisor_logger: Arc<SupervisorLogger>,
     db_pool: DbPool,
+    // Messages plugins send on their own, taken by the plugin message handler
+    incoming_sender: PluginMessageSender,
+    incoming_receiver: Option<mpsc::UnboundedReceiver<(String, Message)>>,
+    // Published events pushed to browser WebSockets
+    browser_events: broadcast::Sender<PublishedEvent>,
+    // Plugin notifications pushed to browser WebSockets
+    browser_notifications: broadcast::Sender<BrowserNotification>,
+    // Jobs plugins registered, cleared when the plugin stops or starts again
+    jobs: HashMap<String, Vec<PluginJob>>,
+    // Plugins kept from starting because another plugin has their route
+    route_conflicts: HashMap<String, String>,
+    // Dependency problems already reported for plugins kept from starting
+    reported_dependency_problems: HashMap<String, Vec<String>>,
+    // Plugins in a dependency cycle, or depending on one, found when computing the start order
+    dependency_cycles: HashSet<String>,
+    // Answers to pings since each plugin was started
+    heartbeats: HashMap<String, Heartbeat>,
+    // Base URL of the host API, passed to plugins
+    api_url: Option<String>,
+    // Resource snapshots answering `system_info` requests
+    resources: ResourceCache,
 }
 
 impl PluginSupervisor {
@@ -59,7 +353,12 @@ impl PluginSupervisor {
     ) -> Result<Self> {
         let plugins_dir = plugins_dir.as_ref().to_path_buf();
         let metadata_dir = plugins_dir.join(".metadata");
-        let sockets_dir = PathBuf::from("/tmp/toru-plugins");
+        let sockets_dir = if cfg!(unix) {
+            PathBuf::from("/tmp/toru-plugins")
+        } else {
+            // Only TCP plugins run here; the directory is kept for a uniform layout
+            std::env::temp_dir().join("toru-plugins")
+        };
         let log_dir = log_dir.as_ref().to_path_buf();
 
         // Create directories if they don't exist
@@ -68,16 +367,23 @@ impl PluginSupervisor {
         fs::create_dir_all(&sockets_dir).context("Failed to create sockets directory")?;
 
         // Initialize loggers
-        let plugin_logger = Arc::new(PluginLogger::new(super::logging::LogConfig {
+        let log_config = super::logging::LogConfig {
             log_dir: log_dir.clone(),
             ..Default::default()
-        })?);
-
-        let supervisor_logger = Arc::new(SupervisorLogger::new(&log_dir)?);
+        };
+        let plugin_logger = Arc::new(PluginLogger::new(log_config.clone())?);
+        let supervisor_logger = Arc::new(SupervisorLogger::new(log_config)?);
+        let (incoming_sender, incoming_receiver) = mpsc::unbounded_channel();
+        let (browser_events, _) = broadcast::channel(BROWSER_EVENT_BUFFER);
+        let (browser_notifications, _) = broadcast::channel(BROWSER_EVENT_BUFFER);
 
         Ok(Self {
             plugins: HashMap::new(),
             restart_counts: HashMap::new(),
+            restarting: HashSet::new(),
+            usage_sampler: UsageSampler::default(),
+            binary_stamps: HashMap::new(),
+            pending_reloads: HashMap::new(),
             plugins_dir,
             metadata_dir,
             sockets_dir,
@@ -86,15 +392,38 @@ impl PluginSupervisor {
             plugin_logger,
             supervisor_logger,
             db_pool,
+            incoming_sender,
+            incoming_receiver: Some(incoming_receiver),
+            browser_events,
+            browser_notifications,
+            jobs: HashMap::new(),
+            route_conflicts: HashMap::new(),
+            reported_dependency_problems: HashMap::new(),
+            dependency_cycles: HashSet::new(),
+            heartbeats: HashMap::new(),
+            api_url: None,
+            resources: ResourceCache::default(),
         })
     }
 
+    /// Tell plugins where the host API is (`TORU_API_URL`)
+    pub fn with_api_url(mut self, api_url: String) -> Self {
+        self.api_url = Some(api_url);
+        self
+    }
+
+    /// Answer `system_info` requests with the snapshots of `GET /api/resources`
+    pub fn with_resources(mut self, resources: ResourceCache) -> Self {
+        self.resources = resources;
+        self
+    }
+
     /// Get a reference to the plugin logger
     pub fn plugin_logger(&self) -> Arc<PluginLogger> {
         Arc::clone(&self.plugin_logger)
     }
 
-    /// Scan the plugins directory for .binary files and load metadata
+    /// Scan the plugins directory for .binary and .wasm files and load metadata
     ///
     /// # Returns
     /// HashMap mapping plugin_id to (binary_path, metadata)
@@ -122,12 +451,15 @@ impl PluginSupervisor {
 
             let path = entry.path();
 
-            // Skip directories and non-.binary files
+            // Skip directories and anything but .binary and .wasm files
             if path.is_dir() {
                 continue;
             }
 
-            if path.extension().and_then(|ext| ext.to_str()) != Some("binary") {
+            if !matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("binary" | "wasm")
+            ) {
                 continue;
             }
 
@@ -174,12 +506,28 @@ impl PluginSupervisor {
             }
         }
 
+        // Instances run the binary of the plugin they belong to
+        for instance in self.plugin_instances().into_values() {
+            match discovered.get(&instance.plugin).cloned() {
+                Some((path, metadata)) => {
+                    discovered.insert(instance.id(), (path, instance.metadata(metadata)));
+                }
+                None => warn!(
+                    "Plugin {} of instance {} is not installed, skipping",
+                    instance.plugin,
+                    instance.id()
+                ),
+            }
+        }
+
         info!("Discovered {} plugins", discovered.len());
         Ok(discovered)
     }
 
     /// Read plugin metadata by running the binary with --metadata flag
     ///
+    /// WASM plugins are loaded and asked through their `toru_metadata` export.
+    ///
     /// # Arguments
     /// * `binary_path` - Path to the plugin binary
     ///
@@ -188,6 +536,19 @@ impl PluginSupervisor {
     async fn read_plugin_metadata(&self, binary_path: &Path) -> Result<PluginMetadata> {
         use tokio::process::Command;
 
+        // Never execute a binary that fails signature checks, not even for metadata
+        plugin_signing::verify_plugin_binary(&self.db_pool, binary_path).await?;
+
+        if wasm_runtime::is_wasm_plugin(binary_path) {
+            let path = binary_path.to_path_buf();
+            let metadata = tokio::task::spawn_blocking(move || {
+                WasmPlugin::load(&path, None).and_then(|wasm| wasm.metadata())
+            })
+            .await??;
+            validate_metadata(&metadata)?;
+            return Ok(metadata);
+        }
+
         let output = Command::new(binary_path)
             .arg("--metadata")
             .stdout(Stdio::piped())
@@ -210,24 +571,46 @@ impl PluginSupervisor {
         let metadata: PluginMetadata =
             serde_json::from_str(&stdout).context("Failed to parse plugin metadata JSON")?;
 
-        // Security: Validate metadata fields to prevent injection attacks
-        if !metadata
-            .id
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '-')
-        {
-            return Err(anyhow::anyhow!("Invalid plugin ID format"));
-        }
-        if !metadata.route.starts_with('/') || metadata.route.contains("..") {
-            return Err(anyhow::anyhow!("Invalid plugin route"));
-        }
-        if metadata.name.len() > 100 || metadata.author.as_ref().is_some_and(|a| a.len() > 100) {
-            return Err(anyhow::anyhow!("Metadata field too long"));
-        }
-
+        validate_metadata(&metadata)?;
         Ok(metadata)
     }
 
+    /// Pick the address a plugin will listen on
+    ///
+    /// Unix plugins get a socket in the sockets directory; TCP plugins get a free
+    /// loopback port and a fresh shared secret.
+    fn plugin_transport(
+        &self,
+        plugin_id: &str,
+        kind: TransportKind,
+    ) -> Result<(PluginTransport, Option<String>)> {
+        match kind {
+            TransportKind::Unix if cfg!(unix) => {
+                let socket_path = self.sockets_dir.join(format!("{}.sock", plugin_id));
+                Ok((PluginTransport::Unix(socket_path), None))
+            }
+            TransportKind::Unix => Err(anyhow::anyhow!(
+                "Plugin {} uses a Unix socket, which this platform lacks; it must declare \"transport\": \"tcp\"",
+                plugin_id
+            )),
+            TransportKind::Tcp => {
+                let port = std::net::TcpListener::bind(("127.0.0.1", 0))
+                    .and_then(|listener| listener.local_addr())
+                    .context("Failed to find a free port for plugin")?
+                    .port();
+                let secret = format!(
+                    "{}{}",
+                    uuid::Uuid::new_v4().simple(),
+                    uuid::Uuid::new_v4().simple()
+                );
+                Ok((
+                    PluginTransport::Tcp(([127, 0, 0, 1], port).into()),
+                    Some(secret),
+                ))
+            }
+        }
+    }
+
     /// Spawn a plugin process
     ///
     /// # Arguments
@@ -243,16 +626,72 @@ impl PluginSupervisor {
         binary_path: &Path,
         metadata: PluginMetadata,
     ) -> Result<()> {
-        let socket_path = self.sockets_dir.join(format!("{}.sock", plugin_id));
-        let socket_path_str = socket_path.to_string_lossy().to_string();
+        // A fresh start registers its jobs again after init
+        self.jobs.remove(plugin_id);
+        self.heartbeats.remove(plugin_id);
+        self.claim_route(plugin_id, &metadata).await?;
+        self.check_dependencies(plugin_id, &metadata).await?;
+
+        // The binary may have been replaced since its metadata was read
+        plugin_signing::verify_plugin_binary(&self.db_pool, binary_path)
+            .await
+            .context("Refusing to spawn plugin")?;
+
+        if wasm_runtime::is_wasm_plugin(binary_path) {
+            return self
+                .spawn_wasm_plugin(plugin_id, binary_path, metadata)
+                .await;
+        }
 
-        // Clean up existing socket if present
-        if socket_path.exists() {
-            fs::remove_file(&socket_path).ok();
+        let (transport, secret) = self.plugin_transport(plugin_id, metadata.transport)?;
+        let socket_path_str = transport.to_string();
+
+        let data_dir = self.plugin_data_dir(plugin_id);
+        fs::create_dir_all(&data_dir).context("Failed to create plugin data directory")?;
+
+        let mut command = tokio::process::Command::new(binary_path);
+        // Host variables go last so they always win
+        command.envs(self.plugin_env(plugin_id));
+        command
+            .env(PLUGIN_ID_ENV, plugin_id)
+            .env(INSTANCE_ID_ENV, &self.instance_id)
+            .env(DATA_DIR_ENV, &data_dir)
+            .env(
+                LOG_PATH_ENV,
+                self.plugin_logger.get_plugin_log_path(plugin_id),
+            );
+        if let Some(api_url) = &self.api_url {
+            command.env(API_URL_ENV, api_url);
+        }
+        command.env(ADDRESS_ENV, &socket_path_str);
+        match &transport {
+            PluginTransport::Unix(socket_path) => {
+                // Clean up existing socket if present
+                if socket_path.exists() {
+                    fs::remove_file(socket_path).ok();
+                }
+                command.env(SOCKET_ENV, socket_path);
+            }
+            PluginTransport::Tcp(_) => {
+                command.env(SECRET_ENV, secret.as_deref().unwrap_or_default());
+            }
         }
 
-        let mut child = tokio::process::Command::new(binary_path)
-            .env("TORU_PLUGIN_SOCKET", &socket_path_str)
+        let limits = self.resource_limits(plugin_id, &metadata);
+        let mut enforcement = if limits.is_empty() {
+            Enforcement::None
+        } else {
+            match plugin_limits::create_cgroup(plugin_id, &limits) {
+                Ok(cgroup) => Enforcement::Cgroup(cgroup),
+                Err(e) => {
+                    debug!("Using rlimits for plugin {}: {:#}", plugin_id, e);
+                    plugin_limits::apply_rlimits(&mut command, &limits);
+                    Enforcement::Rlimit
+                }
+            }
+        };
+
+        let mut child = command
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
@@ -260,10 +699,28 @@ impl PluginSupervisor {
 
         let pid = child.id();
 
-        // Capture stderr to plugin log file
+        if let (Enforcement::Cgroup(cgroup), Some(pid)) = (&enforcement, pid) {
+            if let Err(e) = plugin_limits::add_to_cgroup(cgroup, pid) {
+                // The health monitor still kills the plugin if it goes over its memory limit
+                warn!("Plugin {} runs without cgroup limits: {:#}", plugin_id, e);
+                plugin_limits::remove_cgroup(cgroup);
+                enforcement = Enforcement::None;
+            }
+        }
+
+        // Flags from an earlier run stay visible after a restart
+        let (limit_exceeded, last_crash_at, last_crash) = self
+            .plugins
+            .get(plugin_id)
+            .map(|p| (p.limit_exceeded, p.last_crash_at, p.last_crash.clone()))
+            .unwrap_or_default();
+
+        // Capture stderr to plugin log file, keeping the last lines for crash reports
+        let stderr_tail = Arc::new(StderrTail::default());
         if let Some(mut stderr) = child.stderr.take() {
             let plugin_logger = Arc::clone(&self.plugin_logger);
             let plugin_id_clone = plugin_id.to_string();
+            let stderr_tail = Arc::clone(&stderr_tail);
 
             tokio::spawn(async move {
                 use tokio::io::AsyncReadExt;
@@ -274,6 +731,7 @@ impl PluginSupervisor {
                         Ok(0) => break, // EOF
                         Ok(n) => {
                             let output = String::from_utf8_lossy(&buffer[..n]).to_string();
+                            stderr_tail.push(&output);
                             // Parse structured JSON logs or write as plain text
                             if let Ok(log_entry) =
                                 serde_json::from_str::<crate::services::logging::LogEntry>(&output)
@@ -299,12 +757,30 @@ impl PluginSupervisor {
             id: plugin_id.to_string(),
             process: Some(child),
             socket_path: socket_path_str,
+            transport: Some(transport),
+            secret,
             enabled: true,
             metadata: Some(metadata),
             pid,
+            started_at: Instant::now(),
+            connection: tokio::sync::Mutex::new(None),
+            limits,
+            enforcement,
+            usage: None,
+            limit_exceeded,
+            last_crash_at,
+            last_crash,
+            stderr_tail,
+            dev: false,
+            wasm: None,
+            breaker: Arc::default(),
         };
 
         self.plugins.insert(plugin_id.to_string(), process);
+        if let Some(stamp) = binary_stamp(binary_path) {
+            self.binary_stamps
+                .insert(plugin_id.to_string(), (binary_path.to_path_buf(), stamp));
+        }
         info!("Spawned plugin: {} (PID: {:?})", plugin_id, pid);
 
         // Notify plugin event via notification hooks
@@ -324,42 +800,269 @@ impl PluginSupervisor {
         Ok(())
     }
 
-    /// Kill a plugin process gracefully (with shutdown message)
+    /// Make sure no other plugin serves the route a plugin declares
+    ///
+    /// A plugin whose route is taken is not started. It stays listed, disabled,
+    /// with the conflict recorded, instead of shadowing the plugin that has the route.
+    async fn claim_route(&mut self, plugin_id: &str, metadata: &PluginMetadata) -> Result<()> {
+        let Some(owner) = self.route_owner(&metadata.route, Some(plugin_id)) else {
+            self.route_conflicts.remove(plugin_id);
+            return Ok(());
+        };
+
+        let conflict = format!(
+            "Route {} is already used by plugin {}",
+            metadata.route, owner
+        );
+        warn!("Not starting plugin {}: {}", plugin_id, conflict);
+        let reported = self
+            .route_conflicts
+            .insert(plugin_id.to_string(), conflict.clone());
+        if reported.as_ref() != Some(&conflict) {
+            self.notify_plugin_event(
+                plugin_id,
+                "route_conflict",
+                LogLevel::Warn,
+                Some(&serde_json::json!({ "route": metadata.route, "owner": owner }).to_string()),
+            )
+            .await;
+        }
+
+        self.keep_listed(plugin_id, metadata);
+        Err(anyhow::anyhow!(conflict))
+    }
+
+    /// Make sure the plugins a plugin depends on are installed, enabled and new enough
+    ///
+    /// Like a route conflict, a dependency problem keeps the plugin listed but
+    /// stopped, and is reported as a `dependency_problem` event when it changes.
+    async fn check_dependencies(
+        &mut self,
+        plugin_id: &str,
+        metadata: &PluginMetadata,
+    ) -> Result<()> {
+        let problems = self.find_dependency_problems(plugin_id, metadata);
+        if problems.is_empty() {
+            self.reported_dependency_problems.remove(plugin_id);
+            return Ok(());
+        }
+
+        warn!("Not starting plugin {}: {}", plugin_id, problems.join("; "));
+        let reported = self
+            .reported_dependency_problems
+            .insert(plugin_id.to_string(), problems.clone());
+        if reported.as_ref() != Some(&problems) {
+            self.notify_plugin_event(
+                plugin_id,
+                "dependency_problem",
+                LogLevel::Warn,
+                Some(&serde_json::json!({ "problems": problems }).to_string()),
+            )
+            .await;
+        }
+
+        self.keep_listed(plugin_id, metadata);
+        Err(DependencyProblem {
+            plugin_id: plugin_id.to_string(),
+            problems,
+        }
+        .into())
+    }
+
+    /// Why a plugin's dependencies would keep it from starting; empty if they don't
+    pub fn dependency_problems(&self, plugin_id: &str) -> Vec<String> {
+        self.plugins
+            .get(plugin_id)
+            .and_then(|process| process.metadata.as_ref())
+            .map(|metadata| self.find_dependency_problems(plugin_id, metadata))
+            .unwrap_or_default()
+    }
+
+    fn find_dependency_problems(&self, plugin_id: &str, metadata: &PluginMetadata) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.dependency_cycles.contains(plugin_id) {
+            problems.push("Dependency cycle".to_string());
+        }
+        for spec in &metadata.dependencies {
+            let dependency = match Dependency::parse(spec) {
+                Ok(dependency) => dependency,
+                Err(e) => {
+                    problems.push(e.to_string());
+                    continue;
+                }
+            };
+            let process = self.plugins.get(&dependency.id);
+            if dependency.id == plugin_id {
+                problems.push("Plugin depends on itself".to_string());
+            } else if process.is_none() && !self.plugin_binary_path(&dependency.id).exists() {
+                problems.push(format!("Dependency {} is not installed", dependency.id));
+            } else if !self.is_plugin_enabled(&dependency.id) {
+                problems.push(format!("Dependency {} is disabled", dependency.id));
+            } else if let (Some(min_version), Some(version)) = (
+                &dependency.min_version,
+                process
+                    .and_then(|process| process.metadata.as_ref())
+                    .map(|metadata| &metadata.version),
+            ) {
+                if plugin_deps::compare_versions(version, min_version).is_lt() {
+                    problems.push(format!(
+                        "Dependency {} is version {}, {} or newer is required",
+                        dependency.id, version, min_version
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Keep a plugin that isn't started listed, disabled, with its metadata
+    fn keep_listed(&mut self, plugin_id: &str, metadata: &PluginMetadata) {
+        match self.plugins.get_mut(plugin_id) {
+            Some(process) => {
+                process.enabled = false;
+                process.metadata = Some(metadata.clone());
+            }
+            None => {
+                let process = PluginProcess {
+                    id: plugin_id.to_string(),
+                    process: None,
+                    socket_path: String::new(),
+                    transport: None,
+                    secret: None,
+                    enabled: false,
+                    metadata: Some(metadata.clone()),
+                    pid: None,
+                    started_at: Instant::now(),
+                    connection: tokio::sync::Mutex::new(None),
+                    limits: PluginLimits::default(),
+                    enforcement: Enforcement::None,
+                    usage: None,
+                    limit_exceeded: None,
+                    last_crash_at: None,
+                    last_crash: None,
+                    stderr_tail: Arc::default(),
+                    dev: false,
+                    wasm: None,
+                    breaker: Arc::default(),
+                };
+                self.plugins.insert(plugin_id.to_string(), process);
+            }
+        }
+    }
+
+    /// Route conflict that kept a plugin from starting, if any
+    pub fn route_conflict(&self, plugin_id: &str) -> Option<&str> {
+        self.route_conflicts.get(plugin_id).map(String::as_str)
+    }
+
+    /// Load a WASM plugin and run it inside the supervisor
+    ///
+    /// The module's linear memory is capped at the plugin's memory limit and every
+    /// call into it gets a fuel budget; the CPU limit does not apply.
+    async fn spawn_wasm_plugin(
+        &mut self,
+        plugin_id: &str,
+        module_path: &Path,
+        metadata: PluginMetadata,
+    ) -> Result<()> {
+        let limits = self.resource_limits(plugin_id, &metadata);
+        let path = module_path.to_path_buf();
+        let wasm = tokio::task::spawn_blocking(move || WasmPlugin::load(&path, limits.memory_mb))
+            .await??;
+
+        let (limit_exceeded, last_crash_at, last_crash) = self
+            .plugins
+            .get(plugin_id)
+            .map(|p| (p.limit_exceeded, p.last_crash_at, p.last_crash.clone()))
+            .unwrap_or_default();
+
+        let process = PluginProcess {
+            id: plugin_id.to_string(),
+            process: None,
+            socket_path: module_path.display().to_string(),
+            transport: None,
+            secret: None,
+            enabled: true,
+            metadata: Some(metadata),
+            pid: None,
+            started_at: Instant::now(),
+            connection: tokio::sync::Mutex::new(None),
+            limits,
+            enforcement: Enforcement::Wasm,
+            usage: None,
+            limit_exceeded,
+            last_crash_at,
+            last_crash,
+            stderr_tail: Arc::default(),
+            dev: false,
+            wasm: Some(Arc::new(wasm)),
+            breaker: Arc::default(),
+        };
+
+        self.plugins.insert(plugin_id.to_string(), process);
+        if let Some(stamp) = binary_stamp(module_path) {
+            self.binary_stamps
+                .insert(plugin_id.to_string(), (module_path.to_path_buf(), stamp));
+        }
+        info!("Loaded WASM plugin: {}", plugin_id);
+
+        self.notify_plugin_event(
+            plugin_id,
+            "started",
+            LogLevel::Info,
+            Some(&serde_json::json!({ "runtime": "wasm" }).to_string()),
+        )
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop a plugin process gracefully
+    ///
+    /// Sends the lifecycle shutdown message and waits for the plugin's shutdown
+    /// timeout, then sends SIGTERM, and finally SIGKILL if it is still running.
     ///
     /// # Arguments
     /// * `plugin_id` - Plugin identifier to kill
     pub async fn kill_plugin(&mut self, plugin_id: &str) -> Result<()> {
+        let running = self
+            .plugins
+            .get(plugin_id)
+            .context("Plugin not found")?
+            .is_running();
+
+        let shutdown_timeout = self.shutdown_timeout(plugin_id);
+        if running {
+            if let Err(e) = self.send_shutdown_message(plugin_id).await {
+                debug!("Failed to send shutdown message to {}: {}", plugin_id, e);
+            }
+        }
+
         let process = self
             .plugins
             .get_mut(plugin_id)
             .context("Plugin not found")?;
 
         if let Some(mut child) = process.process.take() {
-            // Try graceful shutdown first
-            match child.start_kill() {
-                Ok(_) => {
-                    info!("Sent kill signal to plugin: {}", plugin_id);
-                }
-                Err(e) => {
-                    warn!("Failed to kill plugin {}: {}", plugin_id, e);
-                }
-            }
+            stop_child(plugin_id, &mut child, shutdown_timeout).await;
+        }
 
-            // Wait for process to exit (with timeout)
-            tokio::select! {
-                _ = child.wait() => {
-                    debug!("Plugin {} exited gracefully", plugin_id);
-                }
-                _ = tokio::time::sleep(tokio::time::Duration::from_secs(5)) => {
-                    warn!("Plugin {} did not exit within 5s, forcing", plugin_id);
-                }
-            }
+        *process.connection.get_mut() = None;
+        process.wasm = None;
+        process.usage = None;
+        self.jobs.remove(plugin_id);
+        self.heartbeats.remove(plugin_id);
+
+        if let Enforcement::Cgroup(cgroup) = &process.enforcement {
+            plugin_limits::remove_cgroup(cgroup);
         }
 
-        // Remove socket if it exists
-        if let Ok(socket_path) = std::path::PathBuf::from(&process.socket_path).canonicalize() {
-            if socket_path.exists() {
-                fs::remove_file(&socket_path).ok();
+        // Remove socket if it exists (a dev plugin's socket belongs to its developer)
+        if let Some(PluginTransport::Unix(socket_path)) = &process.transport {
+            if let Ok(socket_path) = socket_path.canonicalize() {
+                if !process.dev {
+                    fs::remove_file(&socket_path).ok();
+                }
             }
         }
 
@@ -367,12 +1070,56 @@ impl PluginSupervisor {
         info!("Plugin {} killed and disabled", plugin_id);
 
         // Notify plugin event via notification hooks
-        self.notify_plugin_event(plugin_id, "killed", LogLevel::Info, None)
+        self.notify_plugin_event(plugin_id, "stopped", LogLevel::Info, None)
             .await;
 
         Ok(())
     }
 
+    /// Stop every running plugin, for when the server shuts down
+    ///
+    /// All plugins are sent the shutdown message at once and given their shutdown
+    /// timeouts in parallel, instead of one after the other as with `kill_plugin`.
+    /// Their enabled state is kept for the next start.
+    pub async fn shutdown_all(&mut self) {
+        let running: Vec<String> = self
+            .plugins
+            .iter()
+            .filter(|(_, process)| process.is_running())
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for plugin_id in &running {
+            if let Err(e) = self.send_shutdown_message(plugin_id).await {
+                debug!("Failed to send shutdown message to {}: {}", plugin_id, e);
+            }
+        }
+
+        let mut children = Vec::new();
+        for plugin_id in &running {
+            let shutdown_timeout = self.shutdown_timeout(plugin_id);
+            if let Some(child) = self
+                .plugins
+                .get_mut(plugin_id)
+                .and_then(|process| process.process.take())
+            {
+                children.push((plugin_id.clone(), child, shutdown_timeout));
+            }
+        }
+        futures::future::join_all(children.iter_mut().map(
+            |(plugin_id, child, shutdown_timeout)| stop_child(plugin_id, child, *shutdown_timeout),
+        ))
+        .await;
+
+        // Their processes are gone; this releases sockets, cgroups and connections
+        for plugin_id in &running {
+            if let Err(e) = self.kill_plugin(plugin_id).await {
+                warn!("Failed to clean up plugin {}: {}", plugin_id, e);
+            }
+        }
+        info!("Stopped {} plugins", running.len());
+    }
+
     /// Check if a plugin is healthy (socket exists and process is running)
     ///
     /// # Arguments
@@ -380,8 +1127,6 @@ impl PluginSupervisor {
     ///
     /// # Returns
     /// true if healthy, false otherwise
-    // TODO: Integrate in health check endpoints and monitoring
-    #[allow(dead_code)]
     pub fn check_plugin_health(&self, plugin_id: &str) -> bool {
         let process = match self.plugins.get(plugin_id) {
             Some(p) => p,
@@ -393,8 +1138,7 @@ impl PluginSupervisor {
         }
 
         // Check if socket file exists
-        let socket_path = std::path::Path::new(&process.socket_path);
-        if !socket_path.exists() {
+        if !process.endpoint_ready() {
             debug!(
                 "Plugin {} socket not found: {:?}",
                 plugin_id, process.socket_path
@@ -402,6 +1146,11 @@ impl PluginSupervisor {
             return false;
         }
 
+        // A loaded WASM plugin runs as long as the supervisor does
+        if process.wasm.is_some() {
+            return true;
+        }
+
         // Check if process is still running using PID (Unix only)
         #[cfg(unix)]
         {
@@ -440,22 +1189,40 @@ impl PluginSupervisor {
     }
 
     /// Get the plugins directory path
+    #[allow(dead_code)] // Used by integration tests; routes use plugin_files_dir
     pub fn get_plugins_dir(&self) -> PathBuf {
         self.plugins_dir.clone()
     }
 
+    /// Path a plugin was last spawned from, else its `.wasm` module if there is one,
+    /// else `<id>.binary`
+    fn plugin_binary_path(&self, plugin_id: &str) -> PathBuf {
+        if let Some((path, _)) = self.binary_stamps.get(plugin_id) {
+            return path.clone();
+        }
+        if let Some(instance) = self.plugin_instance(plugin_id) {
+            return self.plugin_binary_path(&instance.plugin);
+        }
+        let module_path = self.plugins_dir.join(format!("{}.wasm", plugin_id));
+        if module_path.exists() {
+            return module_path;
+        }
+        self.plugins_dir.join(format!("{}.binary", plugin_id))
+    }
+
     /// Notify plugin event through all configured notification hooks
     ///
     /// This is the unified entry point for plugin event notifications.
     /// Currently writes to:
     /// 1. Log files (via supervisor_logger)
     /// 2. Database (plugin_events table)
+    /// 3. Webhooks subscribed to `plugin.crashed` and notification channels, for crashes
     ///
-    /// Future extensibility: Email, webhooks, Slack, etc.
+    /// Future extensibility: plugin-specific callbacks, etc.
     ///
     /// # Arguments
     /// * `plugin_id` - Plugin identifier
-    /// * `event_type` - Type of event (e.g., "started", "crash", "disabled")
+    /// * `event_type` - Type of event (e.g., "started", "stopped", "crashed", "restarted", "disabled")
     /// * `log_level` - Log level for file logging
     /// * `details` - Optional JSON string with event details
     pub async fn notify_plugin_event(
@@ -474,9 +1241,42 @@ impl PluginSupervisor {
         // Hook 2: Log to database
         let _ = crate::db::plugin_event_log(&self.db_pool, plugin_id, event_type, details).await;
 
-        // Future: Hook 3 - Email notifications
-        // Future: Hook 4 - Webhook calls
-        // Future: Hook 5 - Plugin-specific callbacks
+        // Hook 3: Webhooks and notifications, for events that mean the plugin went down
+        if matches!(
+            event_type,
+            "crashed" | "unresponsive" | "disabled_after_max_restarts"
+        ) {
+            let message = match event_type {
+                "crashed" => format!("Plugin {} crashed and is being restarted.", plugin_id),
+                "unresponsive" => format!(
+                    "Plugin {} stopped answering pings and is being restarted.",
+                    plugin_id
+                ),
+                _ => format!(
+                    "Plugin {} kept crashing and was disabled; enable it again once fixed.",
+                    plugin_id
+                ),
+            };
+            notifications::notify(
+                &self.db_pool,
+                Notification::new(
+                    NotificationKind::PluginCrash,
+                    NotificationLevel::Error,
+                    format!("Plugin {} {}", plugin_id, event_type.replace('_', " ")),
+                    message,
+                ),
+            );
+            crate::services::webhooks::emit(
+                "plugin.crashed",
+                serde_json::json!({
+                    "plugin_id": plugin_id,
+                    "event_type": event_type,
+                    "details": details.and_then(|d| serde_json::from_str::<serde_json::Value>(d).ok()),
+                }),
+            );
+        }
+
+        // Future: Hook 4 - Plugin-specific callbacks
     }
 
     /// Increment restart counter for a plugin
@@ -486,8 +1286,6 @@ impl PluginSupervisor {
     ///
     /// # Returns
     /// Current restart count
-    // Used in restart_plugin_with_backoff
-    #[allow(dead_code)]
     pub fn increment_restart_count(&mut self, plugin_id: &str) -> u32 {
         let count = self
             .restart_counts
@@ -514,8 +1312,6 @@ impl PluginSupervisor {
     ///
     /// # Returns
     /// true if should be disabled
-    // Used in restart_plugin_with_backoff
-    #[allow(dead_code)]
     pub fn should_disable_plugin(&self, plugin_id: &str) -> bool {
         self.get_restart_count(plugin_id) >= self.max_restarts
     }
@@ -524,8 +1320,6 @@ impl PluginSupervisor {
     ///
     /// # Arguments
     /// * `plugin_id` - Plugin identifier
-    // Used in tests and will be needed for crash recovery reset
-    #[allow(dead_code)]
     pub fn reset_restart_count(&mut self, plugin_id: &str) {
         self.restart_counts.remove(plugin_id);
     }
@@ -567,41 +1361,378 @@ impl PluginSupervisor {
     /// * `plugin_id` - Plugin identifier
     /// * `enabled` - Whether plugin should be enabled
     pub async fn set_plugin_enabled(&self, plugin_id: &str, enabled: bool) -> Result<()> {
-        let config_path = self.metadata_dir.join("config.json");
-
-        let mut config: serde_json::Value = if config_path.exists() {
-            let content = fs::read_to_string(&config_path)?;
-            serde_json::from_str(&content).unwrap_or_else(|_| serde_json::json!({}))
-        } else {
-            serde_json::json!({})
-        };
-
-        if !config.is_object() {
-            config = serde_json::json!({});
-        }
+        self.update_plugin_config("plugins", plugin_id, serde_json::json!(enabled))?;
+        debug!("Plugin {} enabled state set to: {}", plugin_id, enabled);
+        Ok(())
+    }
 
-        if !config["plugins"].is_object() {
-            config["plugins"] = serde_json::json!({});
-        }
+    /// How long a plugin gets to exit after the shutdown message
+    ///
+    /// Read from `shutdown_timeouts` in the metadata config, in seconds.
+    pub fn shutdown_timeout(&self, plugin_id: &str) -> Duration {
+        let config_path = self.metadata_dir.join("config.json");
+        fs::read_to_string(config_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|config| config["shutdown_timeouts"][plugin_id].as_u64())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT)
+    }
 
-        config["plugins"][plugin_id] = serde_json::json!(enabled);
+    /// Set how long a plugin gets to exit after the shutdown message
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    /// * `timeout` - Grace period before SIGTERM
+    pub async fn set_shutdown_timeout(&self, plugin_id: &str, timeout: Duration) -> Result<()> {
+        self.update_plugin_config(
+            "shutdown_timeouts",
+            plugin_id,
+            serde_json::json!(timeout.as_secs()),
+        )?;
+        debug!("Plugin {} shutdown timeout set to {:?}", plugin_id, timeout);
+        Ok(())
+    }
 
-        fs::write(&config_path, serde_json::to_string_pretty(&config)? + "\n")
-            .context("Failed to write plugin config")?;
+    /// How long a plugin gets to answer an HTTP request
+    ///
+    /// Read from `request_timeouts` in the metadata config, in seconds.
+    pub fn request_timeout(&self, plugin_id: &str) -> Duration {
+        let config_path = self.metadata_dir.join("config.json");
+        fs::read_to_string(config_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|config| config["request_timeouts"][plugin_id].as_u64())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_REQUEST_TIMEOUT)
+    }
 
-        debug!("Plugin {} enabled state set to: {}", plugin_id, enabled);
+    /// Set how long a plugin gets to answer an HTTP request
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    /// * `timeout` - Time before the request fails and counts against the circuit breaker
+    pub async fn set_request_timeout(&self, plugin_id: &str, timeout: Duration) -> Result<()> {
+        self.update_plugin_config(
+            "request_timeouts",
+            plugin_id,
+            serde_json::json!(timeout.as_secs()),
+        )?;
+        debug!("Plugin {} request timeout set to {:?}", plugin_id, timeout);
         Ok(())
     }
 
-    /// Enable a plugin (spawn process and set enabled flag)
-    pub async fn enable_plugin(&mut self, plugin_id: &str) -> Result<()> {
-        self.set_plugin_enabled(plugin_id, true).await?;
-
+    /// Resource limits for a plugin
+    ///
+    /// Limits under `limits` in the metadata config take precedence over the ones
+    /// the plugin declares in its metadata.
+    pub fn resource_limits(&self, plugin_id: &str, metadata: &PluginMetadata) -> PluginLimits {
+        let config_path = self.metadata_dir.join("config.json");
+        fs::read_to_string(config_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|config| serde_json::from_value(config["limits"][plugin_id].clone()).ok())
+            .unwrap_or(metadata.limits)
+    }
+
+    /// Override the resource limits of a plugin; they apply from its next start
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    /// * `limits` - Limits to use instead of the ones in the plugin metadata
+    pub async fn set_resource_limits(&self, plugin_id: &str, limits: PluginLimits) -> Result<()> {
+        self.update_plugin_config("limits", plugin_id, serde_json::to_value(limits)?)?;
+        debug!("Plugin {} resource limits set to {:?}", plugin_id, limits);
+        Ok(())
+    }
+
+    /// KV storage quota of a plugin
+    ///
+    /// Read from `kv_quotas` in the metadata config; the default quota applies
+    /// to plugins without one.
+    pub fn kv_quota(&self, plugin_id: &str) -> PluginKvQuota {
+        let config_path = self.metadata_dir.join("config.json");
+        fs::read_to_string(config_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|config| serde_json::from_value(config["kv_quotas"][plugin_id].clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Override the KV storage quota of a plugin
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    /// * `quota` - Byte and key limits; `None` fields are unlimited
+    pub async fn set_kv_quota(&self, plugin_id: &str, quota: PluginKvQuota) -> Result<()> {
+        self.update_plugin_config("kv_quotas", plugin_id, serde_json::to_value(quota)?)?;
+        debug!("Plugin {} KV quota set to {:?}", plugin_id, quota);
+        Ok(())
+    }
+
+    /// Capabilities an admin granted a plugin
+    ///
+    /// Read from `capabilities` in the metadata config.
+    pub fn granted_capabilities(&self, plugin_id: &str) -> Vec<String> {
+        let config_path = self.metadata_dir.join("config.json");
+        fs::read_to_string(config_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|config| {
+                serde_json::from_value(config["capabilities"][plugin_id].clone()).ok()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Set the capabilities a plugin is granted, replacing the previous grant
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    /// * `capabilities` - Capabilities such as `call:metrics`; `call:*` grants all calls
+    pub async fn set_granted_capabilities(
+        &self,
+        plugin_id: &str,
+        capabilities: &[String],
+    ) -> Result<()> {
+        self.update_plugin_config(
+            "capabilities",
+            plugin_id,
+            serde_json::to_value(capabilities)?,
+        )?;
+        debug!(
+            "Plugin {} granted capabilities {:?}",
+            plugin_id, capabilities
+        );
+        Ok(())
+    }
+
+    /// Whether a plugin both asks for a capability in its metadata and was granted it
+    pub fn has_capability(&self, plugin_id: &str, capability: &str) -> bool {
+        let requested = self
+            .plugins
+            .get(plugin_id)
+            .and_then(|process| process.metadata.as_ref())
+            .is_some_and(|metadata| metadata.requests_capability(capability));
+        requested
+            && self
+                .granted_capabilities(plugin_id)
+                .iter()
+                .any(|pattern| toru_plugin_api::pattern_matches(pattern, capability))
+    }
+
+    /// Directory a plugin keeps its own files in (`TORU_PLUGIN_DATA_DIR`)
+    pub fn plugin_data_dir(&self, plugin_id: &str) -> PathBuf {
+        self.plugins_dir.join(".data").join(plugin_id)
+    }
+
+    /// Environment variables an admin defined for a plugin
+    ///
+    /// Read from `env` in the metadata config.
+    pub fn plugin_env(&self, plugin_id: &str) -> HashMap<String, String> {
+        let config_path = self.metadata_dir.join("config.json");
+        fs::read_to_string(config_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|config| serde_json::from_value(config["env"][plugin_id].clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Set the environment variables of a plugin; they apply from its next start
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    /// * `env` - Variables by name; replaces the previous ones
+    pub async fn set_plugin_env(
+        &self,
+        plugin_id: &str,
+        env: HashMap<String, String>,
+    ) -> Result<()> {
+        for (name, value) in &env {
+            validate_env_var(name, value)?;
+        }
+        self.update_plugin_config("env", plugin_id, serde_json::to_value(&env)?)?;
+        debug!(
+            "Plugin {} environment set ({} variables)",
+            plugin_id,
+            env.len()
+        );
+        Ok(())
+    }
+
+    /// Directory with the frontend bundle and assets a plugin was installed with;
+    /// instances share their plugin's
+    pub fn plugin_files_dir(&self, plugin_id: &str) -> PathBuf {
+        match self.plugin_instance(plugin_id) {
+            Some(instance) => self.plugins_dir.join(instance.plugin),
+            None => self.plugins_dir.join(plugin_id),
+        }
+    }
+
+    /// Instances defined under `instances` in the metadata config, by instance ID
+    pub fn plugin_instances(&self) -> HashMap<String, PluginInstance> {
+        let config_path = self.metadata_dir.join("config.json");
+        fs::read_to_string(config_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|config| serde_json::from_value(config["instances"].clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Definition of a plugin instance, if `plugin_id` is one
+    pub fn plugin_instance(&self, plugin_id: &str) -> Option<PluginInstance> {
+        if !plugin_id.contains(INSTANCE_SEPARATOR) {
+            return None;
+        }
+        self.plugin_instances().remove(plugin_id)
+    }
+
+    /// Create a named instance of an installed plugin and start it
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin whose binary the instance runs
+    /// * `name` - Instance name, appended to the plugin's ID and route
+    /// * `settings` - Passed to the instance in `init`
+    ///
+    /// # Returns
+    /// The instance, as defined in the metadata config
+    pub async fn create_instance(
+        &mut self,
+        plugin_id: &str,
+        name: &str,
+        settings: HashMap<String, String>,
+    ) -> Result<PluginInstance> {
+        validate_instance_name(name)?;
+        let process = self.plugins.get(plugin_id).context("Plugin not found")?;
+        if process.dev || plugin_id.contains(INSTANCE_SEPARATOR) {
+            return Err(anyhow::anyhow!(
+                "Instances can only be created of installed plugins"
+            ));
+        }
+        let metadata = process
+            .metadata
+            .clone()
+            .context("Plugin metadata is unknown")?;
+
+        let instance = PluginInstance {
+            plugin: plugin_id.to_string(),
+            name: name.to_string(),
+            settings,
+        };
+        let instance_id = instance.id();
+        if self.plugins.contains_key(&instance_id) {
+            return Err(anyhow::anyhow!("Instance {} already exists", instance_id));
+        }
+        let metadata = instance.metadata(metadata);
+        validate_route(&metadata.route)?;
+        if let Some(owner) = self.get_plugin_for_route(&metadata.route) {
+            return Err(anyhow::anyhow!(
+                "Route {} is already used by plugin {}",
+                metadata.route,
+                owner
+            ));
+        }
+
+        self.update_plugin_config("instances", &instance_id, serde_json::to_value(&instance)?)?;
+        let binary_path = self.plugin_binary_path(plugin_id);
+        if let Err(e) = self
+            .spawn_plugin(&instance_id, &binary_path, metadata)
+            .await
+        {
+            self.update_plugin_config("instances", &instance_id, serde_json::Value::Null)?;
+            self.plugins.remove(&instance_id);
+            return Err(e);
+        }
+        if let Err(e) = self.send_init_message(&instance_id).await {
+            error!("Failed to send init message to {}: {}", instance_id, e);
+        }
+
+        info!("Created plugin instance {}", instance_id);
+        Ok(instance)
+    }
+
+    /// Stop a plugin instance and delete its definition
+    ///
+    /// Its KV data is kept, so an instance created again under the same name
+    /// finds it.
+    pub async fn remove_instance(&mut self, instance_id: &str) -> Result<()> {
+        if self.plugin_instance(instance_id).is_none() {
+            return Err(anyhow::anyhow!("Instance {} not found", instance_id));
+        }
+        if self.plugins.contains_key(instance_id) {
+            self.kill_plugin(instance_id).await?;
+            self.plugins.remove(instance_id);
+        }
+        self.binary_stamps.remove(instance_id);
+        self.pending_reloads.remove(instance_id);
+        self.restart_counts.remove(instance_id);
+        self.route_conflicts.remove(instance_id);
+        self.update_plugin_config("instances", instance_id, serde_json::Value::Null)?;
+
+        info!("Removed plugin instance {}", instance_id);
+        Ok(())
+    }
+
+    /// Set `config[section][plugin_id]` in the metadata config file; `null`
+    /// removes the entry
+    fn update_plugin_config(
+        &self,
+        section: &str,
+        plugin_id: &str,
+        value: serde_json::Value,
+    ) -> Result<()> {
+        let config_path = self.metadata_dir.join("config.json");
+
+        let mut config: serde_json::Value = if config_path.exists() {
+            let content = fs::read_to_string(&config_path)?;
+            serde_json::from_str(&content).unwrap_or_else(|_| serde_json::json!({}))
+        } else {
+            serde_json::json!({})
+        };
+
+        if !config.is_object() {
+            config = serde_json::json!({});
+        }
+
+        if !config[section].is_object() {
+            config[section] = serde_json::json!({});
+        }
+
+        if value.is_null() {
+            if let Some(section) = config[section].as_object_mut() {
+                section.remove(plugin_id);
+            }
+        } else {
+            config[section][plugin_id] = value;
+        }
+
+        fs::write(&config_path, serde_json::to_string_pretty(&config)? + "\n")
+            .context("Failed to write plugin config")?;
+        Ok(())
+    }
+
+    /// Enable a plugin (spawn process and set enabled flag)
+    pub async fn enable_plugin(&mut self, plugin_id: &str) -> Result<()> {
+        let problems = self.dependency_problems(plugin_id);
+        if !problems.is_empty() {
+            return Err(DependencyProblem {
+                plugin_id: plugin_id.to_string(),
+                problems,
+            }
+            .into());
+        }
+        self.set_plugin_enabled(plugin_id, true).await?;
+        // A manual enable gives the plugin a fresh restart budget
+        self.reset_restart_count(plugin_id);
+        if let Some(process) = self.plugins.get_mut(plugin_id) {
+            process.limit_exceeded = None;
+        }
+
+        let binary_path = self.plugin_binary_path(plugin_id);
         if let Some(process) = self.plugins.get_mut(plugin_id) {
             // If plugin is disabled or not running, spawn it
-            if !process.enabled || process.process.is_none() {
-                // Get binary path from plugins directory
-                let binary_path = self.plugins_dir.join(format!("{}.binary", plugin_id));
+            if process.dev {
+                process.enabled = true;
+            } else if !process.enabled || !process.is_running() {
                 if let Some(metadata) = process.metadata.clone() {
                     // Spawn the plugin (process reference is dropped automatically at end of scope)
                     let _ = process; // Explicitly indicate we're done with the mutable borrow
@@ -622,10 +1753,12 @@ impl PluginSupervisor {
         }
 
         // Wait for socket to be ready after spawning (similar to send_init_message retry logic)
-        let socket_path = self.sockets_dir.join(format!("{}.sock", plugin_id));
         for _ in 0..20 {
             // 20 retries * 100ms = 2 seconds max
-            if socket_path.exists() {
+            if self
+                .get_plugin_status(plugin_id)
+                .is_none_or(|p| p.endpoint_ready())
+            {
                 break;
             }
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
@@ -665,7 +1798,29 @@ impl PluginSupervisor {
 
         let mut spawned_count = 0;
 
-        for (plugin_id, (binary_path, metadata)) in discovered {
+        // Dependencies start first, otherwise plugins start sorted so the same
+        // plugin wins a route conflict on every start
+        let graph = discovered
+            .iter()
+            .map(|(plugin_id, (_, metadata))| {
+                let dependencies = metadata
+                    .dependencies
+                    .iter()
+                    .filter_map(|spec| Dependency::parse(spec).ok())
+                    .map(|dependency| dependency.id)
+                    .collect();
+                (plugin_id.clone(), dependencies)
+            })
+            .collect();
+        let (mut order, cyclic) = plugin_deps::start_order(&graph);
+        self.dependency_cycles = cyclic.iter().cloned().collect();
+        order.extend(cyclic);
+
+        let mut discovered = discovered;
+        for plugin_id in order {
+            let Some((binary_path, metadata)) = discovered.remove(&plugin_id) else {
+                continue;
+            };
             // Check if plugin is enabled
             if self.is_plugin_enabled(&plugin_id) {
                 match self.spawn_plugin(&plugin_id, &binary_path, metadata).await {
@@ -677,8 +1832,18 @@ impl PluginSupervisor {
                             // Continue anyway - plugin may still work
                         }
                     }
+                    // Already reported as a route_conflict or dependency_problem event
+                    Err(_) if self.route_conflict(&plugin_id).is_some() => {}
+                    Err(e) if e.is::<DependencyProblem>() => {}
                     Err(e) => {
                         error!("Failed to spawn plugin {}: {}", plugin_id, e);
+                        self.notify_plugin_event(
+                            &plugin_id,
+                            "start_failed",
+                            LogLevel::Error,
+                            Some(&serde_json::json!({ "error": e.to_string() }).to_string()),
+                        )
+                        .await;
                     }
                 }
             } else {
@@ -693,229 +1858,703 @@ impl PluginSupervisor {
         Ok(spawned_count)
     }
 
-    /// Send lifecycle init message to a plugin via Unix socket
+    /// Install a plugin from an uploaded package and start it
+    ///
+    /// The binary is staged under a temporary name, validated by running it with
+    /// `--metadata`, and only then renamed to `<id>.binary` so a half-written or
+    /// invalid upload is never picked up by `scan_plugins_directory`.
     ///
     /// # Arguments
-    /// * `plugin_id` - Plugin identifier
-    async fn send_init_message(&self, plugin_id: &str) -> Result<()> {
-        use toru_plugin_api::{LifecycleInitPayload, PluginProtocol};
-
-        let process = self
-            .get_plugin_status(plugin_id)
-            .context("Plugin not found")?;
-
-        // Wait for socket to be available (with timeout)
-        let socket_path = std::path::Path::new(&process.socket_path);
-        let mut retries = 10;
-
-        while !socket_path.exists() && retries > 0 {
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-            retries -= 1;
+    /// * `package` - Plugin binary and optional frontend bundle
+    ///
+    /// # Returns
+    /// Metadata of the installed plugin
+    pub async fn install_plugin(
+        &mut self,
+        package: PluginPackage,
+    ) -> std::result::Result<PluginMetadata, InstallError> {
+        let staged = self.stage_package(&package).await?;
+        let metadata = staged.metadata.clone();
+
+        let binary_path = self.plugins_dir.join(format!("{}.binary", metadata.id));
+        if binary_path.exists() || self.plugins.contains_key(&metadata.id) {
+            staged.discard();
+            return Err(InstallError::AlreadyInstalled(metadata.id));
         }
 
-        if !socket_path.exists() {
-            return Err(anyhow::anyhow!("Plugin socket not available after waiting"));
+        if let Err(e) = self.write_package_files(&metadata.id, &package) {
+            staged.discard();
+            return Err(e.into());
         }
 
-        // Connect to plugin socket
-        let mut stream = UnixStream::connect(&process.socket_path)
-            .await
-            .context("Failed to connect to plugin socket")?;
-
-        // Create init message with instance_id
-        let init_payload = LifecycleInitPayload {
-            instance_id: self.instance_id.clone(),
-            plugin_socket: process.socket_path.clone(),
-            log_path: format!("/var/log/toru/plugins/{}.log", plugin_id),
-        };
-
-        let message = Message::new_lifecycle("init", Some(init_payload));
-
-        // Send message using length-prefixed protocol
-        let protocol = PluginProtocol::new();
-        protocol
-            .write_message(&mut stream, &message)
-            .await
-            .context("Failed to send init message")?;
-
-        debug!("Sent init message to plugin {}", plugin_id);
-        Ok(())
-    }
-
-    /// Send lifecycle shutdown message to a plugin via Unix socket
-    ///
-    /// # Arguments
-    /// * `plugin_id` - Plugin identifier
-    // TODO: Integrate in graceful shutdown flow
-    #[allow(dead_code)]
-    async fn send_shutdown_message(&self, plugin_id: &str) -> Result<()> {
-        use toru_plugin_api::PluginProtocol;
-
-        let process = self
-            .get_plugin_status(plugin_id)
-            .context("Plugin not found")?;
+        staged
+            .install(&binary_path)
+            .context("Failed to install plugin binary")?;
+        info!("Installed plugin {} ({})", metadata.id, metadata.version);
 
-        let socket_path = std::path::Path::new(&process.socket_path);
+        self.notify_plugin_event(
+            &metadata.id,
+            "installed",
+            LogLevel::Info,
+            Some(&serde_json::json!({ "version": metadata.version }).to_string()),
+        )
+        .await;
 
-        if !socket_path.exists() {
-            debug!(
-                "Plugin {} socket not found, skipping shutdown message",
-                plugin_id
-            );
-            return Ok(());
+        if self.is_plugin_enabled(&metadata.id) {
+            self.spawn_plugin(&metadata.id, &binary_path, metadata.clone())
+                .await?;
+            if let Err(e) = self.send_init_message(&metadata.id).await {
+                error!("Failed to send init message to {}: {}", metadata.id, e);
+            }
         }
 
-        // Connect to plugin socket
-        let mut stream = UnixStream::connect(&process.socket_path)
-            .await
-            .context("Failed to connect to plugin socket")?;
-
-        // Create shutdown message
-        let message = Message::new_lifecycle("shutdown", None);
-
-        // Send message using length-prefixed protocol
-        let protocol = PluginProtocol::new();
-        protocol
-            .write_message(&mut stream, &message)
-            .await
-            .context("Failed to send shutdown message")?;
-
-        debug!("Sent shutdown message to plugin {}", plugin_id);
-        Ok(())
+        Ok(metadata)
     }
 
-    /// Forward an HTTP request to a plugin
+    /// Upgrade an installed plugin to a newer version from an uploaded package
     ///
-    /// This method is used by the HTTP router to forward requests to plugins.
-    /// The plugin route path is resolved to the plugin ID, and the request
-    /// is forwarded via the plugin's Unix socket.
+    /// The new binary is validated like an install and must report the same ID and
+    /// a higher version. The old binary is kept as `<id>.binary.prev` while the new
+    /// one starts; if it fails to spawn, take `init` or migrate its data, the old
+    /// binary is put back and started again. A disabled plugin just gets the new
+    /// binary and migrates when it is next started.
     ///
     /// # Arguments
-    /// * `plugin_id` - Plugin identifier
-    /// * `request` - HTTP request to forward
+    /// * `plugin_id` - Plugin to upgrade
+    /// * `package` - Plugin binary and optional frontend bundle
     ///
     /// # Returns
-    /// The plugin's HTTP response
-    pub async fn forward_http_request(
-        &self,
+    /// Metadata of the upgraded plugin
+    pub async fn upgrade_plugin(
+        &mut self,
         plugin_id: &str,
-        request: &HttpRequest,
-    ) -> Result<HttpMessageResponse> {
-        let process = self
-            .get_plugin_status(plugin_id)
-            .context("Plugin not found")?;
+        package: PluginPackage,
+    ) -> std::result::Result<PluginMetadata, InstallError> {
+        let binary_path = self.plugins_dir.join(format!("{}.binary", plugin_id));
+        if !binary_path.exists() {
+            return Err(InstallError::NotInstalled(plugin_id.to_string()));
+        }
 
-        // Check if plugin is enabled and has a socket
-        if !process.enabled {
-            return Err(anyhow::anyhow!("Plugin {} is not enabled", plugin_id));
+        let staged = self.stage_package(&package).await?;
+        let metadata = staged.metadata.clone();
+        if metadata.id != plugin_id {
+            staged.discard();
+            return Err(InstallError::Invalid(format!(
+                "package contains plugin {}, not {}",
+                metadata.id, plugin_id
+            )));
         }
 
-        let socket_path = std::path::Path::new(&process.socket_path);
-        if !socket_path.exists() {
-            return Err(anyhow::anyhow!("Plugin {} socket not found", plugin_id));
+        // A binary too broken to report its metadata can be replaced by any version
+        let installed = match self.plugins.get(plugin_id).and_then(|p| p.metadata.clone()) {
+            Some(installed) => Some(installed),
+            None => self.read_plugin_metadata(&binary_path).await.ok(),
+        };
+        let from_version = installed.as_ref().map(|m| m.version.clone());
+        if let Some(from_version) = &from_version {
+            if plugin_deps::compare_versions(&metadata.version, from_version).is_le() {
+                staged.discard();
+                return Err(InstallError::NotNewer {
+                    plugin_id: plugin_id.to_string(),
+                    installed: from_version.clone(),
+                    version: metadata.version,
+                });
+            }
         }
 
-        // Connect to plugin socket
-        let mut stream = UnixStream::connect(&process.socket_path)
-            .await
-            .context("Failed to connect to plugin socket")?;
+        if self.plugins.get(plugin_id).is_some_and(|p| p.is_running()) {
+            if let Err(e) = self.kill_plugin(plugin_id).await {
+                staged.discard();
+                return Err(e.into());
+            }
+        }
 
-        // Generate a unique request ID
-        let request_id = uuid::Uuid::new_v4().to_string();
+        let previous_path = previous_binary_path(&binary_path);
+        if let Err(e) = move_binary(&binary_path, &previous_path) {
+            staged.discard();
+            return Err(anyhow::Error::from(e)
+                .context("Failed to keep the previous plugin binary")
+                .into());
+        }
+        if let Err(e) = staged.install(&binary_path) {
+            staged.discard();
+            move_binary(&previous_path, &binary_path).ok();
+            return Err(anyhow::Error::from(e)
+                .context("Failed to install plugin binary")
+                .into());
+        }
+        if let Some(from_version) = &from_version {
+            self.update_plugin_config(
+                "pending_migrations",
+                plugin_id,
+                serde_json::json!(from_version),
+            )?;
+        }
 
-        // Create HTTP request message
-        let message = Message::new_http(request_id.clone(), request.clone());
+        if self.is_plugin_enabled(plugin_id) {
+            if let Err(e) = self
+                .start_upgraded_plugin(plugin_id, &binary_path, metadata.clone())
+                .await
+            {
+                let error = format!("{:#}", e);
+                self.roll_back_upgrade(plugin_id, &binary_path, installed, &metadata, &error)
+                    .await;
+                return Err(InstallError::RolledBack {
+                    plugin_id: plugin_id.to_string(),
+                    error,
+                });
+            }
+        }
 
-        // Use the protocol to send the message
-        use toru_plugin_api::PluginProtocol;
-        let mut protocol = PluginProtocol::new();
-        protocol
-            .write_message(&mut stream, &message)
-            .await
-            .context("Failed to send HTTP request to plugin")?;
+        fs::remove_file(plugin_signing::signature_path(&previous_path)).ok();
+        fs::remove_file(&previous_path).ok();
+        self.write_package_files(plugin_id, &package)?;
 
-        // Read the response with timeout to prevent hanging on unresponsive plugins
-        let response_msg = tokio::time::timeout(
-            tokio::time::Duration::from_secs(30),
-            protocol.read_message(&mut stream),
+        info!(
+            "Upgraded plugin {} ({:?} -> {})",
+            plugin_id, from_version, metadata.version
+        );
+        self.notify_plugin_event(
+            plugin_id,
+            "upgraded",
+            LogLevel::Info,
+            Some(
+                &serde_json::json!({
+                    "from_version": from_version,
+                    "to_version": metadata.version,
+                })
+                .to_string(),
+            ),
         )
-        .await
-        .map_err(|_| anyhow::anyhow!("Plugin response timeout after 30s"))?
-        .context("Failed to read HTTP response from plugin")?;
-
-        // Extract the HTTP response - the plugin sends HttpRequest with body containing JSON response
-        // Message structure:
-        // {
-        //   "payload": {
-        //     "type": "http",
-        //     "request_id": "...",
-        //     "payload": {         // HttpRequest
-        //       "method": "RESPONSE",
-        //       "body": "{\"status\":200,\"headers\":{...},\"body\":\"...\"}"  // JSON string
-        //     }
-        //   }
-        // }
-        let response_value =
-            serde_json::to_value(&response_msg).context("Failed to serialize response message")?;
-
-        // Get the inner payload (HttpRequest) and extract the body JSON string
-        let body_json_str = response_value
-            .get("payload")
-            .and_then(|p| p.get("payload"))  // Get HttpRequest from MessagePayload::Http
-            .and_then(|req| req.get("body"))
-            .and_then(|b| b.as_str())
-            .unwrap_or("{}");
-
-        // Parse the body JSON string to get the actual response fields
-        let parsed_response: serde_json::Value =
-            serde_json::from_str(body_json_str).unwrap_or_else(|_| serde_json::json!({}));
-
-        let http_response = toru_plugin_api::HttpMessageResponse {
-            status: parsed_response
-                .get("status")
-                .and_then(|s| s.as_u64())
-                .unwrap_or(500) as u16,
-            headers: parsed_response
-                .get("headers")
-                .and_then(|h| serde_json::from_value(h.clone()).ok())
-                .unwrap_or_default(),
-            body: parsed_response
-                .get("body")
-                .and_then(|b| {
-                    // body can be either a string or null
-                    if b.is_string() {
-                        Some(b.as_str().unwrap().to_string())
-                    } else if b.is_null() {
-                        None
-                    } else {
-                        // If body is an object/array, serialize it
-                        Some(serde_json::to_string(b).unwrap_or_default())
-                    }
-                }),
-        };
+        .await;
 
-        Ok(http_response)
+        Ok(metadata)
     }
 
-    /// Get the plugin ID that owns a given route path
-    ///
-    /// Each plugin declares a route in its metadata (e.g., "/my-plugin").
-    /// This method looks up which plugin owns a given route path.
-    ///
-    /// # Arguments
-    /// * `route_path` - The route path (e.g., "/my-plugin")
-    ///
-    /// # Returns
-    /// The plugin ID that owns the route, if found
-    pub fn get_plugin_for_route(&self, route_path: &str) -> Option<String> {
-        for (plugin_id, process) in self.plugins.iter() {
-            if let Some(metadata) = &process.metadata {
-                if metadata.route == route_path {
-                    return Some(plugin_id.clone());
-                }
+    /// Spawn an upgraded plugin and see it through `init` and its migration
+    async fn start_upgraded_plugin(
+        &mut self,
+        plugin_id: &str,
+        binary_path: &Path,
+        metadata: PluginMetadata,
+    ) -> Result<()> {
+        self.spawn_plugin(plugin_id, binary_path, metadata).await?;
+        self.send_init_message(plugin_id).await?;
+        if self.reap_plugin_process(plugin_id).is_some() {
+            return Err(anyhow::anyhow!("Plugin exited after init"));
+        }
+        Ok(())
+    }
+
+    /// Put the previous binary of a failed upgrade back and start it again
+    async fn roll_back_upgrade(
+        &mut self,
+        plugin_id: &str,
+        binary_path: &Path,
+        installed: Option<PluginMetadata>,
+        attempted: &PluginMetadata,
+        error: &str,
+    ) {
+        error!("Upgrade of plugin {} failed: {}", plugin_id, error);
+        if self.plugins.contains_key(plugin_id) {
+            self.kill_plugin(plugin_id).await.ok();
+        }
+        self.update_plugin_config("pending_migrations", plugin_id, serde_json::Value::Null)
+            .ok();
+
+        let previous_path = previous_binary_path(binary_path);
+        if let Err(e) = move_binary(&previous_path, binary_path) {
+            error!(
+                "Failed to restore the previous binary of {}: {}",
+                plugin_id, e
+            );
+        } else if let Some(installed) = installed.clone() {
+            let restarted = match self.spawn_plugin(plugin_id, binary_path, installed).await {
+                Ok(()) => self.send_init_message(plugin_id).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = restarted {
+                error!("Failed to restart {} after rollback: {}", plugin_id, e);
+            }
+        }
+
+        self.notify_plugin_event(
+            plugin_id,
+            "upgrade_failed",
+            LogLevel::Error,
+            Some(
+                &serde_json::json!({
+                    "from_version": installed.map(|m| m.version),
+                    "to_version": attempted.version,
+                    "error": error,
+                })
+                .to_string(),
+            ),
+        )
+        .await;
+    }
+
+    /// Write an uploaded binary under a temporary name and validate it
+    ///
+    /// Checks the signature too when signed plugins are required.
+    async fn stage_package(
+        &self,
+        package: &PluginPackage,
+    ) -> std::result::Result<StagedBinary, InstallError> {
+        if package.binary.is_empty() {
+            return Err(InstallError::Invalid("empty plugin binary".to_string()));
+        }
+
+        let path = self
+            .plugins_dir
+            .join(format!(".install-{}.tmp", uuid::Uuid::new_v4()));
+        let signature_path = plugin_signing::signature_path(&path);
+        let remove_staged = || {
+            fs::remove_file(&path).ok();
+            fs::remove_file(&signature_path).ok();
+        };
+
+        write_executable(&path, &package.binary).context("Failed to write plugin binary")?;
+        if let Some(signature) = &package.signature {
+            if let Err(e) = fs::write(&signature_path, signature) {
+                remove_staged();
+                return Err(anyhow::Error::from(e)
+                    .context("Failed to write plugin signature")
+                    .into());
+            }
+        }
+
+        let metadata = match self.read_plugin_metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                remove_staged();
+                return Err(InstallError::Invalid(format!("{:#}", e)));
+            }
+        };
+
+        Ok(StagedBinary {
+            path,
+            signature_path,
+            signed: package.signature.is_some(),
+            metadata,
+        })
+    }
+
+    /// Write the frontend bundle and assets of a package, if it has them
+    fn write_package_files(&self, plugin_id: &str, package: &PluginPackage) -> Result<()> {
+        let plugin_dir = self.plugins_dir.join(plugin_id);
+        if let Some(bundle) = &package.bundle {
+            fs::create_dir_all(&plugin_dir)
+                .and_then(|_| write_atomic(&plugin_dir.join("bundle.js"), bundle))
+                .context("Failed to write plugin bundle")?;
+        }
+        if !package.assets.is_empty() {
+            plugin_assets::write_assets(&plugin_dir, &package.assets)?;
+        }
+        Ok(())
+    }
+
+    /// Send lifecycle init message to a plugin via Unix socket
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    async fn send_init_message(&self, plugin_id: &str) -> Result<()> {
+        use toru_plugin_api::LifecycleInitPayload;
+
+        let process = self
+            .get_plugin_status(plugin_id)
+            .context("Plugin not found")?;
+
+        // Wait for the plugin to start listening (with timeout)
+        let mut retries = 10;
+        let connection = loop {
+            match self.plugin_connection(plugin_id).await {
+                Ok(connection) => break connection,
+                Err(_) if retries > 0 => {
+                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                    retries -= 1;
+                }
+                Err(e) => return Err(e.context("Plugin socket not available after waiting")),
+            }
+        };
+
+        let instance = self.plugin_instance(plugin_id);
+
+        // Create init message with instance_id and the version both sides speak
+        let init_payload = LifecycleInitPayload {
+            instance_id: self.instance_id.clone(),
+            plugin_socket: process.socket_path.clone(),
+            log_path: self
+                .plugin_logger
+                .get_plugin_log_path(plugin_id)
+                .display()
+                .to_string(),
+            protocol_version: protocol_version(process).min(PROTOCOL_VERSION),
+            // Every format the plugin might pick; it may also keep to JSON
+            wire_formats: vec![WireFormat::MessagePack, WireFormat::Json],
+            instance_name: instance.as_ref().map(|instance| instance.name.clone()),
+            settings: instance
+                .map(|instance| instance.settings)
+                .unwrap_or_default(),
+        };
+
+        let message = Message::new_lifecycle("init", Some(init_payload));
+
+        connection
+            .send(&message)
+            .await
+            .context("Failed to send init message")?;
+
+        debug!("Sent init message to plugin {}", plugin_id);
+        self.send_pending_migration(plugin_id, &connection).await
+    }
+
+    /// Version a plugin was upgraded from, until it has migrated its data
+    ///
+    /// Read from `pending_migrations` in the metadata config.
+    pub fn pending_migration(&self, plugin_id: &str) -> Option<String> {
+        let config_path = self.metadata_dir.join("config.json");
+        fs::read_to_string(config_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|config| {
+                config["pending_migrations"][plugin_id]
+                    .as_str()
+                    .map(String::from)
+            })
+    }
+
+    /// Ask an upgraded plugin to migrate its data, if it hasn't yet
+    ///
+    /// A failed migration stays pending and is asked for again on the next start.
+    /// Plugins speaking an older protocol don't know the message; their migration
+    /// counts as done.
+    async fn send_pending_migration(
+        &self,
+        plugin_id: &str,
+        connection: &PluginConnection,
+    ) -> Result<()> {
+        let Some(from_version) = self.pending_migration(plugin_id) else {
+            return Ok(());
+        };
+
+        let speaks_migrate = self
+            .plugins
+            .get(plugin_id)
+            .is_some_and(|p| protocol_version(p) >= MIGRATE_PROTOCOL_VERSION);
+        if speaks_migrate {
+            let message = Message::new_migrate(uuid::Uuid::new_v4().to_string(), &from_version);
+            let reply = connection
+                .request(&message, MIGRATE_TIMEOUT)
+                .await
+                .context("Plugin didn't answer the migrate message")?;
+            if let MessagePayload::Lifecycle {
+                error: Some(error), ..
+            } = reply.payload
+            {
+                return Err(anyhow::anyhow!(
+                    "Migration from version {} failed: {}",
+                    from_version,
+                    error
+                ));
+            }
+            info!(
+                "Plugin {} migrated its data from version {}",
+                plugin_id, from_version
+            );
+        }
+
+        self.update_plugin_config("pending_migrations", plugin_id, serde_json::Value::Null)
+    }
+
+    /// Send lifecycle shutdown message to a plugin via Unix socket
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    async fn send_shutdown_message(&self, plugin_id: &str) -> Result<()> {
+        let process = self
+            .get_plugin_status(plugin_id)
+            .context("Plugin not found")?;
+
+        if !process.endpoint_ready() {
+            debug!(
+                "Plugin {} socket not found, skipping shutdown message",
+                plugin_id
+            );
+            return Ok(());
+        }
+
+        // Create shutdown message
+        let message = Message::new_lifecycle("shutdown", None);
+
+        self.plugin_connection(plugin_id)
+            .await?
+            .send(&message)
+            .await
+            .context("Failed to send shutdown message")?;
+
+        debug!("Sent shutdown message to plugin {}", plugin_id);
+        Ok(())
+    }
+
+    /// Get the persistent connection to a plugin, connecting if there is none yet
+    ///
+    /// All messages to a plugin share this connection, so plugins that serve one
+    /// connection at a time keep working.
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    pub async fn plugin_connection(&self, plugin_id: &str) -> Result<Arc<PluginConnection>> {
+        let process = self
+            .get_plugin_status(plugin_id)
+            .context("Plugin not found")?;
+
+        let mut connection = process.connection.lock().await;
+        if let Some(existing) = connection.as_ref().filter(|c| !c.is_closed()) {
+            return Ok(Arc::clone(existing));
+        }
+
+        let new_connection = match (&process.wasm, &process.transport) {
+            (Some(wasm), _) => PluginConnection::new(wasm.connect(self.plugin_logger())),
+            (None, Some(transport)) => PluginConnection::with_incoming(
+                transport
+                    .connect(process.secret.as_deref())
+                    .await
+                    .context("Failed to connect to plugin socket")?,
+                plugin_id,
+                self.incoming_sender.clone(),
+            ),
+            (None, None) => return Err(anyhow::anyhow!("Plugin {} is not loaded", plugin_id)),
+        };
+        let new_connection = Arc::new(new_connection);
+        *connection = Some(Arc::clone(&new_connection));
+        debug!("Opened persistent connection to plugin {}", plugin_id);
+        Ok(new_connection)
+    }
+
+    /// Replace the scheduled jobs of a plugin
+    ///
+    /// Nothing changes if any of the jobs is invalid.
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    /// * `jobs` - Jobs as registered by the plugin
+    pub fn register_jobs(&mut self, plugin_id: &str, jobs: &[ScheduledJob]) -> Result<()> {
+        if !self.plugins.contains_key(plugin_id) {
+            return Err(anyhow::anyhow!("Plugin {} not found", plugin_id));
+        }
+        let jobs = plugin_jobs::parse_jobs(jobs, chrono::Utc::now())?;
+        info!(
+            "Plugin {} registered {} scheduled jobs",
+            plugin_id,
+            jobs.len()
+        );
+        self.jobs.insert(plugin_id.to_string(), jobs);
+        Ok(())
+    }
+
+    /// Jobs a plugin registered
+    pub fn plugin_jobs(&self, plugin_id: &str) -> &[PluginJob] {
+        self.jobs
+            .get(plugin_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Jobs of enabled, reachable plugins that are due at `now`
+    ///
+    /// # Returns
+    /// Plugin IDs with the ID of their due job
+    pub fn take_due_jobs(&mut self, now: chrono::DateTime<chrono::Utc>) -> Vec<(String, String)> {
+        let mut due = Vec::new();
+        for (plugin_id, jobs) in self.jobs.iter_mut() {
+            let reachable = self
+                .plugins
+                .get(plugin_id)
+                .is_some_and(|p| p.enabled && p.endpoint_ready());
+            for job in jobs.iter_mut() {
+                // Jobs of unreachable plugins still move on, so they don't all fire at once later
+                if job.take_due(now) && reachable {
+                    due.push((plugin_id.clone(), job.id.clone()));
+                }
             }
         }
-        None
+        due
+    }
+
+    /// Receive the events plugins publish, as pushed to browsers
+    pub fn subscribe_events(&self) -> broadcast::Receiver<PublishedEvent> {
+        self.browser_events.subscribe()
+    }
+
+    /// Receive the notifications plugins send, as pushed to browsers
+    pub fn subscribe_notifications(&self) -> broadcast::Receiver<BrowserNotification> {
+        self.browser_notifications.subscribe()
+    }
+
+    /// Record a plugin's notification as a `notify` event and push it to browsers
+    pub async fn notify_browsers(&self, notification: BrowserNotification) {
+        if let Err(e) = notification.validate() {
+            warn!(
+                "Dropping notification from plugin {}: {}",
+                notification.plugin_id, e
+            );
+            return;
+        }
+
+        let log_level = match notification.level {
+            NotifyLevel::Info | NotifyLevel::Success => LogLevel::Info,
+            NotifyLevel::Warning => LogLevel::Warn,
+            NotifyLevel::Error => LogLevel::Error,
+        };
+        let details = serde_json::json!({
+            "level": notification.level,
+            "title": notification.title,
+            "body": notification.body,
+        });
+        self.notify_plugin_event(
+            &notification.plugin_id,
+            "notify",
+            log_level,
+            Some(&details.to_string()),
+        )
+        .await;
+
+        // No receivers just means no browser is connected
+        let _ = self.browser_notifications.send(notification);
+    }
+
+    /// Receive plugin log entries as they are written, for live tails
+    pub fn subscribe_logs(&self) -> broadcast::Receiver<LogEntry> {
+        self.plugin_logger.subscribe()
+    }
+
+    /// Connections of the running plugins subscribed to an event, except its publisher
+    async fn event_subscribers(
+        &self,
+        event: &PublishedEvent,
+    ) -> Vec<(String, Arc<PluginConnection>)> {
+        let mut subscribers = Vec::new();
+        for (plugin_id, process) in &self.plugins {
+            let subscribed = process
+                .metadata
+                .as_ref()
+                .is_some_and(|metadata| metadata.subscribes_to(&event.name));
+            if !subscribed || *plugin_id == event.plugin_id || !process.enabled {
+                continue;
+            }
+            if !process.endpoint_ready() {
+                continue;
+            }
+            match self.plugin_connection(plugin_id).await {
+                Ok(connection) => subscribers.push((plugin_id.clone(), connection)),
+                Err(e) => warn!(
+                    "Not forwarding event {} to plugin {}: {}",
+                    event.name, plugin_id, e
+                ),
+            }
+        }
+        subscribers
+    }
+
+    /// Get the connection used to forward HTTP requests to an enabled plugin
+    ///
+    /// Callers can release the supervisor lock before sending the request so
+    /// requests to plugins run concurrently.
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    pub async fn http_connection(&self, plugin_id: &str) -> Result<Arc<PluginConnection>> {
+        let process = self
+            .get_plugin_status(plugin_id)
+            .context("Plugin not found")?;
+
+        // Check if plugin is enabled and has a socket
+        if !process.enabled {
+            return Err(anyhow::anyhow!("Plugin {} is not enabled", plugin_id));
+        }
+
+        if !process.endpoint_ready() {
+            return Err(anyhow::anyhow!("Plugin {} socket not found", plugin_id));
+        }
+
+        self.plugin_connection(plugin_id).await
+    }
+
+    /// Connection, circuit breaker and request timeout of an enabled plugin, for
+    /// sending it HTTP requests after the supervisor lock is released
+    pub async fn http_target(&self, plugin_id: &str) -> Result<HttpTarget> {
+        let connection = self.http_connection(plugin_id).await?;
+        let breaker = self
+            .get_plugin_status(plugin_id)
+            .map(|process| Arc::clone(&process.breaker))
+            .context("Plugin not found")?;
+        Ok(HttpTarget {
+            plugin_id: plugin_id.to_string(),
+            connection,
+            breaker,
+            timeout: self.request_timeout(plugin_id),
+            db_pool: self.db_pool.clone(),
+        })
+    }
+
+    /// Forward an HTTP request to a plugin
+    ///
+    /// This method is used by the HTTP router to forward requests to plugins.
+    /// The plugin route path is resolved to the plugin ID, and the request
+    /// is forwarded via the plugin's Unix socket.
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier
+    /// * `request` - HTTP request to forward
+    ///
+    /// # Returns
+    /// The plugin's HTTP response
+    #[allow(dead_code)] // Used by integration tests; the router uses http_connection directly
+    pub async fn forward_http_request(
+        &self,
+        plugin_id: &str,
+        request: &HttpRequest,
+    ) -> Result<HttpMessageResponse> {
+        let target = self.http_target(plugin_id).await?;
+        let (mut response, body) = target.open(request).await?;
+        if let Some(body) = body {
+            response.set_body_bytes(&body.collect().await?);
+            response.stream = false;
+        }
+        Ok(response)
+    }
+
+    /// Get the plugin ID that owns a given route path
+    ///
+    /// Each plugin declares a route in its metadata (e.g., "/my-plugin").
+    /// This method looks up which plugin owns a given route path.
+    ///
+    /// # Arguments
+    /// * `route_path` - The route path (e.g., "/my-plugin")
+    ///
+    /// # Returns
+    /// The plugin ID that owns the route, if found
+    pub fn get_plugin_for_route(&self, route_path: &str) -> Option<String> {
+        self.route_owner(route_path, None)
+    }
+
+    /// Plugin serving a route, other than `except`
+    ///
+    /// Plugins held back by a route conflict never own their route.
+    fn route_owner(&self, route_path: &str, except: Option<&str>) -> Option<String> {
+        self.plugins
+            .iter()
+            .filter(|(plugin_id, _)| {
+                Some(plugin_id.as_str()) != except && !self.route_conflicts.contains_key(*plugin_id)
+            })
+            .find(|(_, process)| {
+                process
+                    .metadata
+                    .as_ref()
+                    .is_some_and(|metadata| metadata.route == route_path)
+            })
+            .map(|(plugin_id, _)| plugin_id.clone())
     }
 
     /// Restart a crashed plugin with exponential backoff
@@ -924,7 +2563,8 @@ impl PluginSupervisor {
     /// * `plugin_id` - Plugin identifier to restart
     /// * `binary_path` - Path to plugin binary
     /// * `metadata` - Plugin metadata
-    // TODO: Integrate in crash monitoring and auto-recovery system
+    // The health monitor calls prepare_restart/restart_plugin directly so it doesn't
+    // hold the supervisor lock during the backoff delay
     #[allow(dead_code)]
     pub async fn restart_plugin_with_backoff(
         &mut self,
@@ -932,88 +2572,1427 @@ impl PluginSupervisor {
         binary_path: &Path,
         metadata: PluginMetadata,
     ) -> Result<()> {
-        let restart_count = self.increment_restart_count(plugin_id);
+        let delay = self.prepare_restart(plugin_id).await?;
+
+        // Wait with exponential backoff
+        tokio::time::sleep(delay).await;
+
+        self.restart_plugin(plugin_id, binary_path, metadata).await
+    }
+
+    /// Count a restart attempt and compute its backoff delay
+    ///
+    /// Disables the plugin and returns an error once `max_restarts` is reached.
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier to restart
+    ///
+    /// # Returns
+    /// How long to wait before calling `restart_plugin`
+    pub async fn prepare_restart(&mut self, plugin_id: &str) -> Result<Duration> {
+        let restart_count = self.increment_restart_count(plugin_id);
+
+        // Check if we've reached max restarts
+        if self.should_disable_plugin(plugin_id) {
+            error!(
+                "Plugin {} has reached max restarts ({}), disabling",
+                plugin_id, self.max_restarts
+            );
+
+            // Notify plugin event via notification hooks
+            self.notify_plugin_event(
+                plugin_id,
+                "disabled_after_max_restarts",
+                LogLevel::Error,
+                Some(
+                    &serde_json::json!({
+                        "reason": "max_restarts_exceeded",
+                        "restart_count": restart_count,
+                    })
+                    .to_string(),
+                ),
+            )
+            .await;
+
+            warn!(
+                "Plugin {} disabled after {} crashes",
+                plugin_id, restart_count
+            );
+
+            self.disable_plugin(plugin_id).await?;
+
+            return Err(anyhow::anyhow!(
+                "Plugin disabled after {} consecutive failures",
+                restart_count
+            ));
+        }
+
+        // Calculate exponential backoff delay (1s, 2s, 4s, 8s, 16s)
+        let backoff_exponent = restart_count.min(4);
+        let delay_ms = 2u64.pow(backoff_exponent) * 1000; // 1000ms, 2000ms, 4000ms, 8000ms, 16000ms
+
+        info!(
+            "Restarting plugin {} (attempt #{}, waiting {}ms)",
+            plugin_id, restart_count, delay_ms
+        );
+
+        // Notify plugin event via notification hooks
+        self.notify_plugin_event(
+            plugin_id,
+            "restarting_with_backoff",
+            LogLevel::Warn,
+            Some(
+                &serde_json::json!({
+                    "reason": "plugin_crashed",
+                    "restart_attempt": restart_count,
+                    "backoff_delay_ms": delay_ms,
+                })
+                .to_string(),
+            ),
+        )
+        .await;
+
+        warn!(
+            "Plugin {} crashed, restarting in {}ms (attempt #{})",
+            plugin_id, delay_ms, restart_count
+        );
+
+        Ok(Duration::from_millis(delay_ms))
+    }
+
+    /// Respawn a plugin and send it the init message
+    ///
+    /// Any process still registered for the plugin is killed first.
+    ///
+    /// # Arguments
+    /// * `plugin_id` - Plugin identifier to restart
+    /// * `binary_path` - Path to plugin binary
+    /// * `metadata` - Plugin metadata
+    pub async fn restart_plugin(
+        &mut self,
+        plugin_id: &str,
+        binary_path: &Path,
+        metadata: PluginMetadata,
+    ) -> Result<()> {
+        if let Some(mut child) = self
+            .plugins
+            .get_mut(plugin_id)
+            .and_then(|p| p.process.take())
+        {
+            let _ = child.start_kill();
+        }
+
+        // Spawn plugin
+        self.spawn_plugin(plugin_id, binary_path, metadata).await?;
+
+        // Send init message
+        if let Err(e) = self.send_init_message(plugin_id).await {
+            error!("Failed to send init message after restart: {}", e);
+        }
+
+        info!("Plugin {} restarted successfully", plugin_id);
+
+        Ok(())
+    }
+
+    /// Attach a plugin a developer runs by hand (e.g. with `cargo run`)
+    ///
+    /// The supervisor connects to `address` and sends the init message but never
+    /// spawns, restarts or kills the process; if the plugin goes away it is just
+    /// reported unhealthy until it is registered again.
+    ///
+    /// # Arguments
+    /// * `address` - Socket path or `tcp://127.0.0.1:PORT` the plugin listens on
+    /// * `secret` - Handshake secret for TCP plugins
+    /// * `metadata` - Plugin metadata, as the plugin would print with `--metadata`
+    pub async fn register_dev_plugin(
+        &mut self,
+        address: &str,
+        secret: Option<String>,
+        metadata: PluginMetadata,
+    ) -> Result<()> {
+        validate_metadata(&metadata)?;
+        let plugin_id = metadata.id.clone();
+        if self.plugins.get(&plugin_id).is_some_and(|p| !p.dev) {
+            return Err(anyhow::anyhow!(
+                "Plugin {} is already installed; disable or remove it first",
+                plugin_id
+            ));
+        }
+        if let Some(owner) = self
+            .get_plugin_for_route(&metadata.route)
+            .filter(|owner| *owner != plugin_id)
+        {
+            return Err(anyhow::anyhow!(
+                "Route {} is already used by plugin {}",
+                metadata.route,
+                owner
+            ));
+        }
+
+        let transport = PluginTransport::parse(address)?;
+        let process = PluginProcess {
+            id: plugin_id.clone(),
+            process: None,
+            socket_path: transport.to_string(),
+            transport: Some(transport),
+            secret,
+            enabled: true,
+            metadata: Some(metadata),
+            pid: None,
+            started_at: Instant::now(),
+            connection: tokio::sync::Mutex::new(None),
+            limits: PluginLimits::default(),
+            enforcement: Enforcement::None,
+            usage: None,
+            limit_exceeded: None,
+            last_crash_at: None,
+            last_crash: None,
+            stderr_tail: Arc::default(),
+            dev: true,
+            wasm: None,
+            breaker: Arc::default(),
+        };
+        self.plugins.insert(plugin_id.clone(), process);
+
+        if let Err(e) = self.send_init_message(&plugin_id).await {
+            self.plugins.remove(&plugin_id);
+            return Err(e.context(format!("Cannot reach dev plugin at {}", address)));
+        }
+
+        info!("Registered dev plugin {} at {}", plugin_id, address);
+        self.notify_plugin_event(
+            &plugin_id,
+            "dev_registered",
+            LogLevel::Info,
+            Some(&serde_json::json!({ "address": address }).to_string()),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Detach a dev plugin; its process keeps running
+    ///
+    /// # Returns
+    /// false if there is no dev plugin with this ID
+    pub async fn unregister_dev_plugin(&mut self, plugin_id: &str) -> bool {
+        if !self.plugins.get(plugin_id).is_some_and(|p| p.dev) {
+            return false;
+        }
+        self.plugins.remove(plugin_id);
+        self.jobs.remove(plugin_id);
+        info!("Unregistered dev plugin {}", plugin_id);
+        self.notify_plugin_event(plugin_id, "dev_unregistered", LogLevel::Info, None)
+            .await;
+        true
+    }
+
+    /// Find running plugins whose binary was replaced since they were spawned
+    ///
+    /// A changed binary is only reported once it looks the same on two consecutive
+    /// calls, so a file that is still being written isn't picked up.
+    ///
+    /// # Returns
+    /// Plugin IDs with the path of their new binary
+    pub fn changed_binaries(&mut self) -> Vec<(String, PathBuf)> {
+        let mut ready = Vec::new();
+
+        for (plugin_id, (binary_path, stamp)) in &self.binary_stamps {
+            let running = self
+                .plugins
+                .get(plugin_id)
+                .is_some_and(|p| p.enabled && p.is_running());
+            if !running || self.restarting.contains(plugin_id) {
+                continue;
+            }
+
+            // A removed binary is left alone; the running process keeps working
+            match binary_stamp(binary_path) {
+                Some(current) if current != *stamp => {
+                    if self.pending_reloads.get(plugin_id) == Some(&current) {
+                        ready.push((plugin_id.clone(), binary_path.clone()));
+                    } else {
+                        self.pending_reloads.insert(plugin_id.clone(), current);
+                    }
+                }
+                _ => {
+                    self.pending_reloads.remove(plugin_id);
+                }
+            }
+        }
+
+        for (plugin_id, _) in &ready {
+            self.pending_reloads.remove(plugin_id);
+        }
+        ready
+    }
+
+    /// Replace a running plugin with the new binary at `binary_path`
+    ///
+    /// The new binary's metadata is read first, so a broken binary leaves the old
+    /// process running. The old process is then stopped gracefully, the new one
+    /// spawned and an `upgraded` event recorded.
+    pub async fn reload_plugin(&mut self, plugin_id: &str, binary_path: &Path) -> Result<()> {
+        // Don't retry the same file on every check if it turns out to be broken
+        if let Some(stamp) = binary_stamp(binary_path) {
+            self.binary_stamps
+                .insert(plugin_id.to_string(), (binary_path.to_path_buf(), stamp));
+        }
+
+        let metadata = self
+            .read_plugin_metadata(binary_path)
+            .await
+            .context("Failed to read metadata of the new binary")?;
+        let instance = self.plugin_instance(plugin_id);
+        let expected_id = instance
+            .as_ref()
+            .map_or(plugin_id, |instance| instance.plugin.as_str());
+        if metadata.id != expected_id {
+            return Err(anyhow::anyhow!(
+                "New binary reports plugin ID {} instead of {}",
+                metadata.id,
+                expected_id
+            ));
+        }
+        let metadata = match &instance {
+            Some(instance) => instance.metadata(metadata),
+            None => metadata,
+        };
+
+        let previous_version = self
+            .plugins
+            .get(plugin_id)
+            .and_then(|p| p.metadata.as_ref())
+            .map(|m| m.version.clone());
+        let version = metadata.version.clone();
+
+        self.kill_plugin(plugin_id).await?;
+        self.spawn_plugin(plugin_id, binary_path, metadata).await?;
+        if let Err(e) = self.send_init_message(plugin_id).await {
+            error!("Failed to send init message after reload: {}", e);
+        }
+
+        info!(
+            "Plugin {} reloaded ({:?} -> {})",
+            plugin_id, previous_version, version
+        );
+        self.notify_plugin_event(
+            plugin_id,
+            "upgraded",
+            LogLevel::Info,
+            Some(
+                &serde_json::json!({
+                    "from_version": previous_version,
+                    "to_version": version,
+                })
+                .to_string(),
+            ),
+        )
+        .await;
+
+        Ok(())
+    }
+
+    /// Reap a plugin's process if it has exited
+    ///
+    /// # Returns
+    /// `Some(exit_status)` if the plugin has no running process, `None` if it is still running
+    fn reap_plugin_process(&mut self, plugin_id: &str) -> Option<Option<std::process::ExitStatus>> {
+        let process = self.plugins.get_mut(plugin_id)?;
+        let child = match process.process.as_mut() {
+            Some(child) => child,
+            None if process.wasm.is_some() => return None,
+            None => return Some(None),
+        };
+
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                process.process = None;
+                Some(Some(status))
+            }
+            _ => None,
+        }
+    }
+
+    /// Find enabled plugins that have crashed
+    ///
+    /// A plugin is considered crashed if its process exited, or if it has been running
+    /// longer than the startup grace period and fails `check_plugin_health`.
+    /// Plugins already being restarted are skipped. Each crash is recorded as the
+    /// plugin's `last_crash`.
+    ///
+    /// # Returns
+    /// Plugin IDs with their exit code, if known
+    pub fn detect_crashed_plugins(&mut self) -> Vec<(String, Option<i32>)> {
+        let candidates: Vec<String> = self
+            .plugins
+            .iter()
+            .filter(|(id, p)| p.enabled && !p.dev && !self.restarting.contains(*id))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        let mut crashed = Vec::new();
+        for plugin_id in candidates {
+            if let Some(status) = self.reap_plugin_process(&plugin_id) {
+                self.flag_oom_kill(&plugin_id);
+                self.record_crash(&plugin_id, "exited", status);
+                crashed.push((plugin_id, status.and_then(|status| status.code())));
+                continue;
+            }
+
+            let past_grace = self
+                .plugins
+                .get(&plugin_id)
+                .is_some_and(|p| p.started_at.elapsed() >= STARTUP_GRACE_PERIOD);
+            if past_grace && !self.check_plugin_health(&plugin_id) {
+                self.record_crash(&plugin_id, "unhealthy", None);
+                crashed.push((plugin_id, None));
+            }
+        }
+        crashed
+    }
+
+    /// Save a crash report with the plugin's exit status and last stderr lines
+    fn record_crash(
+        &mut self,
+        plugin_id: &str,
+        reason: &str,
+        status: Option<std::process::ExitStatus>,
+    ) {
+        if let Some(process) = self.plugins.get_mut(plugin_id) {
+            let report = CrashReport::new(reason, status, &process.stderr_tail);
+            process.last_crash_at = Some(report.at);
+            process.last_crash = Some(report);
+        }
+    }
+
+    /// The report of a plugin's last crash, if it crashed since the supervisor started
+    pub fn last_crash(&self, plugin_id: &str) -> Option<&CrashReport> {
+        self.plugins.get(plugin_id)?.last_crash.as_ref()
+    }
+
+    /// Connections to the plugins the health monitor pings
+    ///
+    /// Only child processes the supervisor runs are pinged; WASM plugins can't
+    /// hang on their own and dev plugins aren't restarted.
+    async fn heartbeat_targets(&self) -> Vec<(String, Arc<PluginConnection>)> {
+        let mut targets = Vec::new();
+        for (plugin_id, process) in &self.plugins {
+            if !process.enabled
+                || process.process.is_none()
+                || protocol_version(process) < PING_PROTOCOL_VERSION
+                || !process.endpoint_ready()
+                || self.restarting.contains(plugin_id)
+            {
+                continue;
+            }
+            match self.plugin_connection(plugin_id).await {
+                Ok(connection) => targets.push((plugin_id.clone(), connection)),
+                Err(e) => debug!("Not pinging plugin {}: {}", plugin_id, e),
+            }
+        }
+        targets
+    }
+
+    /// Record whether a plugin answered a ping
+    ///
+    /// Misses only count once the plugin has answered a ping: plugins built
+    /// before pings existed never answer and are left to the PID and socket checks.
+    ///
+    /// # Returns
+    /// true if the plugin has now missed `MAX_MISSED_PINGS` pings in a row
+    pub fn record_ping(&mut self, plugin_id: &str, answered: bool) -> bool {
+        let heartbeat = self.heartbeats.entry(plugin_id.to_string()).or_default();
+        if answered {
+            *heartbeat = Heartbeat {
+                answered: true,
+                missed: 0,
+            };
+            return false;
+        }
+        if !heartbeat.answered {
+            return false;
+        }
+        heartbeat.missed += 1;
+        debug!(
+            "Plugin {} missed {} pings in a row",
+            plugin_id, heartbeat.missed
+        );
+        heartbeat.missed >= MAX_MISSED_PINGS
+    }
+
+    /// Whether a plugin has answered a ping since it started
+    pub fn answers_pings(&self, plugin_id: &str) -> bool {
+        self.heartbeats.get(plugin_id).is_some_and(|h| h.answered)
+    }
+
+    /// Flag a plugin whose cgroup saw the kernel OOM-kill it
+    fn flag_oom_kill(&mut self, plugin_id: &str) {
+        let Some(process) = self.plugins.get_mut(plugin_id) else {
+            return;
+        };
+        if let Enforcement::Cgroup(cgroup) = &process.enforcement {
+            if plugin_limits::oom_killed(cgroup) {
+                warn!(
+                    "Plugin {} was killed for exceeding its memory limit",
+                    plugin_id
+                );
+                process.limit_exceeded = Some(LimitExceeded::Memory);
+            }
+        }
+    }
+
+    /// Refresh the CPU and memory usage of every running plugin from its PID
+    ///
+    /// WASM plugins report the size of their linear memory and no CPU usage.
+    pub fn sample_resource_usage(&mut self) {
+        for process in self.plugins.values_mut() {
+            process.usage = match (process.pid, &process.process, &process.wasm) {
+                (Some(pid), Some(_), _) => self.usage_sampler.sample(pid),
+                (_, _, Some(wasm)) => Some(ResourceUsage {
+                    memory_bytes: wasm.memory_bytes(),
+                    cpu_percent: 0.0,
+                }),
+                _ => None,
+            };
+        }
+    }
+
+    /// Sample the resource usage of running plugins and enforce their limits
+    ///
+    /// A plugin over its memory limit is killed (the health monitor then restarts it
+    /// like any crashed plugin); one over its CPU limit is only flagged, since cgroups
+    /// already throttle it where available.
+    pub async fn enforce_resource_limits(&mut self) {
+        self.sample_resource_usage();
+        let mut violations = Vec::new();
+
+        for (plugin_id, process) in self.plugins.iter_mut() {
+            let Some(usage) = process.usage else {
+                continue;
+            };
+
+            let Some(exceeded) = plugin_limits::exceeded(&process.limits, &usage) else {
+                continue;
+            };
+            // CPU overuse is reported once, memory overuse every time it is enforced
+            if exceeded == LimitExceeded::Cpu && process.limit_exceeded.is_some() {
+                continue;
+            }
+            process.limit_exceeded = Some(exceeded);
+
+            if exceeded == LimitExceeded::Memory {
+                warn!(
+                    "Plugin {} uses {} bytes, over its {:?} MB memory limit; killing it",
+                    plugin_id, usage.memory_bytes, process.limits.memory_mb
+                );
+                if let Some(child) = process.process.as_mut() {
+                    child.start_kill().ok();
+                }
+            } else {
+                warn!(
+                    "Plugin {} uses {:.0}% CPU, over its {:?}% limit",
+                    plugin_id, usage.cpu_percent, process.limits.cpu_percent
+                );
+            }
+            violations.push((plugin_id.clone(), exceeded, usage, process.limits));
+        }
 
-        // Check if we've reached max restarts
-        if self.should_disable_plugin(plugin_id) {
-            error!(
-                "Plugin {} has reached max restarts ({}), disabling",
-                plugin_id, self.max_restarts
+        for (plugin_id, exceeded, usage, limits) in violations {
+            self.notify_plugin_event(
+                &plugin_id,
+                "limit_exceeded",
+                LogLevel::Warn,
+                Some(
+                    &serde_json::json!({
+                        "resource": exceeded,
+                        "usage": usage,
+                        "limits": limits,
+                    })
+                    .to_string(),
+                ),
+            )
+            .await;
+        }
+    }
+
+    /// Reset the restart count of plugins that have been up for the stable period
+    pub async fn reset_stable_restart_counts(&mut self) {
+        let stable: Vec<(String, u32)> = self
+            .restart_counts
+            .iter()
+            .filter(|(id, _)| !self.restarting.contains(*id))
+            .filter(|(id, _)| {
+                self.plugins.get(*id).is_some_and(|p| {
+                    p.enabled && p.is_running() && p.started_at.elapsed() >= STABLE_PERIOD
+                })
+            })
+            .map(|(id, count)| (id.clone(), *count))
+            .collect();
+
+        for (plugin_id, count) in stable {
+            self.reset_restart_count(&plugin_id);
+            info!(
+                "Plugin {} stable for {:?}, restart count reset",
+                plugin_id, STABLE_PERIOD
             );
 
-            // Notify plugin event via notification hooks
             self.notify_plugin_event(
-                plugin_id,
-                "disabled_after_max_restarts",
-                LogLevel::Error,
+                &plugin_id,
+                "stable",
+                LogLevel::Info,
                 Some(
                     &serde_json::json!({
-                        "reason": "max_restarts_exceeded",
-                        "restart_count": restart_count,
+                        "previous_restart_count": count,
                     })
                     .to_string(),
                 ),
             )
             .await;
+        }
+    }
+}
 
-            warn!(
-                "Plugin {} disabled after {} crashes",
-                plugin_id, restart_count
-            );
+/// Wait for a plugin process to exit, escalating from waiting to SIGTERM to SIGKILL
+async fn stop_child(plugin_id: &str, child: &mut Child, shutdown_timeout: Duration) {
+    if tokio::time::timeout(shutdown_timeout, child.wait())
+        .await
+        .is_ok()
+    {
+        debug!("Plugin {} exited gracefully", plugin_id);
+        return;
+    }
 
-            self.disable_plugin(plugin_id).await?;
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        warn!(
+            "Plugin {} did not exit within {:?}, sending SIGTERM",
+            plugin_id, shutdown_timeout
+        );
+        unsafe {
+            libc::kill(pid as i32, libc::SIGTERM);
+        }
+        if tokio::time::timeout(TERMINATE_TIMEOUT, child.wait())
+            .await
+            .is_ok()
+        {
+            debug!("Plugin {} exited after SIGTERM", plugin_id);
+            return;
+        }
+    }
+
+    warn!("Plugin {} still running, killing it", plugin_id);
+    if let Err(e) = child.kill().await {
+        warn!("Failed to kill plugin {}: {}", plugin_id, e);
+    }
+}
+
+/// Where the router sends a plugin's HTTP requests
+pub struct HttpTarget {
+    pub plugin_id: String,
+    pub connection: Arc<PluginConnection>,
+    pub breaker: Arc<CircuitBreaker>,
+    pub timeout: Duration,
+    db_pool: DbPool,
+}
+
+impl HttpTarget {
+    /// Send an HTTP request through the plugin's circuit breaker
+    ///
+    /// Fails with `CircuitOpen` right away while the breaker is open. Timeouts are
+    /// counted; the one that opens the breaker is recorded as a `circuit_open`
+    /// plugin event.
+    pub async fn open(
+        &self,
+        request: &HttpRequest,
+    ) -> Result<(HttpMessageResponse, Option<BodyStream>)> {
+        if self.breaker.is_open() {
+            return Err(CircuitOpen(self.plugin_id.clone()).into());
+        }
+
+        match open_http_request(&self.connection, request, self.timeout).await {
+            Ok(response) => {
+                self.breaker.record_success();
+                Ok(response)
+            }
+            Err(e) => {
+                if e.downcast_ref::<RequestTimeout>().is_some() && self.breaker.record_timeout() {
+                    warn!(
+                        "Plugin {} keeps timing out; pausing its requests",
+                        self.plugin_id
+                    );
+                    let details = serde_json::json!({ "timeout_secs": self.timeout.as_secs() });
+                    let _ = crate::db::plugin_event_log(
+                        &self.db_pool,
+                        &self.plugin_id,
+                        "circuit_open",
+                        Some(&details.to_string()),
+                    )
+                    .await;
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Send an HTTP request over a plugin connection and decode the response
+///
+/// A streamed body is read to the end and returned in `body`.
+pub async fn send_http_request(
+    connection: &PluginConnection,
+    request: &HttpRequest,
+    timeout: Duration,
+) -> Result<HttpMessageResponse> {
+    let (mut response, body) = open_http_request(connection, request, timeout).await?;
+    if let Some(body) = body {
+        response.set_body_bytes(&body.collect().await?);
+        response.stream = false;
+    }
+    Ok(response)
+}
+
+/// Send an HTTP request and decode the response as soon as it arrives
+///
+/// # Returns
+/// The response and, when the plugin streams the body, the stream of its chunks
+pub async fn open_http_request(
+    connection: &PluginConnection,
+    request: &HttpRequest,
+    timeout: Duration,
+) -> Result<(HttpMessageResponse, Option<BodyStream>)> {
+    // Generate a unique request ID
+    let request_id = uuid::Uuid::new_v4().to_string();
+
+    // Create HTTP request message
+    let message = Message::new_http(request_id, request.clone());
+
+    // Timeout prevents hanging on unresponsive plugins (it covers the response,
+    // not the streamed body)
+    let (response_msg, body) = connection
+        .request_stream(&message, timeout)
+        .await
+        .context("Failed to get HTTP response from plugin")?;
 
+    let http_response = match response_msg.payload {
+        MessagePayload::HttpResponse { payload, .. } => payload,
+        // Plugins older than protocol version 3 wrap the response in an `http` message
+        MessagePayload::Http { payload, .. } => legacy_http_response(payload.body.as_deref()),
+        _ => {
             return Err(anyhow::anyhow!(
-                "Plugin disabled after {} consecutive failures",
-                restart_count
-            ));
+                "Plugin answered an HTTP request with a {} message",
+                response_msg.message_type
+            ))
         }
+    };
 
-        // Calculate exponential backoff delay (1s, 2s, 4s, 8s, 16s)
-        let backoff_exponent = restart_count.min(4);
-        let delay_ms = 2u64.pow(backoff_exponent) * 1000; // 1000ms, 2000ms, 4000ms, 8000ms, 16000ms
+    let body = http_response.stream.then_some(body);
+    Ok((http_response, body))
+}
 
-        info!(
-            "Restarting plugin {} (attempt #{}, waiting {}ms)",
-            plugin_id, restart_count, delay_ms
-        );
+/// Decode a response a pre-version-3 plugin sent as the JSON body of an `http`
+/// message
+///
+/// Lenient like the plugins sending it: missing fields get defaults and a
+/// non-string body is serialized back to JSON.
+fn legacy_http_response(body: Option<&str>) -> HttpMessageResponse {
+    let parsed_response: serde_json::Value = body
+        .and_then(|body| serde_json::from_str(body).ok())
+        .unwrap_or_else(|| serde_json::json!({}));
+
+    HttpMessageResponse {
+        status: parsed_response
+            .get("status")
+            .and_then(|s| s.as_u64())
+            .unwrap_or(500) as u16,
+        headers: parsed_response
+            .get("headers")
+            .and_then(|h| serde_json::from_value(h.clone()).ok())
+            .unwrap_or_default(),
+        body: parsed_response.get("body").and_then(|b| {
+            // body can be either a string or null
+            if b.is_string() {
+                Some(b.as_str().unwrap().to_string())
+            } else if b.is_null() {
+                None
+            } else {
+                // If body is an object/array, serialize it
+                Some(serde_json::to_string(b).unwrap_or_default())
+            }
+        }),
+        body_encoding: parsed_response
+            .get("body_encoding")
+            .and_then(|e| serde_json::from_value(e.clone()).ok())
+            .unwrap_or_default(),
+        stream: parsed_response
+            .get("stream")
+            .and_then(|s| s.as_bool())
+            .unwrap_or(false),
+    }
+}
 
-        // Notify plugin event via notification hooks
-        self.notify_plugin_event(
-            plugin_id,
-            "restarting_with_backoff",
-            LogLevel::Warn,
-            Some(
-                &serde_json::json!({
-                    "reason": "plugin_crashed",
-                    "restart_attempt": restart_count,
-                    "backoff_delay_ms": delay_ms,
+/// Security: Validate metadata fields to prevent injection attacks
+pub fn validate_metadata(metadata: &PluginMetadata) -> Result<()> {
+    if metadata.id.is_empty()
+        || !metadata
+            .id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(anyhow::anyhow!("Invalid plugin ID format"));
+    }
+    validate_route(&metadata.route)?;
+    toru_plugin_api::check_protocol_version(metadata.protocol_version).map_err(|_| {
+        anyhow::anyhow!(
+            "Plugin speaks protocol version {}, this host supports {} to {}",
+            metadata.protocol_version,
+            MIN_PROTOCOL_VERSION,
+            PROTOCOL_VERSION
+        )
+    })?;
+    if metadata.name.len() > 100 || metadata.author.as_ref().is_some_and(|a| a.len() > 100) {
+        return Err(anyhow::anyhow!("Metadata field too long"));
+    }
+    Ok(())
+}
+
+/// Check an environment variable an admin defines for a plugin: a portable
+/// name that doesn't start with `TORU_`, and a value without NUL bytes
+pub fn validate_env_var(name: &str, value: &str) -> Result<()> {
+    let portable = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !portable {
+        return Err(anyhow::anyhow!(
+            "Invalid environment variable name {:?}",
+            name
+        ));
+    }
+    if name.to_ascii_uppercase().starts_with(RESERVED_PREFIX) {
+        return Err(anyhow::anyhow!(
+            "Environment variable {} is reserved: {}* variables are set by the host",
+            name,
+            RESERVED_PREFIX
+        ));
+    }
+    if value.contains('\0') {
+        return Err(anyhow::anyhow!(
+            "Environment variable {} contains a NUL byte",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Check a plugin instance name: up to `MAX_INSTANCE_NAME_LEN` lowercase letters,
+/// digits and `-`
+pub fn validate_instance_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.len() > MAX_INSTANCE_NAME_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return Err(anyhow::anyhow!(
+            "Invalid instance name {:?}: expected up to {} lowercase letters, digits or -",
+            name,
+            MAX_INSTANCE_NAME_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Protocol version a plugin declared (1 if its metadata is unknown)
+fn protocol_version(process: &PluginProcess) -> u32 {
+    process
+        .metadata
+        .as_ref()
+        .map_or(MIN_PROTOCOL_VERSION, |metadata| metadata.protocol_version)
+}
+
+/// Check a plugin route: a single path segment of letters, digits, `-` and `_`
+/// that isn't one of the built-in paths
+pub fn validate_route(route: &str) -> Result<()> {
+    let Some(name) = route.strip_prefix('/') else {
+        return Err(anyhow::anyhow!(
+            "Plugin route {:?} must start with /",
+            route
+        ));
+    };
+    if name.is_empty()
+        || route.len() > MAX_ROUTE_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow::anyhow!(
+            "Invalid plugin route {:?}: expected / and up to {} letters, digits, - or _",
+            route,
+            MAX_ROUTE_LEN - 1
+        ));
+    }
+    if RESERVED_ROUTES.contains(&name.to_ascii_lowercase().as_str()) {
+        return Err(anyhow::anyhow!(
+            "Plugin route {} is reserved for the built-in API",
+            route
+        ));
+    }
+    Ok(())
+}
+
+/// Modification time and size of a file, if it exists
+fn binary_stamp(path: &Path) -> Option<BinaryStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Write a file via a temporary sibling and rename it into place
+fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Where the binary replaced by an upgrade is kept until the upgrade succeeds
+fn previous_binary_path(binary_path: &Path) -> PathBuf {
+    let mut path = binary_path.as_os_str().to_owned();
+    path.push(".prev");
+    PathBuf::from(path)
+}
+
+/// Move a plugin binary and its signature, if it has one
+fn move_binary(from: &Path, to: &Path) -> std::io::Result<()> {
+    let from_signature = plugin_signing::signature_path(from);
+    let to_signature = plugin_signing::signature_path(to);
+    if from_signature.exists() {
+        fs::rename(&from_signature, &to_signature)?;
+    } else {
+        fs::remove_file(&to_signature).ok();
+    }
+    fs::rename(from, to)
+}
+
+/// Write a file and mark it executable by the owner
+fn write_executable(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    fs::write(path, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// Spawn the background loop that detects crashed plugins and restarts them
+///
+/// Every `HEALTH_CHECK_INTERVAL` the monitor resets restart counts of stable plugins,
+/// samples resource usage and enforces limits, records a `crashed` event for each
+/// crashed plugin and restarts it with backoff.
+pub fn spawn_health_monitor(supervisor: Arc<tokio::sync::Mutex<PluginSupervisor>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let crashed = {
+                let mut sup = supervisor.lock().await;
+                sup.reset_stable_restart_counts().await;
+                sup.enforce_resource_limits().await;
+
+                let crashed = sup.detect_crashed_plugins();
+                for (plugin_id, exit_code) in &crashed {
+                    warn!("Plugin {} crashed (exit code: {:?})", plugin_id, exit_code);
+                    sup.restarting.insert(plugin_id.clone());
+                    let details = match sup.last_crash(plugin_id) {
+                        Some(report) => report.event_details(),
+                        None => serde_json::json!({ "exit_code": exit_code }),
+                    };
+                    sup.notify_plugin_event(
+                        plugin_id,
+                        "crashed",
+                        LogLevel::Error,
+                        Some(&details.to_string()),
+                    )
+                    .await;
+                }
+                crashed
+            };
+
+            for (plugin_id, _) in crashed {
+                tokio::spawn(recover_plugin(Arc::clone(&supervisor), plugin_id));
+            }
+
+            // A hung plugin still has its PID and socket; only a ping tells
+            for plugin_id in check_heartbeats(&supervisor).await {
+                {
+                    let mut sup = supervisor.lock().await;
+                    warn!(
+                        "Plugin {} missed {} pings, restarting it",
+                        plugin_id, MAX_MISSED_PINGS
+                    );
+                    sup.restarting.insert(plugin_id.clone());
+                    sup.record_crash(&plugin_id, "unresponsive", None);
+                    sup.notify_plugin_event(
+                        &plugin_id,
+                        "unresponsive",
+                        LogLevel::Error,
+                        Some(&serde_json::json!({ "missed_pings": MAX_MISSED_PINGS }).to_string()),
+                    )
+                    .await;
+                }
+                tokio::spawn(recover_plugin(Arc::clone(&supervisor), plugin_id));
+            }
+        }
+    });
+}
+
+/// Ping every running plugin and record which ones answered
+///
+/// The pings go out concurrently without holding the supervisor lock.
+///
+/// # Returns
+/// Plugins that missed `MAX_MISSED_PINGS` pings in a row
+pub async fn check_heartbeats(supervisor: &tokio::sync::Mutex<PluginSupervisor>) -> Vec<String> {
+    let targets = supervisor.lock().await.heartbeat_targets().await;
+    let results = futures::future::join_all(targets.into_iter().map(
+        |(plugin_id, connection)| async move {
+            let ping = Message::new_ping(uuid::Uuid::new_v4().to_string());
+            let answered = matches!(
+                connection.request(&ping, PING_TIMEOUT).await,
+                Ok(Message {
+                    payload: MessagePayload::Pong { .. },
+                    ..
                 })
-                .to_string(),
-            ),
+            );
+            (plugin_id, answered)
+        },
+    ))
+    .await;
+
+    let mut sup = supervisor.lock().await;
+    results
+        .into_iter()
+        .filter(|(plugin_id, answered)| sup.record_ping(plugin_id, *answered))
+        .map(|(plugin_id, _)| plugin_id)
+        .collect()
+}
+
+/// Spawn the task that handles the messages plugins send on their own
+///
+/// Published events are fanned out in order (see `publish_event`); calls to other
+/// plugins, `exec` and `system_info` requests run concurrently so a slow one doesn't hold up the rest.
+pub fn spawn_plugin_message_handler(supervisor: Arc<tokio::sync::Mutex<PluginSupervisor>>) {
+    tokio::spawn(async move {
+        let Some(mut incoming) = supervisor.lock().await.incoming_receiver.take() else {
+            warn!("Plugin message handler is already running");
+            return;
+        };
+        while let Some((plugin_id, message)) = incoming.recv().await {
+            match message.payload {
+                MessagePayload::Lifecycle { action, jobs, .. } if action == "register_jobs" => {
+                    let mut sup = supervisor.lock().await;
+                    if let Err(e) = sup.register_jobs(&plugin_id, &jobs) {
+                        warn!("Rejected jobs of plugin {}: {:#}", plugin_id, e);
+                        sup.notify_plugin_event(
+                            &plugin_id,
+                            "jobs_rejected",
+                            LogLevel::Warn,
+                            Some(&serde_json::json!({ "error": format!("{:#}", e) }).to_string()),
+                        )
+                        .await;
+                    }
+                }
+                MessagePayload::Event { payload } => {
+                    let event = PublishedEvent::new(&plugin_id, &payload);
+                    publish_event(&supervisor, event).await;
+                }
+                MessagePayload::CallPlugin {
+                    request_id,
+                    payload,
+                } => {
+                    tokio::spawn(answer_plugin_call(
+                        Arc::clone(&supervisor),
+                        plugin_id,
+                        request_id,
+                        payload,
+                    ));
+                }
+                MessagePayload::Exec {
+                    request_id,
+                    payload,
+                } => {
+                    tokio::spawn(answer_plugin_exec(
+                        Arc::clone(&supervisor),
+                        plugin_id,
+                        request_id,
+                        payload,
+                    ));
+                }
+                MessagePayload::Notify { payload } => {
+                    let notification = BrowserNotification::new(&plugin_id, payload);
+                    supervisor.lock().await.notify_browsers(notification).await;
+                }
+                MessagePayload::SystemInfo { request_id } => {
+                    tokio::spawn(answer_system_info(
+                        Arc::clone(&supervisor),
+                        plugin_id,
+                        request_id,
+                    ));
+                }
+                _ => debug!(
+                    "Ignoring {} message from plugin {}",
+                    message.message_type, plugin_id
+                ),
+            }
+        }
+    });
+}
+
+/// Record an event in `plugin_events` as `event:<name>`, forward it to the
+/// plugins subscribed to it and push it to browsers on the main WebSocket
+async fn publish_event(supervisor: &tokio::sync::Mutex<PluginSupervisor>, event: PublishedEvent) {
+    if let Err(e) = event.validate() {
+        warn!("Dropping event from plugin {}: {}", event.plugin_id, e);
+        return;
+    }
+
+    // Connections are collected under the lock, the messages sent without it
+    let (db_pool, subscribers, browser_events) = {
+        let sup = supervisor.lock().await;
+        (
+            sup.db_pool.clone(),
+            sup.event_subscribers(&event).await,
+            sup.browser_events.clone(),
         )
-        .await;
+    };
 
+    let details = (!event.data.is_null()).then(|| event.data.to_string());
+    let event_type = format!("event:{}", event.name);
+    if let Err(e) =
+        crate::db::plugin_event_log(&db_pool, &event.plugin_id, &event_type, details.as_deref())
+            .await
+    {
         warn!(
-            "Plugin {} crashed, restarting in {}ms (attempt #{})",
-            plugin_id, delay_ms, restart_count
+            "Failed to record event from plugin {}: {}",
+            event.plugin_id, e
         );
+    }
 
-        // Wait with exponential backoff
-        tokio::time::sleep(tokio::time::Duration::from_millis(delay_ms)).await;
+    let message = event.to_message();
+    for (plugin_id, connection) in subscribers {
+        if let Err(e) = connection.send(&message).await {
+            warn!(
+                "Failed to forward event {} to plugin {}: {}",
+                event.name, plugin_id, e
+            );
+        }
+    }
 
-        // Spawn plugin
-        self.spawn_plugin(plugin_id, binary_path, metadata).await?;
+    // No receivers just means no browser is connected
+    let _ = browser_events.send(event);
+}
 
-        // Send init message
-        if let Err(e) = self.send_init_message(plugin_id).await {
-            error!("Failed to send init message after restart: {}", e);
+/// Run a plugin's call to another plugin and send it the result
+async fn answer_plugin_call(
+    supervisor: Arc<tokio::sync::Mutex<PluginSupervisor>>,
+    caller: String,
+    request_id: String,
+    call: PluginCall,
+) {
+    let result = match call_plugin(&supervisor, &caller, &call).await {
+        Ok(response) => PluginCallResult {
+            response: Some(response),
+            error: None,
+        },
+        Err(e) => {
+            debug!(
+                "Call from plugin {} to {} failed: {:#}",
+                caller, call.plugin, e
+            );
+            PluginCallResult {
+                response: None,
+                error: Some(format!("{:#}", e)),
+            }
+        }
+    };
+
+    let connection = supervisor.lock().await.plugin_connection(&caller).await;
+    let sent = match connection {
+        Ok(connection) => {
+            connection
+                .send(&Message::new_call_result(request_id, result))
+                .await
         }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = sent {
+        warn!("Failed to answer call from plugin {}: {}", caller, e);
+    }
+}
 
-        info!("Plugin {} restarted successfully", plugin_id);
+/// Run a plugin's `exec` request and send it the task it started
+async fn answer_plugin_exec(
+    supervisor: Arc<tokio::sync::Mutex<PluginSupervisor>>,
+    caller: String,
+    request_id: String,
+    exec: PluginExec,
+) {
+    let result = exec_for_plugin(&supervisor, &caller, &exec)
+        .await
+        .unwrap_or_else(|e| {
+            debug!("Exec request from plugin {} failed: {:#}", caller, e);
+            PluginExecResult {
+                error: Some(format!("{:#}", e)),
+                ..Default::default()
+            }
+        });
+
+    let connection = supervisor.lock().await.plugin_connection(&caller).await;
+    let sent = match connection {
+        Ok(connection) => {
+            connection
+                .send(&Message::new_exec_result(request_id, result))
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = sent {
+        warn!(
+            "Failed to answer exec request from plugin {}: {}",
+            caller, e
+        );
+    }
+}
 
-        Ok(())
+/// Start the quick action or script a plugin asked for
+///
+/// The plugin needs the `exec:<script>` capability; started tasks are recorded
+/// in `plugin_events` as `exec`.
+async fn exec_for_plugin(
+    supervisor: &tokio::sync::Mutex<PluginSupervisor>,
+    caller: &str,
+    exec: &PluginExec,
+) -> Result<PluginExecResult> {
+    let db_pool = supervisor.lock().await.db_pool.clone();
+    let target = plugin_exec::resolve(&db_pool, exec).await?;
+    if !supervisor
+        .lock()
+        .await
+        .has_capability(caller, &target.capability())
+    {
+        return Err(anyhow::anyhow!(
+            "Plugin {} may not run {}",
+            caller,
+            target.script_name
+        ));
+    }
+
+    let script_name = target.script_name.clone();
+    let result = plugin_exec::start(&db_pool, target).await?;
+    let details = serde_json::json!({ "script": script_name, "task_id": result.task_id });
+    if let Err(e) =
+        crate::db::plugin_event_log(&db_pool, caller, "exec", Some(&details.to_string())).await
+    {
+        warn!("Failed to record exec from plugin {}: {}", caller, e);
+    }
+    Ok(result)
+}
+
+/// Send a plugin the host's resource usage if it has the `system:info` capability
+async fn answer_system_info(
+    supervisor: Arc<tokio::sync::Mutex<PluginSupervisor>>,
+    plugin_id: String,
+    request_id: String,
+) {
+    let (allowed, snapshot) = {
+        let sup = supervisor.lock().await;
+        (
+            sup.has_capability(&plugin_id, "system:info"),
+            sup.resources.snapshot(),
+        )
+    };
+    let result = if allowed {
+        match snapshot {
+            Some(resources) => PluginSystemInfoResult {
+                resources: Some(resources),
+                error: None,
+            },
+            None => PluginSystemInfoResult {
+                resources: None,
+                error: Some("System information is not available".to_string()),
+            },
+        }
+    } else {
+        PluginSystemInfoResult {
+            resources: None,
+            error: Some(format!(
+                "Plugin {} may not read system information",
+                plugin_id
+            )),
+        }
+    };
+
+    let connection = supervisor.lock().await.plugin_connection(&plugin_id).await;
+    let sent = match connection {
+        Ok(connection) => {
+            connection
+                .send(&Message::new_system_info_result(request_id, result))
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = sent {
+        warn!(
+            "Failed to answer system info request from plugin {}: {}",
+            plugin_id, e
+        );
+    }
+}
+
+/// Forward a plugin's HTTP request to the plugin it calls
+///
+/// The caller needs the `call:<callee>` capability, and the call gives up after
+/// its `timeout_ms` (at most the callee's request timeout), including a streamed body.
+async fn call_plugin(
+    supervisor: &tokio::sync::Mutex<PluginSupervisor>,
+    caller: &str,
+    call: &PluginCall,
+) -> Result<HttpMessageResponse> {
+    if call.plugin == caller {
+        return Err(anyhow::anyhow!("A plugin can't call itself"));
+    }
+
+    let (connection, max_timeout) = {
+        let sup = supervisor.lock().await;
+        if !sup.has_capability(caller, &format!("call:{}", call.plugin)) {
+            return Err(anyhow::anyhow!(
+                "Plugin {} may not call plugin {}",
+                caller,
+                call.plugin
+            ));
+        }
+        (
+            sup.http_connection(&call.plugin).await?,
+            sup.request_timeout(&call.plugin),
+        )
+    };
+
+    let timeout = call
+        .timeout_ms
+        .map(Duration::from_millis)
+        .map_or(max_timeout, |timeout| timeout.min(max_timeout));
+    tokio::time::timeout(
+        timeout,
+        send_http_request(&connection, &call.request, timeout),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("Plugin {} did not answer within {:?}", call.plugin, timeout))?
+}
+
+/// Spawn the loop that sends `scheduled` messages for the jobs plugins registered
+///
+/// Every `JOB_TICK_INTERVAL` the due jobs are collected under the supervisor lock
+/// and the messages sent after releasing it.
+pub fn spawn_job_scheduler(supervisor: Arc<tokio::sync::Mutex<PluginSupervisor>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(JOB_TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let due = {
+                let mut sup = supervisor.lock().await;
+                let mut due = Vec::new();
+                for (plugin_id, job_id) in sup.take_due_jobs(chrono::Utc::now()) {
+                    match sup.plugin_connection(&plugin_id).await {
+                        Ok(connection) => due.push((plugin_id, job_id, connection)),
+                        Err(e) => warn!("Skipping job {} of plugin {}: {}", job_id, plugin_id, e),
+                    }
+                }
+                due
+            };
+
+            for (plugin_id, job_id, connection) in due {
+                debug!("Running job {} of plugin {}", job_id, plugin_id);
+                if let Err(e) = connection.send(&Message::new_scheduled(&job_id)).await {
+                    warn!(
+                        "Failed to run job {} of plugin {}: {}",
+                        job_id, plugin_id, e
+                    );
+                }
+            }
+        }
+    });
+}
+
+/// Spawn the background loop that reloads plugins whose binary was replaced
+///
+/// Every `HOT_RELOAD_INTERVAL` the watcher checks the binaries of running plugins
+/// and swaps in the new version of any that changed (see `reload_plugin`).
+pub fn spawn_hot_reload_watcher(supervisor: Arc<tokio::sync::Mutex<PluginSupervisor>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HOT_RELOAD_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let mut sup = supervisor.lock().await;
+            for (plugin_id, binary_path) in sup.changed_binaries() {
+                info!("Plugin {} binary changed, reloading", plugin_id);
+                if let Err(e) = sup.reload_plugin(&plugin_id, &binary_path).await {
+                    error!("Failed to reload plugin {}: {:#}", plugin_id, e);
+                    sup.notify_plugin_event(
+                        &plugin_id,
+                        "upgrade_failed",
+                        LogLevel::Error,
+                        Some(&serde_json::json!({ "error": format!("{:#}", e) }).to_string()),
+                    )
+                    .await;
+                }
+            }
+        }
+    });
+}
+
+/// Restart a crashed plugin without holding the supervisor lock during the backoff delay
+async fn recover_plugin(supervisor: Arc<tokio::sync::Mutex<PluginSupervisor>>, plugin_id: String) {
+    let crashed_at = Instant::now();
+
+    let delay = {
+        let mut sup = supervisor.lock().await;
+        match sup.prepare_restart(&plugin_id).await {
+            Ok(delay) => delay,
+            Err(e) => {
+                warn!("Not restarting plugin {}: {}", plugin_id, e);
+                sup.restarting.remove(&plugin_id);
+                return;
+            }
+        }
+    };
+
+    tokio::time::sleep(delay).await;
+
+    let mut sup = supervisor.lock().await;
+    sup.restarting.remove(&plugin_id);
+
+    // Skip if the plugin was disabled or restarted manually while we waited
+    let metadata = match sup.plugins.get(&plugin_id) {
+        Some(p) if p.enabled && p.started_at < crashed_at => p.metadata.clone(),
+        _ => return,
+    };
+    let Some(metadata) = metadata else {
+        warn!("Cannot restart plugin {}: metadata unknown", plugin_id);
+        return;
+    };
+
+    let binary_path = sup.plugin_binary_path(&plugin_id);
+    if let Err(e) = sup.restart_plugin(&plugin_id, &binary_path, metadata).await {
+        error!("Failed to restart plugin {}: {}", plugin_id, e);
+        return;
     }
+
+    sup.notify_plugin_event(&plugin_id, "restarted", LogLevel::Info, None)
+        .await;
 }
 
 #[cfg(test)]
@@ -1061,6 +4040,52 @@ mod tests {
         assert_eq!(supervisor.get_restart_count("test"), 0);
     }
 
+    #[test]
+    fn test_validate_route() {
+        for route in ["/hello-rust", "/my_plugin", "/Weather2"] {
+            assert!(validate_route(route).is_ok(), "{} was rejected", route);
+        }
+        for route in [
+            "", "/", "hello", "/a/b", "/../x", "/a b", "/api", "/Plugins", "/ws",
+        ] {
+            assert!(validate_route(route).is_err(), "{:?} was accepted", route);
+        }
+        assert!(validate_route(&format!("/{}", "a".repeat(MAX_ROUTE_LEN))).is_err());
+    }
+
+    #[test]
+    fn test_validate_env_var() {
+        assert!(validate_env_var("BACKUP_TARGET", "s3://bucket").is_ok());
+        assert!(validate_env_var("_private2", "").is_ok());
+        for name in ["", "2FAST", "A-B", "A B", "TORU_PLUGIN_ID", "toru_api_url"] {
+            assert!(
+                validate_env_var(name, "x").is_err(),
+                "{:?} was accepted",
+                name
+            );
+        }
+        assert!(validate_env_var("NUL", "a\0b").is_err());
+    }
+
+    #[test]
+    fn test_validate_instance_name() {
+        for name in ["offsite", "nas-2"] {
+            assert!(
+                validate_instance_name(name).is_ok(),
+                "{} was rejected",
+                name
+            );
+        }
+        for name in ["", "Offsite", "a b", "a@b", "../x"] {
+            assert!(
+                validate_instance_name(name).is_err(),
+                "{:?} was accepted",
+                name
+            );
+        }
+        assert!(validate_instance_name(&"a".repeat(MAX_INSTANCE_NAME_LEN + 1)).is_err());
+    }
+
     #[test]
     fn test_should_disable() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -1083,4 +4108,55 @@ mod tests {
 
         assert!(supervisor.should_disable_plugin("test"));
     }
+
+    #[tokio::test]
+    async fn test_http_responses_old_and_new() {
+        let (host, mut plugin) = tokio::io::duplex(64 * 1024);
+        let connection = PluginConnection::new(host);
+
+        // Fake plugin: answer the first request as version 3 does, the second
+        // wrapped in an `http` message like older plugins
+        tokio::spawn(async move {
+            let mut protocol = toru_plugin_api::PluginProtocol::new();
+            let first = protocol.read_message(&mut plugin).await.unwrap();
+            let response = HttpMessageResponse {
+                status: 201,
+                headers: HashMap::new(),
+                body: Some("new".to_string()),
+                body_encoding: Default::default(),
+                stream: false,
+            };
+            let reply = Message::new_http_response(first.request_id.unwrap(), response);
+            protocol.write_message(&mut plugin, &reply).await.unwrap();
+
+            let second = protocol.read_message(&mut plugin).await.unwrap();
+            let wrapped = HttpRequest {
+                method: "RESPONSE".to_string(),
+                path: String::new(),
+                headers: HashMap::new(),
+                body: Some(r#"{"status":202,"headers":{},"body":{"legacy":true}}"#.to_string()),
+                body_encoding: Default::default(),
+            };
+            let reply = Message::new_http(second.request_id.unwrap(), wrapped);
+            protocol.write_message(&mut plugin, &reply).await.unwrap();
+        });
+
+        let request = HttpRequest {
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            headers: HashMap::new(),
+            body: None,
+            body_encoding: Default::default(),
+        };
+        let new = send_http_request(&connection, &request, DEFAULT_REQUEST_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(new.status, 201);
+        assert_eq!(new.body.as_deref(), Some("new"));
+        let legacy = send_http_request(&connection, &request, DEFAULT_REQUEST_TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(legacy.status, 202);
+        assert_eq!(legacy.body.as_deref(), Some(r#"{"legacy":true}"#));
+    }
 }
@@ -0,0 +1,166 @@
+//! Sockets listening on the host, for the port inspector
+//!
+//! Read from `/proc/net` on Linux. The owning process is found by matching
+//! socket inodes against `/proc/<pid>/fd`, which only works for other users'
+//! processes when the Steering Center runs as root; otherwise `pid` and
+//! `process` are left empty for them.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ListeningSocket {
+    /// `tcp` or `udp`
+    pub protocol: &'static str,
+    /// Local address, e.g. `0.0.0.0` or `::1`
+    pub address: String,
+    pub port: u16,
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+    /// Owner of the socket
+    pub user: Option<String>,
+}
+
+/// A socket as read from `/proc/net`, before its owner is looked up
+#[derive(Debug, Clone)]
+struct RawSocket {
+    protocol: &'static str,
+    local: SocketAddr,
+    uid: u32,
+    inode: u64,
+}
+
+/// List listening TCP sockets and unconnected UDP sockets, ordered by port
+#[cfg(target_os = "linux")]
+pub fn list_listening_sockets() -> Result<Vec<ListeningSocket>> {
+    use procfs::net::{TcpState, UdpState};
+
+    let mut sockets = Vec::new();
+    for entry in procfs::net::tcp()?
+        .into_iter()
+        .chain(procfs::net::tcp6().unwrap_or_default())
+        .filter(|entry| entry.state == TcpState::Listen)
+    {
+        sockets.push(RawSocket {
+            protocol: "tcp",
+            local: entry.local_address,
+            uid: entry.uid,
+            inode: entry.inode,
+        });
+    }
+    for entry in procfs::net::udp()?
+        .into_iter()
+        .chain(procfs::net::udp6().unwrap_or_default())
+        // UDP has no listen state; a socket bound but not connected is receiving
+        .filter(|entry| entry.state == UdpState::Close && entry.remote_address.port() == 0)
+    {
+        sockets.push(RawSocket {
+            protocol: "udp",
+            local: entry.local_address,
+            uid: entry.uid,
+            inode: entry.inode,
+        });
+    }
+
+    let users = sysinfo::Users::new_with_refreshed_list();
+    let user_name = |uid: u32| {
+        users
+            .iter()
+            .find(|user| **user.id() == uid)
+            .map(|user| user.name().to_string())
+    };
+    Ok(resolve(sockets, &socket_owners(), user_name))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn list_listening_sockets() -> Result<Vec<ListeningSocket>> {
+    Err(anyhow::anyhow!(
+        "Listing listening sockets is only supported on Linux"
+    ))
+}
+
+/// Process (PID and name) holding each socket inode, for the processes we can read
+#[cfg(target_os = "linux")]
+fn socket_owners() -> HashMap<u64, (u32, String)> {
+    use procfs::process::FDTarget;
+
+    let mut owners = HashMap::new();
+    let Ok(processes) = procfs::process::all_processes() else {
+        return owners;
+    };
+    for process in processes.flatten() {
+        let Ok(fds) = process.fd() else {
+            continue;
+        };
+        let name = process.stat().map(|stat| stat.comm).unwrap_or_default();
+        for fd in fds.flatten() {
+            if let FDTarget::Socket(inode) = fd.target {
+                owners
+                    .entry(inode)
+                    .or_insert_with(|| (process.pid as u32, name.clone()));
+            }
+        }
+    }
+    owners
+}
+
+fn resolve(
+    sockets: Vec<RawSocket>,
+    owners: &HashMap<u64, (u32, String)>,
+    user_name: impl Fn(u32) -> Option<String>,
+) -> Vec<ListeningSocket> {
+    let mut resolved: Vec<ListeningSocket> = sockets
+        .into_iter()
+        .map(|socket| {
+            let owner = owners.get(&socket.inode);
+            ListeningSocket {
+                protocol: socket.protocol,
+                address: socket.local.ip().to_string(),
+                port: socket.local.port(),
+                pid: owner.map(|(pid, _)| *pid),
+                process: owner.map(|(_, name)| name.clone()),
+                user: user_name(socket.uid),
+            }
+        })
+        .collect();
+    resolved
+        .sort_by(|a, b| (a.port, a.protocol, &a.address).cmp(&(b.port, b.protocol, &b.address)));
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_owners() {
+        let sockets = vec![
+            RawSocket {
+                protocol: "udp",
+                local: "0.0.0.0:53".parse().unwrap(),
+                uid: 101,
+                inode: 7,
+            },
+            RawSocket {
+                protocol: "tcp",
+                local: "[::]:22".parse().unwrap(),
+                uid: 0,
+                inode: 5,
+            },
+        ];
+        let owners = HashMap::from([(5, (812, "sshd".to_string()))]);
+        let user_name = |uid: u32| (uid == 0).then(|| "root".to_string());
+
+        let resolved = resolve(sockets, &owners, user_name);
+        assert_eq!(resolved[0].port, 22);
+        assert_eq!(resolved[0].address, "::");
+        assert_eq!(resolved[0].pid, Some(812));
+        assert_eq!(resolved[0].process.as_deref(), Some("sshd"));
+        assert_eq!(resolved[0].user.as_deref(), Some("root"));
+        assert_eq!(resolved[1].protocol, "udp");
+        assert_eq!(resolved[1].pid, None);
+        assert_eq!(resolved[1].user, None);
+    }
+}
@@ -0,0 +1,463 @@
+//! Battery and UPS status, and alerts when the host runs on battery
+//!
+//! Batteries are read from sysfs. A UPS is read from a NUT (Network UPS Tools)
+//! server when the `nut_ups` setting names one, as `upsname@host[:port]`. A
+//! background job checks both every 30 seconds and broadcasts an alert when the
+//! host goes on battery or its charge drops below `power_alert_threshold`
+//! percent, and again when that clears.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+
+use super::notifications::{self, Notification, NotificationKind, NotificationLevel};
+use super::sensors;
+use super::system::BatteryInfo;
+use super::webhooks;
+use crate::db::{self, DbPool};
+
+/// Setting naming the UPS to read from a NUT server, e.g. `myups@localhost`
+pub const NUT_UPS_SETTING: &str = "nut_ups";
+
+/// Setting with the charge, in percent, below which a low charge alert is raised
+pub const ALERT_THRESHOLD_SETTING: &str = "power_alert_threshold";
+
+const DEFAULT_ALERT_THRESHOLD: f32 = 20.0;
+
+/// Port `upsd` listens on unless the setting names another
+const DEFAULT_NUT_PORT: u16 = 3493;
+
+/// How long the NUT server may take to connect and answer
+const NUT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the background job checks for alerts
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpsInfo {
+    pub name: String,
+    pub model: Option<String>,
+    /// Raw `ups.status` flags, e.g. `OL CHRG` or `OB LB`
+    pub status: String,
+    pub on_battery: bool,
+    /// The UPS itself reports its battery as low
+    pub low_battery: bool,
+    pub charge_percent: Option<f32>,
+    pub runtime_seconds: Option<u64>,
+    pub load_percent: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerAlert {
+    /// Battery name, or `ups:<name>`
+    pub source: String,
+    /// `on_battery` or `low_charge`
+    pub kind: &'static str,
+    /// `warning` or `error`; `info` once resolved
+    pub level: &'static str,
+    pub message: String,
+    /// Sent by the background job when the condition has cleared
+    pub resolved: bool,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerStatus {
+    pub batteries: Vec<BatteryInfo>,
+    /// Absent when no UPS is configured
+    pub ups: Option<UpsInfo>,
+    /// Why the configured UPS couldn't be read
+    pub ups_error: Option<String>,
+    pub alert_threshold: f32,
+    /// Alerts active right now
+    pub alerts: Vec<PowerAlert>,
+}
+
+/// Read batteries and the configured UPS, and work out the active alerts
+pub async fn read_power_status(db: &DbPool) -> PowerStatus {
+    let batteries = tokio::task::spawn_blocking(sensors::read_batteries)
+        .await
+        .unwrap_or_default();
+    let alert_threshold = db::get_setting(db, ALERT_THRESHOLD_SETTING)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|threshold| threshold.trim().parse().ok())
+        .unwrap_or(DEFAULT_ALERT_THRESHOLD);
+    let target = db::get_setting(db, NUT_UPS_SETTING)
+        .await
+        .ok()
+        .flatten()
+        .filter(|target| !target.trim().is_empty());
+
+    let (ups, ups_error) = match target {
+        Some(target) => match read_ups(target.trim()).await {
+            Ok(ups) => (Some(ups), None),
+            Err(e) => (None, Some(format!("{:#}", e))),
+        },
+        None => (None, None),
+    };
+    let alerts = power_alerts(&batteries, ups.as_ref(), alert_threshold);
+    PowerStatus {
+        batteries,
+        ups,
+        ups_error,
+        alert_threshold,
+        alerts,
+    }
+}
+
+/// Check for power alerts every 30 seconds, logging and broadcasting changes
+pub fn spawn_power_monitor(db: DbPool, alerts: broadcast::Sender<PowerAlert>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        // Alerts active at the previous check, by source and kind
+        let mut active: HashMap<(String, &'static str), PowerAlert> = HashMap::new();
+        loop {
+            interval.tick().await;
+            let status = read_power_status(&db).await;
+            if let Some(e) = &status.ups_error {
+                tracing::debug!("Failed to read UPS status: {}", e);
+            }
+
+            let mut current = HashMap::new();
+            for alert in status.alerts {
+                let key = (alert.source.clone(), alert.kind);
+                if !active.contains_key(&key) {
+                    tracing::warn!("{}", alert.message);
+                    notify(&db, &alert);
+                    let _ = alerts.send(alert.clone());
+                }
+                current.insert(key, alert);
+            }
+            for (key, alert) in active.drain() {
+                if !current.contains_key(&key) {
+                    let resolved = alert.resolve();
+                    tracing::info!("{}", resolved.message);
+                    notify(&db, &resolved);
+                    let _ = alerts.send(resolved);
+                }
+            }
+            active = current;
+        }
+    });
+}
+
+/// Send `alert.raised` or `alert.resolved` to webhooks and the alert channels
+fn notify(db: &DbPool, alert: &PowerAlert) {
+    let event = if alert.resolved {
+        "alert.resolved"
+    } else {
+        "alert.raised"
+    };
+    if let Ok(data) = serde_json::to_value(alert) {
+        webhooks::emit(event, data);
+    }
+
+    let (level, title) = match alert.level {
+        "error" => (NotificationLevel::Error, "Power alert"),
+        "warning" => (NotificationLevel::Warning, "Power alert"),
+        _ => (NotificationLevel::Info, "Power alert cleared"),
+    };
+    notifications::notify(
+        db,
+        Notification::new(NotificationKind::Alert, level, title, alert.message.clone()),
+    );
+}
+
+impl PowerAlert {
+    fn new(source: String, kind: &'static str, level: &'static str, message: String) -> Self {
+        Self {
+            source,
+            kind,
+            level,
+            message,
+            resolved: false,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// The alert announcing that this one has cleared
+    fn resolve(self) -> Self {
+        let message = match self.kind {
+            "on_battery" => format!("{} is back on mains power", self.source),
+            _ => format!("{} is no longer low on charge", self.source),
+        };
+        Self {
+            level: "info",
+            message,
+            resolved: true,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            ..self
+        }
+    }
+}
+
+/// Alerts for batteries that are discharging and a UPS running on battery
+fn power_alerts(
+    batteries: &[BatteryInfo],
+    ups: Option<&UpsInfo>,
+    threshold: f32,
+) -> Vec<PowerAlert> {
+    let mut alerts = Vec::new();
+    for battery in batteries
+        .iter()
+        .filter(|battery| battery.status == "Discharging")
+    {
+        alerts.push(PowerAlert::new(
+            battery.name.clone(),
+            "on_battery",
+            "warning",
+            format!("{} is discharging", battery.name),
+        ));
+        if let Some(percent) = battery.percent.filter(|percent| *percent <= threshold) {
+            alerts.push(PowerAlert::new(
+                battery.name.clone(),
+                "low_charge",
+                "error",
+                format!("{} is at {:.0}% charge", battery.name, percent),
+            ));
+        }
+    }
+    if let Some(ups) = ups {
+        let source = format!("ups:{}", ups.name);
+        if ups.on_battery {
+            alerts.push(PowerAlert::new(
+                source.clone(),
+                "on_battery",
+                "warning",
+                format!("UPS {} is running on battery", ups.name),
+            ));
+        }
+        let below_threshold = ups.on_battery
+            && ups
+                .charge_percent
+                .is_some_and(|percent| percent <= threshold);
+        if ups.low_battery || below_threshold {
+            let charge = ups
+                .charge_percent
+                .map(|percent| format!(" ({:.0}% charge)", percent))
+                .unwrap_or_default();
+            alerts.push(PowerAlert::new(
+                source,
+                "low_charge",
+                "error",
+                format!("UPS {} battery is low{}", ups.name, charge),
+            ));
+        }
+    }
+    alerts
+}
+
+/// Read a UPS from the NUT server named by `target`
+async fn read_ups(target: &str) -> Result<UpsInfo> {
+    let (name, host, port) = parse_ups_target(target)?;
+    let vars = tokio::time::timeout(NUT_TIMEOUT, list_ups_vars(&name, &host, port))
+        .await
+        .map_err(|_| anyhow!("NUT server at {}:{} timed out", host, port))??;
+    Ok(ups_info(name, &vars))
+}
+
+/// Split `upsname[@host[:port]]`; the host defaults to `localhost`
+fn parse_ups_target(target: &str) -> Result<(String, String, u16)> {
+    let (name, server) = target.split_once('@').unwrap_or((target, "localhost"));
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(anyhow!(
+            "Invalid UPS name in {}: {}",
+            NUT_UPS_SETTING,
+            target
+        ));
+    }
+    let (host, port) = match server.rsplit_once(':') {
+        // A bare IPv6 address has colons but no port
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => (
+            host,
+            port.parse()
+                .map_err(|_| anyhow!("Invalid port in {}: {}", NUT_UPS_SETTING, target))?,
+        ),
+        _ => (server, DEFAULT_NUT_PORT),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Ok((name.to_string(), host.to_string(), port))
+}
+
+/// Variables of a UPS, as answered to `LIST VAR`
+async fn list_ups_vars(name: &str, host: &str, port: u16) -> Result<HashMap<String, String>> {
+    let stream = TcpStream::connect((host, port))
+        .await
+        .with_context(|| format!("Failed to connect to NUT server at {}:{}", host, port))?;
+    let (reader, mut writer) = stream.into_split();
+    writer
+        .write_all(format!("LIST VAR {}\n", name).as_bytes())
+        .await?;
+
+    let mut lines = BufReader::new(reader).lines();
+    let mut response = String::new();
+    while let Some(line) = lines.next_line().await? {
+        let done = line.starts_with("END LIST") || line.starts_with("ERR ");
+        response.push_str(&line);
+        response.push('\n');
+        if done {
+            break;
+        }
+    }
+    let _ = writer.write_all(b"LOGOUT\n").await;
+    parse_var_list(&response)
+}
+
+/// Parse the `VAR <ups> <name> "<value>"` lines of a `LIST VAR` response
+fn parse_var_list(response: &str) -> Result<HashMap<String, String>> {
+    let mut vars = HashMap::new();
+    for line in response.lines() {
+        if let Some(error) = line.strip_prefix("ERR ") {
+            return Err(anyhow!("NUT server answered {}", error.trim()));
+        }
+        let Some(rest) = line.strip_prefix("VAR ") else {
+            continue;
+        };
+        let mut parts = rest.splitn(3, ' ');
+        let (Some(_ups), Some(name), Some(value)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        vars.insert(name.to_string(), unquote(value));
+    }
+    if !response.lines().any(|line| line.starts_with("END LIST")) {
+        return Err(anyhow!("NUT server closed the connection"));
+    }
+    Ok(vars)
+}
+
+/// A double-quoted NUT value, with `\"` and `\\` escapes
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    let mut unquoted = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+fn ups_info(name: String, vars: &HashMap<String, String>) -> UpsInfo {
+    let number = |var: &str| vars.get(var).and_then(|value| value.parse::<f32>().ok());
+    let status = vars.get("ups.status").cloned().unwrap_or_default();
+    let flags: Vec<&str> = status.split_whitespace().collect();
+    UpsInfo {
+        model: vars
+            .get("ups.model")
+            .or_else(|| vars.get("device.model"))
+            .cloned(),
+        on_battery: flags.contains(&"OB"),
+        low_battery: flags.contains(&"LB"),
+        charge_percent: number("battery.charge"),
+        runtime_seconds: number("battery.runtime").map(|seconds| seconds as u64),
+        load_percent: number("ups.load"),
+        name,
+        status,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ups_target() {
+        assert_eq!(
+            parse_ups_target("myups").unwrap(),
+            ("myups".to_string(), "localhost".to_string(), 3493)
+        );
+        assert_eq!(
+            parse_ups_target("rack@10.0.0.5:3494").unwrap(),
+            ("rack".to_string(), "10.0.0.5".to_string(), 3494)
+        );
+        assert_eq!(
+            parse_ups_target("rack@[::1]:3493").unwrap(),
+            ("rack".to_string(), "::1".to_string(), 3493)
+        );
+        assert!(parse_ups_target("@localhost").is_err());
+        assert!(parse_ups_target("rack@nas:port").is_err());
+    }
+
+    #[test]
+    fn test_parse_var_list() {
+        let response = "BEGIN LIST VAR myups\n\
+            VAR myups battery.charge \"35\"\n\
+            VAR myups battery.runtime \"540\"\n\
+            VAR myups ups.load \"22\"\n\
+            VAR myups ups.model \"Back-UPS \\\"ES\\\" 700\"\n\
+            VAR myups ups.status \"OB DISCHRG\"\n\
+            END LIST VAR myups\n";
+        let vars = parse_var_list(response).unwrap();
+        let ups = ups_info("myups".to_string(), &vars);
+        assert_eq!(ups.model.as_deref(), Some("Back-UPS \"ES\" 700"));
+        assert!(ups.on_battery);
+        assert!(!ups.low_battery);
+        assert_eq!(ups.charge_percent, Some(35.0));
+        assert_eq!(ups.runtime_seconds, Some(540));
+
+        assert!(parse_var_list("ERR UNKNOWN-UPS\n").is_err());
+    }
+
+    #[test]
+    fn test_power_alerts() {
+        let batteries = vec![
+            BatteryInfo {
+                name: "BAT0".to_string(),
+                percent: Some(15.0),
+                status: "Discharging".to_string(),
+            },
+            BatteryInfo {
+                name: "BAT1".to_string(),
+                percent: Some(10.0),
+                status: "Charging".to_string(),
+            },
+        ];
+        let ups = UpsInfo {
+            name: "myups".to_string(),
+            model: None,
+            status: "OB".to_string(),
+            on_battery: true,
+            low_battery: false,
+            charge_percent: Some(60.0),
+            runtime_seconds: None,
+            load_percent: None,
+        };
+
+        let alerts = power_alerts(&batteries, Some(&ups), 20.0);
+        let alerts: Vec<(&str, &str)> = alerts
+            .iter()
+            .map(|alert| (alert.source.as_str(), alert.kind))
+            .collect();
+        assert_eq!(
+            alerts,
+            [
+                ("BAT0", "on_battery"),
+                ("BAT0", "low_charge"),
+                ("ups:myups", "on_battery")
+            ]
+        );
+
+        let resolved = PowerAlert::new(
+            "ups:myups".to_string(),
+            "on_battery",
+            "warning",
+            String::new(),
+        )
+        .resolve();
+        assert!(resolved.resolved);
+        assert_eq!(resolved.level, "info");
+    }
+}
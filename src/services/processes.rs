@@ -0,0 +1,270 @@
+//! Processes running on the host, for the process explorer
+//!
+//! Lists come from the shared `sysinfo::System`, so CPU usage is measured since
+//! the previous refresh by any caller (the resources endpoint refreshes it too).
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{System, Users};
+
+/// Processes returned when the request doesn't ask for a page size
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Most processes returned at once
+pub const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub parent_pid: Option<u32>,
+    pub name: String,
+    /// Full command line, empty when it can't be read
+    pub command: String,
+    /// CPU usage in percent of one core since the previous refresh
+    pub cpu_percent: f32,
+    /// Resident memory
+    pub memory_bytes: u64,
+    pub user: Option<String>,
+    /// RFC 3339
+    pub start_time: Option<String>,
+    pub status: String,
+}
+
+/// Column to sort the process list by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessSort {
+    Pid,
+    Name,
+    #[default]
+    Cpu,
+    Memory,
+    StartTime,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// One page of the process list
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessPage {
+    /// Processes on the host, not just on this page
+    pub total: usize,
+    pub processes: Vec<ProcessInfo>,
+}
+
+/// Refresh and list the host's processes
+///
+/// # Arguments
+/// * `sort` / `order` - Ordering of the whole list, before paging
+/// * `limit` - Page size, capped at `MAX_PAGE_SIZE`
+/// * `offset` - Processes to skip
+pub fn list_processes(
+    sys: &mut System,
+    sort: ProcessSort,
+    order: SortOrder,
+    limit: Option<usize>,
+    offset: usize,
+) -> ProcessPage {
+    sys.refresh_processes();
+    let users = Users::new_with_refreshed_list();
+
+    let mut processes: Vec<ProcessInfo> = sys
+        .processes()
+        .values()
+        // Threads show up as processes on Linux; list each process once
+        .filter(|process| process.thread_kind().is_none())
+        .map(|process| ProcessInfo {
+            pid: process.pid().as_u32(),
+            parent_pid: process.parent().map(|pid| pid.as_u32()),
+            name: process.name().to_string(),
+            command: process.cmd().join(" "),
+            cpu_percent: process.cpu_usage(),
+            memory_bytes: process.memory(),
+            user: process
+                .user_id()
+                .and_then(|uid| users.get_user_by_id(uid))
+                .map(|user| user.name().to_string()),
+            start_time: chrono::DateTime::from_timestamp(process.start_time() as i64, 0)
+                .map(|at| at.to_rfc3339()),
+            status: process.status().to_string(),
+        })
+        .collect();
+
+    sort_processes(&mut processes, sort, order);
+    let total = processes.len();
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let processes = processes.into_iter().skip(offset).take(limit).collect();
+    ProcessPage { total, processes }
+}
+
+fn sort_processes(processes: &mut [ProcessInfo], sort: ProcessSort, order: SortOrder) {
+    processes.sort_by(|a, b| {
+        let ordering = match sort {
+            ProcessSort::Pid => a.pid.cmp(&b.pid),
+            ProcessSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            ProcessSort::Cpu => a.cpu_percent.total_cmp(&b.cpu_percent),
+            ProcessSort::Memory => a.memory_bytes.cmp(&b.memory_bytes),
+            ProcessSort::StartTime => a.start_time.cmp(&b.start_time),
+        }
+        // Ties keep a stable order between refreshes
+        .then(a.pid.cmp(&b.pid));
+        match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+}
+
+/// Errors returned when signalling a process
+#[derive(Debug)]
+pub enum KillError {
+    InvalidSignal(String),
+    /// PID 1 and the Steering Center itself can't be signalled from the panel
+    Protected(u32),
+    NotFound(u32),
+    PermissionDenied(u32),
+    /// Signals only exist on Unix
+    #[cfg_attr(unix, allow(dead_code))]
+    Unsupported,
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for KillError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KillError::InvalidSignal(signal) => write!(f, "Unsupported signal: {}", signal),
+            KillError::Protected(pid) => write!(f, "Process {} can't be signalled", pid),
+            KillError::NotFound(pid) => write!(f, "Process {} not found", pid),
+            KillError::PermissionDenied(pid) => {
+                write!(f, "Not permitted to signal process {}", pid)
+            }
+            KillError::Unsupported => {
+                write!(f, "Sending signals is not supported on this platform")
+            }
+            KillError::Io(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for KillError {}
+
+/// Signals the panel can send, by name
+#[cfg(unix)]
+const SIGNALS: [(&str, libc::c_int); 9] = [
+    ("TERM", libc::SIGTERM),
+    ("KILL", libc::SIGKILL),
+    ("INT", libc::SIGINT),
+    ("HUP", libc::SIGHUP),
+    ("QUIT", libc::SIGQUIT),
+    ("STOP", libc::SIGSTOP),
+    ("CONT", libc::SIGCONT),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+];
+
+/// Signal number for a name like `TERM` or `SIGKILL` (case-insensitive)
+#[cfg(unix)]
+pub fn parse_signal(name: &str) -> Result<libc::c_int, KillError> {
+    let upper = name.trim().to_uppercase();
+    let short = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(signal, _)| *signal == short)
+        .map(|(_, number)| *number)
+        .ok_or_else(|| KillError::InvalidSignal(name.to_string()))
+}
+
+/// Send `signal` to a process
+#[cfg(unix)]
+pub fn kill_process(pid: u32, signal: &str) -> Result<(), KillError> {
+    let number = parse_signal(signal)?;
+    // PIDs past pid_t's range would wrap to -1 (every process) or a process group
+    let target = match libc::pid_t::try_from(pid) {
+        Ok(target) if target > 1 && pid != std::process::id() => target,
+        _ => return Err(KillError::Protected(pid)),
+    };
+
+    let result = unsafe { libc::kill(target, number) };
+    if result == 0 {
+        return Ok(());
+    }
+    let e = std::io::Error::last_os_error();
+    match e.raw_os_error() {
+        Some(libc::ESRCH) => Err(KillError::NotFound(pid)),
+        Some(libc::EPERM) => Err(KillError::PermissionDenied(pid)),
+        _ => Err(KillError::Io(e)),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn kill_process(_pid: u32, _signal: &str) -> Result<(), KillError> {
+    Err(KillError::Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, name: &str, cpu_percent: f32) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            parent_pid: None,
+            name: name.to_string(),
+            command: String::new(),
+            cpu_percent,
+            memory_bytes: 0,
+            user: None,
+            start_time: None,
+            status: "Run".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_sort_processes() {
+        let mut processes = vec![
+            process(3, "bash", 1.0),
+            process(1, "Zsh", 50.0),
+            process(2, "awk", 1.0),
+        ];
+        sort_processes(&mut processes, ProcessSort::Cpu, SortOrder::Desc);
+        let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, [1, 3, 2]);
+
+        sort_processes(&mut processes, ProcessSort::Name, SortOrder::Asc);
+        let names: Vec<&str> = processes.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["awk", "bash", "Zsh"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("term").unwrap(), libc::SIGTERM);
+        assert_eq!(parse_signal("SIGKILL").unwrap(), libc::SIGKILL);
+        assert!(parse_signal("SEGV").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_protected_processes() {
+        assert!(matches!(
+            kill_process(1, "TERM"),
+            Err(KillError::Protected(1))
+        ));
+        let own = std::process::id();
+        assert!(matches!(
+            kill_process(own, "KILL"),
+            Err(KillError::Protected(_))
+        ));
+        for pid in [u32::MAX, 1 << 31] {
+            assert!(matches!(
+                kill_process(pid, "TERM"),
+                Err(KillError::Protected(_))
+            ));
+        }
+    }
+}
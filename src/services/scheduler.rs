@@ -0,0 +1,138 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::db::{self, DbPool, ScheduledTask};
+use crate::services::action_params;
+use crate::services::executor::{self, ResourceLimits};
+
+/// How often the scheduler checks for due tasks
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Format a timestamp the way `scheduled_tasks.run_at` stores it
+///
+/// Second precision in UTC with a `Z` suffix, so text comparison matches time order.
+pub fn format_run_at(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Queue a script to run once at `run_at`
+pub async fn schedule_task(
+    db: &DbPool,
+    script_name: &str,
+    run_at: DateTime<Utc>,
+    created_by: Option<&str>,
+) -> Result<ScheduledTask> {
+    let task = ScheduledTask {
+        id: uuid::Uuid::new_v4().to_string(),
+        script_name: script_name.to_string(),
+        run_at: format_run_at(run_at),
+        status: "pending".to_string(),
+        task_id: None,
+        error: None,
+        created_by: created_by.map(String::from),
+        created_at: Utc::now().to_rfc3339(),
+    };
+    db::insert_scheduled_task(db, &task).await?;
+    Ok(task)
+}
+
+/// Start every pending task whose time has come. Returns how many were started.
+pub async fn run_due_tasks(db: &DbPool) -> Result<usize> {
+    // Read before claiming: an error past this point must not strand claimed tasks
+    let scripts_dir = db::get_setting(db, "scripts_dir")
+        .await?
+        .unwrap_or_else(|| "./scripts".to_string());
+    let due = db::take_due_scheduled_tasks(db, &format_run_at(Utc::now())).await?;
+    if due.is_empty() {
+        return Ok(0);
+    }
+
+    let actions = db::get_quick_actions(db).await.unwrap_or_default();
+    let global_limits = ResourceLimits::from_settings(db).await;
+
+    let mut started = 0;
+    for scheduled in due {
+        // Quick action settings apply to scheduled runs of the same script
+        let action = actions
+            .iter()
+            .find(|a| a.script_path == scheduled.script_name);
+        let limits = match action {
+            Some(a) => global_limits.with_action_overrides(a),
+            None => global_limits,
+        };
+        let concurrency = action.map(|a| a.concurrency).unwrap_or_default();
+        // Scheduled runs get the parameters' defaults
+        let env = action
+            .map(|a| action_params::run_env(a, &HashMap::new()))
+            .transpose();
+
+        let task_id = uuid::Uuid::new_v4().to_string();
+        let result = match env {
+            Ok(env) => {
+                executor::run_script_task(
+                    format!("{}/{}", scripts_dir, scheduled.script_name),
+                    task_id.clone(),
+                    scheduled.script_name.clone(),
+                    db.clone(),
+                    executor::create_task_registry(),
+                    limits,
+                    concurrency,
+                    env.unwrap_or_default(),
+                    None,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        let finished = match result {
+            Ok(()) => {
+                started += 1;
+                db::finish_scheduled_task(db, &scheduled.id, Some(&task_id), None).await
+            }
+            Err(e) => {
+                tracing::warn!("Scheduled task {} failed to start: {}", scheduled.id, e);
+                db::finish_scheduled_task(db, &scheduled.id, None, Some(&e.to_string())).await
+            }
+        };
+        // The rest of the batch is claimed too, so keep going
+        if let Err(e) = finished {
+            tracing::error!("Failed to record scheduled task {}: {}", scheduled.id, e);
+            continue;
+        }
+    }
+    Ok(started)
+}
+
+/// Spawn the background loop that executes scheduled tasks
+pub fn spawn_scheduler(db: DbPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_due_tasks(&db).await {
+                tracing::warn!("Failed to run scheduled tasks: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_at_sorts_as_text() {
+        let earlier = DateTime::parse_from_rfc3339("2025-03-01T09:59:59.900+01:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let later = DateTime::parse_from_rfc3339("2025-03-01T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(format_run_at(earlier), "2025-03-01T08:59:59Z");
+        assert!(format_run_at(earlier) < format_run_at(later));
+    }
+}
@@ -0,0 +1,246 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Maximum size of a script file accepted through the API (256 KB)
+pub const MAX_SCRIPT_SIZE: usize = 256 * 1024;
+
+/// File extensions recognised as runnable scripts
+#[cfg(not(windows))]
+pub const SCRIPT_EXTENSIONS: &[&str] = &["sh", "bash"];
+/// File extensions recognised as runnable scripts
+#[cfg(windows)]
+pub const SCRIPT_EXTENSIONS: &[&str] = &["sh", "bash", "ps1", "bat", "cmd"];
+
+/// Errors returned by script file management
+#[derive(Debug)]
+pub enum ScriptError {
+    InvalidName(String),
+    NotFound,
+    AlreadyExists,
+    TooLarge,
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::InvalidName(reason) => write!(f, "Invalid script name: {}", reason),
+            ScriptError::NotFound => write!(f, "Script not found"),
+            ScriptError::AlreadyExists => write!(f, "A script with that name already exists"),
+            ScriptError::TooLarge => write!(
+                f,
+                "Script exceeds maximum size of {} bytes",
+                MAX_SCRIPT_SIZE
+            ),
+            ScriptError::Io(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<std::io::Error> for ScriptError {
+    fn from(e: std::io::Error) -> Self {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            ScriptError::NotFound
+        } else {
+            ScriptError::Io(e)
+        }
+    }
+}
+
+/// Returns true if the file name has a recognised script extension
+pub fn is_script_file(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| SCRIPT_EXTENSIONS.contains(&ext))
+}
+
+/// Validate a script file name
+///
+/// Only plain file names are accepted (no directories, no hidden files), made of
+/// ASCII alphanumerics, `-`, `_` and `.`, with a recognised script extension.
+pub fn validate_script_name(name: &str) -> Result<(), ScriptError> {
+    if name.is_empty() || name.len() > 128 {
+        return Err(ScriptError::InvalidName(
+            "must be between 1 and 128 characters".to_string(),
+        ));
+    }
+    if name.starts_with('.') || name.contains("..") {
+        return Err(ScriptError::InvalidName(
+            "must not start with '.' or contain '..'".to_string(),
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(ScriptError::InvalidName(
+            "only letters, digits, '-', '_' and '.' are allowed".to_string(),
+        ));
+    }
+    if !is_script_file(name) {
+        return Err(ScriptError::InvalidName(format!(
+            "extension must be one of: {}",
+            SCRIPT_EXTENSIONS.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Resolve a validated script name to a path inside `scripts_dir`
+pub fn resolve_script_path(scripts_dir: &Path, name: &str) -> Result<PathBuf, ScriptError> {
+    validate_script_name(name)?;
+    Ok(scripts_dir.join(name))
+}
+
+/// List script files in the scripts directory, sorted by name
+pub fn list_scripts(scripts_dir: &Path) -> Vec<String> {
+    let mut scripts: Vec<String> = fs::read_dir(scripts_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str().map(String::from))
+                .filter(|name| is_script_file(name))
+                .collect()
+        })
+        .unwrap_or_default();
+    scripts.sort();
+    scripts
+}
+
+/// Read a script's content
+pub fn read_script(scripts_dir: &Path, name: &str) -> Result<String, ScriptError> {
+    let path = resolve_script_path(scripts_dir, name)?;
+    Ok(fs::read_to_string(path)?)
+}
+
+/// Create or overwrite a script
+///
+/// The file is written to a temporary file first and renamed into place so a
+/// running task never sees a half-written script.
+pub fn write_script(
+    scripts_dir: &Path,
+    name: &str,
+    content: &[u8],
+    executable: bool,
+) -> Result<(), ScriptError> {
+    if content.len() > MAX_SCRIPT_SIZE {
+        return Err(ScriptError::TooLarge);
+    }
+    let path = resolve_script_path(scripts_dir, name)?;
+    fs::create_dir_all(scripts_dir)?;
+
+    let tmp_path = scripts_dir.join(format!(".{}.tmp", name));
+    fs::write(&tmp_path, content)?;
+    set_executable(&tmp_path, executable)?;
+    fs::rename(&tmp_path, &path).inspect_err(|_| {
+        fs::remove_file(&tmp_path).ok();
+    })?;
+
+    Ok(())
+}
+
+/// Rename a script, refusing to overwrite an existing one
+pub fn rename_script(scripts_dir: &Path, from: &str, to: &str) -> Result<(), ScriptError> {
+    let from_path = resolve_script_path(scripts_dir, from)?;
+    let to_path = resolve_script_path(scripts_dir, to)?;
+
+    if !from_path.is_file() {
+        return Err(ScriptError::NotFound);
+    }
+    if to_path.exists() {
+        return Err(ScriptError::AlreadyExists);
+    }
+
+    fs::rename(from_path, to_path)?;
+    Ok(())
+}
+
+/// Delete a script
+pub fn delete_script(scripts_dir: &Path, name: &str) -> Result<(), ScriptError> {
+    let path = resolve_script_path(scripts_dir, name)?;
+    if !path.is_file() {
+        return Err(ScriptError::NotFound);
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path, executable: bool) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = if executable { 0o750 } else { 0o640 };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path, _executable: bool) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_script_name() {
+        assert!(validate_script_name("backup.sh").is_ok());
+        assert!(validate_script_name("disk-usage_v2.bash").is_ok());
+
+        assert!(validate_script_name("../etc/passwd.sh").is_err());
+        assert!(validate_script_name("sub/dir.sh").is_err());
+        assert!(validate_script_name(".hidden.sh").is_err());
+        assert!(validate_script_name("no-extension").is_err());
+        assert!(validate_script_name("binary.exe").is_err());
+        assert!(validate_script_name("").is_err());
+    }
+
+    #[test]
+    fn test_write_rename_delete_script() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+
+        write_script(dir, "hello.sh", b"echo hello\n", true).unwrap();
+        assert_eq!(read_script(dir, "hello.sh").unwrap(), "echo hello\n");
+        assert_eq!(list_scripts(dir), vec!["hello.sh".to_string()]);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join("hello.sh"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o111, 0o110);
+        }
+
+        write_script(dir, "other.sh", b"echo other\n", false).unwrap();
+        assert!(matches!(
+            rename_script(dir, "hello.sh", "other.sh"),
+            Err(ScriptError::AlreadyExists)
+        ));
+        rename_script(dir, "hello.sh", "greet.sh").unwrap();
+        assert!(matches!(
+            read_script(dir, "hello.sh"),
+            Err(ScriptError::NotFound)
+        ));
+
+        delete_script(dir, "greet.sh").unwrap();
+        assert!(matches!(
+            delete_script(dir, "greet.sh"),
+            Err(ScriptError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_write_script_size_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let content = vec![b'#'; MAX_SCRIPT_SIZE + 1];
+        assert!(matches!(
+            write_script(temp_dir.path(), "big.sh", &content, true),
+            Err(ScriptError::TooLarge)
+        ));
+    }
+}
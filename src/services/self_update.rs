@@ -0,0 +1,352 @@
+//! Updating the server binary from its releases
+//!
+//! The latest release is read from `update.releases_url` (the GitHub releases
+//! API by default). It must carry a binary for this platform
+//! (`steering-center-<arch>-<os>`) and a `SHA256SUMS` file listing it; when
+//! `update.public_key` is set, a minisign signature (`<binary>.minisig`) is
+//! required as well. The new binary is written next to the running one, tried
+//! with `--help`, and renamed over it; the previous one is kept as `<name>.old`.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::config::UpdateConfig;
+use crate::db::TrustedKey;
+use crate::services::{plugin_deps, plugin_registry, plugin_signing};
+
+/// Version of the running binary
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// Largest release document and checksum file read (1 MB)
+const MAX_METADATA_SIZE: usize = 1024 * 1024;
+
+/// Largest binary downloaded (256 MB)
+const MAX_BINARY_SIZE: usize = 256 * 1024 * 1024;
+
+/// How long the new binary may take to print its help
+const SMOKE_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Held while an update is installed
+static UPDATE_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug)]
+pub enum UpdateError {
+    /// Another update is being installed
+    InProgress,
+    /// The release couldn't be fetched or didn't verify
+    Release(anyhow::Error),
+    /// The binary couldn't be replaced
+    Failed(anyhow::Error),
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::InProgress => write!(f, "An update is already being installed"),
+            UpdateError::Release(e) => write!(f, "{:#}", e),
+            UpdateError::Failed(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for UpdateError {}
+
+/// A release as described by the GitHub API
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// Whether a newer release is available
+#[derive(Debug, Clone, Serialize)]
+pub struct ReleaseCheck {
+    pub current_version: String,
+    pub latest_version: String,
+    pub update_available: bool,
+    pub release_url: Option<String>,
+    pub published_at: Option<String>,
+    /// Binary for this platform, when the release has one
+    pub asset: Option<String>,
+}
+
+/// Name of the release asset with the binary for this platform
+pub fn asset_name() -> String {
+    format!(
+        "steering-center-{}-{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    )
+}
+
+pub async fn fetch_latest(config: &UpdateConfig) -> Result<Release> {
+    let data = plugin_registry::download(&config.releases_url, MAX_METADATA_SIZE)
+        .await
+        .context("Failed to fetch the latest release")?;
+    serde_json::from_slice(&data).context("Invalid release document")
+}
+
+/// Compare the latest release with the running version
+pub async fn check(config: &UpdateConfig) -> Result<(Release, ReleaseCheck)> {
+    let release = fetch_latest(config).await?;
+    let asset = asset_name();
+    let check = ReleaseCheck {
+        current_version: CURRENT_VERSION.to_string(),
+        latest_version: release.version().to_string(),
+        update_available: plugin_deps::compare_versions(release.version(), CURRENT_VERSION)
+            == Ordering::Greater,
+        release_url: release.html_url.clone(),
+        published_at: release.published_at.clone(),
+        asset: release.asset(&asset).map(|a| a.name.clone()),
+    };
+    Ok((release, check))
+}
+
+/// Download, verify and install the latest release over `binary` if it is newer
+///
+/// # Returns
+/// The installed version, or `None` when already up to date
+pub async fn update(config: &UpdateConfig, binary: &Path) -> Result<Option<String>, UpdateError> {
+    let _guard = UPDATE_LOCK
+        .try_lock()
+        .map_err(|_| UpdateError::InProgress)?;
+
+    let (release, check) = check(config).await.map_err(UpdateError::Release)?;
+    if !check.update_available {
+        return Ok(None);
+    }
+    let data = download_verified(config, &release)
+        .await
+        .map_err(UpdateError::Release)?;
+    install(binary, &data).await.map_err(UpdateError::Failed)?;
+    tracing::info!(
+        "Updated {} from {} to {}",
+        binary.display(),
+        CURRENT_VERSION,
+        release.version()
+    );
+    Ok(Some(release.version().to_string()))
+}
+
+/// The binary for this platform, checked against `SHA256SUMS` and the signature
+async fn download_verified(config: &UpdateConfig, release: &Release) -> Result<Vec<u8>> {
+    let name = asset_name();
+    let asset = release
+        .asset(&name)
+        .ok_or_else(|| anyhow!("Release {} has no {} binary", release.tag_name, name))?;
+    let checksums = release
+        .asset(CHECKSUMS_ASSET)
+        .ok_or_else(|| anyhow!("Release {} has no {}", release.tag_name, CHECKSUMS_ASSET))?;
+
+    let checksums = plugin_registry::download(&checksums.browser_download_url, MAX_METADATA_SIZE)
+        .await
+        .context("Failed to download the checksums")?;
+    let expected = find_checksum(&String::from_utf8_lossy(&checksums), &name)
+        .ok_or_else(|| anyhow!("{} doesn't list {}", CHECKSUMS_ASSET, name))?;
+
+    let data = plugin_registry::download(&asset.browser_download_url, MAX_BINARY_SIZE)
+        .await
+        .with_context(|| format!("Failed to download {}", name))?;
+    plugin_registry::verify_checksum(&data, &expected)?;
+
+    if let Some(public_key) = &config.public_key {
+        let signature_name = format!("{}.minisig", name);
+        let signature = release
+            .asset(&signature_name)
+            .ok_or_else(|| anyhow!("Release {} is not signed", release.tag_name))?;
+        let signature =
+            plugin_registry::download(&signature.browser_download_url, MAX_METADATA_SIZE)
+                .await
+                .context("Failed to download the signature")?;
+        let key = TrustedKey {
+            id: "update".to_string(),
+            name: "Release key".to_string(),
+            public_key: plugin_signing::normalize_public_key(public_key)?,
+            created_at: String::new(),
+        };
+        plugin_signing::verify_signature(
+            &data,
+            &String::from_utf8_lossy(&signature),
+            std::slice::from_ref(&key),
+        )?;
+    }
+    Ok(data)
+}
+
+/// Hash listed for `name` in a `sha256sum` style file
+fn find_checksum(checksums: &str, name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (hash, file) = line.trim().split_once(char::is_whitespace)?;
+        (file.trim().trim_start_matches('*') == name).then(|| hash.to_string())
+    })
+}
+
+/// Put `data` in place of `binary`, keeping the previous one as `<name>.old`
+async fn install(binary: &Path, data: &[u8]) -> Result<()> {
+    let staged = sibling(binary, ".new")?;
+    let backup = sibling(binary, ".old")?;
+
+    let written = async {
+        let _ = tokio::fs::remove_file(&staged).await;
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o755);
+        let mut file = options.open(&staged).await?;
+        file.write_all(data).await?;
+        file.sync_all().await
+    }
+    .await
+    .with_context(|| format!("Failed to write {}", staged.display()));
+    let result = match written {
+        Ok(()) => smoke_test(&staged).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&staged).await;
+        return Err(e);
+    }
+
+    let _ = tokio::fs::remove_file(&backup).await;
+    tokio::fs::hard_link(binary, &backup)
+        .await
+        .with_context(|| format!("Failed to keep the current binary as {}", backup.display()))?;
+    tokio::fs::rename(&staged, binary)
+        .await
+        .with_context(|| format!("Failed to replace {}", binary.display()))
+}
+
+/// The new binary must at least start on this machine
+async fn smoke_test(binary: &Path) -> Result<()> {
+    let mut attempts = 0;
+    let mut child = loop {
+        let spawned = Command::new(binary)
+            .arg("--help")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn();
+        match spawned {
+            // A process forked while the file was open for writing may still hold it
+            Err(e) if is_text_busy(&e) && attempts < 5 => {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            spawned => break spawned.context("The new binary doesn't start")?,
+        }
+    };
+    match tokio::time::timeout(SMOKE_TEST_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) if status.success() => Ok(()),
+        Ok(Ok(status)) => Err(anyhow!("The new binary exited with {}", status)),
+        Ok(Err(e)) => Err(anyhow!("The new binary doesn't start: {}", e)),
+        Err(_) => Err(anyhow!("The new binary didn't answer --help")),
+    }
+}
+
+/// Whether spawning failed because the file is still open for writing
+#[cfg(unix)]
+fn is_text_busy(e: &std::io::Error) -> bool {
+    e.raw_os_error() == Some(libc::ETXTBSY)
+}
+
+#[cfg(not(unix))]
+fn is_text_busy(_e: &std::io::Error) -> bool {
+    false
+}
+
+/// `<dir>/<name><suffix>` for the file at `path`
+fn sibling(path: &Path, suffix: &str) -> Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid binary path {}", path.display()))?;
+    let mut name = name.to_os_string();
+    name.push(suffix);
+    Ok(path.with_file_name(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_document() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "tag_name": "v0.3.0",
+                "html_url": "https://github.com/ToruAI/toru-steering-center/releases/tag/v0.3.0",
+                "assets": [
+                    { "name": "steering-center-x86_64-linux", "browser_download_url": "https://example.com/a", "size": 1 },
+                    { "name": "SHA256SUMS", "browser_download_url": "https://example.com/b", "size": 1 }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(release.version(), "0.3.0");
+        assert!(release.asset(CHECKSUMS_ASSET).is_some());
+        assert!(release.asset("steering-center-aarch64-linux").is_none());
+    }
+
+    #[test]
+    fn test_find_checksum() {
+        let sums = "\
+2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  steering-center-x86_64-linux
+486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7 *steering-center-aarch64-linux
+";
+        assert_eq!(
+            find_checksum(sums, "steering-center-aarch64-linux").as_deref(),
+            Some("486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7")
+        );
+        assert!(find_checksum(sums, "steering-center").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_install_keeps_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("steering-center");
+        std::fs::write(&binary, b"old").unwrap();
+
+        // A "binary" that can't run is refused and the current one stays
+        assert!(install(&binary, b"not a program").await.is_err());
+        assert_eq!(std::fs::read(&binary).unwrap(), b"old");
+        assert!(!sibling(&binary, ".new").unwrap().exists());
+
+        install(&binary, b"#!/bin/sh\nexit 0\n").await.unwrap();
+        assert_eq!(std::fs::read(&binary).unwrap(), b"#!/bin/sh\nexit 0\n");
+        assert_eq!(
+            std::fs::read(sibling(&binary, ".old").unwrap()).unwrap(),
+            b"old"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Temperatures, fans, GPUs and batteries, for the resources dashboard
+//!
+//! Temperatures come from `sysinfo::Components`. Fans, batteries and AMD GPUs
+//! are read from sysfs, so they are only found on Linux. NVIDIA GPUs are read through
+//! NVML, which is loaded from the driver at runtime; without the driver there
+//! are simply no NVIDIA GPUs.
+
+use nvml_wrapper::enum_wrappers::device::TemperatureSensor as NvmlSensor;
+use nvml_wrapper::Nvml;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use sysinfo::Components;
+
+use super::system::{BatteryInfo, FanSensor, GpuInfo, TemperatureSensor};
+
+/// PCI vendor ID of AMD GPUs
+const AMD_VENDOR_ID: &str = "0x1002";
+
+pub fn read_temperatures() -> Vec<TemperatureSensor> {
+    let components = Components::new_with_refreshed_list();
+    components
+        .iter()
+        .filter(|component| component.temperature().is_finite())
+        .map(|component| TemperatureSensor {
+            label: component.label().to_string(),
+            temperature_celsius: component.temperature(),
+            max_celsius: Some(component.max()).filter(|max| max.is_finite() && *max > 0.0),
+            critical_celsius: component.critical(),
+        })
+        .collect()
+}
+
+pub fn read_fans() -> Vec<FanSensor> {
+    read_fans_from(Path::new("/sys/class/hwmon"))
+}
+
+/// Fans of every hwmon device under `hwmon_dir`
+///
+/// A fan without a `fanN_label` is named after its device, e.g. `nct6775 fan 2`.
+fn read_fans_from(hwmon_dir: &Path) -> Vec<FanSensor> {
+    let mut fans = Vec::new();
+    for device in sorted_entries(hwmon_dir) {
+        let device_name = read_trimmed(&device.join("name")).unwrap_or_default();
+        for entry in sorted_entries(&device) {
+            let Some(file_name) = entry.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some(index) = file_name
+                .strip_prefix("fan")
+                .and_then(|rest| rest.strip_suffix("_input"))
+            else {
+                continue;
+            };
+            let Some(rpm) = read_trimmed(&entry).and_then(|rpm| rpm.parse().ok()) else {
+                continue;
+            };
+            let label = read_trimmed(&device.join(format!("fan{}_label", index)))
+                .unwrap_or_else(|| format!("{} fan {}", device_name, index).trim().to_string());
+            fans.push(FanSensor { label, rpm });
+        }
+    }
+    fans
+}
+
+pub fn read_gpus() -> Vec<GpuInfo> {
+    let mut gpus = read_nvidia_gpus();
+    gpus.extend(read_amd_gpus_from(Path::new("/sys/class/drm")));
+    gpus
+}
+
+/// NVML, loaded on first use; `None` when there is no NVIDIA driver
+fn nvml() -> Option<&'static Nvml> {
+    static NVML: OnceLock<Option<Nvml>> = OnceLock::new();
+    NVML.get_or_init(|| match Nvml::init() {
+        Ok(nvml) => Some(nvml),
+        Err(e) => {
+            tracing::debug!("NVML not available, skipping NVIDIA GPUs: {}", e);
+            None
+        }
+    })
+    .as_ref()
+}
+
+fn read_nvidia_gpus() -> Vec<GpuInfo> {
+    let Some(nvml) = nvml() else {
+        return Vec::new();
+    };
+    let count = nvml.device_count().unwrap_or(0);
+    (0..count)
+        .filter_map(|index| nvml.device_by_index(index).ok())
+        .map(|device| {
+            let memory = device.memory_info().ok();
+            GpuInfo {
+                name: device.name().unwrap_or_else(|_| "NVIDIA GPU".to_string()),
+                vendor: "nvidia".to_string(),
+                utilization_percent: device
+                    .utilization_rates()
+                    .ok()
+                    .map(|rates| rates.gpu as f32),
+                memory_used: memory.as_ref().map(|memory| memory.used),
+                memory_total: memory.as_ref().map(|memory| memory.total),
+                temperature_celsius: device
+                    .temperature(NvmlSensor::Gpu)
+                    .ok()
+                    .map(|celsius| celsius as f32),
+            }
+        })
+        .collect()
+}
+
+/// AMD GPUs under `drm_dir`, read from the amdgpu driver's sysfs files
+fn read_amd_gpus_from(drm_dir: &Path) -> Vec<GpuInfo> {
+    sorted_entries(drm_dir)
+        .into_iter()
+        .filter(|card| {
+            // `card0` is the GPU; `card0-DP-1` and the like are its outputs
+            card.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("card") && !name.contains('-'))
+        })
+        .map(|card| card.join("device"))
+        .filter(|device| read_trimmed(&device.join("vendor")).as_deref() == Some(AMD_VENDOR_ID))
+        .map(|device| {
+            let temperature_celsius = sorted_entries(&device.join("hwmon"))
+                .iter()
+                .find_map(|hwmon| read_number(&hwmon.join("temp1_input")))
+                .map(|millidegrees| millidegrees as f32 / 1000.0);
+            GpuInfo {
+                name: read_trimmed(&device.join("product_name"))
+                    .unwrap_or_else(|| "AMD GPU".to_string()),
+                vendor: "amd".to_string(),
+                utilization_percent: read_number(&device.join("gpu_busy_percent"))
+                    .map(|percent| percent as f32),
+                memory_used: read_number(&device.join("mem_info_vram_used")),
+                memory_total: read_number(&device.join("mem_info_vram_total")),
+                temperature_celsius,
+            }
+        })
+        .collect()
+}
+
+pub fn read_batteries() -> Vec<BatteryInfo> {
+    read_batteries_from(Path::new("/sys/class/power_supply"))
+}
+
+/// Batteries powering the machine under `power_supply_dir`
+///
+/// Batteries of peripherals, such as a wireless mouse, are left out.
+fn read_batteries_from(power_supply_dir: &Path) -> Vec<BatteryInfo> {
+    sorted_entries(power_supply_dir)
+        .into_iter()
+        .filter(|supply| read_trimmed(&supply.join("type")).as_deref() == Some("Battery"))
+        .filter(|supply| read_trimmed(&supply.join("scope")).as_deref() != Some("Device"))
+        .map(|supply| {
+            // Some drivers only report energy (µWh) or charge (µAh) levels
+            let percent = read_number(&supply.join("capacity"))
+                .map(|percent| percent as f32)
+                .or_else(|| {
+                    ["energy", "charge"].iter().find_map(|level| {
+                        let now = read_number(&supply.join(format!("{}_now", level)))?;
+                        let full = read_number(&supply.join(format!("{}_full", level)))
+                            .filter(|full| *full > 0)?;
+                        Some((now as f32 / full as f32 * 100.0).min(100.0))
+                    })
+                });
+            BatteryInfo {
+                name: supply
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                percent,
+                status: read_trimmed(&supply.join("status"))
+                    .unwrap_or_else(|| "Unknown".to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Entries of a directory sorted by name; empty if it can't be read
+fn sorted_entries(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+}
+
+fn read_number(path: &Path) -> Option<u64> {
+    read_trimmed(path).and_then(|content| content.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_read_fans() {
+        let dir = tempfile::tempdir().unwrap();
+        let hwmon = dir.path().join("hwmon1");
+        write(&hwmon.join("name"), "nct6775\n");
+        write(&hwmon.join("fan1_input"), "1200\n");
+        write(&hwmon.join("fan1_label"), "CPU Fan\n");
+        write(&hwmon.join("fan2_input"), "800\n");
+        write(&hwmon.join("temp1_input"), "45000\n");
+
+        let fans = read_fans_from(dir.path());
+        let fans: Vec<(&str, u64)> = fans.iter().map(|f| (f.label.as_str(), f.rpm)).collect();
+        assert_eq!(fans, [("CPU Fan", 1200), ("nct6775 fan 2", 800)]);
+    }
+
+    #[test]
+    fn test_read_amd_gpus() {
+        let dir = tempfile::tempdir().unwrap();
+        let amd = dir.path().join("card0/device");
+        write(&amd.join("vendor"), "0x1002\n");
+        write(&amd.join("gpu_busy_percent"), "37\n");
+        write(&amd.join("mem_info_vram_used"), "1073741824\n");
+        write(&amd.join("mem_info_vram_total"), "8589934592\n");
+        write(&amd.join("hwmon/hwmon3/temp1_input"), "52000\n");
+        write(&dir.path().join("card0-DP-1/status"), "connected\n");
+        write(&dir.path().join("card1/device/vendor"), "0x8086\n");
+
+        let gpus = read_amd_gpus_from(dir.path());
+        assert_eq!(gpus.len(), 1);
+        assert_eq!(gpus[0].name, "AMD GPU");
+        assert_eq!(gpus[0].utilization_percent, Some(37.0));
+        assert_eq!(gpus[0].memory_total, Some(8589934592));
+        assert_eq!(gpus[0].temperature_celsius, Some(52.0));
+    }
+
+    #[test]
+    fn test_read_batteries() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("AC/type"), "Mains\n");
+        write(&dir.path().join("BAT0/type"), "Battery\n");
+        write(&dir.path().join("BAT0/capacity"), "87\n");
+        write(&dir.path().join("BAT0/status"), "Discharging\n");
+        write(&dir.path().join("BAT1/type"), "Battery\n");
+        write(&dir.path().join("BAT1/energy_now"), "25000000\n");
+        write(&dir.path().join("BAT1/energy_full"), "50000000\n");
+        write(&dir.path().join("hidpp_battery_0/type"), "Battery\n");
+        write(&dir.path().join("hidpp_battery_0/scope"), "Device\n");
+
+        let batteries = read_batteries_from(dir.path());
+        assert_eq!(batteries.len(), 2);
+        assert_eq!(batteries[0].name, "BAT0");
+        assert_eq!(batteries[0].percent, Some(87.0));
+        assert_eq!(batteries[0].status, "Discharging");
+        assert_eq!(batteries[1].percent, Some(50.0));
+        assert_eq!(batteries[1].status, "Unknown");
+    }
+}
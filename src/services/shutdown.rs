@@ -0,0 +1,148 @@
+//! Graceful shutdown on SIGTERM and SIGINT
+//!
+//! On a signal the server stops accepting connections and gives open requests
+//! `CONNECTION_DRAIN_TIMEOUT` to finish. Running tasks are then waited for, up to
+//! the `shutdown_task_timeout_secs` setting, and cancelled after it, plugins are
+//! sent the shutdown message, and the database log is checkpointed before exiting.
+//!
+//! A restart (after a self-update) goes through the same steps, then starts the
+//! binary again in place of the process.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{watch, Mutex};
+
+use crate::db::{self, DbPool};
+use crate::services::executor;
+use crate::services::plugins::PluginSupervisor;
+
+/// Setting with the seconds running tasks get to finish on shutdown
+pub const TASK_TIMEOUT_SETTING: &str = "shutdown_task_timeout_secs";
+
+const DEFAULT_TASK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time open connections (WebSockets included) get to close after a signal
+pub const CONNECTION_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sender of the signal, for shutting down from within (restarts)
+static SHUTDOWN_TX: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+static RESTART_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Resolves once a shutdown signal was received; cheap to clone
+#[derive(Clone)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    pub async fn received(mut self) {
+        let _ = self.0.wait_for(|received| *received).await;
+    }
+}
+
+/// Listen for SIGTERM and SIGINT (Ctrl-C)
+pub fn listen() -> ShutdownSignal {
+    let (tx, rx) = watch::channel(false);
+    let _ = SHUTDOWN_TX.set(tx.clone());
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        let _ = tx.send(true);
+    });
+    ShutdownSignal(rx)
+}
+
+/// Shut down like on SIGTERM, then start again
+pub fn request_restart() {
+    tracing::info!("Restarting");
+    RESTART_REQUESTED.store(true, Ordering::SeqCst);
+    if let Some(tx) = SHUTDOWN_TX.get() {
+        let _ = tx.send(true);
+    }
+}
+
+/// Whether the process should start again once drained
+pub fn restart_requested() -> bool {
+    RESTART_REQUESTED.load(Ordering::SeqCst)
+}
+
+async fn wait_for_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received SIGINT, shutting down"),
+        _ = terminate => tracing::info!("Received SIGTERM, shutting down"),
+    }
+}
+
+/// Run `server` until it stops, or until `CONNECTION_DRAIN_TIMEOUT` after the signal
+///
+/// Graceful shutdown of the server waits for every connection to close, which
+/// open WebSockets never do by themselves.
+pub async fn serve_until_drained<F>(server: F, signal: ShutdownSignal) -> std::io::Result<()>
+where
+    F: std::future::IntoFuture<Output = std::io::Result<()>>,
+{
+    tokio::select! {
+        result = server.into_future() => result,
+        _ = async {
+            signal.received().await;
+            tokio::time::sleep(CONNECTION_DRAIN_TIMEOUT).await;
+        } => {
+            tracing::warn!("Connections still open after {:?}, closing them", CONNECTION_DRAIN_TIMEOUT);
+            Ok(())
+        }
+    }
+}
+
+/// Drain tasks, stop plugins and flush the database, once the server has stopped
+pub async fn drain(db: &DbPool, supervisor: Option<&Arc<Mutex<PluginSupervisor>>>) {
+    let task_timeout = db::get_setting(db, TASK_TIMEOUT_SETTING)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TASK_TIMEOUT);
+
+    let running = executor::running_task_count();
+    if running > 0 {
+        tracing::info!(
+            "Waiting up to {:?} for {} running tasks",
+            task_timeout,
+            running
+        );
+    }
+    let cancelled = executor::drain_tasks(task_timeout).await;
+    if cancelled > 0 {
+        tracing::warn!("Cancelled {} tasks still running at shutdown", cancelled);
+    }
+
+    if let Some(supervisor) = supervisor {
+        supervisor.lock().await.shutdown_all().await;
+    }
+
+    if let Err(e) = db::checkpoint(db).await {
+        tracing::warn!("Failed to checkpoint database: {}", e);
+    }
+    tracing::info!("Shutdown complete");
+}
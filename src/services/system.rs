@@ -1,49 +1,256 @@
-use serde::{Deserialize, Serialize};
+//! Host resource snapshots for the dashboard, metrics, WebSocket and plugins
+//!
+//! A sampler task refreshes each category of `SystemResources` on its own
+//! interval and keeps the latest snapshot in a `ResourceCache`, so requests are
+//! served without touching the system. Intervals are read from the
+//! `resource_interval_*_secs` settings.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use sysinfo::{Disks, Networks, System};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+// Shared with plugins, which receive the same snapshot in `system_info_result` messages
+pub use toru_plugin_api::system::{
+    BatteryInfo, CpuCore, DiskInfo, FanSensor, GpuInfo, NetworkInterface, SystemResources,
+    TemperatureSensor,
+};
+
+use super::sensors;
+use crate::db::{self, DbPool};
+
+/// How often the sampler looks whether a category is due
+const SAMPLER_TICK: Duration = Duration::from_secs(1);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CpuCore {
-    pub name: String,
-    pub usage: f32,
+/// How often the sampler re-reads its interval settings
+const SETTINGS_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Longest interval a setting may ask for
+const MAX_INTERVAL_SECS: u64 = 3600;
+
+/// A group of resources refreshed together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceCategory {
+    /// CPU, memory, swap, uptime and process count
+    Cpu,
+    Network,
+    Disks,
+    /// Temperatures, fans, GPUs and batteries
+    Sensors,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DiskInfo {
-    pub name: String,
-    pub mount_point: String,
-    pub total_space: u64,
-    pub available_space: u64,
-    pub used_space: u64,
-    pub usage_percent: f32,
+impl ResourceCategory {
+    pub const ALL: [ResourceCategory; 4] = [
+        ResourceCategory::Cpu,
+        ResourceCategory::Network,
+        ResourceCategory::Disks,
+        ResourceCategory::Sensors,
+    ];
+
+    /// Setting with the seconds between refreshes of this category
+    pub fn setting(self) -> &'static str {
+        match self {
+            ResourceCategory::Cpu => "resource_interval_cpu_secs",
+            ResourceCategory::Network => "resource_interval_network_secs",
+            ResourceCategory::Disks => "resource_interval_disks_secs",
+            ResourceCategory::Sensors => "resource_interval_sensors_secs",
+        }
+    }
+
+    fn default_interval_secs(self) -> u64 {
+        match self {
+            ResourceCategory::Cpu => 2,
+            ResourceCategory::Network => 5,
+            ResourceCategory::Disks => 30,
+            ResourceCategory::Sensors => 10,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NetworkInterface {
-    pub name: String,
-    pub received: u64,
-    pub transmitted: u64,
+/// The latest resource snapshot, kept up to date by the sampler task
+///
+/// Empty until a sampler is started with `spawn_resource_sampler`.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceCache(Arc<RwLock<Option<SystemResources>>>);
+
+impl ResourceCache {
+    pub fn snapshot(&self) -> Option<SystemResources> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut SystemResources)) {
+        let mut snapshot = self.0.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(resources) = snapshot.as_mut() {
+            f(resources);
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SystemResources {
-    pub cpu_percent: f32,
-    pub cpu_cores: Vec<CpuCore>,
-    pub memory_percent: f32,
-    pub memory_used: u64,
-    pub memory_total: u64,
-    pub swap_used: u64,
-    pub swap_total: u64,
-    pub uptime_seconds: u64,
-    pub disks: Vec<DiskInfo>,
-    pub network: Vec<NetworkInterface>,
-    pub process_count: usize,
-    pub system_name: Option<String>,
-    pub kernel_version: Option<String>,
-    pub os_version: Option<String>,
-    pub host_name: Option<String>,
+/// Take a first snapshot, then keep refreshing it in the background
+///
+/// `sys` is shared with the process explorer; the sampler only holds it while
+/// refreshing CPU and memory.
+pub async fn spawn_resource_sampler(db: DbPool, sys: Arc<Mutex<System>>) -> ResourceCache {
+    let mut disks = Disks::new_with_refreshed_list();
+    let mut networks = Networks::new_with_refreshed_list();
+
+    let cpu = {
+        let mut sys = sys.lock().await;
+        sample_cpu(&mut sys)
+    };
+    let disks_info = sample_disks(&mut disks);
+    let sensors = sample_sensors();
+
+    let cache = ResourceCache(Arc::new(RwLock::new(Some(SystemResources {
+        cpu_percent: cpu.cpu_percent,
+        cpu_cores: cpu.cpu_cores,
+        memory_percent: cpu.memory_percent,
+        memory_used: cpu.memory_used,
+        memory_total: cpu.memory_total,
+        swap_used: cpu.swap_used,
+        swap_total: cpu.swap_total,
+        uptime_seconds: cpu.uptime_seconds,
+        disks: disks_info,
+        network: sample_network(&mut networks),
+        process_count: cpu.process_count,
+        system_name: System::name(),
+        kernel_version: System::kernel_version(),
+        os_version: System::os_version(),
+        host_name: System::host_name(),
+        temperatures: sensors.temperatures,
+        fans: sensors.fans,
+        gpus: sensors.gpus,
+        batteries: sensors.batteries,
+    }))));
+
+    let sampler_cache = cache.clone();
+    tokio::spawn(async move {
+        let cache = sampler_cache;
+        let mut intervals = load_intervals(&db).await;
+        let mut settings_loaded = Instant::now();
+        let now = Instant::now();
+        let mut next_due = intervals.map(|interval| now + interval);
+        let mut disks = Some(disks);
+        let mut tick = tokio::time::interval(SAMPLER_TICK);
+
+        loop {
+            tick.tick().await;
+            let now = Instant::now();
+            if now - settings_loaded >= SETTINGS_RELOAD_INTERVAL {
+                intervals = load_intervals(&db).await;
+                settings_loaded = now;
+            }
+
+            for (index, category) in ResourceCategory::ALL.into_iter().enumerate() {
+                if now < next_due[index] {
+                    continue;
+                }
+                next_due[index] = now + intervals[index];
+                match category {
+                    ResourceCategory::Cpu => {
+                        let cpu = {
+                            let mut sys = sys.lock().await;
+                            sample_cpu(&mut sys)
+                        };
+                        cache.update(|resources| cpu.apply(resources));
+                    }
+                    ResourceCategory::Network => {
+                        let network = sample_network(&mut networks);
+                        cache.update(|resources| resources.network = network);
+                    }
+                    ResourceCategory::Disks => {
+                        // Mounts that don't answer (e.g. a stale NFS share) mustn't stall the runtime
+                        let Some(mut owned) = disks.take() else {
+                            continue;
+                        };
+                        match tokio::task::spawn_blocking(move || (sample_disks(&mut owned), owned))
+                            .await
+                        {
+                            Ok((info, owned)) => {
+                                disks = Some(owned);
+                                cache.update(|resources| resources.disks = info);
+                            }
+                            Err(e) => {
+                                tracing::warn!("Failed to refresh disks: {}", e);
+                                disks = Some(Disks::new_with_refreshed_list());
+                            }
+                        }
+                    }
+                    ResourceCategory::Sensors => {
+                        match tokio::task::spawn_blocking(sample_sensors).await {
+                            Ok(sensors) => cache.update(|resources| sensors.apply(resources)),
+                            Err(e) => tracing::warn!("Failed to read sensors: {}", e),
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    cache
 }
 
-pub fn get_system_resources(sys: &mut System) -> SystemResources {
+/// Interval of each category, in the order of `ResourceCategory::ALL`
+async fn load_intervals(db: &DbPool) -> [Duration; 4] {
+    let mut intervals = [Duration::ZERO; 4];
+    for (interval, category) in intervals.iter_mut().zip(ResourceCategory::ALL) {
+        let secs = db::get_setting(db, category.setting())
+            .await
+            .ok()
+            .flatten()
+            .and_then(|secs| secs.trim().parse::<u64>().ok())
+            .unwrap_or_else(|| category.default_interval_secs());
+        *interval = Duration::from_secs(secs.clamp(1, MAX_INTERVAL_SECS));
+    }
+    intervals
+}
+
+/// The `ResourceCategory::Cpu` part of a snapshot
+struct CpuSample {
+    cpu_percent: f32,
+    cpu_cores: Vec<CpuCore>,
+    memory_percent: f32,
+    memory_used: u64,
+    memory_total: u64,
+    swap_used: u64,
+    swap_total: u64,
+    uptime_seconds: u64,
+    process_count: usize,
+}
+
+impl CpuSample {
+    fn apply(self, resources: &mut SystemResources) {
+        resources.cpu_percent = self.cpu_percent;
+        resources.cpu_cores = self.cpu_cores;
+        resources.memory_percent = self.memory_percent;
+        resources.memory_used = self.memory_used;
+        resources.memory_total = self.memory_total;
+        resources.swap_used = self.swap_used;
+        resources.swap_total = self.swap_total;
+        resources.uptime_seconds = self.uptime_seconds;
+        resources.process_count = self.process_count;
+    }
+}
+
+/// The `ResourceCategory::Sensors` part of a snapshot
+struct Sensors {
+    temperatures: Vec<TemperatureSensor>,
+    fans: Vec<FanSensor>,
+    gpus: Vec<GpuInfo>,
+    batteries: Vec<BatteryInfo>,
+}
+
+impl Sensors {
+    fn apply(self, resources: &mut SystemResources) {
+        resources.temperatures = self.temperatures;
+        resources.fans = self.fans;
+        resources.gpus = self.gpus;
+        resources.batteries = self.batteries;
+    }
+}
+
+fn sample_cpu(sys: &mut System) -> CpuSample {
     sys.refresh_cpu_usage();
     sys.refresh_memory();
     sys.refresh_processes();
@@ -75,12 +282,23 @@ pub fn get_system_resources(sys: &mut System) -> SystemResources {
         0.0
     };
 
-    let swap_total = sys.total_swap();
-    let swap_used = sys.used_swap();
+    CpuSample {
+        cpu_percent,
+        cpu_cores,
+        memory_percent,
+        memory_used,
+        memory_total,
+        swap_used: sys.used_swap(),
+        swap_total: sys.total_swap(),
+        uptime_seconds: System::uptime(),
+        process_count: sys.processes().len(),
+    }
+}
 
-    // Disk info
-    let disks = Disks::new_with_refreshed_list();
-    let disk_info: Vec<DiskInfo> = disks
+fn sample_disks(disks: &mut Disks) -> Vec<DiskInfo> {
+    // Picks up mounts added or removed since the last refresh
+    disks.refresh_list();
+    disks
         .iter()
         .map(|disk| {
             let total = disk.total_space();
@@ -101,37 +319,26 @@ pub fn get_system_resources(sys: &mut System) -> SystemResources {
                 usage_percent,
             }
         })
-        .collect();
+        .collect()
+}
 
-    // Network info
-    let networks = Networks::new_with_refreshed_list();
-    let network_info: Vec<NetworkInterface> = networks
+fn sample_network(networks: &mut Networks) -> Vec<NetworkInterface> {
+    networks.refresh_list();
+    networks
         .iter()
         .map(|(name, data)| NetworkInterface {
             name: name.clone(),
             received: data.total_received(),
             transmitted: data.total_transmitted(),
         })
-        .collect();
-
-    let uptime_seconds = System::uptime();
-    let process_count = sys.processes().len();
+        .collect()
+}
 
-    SystemResources {
-        cpu_percent,
-        cpu_cores,
-        memory_percent,
-        memory_used,
-        memory_total,
-        swap_used,
-        swap_total,
-        uptime_seconds,
-        disks: disk_info,
-        network: network_info,
-        process_count,
-        system_name: System::name(),
-        kernel_version: System::kernel_version(),
-        os_version: System::os_version(),
-        host_name: System::host_name(),
+fn sample_sensors() -> Sensors {
+    Sensors {
+        temperatures: sensors::read_temperatures(),
+        fans: sensors::read_fans(),
+        gpus: sensors::read_gpus(),
+        batteries: sensors::read_batteries(),
     }
 }
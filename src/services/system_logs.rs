@@ -0,0 +1,336 @@
+//! The host's system log, for the system log viewer
+//!
+//! Entries are read from journald through `journalctl`, or from a plain log file
+//! when the `system_log_file` setting names one. Both are returned newest first
+//! and paged with an opaque cursor.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::db::{self, DbPool};
+
+/// Setting naming a log file to read instead of journald
+pub const SYSTEM_LOG_FILE_SETTING: &str = "system_log_file";
+
+/// Entries returned when the request doesn't ask for a page size
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Most entries returned at once
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// How long `journalctl` may take before the request fails
+const JOURNALCTL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Syslog priority names, indexed by level
+const PRIORITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// Errors returned when reading the system log
+#[derive(Debug)]
+pub enum SystemLogError {
+    InvalidQuery(String),
+    /// Neither journald nor a log file can be read
+    Unavailable(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for SystemLogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SystemLogError::InvalidQuery(e) => write!(f, "Invalid query: {}", e),
+            SystemLogError::Unavailable(e) => write!(f, "System log unavailable: {}", e),
+            SystemLogError::Io(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SystemLogError {}
+
+/// Filters and paging of a system log request
+///
+/// `unit`, `priority`, `since` and `until` only apply to journald.
+#[derive(Debug, Default, Deserialize)]
+pub struct SystemLogQuery {
+    /// systemd unit, e.g. `nginx.service`
+    pub unit: Option<String>,
+    /// Most verbose priority to include, as a number (0-7) or name (`err`)
+    pub priority: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemLogEntry {
+    pub timestamp: Option<DateTime<Utc>>,
+    pub unit: Option<String>,
+    /// Program that wrote the entry, e.g. `sshd`
+    pub identifier: Option<String>,
+    /// Syslog priority, 0 (emerg) to 7 (debug)
+    pub priority: Option<u8>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemLogPage {
+    /// `journald` or the path of the log file
+    pub source: String,
+    /// Newest first
+    pub entries: Vec<SystemLogEntry>,
+    /// Pass as `cursor` to get older entries; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Read a page of the system log
+pub async fn read_system_log(
+    db: &DbPool,
+    query: &SystemLogQuery,
+) -> Result<SystemLogPage, SystemLogError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let log_file = db::get_setting(db, SYSTEM_LOG_FILE_SETTING)
+        .await
+        .ok()
+        .flatten()
+        .filter(|path| !path.trim().is_empty());
+
+    match log_file {
+        Some(path) => {
+            let skip = match &query.cursor {
+                Some(cursor) => cursor
+                    .parse()
+                    .map_err(|_| SystemLogError::InvalidQuery("Invalid cursor".to_string()))?,
+                None => 0,
+            };
+            let path_for_read = path.clone();
+            let lines = tokio::task::spawn_blocking(move || {
+                tail_lines(Path::new(&path_for_read), skip, limit)
+            })
+            .await
+            .map_err(|e| SystemLogError::Unavailable(e.to_string()))??;
+            let next_cursor = (lines.len() == limit).then(|| (skip + limit).to_string());
+            Ok(SystemLogPage {
+                source: path,
+                entries: lines
+                    .into_iter()
+                    .map(|message| SystemLogEntry {
+                        timestamp: None,
+                        unit: None,
+                        identifier: None,
+                        priority: None,
+                        message,
+                    })
+                    .collect(),
+                next_cursor,
+            })
+        }
+        None => read_journal(query, limit).await,
+    }
+}
+
+async fn read_journal(
+    query: &SystemLogQuery,
+    limit: usize,
+) -> Result<SystemLogPage, SystemLogError> {
+    let mut command = Command::new("journalctl");
+    command
+        .args(["--output=json", "--reverse", "--no-pager", "--quiet"])
+        .arg(format!("--lines={}", limit));
+    if let Some(unit) = &query.unit {
+        if !is_valid_unit(unit) {
+            return Err(SystemLogError::InvalidQuery(format!(
+                "Invalid unit name: {}",
+                unit
+            )));
+        }
+        command.arg(format!("--unit={}", unit));
+    }
+    if let Some(priority) = &query.priority {
+        command.arg(format!("--priority={}", parse_priority(priority)?));
+    }
+    if let Some(since) = query.since {
+        command.arg(format!("--since=@{}", since.timestamp()));
+    }
+    if let Some(until) = query.until {
+        command.arg(format!("--until=@{}", until.timestamp()));
+    }
+    if let Some(cursor) = &query.cursor {
+        // With --reverse this continues with older entries
+        command.arg(format!("--after-cursor={}", cursor));
+    }
+
+    let output = tokio::time::timeout(JOURNALCTL_TIMEOUT, command.output())
+        .await
+        .map_err(|_| SystemLogError::Unavailable("journalctl timed out".to_string()))?
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => SystemLogError::Unavailable(format!(
+                "journalctl not found; set {} to read a log file instead",
+                SYSTEM_LOG_FILE_SETTING
+            )),
+            _ => SystemLogError::Io(e),
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(SystemLogError::Unavailable(stderr.trim().to_string()));
+    }
+
+    let mut entries = Vec::new();
+    let mut last_cursor = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Ok(fields) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        last_cursor = fields["__CURSOR"].as_str().map(String::from);
+        entries.push(parse_journal_entry(&fields));
+    }
+    let next_cursor = if entries.len() == limit {
+        last_cursor
+    } else {
+        None
+    };
+    Ok(SystemLogPage {
+        source: "journald".to_string(),
+        entries,
+        next_cursor,
+    })
+}
+
+/// An entry from `journalctl --output=json`
+fn parse_journal_entry(fields: &serde_json::Value) -> SystemLogEntry {
+    let text = |name: &str| fields[name].as_str().map(String::from);
+    SystemLogEntry {
+        timestamp: text("__REALTIME_TIMESTAMP")
+            .and_then(|micros| micros.parse::<i64>().ok())
+            .and_then(DateTime::from_timestamp_micros),
+        unit: text("_SYSTEMD_UNIT"),
+        identifier: text("SYSLOG_IDENTIFIER"),
+        priority: text("PRIORITY").and_then(|priority| priority.parse().ok()),
+        message: match &fields["MESSAGE"] {
+            serde_json::Value::String(message) => message.clone(),
+            // Messages that aren't valid UTF-8 are written as an array of bytes
+            serde_json::Value::Array(bytes) => {
+                let bytes: Vec<u8> = bytes
+                    .iter()
+                    .filter_map(|byte| byte.as_u64().map(|byte| byte as u8))
+                    .collect();
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+            _ => String::new(),
+        },
+    }
+}
+
+/// Priority level for a number (`3`) or name (`err`, `error`, `warn`)
+fn parse_priority(priority: &str) -> Result<u8, SystemLogError> {
+    let priority = priority.trim().to_lowercase();
+    if let Ok(level) = priority.parse::<u8>() {
+        if (level as usize) < PRIORITIES.len() {
+            return Ok(level);
+        }
+    }
+    let name = match priority.as_str() {
+        "error" => "err",
+        "warn" => "warning",
+        "emergency" => "emerg",
+        "critical" => "crit",
+        name => name,
+    };
+    PRIORITIES
+        .iter()
+        .position(|known| *known == name)
+        .map(|level| level as u8)
+        .ok_or_else(|| SystemLogError::InvalidQuery(format!("Unknown priority: {}", priority)))
+}
+
+/// Unit names are passed to journalctl, so keep them to the characters systemd allows
+fn is_valid_unit(unit: &str) -> bool {
+    !unit.is_empty()
+        && !unit.starts_with('-')
+        && unit
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '@' | '.' | '_' | ':' | '-' | '\\'))
+}
+
+/// Up to `limit` lines of a file, newest first, after skipping the `skip` newest
+fn tail_lines(path: &Path, skip: usize, limit: usize) -> Result<Vec<String>, SystemLogError> {
+    let file = std::fs::File::open(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => {
+            SystemLogError::Unavailable(format!("{} not found", path.display()))
+        }
+        _ => SystemLogError::Io(e),
+    })?;
+
+    let keep = skip + limit;
+    let mut tail: VecDeque<String> = VecDeque::with_capacity(keep);
+    for line in std::io::BufReader::new(file).split(b'\n') {
+        let line = line.map_err(SystemLogError::Io)?;
+        if tail.len() == keep {
+            tail.pop_front();
+        }
+        tail.push_back(String::from_utf8_lossy(&line).trim_end().to_string());
+    }
+    Ok(tail.into_iter().rev().skip(skip).take(limit).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_journal_entry() {
+        let fields = serde_json::json!({
+            "__CURSOR": "s=abc;i=1",
+            "__REALTIME_TIMESTAMP": "1735560000000000",
+            "_SYSTEMD_UNIT": "nginx.service",
+            "SYSLOG_IDENTIFIER": "nginx",
+            "PRIORITY": "3",
+            "MESSAGE": [104, 105],
+        });
+        let entry = parse_journal_entry(&fields);
+        assert_eq!(entry.unit.as_deref(), Some("nginx.service"));
+        assert_eq!(entry.priority, Some(3));
+        assert_eq!(entry.message, "hi");
+        assert_eq!(
+            entry.timestamp.unwrap().to_rfc3339(),
+            "2024-12-30T12:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_parse_priority() {
+        assert_eq!(parse_priority("3").unwrap(), 3);
+        assert_eq!(parse_priority("WARNING").unwrap(), 4);
+        assert_eq!(parse_priority("error").unwrap(), 3);
+        assert!(parse_priority("8").is_err());
+        assert!(parse_priority("loud").is_err());
+    }
+
+    #[test]
+    fn test_valid_unit() {
+        assert!(is_valid_unit("nginx.service"));
+        assert!(is_valid_unit("getty@tty1.service"));
+        assert!(!is_valid_unit("--since=yesterday"));
+        assert!(!is_valid_unit("a b"));
+    }
+
+    #[test]
+    fn test_tail_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("syslog");
+        std::fs::write(&path, "one\ntwo\nthree\nfour\nfive\n").unwrap();
+
+        assert_eq!(tail_lines(&path, 0, 2).unwrap(), ["five", "four"]);
+        assert_eq!(tail_lines(&path, 2, 2).unwrap(), ["three", "two"]);
+        assert_eq!(tail_lines(&path, 4, 2).unwrap(), ["one"]);
+    }
+}
@@ -0,0 +1,151 @@
+//! System notifications pushed to connected WebSocket sessions
+//!
+//! Everything sent through `notifications::notify` (alerts, failed tasks,
+//! plugin crashes, security events) and new sign-ins are broadcast to the
+//! WebSocket sessions whose role may see them, so the UI learns about problems
+//! without polling.
+
+use serde::Serialize;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::broadcast;
+
+use crate::db::UserRole;
+use crate::services::event_bus::BROWSER_EVENT_BUFFER;
+use crate::services::notifications::{Notification, NotificationKind, NotificationLevel};
+
+/// Hub `notifications::notify` broadcasts through, once the server runs
+static HUB: OnceLock<Arc<NotificationHub>> = OnceLock::new();
+
+/// Who is shown a notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Audience {
+    Everyone,
+    Admins,
+}
+
+impl Audience {
+    pub fn includes(self, role: UserRole) -> bool {
+        self == Audience::Everyone || role == UserRole::Admin
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemNotification {
+    pub kind: NotificationKind,
+    pub level: NotificationLevel,
+    pub title: String,
+    pub message: String,
+    pub timestamp: String,
+    #[serde(skip)]
+    pub audience: Audience,
+}
+
+impl SystemNotification {
+    pub fn new(
+        kind: NotificationKind,
+        level: NotificationLevel,
+        title: impl Into<String>,
+        message: impl Into<String>,
+        audience: Audience,
+    ) -> Self {
+        Self {
+            kind,
+            level,
+            title: title.into(),
+            message: message.into(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            audience,
+        }
+    }
+}
+
+impl From<&Notification> for SystemNotification {
+    /// Alerts concern everyone; task, plugin and security details only admins
+    fn from(notification: &Notification) -> Self {
+        let audience = match notification.kind {
+            NotificationKind::Alert => Audience::Everyone,
+            _ => Audience::Admins,
+        };
+        Self::new(
+            notification.kind,
+            notification.level,
+            notification.title.clone(),
+            notification.message.clone(),
+            audience,
+        )
+    }
+}
+
+/// Broadcasts system notifications to every WebSocket session
+pub struct NotificationHub {
+    tx: broadcast::Sender<SystemNotification>,
+}
+
+impl Default for NotificationHub {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(BROWSER_EVENT_BUFFER).0,
+        }
+    }
+}
+
+impl NotificationHub {
+    pub fn subscribe(&self) -> broadcast::Receiver<SystemNotification> {
+        self.tx.subscribe()
+    }
+
+    pub fn publish(&self, notification: SystemNotification) {
+        // No receivers just means no client is connected
+        let _ = self.tx.send(notification);
+    }
+}
+
+/// Make `hub` the one `notifications::notify` broadcasts through
+pub fn install(hub: Arc<NotificationHub>) {
+    if HUB.set(hub).is_err() {
+        tracing::warn!("Notification hub is already installed");
+    }
+}
+
+/// Broadcast a notification; does nothing until a hub is installed
+pub fn publish(notification: SystemNotification) {
+    if let Some(hub) = HUB.get() {
+        hub.publish(notification);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audience_by_kind() {
+        let alert = Notification::new(
+            NotificationKind::Alert,
+            NotificationLevel::Warning,
+            "Power alert",
+            "UPS on battery",
+        );
+        let alert = SystemNotification::from(&alert);
+        assert!(alert.audience.includes(UserRole::Client));
+
+        let crash = Notification::new(
+            NotificationKind::PluginCrash,
+            NotificationLevel::Error,
+            "Plugin weather crashed",
+            "Plugin weather crashed and is being restarted.",
+        );
+        let crash = SystemNotification::from(&crash);
+        assert!(!crash.audience.includes(UserRole::Client));
+        assert!(crash.audience.includes(UserRole::Admin));
+
+        let hub = NotificationHub::default();
+        let mut rx = hub.subscribe();
+        hub.publish(crash);
+        let received = rx.try_recv().unwrap();
+        let json = serde_json::to_value(&received).unwrap();
+        assert_eq!(json["kind"], "plugin_crash");
+        assert_eq!(json["level"], "error");
+        assert!(json.get("audience").is_none());
+    }
+}
@@ -0,0 +1,365 @@
+//! `steering-center install-service`
+//!
+//! Installs the running binary as a hardened systemd service: a dedicated
+//! system user, a state directory it may write to (database, logs, plugins),
+//! an environment file for the admin credentials and the unit itself.
+
+use anyhow::{bail, Context, Result};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use std::fmt::Write as _;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+const DEFAULT_USER: &str = "steering";
+const DEFAULT_STATE_DIR: &str = "/var/lib/steering-center";
+const DEFAULT_ENV_FILE: &str = "/etc/steering-center/env";
+const DEFAULT_UNIT_PATH: &str = "/etc/systemd/system/steering-center.service";
+
+/// Length of the admin password generated for a new environment file
+const PASSWORD_LENGTH: usize = 24;
+
+/// Options of `install-service`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallOptions {
+    pub binary: PathBuf,
+    pub user: String,
+    pub state_dir: PathBuf,
+    pub env_file: PathBuf,
+    pub config: Option<PathBuf>,
+    pub unit_path: PathBuf,
+    /// Enable and start the service once installed
+    pub enable: bool,
+    /// Only print the unit
+    pub print: bool,
+}
+
+impl InstallOptions {
+    /// Parse the arguments after `install-service`
+    pub fn parse(binary: PathBuf, args: &[String]) -> Result<Self> {
+        let mut options = Self {
+            binary,
+            user: DEFAULT_USER.to_string(),
+            state_dir: PathBuf::from(DEFAULT_STATE_DIR),
+            env_file: PathBuf::from(DEFAULT_ENV_FILE),
+            config: None,
+            unit_path: PathBuf::from(DEFAULT_UNIT_PATH),
+            enable: false,
+            print: false,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            match flag {
+                "--enable" => options.enable = true,
+                "--print" => options.print = true,
+                "--user" | "--state-dir" | "--env-file" | "--config" | "--unit" | "--binary" => {
+                    let Some(value) = inline.or_else(|| args.next().cloned()) else {
+                        bail!("{} needs a value", flag);
+                    };
+                    match flag {
+                        "--user" => options.user = value,
+                        "--state-dir" => options.state_dir = PathBuf::from(value),
+                        "--env-file" => options.env_file = PathBuf::from(value),
+                        "--config" => options.config = Some(PathBuf::from(value)),
+                        "--unit" => options.unit_path = PathBuf::from(value),
+                        _ => options.binary = PathBuf::from(value),
+                    }
+                }
+                _ => bail!("Unknown install-service option: {}", arg),
+            }
+        }
+
+        if !is_valid_user(&options.user) {
+            bail!("Invalid user name: {}", options.user);
+        }
+        for path in [
+            &options.binary,
+            &options.state_dir,
+            &options.env_file,
+            &options.unit_path,
+        ]
+        .into_iter()
+        .chain(options.config.as_ref())
+        {
+            if !path.is_absolute() {
+                bail!("{} must be an absolute path", path.display());
+            }
+        }
+        if options.unit_path.extension().and_then(|e| e.to_str()) != Some("service") {
+            bail!("The unit file must end in .service");
+        }
+        Ok(options)
+    }
+
+    /// Name `systemctl` knows the service by
+    pub fn unit_name(&self) -> String {
+        self.unit_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// The systemd unit running the server as `options.user`
+pub fn unit_file(options: &InstallOptions) -> String {
+    let mut exec_start = unit_arg(&options.binary);
+    if let Some(config) = &options.config {
+        write!(exec_start, " --config {}", unit_arg(config)).unwrap();
+    }
+    let state_dir = unit_arg(&options.state_dir);
+
+    format!(
+        "\
+[Unit]
+Description=Steering Center
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=simple
+User={user}
+Group={user}
+WorkingDirectory={state_dir}
+EnvironmentFile={env_file}
+ExecStart={exec_start}
+Restart=on-failure
+RestartSec=5
+# SIGTERM goes to the server only, which finishes tasks and stops its plugins
+KillMode=mixed
+TimeoutStopSec=90
+
+# Hardening; the server may only write to its state directory
+NoNewPrivileges=true
+ProtectSystem=strict
+ReadWritePaths={state_dir}
+ProtectHome=true
+PrivateTmp=true
+ProtectKernelTunables=true
+ProtectKernelModules=true
+ProtectControlGroups=true
+RestrictSUIDSGID=true
+RestrictRealtime=true
+LockPersonality=true
+SystemCallArchitectures=native
+UMask=0027
+
+[Install]
+WantedBy=multi-user.target
+",
+        user = options.user,
+        env_file = unit_arg(&options.env_file),
+    )
+}
+
+/// A new environment file, with a generated admin password
+fn env_file_contents(password: &str) -> String {
+    format!(
+        "\
+# Steering Center environment, read by systemd
+ADMIN_USERNAME=admin
+ADMIN_PASSWORD={}
+# Uncomment when the panel is reached over HTTPS
+#PRODUCTION=true
+",
+        password
+    )
+}
+
+/// Install the service as described by the arguments after `install-service`
+pub async fn install(args: &[String]) -> Result<()> {
+    let binary = std::env::current_exe()
+        .and_then(|p| p.canonicalize())
+        .context("Failed to locate the steering-center binary")?;
+    let options = InstallOptions::parse(binary, args)?;
+
+    if options.print {
+        print!("{}", unit_file(&options));
+        return Ok(());
+    }
+    if unsafe { libc::geteuid() } != 0 {
+        bail!("install-service must run as root (or use --print to only show the unit)");
+    }
+
+    if run(Command::new("id").args(["-u", &options.user]))
+        .await
+        .is_err()
+    {
+        run(Command::new("useradd")
+            .args([
+                "--system",
+                "--user-group",
+                "--shell",
+                "/usr/sbin/nologin",
+                "--home-dir",
+            ])
+            .arg(&options.state_dir)
+            .arg(&options.user))
+        .await?;
+        println!("Created system user {}", options.user);
+    }
+
+    tokio::fs::create_dir_all(&options.state_dir)
+        .await
+        .with_context(|| format!("Failed to create {}", options.state_dir.display()))?;
+    run(Command::new("chown")
+        .arg(format!("{0}:{0}", options.user))
+        .arg(&options.state_dir))
+    .await?;
+    tokio::fs::set_permissions(&options.state_dir, std::fs::Permissions::from_mode(0o750)).await?;
+
+    let mut password = None;
+    if !options.env_file.exists() {
+        if let Some(parent) = options.env_file.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let generated = Alphanumeric.sample_string(&mut OsRng, PASSWORD_LENGTH);
+        write_file(&options.env_file, &env_file_contents(&generated), 0o600).await?;
+        password = Some(generated);
+    }
+
+    write_file(&options.unit_path, &unit_file(&options), 0o644).await?;
+    println!("Wrote {}", options.unit_path.display());
+    run(Command::new("systemctl").arg("daemon-reload")).await?;
+
+    let unit_name = options.unit_name();
+    if options.enable {
+        run(Command::new("systemctl").args(["enable", "--now", &unit_name])).await?;
+        println!("Enabled and started {}", unit_name);
+    } else {
+        println!("Start it with: systemctl enable --now {}", unit_name);
+    }
+    match password {
+        Some(password) => println!(
+            "Admin credentials are in {}: admin / {}",
+            options.env_file.display(),
+            password
+        ),
+        None => println!("Kept the existing {}", options.env_file.display()),
+    }
+    Ok(())
+}
+
+/// Write `content` to `path` through a temp file created with `mode`, so it's never readable by others
+async fn write_file(path: &Path, content: &str, mode: u32) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    let result = async {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(&tmp)
+            .await?;
+        file.write_all(content.as_bytes()).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp, path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    result.with_context(|| format!("Failed to write {}", path.display()))
+}
+
+async fn run(command: &mut Command) -> Result<()> {
+    let program = command
+        .as_std()
+        .get_program()
+        .to_string_lossy()
+        .into_owned();
+    let output = command
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        bail!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// A path as a unit file argument: quoted when needed, with `%` specifiers escaped
+fn unit_arg(path: &Path) -> String {
+    let value = path.to_string_lossy().replace('%', "%%");
+    if value.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value
+    }
+}
+
+fn is_valid_user(user: &str) -> bool {
+    !user.is_empty()
+        && user.len() <= 32
+        && !user.starts_with('-')
+        && user
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let binary = PathBuf::from("/usr/local/bin/steering-center");
+        let options = InstallOptions::parse(binary.clone(), &[]).unwrap();
+        assert_eq!(options.user, "steering");
+        assert_eq!(options.unit_name(), "steering-center.service");
+        assert!(!options.enable);
+
+        let options = InstallOptions::parse(
+            binary.clone(),
+            &args(&[
+                "--user",
+                "toru",
+                "--config=/etc/steering/config.toml",
+                "--enable",
+            ]),
+        )
+        .unwrap();
+        assert_eq!(options.user, "toru");
+        assert_eq!(
+            options.config,
+            Some(PathBuf::from("/etc/steering/config.toml"))
+        );
+        assert!(options.enable);
+
+        assert!(InstallOptions::parse(binary.clone(), &args(&["--user"])).is_err());
+        assert!(InstallOptions::parse(binary.clone(), &args(&["--user", "Root!"])).is_err());
+        assert!(InstallOptions::parse(binary.clone(), &args(&["--state-dir", "data"])).is_err());
+        assert!(InstallOptions::parse(binary.clone(), &args(&["--unit", "/tmp/x.conf"])).is_err());
+        assert!(InstallOptions::parse(binary, &args(&["--force"])).is_err());
+    }
+
+    #[test]
+    fn test_unit_file() {
+        let options = InstallOptions::parse(
+            PathBuf::from("/opt/steering center/steering-center"),
+            &args(&["--config", "/etc/steering/100%.toml"]),
+        )
+        .unwrap();
+        let unit = unit_file(&options);
+        assert!(unit.contains("User=steering\nGroup=steering\n"));
+        assert!(unit.contains(
+            "ExecStart=\"/opt/steering center/steering-center\" --config /etc/steering/100%%.toml\n"
+        ));
+        assert!(unit.contains("ReadWritePaths=/var/lib/steering-center\n"));
+        assert!(unit.contains("EnvironmentFile=/etc/steering-center/env\n"));
+    }
+}
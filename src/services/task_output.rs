@@ -0,0 +1,199 @@
+//! Task output kept for WebSocket clients that reconnect
+//!
+//! Every message of a task started from a WebSocket gets the next sequence
+//! number of that task (`seq`, from 1) and is kept in a bounded buffer. A
+//! client that lost its connection sends `resume` with the last `seq` it saw
+//! per task and gets the messages it missed, then the live output again.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::services::executor::TaskMessage;
+
+/// Messages kept per task; older ones are only in the task log
+pub const REPLAY_BUFFER_MESSAGES: usize = 5000;
+
+/// How long the output of a finished task can still be resumed
+pub const REPLAY_RETENTION: Duration = Duration::from_secs(10 * 60);
+
+/// How many messages a connection may fall behind before it replays from the buffer
+const LIVE_BUFFER: usize = 1024;
+
+/// A task message with its position in the task's output
+#[derive(Debug, Clone, Serialize)]
+pub struct SequencedMessage {
+    #[serde(flatten)]
+    pub message: TaskMessage,
+    pub seq: u64,
+}
+
+impl SequencedMessage {
+    pub fn task_id(&self) -> &str {
+        self.message.task_id.as_deref().unwrap_or_default()
+    }
+
+    /// Whether this is the last message of its task
+    pub fn is_final(&self) -> bool {
+        matches!(self.message.r#type.as_str(), "exit" | "error")
+    }
+}
+
+/// Messages of a task missed by a client
+#[derive(Debug, Default)]
+pub struct Replay {
+    pub messages: Vec<SequencedMessage>,
+    /// Messages no longer buffered between the client's `last_seq` and `messages`
+    pub dropped: u64,
+}
+
+struct TaskOutput {
+    /// User who started the task (`None` for the environment admin)
+    owner: Option<String>,
+    next_seq: u64,
+    messages: VecDeque<SequencedMessage>,
+    finished_at: Option<Instant>,
+}
+
+pub struct TaskOutputHub {
+    tasks: Mutex<HashMap<String, TaskOutput>>,
+    live: broadcast::Sender<SequencedMessage>,
+}
+
+impl Default for TaskOutputHub {
+    fn default() -> Self {
+        Self {
+            tasks: Mutex::default(),
+            live: broadcast::channel(LIVE_BUFFER).0,
+        }
+    }
+}
+
+impl TaskOutputHub {
+    /// Live messages of every task; connections keep those of the tasks they follow
+    pub fn subscribe(&self) -> broadcast::Receiver<SequencedMessage> {
+        self.live.subscribe()
+    }
+
+    /// Number and keep a message of a task started by `owner`, and pass it on live
+    ///
+    /// Messages without a task are ignored.
+    pub fn publish(&self, owner: Option<&str>, message: TaskMessage) {
+        let Some(task_id) = message.task_id.clone() else {
+            return;
+        };
+        let mut tasks = self.lock();
+        prune(&mut tasks);
+        let output = tasks.entry(task_id).or_insert_with(|| TaskOutput {
+            owner: owner.map(str::to_string),
+            next_seq: 1,
+            messages: VecDeque::new(),
+            finished_at: None,
+        });
+
+        let message = SequencedMessage {
+            message,
+            seq: output.next_seq,
+        };
+        output.next_seq += 1;
+        if message.is_final() {
+            output.finished_at = Some(Instant::now());
+        }
+        if output.messages.len() == REPLAY_BUFFER_MESSAGES {
+            output.messages.pop_front();
+        }
+        output.messages.push_back(message.clone());
+        // No receivers just means no client is connected
+        let _ = self.live.send(message);
+    }
+
+    /// Messages of a task after `last_seq`, if the task is known and `user_id` may see it
+    pub fn replay(
+        &self,
+        task_id: &str,
+        last_seq: u64,
+        user_id: Option<&str>,
+        is_admin: bool,
+    ) -> Option<Replay> {
+        let tasks = self.lock();
+        let output = tasks.get(task_id)?;
+        if !is_admin && (output.owner.is_none() || output.owner.as_deref() != user_id) {
+            return None;
+        }
+        let first = output.messages.front().map_or(output.next_seq, |m| m.seq);
+        Some(Replay {
+            messages: output
+                .messages
+                .iter()
+                .filter(|m| m.seq > last_seq)
+                .cloned()
+                .collect(),
+            dropped: first.saturating_sub(last_seq + 1),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, TaskOutput>> {
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Forget tasks that finished longer than [`REPLAY_RETENTION`] ago
+fn prune(tasks: &mut HashMap<String, TaskOutput>) {
+    tasks.retain(|_, output| {
+        output
+            .finished_at
+            .is_none_or(|finished| finished.elapsed() < REPLAY_RETENTION)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_after_last_seq() {
+        let hub = TaskOutputHub::default();
+        let mut live = hub.subscribe();
+        hub.publish(Some("u1"), TaskMessage::event("started", "t1"));
+        for line in ["one", "two", "three"] {
+            hub.publish(
+                Some("u1"),
+                TaskMessage::event("stdout", "t1").with_data(line),
+            );
+        }
+        hub.publish(Some("u1"), TaskMessage::event("exit", "t1").with_code(0));
+        assert_eq!(live.try_recv().unwrap().seq, 1);
+
+        let replay = hub.replay("t1", 2, Some("u1"), false).unwrap();
+        let seqs: Vec<u64> = replay.messages.iter().map(|m| m.seq).collect();
+        assert_eq!(seqs, [3, 4, 5]);
+        assert_eq!(replay.messages[0].message.data.as_deref(), Some("two"));
+        assert!(replay.messages[2].is_final());
+        assert_eq!(replay.dropped, 0);
+
+        // Only the owner and admins may resume a task
+        assert!(hub.replay("t1", 0, Some("u2"), false).is_none());
+        assert!(hub.replay("t1", 0, None, false).is_none());
+        assert!(hub.replay("t1", 0, None, true).is_some());
+        assert!(hub.replay("t2", 0, Some("u1"), false).is_none());
+    }
+
+    #[test]
+    fn test_replay_reports_dropped_messages() {
+        let hub = TaskOutputHub::default();
+        for _ in 0..REPLAY_BUFFER_MESSAGES + 10 {
+            hub.publish(None, TaskMessage::event("stdout", "t1").with_data("x"));
+        }
+        let replay = hub.replay("t1", 4, None, true).unwrap();
+        assert_eq!(replay.dropped, 6);
+        assert_eq!(replay.messages.len(), REPLAY_BUFFER_MESSAGES);
+        assert_eq!(replay.messages[0].seq, 11);
+
+        let serialized = serde_json::to_value(&replay.messages[0]).unwrap();
+        assert_eq!(serialized["type"], "stdout");
+        assert_eq!(serialized["task_id"], "t1");
+        assert_eq!(serialized["seq"], 11);
+    }
+}
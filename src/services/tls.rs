@@ -0,0 +1,223 @@
+//! HTTPS served by the Steering Center itself
+//!
+//! When a certificate and key are given (`--tls-cert`/`--tls-key`, the
+//! `TORU_TLS_CERT`/`TORU_TLS_KEY` environment variables or the `tls_cert` and
+//! `tls_key` settings) the server terminates TLS with rustls instead of relying
+//! on a reverse proxy. Session cookies are then always marked Secure, and a
+//! plain HTTP listener on the `tls_redirect_port` sends browsers to HTTPS.
+
+use anyhow::{bail, Context, Result};
+use axum::{
+    http::{header, HeaderMap, Uri},
+    response::{IntoResponse, Redirect},
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::TlsConfig;
+use crate::db::{self, DbPool};
+use crate::services::shutdown::{ShutdownSignal, CONNECTION_DRAIN_TIMEOUT};
+
+/// Setting with the path of the PEM certificate chain
+pub const TLS_CERT_SETTING: &str = "tls_cert";
+
+/// Setting with the path of the PEM private key
+pub const TLS_KEY_SETTING: &str = "tls_key";
+
+/// Setting with the port of the HTTP listener redirecting to HTTPS; unset turns it off
+pub const TLS_REDIRECT_PORT_SETTING: &str = "tls_redirect_port";
+
+/// Whether the server terminates TLS itself, set once it starts serving HTTPS
+static TLS_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether the server is serving HTTPS itself
+pub fn is_active() -> bool {
+    TLS_ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Certificate and key the server terminates TLS with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsOptions {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Port of the HTTP listener redirecting to HTTPS
+    pub redirect_port: Option<u16>,
+}
+
+/// TLS options from the configuration, or the settings for the values it leaves unset
+///
+/// Returns `None` when no certificate and key are configured, and an error when only one is.
+pub async fn resolve_options(db: &DbPool, config: &TlsConfig) -> Result<Option<TlsOptions>> {
+    let cert = match config.cert.clone() {
+        Some(cert) => Some(cert),
+        None => db::get_setting(db, TLS_CERT_SETTING).await?,
+    };
+    let key = match config.key.clone() {
+        Some(key) => Some(key),
+        None => db::get_setting(db, TLS_KEY_SETTING).await?,
+    };
+    let redirect_port = resolve_redirect_port(db, config.redirect_port).await?;
+    options_from(cert, key, redirect_port)
+}
+
+/// Port of the HTTP listener redirecting to HTTPS, from the configuration or settings
+pub async fn resolve_redirect_port(db: &DbPool, configured: Option<u16>) -> Result<Option<u16>> {
+    match configured {
+        Some(port) => Ok(Some(port)),
+        None => Ok(db::get_setting(db, TLS_REDIRECT_PORT_SETTING)
+            .await?
+            .and_then(|p| p.trim().parse().ok())),
+    }
+}
+
+fn options_from(
+    cert: Option<String>,
+    key: Option<String>,
+    redirect_port: Option<u16>,
+) -> Result<Option<TlsOptions>> {
+    let cert = cert.filter(|c| !c.trim().is_empty());
+    let key = key.filter(|k| !k.trim().is_empty());
+    match (cert, key) {
+        (Some(cert), Some(key)) => Ok(Some(TlsOptions {
+            cert: PathBuf::from(cert),
+            key: PathBuf::from(key),
+            redirect_port,
+        })),
+        (None, None) => Ok(None),
+        (Some(_), None) => bail!("A TLS certificate was given without a key (--tls-key)"),
+        (None, Some(_)) => bail!("A TLS key was given without a certificate (--tls-cert)"),
+    }
+}
+
+/// Load the certificate and key into a rustls configuration
+pub async fn load_config(options: &TlsOptions) -> Result<RustlsConfig> {
+    // Several crypto providers may be compiled in; use ring, unless one was already chosen
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(&options.cert, &options.key)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load TLS certificate {} and key {}",
+                options.cert.display(),
+                options.key.display()
+            )
+        })
+}
+
+/// Serve the app over HTTPS until a shutdown signal is received
+pub async fn serve(
+    addr: SocketAddr,
+    config: RustlsConfig,
+    app: Router,
+    signal: ShutdownSignal,
+) -> Result<()> {
+    TLS_ACTIVE.store(true, Ordering::Relaxed);
+    let handle = axum_server::Handle::new();
+    let shutdown = handle.clone();
+    tokio::spawn(async move {
+        signal.received().await;
+        shutdown.graceful_shutdown(Some(CONNECTION_DRAIN_TIMEOUT));
+    });
+    axum_server::bind_rustls(addr, config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await?;
+    Ok(())
+}
+
+/// Listen for plain HTTP on `addr`, redirecting every request to HTTPS on `https_port`
+///
+/// Requests matching `routes` (such as ACME challenges) are answered instead.
+pub fn spawn_http_redirect(addr: SocketAddr, https_port: u16, routes: Router) {
+    let app = routes.fallback(move |headers: HeaderMap, uri: Uri| async move {
+        redirect_to_https(https_port, &headers, &uri)
+    });
+    tokio::spawn(async move {
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::warn!("Failed to bind HTTP redirect listener on {}: {}", addr, e);
+                return;
+            }
+        };
+        tracing::info!("Redirecting http://{} to HTTPS", addr);
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::warn!("HTTP redirect listener stopped: {}", e);
+        }
+    });
+}
+
+fn redirect_to_https(https_port: u16, headers: &HeaderMap, uri: &Uri) -> impl IntoResponse {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost");
+    Redirect::permanent(&https_url(host, uri, https_port))
+}
+
+/// The HTTPS URL for a request to `host` (as in the Host header) and `uri`
+fn https_url(host: &str, uri: &Uri, https_port: u16) -> String {
+    // Drop the port of the Host header, keeping IPv6 addresses intact
+    let hostname = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) && !name.ends_with(':') => {
+            name
+        }
+        _ => host,
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    if https_port == 443 {
+        format!("https://{}{}", hostname, path)
+    } else {
+        format!("https://{}:{}{}", hostname, https_port, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_need_cert_and_key() {
+        assert_eq!(options_from(None, None, Some(80)).unwrap(), None);
+        assert_eq!(
+            options_from(Some("cert.pem".into()), Some("key.pem".into()), Some(80)).unwrap(),
+            Some(TlsOptions {
+                cert: PathBuf::from("cert.pem"),
+                key: PathBuf::from("key.pem"),
+                redirect_port: Some(80),
+            })
+        );
+        assert!(options_from(Some("cert.pem".into()), None, None).is_err());
+        assert!(options_from(Some("".into()), Some("key.pem".into()), None).is_err());
+    }
+
+    #[test]
+    fn test_https_url() {
+        let uri: Uri = "/api/resources?x=1".parse().unwrap();
+        assert_eq!(
+            https_url("example.com:8080", &uri, 443),
+            "https://example.com/api/resources?x=1"
+        );
+        assert_eq!(
+            https_url("example.com", &uri, 3443),
+            "https://example.com:3443/api/resources?x=1"
+        );
+        assert_eq!(
+            https_url("[::1]:80", &"/".parse().unwrap(), 443),
+            "https://[::1]/"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_config_fails_for_missing_files() {
+        let options = TlsOptions {
+            cert: PathBuf::from("/nonexistent/cert.pem"),
+            key: PathBuf::from("/nonexistent/key.pem"),
+            redirect_port: None,
+        };
+        assert!(load_config(&options).await.is_err());
+    }
+}
@@ -0,0 +1,384 @@
+//! Pending OS package updates
+//!
+//! The package manager of the host (apt, dnf or pacman) is asked which of the
+//! installed packages have newer versions. Checks run in the background every
+//! `update_check_interval_hours` and on demand; the last result is stored so the
+//! dashboard can show it without waiting for a check.
+//!
+//! Checks use the package lists the system already has (`apt update` and the
+//! like aren't run), so they are only as fresh as the host's own refreshes.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::db::{self, DbPool};
+
+/// Setting with the hours between background checks; `0` turns them off
+pub const CHECK_INTERVAL_SETTING: &str = "update_check_interval_hours";
+
+const DEFAULT_CHECK_INTERVAL_HOURS: u64 = 24;
+
+/// How often the background job looks whether a check is due
+const POLL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long a package manager may take to answer
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Held while a check runs, so background and on-demand checks don't overlap
+static CHECK_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    Apt,
+    Dnf,
+    Pacman,
+}
+
+impl PackageManager {
+    /// The package manager installed on this host, if a supported one is
+    pub fn detect() -> Option<Self> {
+        [
+            (PackageManager::Apt, "apt"),
+            (PackageManager::Dnf, "dnf"),
+            (PackageManager::Pacman, "checkupdates"),
+        ]
+        .into_iter()
+        .find(|(_, program)| find_program(program))
+        .map(|(manager, _)| manager)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Pacman => "pacman",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageUpdate {
+    pub name: String,
+    pub current_version: Option<String>,
+    pub new_version: String,
+    /// Whether the update fixes a security issue, as far as the package manager says
+    pub security: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateCheck {
+    pub checked_at: DateTime<Utc>,
+    pub package_manager: Option<String>,
+    pub total: usize,
+    pub security: usize,
+    pub packages: Vec<PackageUpdate>,
+    /// Why the check failed; the counts are 0 then
+    pub error: Option<String>,
+}
+
+impl UpdateCheck {
+    fn new(package_manager: Option<PackageManager>, result: Result<Vec<PackageUpdate>>) -> Self {
+        let (mut packages, error) = match result {
+            Ok(packages) => (packages, None),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+        packages.sort_by(|a, b| b.security.cmp(&a.security).then(a.name.cmp(&b.name)));
+        Self {
+            checked_at: Utc::now(),
+            package_manager: package_manager.map(|manager| manager.name().to_string()),
+            total: packages.len(),
+            security: packages.iter().filter(|package| package.security).count(),
+            packages,
+            error,
+        }
+    }
+}
+
+/// Check for updates now and store the result
+///
+/// Waits for a check already running to finish first.
+pub async fn run_check(db: &DbPool) -> Result<UpdateCheck> {
+    let _guard = CHECK_LOCK.lock().await;
+    let manager = PackageManager::detect();
+    let result = match manager {
+        Some(manager) => list_updates(manager).await,
+        None => Err(anyhow!(
+            "No supported package manager (apt, dnf, pacman) found"
+        )),
+    };
+    let check = UpdateCheck::new(manager, result);
+    if let Some(error) = &check.error {
+        tracing::warn!("Package update check failed: {}", error);
+    } else {
+        tracing::info!(
+            "Package update check: {} updates, {} security",
+            check.total,
+            check.security
+        );
+    }
+
+    db::save_package_update_check(
+        db,
+        &check.checked_at.to_rfc3339(),
+        &serde_json::to_string(&check)?,
+    )
+    .await?;
+    Ok(check)
+}
+
+/// The last stored check
+pub async fn last_check(db: &DbPool) -> Result<Option<UpdateCheck>> {
+    Ok(db::get_package_update_check(db)
+        .await?
+        .and_then(|result| serde_json::from_str(&result).ok()))
+}
+
+/// Check for updates whenever the last check is older than the configured interval
+pub fn spawn_update_checker(db: DbPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let hours = db::get_setting(&db, CHECK_INTERVAL_SETTING)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|hours| hours.parse().ok())
+                .unwrap_or(DEFAULT_CHECK_INTERVAL_HOURS);
+            if hours == 0 {
+                continue;
+            }
+
+            let due = match last_check(&db).await {
+                Ok(Some(check)) => {
+                    Utc::now() - check.checked_at >= chrono::Duration::hours(hours as i64)
+                }
+                Ok(None) => true,
+                Err(e) => {
+                    tracing::warn!("Failed to read last package update check: {}", e);
+                    false
+                }
+            };
+            if due {
+                if let Err(e) = run_check(&db).await {
+                    tracing::warn!("Failed to store package update check: {}", e);
+                }
+            }
+        }
+    });
+}
+
+async fn list_updates(manager: PackageManager) -> Result<Vec<PackageUpdate>> {
+    match manager {
+        PackageManager::Apt => {
+            let output = run(Command::new("apt").args(["list", "--upgradable"]), &[0]).await?;
+            Ok(parse_apt(&output))
+        }
+        PackageManager::Dnf => {
+            // Exit code 100 means updates are available
+            let output = run(
+                Command::new("dnf").args(["check-update", "--quiet"]),
+                &[0, 100],
+            )
+            .await?;
+            let advisories = run(
+                Command::new("dnf").args(["updateinfo", "list", "--security", "--quiet"]),
+                &[0],
+            )
+            .await
+            .unwrap_or_default();
+            Ok(parse_dnf(&output, &advisories))
+        }
+        PackageManager::Pacman => {
+            // Exit code 2 means there are no updates
+            let output = run(&mut Command::new("checkupdates"), &[0, 2]).await?;
+            Ok(parse_checkupdates(&output))
+        }
+    }
+}
+
+/// Run a command and return its stdout, failing on an exit code not in `ok_codes`
+async fn run(command: &mut Command, ok_codes: &[i32]) -> Result<String> {
+    command.env("LC_ALL", "C").kill_on_drop(true);
+    let output = tokio::time::timeout(CHECK_TIMEOUT, command.output())
+        .await
+        .map_err(|_| anyhow!("Package manager timed out"))??;
+    match output.status.code() {
+        Some(code) if ok_codes.contains(&code) => {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        _ => Err(anyhow!(
+            "Package manager failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// Parse `apt list --upgradable`
+///
+/// Lines look like `openssl/jammy-updates,jammy-security 3.0.2-0ubuntu1.15 amd64
+/// [upgradable from: 3.0.2-0ubuntu1.14]`.
+fn parse_apt(output: &str) -> Vec<PackageUpdate> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, rest) = line.split_once('/')?;
+            let mut fields = rest.split_whitespace();
+            let sources = fields.next()?;
+            let new_version = fields.next()?;
+            let current_version = rest
+                .split_once("[upgradable from: ")
+                .and_then(|(_, from)| from.strip_suffix(']'))
+                .map(String::from);
+            Some(PackageUpdate {
+                name: name.to_string(),
+                current_version,
+                new_version: new_version.to_string(),
+                security: sources
+                    .split(',')
+                    .any(|source| source.ends_with("-security")),
+            })
+        })
+        .collect()
+}
+
+/// Parse `dnf check-update` with the packages of `dnf updateinfo list --security`
+///
+/// Updates are listed as `name.arch  version  repo`; advisories as
+/// `FEDORA-2024-1234  Important/Sec.  name-version.arch`.
+fn parse_dnf(output: &str, advisories: &str) -> Vec<PackageUpdate> {
+    let secure: HashSet<&str> = advisories
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(2))
+        .collect();
+
+    output
+        .lines()
+        // A section of packages to be removed follows; it isn't an update
+        .take_while(|line| !line.starts_with("Obsoleting"))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (package, version, _repo) = (fields.next()?, fields.next()?, fields.next()?);
+            if fields.next().is_some() {
+                return None;
+            }
+            let (name, arch) = package.rsplit_once('.')?;
+            // Advisories leave out the epoch (`2:`)
+            let release = version
+                .split_once(':')
+                .map_or(version, |(_, release)| release);
+            let nevra = format!("{}-{}.{}", name, release, arch);
+            Some(PackageUpdate {
+                name: name.to_string(),
+                current_version: None,
+                new_version: version.to_string(),
+                security: secure.contains(nevra.as_str()),
+            })
+        })
+        .collect()
+}
+
+/// Parse `checkupdates` (pacman-contrib): `name current -> new`
+fn parse_checkupdates(output: &str) -> Vec<PackageUpdate> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [name, current, "->", new, ..] => Some(PackageUpdate {
+                    name: name.to_string(),
+                    current_version: Some(current.to_string()),
+                    new_version: new.to_string(),
+                    security: false,
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Whether an executable with this name is on `PATH`
+fn find_program(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(name).is_file()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_apt() {
+        let output = "Listing...\n\
+            openssl/jammy-updates,jammy-security 3.0.2-0ubuntu1.15 amd64 [upgradable from: 3.0.2-0ubuntu1.14]\n\
+            vim/jammy-updates 2:8.2.3995-1ubuntu2.16 amd64 [upgradable from: 2:8.2.3995-1ubuntu2.15]\n";
+        let updates = parse_apt(output);
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].name, "openssl");
+        assert_eq!(updates[0].new_version, "3.0.2-0ubuntu1.15");
+        assert_eq!(
+            updates[0].current_version.as_deref(),
+            Some("3.0.2-0ubuntu1.14")
+        );
+        assert!(updates[0].security);
+        assert!(!updates[1].security);
+    }
+
+    #[test]
+    fn test_parse_dnf() {
+        let output = "\n\
+            kernel.x86_64            6.8.9-300.fc40          updates\n\
+            vim-enhanced.x86_64      2:9.1.393-1.fc40        updates\n\
+            Obsoleting Packages\n\
+            grub2-tools.x86_64       1:2.06-1.fc40           updates\n";
+        let advisories = "FEDORA-2024-1234 Important/Sec. kernel-6.8.9-300.fc40.x86_64\n\
+            FEDORA-2024-5678 Moderate/Sec.  vim-enhanced-9.1.393-1.fc40.x86_64\n";
+        let updates = parse_dnf(output, advisories);
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].name, "kernel");
+        assert!(updates[0].security);
+        assert_eq!(updates[1].new_version, "2:9.1.393-1.fc40");
+        assert!(updates[1].security);
+    }
+
+    #[test]
+    fn test_parse_checkupdates() {
+        let updates = parse_checkupdates("linux 6.9.1.arch1-1 -> 6.9.2.arch1-1\nnot an update\n");
+        assert_eq!(
+            updates,
+            [PackageUpdate {
+                name: "linux".to_string(),
+                current_version: Some("6.9.1.arch1-1".to_string()),
+                new_version: "6.9.2.arch1-1".to_string(),
+                security: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_security_updates_first() {
+        let package = |name: &str, security| PackageUpdate {
+            name: name.to_string(),
+            current_version: None,
+            new_version: "1".to_string(),
+            security,
+        };
+        let check = UpdateCheck::new(
+            Some(PackageManager::Apt),
+            Ok(vec![
+                package("b", false),
+                package("c", true),
+                package("a", false),
+            ]),
+        );
+        let names: Vec<&str> = check.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["c", "a", "b"]);
+        assert_eq!((check.total, check.security), (3, 1));
+    }
+}
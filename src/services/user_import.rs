@@ -0,0 +1,333 @@
+//! Creating many user accounts at once from CSV or JSON, and listing them back
+//!
+//! Each row names a user and either an initial password or `invite`, which
+//! creates the account with a generated password reported back once so the
+//! admin can hand it over. Rows are imported one by one: a bad row is reported
+//! and the others still go through.
+
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::auth::{hash_password, validate_password};
+use crate::db::{self, DbPool, User, UserRole};
+
+/// Most rows one import may hold
+pub const MAX_ROWS: usize = 1000;
+
+/// Length of a password generated for an invited user
+const INVITE_PASSWORD_LENGTH: usize = 20;
+
+/// Columns of an exported CSV, also accepted on import
+pub const CSV_COLUMNS: [&str; 7] = [
+    "id",
+    "username",
+    "display_name",
+    "role",
+    "is_active",
+    "created_at",
+    "expires_at",
+];
+
+/// A user to create
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportRow {
+    pub username: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default = "default_role")]
+    pub role: UserRole,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Create the account with a generated password instead
+    #[serde(default)]
+    pub invite: bool,
+    #[serde(default = "default_active")]
+    pub is_active: bool,
+    /// RFC 3339 time after which the account can't sign in
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+fn default_role() -> UserRole {
+    UserRole::Client
+}
+
+fn default_active() -> bool {
+    true
+}
+
+/// Outcome of one row, numbered from 1
+#[derive(Debug, Clone, Serialize)]
+pub struct RowResult {
+    pub row: usize,
+    pub username: String,
+    pub created: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Generated password of an invited user, only returned here
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Split CSV text into records of fields, with `"` quoting as in RFC 4180
+fn csv_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    // Blank lines carry no user
+    records.retain(|r| !(r.len() == 1 && r[0].trim().is_empty()));
+    Ok(records)
+}
+
+/// Rows of a CSV whose first line names the columns: `username` and any of
+/// `display_name`, `role`, `password`, `invite`, `is_active` and `expires_at`
+pub fn parse_csv(text: &str) -> Result<Vec<ImportRow>, String> {
+    let mut records = csv_records(text.trim_start_matches('\u{feff}'))?.into_iter();
+    let header: Vec<String> = records
+        .next()
+        .ok_or("Empty CSV")?
+        .into_iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let username = column("username").ok_or("Missing username column")?;
+    let (display_name, role, password, invite, is_active, expires_at) = (
+        column("display_name"),
+        column("role"),
+        column("password"),
+        column("invite"),
+        column("is_active"),
+        column("expires_at"),
+    );
+
+    let flag = |value: &str| matches!(value.trim().to_lowercase().as_str(), "true" | "yes" | "1");
+    let mut rows = Vec::new();
+    for (i, record) in records.enumerate() {
+        let get = |col: Option<usize>| {
+            col.and_then(|c| record.get(c))
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+        };
+        rows.push(ImportRow {
+            username: get(Some(username)).unwrap_or_default().to_string(),
+            display_name: get(display_name).map(str::to_string),
+            role: match get(role) {
+                Some(role) => role
+                    .parse()
+                    .map_err(|e: anyhow::Error| format!("Row {}: {}", i + 1, e))?,
+                None => UserRole::Client,
+            },
+            password: get(password).map(str::to_string),
+            invite: get(invite).is_some_and(flag),
+            is_active: get(is_active).is_none_or(flag),
+            expires_at: get(expires_at).map(str::to_string),
+        });
+    }
+    Ok(rows)
+}
+
+/// A password meeting the strength rules, for an invited user
+fn invite_password() -> String {
+    loop {
+        let password = format!(
+            "{}-{}",
+            Alphanumeric.sample_string(&mut OsRng, INVITE_PASSWORD_LENGTH / 2),
+            Alphanumeric.sample_string(&mut OsRng, INVITE_PASSWORD_LENGTH / 2)
+        );
+        if validate_password(&password).is_ok() {
+            return password;
+        }
+    }
+}
+
+/// Create the users of each row, reporting how every row went
+pub async fn import_users(db: &DbPool, rows: Vec<ImportRow>) -> Vec<RowResult> {
+    let mut seen = HashSet::new();
+    let mut results = Vec::with_capacity(rows.len());
+    for (i, row) in rows.into_iter().enumerate() {
+        let username = row.username.trim().to_string();
+        let mut result = RowResult {
+            row: i + 1,
+            username: username.clone(),
+            created: false,
+            id: None,
+            initial_password: None,
+            error: None,
+        };
+        match create_user(db, row, &username, &mut seen).await {
+            Ok((id, initial_password)) => {
+                result.created = true;
+                result.id = Some(id);
+                result.initial_password = initial_password;
+            }
+            Err(e) => result.error = Some(e),
+        }
+        results.push(result);
+    }
+    results
+}
+
+async fn create_user(
+    db: &DbPool,
+    row: ImportRow,
+    username: &str,
+    seen: &mut HashSet<String>,
+) -> Result<(String, Option<String>), String> {
+    if username.is_empty() {
+        return Err("Missing username".to_string());
+    }
+    if !seen.insert(username.to_string()) {
+        return Err("Username appears more than once".to_string());
+    }
+    if let Some(expires_at) = &row.expires_at {
+        chrono::DateTime::parse_from_rfc3339(expires_at)
+            .map_err(|_| "expires_at must be an RFC 3339 timestamp")?;
+    }
+    let (password, initial_password) = match (row.password, row.invite) {
+        (Some(_), true) => return Err("Give either a password or invite, not both".to_string()),
+        (Some(password), false) => {
+            validate_password(&password)?;
+            (password, None)
+        }
+        (None, true) => {
+            let password = invite_password();
+            (password.clone(), Some(password))
+        }
+        (None, false) => return Err("A password or invite is required".to_string()),
+    };
+    match db::get_user_by_username(db, username).await {
+        Ok(None) => {}
+        Ok(Some(_)) => return Err("Username already exists".to_string()),
+        Err(_) => return Err("Database error".to_string()),
+    }
+
+    let user = User {
+        id: uuid::Uuid::new_v4().to_string(),
+        username: username.to_string(),
+        password_hash: hash_password(&password).map_err(|_| "Failed to hash password")?,
+        display_name: row.display_name.filter(|name| !name.trim().is_empty()),
+        role: row.role,
+        is_active: row.is_active,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        expires_at: row.expires_at,
+        session_hours: None,
+        idle_timeout_minutes: None,
+    };
+    db::create_user(db, &user)
+        .await
+        .map_err(|_| "Failed to create user".to_string())?;
+    Ok((user.id, initial_password))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Users as CSV with the columns in `CSV_COLUMNS`, without password hashes
+pub fn users_csv(users: &[User]) -> String {
+    let mut csv = CSV_COLUMNS.join(",") + "\n";
+    for user in users {
+        let fields = [
+            user.id.as_str(),
+            &user.username,
+            user.display_name.as_deref().unwrap_or(""),
+            &user.role.to_string(),
+            if user.is_active { "true" } else { "false" },
+            &user.created_at,
+            user.expires_at.as_deref().unwrap_or(""),
+        ];
+        let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let rows = parse_csv(
+            "Username,Display_Name,role,password,invite\r\n\
+             alice,\"Smith, Alice\",admin,Secret123!,\n\
+             \n\
+             bob,\"Bob \"\"B\"\"\",,,yes\n",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].display_name.as_deref(), Some("Smith, Alice"));
+        assert_eq!(rows[0].role, UserRole::Admin);
+        assert!(!rows[0].invite);
+        assert_eq!(rows[1].display_name.as_deref(), Some("Bob \"B\""));
+        assert_eq!(rows[1].role, UserRole::Client);
+        assert!(rows[1].invite && rows[1].password.is_none());
+
+        assert!(parse_csv("name\nalice\n").is_err());
+        assert!(parse_csv("username,role\nalice,owner\n").is_err());
+        assert!(parse_csv("username\n\"alice\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_users() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::create_pool(dir.path().join("steering.db"), Default::default()).unwrap();
+        db::create_schema(&pool.get().await.unwrap()).unwrap();
+
+        let rows = parse_csv(
+            "username,password,invite\n\
+             alice,Secret123!,\n\
+             bob,,true\n\
+             alice,Secret123!,\n\
+             carol,weak,\n\
+             dave,,\n",
+        )
+        .unwrap();
+        let results = import_users(&pool, rows).await;
+        let created: Vec<bool> = results.iter().map(|r| r.created).collect();
+        assert_eq!(created, [true, true, false, false, false]);
+        let password = results[1].initial_password.as_deref().unwrap();
+        assert!(validate_password(password).is_ok());
+
+        let users = db::get_all_users(&pool).await.unwrap();
+        assert_eq!(users.len(), 2);
+        let csv = users_csv(&users);
+        assert!(csv.starts_with("id,username,display_name,role,is_active,created_at,expires_at\n"));
+        assert_eq!(parse_csv(&csv).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,514 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::io::DuplexStream;
+use tracing::{debug, warn};
+use wasmtime::{
+    Caller, Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, Trap, TypedFunc,
+};
+
+use toru_plugin_api::{
+    HttpMessageResponse, HttpRequest, HttpResponse, LifecycleInitPayload, Message, MessagePayload,
+    PluginMetadata, PluginProtocol,
+};
+
+use super::logging::{LogEntry, LogLevel};
+
+/// Fuel each call into a plugin may burn (roughly one unit per instruction)
+pub const FUEL_PER_CALL: u64 = 1_000_000_000;
+
+/// Linear memory a plugin may grow to when it sets no memory limit
+pub const DEFAULT_MEMORY_MB: u64 = 256;
+
+/// Buffer of the in-memory stream between the supervisor and a plugin
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Compiler shared by all WASM plugins, with fuel metering switched on
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config).expect("Invalid WASM engine configuration")
+    })
+}
+
+/// Per-instance state the host functions can reach
+struct HostState {
+    limits: StoreLimits,
+    // Lines written through `toru.log`, flushed to the plugin log after each call
+    logs: Vec<LogEntry>,
+}
+
+/// A live instance of the module with the exports every call needs
+struct WasmInstance {
+    store: Store<HostState>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+}
+
+/// A plugin compiled from a `.wasm` module and run inside the supervisor
+///
+/// The module talks to the host through its linear memory: the host copies JSON
+/// into a buffer from `toru_alloc` and gets JSON back as a pointer and length
+/// packed into an i64 (`ptr << 32 | len`). Exports:
+///
+/// * `memory`, `toru_alloc(len) -> ptr`
+/// * `toru_metadata() -> packed` - `PluginMetadata` JSON
+/// * `toru_http(ptr, len) -> packed` - `HttpRequest` JSON in, `HttpResponse` JSON out
+/// * `toru_init(ptr, len)` and `toru_shutdown()` - optional lifecycle hooks
+///
+/// A module may import `toru.log(level, ptr, len)` to write to its plugin log
+/// (levels as in `LogLevel::severity`). Every call gets `FUEL_PER_CALL` fuel and the
+/// memory cap; a call that traps resets the instance, like a crashed process.
+pub struct WasmPlugin {
+    id: String,
+    path: PathBuf,
+    module: Module,
+    memory_limit: u64,
+    fuel_per_call: u64,
+    instance: Mutex<Option<WasmInstance>>,
+}
+
+impl WasmPlugin {
+    /// Compile a module and check that it can be instantiated
+    ///
+    /// # Arguments
+    /// * `path` - Path to the `.wasm` file
+    /// * `memory_mb` - Cap on linear memory, `DEFAULT_MEMORY_MB` if none
+    pub fn load(path: &Path, memory_mb: Option<u64>) -> Result<Self> {
+        let module = Module::from_file(engine(), path).context("Failed to compile WASM module")?;
+        let id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let plugin = Self {
+            id,
+            path: path.to_path_buf(),
+            module,
+            memory_limit: memory_mb
+                .unwrap_or(DEFAULT_MEMORY_MB)
+                .saturating_mul(1024 * 1024),
+            fuel_per_call: FUEL_PER_CALL,
+            instance: Mutex::new(None),
+        };
+        *plugin.instance.lock().unwrap() = Some(plugin.instantiate()?);
+        Ok(plugin)
+    }
+
+    /// Size of the instance's linear memory in bytes (0 after a trap)
+    pub fn memory_bytes(&self) -> u64 {
+        match self.instance.try_lock().as_deref() {
+            Ok(Some(wasm)) => wasm.memory.data_size(&wasm.store) as u64,
+            _ => 0,
+        }
+    }
+
+    /// Read the plugin's metadata
+    pub fn metadata(&self) -> Result<PluginMetadata> {
+        let (output, _) = self.call("toru_metadata", None)?;
+        let output = output.context("Module does not export toru_metadata")?;
+        serde_json::from_slice(&output).context("Failed to parse plugin metadata JSON")
+    }
+
+    fn instantiate(&self) -> Result<WasmInstance> {
+        let engine = engine();
+        let state = HostState {
+            limits: StoreLimitsBuilder::new()
+                .memory_size(self.memory_limit as usize)
+                .instances(1)
+                .build(),
+            logs: Vec::new(),
+        };
+        let mut store = Store::new(engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.fuel_per_call)?;
+
+        let mut linker = Linker::new(engine);
+        let plugin_id = self.id.clone();
+        linker.func_wrap(
+            "toru",
+            "log",
+            move |mut caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32| {
+                let Some(memory) = caller.get_export("memory").and_then(|e| e.into_memory()) else {
+                    return;
+                };
+                let Some(bytes) = read_bytes(memory.data(&caller), ptr, len) else {
+                    return;
+                };
+                let level = match level {
+                    0 => LogLevel::Trace,
+                    1 => LogLevel::Debug,
+                    3 => LogLevel::Warn,
+                    4 => LogLevel::Error,
+                    _ => LogLevel::Info,
+                };
+                let entry = LogEntry::new(level, String::from_utf8_lossy(bytes).trim())
+                    .with_plugin(&plugin_id);
+                caller.data_mut().logs.push(entry);
+            },
+        )?;
+
+        let instance = linker
+            .instantiate(&mut store, &self.module)
+            .context("Failed to instantiate WASM module")?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("Module does not export its memory")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "toru_alloc")
+            .context("Module does not export toru_alloc(i32) -> i32")?;
+
+        Ok(WasmInstance {
+            store,
+            instance,
+            memory,
+            alloc,
+        })
+    }
+
+    /// Call an export with an optional JSON input
+    ///
+    /// Exports taking input have the signature `(ptr, len)`; exports returning
+    /// output return a packed i64, others nothing.
+    ///
+    /// # Returns
+    /// The output (`None` if the export is missing or returns nothing) and the log
+    /// lines written during the call
+    fn call(&self, export: &str, input: Option<&[u8]>) -> Result<(Option<Vec<u8>>, Vec<LogEntry>)> {
+        let mut guard = self.instance.lock().unwrap();
+        if guard.is_none() {
+            *guard = Some(self.instantiate()?);
+        }
+        let wasm = guard.as_mut().unwrap();
+        wasm.store.set_fuel(self.fuel_per_call)?;
+
+        let result = call_export(wasm, export, input);
+        let logs = std::mem::take(&mut wasm.store.data_mut().logs);
+
+        match result {
+            Ok(output) => Ok((output, logs)),
+            Err(e) => {
+                // A trapped instance may be in any state; start over on the next call
+                if e.downcast_ref::<Trap>().is_some() {
+                    warn!(
+                        "WASM plugin {} trapped in {}, resetting it",
+                        self.id, export
+                    );
+                    *guard = None;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Open an in-memory connection that speaks the plugin protocol
+    ///
+    /// Messages written to the returned stream are translated into calls to the
+    /// module, so the supervisor handles WASM plugins through the same
+    /// `PluginConnection` as socket plugins.
+    pub fn connect(self: &Arc<Self>, logger: Arc<super::logging::PluginLogger>) -> DuplexStream {
+        let (host, mut plugin) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+        let wasm = Arc::clone(self);
+
+        tokio::spawn(async move {
+            let mut protocol = PluginProtocol::new();
+            while let Ok(message) = protocol.read_message(&mut plugin).await {
+                let (reply, logs) = wasm.handle_message(message).await;
+                for entry in logs {
+                    let _ = logger.log_plugin(entry).await;
+                }
+                if let Some(reply) = reply {
+                    if let Err(e) = protocol.write_message(&mut plugin, &reply).await {
+                        debug!("WASM plugin {} connection closed: {}", wasm.id, e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        host
+    }
+
+    /// Run the call a protocol message asks for on a blocking thread
+    async fn handle_message(
+        self: &Arc<Self>,
+        message: Message,
+    ) -> (Option<Message>, Vec<LogEntry>) {
+        let wasm = Arc::clone(self);
+        let handled = tokio::task::spawn_blocking(move || match message.payload {
+            MessagePayload::Lifecycle {
+                action, payload, ..
+            } => {
+                let (export, input) = match action.as_str() {
+                    "init" => ("toru_init", payload.as_ref().map(init_json)),
+                    "shutdown" => ("toru_shutdown", None),
+                    _ => return (None, Vec::new()),
+                };
+                match wasm.call(export, input.as_deref()) {
+                    Ok((_, logs)) => (None, logs),
+                    Err(e) => {
+                        warn!("WASM plugin {} failed in {}: {:#}", wasm.id, export, e);
+                        (None, Vec::new())
+                    }
+                }
+            }
+            MessagePayload::Http {
+                request_id,
+                payload,
+            } => {
+                let (response, logs) = wasm.handle_http(&payload);
+                (Some(Message::new_http_response(request_id, response)), logs)
+            }
+            // The module runs inside the supervisor, so the host answers for it
+            MessagePayload::Ping { request_id } => {
+                (Some(Message::new_pong(request_id)), Vec::new())
+            }
+            MessagePayload::Kv { .. }
+            | MessagePayload::HttpChunk { .. }
+            | MessagePayload::WsFrame { .. }
+            | MessagePayload::Event { .. }
+            | MessagePayload::CallPlugin { .. }
+            | MessagePayload::CallResult { .. }
+            | MessagePayload::Exec { .. }
+            | MessagePayload::ExecResult { .. }
+            | MessagePayload::SystemInfo { .. }
+            | MessagePayload::SystemInfoResult { .. }
+            | MessagePayload::Notify { .. }
+            | MessagePayload::Scheduled { .. }
+            | MessagePayload::Pong { .. }
+            | MessagePayload::HttpResponse { .. }
+            | MessagePayload::KvResponse { .. } => {
+                warn!(
+                    "WASM plugin {} does not handle {} messages",
+                    wasm.id, message.message_type
+                );
+                (None, Vec::new())
+            }
+        })
+        .await;
+
+        handled.unwrap_or_else(|e| {
+            warn!("WASM plugin {} call panicked: {}", self.id, e);
+            (None, Vec::new())
+        })
+    }
+
+    /// Call `toru_http`, turning failures into a 500 response
+    fn handle_http(&self, request: &HttpRequest) -> (HttpMessageResponse, Vec<LogEntry>) {
+        let result = serde_json::to_vec(request)
+            .map_err(anyhow::Error::from)
+            .and_then(|input| self.call("toru_http", Some(&input)))
+            .and_then(|(output, logs)| {
+                let output = output.context("Module does not export toru_http")?;
+                let response: HttpResponse = serde_json::from_slice(&output)
+                    .context("Failed to parse plugin HTTP response JSON")?;
+                Ok((response, logs))
+            });
+
+        match result {
+            Ok((response, logs)) => (
+                HttpMessageResponse {
+                    status: response.status,
+                    headers: response.headers,
+                    body: response.body,
+                    body_encoding: response.body_encoding,
+                    stream: false,
+                },
+                logs,
+            ),
+            Err(e) => {
+                warn!("WASM plugin {} failed to handle request: {:#}", self.id, e);
+                let body = serde_json::json!({ "error": format!("{:#}", e) }).to_string();
+                (
+                    HttpMessageResponse {
+                        status: 500,
+                        headers: HashMap::from([(
+                            "Content-Type".to_string(),
+                            "application/json".to_string(),
+                        )]),
+                        body: Some(body),
+                        body_encoding: Default::default(),
+                        stream: false,
+                    },
+                    Vec::new(),
+                )
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for WasmPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmPlugin")
+            .field("id", &self.id)
+            .field("path", &self.path)
+            .field("memory_limit", &self.memory_limit)
+            .finish()
+    }
+}
+
+/// Whether a path is a WASM plugin rather than a native binary
+pub fn is_wasm_plugin(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("wasm")
+}
+
+fn call_export(
+    wasm: &mut WasmInstance,
+    export: &str,
+    input: Option<&[u8]>,
+) -> Result<Option<Vec<u8>>> {
+    let Some(func) = wasm.instance.get_func(&mut wasm.store, export) else {
+        return Ok(None);
+    };
+    let returns_output = func.ty(&wasm.store).results().len() == 1;
+
+    let args = match input {
+        Some(input) => {
+            let len = i32::try_from(input.len()).context("Input too large for WASM plugin")?;
+            let ptr = wasm.alloc.call(&mut wasm.store, len)?;
+            wasm.memory
+                .write(&mut wasm.store, ptr as u32 as usize, input)
+                .context("toru_alloc returned a buffer outside memory")?;
+            Some((ptr, len))
+        }
+        None => None,
+    };
+
+    let packed = match (args, returns_output) {
+        (Some(args), true) => Some(
+            func.typed::<(i32, i32), i64>(&wasm.store)?
+                .call(&mut wasm.store, args)?,
+        ),
+        (Some(args), false) => {
+            func.typed::<(i32, i32), ()>(&wasm.store)?
+                .call(&mut wasm.store, args)?;
+            None
+        }
+        (None, true) => Some(
+            func.typed::<(), i64>(&wasm.store)?
+                .call(&mut wasm.store, ())?,
+        ),
+        (None, false) => {
+            func.typed::<(), ()>(&wasm.store)?
+                .call(&mut wasm.store, ())?;
+            None
+        }
+    };
+
+    let Some(packed) = packed else {
+        return Ok(None);
+    };
+    let (ptr, len) = ((packed >> 32) as i32, packed as i32);
+    read_bytes(wasm.memory.data(&wasm.store), ptr, len)
+        .map(|bytes| Some(bytes.to_vec()))
+        .ok_or_else(|| anyhow!("{} returned a buffer outside memory", export))
+}
+
+/// Slice of guest memory, if the range is in bounds
+fn read_bytes(data: &[u8], ptr: i32, len: i32) -> Option<&[u8]> {
+    let start = ptr as u32 as usize;
+    let end = start.checked_add(len as u32 as usize)?;
+    data.get(start..end)
+}
+
+fn init_json(payload: &LifecycleInitPayload) -> Vec<u8> {
+    serde_json::to_vec(payload).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"{"id":"wasm-test","name":"WASM Test","version":"0.1.0","author":null,"icon":"","route":"/wasm-test"}"#;
+    const RESPONSE: &str = r#"{"status":200,"headers":{},"body":"hello from wasm"}"#;
+
+    /// Module returning fixed metadata and a fixed response, or spinning forever
+    /// when the request body is non-empty
+    fn test_module(dir: &Path) -> PathBuf {
+        let escape = |s: &str| s.replace('"', "\\\"");
+        let wat = format!(
+            r#"(module
+                (import "toru" "log" (func $log (param i32 i32 i32)))
+                (memory (export "memory") 2)
+                (data (i32.const 0) "{metadata}")
+                (data (i32.const 1024) "{response}")
+                (data (i32.const 2048) "handled")
+                (global $next (mut i32) (i32.const 4096))
+                (func (export "toru_alloc") (param $len i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $next))
+                    (global.set $next (i32.add (global.get $next) (local.get $len)))
+                    (local.get $ptr))
+                (func (export "toru_metadata") (result i64)
+                    (i64.const {metadata_len}))
+                (func (export "toru_http") (param $ptr i32) (param $len i32) (result i64)
+                    (call $log (i32.const 2) (i32.const 2048) (i32.const 7))
+                    ;; Requests with a body never return, to exhaust the fuel
+                    (if (i32.gt_u (local.get $len) (i32.const 60))
+                        (then (loop $spin (br $spin))))
+                    (i64.or
+                        (i64.shl (i64.const 1024) (i64.const 32))
+                        (i64.const {response_len}))))"#,
+            metadata = escape(METADATA),
+            response = escape(RESPONSE),
+            metadata_len = METADATA.len(),
+            response_len = RESPONSE.len(),
+        );
+        let path = dir.join("wasm-test.wasm");
+        std::fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+        path
+    }
+
+    fn request(body: Option<&str>) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            headers: HashMap::new(),
+            body: body.map(str::to_string),
+            body_encoding: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_metadata_and_http() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = WasmPlugin::load(&test_module(dir.path()), None).unwrap();
+
+        let metadata = plugin.metadata().unwrap();
+        assert_eq!(metadata.id, "wasm-test");
+        assert_eq!(metadata.route, "/wasm-test");
+
+        let (response, logs) = plugin.handle_http(&request(None));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body.as_deref(), Some("hello from wasm"));
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].message, "handled");
+    }
+
+    #[test]
+    fn test_runaway_call_runs_out_of_fuel() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut plugin = WasmPlugin::load(&test_module(dir.path()), None).unwrap();
+        plugin.fuel_per_call = 100_000;
+
+        let (response, _) = plugin.handle_http(&request(Some("spin until the fuel is gone")));
+        assert_eq!(response.status, 500);
+        assert!(plugin.instance.lock().unwrap().is_none());
+
+        // The next call gets a fresh instance
+        let (response, _) = plugin.handle_http(&request(None));
+        assert_eq!(response.status, 200);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        // The module asks for 128 KiB of memory up front
+        assert!(WasmPlugin::load(&test_module(dir.path()), Some(0)).is_err());
+    }
+}
@@ -0,0 +1,475 @@
+//! Outbound webhooks
+//!
+//! Admins register URLs with a secret and event filters. `emit` hands an event
+//! to the dispatcher, which POSTs it as JSON to every enabled webhook whose
+//! filters match, signed with HMAC-SHA256 of the webhook's secret. Failed
+//! deliveries are retried with backoff. `webhook_deliveries` is both the
+//! delivery log and the retry queue, so pending retries survive a restart.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, KeyInit, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::Sha256;
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+use crate::db::{self, DbPool, Webhook, WebhookDelivery};
+use crate::services::plugin_registry::validate_url;
+use crate::services::scheduler::format_run_at;
+
+/// Events webhooks can subscribe to
+pub const EVENTS: &[&str] = &[
+    "task.succeeded",
+    "task.failed",
+    "login.succeeded",
+    "login.failed",
+    "plugin.crashed",
+    "alert.raised",
+    "alert.resolved",
+];
+
+/// Event sent by the test endpoint, whatever the webhook's filters
+pub const PING_EVENT: &str = "ping";
+
+/// `sha256=<hex>`: HMAC-SHA256 of `<timestamp>.<body>` keyed with the secret
+pub const SIGNATURE_HEADER: &str = "X-Toru-Signature";
+/// Unix time the delivery was signed at
+pub const TIMESTAMP_HEADER: &str = "X-Toru-Timestamp";
+pub const EVENT_HEADER: &str = "X-Toru-Event";
+/// Delivery ID; the same on every retry of a delivery
+pub const DELIVERY_HEADER: &str = "X-Toru-Delivery";
+
+/// How often the dispatcher looks for deliveries due for a retry
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time a receiver gets to answer
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+
+/// Wait before each retry; a delivery fails for good once they are used up
+const RETRY_DELAYS: [Duration; 5] = [
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(30 * 60),
+    Duration::from_secs(2 * 60 * 60),
+];
+
+/// How long a claimed delivery is left to its attempt before it is claimed again
+const LEASE: Duration = Duration::from_secs(60);
+
+type Event = (String, serde_json::Value);
+
+static EVENTS_TX: OnceLock<mpsc::UnboundedSender<Event>> = OnceLock::new();
+
+/// JSON body POSTed to webhooks
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    id: &'a str,
+    event: &'a str,
+    created_at: &'a str,
+    data: serde_json::Value,
+}
+
+/// Queue an event for the webhooks subscribed to it
+///
+/// Does nothing until the dispatcher is running.
+pub fn emit(event: &str, data: serde_json::Value) {
+    if let Some(tx) = EVENTS_TX.get() {
+        let _ = tx.send((event.to_string(), data));
+    }
+}
+
+/// Deliver emitted events, and retry failed deliveries, in the background
+pub fn spawn_dispatcher(db: DbPool) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    if EVENTS_TX.set(tx).is_err() {
+        tracing::warn!("Webhook dispatcher is already running");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                Some((event, data)) = rx.recv() => {
+                    match create_deliveries(&db, &event, data).await {
+                        Ok(deliveries) => {
+                            for delivery in deliveries {
+                                tokio::spawn(deliver(db.clone(), delivery));
+                            }
+                        }
+                        Err(e) => tracing::warn!("Failed to queue {} webhooks: {}", event, e),
+                    }
+                }
+                _ = interval.tick() => {
+                    if let Err(e) = deliver_due(&db).await {
+                        tracing::warn!("Failed to retry webhook deliveries: {}", e);
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Whether an event passes a webhook's filters
+///
+/// A filter is an event name, a group like `task.*`, or `*`; no filters means every event.
+pub fn matches(filters: &[String], event: &str) -> bool {
+    filters.is_empty()
+        || filters.iter().any(|filter| {
+            filter == "*"
+                || filter == event
+                || filter.strip_suffix(".*").is_some_and(|group| {
+                    event
+                        .strip_prefix(group)
+                        .is_some_and(|rest| rest.starts_with('.'))
+                })
+        })
+}
+
+/// Check a webhook's URL and filters before saving it
+pub fn validate(url: &str, filters: &[String]) -> Result<()> {
+    validate_url(url)?;
+    for filter in filters {
+        if !EVENTS
+            .iter()
+            .any(|event| matches(std::slice::from_ref(filter), event))
+        {
+            bail!("Unknown event filter: {}", filter);
+        }
+    }
+    Ok(())
+}
+
+/// Random secret for a new webhook
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `X-Toru-Signature` value for a body signed at `timestamp`
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let message = format!("{}.{}", timestamp, body);
+    format!(
+        "sha256={}",
+        hmac_sha256_hex(secret.as_bytes(), message.as_bytes())
+    )
+}
+
+fn hmac_sha256_hex(key: &[u8], message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Wait before the retry following `attempts` failed attempts, or None to give up
+fn retry_delay(attempts: i64) -> Option<Duration> {
+    let index = usize::try_from(attempts).ok()?.checked_sub(1)?;
+    RETRY_DELAYS.get(index).copied()
+}
+
+fn lease_until() -> String {
+    format_run_at(Utc::now() + LEASE)
+}
+
+/// Record a delivery of an event for each enabled webhook subscribed to it
+///
+/// The deliveries come back claimed, ready for `deliver`.
+async fn create_deliveries(
+    db: &DbPool,
+    event: &str,
+    data: serde_json::Value,
+) -> Result<Vec<WebhookDelivery>> {
+    let mut deliveries = Vec::new();
+    for webhook in db::get_webhooks(db).await? {
+        if webhook.enabled && matches(&webhook.events, event) {
+            deliveries.push(create_delivery(db, &webhook, event, data.clone()).await?);
+        }
+    }
+    Ok(deliveries)
+}
+
+async fn create_delivery(
+    db: &DbPool,
+    webhook: &Webhook,
+    event: &str,
+    data: serde_json::Value,
+) -> Result<WebhookDelivery> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    let payload = Payload {
+        id: &id,
+        event,
+        created_at: &now,
+        data,
+    };
+    let delivery = WebhookDelivery {
+        id: id.clone(),
+        webhook_id: webhook.id.clone(),
+        event: event.to_string(),
+        payload: serde_json::to_string(&payload)?,
+        status: "pending".to_string(),
+        attempts: 0,
+        response_status: None,
+        error: None,
+        next_attempt_at: Some(lease_until()),
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    db::insert_webhook_delivery(db, &delivery).await?;
+    Ok(delivery)
+}
+
+/// Send a `ping` event to a webhook now
+pub async fn send_test(db: &DbPool, webhook: &Webhook) -> Result<WebhookDelivery> {
+    let data = serde_json::json!({ "webhook_id": webhook.id, "name": webhook.name });
+    let delivery = create_delivery(db, webhook, PING_EVENT, data).await?;
+    tokio::spawn(deliver(db.clone(), delivery.clone()));
+    Ok(delivery)
+}
+
+/// Send a finished delivery again, with a fresh set of retries
+///
+/// Returns None if it does not exist or is still pending.
+pub async fn redeliver(db: &DbPool, id: &str) -> Result<Option<WebhookDelivery>> {
+    let delivery = db::requeue_webhook_delivery(db, id, &lease_until()).await?;
+    if let Some(delivery) = &delivery {
+        tokio::spawn(deliver(db.clone(), delivery.clone()));
+    }
+    Ok(delivery)
+}
+
+/// Start an attempt for every delivery whose retry is due
+async fn deliver_due(db: &DbPool) -> Result<()> {
+    let due =
+        db::take_due_webhook_deliveries(db, &format_run_at(Utc::now()), &lease_until()).await?;
+    for delivery in due {
+        tokio::spawn(deliver(db.clone(), delivery));
+    }
+    Ok(())
+}
+
+/// Make one attempt at a delivery and record the outcome
+async fn deliver(db: DbPool, delivery: WebhookDelivery) {
+    let webhook = match db::get_webhook(&db, &delivery.webhook_id).await {
+        Ok(Some(webhook)) if webhook.enabled || delivery.event == PING_EVENT => webhook,
+        Ok(Some(_)) => {
+            record(
+                &db,
+                &delivery,
+                "failed",
+                None,
+                Some("Webhook is disabled"),
+                None,
+            )
+            .await;
+            return;
+        }
+        Ok(None) => {
+            record(
+                &db,
+                &delivery,
+                "failed",
+                None,
+                Some("Webhook was deleted"),
+                None,
+            )
+            .await;
+            return;
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load webhook {}: {}", delivery.webhook_id, e);
+            return;
+        }
+    };
+
+    let (code, error) = match post(&webhook, &delivery).await {
+        Ok(code) if (200..300).contains(&code) => {
+            record(&db, &delivery, "delivered", Some(code), None, None).await;
+            return;
+        }
+        Ok(code) => (Some(code), format!("Receiver answered HTTP {}", code)),
+        Err(e) => (None, format!("{:#}", e)),
+    };
+
+    let attempts = delivery.attempts + 1;
+    match retry_delay(attempts) {
+        Some(delay) => {
+            tracing::debug!(
+                "Webhook delivery {} failed (attempt {}): {}",
+                delivery.id,
+                attempts,
+                error
+            );
+            let retry_at = format_run_at(Utc::now() + delay);
+            record(
+                &db,
+                &delivery,
+                "pending",
+                code,
+                Some(&error),
+                Some(&retry_at),
+            )
+            .await;
+        }
+        None => {
+            tracing::warn!(
+                "Webhook delivery {} of {} failed after {} attempts: {}",
+                delivery.id,
+                delivery.event,
+                attempts,
+                error
+            );
+            record(&db, &delivery, "failed", code, Some(&error), None).await;
+        }
+    }
+}
+
+async fn record(
+    db: &DbPool,
+    delivery: &WebhookDelivery,
+    status: &str,
+    code: Option<i64>,
+    error: Option<&str>,
+    retry_at: Option<&str>,
+) {
+    if let Err(e) =
+        db::record_webhook_attempt(db, &delivery.id, status, code, error, retry_at).await
+    {
+        tracing::warn!("Failed to record webhook delivery {}: {}", delivery.id, e);
+    }
+}
+
+/// POST a delivery's payload using `curl`; returns the HTTP status the receiver answered
+async fn post(webhook: &Webhook, delivery: &WebhookDelivery) -> Result<i64> {
+    validate_url(&webhook.url)?;
+
+    let timestamp = Utc::now().timestamp();
+    let headers = [
+        "Content-Type: application/json".to_string(),
+        format!(
+            "User-Agent: toru-steering-center/{}",
+            env!("CARGO_PKG_VERSION")
+        ),
+        format!("{}: {}", EVENT_HEADER, delivery.event),
+        format!("{}: {}", DELIVERY_HEADER, delivery.id),
+        format!("{}: {}", TIMESTAMP_HEADER, timestamp),
+        format!(
+            "{}: {}",
+            SIGNATURE_HEADER,
+            sign(&webhook.secret, timestamp, &delivery.payload)
+        ),
+    ];
+
+    let mut command = Command::new("curl");
+    command.args([
+        "--silent",
+        "--show-error",
+        "--proto",
+        "=http,https",
+        "--max-time",
+        &DELIVERY_TIMEOUT_SECS.to_string(),
+        "--request",
+        "POST",
+        "--data-binary",
+        "@-",
+        "--output",
+        "/dev/null",
+        "--write-out",
+        "%{http_code}",
+    ]);
+    for header in &headers {
+        command.args(["--header", header]);
+    }
+    let mut child = command
+        .args(["--", &webhook.url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(delivery.payload.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Delivery failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("curl printed no HTTP status")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(list: &[&str]) -> Vec<String> {
+        list.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(&[], "task.failed"));
+        assert!(matches(&filters(&["*"]), "login.failed"));
+        assert!(matches(&filters(&["task.*"]), "task.failed"));
+        assert!(matches(
+            &filters(&["login.failed", "task.failed"]),
+            "task.failed"
+        ));
+        assert!(!matches(&filters(&["task.*"]), "login.failed"));
+        assert!(!matches(&filters(&["task.succeeded"]), "task.failed"));
+        assert!(!matches(&filters(&["task.*"]), "tasks.failed"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(
+            "https://example.com/hook",
+            &filters(&["task.*", "alert.raised"])
+        )
+        .is_ok());
+        assert!(validate("https://example.com/hook", &filters(&["task.started"])).is_err());
+        assert!(validate("ftp://example.com/hook", &[]).is_err());
+    }
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            sign("Jefe", 1700000000, "{}"),
+            format!("sha256={}", hmac_sha256_hex(b"Jefe", b"1700000000.{}"))
+        );
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Some(RETRY_DELAYS[0]));
+        assert_eq!(
+            retry_delay(RETRY_DELAYS.len() as i64),
+            RETRY_DELAYS.last().copied()
+        );
+        assert_eq!(retry_delay(RETRY_DELAYS.len() as i64 + 1), None);
+    }
+}
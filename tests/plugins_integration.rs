@@ -5,20 +5,37 @@
 //
 // Critical paths tested:
 // - T1-T4: Plugin loading (valid spawn, invalid handled, directory creation, metadata failures)
+// - T9: Plugin installation from an uploaded package
+// - T10: TCP loopback transport with shared-secret handshake
 // - T5-T8: Instance identity (generation, persistence, UUID format, passing to plugin)
-// - T12-T15: Plugin lifecycle (enable/disable, persistence, crash restart)
+// - T12-T17: Plugin lifecycle (enable/disable, persistence, crash restart, crash detection,
+//   graceful shutdown)
 // - T18-T19: KV/Socket tests (protocol and error handling)
+// - T20: Resource limits (host overrides, enforcement, usage sampling)
+// - T21: Hot reload of replaced plugin binaries
+// - T22: Dev plugins registered without spawning
 // - T23: Observability (plugin events written to database)
+// - T24: WASM plugins run inside the supervisor
+// - T25: Streamed HTTP response bodies
+// - T26: Binary request and response bodies
+// - T27: WebSocket frames relayed to a plugin
+// - T28: Plugin events fanned out to subscribers, browsers and the database
+// - T29: Plugin-to-plugin calls gated by capabilities
+// - T30: Scheduled jobs registered by a plugin
+// - T31: Route conflicts and reserved routes
+// - T32: Ping/pong health checks catch hung plugins
+// - T36: Plugin upgrades with data migration and rollback
 //
 // Run with: cargo test --test plugins_integration -- --nocapture
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 // Import PluginSupervisor for actual integration tests
 use steering_center::db;
-use steering_center::services::plugins::PluginSupervisor;
+use steering_center::services::plugin_package::PluginPackage;
+use steering_center::services::plugins::{self, InstallError, PluginSupervisor};
 
 // ============ Test Helpers ============
 
@@ -63,7 +80,7 @@ fn copy_test_binary(temp_dir: &TempDir) -> PathBuf {
 }
 
 /// Create a minimal test plugin binary (shell script)
-fn create_test_plugin(dir: &PathBuf, plugin_id: &str) -> PathBuf {
+fn create_test_plugin(dir: &Path, plugin_id: &str) -> PathBuf {
     let binary_path = dir.join(format!("{}.binary", plugin_id));
 
     // Create a simple shell script that acts as a test plugin
@@ -196,6 +213,13 @@ async fn test_t2_invalid_binary_handled_gracefully() {
         author: Some("Test".to_string()),
         icon: "🔧".to_string(),
         route: "/invalid".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
     };
 
     let result = supervisor
@@ -391,7 +415,133 @@ fn test_t8_instance_id_passed_to_plugin() {
     println!("✅ T8: Instance ID passed to plugin in init message");
 }
 
-// ============ T12-T15: Plugin Lifecycle Tests ============
+// ============ T9: Plugin Installation Tests ============
+
+/// Test T9: Uploaded package is validated, installed and refused a second time
+#[tokio::test]
+async fn test_t9_install_plugin_from_package() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let plugins_dir = supervisor.get_plugins_dir();
+
+    // Build the package from a test plugin outside the plugins dir
+    let source_dir = temp_dir.path().join("upload");
+    fs::create_dir_all(&source_dir).expect("Failed to create upload dir");
+    let source = create_test_plugin(&source_dir, "uploaded-plugin");
+    let package = || PluginPackage {
+        binary: fs::read(&source).expect("Failed to read test plugin"),
+        bundle: Some(b"export default {}".to_vec()),
+        signature: None,
+        assets: vec![("css/app.css".to_string(), b"body {}".to_vec())],
+    };
+
+    let metadata = supervisor
+        .install_plugin(package())
+        .await
+        .expect("Failed to install plugin");
+    assert_eq!(metadata.id, "uploaded-plugin");
+    assert!(plugins_dir.join("uploaded-plugin.binary").exists());
+    assert!(plugins_dir.join("uploaded-plugin/bundle.js").exists());
+    assert!(plugins_dir
+        .join("uploaded-plugin/assets/css/app.css")
+        .exists());
+    assert!(supervisor.get_plugin_status("uploaded-plugin").is_some());
+
+    // Installing the same plugin again is refused
+    assert!(matches!(
+        supervisor.install_plugin(package()).await,
+        Err(InstallError::AlreadyInstalled(_))
+    ));
+
+    // Garbage is rejected and leaves no files behind
+    let garbage = PluginPackage {
+        binary: b"not a plugin".to_vec(),
+        bundle: None,
+        signature: None,
+        assets: Vec::new(),
+    };
+    assert!(matches!(
+        supervisor.install_plugin(garbage).await,
+        Err(InstallError::Invalid(_))
+    ));
+    let leftovers = fs::read_dir(&plugins_dir)
+        .unwrap()
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+        .count();
+    assert_eq!(leftovers, 0, "Staging files should be cleaned up");
+
+    supervisor.kill_plugin("uploaded-plugin").await.ok();
+    println!("✅ T9: Plugin installed from uploaded package via install_plugin()");
+}
+
+// ============ T10: Plugin Transport Tests ============
+
+/// Test T10: A plugin asking for the TCP transport is reached over loopback with its secret
+#[tokio::test]
+async fn test_t10_tcp_transport_plugin_serves_requests() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let binary_path = copy_test_binary(&temp_dir);
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "hello-plugin-rust".to_string(),
+        name: "Hello World (Rust)".to_string(),
+        version: "0.1.0".to_string(),
+        author: None,
+        icon: "🦀".to_string(),
+        route: "/hello-rust".to_string(),
+        transport: toru_plugin_api::TransportKind::Tcp,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    supervisor
+        .spawn_plugin("hello-plugin-rust", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+
+    let status = supervisor
+        .get_plugin_status("hello-plugin-rust")
+        .expect("Plugin should exist");
+    assert!(
+        status.socket_path.starts_with("tcp://127.0.0.1:"),
+        "TCP plugin should get a loopback address, got {}",
+        status.socket_path
+    );
+    assert!(status.secret.is_some(), "TCP plugin should get a secret");
+
+    let http_request = toru_plugin_api::HttpRequest {
+        method: "GET".to_string(),
+        path: "/".to_string(),
+        headers: std::collections::HashMap::new(),
+        body: None,
+        body_encoding: Default::default(),
+    };
+    let mut response = None;
+    for _ in 0..20 {
+        match supervisor
+            .forward_http_request("hello-plugin-rust", &http_request)
+            .await
+        {
+            Ok(r) => {
+                response = Some(r);
+                break;
+            }
+            Err(_) => tokio::time::sleep(tokio::time::Duration::from_millis(100)).await,
+        }
+    }
+    let response = response.expect("Plugin should answer over TCP");
+    assert_eq!(response.status, 200);
+
+    supervisor.kill_plugin("hello-plugin-rust").await.ok();
+    println!("✅ T10: TCP transport plugin served a request over loopback");
+}
+
+// ============ T12-T17: Plugin Lifecycle Tests ============
 
 /// Test T12: Enable plugin spawns process and makes routes available via enable_plugin()
 #[tokio::test]
@@ -541,6 +691,13 @@ async fn test_t15_plugin_crash_triggers_restart_with_backoff() {
         author: Some("Test".to_string()),
         icon: "🔧".to_string(),
         route: "/test-restart-plugin".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
     };
 
     // Test restart counter logic
@@ -588,6 +745,127 @@ async fn test_t15_plugin_crash_triggers_restart_with_backoff() {
     );
 }
 
+/// Test T16: Health monitor detects a plugin whose process exited
+#[tokio::test]
+async fn test_t16_crashed_plugin_detected() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let plugins_dir = supervisor.get_plugins_dir();
+
+    // A plugin that exits right after starting
+    let binary_path = plugins_dir.join("crashing-plugin.binary");
+    fs::write(
+        &binary_path,
+        "#!/bin/bash\necho 'starting up' >&2\necho 'fatal: config missing' >&2\nexit 3\n",
+    )
+    .expect("Failed to write test plugin");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&binary_path, fs::Permissions::from_mode(0o755))
+            .expect("Failed to set permissions");
+    }
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "crashing-plugin".to_string(),
+        name: "Crashing Plugin".to_string(),
+        version: "1.0.0".to_string(),
+        author: None,
+        icon: "💥".to_string(),
+        route: "/crashing-plugin".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    supervisor
+        .spawn_plugin("crashing-plugin", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+
+    let crashed = supervisor.detect_crashed_plugins();
+    assert_eq!(crashed, vec![("crashing-plugin".to_string(), Some(3))]);
+
+    let status = supervisor
+        .get_plugin_status("crashing-plugin")
+        .expect("Plugin should exist");
+    assert!(
+        status.last_crash_at.is_some(),
+        "Crash time should be recorded"
+    );
+    assert!(status.uptime().is_none(), "Crashed plugin has no uptime");
+    let report = supervisor
+        .last_crash("crashing-plugin")
+        .expect("Crash report should be recorded");
+    assert_eq!(report.reason, "exited");
+    assert_eq!(report.exit_code, Some(3));
+    assert_eq!(report.signal, None);
+    assert_eq!(report.stderr, ["starting up", "fatal: config missing"]);
+
+    println!("✅ T16: Crashed plugin detected via detect_crashed_plugins()");
+}
+
+/// Test T17: A plugin ignoring the shutdown message is terminated after its shutdown timeout
+#[tokio::test]
+async fn test_t17_unresponsive_plugin_terminated_after_shutdown_timeout() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let plugins_dir = supervisor.get_plugins_dir();
+
+    // The shell-script plugin never opens its socket, so it can't receive the shutdown message
+    let binary_path = create_test_plugin(&plugins_dir, "stubborn-plugin");
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "stubborn-plugin".to_string(),
+        name: "Stubborn Plugin".to_string(),
+        version: "1.0.0".to_string(),
+        author: None,
+        icon: "🐢".to_string(),
+        route: "/stubborn-plugin".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    supervisor
+        .spawn_plugin("stubborn-plugin", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+
+    supervisor
+        .set_shutdown_timeout("stubborn-plugin", std::time::Duration::from_secs(1))
+        .await
+        .expect("Failed to set shutdown timeout");
+    assert_eq!(
+        supervisor.shutdown_timeout("stubborn-plugin"),
+        std::time::Duration::from_secs(1)
+    );
+
+    let started = std::time::Instant::now();
+    supervisor
+        .kill_plugin("stubborn-plugin")
+        .await
+        .expect("Failed to stop plugin");
+    let elapsed = started.elapsed();
+
+    // Waited out the shutdown timeout, then SIGTERM ended it without needing SIGKILL
+    assert!(elapsed >= std::time::Duration::from_secs(1));
+    assert!(elapsed < std::time::Duration::from_secs(4));
+    let status = supervisor
+        .get_plugin_status("stubborn-plugin")
+        .expect("Plugin should still exist in memory");
+    assert!(status.process.is_none(), "Process should be gone");
+
+    println!("✅ T17: Unresponsive plugin terminated after its shutdown timeout");
+}
+
 // ============ T18-T19: KV/Socket Tests ============
 
 /// Test T18: KV requests handled correctly (protocol test, not full integration)
@@ -625,6 +903,7 @@ async fn test_t19_invalid_plugin_socket_handled_gracefully() {
         path: "/test".to_string(),
         headers: std::collections::HashMap::new(),
         body: None,
+        body_encoding: Default::default(),
     };
 
     let result = supervisor
@@ -658,14 +937,223 @@ async fn test_t19_invalid_plugin_socket_handled_gracefully() {
     println!("✅ T19: Invalid plugin socket handled gracefully via forward_http_request()");
 }
 
+// ============ T20: Resource Limit Tests ============
+
+/// Test T20: Host config limits override metadata and usage is sampled
+#[tokio::test]
+async fn test_t20_resource_limits_applied_and_usage_sampled() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let plugins_dir = supervisor.get_plugins_dir();
+    let binary_path = create_test_plugin(&plugins_dir, "limited-plugin");
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "limited-plugin".to_string(),
+        name: "Limited Plugin".to_string(),
+        version: "1.0.0".to_string(),
+        author: None,
+        icon: "📏".to_string(),
+        route: "/limited-plugin".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits {
+            memory_mb: Some(64),
+            cpu_percent: Some(10),
+        },
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    let host_limits = toru_plugin_api::PluginLimits {
+        memory_mb: Some(512),
+        cpu_percent: Some(50),
+    };
+    supervisor
+        .set_resource_limits("limited-plugin", host_limits)
+        .await
+        .expect("Failed to set limits");
+    assert_eq!(
+        supervisor.resource_limits("limited-plugin", &metadata),
+        host_limits,
+        "Host config should take precedence over metadata"
+    );
+
+    supervisor
+        .spawn_plugin("limited-plugin", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+
+    supervisor.enforce_resource_limits().await;
+    let status = supervisor
+        .get_plugin_status("limited-plugin")
+        .expect("Plugin should exist");
+    assert_eq!(status.limits, host_limits);
+    assert_ne!(status.enforcement.as_str(), "none");
+    let usage = status.usage.expect("Usage should be sampled");
+    assert!(usage.memory_bytes > 0, "Plugin should use some memory");
+    assert!(status.limit_exceeded.is_none());
+    assert!(
+        status.process.is_some(),
+        "Plugin within limits keeps running"
+    );
+
+    supervisor.kill_plugin("limited-plugin").await.ok();
+    println!("✅ T20: Resource limits applied and usage sampled via enforce_resource_limits()");
+}
+
+// ============ T21: Hot Reload Tests ============
+
+/// Test T21: A replaced binary is detected once fully written and swapped in
+#[tokio::test]
+async fn test_t21_replaced_binary_hot_reloaded() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let plugins_dir = supervisor.get_plugins_dir();
+    let binary_path = create_test_plugin(&plugins_dir, "reload-plugin");
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "reload-plugin".to_string(),
+        name: "Test Plugin".to_string(),
+        version: "1.0.0".to_string(),
+        author: Some("Test".to_string()),
+        icon: "🔧".to_string(),
+        route: "/reload-plugin".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    supervisor
+        .set_shutdown_timeout("reload-plugin", std::time::Duration::from_secs(1))
+        .await
+        .expect("Failed to set shutdown timeout");
+    supervisor
+        .spawn_plugin("reload-plugin", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+    let old_pid = supervisor.get_plugin_status("reload-plugin").unwrap().pid;
+    assert!(supervisor.changed_binaries().is_empty());
+
+    // Replace the binary the way a build or deploy would: write aside, rename over
+    let script = fs::read_to_string(&binary_path).expect("Failed to read test plugin");
+    let new_path = plugins_dir.join("reload-plugin.new");
+    fs::write(&new_path, script.replace("1.0.0", "2.0.0")).expect("Failed to write binary");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&new_path, fs::Permissions::from_mode(0o755))
+            .expect("Failed to set permissions");
+    }
+    fs::rename(&new_path, &binary_path).expect("Failed to replace binary");
+
+    // First check sees the change, the second confirms the file is stable
+    assert!(supervisor.changed_binaries().is_empty());
+    let changed = supervisor.changed_binaries();
+    assert_eq!(
+        changed,
+        vec![("reload-plugin".to_string(), binary_path.clone())]
+    );
+
+    supervisor
+        .reload_plugin("reload-plugin", &binary_path)
+        .await
+        .expect("Failed to reload plugin");
+
+    let status = supervisor
+        .get_plugin_status("reload-plugin")
+        .expect("Plugin should exist");
+    assert_eq!(status.metadata.as_ref().unwrap().version, "2.0.0");
+    assert!(status.enabled && status.process.is_some());
+    assert_ne!(status.pid, old_pid, "A new process should be running");
+    assert!(supervisor.changed_binaries().is_empty());
+
+    supervisor.kill_plugin("reload-plugin").await.ok();
+    println!("✅ T21: Replaced binary hot reloaded via changed_binaries() and reload_plugin()");
+}
+
+// ============ T22: Dev Plugin Tests ============
+
+/// Test T22: An externally run plugin is attached without spawning and never restarted
+#[tokio::test]
+async fn test_t22_dev_plugin_registered_without_spawning() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let binary_path = copy_test_binary(&temp_dir);
+
+    // The developer starts the plugin by hand
+    let socket_path = temp_dir.path().join("dev-hello.sock");
+    let mut external = std::process::Command::new(&binary_path)
+        .env("TORU_PLUGIN_SOCKET", &socket_path)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to start plugin");
+    for _ in 0..20 {
+        if socket_path.exists() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "hello-plugin-rust".to_string(),
+        name: "Hello World (Rust)".to_string(),
+        version: "0.1.0".to_string(),
+        author: None,
+        icon: "🦀".to_string(),
+        route: "/hello-rust".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    supervisor
+        .register_dev_plugin(socket_path.to_str().unwrap(), None, metadata)
+        .await
+        .expect("Failed to register dev plugin");
+
+    let status = supervisor
+        .get_plugin_status("hello-plugin-rust")
+        .expect("Plugin should exist");
+    assert!(status.dev && status.enabled);
+    assert!(status.process.is_none(), "Dev plugins are not spawned");
+
+    let http_request = toru_plugin_api::HttpRequest {
+        method: "GET".to_string(),
+        path: "/".to_string(),
+        headers: std::collections::HashMap::new(),
+        body: None,
+        body_encoding: Default::default(),
+    };
+    let response = supervisor
+        .forward_http_request("hello-plugin-rust", &http_request)
+        .await
+        .expect("Dev plugin should answer");
+    assert_eq!(response.status, 200);
+
+    // Stopping the external process is not treated as a crash
+    external.kill().ok();
+    external.wait().ok();
+    assert!(supervisor.detect_crashed_plugins().is_empty());
+
+    assert!(supervisor.unregister_dev_plugin("hello-plugin-rust").await);
+    assert!(supervisor.get_plugin_status("hello-plugin-rust").is_none());
+    println!("✅ T22: Dev plugin registered via register_dev_plugin() without spawning");
+}
+
 // ============ T23: Observability Tests ============
 
 /// Test T23: Plugin events written to database via notify_plugin_event()
 #[tokio::test]
 async fn test_t23_plugin_events_written_to_database() {
     use rusqlite::Connection;
-    use std::sync::Arc;
-    use tokio::sync::Mutex;
 
     let temp_dir = TempDir::new().expect("Failed to create temp dir");
     let _supervisor = create_test_supervisor(&temp_dir).await;
@@ -687,7 +1175,9 @@ async fn test_t23_plugin_events_written_to_database() {
     )
     .expect("Failed to create table");
 
-    let db_pool = Arc::new(Mutex::new(conn));
+    drop(conn);
+    let db_pool =
+        db::create_pool(&temp_db_path, Default::default()).expect("Failed to open temp db");
 
     // Write plugin events to database
     let event_id_1 = db::plugin_event_log(
@@ -732,3 +1222,1021 @@ async fn test_t23_plugin_events_written_to_database() {
 
     println!("✅ T23: Plugin events written to database via notify_plugin_event()");
 }
+
+// ============ T24: WASM Plugin Tests ============
+
+/// Test T24: A .wasm module is discovered, loaded in-process and serves requests
+#[tokio::test]
+async fn test_t24_wasm_plugin_serves_requests() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let plugins_dir = temp_dir.path().join("plugins");
+    fs::create_dir_all(&plugins_dir).expect("Failed to create plugins dir");
+
+    let metadata = r#"{"id":"wasm-hello","name":"WASM Hello","version":"0.1.0","author":null,"icon":"","route":"/wasm-hello"}"#;
+    let response = r#"{"status":200,"headers":{},"body":"hello from wasm"}"#;
+    let wat = format!(
+        r#"(module
+            (memory (export "memory") 1)
+            (data (i32.const 0) "{metadata}")
+            (data (i32.const 1024) "{response}")
+            (func (export "toru_alloc") (param i32) (result i32) (i32.const 4096))
+            (func (export "toru_metadata") (result i64) (i64.const {metadata_len}))
+            (func (export "toru_http") (param i32 i32) (result i64)
+                (i64.or (i64.shl (i64.const 1024) (i64.const 32)) (i64.const {response_len}))))"#,
+        metadata = metadata.replace('"', "\\\""),
+        response = response.replace('"', "\\\""),
+        metadata_len = metadata.len(),
+        response_len = response.len(),
+    );
+    let module_path = plugins_dir.join("wasm-hello.wasm");
+    fs::write(
+        &module_path,
+        wat::parse_str(wat).expect("Invalid test module"),
+    )
+    .expect("Failed to write module");
+
+    let discovered = supervisor
+        .scan_plugins_directory()
+        .await
+        .expect("Failed to scan plugins");
+    let (path, metadata) = discovered
+        .get("wasm-hello")
+        .expect("WASM plugin should be discovered")
+        .clone();
+    supervisor
+        .spawn_plugin("wasm-hello", &path, metadata)
+        .await
+        .expect("Failed to load WASM plugin");
+
+    let status = supervisor
+        .get_plugin_status("wasm-hello")
+        .expect("Plugin should exist");
+    assert!(status.wasm.is_some() && status.process.is_none());
+    assert!(status.is_running());
+    assert!(supervisor.check_plugin_health("wasm-hello"));
+
+    let http_request = toru_plugin_api::HttpRequest {
+        method: "GET".to_string(),
+        path: "/".to_string(),
+        headers: std::collections::HashMap::new(),
+        body: None,
+        body_encoding: Default::default(),
+    };
+    let response = supervisor
+        .forward_http_request("wasm-hello", &http_request)
+        .await
+        .expect("WASM plugin should answer");
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body.as_deref(), Some("hello from wasm"));
+    assert!(supervisor.detect_crashed_plugins().is_empty());
+
+    supervisor
+        .kill_plugin("wasm-hello")
+        .await
+        .expect("Failed to stop WASM plugin");
+    assert!(!supervisor
+        .get_plugin_status("wasm-hello")
+        .unwrap()
+        .is_running());
+    assert!(module_path.exists(), "Stopping must not remove the module");
+    println!("✅ T24: WASM plugin loaded via spawn_plugin() and served a request in-process");
+}
+
+// ============ T25: Streaming Tests ============
+
+/// Test T25: A body streamed in http_chunk messages arrives whole and in order
+#[tokio::test]
+async fn test_t25_streamed_response_body() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let binary_path = copy_test_binary(&temp_dir);
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "hello-plugin-rust".to_string(),
+        name: "Hello World (Rust)".to_string(),
+        version: "0.1.0".to_string(),
+        author: None,
+        icon: "🦀".to_string(),
+        route: "/hello-rust".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    supervisor
+        .spawn_plugin("hello-plugin-rust", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+    for _ in 0..20 {
+        if supervisor
+            .get_plugin_status("hello-plugin-rust")
+            .is_some_and(|p| p.endpoint_ready())
+        {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    let connection = supervisor
+        .http_connection("hello-plugin-rust")
+        .await
+        .expect("Failed to connect to plugin");
+    let http_request = toru_plugin_api::HttpRequest {
+        method: "GET".to_string(),
+        path: "/ticks".to_string(),
+        headers: std::collections::HashMap::new(),
+        body: None,
+        body_encoding: Default::default(),
+    };
+    let (response, stream) =
+        plugins::open_http_request(&connection, &http_request, plugins::DEFAULT_REQUEST_TIMEOUT)
+            .await
+            .expect("Plugin should answer");
+    assert_eq!(response.status, 200);
+    assert!(response.stream && response.body.is_none());
+
+    let mut stream = stream.expect("Body should be streamed");
+    let mut chunks = Vec::new();
+    while let Some(chunk) = stream.next_chunk().await.expect("Stream broke off") {
+        chunks.push(chunk);
+    }
+    assert_eq!(chunks, [b"tick 1\n", b"tick 2\n", b"tick 3\n"]);
+
+    // Buffered callers get the whole body
+    let response = supervisor
+        .forward_http_request("hello-plugin-rust", &http_request)
+        .await
+        .expect("Plugin should answer");
+    assert_eq!(response.body.as_deref(), Some("tick 1\ntick 2\ntick 3\n"));
+
+    supervisor.kill_plugin("hello-plugin-rust").await.ok();
+    println!("✅ T25: Streamed body received via open_http_request() and send_http_request()");
+}
+
+// ============ T26: Binary Body Tests ============
+
+/// Test T26: A body that isn't UTF-8 reaches the plugin and comes back unchanged
+#[tokio::test]
+async fn test_t26_binary_body_round_trip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let binary_path = copy_test_binary(&temp_dir);
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "hello-plugin-rust".to_string(),
+        name: "Hello World (Rust)".to_string(),
+        version: "0.1.0".to_string(),
+        author: None,
+        icon: "🦀".to_string(),
+        route: "/hello-rust".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    supervisor
+        .spawn_plugin("hello-plugin-rust", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+    for _ in 0..20 {
+        if supervisor
+            .get_plugin_status("hello-plugin-rust")
+            .is_some_and(|p| p.endpoint_ready())
+        {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    // PNG signature: not valid UTF-8
+    let upload = [
+        0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0x00, 0xff,
+    ];
+    let mut http_request = toru_plugin_api::HttpRequest {
+        method: "POST".to_string(),
+        path: "/echo".to_string(),
+        headers: std::collections::HashMap::new(),
+        body: None,
+        body_encoding: Default::default(),
+    };
+    http_request.set_body_bytes(&upload);
+    assert_eq!(
+        http_request.body_encoding,
+        toru_plugin_api::BodyEncoding::Base64
+    );
+
+    let response = supervisor
+        .forward_http_request("hello-plugin-rust", &http_request)
+        .await
+        .expect("Plugin should answer");
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.body_bytes().expect("Invalid body").as_deref(),
+        Some(&upload[..])
+    );
+
+    supervisor.kill_plugin("hello-plugin-rust").await.ok();
+    println!("✅ T26: Binary body survived the round trip via forward_http_request()");
+}
+
+// ============ T27: WebSocket Tests ============
+
+/// Test T27: Frames sent on a relayed WebSocket reach the plugin and its replies come back
+#[tokio::test]
+async fn test_t27_websocket_frames_echoed() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let binary_path = copy_test_binary(&temp_dir);
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "hello-plugin-rust".to_string(),
+        name: "Hello World (Rust)".to_string(),
+        version: "0.1.0".to_string(),
+        author: None,
+        icon: "🦀".to_string(),
+        route: "/hello-rust".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: vec!["/echo".to_string()],
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    supervisor
+        .spawn_plugin("hello-plugin-rust", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+    for _ in 0..20 {
+        if supervisor
+            .get_plugin_status("hello-plugin-rust")
+            .is_some_and(|p| p.endpoint_ready())
+        {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    let connection = supervisor
+        .http_connection("hello-plugin-rust")
+        .await
+        .expect("Failed to connect to plugin");
+    let mut socket = connection.open_socket("t27");
+    let frames = [
+        toru_plugin_api::WsFrame::open("/echo"),
+        toru_plugin_api::WsFrame::text("ping"),
+        toru_plugin_api::WsFrame::binary(&[0x00, 0xff]),
+    ];
+    for frame in frames {
+        connection
+            .send(&toru_plugin_api::Message::new_ws_frame(
+                "t27".to_string(),
+                frame,
+            ))
+            .await
+            .expect("Failed to send frame");
+    }
+
+    let wait = tokio::time::Duration::from_secs(5);
+    let text = tokio::time::timeout(wait, socket.recv())
+        .await
+        .expect("No text echo")
+        .expect("Socket closed");
+    assert_eq!(text.kind, toru_plugin_api::WsFrameKind::Text);
+    assert_eq!(text.data.as_deref(), Some("ping"));
+    let binary = tokio::time::timeout(wait, socket.recv())
+        .await
+        .expect("No binary echo")
+        .expect("Socket closed");
+    assert_eq!(binary.bytes().expect("Invalid frame"), [0x00, 0xff]);
+
+    supervisor.kill_plugin("hello-plugin-rust").await.ok();
+    println!("✅ T27: WebSocket frames echoed via open_socket()");
+}
+
+// ============ T28: Event Bus Tests ============
+
+/// Test T28: An event one plugin publishes reaches a subscribed plugin, browsers and plugin_events
+#[tokio::test]
+async fn test_t28_event_bus_fan_out() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let supervisor = create_test_supervisor(&temp_dir).await;
+    let binary_path = copy_test_binary(&temp_dir);
+    let mut browser_events = supervisor.subscribe_events();
+    let supervisor = std::sync::Arc::new(tokio::sync::Mutex::new(supervisor));
+    plugins::spawn_plugin_message_handler(supervisor.clone());
+
+    // Two instances of the hello plugin; only the second subscribes to hello.*
+    for (plugin_id, subscriptions) in [
+        ("t28-publisher", Vec::new()),
+        ("t28-subscriber", vec!["hello.*".to_string()]),
+    ] {
+        let metadata = toru_plugin_api::PluginMetadata {
+            id: plugin_id.to_string(),
+            name: "Hello World (Rust)".to_string(),
+            version: "0.1.0".to_string(),
+            author: None,
+            icon: "🦀".to_string(),
+            route: format!("/{}", plugin_id),
+            transport: toru_plugin_api::TransportKind::Unix,
+            websockets: Vec::new(),
+            subscriptions,
+            capabilities: Vec::new(),
+            dependencies: Vec::new(),
+            limits: toru_plugin_api::PluginLimits::default(),
+            protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+        };
+        let mut sup = supervisor.lock().await;
+        sup.spawn_plugin(plugin_id, &binary_path, metadata)
+            .await
+            .expect("Failed to spawn plugin");
+        for _ in 0..20 {
+            if sup
+                .get_plugin_status(plugin_id)
+                .is_some_and(|p| p.endpoint_ready())
+            {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+    }
+
+    let request = |path: &str| toru_plugin_api::HttpRequest {
+        method: "POST".to_string(),
+        path: path.to_string(),
+        headers: std::collections::HashMap::new(),
+        body: Some("backup done".to_string()),
+        body_encoding: Default::default(),
+    };
+    let response = supervisor
+        .lock()
+        .await
+        .forward_http_request("t28-publisher", &request("/publish"))
+        .await
+        .expect("Plugin should answer");
+    assert_eq!(response.status, 202);
+
+    // Browsers see the event with its publisher
+    let event = tokio::time::timeout(tokio::time::Duration::from_secs(5), browser_events.recv())
+        .await
+        .expect("No event pushed to browsers")
+        .expect("Event channel closed");
+    assert_eq!(event.plugin_id, "t28-publisher");
+    assert_eq!(event.name, "hello.published");
+    assert_eq!(event.data["body"], "backup done");
+
+    // The subscriber got it with the publisher as source
+    let mut received = serde_json::Value::Null;
+    for _ in 0..20 {
+        let response = supervisor
+            .lock()
+            .await
+            .forward_http_request("t28-subscriber", &request("/events"))
+            .await
+            .expect("Plugin should answer");
+        received = serde_json::from_str(response.body.as_deref().unwrap_or("[]")).unwrap();
+        if received.as_array().is_some_and(|events| !events.is_empty()) {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(received[0]["name"], "hello.published");
+    assert_eq!(received[0]["source"], "t28-publisher");
+
+    // The publisher doesn't get its own event back
+    let response = supervisor
+        .lock()
+        .await
+        .forward_http_request("t28-publisher", &request("/events"))
+        .await
+        .expect("Plugin should answer");
+    assert_eq!(response.body.as_deref(), Some("[]"));
+
+    let db_pool = db::init_db().expect("Failed to init test db");
+    let events = db::plugin_event_get_recent(&db_pool, "t28-publisher", 10)
+        .await
+        .expect("Failed to read plugin events");
+    assert!(events
+        .iter()
+        .any(|e| e.event_type == "event:hello.published"));
+
+    let mut sup = supervisor.lock().await;
+    sup.kill_plugin("t28-publisher").await.ok();
+    sup.kill_plugin("t28-subscriber").await.ok();
+    println!(
+        "✅ T28: Event published via the event bus reached the subscriber, browsers and database"
+    );
+}
+
+// ============ T29: Plugin Call Tests ============
+
+/// Test T29: A plugin calls another plugin's HTTP handler once it is granted the capability
+#[tokio::test]
+async fn test_t29_plugin_calls_plugin() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let supervisor = create_test_supervisor(&temp_dir).await;
+    let binary_path = copy_test_binary(&temp_dir);
+    let supervisor = std::sync::Arc::new(tokio::sync::Mutex::new(supervisor));
+    plugins::spawn_plugin_message_handler(supervisor.clone());
+
+    for (plugin_id, capabilities) in [
+        ("t29-caller", vec!["call:t29-target".to_string()]),
+        ("t29-target", Vec::new()),
+    ] {
+        let metadata = toru_plugin_api::PluginMetadata {
+            id: plugin_id.to_string(),
+            name: "Hello World (Rust)".to_string(),
+            version: "0.1.0".to_string(),
+            author: None,
+            icon: "🦀".to_string(),
+            route: format!("/{}", plugin_id),
+            transport: toru_plugin_api::TransportKind::Unix,
+            websockets: Vec::new(),
+            subscriptions: Vec::new(),
+            capabilities,
+            dependencies: Vec::new(),
+            limits: toru_plugin_api::PluginLimits::default(),
+            protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+        };
+        let mut sup = supervisor.lock().await;
+        sup.spawn_plugin(plugin_id, &binary_path, metadata)
+            .await
+            .expect("Failed to spawn plugin");
+        for _ in 0..20 {
+            if sup
+                .get_plugin_status(plugin_id)
+                .is_some_and(|p| p.endpoint_ready())
+            {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+    }
+
+    // The caller relays /relay/<plugin>/<path> to the other plugin; the supervisor
+    // lock must not be held while it waits, as the call goes through the supervisor
+    let relay = || async {
+        let connection = supervisor
+            .lock()
+            .await
+            .http_connection("t29-caller")
+            .await
+            .expect("Failed to connect to plugin");
+        let request = toru_plugin_api::HttpRequest {
+            method: "POST".to_string(),
+            path: "/relay/t29-target/echo".to_string(),
+            headers: std::collections::HashMap::new(),
+            body: Some("hello from the caller".to_string()),
+            body_encoding: Default::default(),
+        };
+        plugins::send_http_request(&connection, &request, plugins::DEFAULT_REQUEST_TIMEOUT)
+            .await
+            .expect("Plugin should answer")
+    };
+
+    // Asked for in the metadata but not granted yet
+    let response = relay().await;
+    assert_eq!(response.status, 502);
+    assert!(response.body.unwrap_or_default().contains("may not call"));
+
+    supervisor
+        .lock()
+        .await
+        .set_granted_capabilities("t29-caller", &["call:t29-target".to_string()])
+        .await
+        .expect("Failed to grant capability");
+    let response = relay().await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body.as_deref(), Some("hello from the caller"));
+
+    let mut sup = supervisor.lock().await;
+    sup.kill_plugin("t29-caller").await.ok();
+    sup.kill_plugin("t29-target").await.ok();
+    println!("✅ T29: Plugin call denied without the capability and relayed with it");
+}
+
+// ============ T30: Scheduled Job Tests ============
+
+/// Test T30: Jobs a plugin registers at init are triggered by the host scheduler
+#[tokio::test]
+async fn test_t30_scheduled_jobs() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let supervisor = create_test_supervisor(&temp_dir).await;
+    let binary_path = copy_test_binary(&temp_dir);
+    let supervisor = std::sync::Arc::new(tokio::sync::Mutex::new(supervisor));
+    plugins::spawn_plugin_message_handler(supervisor.clone());
+    plugins::spawn_job_scheduler(supervisor.clone());
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "t30-jobs".to_string(),
+        name: "Hello World (Rust)".to_string(),
+        version: "0.1.0".to_string(),
+        author: None,
+        icon: "🦀".to_string(),
+        route: "/t30-jobs".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    // restart_plugin also sends init, which is when the plugin registers its jobs
+    supervisor
+        .lock()
+        .await
+        .restart_plugin("t30-jobs", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+
+    // The plugin registers its hourly job after init
+    let mut registered = Vec::new();
+    for _ in 0..30 {
+        registered = supervisor
+            .lock()
+            .await
+            .plugin_jobs("t30-jobs")
+            .iter()
+            .map(|job| job.id.clone())
+            .collect();
+        if !registered.is_empty() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(registered, ["hourly-greeting"]);
+
+    let request = |method: &str, body: Option<&str>| toru_plugin_api::HttpRequest {
+        method: method.to_string(),
+        path: "/jobs".to_string(),
+        headers: std::collections::HashMap::new(),
+        body: body.map(str::to_string),
+        body_encoding: Default::default(),
+    };
+
+    // Replace it with a job running every second (six fields: with seconds)
+    let response = supervisor
+        .lock()
+        .await
+        .forward_http_request(
+            "t30-jobs",
+            &request("POST", Some(r#"[{"id":"tick","schedule":"* * * * * *"}]"#)),
+        )
+        .await
+        .expect("Plugin should answer");
+    assert_eq!(response.status, 202);
+
+    let mut runs = 0;
+    for _ in 0..50 {
+        let response = supervisor
+            .lock()
+            .await
+            .forward_http_request("t30-jobs", &request("GET", None))
+            .await
+            .expect("Plugin should answer");
+        let counts: serde_json::Value =
+            serde_json::from_str(response.body.as_deref().unwrap_or("{}")).unwrap();
+        runs = counts["tick"].as_u64().unwrap_or(0);
+        if runs >= 2 {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    assert!(runs >= 2, "tick ran {} times", runs);
+
+    let mut sup = supervisor.lock().await;
+    let jobs: Vec<_> = sup
+        .plugin_jobs("t30-jobs")
+        .iter()
+        .map(|j| j.id.clone())
+        .collect();
+    assert_eq!(jobs, ["tick"]);
+
+    // Stopping the plugin drops its jobs
+    sup.kill_plugin("t30-jobs").await.ok();
+    assert!(sup.plugin_jobs("t30-jobs").is_empty());
+    println!("✅ T30: Registered jobs triggered by the host scheduler");
+}
+
+// ============ T31: Route Conflict Tests ============
+
+/// Test T31: Duplicate and reserved routes are refused instead of shadowing a plugin
+#[tokio::test]
+async fn test_t31_route_conflicts() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let plugins_dir = supervisor.get_plugins_dir();
+
+    // t31-beta declares the route of t31-alpha; t31-api takes a built-in path
+    create_test_plugin(&plugins_dir, "t31-alpha");
+    let beta = create_test_plugin(&plugins_dir, "t31-beta");
+    let script = fs::read_to_string(&beta).unwrap();
+    fs::write(&beta, script.replace("\"/t31-beta\"", "\"/t31-alpha\"")).unwrap();
+    let reserved = create_test_plugin(&plugins_dir, "t31-api");
+    let script = fs::read_to_string(&reserved).unwrap();
+    fs::write(&reserved, script.replace("\"/t31-api\"", "\"/api\"")).unwrap();
+
+    let discovered = supervisor
+        .scan_plugins_directory()
+        .await
+        .expect("Scan should succeed");
+    assert!(!discovered.contains_key("t31-api"));
+
+    supervisor.initialize().await.expect("Failed to initialize");
+
+    // The first plugin by ID keeps the route; the other is listed but not started
+    assert_eq!(
+        supervisor.get_plugin_for_route("/t31-alpha").as_deref(),
+        Some("t31-alpha")
+    );
+    let beta = supervisor
+        .get_plugin_status("t31-beta")
+        .expect("Conflicting plugin should be listed");
+    assert!(!beta.enabled && !beta.is_running());
+    assert_eq!(
+        supervisor.route_conflict("t31-beta"),
+        Some("Route /t31-alpha is already used by plugin t31-alpha")
+    );
+    assert!(supervisor.route_conflict("t31-alpha").is_none());
+
+    // Enabling it again fails as long as the route is taken
+    assert!(supervisor.enable_plugin("t31-beta").await.is_err());
+
+    supervisor.kill_plugin("t31-alpha").await.ok();
+    println!("✅ T31: Route conflicts reported instead of shadowing a plugin");
+}
+
+// ============ T32: Heartbeat Tests ============
+
+/// Test T32: A plugin that stops answering pings is reported hung, a silent one is not
+#[tokio::test]
+async fn test_t32_ping_detects_hung_plugin() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let binary_path = copy_test_binary(&temp_dir);
+    let plugins_dir = supervisor.get_plugins_dir();
+
+    let metadata = toru_plugin_api::PluginMetadata {
+        id: "t32-ping".to_string(),
+        name: "Hello World (Rust)".to_string(),
+        version: "0.1.0".to_string(),
+        author: None,
+        icon: "🦀".to_string(),
+        route: "/t32-ping".to_string(),
+        transport: toru_plugin_api::TransportKind::Unix,
+        websockets: Vec::new(),
+        subscriptions: Vec::new(),
+        capabilities: Vec::new(),
+        dependencies: Vec::new(),
+        limits: toru_plugin_api::PluginLimits::default(),
+        protocol_version: toru_plugin_api::PROTOCOL_VERSION,
+    };
+    supervisor
+        .spawn_plugin("t32-ping", &binary_path, metadata)
+        .await
+        .expect("Failed to spawn plugin");
+    // The shell test plugin never reads its socket, like a plugin that predates pings
+    let silent = create_test_plugin(&plugins_dir, "t32-silent");
+    let silent_metadata = toru_plugin_api::PluginMetadata {
+        id: "t32-silent".to_string(),
+        route: "/t32-silent".to_string(),
+        ..supervisor
+            .get_plugin_status("t32-ping")
+            .unwrap()
+            .metadata
+            .clone()
+            .unwrap()
+    };
+    supervisor
+        .spawn_plugin("t32-silent", &silent, silent_metadata)
+        .await
+        .expect("Failed to spawn plugin");
+    for _ in 0..20 {
+        if supervisor
+            .get_plugin_status("t32-ping")
+            .is_some_and(|p| p.endpoint_ready())
+        {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    let pid = supervisor
+        .get_plugin_status("t32-ping")
+        .unwrap()
+        .pid
+        .unwrap();
+    let supervisor = std::sync::Arc::new(tokio::sync::Mutex::new(supervisor));
+
+    assert!(plugins::check_heartbeats(&supervisor).await.is_empty());
+    assert!(supervisor.lock().await.answers_pings("t32-ping"));
+    assert!(!supervisor.lock().await.answers_pings("t32-silent"));
+
+    // A stopped process keeps its PID and socket but can't answer
+    unsafe { libc::kill(pid as i32, libc::SIGSTOP) };
+    assert!(plugins::check_heartbeats(&supervisor).await.is_empty());
+    {
+        let mut sup = supervisor.lock().await;
+        assert!(sup.check_plugin_health("t32-ping"));
+        assert!(!sup.record_ping("t32-ping", false));
+        assert!(sup.record_ping("t32-ping", false));
+        // Plugins that never answered aren't held to it
+        for _ in 0..plugins::MAX_MISSED_PINGS {
+            assert!(!sup.record_ping("t32-silent", false));
+        }
+    }
+    unsafe { libc::kill(pid as i32, libc::SIGCONT) };
+
+    let mut sup = supervisor.lock().await;
+    sup.kill_plugin("t32-ping").await.ok();
+    sup.kill_plugin("t32-silent").await.ok();
+    println!("✅ T32: Hung plugin detected by check_heartbeats()");
+}
+
+// ============ T33: Plugin Instance Tests ============
+
+/// Test T33: A second instance of a plugin runs as a plugin of its own
+#[tokio::test]
+async fn test_t33_plugin_instances() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    copy_test_binary(&temp_dir);
+    supervisor.initialize().await.expect("Failed to initialize");
+
+    let settings = std::collections::HashMap::from([("target".to_string(), "s3".to_string())]);
+    let instance = supervisor
+        .create_instance("hello-plugin-rust", "second", settings)
+        .await
+        .expect("Failed to create instance");
+    assert_eq!(instance.id(), "hello-plugin-rust@second");
+    assert!(supervisor
+        .create_instance("hello-plugin-rust", "Bad Name", Default::default())
+        .await
+        .is_err());
+
+    let original = supervisor.get_plugin_status("hello-plugin-rust").unwrap();
+    let second = supervisor
+        .get_plugin_status("hello-plugin-rust@second")
+        .expect("Instance should be listed");
+    assert_ne!(original.pid, second.pid);
+    assert_ne!(original.socket_path, second.socket_path);
+    let route = second.metadata.as_ref().unwrap().route.clone();
+    assert_eq!(route, "/hello-rust-second");
+    assert_eq!(
+        supervisor.get_plugin_for_route(&route).as_deref(),
+        Some("hello-plugin-rust@second")
+    );
+
+    // The instance gets its name and settings in init
+    let request = toru_plugin_api::HttpRequest {
+        method: "GET".to_string(),
+        path: "/".to_string(),
+        headers: std::collections::HashMap::new(),
+        body: None,
+        body_encoding: Default::default(),
+    };
+    let response = supervisor
+        .forward_http_request("hello-plugin-rust@second", &request)
+        .await
+        .expect("Instance should answer");
+    let json: serde_json::Value = serde_json::from_str(response.body.as_deref().unwrap()).unwrap();
+    assert_eq!(json["instance_name"], "second");
+    assert_eq!(json["settings"]["target"], "s3");
+
+    // Instances are found again on the next scan
+    let discovered = supervisor.scan_plugins_directory().await.unwrap();
+    assert_eq!(
+        discovered["hello-plugin-rust@second"].1.route,
+        "/hello-rust-second"
+    );
+
+    supervisor
+        .remove_instance("hello-plugin-rust@second")
+        .await
+        .expect("Failed to remove instance");
+    assert!(supervisor
+        .get_plugin_status("hello-plugin-rust@second")
+        .is_none());
+    assert!(supervisor.plugin_instances().is_empty());
+
+    supervisor.kill_plugin("hello-plugin-rust").await.ok();
+    println!("✅ T33: Plugin instances run side by side");
+}
+
+// ============ T34: Plugin Environment Tests ============
+
+/// Test T34: Admin-defined variables and host context reach the plugin process
+#[tokio::test]
+async fn test_t34_plugin_environment() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let supervisor = create_test_supervisor(&temp_dir).await;
+    let mut supervisor = supervisor.with_api_url("http://127.0.0.1:3000/api".to_string());
+    let plugins_dir = supervisor.get_plugins_dir();
+
+    // The shell plugin writes its environment into its data directory
+    let binary = create_test_plugin(&plugins_dir, "t34-env");
+    let script = fs::read_to_string(&binary).unwrap();
+    fs::write(
+        &binary,
+        script.replace(
+            "echo \"Plugin t34-env started\"",
+            "env > \"$TORU_PLUGIN_DATA_DIR/env.txt\"",
+        ),
+    )
+    .unwrap();
+
+    let env =
+        std::collections::HashMap::from([("BACKUP_TARGET".to_string(), "s3://bucket".to_string())]);
+    supervisor
+        .set_plugin_env("t34-env", env)
+        .await
+        .expect("Failed to set env");
+    let reserved =
+        std::collections::HashMap::from([("TORU_PLUGIN_ID".to_string(), "other".to_string())]);
+    assert!(supervisor
+        .set_plugin_env("t34-env", reserved)
+        .await
+        .is_err());
+
+    supervisor.initialize().await.expect("Failed to initialize");
+
+    let env_file = supervisor.plugin_data_dir("t34-env").join("env.txt");
+    let mut written = String::new();
+    for _ in 0..30 {
+        written = fs::read_to_string(&env_file).unwrap_or_default();
+        if written.contains("TORU_API_URL") {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    let vars: std::collections::HashMap<_, _> = written
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .collect();
+    assert_eq!(vars.get("BACKUP_TARGET"), Some(&"s3://bucket"));
+    assert_eq!(vars.get("TORU_PLUGIN_ID"), Some(&"t34-env"));
+    assert_eq!(vars.get("TORU_API_URL"), Some(&"http://127.0.0.1:3000/api"));
+    assert!(vars.contains_key("TORU_INSTANCE_ID"));
+    assert!(vars["TORU_PLUGIN_LOG_PATH"].ends_with("t34-env.log"));
+
+    supervisor.kill_plugin("t34-env").await.ok();
+    println!("✅ T34: Plugin environment injected at spawn");
+}
+
+// ============ T35: Plugin Dependency Tests ============
+
+/// Test T35: Plugins start after their dependencies and stay stopped while they're unmet
+#[tokio::test]
+async fn test_t35_plugin_dependencies() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let plugins_dir = supervisor.get_plugins_dir();
+
+    let with_dependencies = |plugin_id: &str, dependencies: &str| {
+        let binary = create_test_plugin(&plugins_dir, plugin_id);
+        let script = fs::read_to_string(&binary).unwrap();
+        let route = format!("\"route\": \"/{}\"", plugin_id);
+        fs::write(
+            &binary,
+            script.replace(
+                &route,
+                &format!("{}, \"dependencies\": {}", route, dependencies),
+            ),
+        )
+        .unwrap();
+    };
+    create_test_plugin(&plugins_dir, "t35-db");
+    with_dependencies("t35-app", r#"["t35-db>=1.0"]"#);
+    with_dependencies("t35-new", r#"["t35-db>=2.0"]"#);
+    with_dependencies("t35-orphan", r#"["t35-missing"]"#);
+    with_dependencies("t35-cycle-a", r#"["t35-cycle-b"]"#);
+    with_dependencies("t35-cycle-b", r#"["t35-cycle-a"]"#);
+
+    supervisor.initialize().await.expect("Failed to initialize");
+
+    let app = supervisor.get_plugin_status("t35-app").unwrap();
+    assert!(app.is_running());
+    assert!(supervisor.dependency_problems("t35-app").is_empty());
+
+    for (plugin_id, problem) in [
+        (
+            "t35-new",
+            "Dependency t35-db is version 1.0.0, 2.0 or newer is required",
+        ),
+        ("t35-orphan", "Dependency t35-missing is not installed"),
+        ("t35-cycle-a", "Dependency cycle"),
+        ("t35-cycle-b", "Dependency cycle"),
+    ] {
+        let process = supervisor
+            .get_plugin_status(plugin_id)
+            .expect("Plugin with unmet dependencies should be listed");
+        assert!(!process.is_running(), "{} should not run", plugin_id);
+        assert_eq!(supervisor.dependency_problems(plugin_id), [problem]);
+    }
+
+    // A disabled dependency is reported, and keeps dependents from being enabled
+    supervisor
+        .disable_plugin("t35-db")
+        .await
+        .expect("Failed to disable plugin");
+    assert_eq!(
+        supervisor.dependency_problems("t35-app"),
+        ["Dependency t35-db is disabled"]
+    );
+    supervisor.disable_plugin("t35-app").await.ok();
+    let err = supervisor.enable_plugin("t35-app").await.unwrap_err();
+    assert!(err.to_string().contains("Dependency t35-db is disabled"));
+
+    println!("✅ T35: Dependencies ordered and enforced");
+}
+
+// ============ T36: Plugin Upgrade Tests ============
+
+/// Test T36: An upgrade swaps in a newer binary, migrates, and rolls back when the new one fails
+#[tokio::test]
+async fn test_t36_plugin_upgrade_and_rollback() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mut supervisor = create_test_supervisor(&temp_dir).await;
+    let plugins_dir = supervisor.get_plugins_dir();
+    fs::create_dir_all(&plugins_dir).expect("Failed to create plugins dir");
+    let binary_path = plugins_dir.join("hello-plugin-rust.binary");
+
+    // An old version that never opens its socket, installed before the real plugin
+    let source_dir = temp_dir.path().join("upload");
+    fs::create_dir_all(&source_dir).expect("Failed to create upload dir");
+    let script = fs::read_to_string(create_test_plugin(&source_dir, "hello-plugin-rust"))
+        .expect("Failed to read test plugin");
+    let script_package = |version: &str| PluginPackage {
+        binary: script.replace("1.0.0", version).into_bytes(),
+        bundle: None,
+        signature: None,
+        assets: Vec::new(),
+    };
+    let hello_package = || PluginPackage {
+        binary: fs::read("plugins/hello-plugin-rust.binary").expect("Failed to read test binary"),
+        bundle: None,
+        signature: None,
+        assets: Vec::new(),
+    };
+    fs::write(&binary_path, script.replace("1.0.0", "0.0.1")).expect("Failed to write plugin");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&binary_path, fs::Permissions::from_mode(0o755))
+            .expect("Failed to set permissions");
+    }
+
+    // 0.0.1 -> 0.1.0 starts the real plugin, which answers the migrate message
+    let metadata = supervisor
+        .upgrade_plugin("hello-plugin-rust", hello_package())
+        .await
+        .expect("Upgrade should succeed");
+    assert_eq!(metadata.version, "0.1.0");
+    let status = supervisor.get_plugin_status("hello-plugin-rust").unwrap();
+    assert!(status.is_running());
+    assert_eq!(supervisor.pending_migration("hello-plugin-rust"), None);
+    assert!(!plugins_dir.join("hello-plugin-rust.binary.prev").exists());
+
+    // The same version again is not an upgrade
+    assert!(matches!(
+        supervisor
+            .upgrade_plugin("hello-plugin-rust", hello_package())
+            .await,
+        Err(InstallError::NotNewer { .. })
+    ));
+
+    // A newer version that fails init is rolled back to the running 0.1.0
+    let err = supervisor
+        .upgrade_plugin("hello-plugin-rust", script_package("9.0.0"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, InstallError::RolledBack { .. }), "{}", err);
+    assert_eq!(
+        fs::read(&binary_path).unwrap(),
+        hello_package().binary,
+        "Previous binary should be restored"
+    );
+    let status = supervisor.get_plugin_status("hello-plugin-rust").unwrap();
+    assert_eq!(status.metadata.as_ref().unwrap().version, "0.1.0");
+    assert!(status.is_running());
+    assert_eq!(supervisor.pending_migration("hello-plugin-rust"), None);
+
+    // Only installed plugins can be upgraded
+    assert!(matches!(
+        supervisor
+            .upgrade_plugin("other-plugin", script_package("2.0.0"))
+            .await,
+        Err(InstallError::NotInstalled(_))
+    ));
+
+    supervisor.kill_plugin("hello-plugin-rust").await.ok();
+    println!("✅ T36: Plugin upgraded, migrated and rolled back via upgrade_plugin()");
+}
@@ -0,0 +1,112 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PluginError, PluginResult};
+use crate::types::{HttpBodyChunk, HttpMessageResponse, HttpRequest, HttpResponse};
+
+/// How the bytes of an HTTP body are carried in its JSON string
+///
+/// Text bodies travel as they are; anything that isn't valid UTF-8 (uploads,
+/// images, archives) is base64 encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+impl BodyEncoding {
+    /// Encode bytes, as plain text when they are valid UTF-8
+    pub fn encode(bytes: &[u8]) -> (String, Self) {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => (text.to_string(), Self::Utf8),
+            Err(_) => (STANDARD.encode(bytes), Self::Base64),
+        }
+    }
+
+    /// Bytes of a body string in this encoding
+    pub fn decode(&self, body: &str) -> PluginResult<Vec<u8>> {
+        match self {
+            Self::Utf8 => Ok(body.as_bytes().to_vec()),
+            Self::Base64 => STANDARD
+                .decode(body)
+                .map_err(|e| PluginError::Protocol(format!("Invalid base64 body: {}", e))),
+        }
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        *self == Self::Utf8
+    }
+}
+
+macro_rules! body_accessors {
+    ($($type:ty),*) => {$(
+        impl $type {
+            /// Decoded body bytes
+            pub fn body_bytes(&self) -> PluginResult<Option<Vec<u8>>> {
+                self.body
+                    .as_deref()
+                    .map(|body| self.body_encoding.decode(body))
+                    .transpose()
+            }
+
+            /// Set the body from bytes, base64 encoding them if they aren't text
+            pub fn set_body_bytes(&mut self, bytes: &[u8]) {
+                let (body, encoding) = BodyEncoding::encode(bytes);
+                self.body = Some(body);
+                self.body_encoding = encoding;
+            }
+        }
+    )*};
+}
+
+body_accessors!(HttpRequest, HttpResponse, HttpMessageResponse);
+
+impl HttpBodyChunk {
+    /// A chunk carrying `bytes`, base64 encoded if they aren't text
+    pub fn new(bytes: &[u8], done: bool) -> Self {
+        let (data, encoding) = BodyEncoding::encode(bytes);
+        Self {
+            data,
+            encoding,
+            done,
+        }
+    }
+
+    /// Decoded chunk bytes
+    pub fn bytes(&self) -> PluginResult<Vec<u8>> {
+        self.encoding.decode(&self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_binary_body_round_trips() {
+        let bytes = [0x89, b'P', b'N', b'G', 0x00, 0xff];
+        let mut response = HttpResponse {
+            status: 200,
+            headers: HashMap::new(),
+            body: None,
+            body_encoding: BodyEncoding::Utf8,
+        };
+        response.set_body_bytes(&bytes);
+        assert_eq!(response.body_encoding, BodyEncoding::Base64);
+
+        let json = serde_json::to_string(&response).unwrap();
+        let parsed: HttpResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.body_bytes().unwrap().unwrap(), bytes);
+
+        // Text stays readable and the encoding is left out of the JSON
+        response.set_body_bytes(b"hello");
+        assert_eq!(response.body.as_deref(), Some("hello"));
+        assert!(!serde_json::to_string(&response)
+            .unwrap()
+            .contains("body_encoding"));
+    }
+}
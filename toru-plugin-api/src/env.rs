@@ -0,0 +1,23 @@
+//! Environment variables the host sets for every native plugin
+//!
+//! The transport variables (`TORU_PLUGIN_ADDRESS`, ...) live in
+//! [`transport`](crate::transport). Admins can add variables of their own per
+//! plugin, but never ones starting with [`RESERVED_PREFIX`].
+
+/// Plugin ID (for an instance, `<plugin>@<name>`)
+pub const PLUGIN_ID_ENV: &str = "TORU_PLUGIN_ID";
+
+/// ID of the Steering Center installation running the plugin
+pub const INSTANCE_ID_ENV: &str = "TORU_INSTANCE_ID";
+
+/// Directory the plugin may keep files in; created before the plugin starts
+pub const DATA_DIR_ENV: &str = "TORU_PLUGIN_DATA_DIR";
+
+/// File the host writes the plugin's log to
+pub const LOG_PATH_ENV: &str = "TORU_PLUGIN_LOG_PATH";
+
+/// Base URL of the host's HTTP API, e.g. `http://127.0.0.1:3000/api`
+pub const API_URL_ENV: &str = "TORU_API_URL";
+
+/// Prefix of the variables set by the host
+pub const RESERVED_PREFIX: &str = "TORU_";
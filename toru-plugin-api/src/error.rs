@@ -10,6 +10,9 @@ pub enum PluginError {
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
+    #[error("MessagePack error: {0}")]
+    MessagePack(String),
+
     #[error("Protocol error: {0}")]
     Protocol(String),
 
@@ -1,11 +1,21 @@
+pub mod body;
+pub mod env;
 pub mod error;
 pub mod message;
 pub mod protocol;
+pub mod server;
+pub mod system;
+pub mod transport;
 pub mod types;
 
+pub use body::BodyEncoding;
 pub use error::{PluginError, PluginResult};
 pub use message::Message;
-pub use protocol::PluginProtocol;
+pub use protocol::{PluginProtocol, WireFormat};
+pub use server::{MemoryKvStore, PluginServer};
+pub use system::{CpuCore, DiskInfo, NetworkInterface, SystemResources};
+pub use toru_plugin_macros::toru_plugin;
+pub use transport::{PluginListener, PluginStream, PluginTransport, TransportKind};
 pub use types::{KvMessagePayload, *};
 
 #[async_trait::async_trait]
@@ -17,4 +27,12 @@ pub trait ToruPlugin {
     async fn handle_http(&self, req: HttpRequest) -> PluginResult<HttpResponse>;
 
     async fn handle_kv(&mut self, op: KvOp) -> PluginResult<Option<String>>;
+
+    /// Bring data written by an older version up to date after an upgrade
+    ///
+    /// Called after `init` when the host upgraded the plugin from `from_version`.
+    /// An error makes the host roll back to the previous binary.
+    async fn migrate(&mut self, _from_version: &str) -> PluginResult<()> {
+        Ok(())
+    }
 }
@@ -1,29 +1,76 @@
-use crate::{error::PluginResult, types::Message};
-use tokio::net::UnixStream;
+use crate::{
+    error::{PluginError, PluginResult},
+    types::Message,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
 
 /// Maximum message size to prevent memory exhaustion attacks (16 MB)
 const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
 
-pub struct PluginProtocol;
+/// Set in the length prefix of frames whose message is MessagePack instead of
+/// JSON; lengths never get this big
+const MESSAGE_PACK_FLAG: u32 = 1 << 31;
+
+/// How messages are encoded inside their frames
+///
+/// Every frame says how it is encoded, so a reader takes either; the format
+/// only decides what gets written. Both sides write JSON until the plugin
+/// picks another format offered in `init` (see `Message::new_wire_format`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WireFormat {
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginProtocol {
+    format: WireFormat,
+}
 
 impl PluginProtocol {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// A protocol writing messages in `format`
+    pub fn with_format(format: WireFormat) -> Self {
+        Self { format }
+    }
+
+    /// Format messages are written in
+    pub fn format(&self) -> WireFormat {
+        self.format
     }
 
-    pub async fn read_message(&mut self, stream: &mut UnixStream) -> PluginResult<Message> {
-        use tokio::io::{AsyncReadExt, BufReader};
+    /// Write messages in `format` from now on
+    pub fn set_format(&mut self, format: WireFormat) {
+        self.format = format;
+    }
+
+    /// Read a length-prefixed message from any async stream (Unix socket, TCP, ...)
+    ///
+    /// Reads exactly one message and nothing more, so the stream can be kept
+    /// open and used for further messages.
+    pub async fn read_message<S>(&mut self, stream: &mut S) -> PluginResult<Message>
+    where
+        S: AsyncRead + Unpin,
+    {
+        use tokio::io::AsyncReadExt;
 
-        let mut reader = BufReader::new(stream);
         let mut length_buf = [0u8; 4];
 
-        reader.read_exact(&mut length_buf).await?;
+        stream.read_exact(&mut length_buf).await?;
 
-        let length = u32::from_be_bytes(length_buf) as usize;
+        let prefix = u32::from_be_bytes(length_buf);
+        let length = (prefix & !MESSAGE_PACK_FLAG) as usize;
 
         // Security: Prevent memory exhaustion from malicious length values
         if length > MAX_MESSAGE_SIZE {
-            return Err(crate::error::PluginError::Protocol(format!(
+            return Err(PluginError::Protocol(format!(
                 "Message size {} exceeds maximum allowed size {}",
                 length, MAX_MESSAGE_SIZE
             )));
@@ -31,33 +78,103 @@ impl PluginProtocol {
 
         let mut msg_buf = vec![0u8; length];
 
-        reader.read_exact(&mut msg_buf).await?;
+        stream.read_exact(&mut msg_buf).await?;
 
+        if prefix & MESSAGE_PACK_FLAG != 0 {
+            return rmp_serde::from_slice(&msg_buf)
+                .map_err(|e| PluginError::MessagePack(e.to_string()));
+        }
         let message: Message = serde_json::from_slice(&msg_buf)?;
 
         Ok(message)
     }
 
-    pub async fn write_message(
-        &self,
-        stream: &mut UnixStream,
-        message: &Message,
-    ) -> PluginResult<()> {
+    /// Write a length-prefixed message to any async stream (Unix socket, TCP, ...)
+    pub async fn write_message<S>(&self, stream: &mut S, message: &Message) -> PluginResult<()>
+    where
+        S: AsyncWrite + Unpin,
+    {
         use tokio::io::AsyncWriteExt;
 
-        let json = serde_json::to_vec(message)?;
-        let length = json.len() as u32;
+        let (body, flag) = match self.format {
+            WireFormat::Json => (serde_json::to_vec(message)?, 0),
+            // Named fields: the tagged and flattened payloads need maps, not arrays
+            WireFormat::MessagePack => (
+                rmp_serde::to_vec_named(message)
+                    .map_err(|e| PluginError::MessagePack(e.to_string()))?,
+                MESSAGE_PACK_FLAG,
+            ),
+        };
+        if body.len() > MAX_MESSAGE_SIZE {
+            return Err(PluginError::Protocol(format!(
+                "Message size {} exceeds maximum allowed size {}",
+                body.len(),
+                MAX_MESSAGE_SIZE
+            )));
+        }
+        let prefix = body.len() as u32 | flag;
 
-        stream.write_all(&length.to_be_bytes()).await?;
-        stream.write_all(&json).await?;
+        stream.write_all(&prefix.to_be_bytes()).await?;
+        stream.write_all(&body).await?;
         stream.flush().await?;
 
         Ok(())
     }
 }
 
-impl Default for PluginProtocol {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_formats_mix_on_one_stream() {
+        let init = LifecycleInitPayload {
+            instance_id: "instance-1".to_string(),
+            plugin_socket: String::new(),
+            log_path: String::new(),
+            protocol_version: crate::PROTOCOL_VERSION,
+            wire_formats: vec![WireFormat::MessagePack, WireFormat::Json],
+            instance_name: Some("offsite".to_string()),
+            settings: HashMap::from([("target".to_string(), "s3".to_string())]),
+        };
+        let request = HttpRequest {
+            method: "POST".to_string(),
+            path: "/upload".to_string(),
+            headers: HashMap::new(),
+            body: None,
+            body_encoding: Default::default(),
+        };
+        let messages = [
+            Message::new_lifecycle("init", Some(init)),
+            Message::new_wire_format(WireFormat::MessagePack),
+            Message::new_http("r1".to_string(), request),
+            Message::new_kv(
+                "r2".to_string(),
+                KvOp::Get {
+                    key: "k".to_string(),
+                },
+            ),
+            Message::new_kv_response("r2".to_string(), Some("v".to_string())),
+            Message::new_event("hello", serde_json::json!({ "n": [1, 2.5, null] })),
+            Message::new_http_chunk("r1".to_string(), &[0, 159, 255], true),
+        ];
+
+        let (mut a, mut b) = tokio::io::duplex(64 * 1024);
+        let mut json = PluginProtocol::new();
+        let msgpack = PluginProtocol::with_format(WireFormat::MessagePack);
+        for (i, message) in messages.iter().enumerate() {
+            let writer = if i % 2 == 0 { &msgpack } else { &json };
+            writer.write_message(&mut a, message).await.unwrap();
+        }
+        for message in &messages {
+            let read = json.read_message(&mut b).await.unwrap();
+            // Same message, whichever way it was encoded
+            assert_eq!(
+                serde_json::to_value(&read).unwrap(),
+                serde_json::to_value(message).unwrap()
+            );
+        }
     }
 }
@@ -0,0 +1,550 @@
+//! Ready-made plugin process: socket, message loop and response framing
+//!
+//! With this a native plugin is just its [`ToruPlugin`] implementation:
+//!
+//! ```no_run
+//! # use toru_plugin_api::*;
+//! # struct MyPlugin;
+//! # #[async_trait::async_trait]
+//! # impl ToruPlugin for MyPlugin {
+//! #     fn metadata() -> PluginMetadata { unimplemented!() }
+//! #     async fn init(&mut self, _ctx: PluginContext) -> PluginResult<()> { Ok(()) }
+//! #     async fn handle_http(&self, _req: HttpRequest) -> PluginResult<HttpResponse> { unimplemented!() }
+//! #     async fn handle_kv(&mut self, _op: KvOp) -> PluginResult<Option<String>> { Ok(None) }
+//! # }
+//! #[tokio::main]
+//! async fn main() -> PluginResult<()> {
+//!     PluginServer::run(MyPlugin).await
+//! }
+//! ```
+
+use crate::transport::SECRET_ENV;
+use crate::{
+    HttpMessageResponse, KvMessagePayload, KvPage, Message, MessagePayload, PluginConfig,
+    PluginContext, PluginError, PluginKvStore, PluginListener, PluginProtocol, PluginResult,
+    PluginTransport, ToruPlugin, WireFormat,
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Runs a [`ToruPlugin`] as a plugin process
+pub struct PluginServer<P> {
+    plugin: P,
+    protocol: PluginProtocol,
+    id: String,
+    /// Format to switch to when the host offers it
+    wire_format: WireFormat,
+}
+
+impl<P: ToruPlugin + Send + Sync> PluginServer<P> {
+    pub fn new(plugin: P) -> Self {
+        Self {
+            plugin,
+            protocol: PluginProtocol::new(),
+            id: P::metadata().id,
+            wire_format: WireFormat::Json,
+        }
+    }
+
+    /// Ask the host to switch to `format` (e.g. MessagePack for binary-heavy
+    /// plugins) if it offers it in `init`
+    pub fn with_wire_format(mut self, format: WireFormat) -> Self {
+        self.wire_format = format;
+        self
+    }
+
+    /// Entry point for a plugin binary
+    ///
+    /// Prints the metadata when started with `--metadata`. Otherwise listens on
+    /// the address the supervisor passed in (or `/tmp/toru-plugins/<id>.sock`
+    /// when started by hand) and serves the host until it sends `shutdown`.
+    pub async fn run(plugin: P) -> PluginResult<()> {
+        Self::new(plugin).start().await
+    }
+
+    /// [`run`](Self::run) for a server set up with the `with_*` methods
+    pub async fn start(self) -> PluginResult<()> {
+        if std::env::args().nth(1).as_deref() == Some("--metadata") {
+            println!("{}", serde_json::to_string_pretty(&P::metadata())?);
+            return Ok(());
+        }
+
+        let server = self;
+        let transport = PluginTransport::from_env().unwrap_or_else(|_| {
+            PluginTransport::Unix(format!("/tmp/toru-plugins/{}.sock", server.id).into())
+        });
+        eprintln!("[{}] Listening on {}", server.id, transport);
+
+        // TCP connections must present the secret from TORU_PLUGIN_SECRET
+        let listener = transport.bind(std::env::var(SECRET_ENV).ok()).await?;
+        server.listen(listener).await
+    }
+
+    /// [`run`](Self::run) on a new Tokio runtime, for a synchronous `main`
+    pub fn run_blocking(plugin: P) -> PluginResult<()> {
+        tokio::runtime::Runtime::new()?.block_on(Self::run(plugin))
+    }
+
+    /// Serve connections from `listener` one after another until the host
+    /// sends `shutdown`
+    pub async fn listen(mut self, listener: PluginListener) -> PluginResult<()> {
+        loop {
+            let mut stream = match listener.accept().await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("[{}] Failed to accept connection: {}", self.id, e);
+                    continue;
+                }
+            };
+            if self.serve(&mut stream).await? {
+                eprintln!("[{}] Shutdown received", self.id);
+                return Ok(());
+            }
+        }
+    }
+
+    /// Handle messages on one connection until it closes
+    ///
+    /// # Returns
+    /// true if the host asked the plugin to shut down
+    pub async fn serve<S>(&mut self, stream: &mut S) -> PluginResult<bool>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        // Every connection starts out in JSON
+        self.protocol.set_format(WireFormat::Json);
+        loop {
+            let message = match self.protocol.read_message(stream).await {
+                Ok(message) => message,
+                Err(e) => {
+                    eprintln!("[{}] Connection closed: {}", self.id, e);
+                    return Ok(false);
+                }
+            };
+
+            let reply = match message.payload {
+                MessagePayload::Lifecycle {
+                    action,
+                    payload,
+                    from_version,
+                    ..
+                } => match action.as_str() {
+                    "init" => {
+                        let Some(init) = payload else {
+                            eprintln!("[{}] Init without payload", self.id);
+                            continue;
+                        };
+                        let format = self.wire_format;
+                        if format != WireFormat::Json && init.wire_formats.contains(&format) {
+                            let switch = Message::new_wire_format(format);
+                            self.protocol.write_message(stream, &switch).await?;
+                            self.protocol.set_format(format);
+                        }
+                        let ctx = PluginContext {
+                            instance_id: init.instance_id,
+                            config: PluginConfig {
+                                instance_name: init.instance_name,
+                                settings: init.settings,
+                                ..Default::default()
+                            },
+                            kv: Box::new(MemoryKvStore::default()),
+                        };
+                        if let Err(e) = self.plugin.init(ctx).await {
+                            eprintln!("[{}] Init failed: {}", self.id, e);
+                        }
+                        None
+                    }
+                    "migrate" => {
+                        let from_version = from_version.unwrap_or_default();
+                        let error = match self.plugin.migrate(&from_version).await {
+                            Ok(()) => None,
+                            Err(e) => {
+                                eprintln!("[{}] Migration failed: {}", self.id, e);
+                                Some(e.to_string())
+                            }
+                        };
+                        message
+                            .request_id
+                            .map(|request_id| Message::new_migrated(request_id, error))
+                    }
+                    "shutdown" => return Ok(true),
+                    _ => None,
+                },
+                MessagePayload::Http {
+                    request_id,
+                    payload,
+                } => {
+                    let response = match self.plugin.handle_http(payload).await {
+                        Ok(response) => HttpMessageResponse {
+                            status: response.status,
+                            headers: response.headers,
+                            body: response.body,
+                            body_encoding: response.body_encoding,
+                            stream: false,
+                        },
+                        Err(e) => error_response(&e),
+                    };
+                    Some(Message::new_http_response(request_id, response))
+                }
+                MessagePayload::Kv {
+                    request_id,
+                    payload: KvMessagePayload::Request(op),
+                } => match self.plugin.handle_kv(op).await {
+                    Ok(value) => Some(Message::new_kv_response(request_id, value)),
+                    Err(e) => {
+                        eprintln!("[{}] KV operation failed: {}", self.id, e);
+                        Some(Message::new_kv_response(request_id, None))
+                    }
+                },
+                MessagePayload::Ping { request_id } => Some(Message::new_pong(request_id)),
+                _ => {
+                    eprintln!("[{}] Ignoring {} message", self.id, message.message_type);
+                    None
+                }
+            };
+
+            if let Some(reply) = reply {
+                self.protocol.write_message(stream, &reply).await?;
+            }
+        }
+    }
+}
+
+/// 500 response for a request the plugin failed to handle
+fn error_response(error: &PluginError) -> HttpMessageResponse {
+    HttpMessageResponse {
+        status: 500,
+        headers: HashMap::from([("Content-Type".to_string(), "application/json".to_string())]),
+        body: Some(serde_json::json!({ "error": error.to_string() }).to_string()),
+        body_encoding: Default::default(),
+        stream: false,
+    }
+}
+
+/// KV store handed to the plugin in its [`PluginContext`]
+///
+/// Lives in the plugin process, so values don't survive a restart.
+#[derive(Debug, Default)]
+pub struct MemoryKvStore {
+    /// Value and expiry of each key
+    values: Mutex<HashMap<String, (String, Option<Instant>)>>,
+}
+
+#[async_trait::async_trait]
+impl PluginKvStore for MemoryKvStore {
+    async fn get(&self, key: &str) -> PluginResult<Option<String>> {
+        let mut values = self.values.lock().unwrap();
+        match values.get(key) {
+            Some((_, Some(expires_at))) if *expires_at <= Instant::now() => {
+                values.remove(key);
+                Ok(None)
+            }
+            Some((value, _)) => Ok(Some(value.clone())),
+            None => Ok(None),
+        }
+    }
+
+    async fn list(&self, prefix: &str, cursor: Option<&str>, limit: usize) -> PluginResult<KvPage> {
+        let now = Instant::now();
+        let mut keys: Vec<String> = self
+            .values
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, (_, expires_at))| expires_at.is_none_or(|at| at > now))
+            .map(|(key, _)| key)
+            .filter(|key| key.starts_with(prefix) && cursor.is_none_or(|c| key.as_str() > c))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys.truncate(limit + 1);
+        Ok(KvPage::from_keys(keys, limit))
+    }
+
+    async fn set(&self, key: &str, value: &str) -> PluginResult<()> {
+        self.set_with_ttl(key, value, None).await
+    }
+
+    async fn incr(&self, key: &str, by: i64) -> PluginResult<i64> {
+        let mut values = self.values.lock().unwrap();
+        let now = Instant::now();
+        let (current, expires_at) = match values.get(key) {
+            Some((value, expires_at)) if expires_at.is_none_or(|at| at > now) => {
+                let current = value.parse::<i64>().map_err(|_| {
+                    PluginError::Internal(format!("Value of {} is not an integer", key))
+                })?;
+                (current, *expires_at)
+            }
+            _ => (0, None),
+        };
+        let value = current
+            .checked_add(by)
+            .ok_or_else(|| PluginError::Internal(format!("Value of {} overflowed", key)))?;
+        values.insert(key.to_string(), (value.to_string(), expires_at));
+        Ok(value)
+    }
+
+    async fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&str>,
+        new: &str,
+    ) -> PluginResult<bool> {
+        let mut values = self.values.lock().unwrap();
+        let now = Instant::now();
+        let current = values
+            .get(key)
+            .filter(|(_, expires_at)| expires_at.is_none_or(|at| at > now))
+            .map(|(value, _)| value.as_str());
+        if current != expected {
+            return Ok(false);
+        }
+        values.insert(key.to_string(), (new.to_string(), None));
+        Ok(true)
+    }
+
+    async fn set_with_ttl(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: Option<Duration>,
+    ) -> PluginResult<()> {
+        let expires_at = ttl.map(|ttl| Instant::now() + ttl);
+        self.values
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), (value.to_string(), expires_at));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> PluginResult<()> {
+        self.values.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HttpRequest, HttpResponse, KvOp, LifecycleInitPayload, PluginMetadata, TransportKind,
+    };
+
+    struct CounterPlugin {
+        ctx: Option<PluginContext>,
+    }
+
+    #[async_trait::async_trait]
+    impl ToruPlugin for CounterPlugin {
+        fn metadata() -> PluginMetadata {
+            PluginMetadata {
+                id: "counter".to_string(),
+                name: "Counter".to_string(),
+                version: "0.1.0".to_string(),
+                author: None,
+                icon: String::new(),
+                route: "/counter".to_string(),
+                transport: TransportKind::Unix,
+                limits: Default::default(),
+                websockets: Vec::new(),
+                subscriptions: Vec::new(),
+                capabilities: Vec::new(),
+                dependencies: Vec::new(),
+                protocol_version: crate::PROTOCOL_VERSION,
+            }
+        }
+
+        async fn init(&mut self, ctx: PluginContext) -> PluginResult<()> {
+            self.ctx = Some(ctx);
+            Ok(())
+        }
+
+        async fn handle_http(&self, req: HttpRequest) -> PluginResult<HttpResponse> {
+            let ctx = self.ctx.as_ref().ok_or(PluginError::NotInitialized)?;
+            if req.path == "/fail" {
+                return Err(PluginError::Internal("boom".to_string()));
+            }
+            Ok(HttpResponse {
+                status: 200,
+                headers: HashMap::new(),
+                body: Some(ctx.instance_id.clone()),
+                body_encoding: Default::default(),
+            })
+        }
+
+        async fn handle_kv(&mut self, op: KvOp) -> PluginResult<Option<String>> {
+            let kv = &self.ctx.as_ref().ok_or(PluginError::NotInitialized)?.kv;
+            match op {
+                KvOp::Get { key } => kv.get(&key).await,
+                KvOp::Set {
+                    key,
+                    value,
+                    ttl_secs,
+                } => kv
+                    .set_with_ttl(&key, &value, ttl_secs.map(Duration::from_secs))
+                    .await
+                    .map(|_| None),
+                KvOp::Delete { key } => kv.delete(&key).await.map(|_| None),
+                KvOp::List {
+                    prefix,
+                    cursor,
+                    limit,
+                } => {
+                    let page = kv
+                        .list(&prefix, cursor.as_deref(), KvOp::list_limit(limit))
+                        .await?;
+                    Ok(Some(serde_json::to_string(&page)?))
+                }
+                KvOp::Incr { key, by } => kv.incr(&key, by).await.map(|v| Some(v.to_string())),
+                KvOp::Cas { key, expected, new } => kv
+                    .compare_and_swap(&key, expected.as_deref(), &new)
+                    .await
+                    .map(|swapped| Some(swapped.to_string())),
+            }
+        }
+    }
+
+    fn request(path: &str) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            headers: HashMap::new(),
+            body: None,
+            body_encoding: Default::default(),
+        }
+    }
+
+    /// Body of the HTTP response in `message`
+    fn response(message: Message) -> HttpMessageResponse {
+        let MessagePayload::HttpResponse { payload, .. } = message.payload else {
+            panic!("Expected an HTTP response, got {:?}", message.payload);
+        };
+        payload
+    }
+
+    #[tokio::test]
+    async fn test_serve_dispatches_messages() {
+        let (mut host, mut plugin) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            let mut server = PluginServer::new(CounterPlugin { ctx: None });
+            server.serve(&mut plugin).await
+        });
+        let mut protocol = PluginProtocol::new();
+        let init = LifecycleInitPayload {
+            instance_id: "instance-1".to_string(),
+            plugin_socket: String::new(),
+            log_path: String::new(),
+            protocol_version: crate::PROTOCOL_VERSION,
+            wire_formats: Vec::new(),
+            instance_name: None,
+            settings: HashMap::new(),
+        };
+        let messages = [
+            Message::new_lifecycle("init", Some(init)),
+            Message::new_http("r1".to_string(), request("/")),
+            Message::new_http("r2".to_string(), request("/fail")),
+            Message::new_kv(
+                "r3".to_string(),
+                KvOp::Set {
+                    key: "count".to_string(),
+                    value: "1".to_string(),
+                    ttl_secs: None,
+                },
+            ),
+            Message::new_kv(
+                "r4".to_string(),
+                KvOp::Get {
+                    key: "count".to_string(),
+                },
+            ),
+            Message::new_ping("r5".to_string()),
+            Message::new_migrate("r6".to_string(), "0.0.1"),
+        ];
+        for message in &messages {
+            protocol.write_message(&mut host, message).await.unwrap();
+        }
+
+        let ok = protocol.read_message(&mut host).await.unwrap();
+        assert_eq!(ok.request_id.as_deref(), Some("r1"));
+        assert_eq!(response(ok).body.as_deref(), Some("instance-1"));
+        let failed = response(protocol.read_message(&mut host).await.unwrap());
+        assert_eq!(failed.status, 500);
+        assert!(failed.body.unwrap().contains("boom"));
+        let set = protocol.read_message(&mut host).await.unwrap();
+        assert_eq!(set.request_id.as_deref(), Some("r3"));
+        let get = protocol.read_message(&mut host).await.unwrap();
+        assert!(matches!(
+            get.payload,
+            MessagePayload::KvResponse { value: Some(ref v), .. } if v == "1"
+        ));
+        let pong = protocol.read_message(&mut host).await.unwrap();
+        assert!(
+            matches!(pong.payload, MessagePayload::Pong { ref request_id } if request_id == "r5")
+        );
+        let migrated = protocol.read_message(&mut host).await.unwrap();
+        assert_eq!(migrated.request_id.as_deref(), Some("r6"));
+        assert!(matches!(
+            migrated.payload,
+            MessagePayload::Lifecycle { ref action, error: None, .. } if action == "migrated"
+        ));
+
+        protocol
+            .write_message(&mut host, &Message::new_lifecycle("shutdown", None))
+            .await
+            .unwrap();
+        assert!(server.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_memory_kv_expiry() {
+        let kv = MemoryKvStore::default();
+        kv.set_with_ttl("cached", "1", Some(Duration::ZERO))
+            .await
+            .unwrap();
+        kv.set_with_ttl("kept", "2", Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        assert_eq!(kv.get("cached").await.unwrap(), None);
+        assert_eq!(kv.get("kept").await.unwrap().as_deref(), Some("2"));
+
+        // A plain set clears the expiry
+        kv.set("cached", "3").await.unwrap();
+        assert_eq!(kv.get("cached").await.unwrap().as_deref(), Some("3"));
+    }
+
+    #[tokio::test]
+    async fn test_memory_kv_list() {
+        let kv = MemoryKvStore::default();
+        for key in ["cache:b", "cache:a", "cache:c", "setting"] {
+            kv.set(key, "1").await.unwrap();
+        }
+        kv.set_with_ttl("cache:old", "1", Some(Duration::ZERO))
+            .await
+            .unwrap();
+
+        let first = kv.list("cache:", None, 2).await.unwrap();
+        assert_eq!(first.keys, ["cache:a", "cache:b"]);
+        let rest = kv
+            .list("cache:", first.next_cursor.as_deref(), 2)
+            .await
+            .unwrap();
+        assert_eq!(rest.keys, ["cache:c"]);
+        assert_eq!(rest.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn test_memory_kv_atomic_ops() {
+        let kv = MemoryKvStore::default();
+        assert_eq!(kv.incr("hits", 1).await.unwrap(), 1);
+        assert_eq!(kv.incr("hits", 5).await.unwrap(), 6);
+        kv.set("name", "toru").await.unwrap();
+        assert!(kv.incr("name", 1).await.is_err());
+
+        assert!(kv.compare_and_swap("lock", None, "a").await.unwrap());
+        assert!(!kv.compare_and_swap("lock", None, "b").await.unwrap());
+        assert!(kv.compare_and_swap("lock", Some("a"), "b").await.unwrap());
+        assert_eq!(kv.get("lock").await.unwrap().as_deref(), Some("b"));
+    }
+}
@@ -0,0 +1,92 @@
+//! Snapshot of the host's resource usage, as served by `GET /api/resources` and
+//! sent to plugins in `system_info_result` messages
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpuCore {
+    pub name: String,
+    pub usage: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskInfo {
+    pub name: String,
+    pub mount_point: String,
+    pub total_space: u64,
+    pub available_space: u64,
+    pub used_space: u64,
+    pub usage_percent: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub received: u64,
+    pub transmitted: u64,
+}
+
+/// A temperature sensor, e.g. a CPU package or an NVMe drive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemperatureSensor {
+    pub label: String,
+    pub temperature_celsius: f32,
+    /// Highest temperature seen since the sensor was first read
+    pub max_celsius: Option<f32>,
+    /// Temperature at which the hardware is in danger
+    pub critical_celsius: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanSensor {
+    pub label: String,
+    pub rpm: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuInfo {
+    pub name: String,
+    /// `nvidia` or `amd`
+    pub vendor: String,
+    pub utilization_percent: Option<f32>,
+    pub memory_used: Option<u64>,
+    pub memory_total: Option<u64>,
+    pub temperature_celsius: Option<f32>,
+}
+
+/// A battery, e.g. of a laptop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatteryInfo {
+    pub name: String,
+    pub percent: Option<f32>,
+    /// `Charging`, `Discharging`, `Full`, `Not charging` or `Unknown`
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemResources {
+    pub cpu_percent: f32,
+    pub cpu_cores: Vec<CpuCore>,
+    pub memory_percent: f32,
+    pub memory_used: u64,
+    pub memory_total: u64,
+    pub swap_used: u64,
+    pub swap_total: u64,
+    pub uptime_seconds: u64,
+    pub disks: Vec<DiskInfo>,
+    pub network: Vec<NetworkInterface>,
+    pub process_count: usize,
+    pub system_name: Option<String>,
+    pub kernel_version: Option<String>,
+    pub os_version: Option<String>,
+    pub host_name: Option<String>,
+    /// Empty where the host exposes no sensors (most VMs and containers)
+    #[serde(default)]
+    pub temperatures: Vec<TemperatureSensor>,
+    #[serde(default)]
+    pub fans: Vec<FanSensor>,
+    #[serde(default)]
+    pub gpus: Vec<GpuInfo>,
+    #[serde(default)]
+    pub batteries: Vec<BatteryInfo>,
+}
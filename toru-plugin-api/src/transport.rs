@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::error::{PluginError, PluginResult};
+
+/// Environment variable with the address the plugin must listen on
+pub const ADDRESS_ENV: &str = "TORU_PLUGIN_ADDRESS";
+
+/// Environment variable with the shared secret TCP connections must present
+pub const SECRET_ENV: &str = "TORU_PLUGIN_SECRET";
+
+/// Environment variable with the Unix socket path (kept for older plugins)
+pub const SOCKET_ENV: &str = "TORU_PLUGIN_SOCKET";
+
+/// Longest secret accepted during the handshake
+const MAX_SECRET_SIZE: usize = 256;
+
+/// How long a client gets to complete the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Transport a plugin asks for in its metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    #[default]
+    Unix,
+    Tcp,
+}
+
+/// Address a plugin listens on: a Unix socket path or `tcp://127.0.0.1:PORT`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginTransport {
+    Unix(PathBuf),
+    Tcp(SocketAddr),
+}
+
+impl PluginTransport {
+    /// Parse an address as written by `Display`; `unix://` prefixes are accepted too
+    pub fn parse(address: &str) -> PluginResult<Self> {
+        if let Some(addr) = address.strip_prefix("tcp://") {
+            let addr: SocketAddr = addr
+                .parse()
+                .map_err(|_| PluginError::Socket(format!("Invalid TCP address: {}", address)))?;
+            // Plugins are local processes; never expose them beyond loopback
+            if !addr.ip().is_loopback() {
+                return Err(PluginError::Socket(
+                    "TCP transport must use a loopback address".to_string(),
+                ));
+            }
+            return Ok(Self::Tcp(addr));
+        }
+
+        let path = address.strip_prefix("unix://").unwrap_or(address);
+        if path.is_empty() {
+            return Err(PluginError::Socket("Empty plugin address".to_string()));
+        }
+        Ok(Self::Unix(PathBuf::from(path)))
+    }
+
+    /// Address the supervisor told the plugin to listen on
+    pub fn from_env() -> PluginResult<Self> {
+        if let Ok(address) = std::env::var(ADDRESS_ENV) {
+            return Self::parse(&address);
+        }
+        std::env::var(SOCKET_ENV)
+            .map(|path| Self::Unix(PathBuf::from(path)))
+            .map_err(|_| PluginError::Socket(format!("{} is not set", ADDRESS_ENV)))
+    }
+
+    pub fn kind(&self) -> TransportKind {
+        match self {
+            Self::Unix(_) => TransportKind::Unix,
+            Self::Tcp(_) => TransportKind::Tcp,
+        }
+    }
+
+    /// Connect to a plugin listening on this address (supervisor side)
+    ///
+    /// TCP connections start by presenting `secret` to the plugin.
+    pub async fn connect(&self, secret: Option<&str>) -> PluginResult<PluginStream> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(path) => Ok(PluginStream::Unix(
+                tokio::net::UnixStream::connect(path).await?,
+            )),
+            #[cfg(not(unix))]
+            Self::Unix(_) => Err(unix_unsupported()),
+            Self::Tcp(addr) => {
+                let mut stream = tokio::net::TcpStream::connect(addr).await?;
+                stream.set_nodelay(true)?;
+                write_secret(&mut stream, secret.unwrap_or_default()).await?;
+                Ok(PluginStream::Tcp(stream))
+            }
+        }
+    }
+
+    /// Listen on this address (plugin side)
+    ///
+    /// TCP listeners only hand out connections that present `secret`.
+    pub async fn bind(&self, secret: Option<String>) -> PluginResult<PluginListener> {
+        let inner = match self {
+            #[cfg(unix)]
+            Self::Unix(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                // A stale socket from a previous run would make bind fail
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                ListenerInner::Unix(tokio::net::UnixListener::bind(path)?)
+            }
+            #[cfg(not(unix))]
+            Self::Unix(_) => return Err(unix_unsupported()),
+            Self::Tcp(addr) => ListenerInner::Tcp(tokio::net::TcpListener::bind(addr).await?),
+        };
+        Ok(PluginListener {
+            inner,
+            secret: secret.unwrap_or_default(),
+        })
+    }
+}
+
+impl fmt::Display for PluginTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unix(path) => write!(f, "{}", path.display()),
+            Self::Tcp(addr) => write!(f, "tcp://{}", addr),
+        }
+    }
+}
+
+/// Bind the address and secret the supervisor passed in the environment
+pub async fn listen_from_env() -> PluginResult<PluginListener> {
+    let transport = PluginTransport::from_env()?;
+    transport.bind(std::env::var(SECRET_ENV).ok()).await
+}
+
+#[cfg(not(unix))]
+fn unix_unsupported() -> PluginError {
+    PluginError::Socket("Unix sockets are not supported on this platform".to_string())
+}
+
+enum ListenerInner {
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+    Tcp(tokio::net::TcpListener),
+}
+
+/// Listener a plugin accepts supervisor connections on
+pub struct PluginListener {
+    inner: ListenerInner,
+    secret: String,
+}
+
+impl PluginListener {
+    /// Accept the next connection
+    ///
+    /// TCP connections that don't present the shared secret are dropped.
+    pub async fn accept(&self) -> PluginResult<PluginStream> {
+        loop {
+            match &self.inner {
+                #[cfg(unix)]
+                ListenerInner::Unix(listener) => {
+                    let (stream, _) = listener.accept().await?;
+                    return Ok(PluginStream::Unix(stream));
+                }
+                ListenerInner::Tcp(listener) => {
+                    let (mut stream, peer) = listener.accept().await?;
+                    let handshake =
+                        tokio::time::timeout(HANDSHAKE_TIMEOUT, read_secret(&mut stream)).await;
+                    match handshake {
+                        Ok(Ok(secret)) if secrets_match(&secret, &self.secret) => {
+                            stream.set_nodelay(true)?;
+                            return Ok(PluginStream::Tcp(stream));
+                        }
+                        _ => {
+                            eprintln!("Rejected plugin connection from {}: bad handshake", peer);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Address the listener is bound to
+    pub fn local_transport(&self) -> PluginResult<PluginTransport> {
+        match &self.inner {
+            #[cfg(unix)]
+            ListenerInner::Unix(listener) => {
+                let addr = listener.local_addr()?;
+                let path = addr
+                    .as_pathname()
+                    .ok_or_else(|| PluginError::Socket("Unix listener has no path".to_string()))?;
+                Ok(PluginTransport::Unix(path.to_path_buf()))
+            }
+            ListenerInner::Tcp(listener) => Ok(PluginTransport::Tcp(listener.local_addr()?)),
+        }
+    }
+}
+
+/// Connection between the supervisor and a plugin over either transport
+pub enum PluginStream {
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+    Tcp(tokio::net::TcpStream),
+}
+
+impl AsyncRead for PluginStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for PluginStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Handshake: the secret as a length-prefixed frame, like protocol messages
+async fn write_secret<S: AsyncWrite + Unpin>(stream: &mut S, secret: &str) -> PluginResult<()> {
+    stream
+        .write_all(&(secret.len() as u32).to_be_bytes())
+        .await?;
+    stream.write_all(secret.as_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_secret<S: AsyncRead + Unpin>(stream: &mut S) -> PluginResult<Vec<u8>> {
+    let mut length_buf = [0u8; 4];
+    stream.read_exact(&mut length_buf).await?;
+    let length = u32::from_be_bytes(length_buf) as usize;
+    if length > MAX_SECRET_SIZE {
+        return Err(PluginError::Protocol(
+            "Handshake secret too long".to_string(),
+        ));
+    }
+    let mut secret = vec![0u8; length];
+    stream.read_exact(&mut secret).await?;
+    Ok(secret)
+}
+
+/// Compare secrets without leaking the position of the first difference
+fn secrets_match(given: &[u8], expected: &str) -> bool {
+    let expected = expected.as_bytes();
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_round_trips() {
+        for address in ["/tmp/toru-plugins/hello.sock", "tcp://127.0.0.1:4100"] {
+            let transport = PluginTransport::parse(address).unwrap();
+            assert_eq!(transport.to_string(), address);
+        }
+        assert_eq!(
+            PluginTransport::parse("unix:///tmp/a.sock").unwrap(),
+            PluginTransport::Unix(PathBuf::from("/tmp/a.sock"))
+        );
+        assert!(PluginTransport::parse("tcp://0.0.0.0:4100").is_err());
+        assert!(PluginTransport::parse("tcp://localhost").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_handshake_requires_secret() {
+        let transport = PluginTransport::parse("tcp://127.0.0.1:0").unwrap();
+        let listener = transport.bind(Some("s3cret".to_string())).await.unwrap();
+        let address = listener.local_transport().unwrap();
+
+        let accepted = tokio::spawn(async move {
+            let mut stream = listener.accept().await.unwrap();
+            let mut buf = [0u8; 2];
+            stream.read_exact(&mut buf).await.unwrap();
+            buf
+        });
+
+        // The wrong secret is dropped, the right one gets through
+        let mut rejected = address.connect(Some("wrong")).await.unwrap();
+        let mut stream = address.connect(Some("s3cret")).await.unwrap();
+        stream.write_all(b"ok").await.unwrap();
+
+        assert_eq!(&accepted.await.unwrap(), b"ok");
+        let mut buf = [0u8; 1];
+        assert_eq!(rejected.read(&mut buf).await.unwrap_or(0), 0);
+    }
+}
@@ -1,6 +1,44 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::body::BodyEncoding;
+use crate::error::{PluginError, PluginResult};
+use crate::protocol::WireFormat;
+
+/// Version of the plugin protocol this crate speaks
+///
+/// Version 1 is the original protocol (lifecycle, HTTP and KV messages); 2 added
+/// streamed and binary bodies, WebSockets, events, plugin calls, scheduled jobs
+/// and pings; 3 answers requests with `http_response` and `kv_response` instead
+/// of wrapping the response in an `http` or `kv` message; 4 added the `migrate`
+/// lifecycle message sent after an upgrade.
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// Oldest protocol version a peer may speak
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Keys returned by a KV list without a `limit`
+pub const KV_LIST_DEFAULT_LIMIT: usize = 100;
+
+/// Most keys a single KV list returns
+pub const KV_LIST_MAX_LIMIT: usize = 1000;
+
+/// Version of plugins and messages from before the protocol was versioned
+fn legacy_protocol_version() -> u32 {
+    1
+}
+
+/// Check that a peer speaking `version` can be talked to
+pub fn check_protocol_version(version: u32) -> PluginResult<()> {
+    if (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+        return Ok(());
+    }
+    Err(PluginError::Protocol(format!(
+        "Protocol version {} is not supported (expected {} to {})",
+        version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+    )))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginMetadata {
     pub id: String,
@@ -9,6 +47,71 @@ pub struct PluginMetadata {
     pub author: Option<String>,
     pub icon: String,
     pub route: String,
+    /// How the supervisor connects to the plugin (Unix socket unless stated)
+    #[serde(default)]
+    pub transport: crate::transport::TransportKind,
+    /// Resources the plugin may use; the host config can override them
+    #[serde(default)]
+    pub limits: PluginLimits,
+    /// Paths under `<route>/ws/` that browsers may open WebSockets on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub websockets: Vec<String>,
+    /// Names of events published by other plugins this plugin wants to receive
+    ///
+    /// `"*"` matches every event and `"backup.*"` every event starting with `backup.`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subscriptions: Vec<String>,
+    /// Host features the plugin asks for, e.g. `call:metrics` to call the
+    /// `metrics` plugin; they only take effect once an admin grants them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<String>,
+    /// Plugins that must run before this one, as `metrics` or `metrics>=1.2.0`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    /// Protocol version the plugin was built against (1 when not stated)
+    #[serde(default = "legacy_protocol_version")]
+    pub protocol_version: u32,
+}
+
+impl PluginMetadata {
+    /// Whether the plugin subscribed to events with this name
+    pub fn subscribes_to(&self, event: &str) -> bool {
+        self.subscriptions
+            .iter()
+            .any(|pattern| pattern_matches(pattern, event))
+    }
+
+    /// Whether the plugin asks for a capability (`call:*` covers `call:metrics`)
+    pub fn requests_capability(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|pattern| pattern_matches(pattern, capability))
+    }
+}
+
+/// Match a name against a pattern that is either exact or a prefix ending in `*`
+pub fn pattern_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
+/// Memory and CPU limits for a plugin process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginLimits {
+    /// Maximum memory in MB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_mb: Option<u64>,
+    /// Maximum CPU usage in percent of one core (200 = two cores)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<u32>,
+}
+
+impl PluginLimits {
+    pub fn is_empty(&self) -> bool {
+        self.memory_mb.is_none() && self.cpu_percent.is_none()
+    }
 }
 
 pub struct PluginContext {
@@ -20,6 +123,11 @@ pub struct PluginContext {
 #[derive(Debug, Clone, Default)]
 pub struct PluginConfig {
     pub env: std::collections::HashMap<String, String>,
+    /// Name of the instance, when the host runs the plugin as one of several
+    /// named instances
+    pub instance_name: Option<String>,
+    /// Settings the admin gave this instance
+    pub settings: std::collections::HashMap<String, String>,
 }
 
 #[async_trait::async_trait]
@@ -27,6 +135,85 @@ pub trait PluginKvStore: Send + Sync {
     async fn get(&self, key: &str) -> crate::PluginResult<Option<String>>;
     async fn set(&self, key: &str, value: &str) -> crate::PluginResult<()>;
     async fn delete(&self, key: &str) -> crate::PluginResult<()>;
+
+    /// Set a value that reads as absent once `ttl` has passed
+    ///
+    /// Stores without expiry support reject a TTL; `None` is a plain `set`.
+    async fn set_with_ttl(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: Option<std::time::Duration>,
+    ) -> crate::PluginResult<()> {
+        match ttl {
+            None => self.set(key, value).await,
+            Some(_) => Err(crate::PluginError::Internal(
+                "This KV store does not support expiring keys".to_string(),
+            )),
+        }
+    }
+
+    /// List keys starting with `prefix` in key order, after `cursor`
+    ///
+    /// Pass the returned `next_cursor` to get the following page.
+    async fn list(
+        &self,
+        _prefix: &str,
+        _cursor: Option<&str>,
+        _limit: usize,
+    ) -> crate::PluginResult<KvPage> {
+        Err(crate::PluginError::Internal(
+            "This KV store does not support listing keys".to_string(),
+        ))
+    }
+
+    /// Atomically add `by` to the integer stored at `key` and return the result
+    ///
+    /// An absent key counts as 0. Fails if the value isn't an integer.
+    async fn incr(&self, _key: &str, _by: i64) -> crate::PluginResult<i64> {
+        Err(crate::PluginError::Internal(
+            "This KV store does not support atomic increments".to_string(),
+        ))
+    }
+
+    /// Atomically set `key` to `new` if it currently holds `expected`
+    ///
+    /// `expected: None` means the key must be absent. Returns whether the
+    /// value was swapped.
+    async fn compare_and_swap(
+        &self,
+        _key: &str,
+        _expected: Option<&str>,
+        _new: &str,
+    ) -> crate::PluginResult<bool> {
+        Err(crate::PluginError::Internal(
+            "This KV store does not support compare-and-swap".to_string(),
+        ))
+    }
+}
+
+/// One page of a KV key listing
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KvPage {
+    pub keys: Vec<String>,
+    /// Cursor for the next page; absent on the last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl KvPage {
+    /// Page from sorted keys, of which up to `limit + 1` were fetched
+    ///
+    /// Fetching one extra key tells whether there is a next page.
+    pub fn from_keys(mut keys: Vec<String>, limit: usize) -> Self {
+        let next_cursor = if keys.len() > limit {
+            keys.truncate(limit);
+            keys.last().cloned()
+        } else {
+            None
+        };
+        Self { keys, next_cursor }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +222,35 @@ pub struct HttpRequest {
     pub path: String,
     pub headers: std::collections::HashMap<String, String>,
     pub body: Option<String>,
+    /// How `body` is encoded (UTF-8 text unless stated)
+    #[serde(default, skip_serializing_if = "BodyEncoding::is_utf8")]
+    pub body_encoding: BodyEncoding,
+}
+
+/// Header with the ID the Steering Center gave a request, also sent back in its response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Header with the public path the plugin's route is reached under, e.g.
+/// `/steering/api/plugins/route/hello-plugin`, for links and redirects
+pub const FORWARDED_PREFIX_HEADER: &str = "x-forwarded-prefix";
+
+impl HttpRequest {
+    /// ID the Steering Center gave the request, to include in the plugin's logs
+    /// so a failing request can be traced across the host and the plugin
+    pub fn request_id(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(REQUEST_ID_HEADER))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Public path of the plugin's route, to prefix links and redirects with
+    pub fn forwarded_prefix(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(FORWARDED_PREFIX_HEADER))
+            .map(|(_, value)| value.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,14 +258,65 @@ pub struct HttpResponse {
     pub status: u16,
     pub headers: std::collections::HashMap<String, String>,
     pub body: Option<String>,
+    /// How `body` is encoded (UTF-8 text unless stated)
+    #[serde(default, skip_serializing_if = "BodyEncoding::is_utf8")]
+    pub body_encoding: BodyEncoding,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "action")]
 pub enum KvOp {
-    Get { key: String },
-    Set { key: String, value: String },
-    Delete { key: String },
+    Get {
+        key: String,
+    },
+    Set {
+        key: String,
+        value: String,
+        /// Seconds until the key expires; kept forever if absent
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ttl_secs: Option<u64>,
+    },
+    Delete {
+        key: String,
+    },
+    /// Answered with a [`KvPage`] as JSON in the response value
+    List {
+        #[serde(default)]
+        prefix: String,
+        /// `next_cursor` of the previous page
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cursor: Option<String>,
+        /// Defaults to [`KV_LIST_DEFAULT_LIMIT`], capped at [`KV_LIST_MAX_LIMIT`]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<usize>,
+    },
+    /// Answered with the new value
+    Incr {
+        key: String,
+        #[serde(default = "default_incr")]
+        by: i64,
+    },
+    /// Answered with `"true"` if the value was swapped, `"false"` otherwise
+    Cas {
+        key: String,
+        /// Current value the swap requires; absent if the key must not exist
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected: Option<String>,
+        new: String,
+    },
+}
+
+fn default_incr() -> i64 {
+    1
+}
+
+impl KvOp {
+    /// Page size a list asks for, after applying the default and the cap
+    pub fn list_limit(limit: Option<usize>) -> usize {
+        limit
+            .unwrap_or(KV_LIST_DEFAULT_LIMIT)
+            .clamp(1, KV_LIST_MAX_LIMIT)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +324,18 @@ pub struct LifecycleInitPayload {
     pub instance_id: String,
     pub plugin_socket: String,
     pub log_path: String,
+    /// Protocol version the host speaks to this plugin
+    #[serde(default = "legacy_protocol_version")]
+    pub protocol_version: u32,
+    /// Wire formats the host can read, preferred first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wire_formats: Vec<WireFormat>,
+    /// Name of the plugin instance, if the plugin runs as a named instance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_name: Option<String>,
+    /// Settings of the plugin instance
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub settings: std::collections::HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +346,18 @@ pub enum MessagePayload {
         action: String,
         #[serde(flatten)]
         payload: Option<LifecycleInitPayload>,
+        /// Jobs a plugin registers with `register_jobs` (Plugin → Core)
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        jobs: Vec<ScheduledJob>,
+        /// Format a plugin picks with `wire_format` (Plugin → Core)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        format: Option<WireFormat>,
+        /// Version the plugin was upgraded from, in `migrate` (Core → Plugin)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from_version: Option<String>,
+        /// Why the migration failed, in `migrated` (Plugin → Core)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
     },
     #[serde(rename = "http")]
     Http {
@@ -79,9 +370,261 @@ pub enum MessagePayload {
         #[serde(flatten)]
         payload: KvMessagePayload,
     },
+    /// Part of a streamed HTTP response body (Plugin → Core)
+    #[serde(rename = "http_chunk")]
+    HttpChunk {
+        request_id: String,
+        payload: HttpBodyChunk,
+    },
+    /// A frame on a WebSocket relayed between the browser and the plugin (both ways)
+    #[serde(rename = "ws_frame")]
+    WsFrame { socket_id: String, payload: WsFrame },
+    /// A named event published by a plugin (Plugin → Core) or forwarded to a
+    /// subscriber (Core → Plugin)
+    #[serde(rename = "event")]
+    Event { payload: PluginEventPayload },
+    /// A request to another plugin's HTTP handler (Plugin → Core)
+    #[serde(rename = "call_plugin")]
+    CallPlugin {
+        request_id: String,
+        payload: PluginCall,
+    },
+    /// A request to run a quick action or script through the host's task
+    /// executor (Plugin → Core)
+    #[serde(rename = "exec")]
+    Exec {
+        request_id: String,
+        payload: PluginExec,
+    },
+    /// The outcome of an `exec` request (Core → Plugin)
+    #[serde(rename = "exec_result")]
+    ExecResult {
+        request_id: String,
+        payload: PluginExecResult,
+    },
+    /// A notification to show to users in the Steering Center UI (Plugin → Core)
+    #[serde(rename = "notify")]
+    Notify { payload: PluginNotification },
+    /// A request for the host's resource usage (Plugin → Core)
+    #[serde(rename = "system_info")]
+    SystemInfo { request_id: String },
+    /// The answer to a `system_info` request (Core → Plugin)
+    #[serde(rename = "system_info_result")]
+    SystemInfoResult {
+        request_id: String,
+        payload: PluginSystemInfoResult,
+    },
+    /// A registered job is due (Core → Plugin)
+    #[serde(rename = "scheduled")]
+    Scheduled { job_id: String },
+    /// The outcome of a `call_plugin` request (Core → Plugin)
+    #[serde(rename = "call_result")]
+    CallResult {
+        request_id: String,
+        payload: PluginCallResult,
+    },
+    /// Health check; the plugin answers with a `pong` carrying the same
+    /// `request_id` (Core → Plugin)
+    #[serde(rename = "ping")]
+    Ping { request_id: String },
+    /// Answer to a `ping` (Plugin → Core)
+    #[serde(rename = "pong")]
+    Pong { request_id: String },
+    /// Answer to an `http` request (Plugin → Core)
+    #[serde(rename = "http_response")]
+    HttpResponse {
+        request_id: String,
+        payload: HttpMessageResponse,
+    },
+    /// Answer to a `kv` request (Plugin → Core)
+    #[serde(rename = "kv_response")]
+    KvResponse {
+        request_id: String,
+        value: Option<String>,
+    },
+}
+
+/// A cron-style job a plugin asks core to trigger
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    pub id: String,
+    /// Cron expression: `min hour day month weekday`, optionally with a leading
+    /// seconds field (`*/30 * * * * *` runs every 30 seconds)
+    pub schedule: String,
+}
+
+/// An HTTP request a plugin sends to another plugin through core
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginCall {
+    /// ID of the plugin to call
+    pub plugin: String,
+    pub request: HttpRequest,
+    /// How long to wait for the answer; core caps it at its own request timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+/// The called plugin's response, or why the call failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginCallResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<HttpMessageResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A task a plugin asks core to run: either a quick action or a script
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginExec {
+    /// ID of the quick action to run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// File name of a script in the scripts directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+}
+
+/// The task started for an `exec` request, or why none was
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginExecResult {
+    /// ID of the task in the task history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    /// `started`, or `queued` behind a running instance of the same script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// How prominently a notification is shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyLevel {
+    #[default]
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+/// A toast or alert a plugin shows in the Steering Center UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginNotification {
+    #[serde(default)]
+    pub level: NotifyLevel,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+/// The host's resource usage, or why it wasn't sent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginSystemInfoResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<crate::system::SystemResources>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A named event on the plugin event bus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginEventPayload {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub data: serde_json::Value,
+    /// Plugin that published the event; set by core when forwarding it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// A piece of a streamed response body; the last chunk has `done` set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpBodyChunk {
+    pub data: String,
+    /// How `data` is encoded (UTF-8 text unless stated)
+    #[serde(default, skip_serializing_if = "BodyEncoding::is_utf8")]
+    pub encoding: BodyEncoding,
+    #[serde(default)]
+    pub done: bool,
+}
+
+/// What a WebSocket frame carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WsFrameKind {
+    /// A browser connected to one of the plugin's `websockets` paths
+    Open,
+    Text,
+    Binary,
+    /// Either side closed the socket
+    Close,
+}
+
+/// A WebSocket frame; `path` is set on `open`, `data` on `text` and `binary`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsFrame {
+    pub kind: WsFrameKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// How `data` is encoded (UTF-8 text unless stated)
+    #[serde(default, skip_serializing_if = "BodyEncoding::is_utf8")]
+    pub encoding: BodyEncoding,
+}
+
+impl WsFrame {
+    pub fn open(path: &str) -> Self {
+        Self {
+            kind: WsFrameKind::Open,
+            path: Some(path.to_string()),
+            data: None,
+            encoding: BodyEncoding::Utf8,
+        }
+    }
+
+    pub fn text(text: &str) -> Self {
+        Self {
+            kind: WsFrameKind::Text,
+            path: None,
+            data: Some(text.to_string()),
+            encoding: BodyEncoding::Utf8,
+        }
+    }
+
+    pub fn binary(bytes: &[u8]) -> Self {
+        let (data, encoding) = BodyEncoding::encode(bytes);
+        Self {
+            kind: WsFrameKind::Binary,
+            path: None,
+            data: Some(data),
+            encoding,
+        }
+    }
+
+    pub fn close() -> Self {
+        Self {
+            kind: WsFrameKind::Close,
+            path: None,
+            data: None,
+            encoding: BodyEncoding::Utf8,
+        }
+    }
+
+    /// Decoded frame data (empty for `open` and `close`)
+    pub fn bytes(&self) -> crate::PluginResult<Vec<u8>> {
+        self.data
+            .as_deref()
+            .map(|data| self.encoding.decode(data))
+            .unwrap_or_else(|| Ok(Vec::new()))
+    }
 }
 
 /// KV message payload - can be either a request (operation) or response (value)
+///
+/// Plugins speaking protocol version 3 answer with `kv_response` instead of a
+/// `Response`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum KvMessagePayload {
@@ -93,6 +636,9 @@ pub enum KvMessagePayload {
 pub struct Message {
     #[serde(rename = "type")]
     pub message_type: String,
+    /// Protocol version of the sender (1 when not stated)
+    #[serde(default = "legacy_protocol_version")]
+    pub version: u32,
     pub timestamp: DateTime<Utc>,
     pub request_id: Option<String>,
     pub payload: MessagePayload,
@@ -102,11 +648,109 @@ impl Message {
     pub fn new_lifecycle(action: &str, init_payload: Option<LifecycleInitPayload>) -> Self {
         Self {
             message_type: "lifecycle".to_string(),
+            version: PROTOCOL_VERSION,
             timestamp: Utc::now(),
             request_id: None,
             payload: MessagePayload::Lifecycle {
                 action: action.to_string(),
                 payload: init_payload,
+                jobs: Vec::new(),
+                format: None,
+                from_version: None,
+                error: None,
+            },
+        }
+    }
+
+    /// Create the message a plugin registers its scheduled jobs with
+    ///
+    /// The jobs replace any the plugin registered before.
+    pub fn new_register_jobs(jobs: Vec<ScheduledJob>) -> Self {
+        Self {
+            message_type: "lifecycle".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: None,
+            payload: MessagePayload::Lifecycle {
+                action: "register_jobs".to_string(),
+                payload: None,
+                jobs,
+                format: None,
+                from_version: None,
+                error: None,
+            },
+        }
+    }
+
+    /// Create the message a plugin answers `init` with to switch to one of the
+    /// offered wire formats
+    ///
+    /// Both sides write in `format` from then on.
+    pub fn new_wire_format(format: WireFormat) -> Self {
+        Self {
+            message_type: "lifecycle".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: None,
+            payload: MessagePayload::Lifecycle {
+                action: "wire_format".to_string(),
+                payload: None,
+                jobs: Vec::new(),
+                format: Some(format),
+                from_version: None,
+                error: None,
+            },
+        }
+    }
+
+    /// Create the message asking a plugin to migrate its data after an upgrade
+    ///
+    /// Sent after `init` to a plugin that was upgraded from `from_version`; the
+    /// plugin answers with `migrated` carrying the same request ID.
+    pub fn new_migrate(request_id: String, from_version: &str) -> Self {
+        Self {
+            message_type: "lifecycle".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id),
+            payload: MessagePayload::Lifecycle {
+                action: "migrate".to_string(),
+                payload: None,
+                jobs: Vec::new(),
+                format: None,
+                from_version: Some(from_version.to_string()),
+                error: None,
+            },
+        }
+    }
+
+    /// Create the answer to `migrate`; `error` says why the migration failed
+    pub fn new_migrated(request_id: String, error: Option<String>) -> Self {
+        Self {
+            message_type: "lifecycle".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id),
+            payload: MessagePayload::Lifecycle {
+                action: "migrated".to_string(),
+                payload: None,
+                jobs: Vec::new(),
+                format: None,
+                from_version: None,
+                error,
+            },
+        }
+    }
+
+    /// Create the message telling a plugin one of its jobs is due
+    pub fn new_scheduled(job_id: &str) -> Self {
+        Self {
+            message_type: "scheduled".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: None,
+            payload: MessagePayload::Scheduled {
+                job_id: job_id.to_string(),
             },
         }
     }
@@ -115,6 +759,7 @@ impl Message {
         let request_id_clone = request_id.clone();
         Self {
             message_type: "http".to_string(),
+            version: PROTOCOL_VERSION,
             timestamp: Utc::now(),
             request_id: Some(request_id),
             payload: MessagePayload::Http {
@@ -128,6 +773,7 @@ impl Message {
         let request_id_clone = request_id.clone();
         Self {
             message_type: "kv".to_string(),
+            version: PROTOCOL_VERSION,
             timestamp: Utc::now(),
             request_id: Some(request_id),
             payload: MessagePayload::Kv {
@@ -137,16 +783,198 @@ impl Message {
         }
     }
 
+    /// Create a body chunk of a streamed HTTP response (used by plugins after
+    /// answering with `stream: true`)
+    pub fn new_http_chunk(request_id: String, data: &[u8], done: bool) -> Self {
+        Self {
+            message_type: "http_chunk".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id.clone()),
+            payload: MessagePayload::HttpChunk {
+                request_id,
+                payload: HttpBodyChunk::new(data, done),
+            },
+        }
+    }
+
+    /// Create a WebSocket frame message for the socket with this ID
+    pub fn new_ws_frame(socket_id: String, frame: WsFrame) -> Self {
+        Self {
+            message_type: "ws_frame".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: None,
+            payload: MessagePayload::WsFrame {
+                socket_id,
+                payload: frame,
+            },
+        }
+    }
+
+    /// Create an event message; plugins publish events with it
+    pub fn new_event(name: &str, data: serde_json::Value) -> Self {
+        Self {
+            message_type: "event".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: None,
+            payload: MessagePayload::Event {
+                payload: PluginEventPayload {
+                    name: name.to_string(),
+                    data,
+                    source: None,
+                },
+            },
+        }
+    }
+
+    /// Create a message calling another plugin's HTTP handler through core
+    pub fn new_call_plugin(request_id: String, call: PluginCall) -> Self {
+        Self {
+            message_type: "call_plugin".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id.clone()),
+            payload: MessagePayload::CallPlugin {
+                request_id,
+                payload: call,
+            },
+        }
+    }
+
+    /// Create the message answering a `call_plugin` request
+    pub fn new_call_result(request_id: String, result: PluginCallResult) -> Self {
+        Self {
+            message_type: "call_result".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id.clone()),
+            payload: MessagePayload::CallResult {
+                request_id,
+                payload: result,
+            },
+        }
+    }
+
+    /// Create a message asking core to run a quick action or script
+    pub fn new_exec(request_id: String, exec: PluginExec) -> Self {
+        Self {
+            message_type: "exec".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id.clone()),
+            payload: MessagePayload::Exec {
+                request_id,
+                payload: exec,
+            },
+        }
+    }
+
+    /// Create the message answering an `exec` request
+    pub fn new_exec_result(request_id: String, result: PluginExecResult) -> Self {
+        Self {
+            message_type: "exec_result".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id.clone()),
+            payload: MessagePayload::ExecResult {
+                request_id,
+                payload: result,
+            },
+        }
+    }
+
+    /// Create a notification for the users of the Steering Center UI
+    pub fn new_notify(level: NotifyLevel, title: &str, body: Option<&str>) -> Self {
+        Self {
+            message_type: "notify".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: None,
+            payload: MessagePayload::Notify {
+                payload: PluginNotification {
+                    level,
+                    title: title.to_string(),
+                    body: body.map(String::from),
+                },
+            },
+        }
+    }
+
+    /// Create a message asking core for the host's resource usage
+    pub fn new_system_info(request_id: String) -> Self {
+        Self {
+            message_type: "system_info".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id.clone()),
+            payload: MessagePayload::SystemInfo { request_id },
+        }
+    }
+
+    /// Create the message answering a `system_info` request
+    pub fn new_system_info_result(request_id: String, result: PluginSystemInfoResult) -> Self {
+        Self {
+            message_type: "system_info_result".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id.clone()),
+            payload: MessagePayload::SystemInfoResult {
+                request_id,
+                payload: result,
+            },
+        }
+    }
+
+    /// Create a health check message
+    pub fn new_ping(request_id: String) -> Self {
+        Self {
+            message_type: "ping".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id.clone()),
+            payload: MessagePayload::Ping { request_id },
+        }
+    }
+
+    /// Create the answer to a `ping`
+    pub fn new_pong(request_id: String) -> Self {
+        Self {
+            message_type: "pong".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id.clone()),
+            payload: MessagePayload::Pong { request_id },
+        }
+    }
+
+    /// Create an HTTP response message (used by plugins to answer HTTP requests)
+    pub fn new_http_response(request_id: String, response: HttpMessageResponse) -> Self {
+        let request_id_clone = request_id.clone();
+        Self {
+            message_type: "http_response".to_string(),
+            version: PROTOCOL_VERSION,
+            timestamp: Utc::now(),
+            request_id: Some(request_id),
+            payload: MessagePayload::HttpResponse {
+                request_id: request_id_clone,
+                payload: response,
+            },
+        }
+    }
+
     /// Create a KV response message (used by plugins to respond to KV operations)
     pub fn new_kv_response(request_id: String, value: Option<String>) -> Self {
         let request_id_clone = request_id.clone();
         Self {
-            message_type: "kv".to_string(),
+            message_type: "kv_response".to_string(),
+            version: PROTOCOL_VERSION,
             timestamp: Utc::now(),
             request_id: Some(request_id),
-            payload: MessagePayload::Kv {
+            payload: MessagePayload::KvResponse {
                 request_id: request_id_clone,
-                payload: KvMessagePayload::Response { value },
+                value,
             },
         }
     }
@@ -157,9 +985,191 @@ pub struct HttpMessageResponse {
     pub status: u16,
     pub headers: std::collections::HashMap<String, String>,
     pub body: Option<String>,
+    /// How `body` is encoded (UTF-8 text unless stated)
+    #[serde(default, skip_serializing_if = "BodyEncoding::is_utf8")]
+    pub body_encoding: BodyEncoding,
+    /// The body follows as `http_chunk` messages instead of in `body`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KvMessageResponse {
     pub value: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_headers_are_case_insensitive() {
+        let mut request = HttpRequest {
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            headers: std::collections::HashMap::new(),
+            body: None,
+            body_encoding: BodyEncoding::Utf8,
+        };
+        assert_eq!(request.request_id(), None);
+        request
+            .headers
+            .insert("X-Request-Id".to_string(), "abc-123".to_string());
+        assert_eq!(request.request_id(), Some("abc-123"));
+        request.headers.insert(
+            "X-Forwarded-Prefix".to_string(),
+            "/steering/api/plugins/route/hello".to_string(),
+        );
+        assert_eq!(
+            request.forwarded_prefix(),
+            Some("/steering/api/plugins/route/hello")
+        );
+    }
+
+    #[test]
+    fn test_unversioned_peers_are_legacy() {
+        let message: Message = serde_json::from_str(
+            r#"{"type":"lifecycle","timestamp":"2025-12-30T12:00:00Z","request_id":null,
+                "payload":{"type":"lifecycle","action":"shutdown"}}"#,
+        )
+        .unwrap();
+        assert_eq!(message.version, 1);
+
+        let metadata: PluginMetadata = serde_json::from_str(
+            r#"{"id":"old","name":"Old","version":"0.1.0","author":null,"icon":"","route":"/old"}"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.protocol_version, 1);
+        assert!(check_protocol_version(metadata.protocol_version).is_ok());
+
+        assert_eq!(Message::new_ping("p".to_string()).version, PROTOCOL_VERSION);
+        assert!(check_protocol_version(PROTOCOL_VERSION + 1).is_err());
+        assert!(check_protocol_version(0).is_err());
+    }
+
+    #[test]
+    fn test_kv_page_from_keys() {
+        let keys = |names: &[&str]| names.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        let page = KvPage::from_keys(keys(&["a", "b", "c"]), 2);
+        assert_eq!(page.keys, ["a", "b"]);
+        assert_eq!(page.next_cursor.as_deref(), Some("b"));
+        let last = KvPage::from_keys(keys(&["c"]), 2);
+        assert_eq!(last.next_cursor, None);
+
+        let op: KvOp = serde_json::from_str(r#"{"action":"List"}"#).unwrap();
+        assert!(
+            matches!(op, KvOp::List { ref prefix, cursor: None, limit: None } if prefix.is_empty())
+        );
+        assert_eq!(KvOp::list_limit(None), KV_LIST_DEFAULT_LIMIT);
+        assert_eq!(KvOp::list_limit(Some(5000)), KV_LIST_MAX_LIMIT);
+
+        let op: KvOp = serde_json::from_str(r#"{"action":"Incr","key":"hits"}"#).unwrap();
+        assert!(matches!(op, KvOp::Incr { by: 1, .. }));
+    }
+
+    #[test]
+    fn test_response_messages() {
+        let response = HttpMessageResponse {
+            status: 201,
+            headers: std::collections::HashMap::new(),
+            body: Some("created".to_string()),
+            body_encoding: BodyEncoding::Utf8,
+            stream: false,
+        };
+        let json =
+            serde_json::to_value(Message::new_http_response("r1".to_string(), response)).unwrap();
+        assert_eq!(json["type"], "http_response");
+        assert_eq!(json["payload"]["payload"]["status"], 201);
+
+        let message: Message = serde_json::from_value(json).unwrap();
+        assert!(matches!(
+            message.payload,
+            MessagePayload::HttpResponse { ref request_id, ref payload }
+                if request_id == "r1" && payload.body.as_deref() == Some("created")
+        ));
+
+        let json = serde_json::to_value(Message::new_kv_response("r2".to_string(), None)).unwrap();
+        assert_eq!(
+            json["payload"],
+            serde_json::json!({"type": "kv_response", "request_id": "r2", "value": null})
+        );
+    }
+
+    #[test]
+    fn test_notify_message() {
+        let json = serde_json::to_value(Message::new_notify(
+            NotifyLevel::Warning,
+            "Backup overdue",
+            None,
+        ))
+        .unwrap();
+        assert_eq!(
+            json["payload"],
+            serde_json::json!({
+                "type": "notify",
+                "payload": {"level": "warning", "title": "Backup overdue"}
+            })
+        );
+
+        // The level defaults to info
+        let message: Message = serde_json::from_value(serde_json::json!({
+            "type": "notify",
+            "timestamp": "2025-12-30T12:00:00Z",
+            "payload": {"type": "notify", "payload": {"title": "Hi", "body": "there"}}
+        }))
+        .unwrap();
+        assert!(matches!(
+            message.payload,
+            MessagePayload::Notify { ref payload }
+                if payload.level == NotifyLevel::Info && payload.body.as_deref() == Some("there")
+        ));
+    }
+
+    #[test]
+    fn test_migrate_messages() {
+        let json = serde_json::to_value(Message::new_migrate("m-1".to_string(), "1.2.0")).unwrap();
+        assert_eq!(json["request_id"], "m-1");
+        assert_eq!(
+            json["payload"],
+            serde_json::json!({"type": "lifecycle", "action": "migrate", "from_version": "1.2.0"})
+        );
+
+        let json = serde_json::to_string(&Message::new_migrated(
+            "m-1".to_string(),
+            Some("schema too old".to_string()),
+        ))
+        .unwrap();
+        let message: Message = serde_json::from_str(&json).unwrap();
+        assert!(matches!(
+            message.payload,
+            MessagePayload::Lifecycle { ref action, ref error, payload: None, .. }
+                if action == "migrated" && error.as_deref() == Some("schema too old")
+        ));
+    }
+
+    #[test]
+    fn test_system_info_messages() {
+        let json = serde_json::to_value(Message::new_system_info("s1".to_string())).unwrap();
+        assert_eq!(
+            json["payload"],
+            serde_json::json!({"type": "system_info", "request_id": "s1"})
+        );
+        let message: Message = serde_json::from_value(json).unwrap();
+        assert!(matches!(
+            message.payload,
+            MessagePayload::SystemInfo { ref request_id } if request_id == "s1"
+        ));
+
+        let result = PluginSystemInfoResult {
+            resources: None,
+            error: Some("denied".to_string()),
+        };
+        let json = serde_json::to_value(Message::new_system_info_result("s1".to_string(), result))
+            .unwrap();
+        assert_eq!(json["type"], "system_info_result");
+        assert_eq!(
+            json["payload"]["payload"],
+            serde_json::json!({"error": "denied"})
+        );
+    }
+}
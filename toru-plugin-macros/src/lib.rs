@@ -0,0 +1,213 @@
+//! Procedural macros for Toru plugins
+//!
+//! Use them through `toru_plugin_api`, which re-exports them.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, ImplItem, ItemImpl, LitBool, LitStr, Token};
+
+/// Generate `metadata()` and the `main` function of a plugin
+///
+/// Put it on the `impl ToruPlugin for ...` block, above `#[async_trait]`:
+///
+/// ```ignore
+/// #[derive(Default)]
+/// struct MyPlugin;
+///
+/// #[toru_plugin(id = "my-plugin", route = "/my-plugin", name = "My Plugin", icon = "🚀")]
+/// #[async_trait::async_trait]
+/// impl ToruPlugin for MyPlugin {
+///     async fn init(&mut self, ctx: PluginContext) -> PluginResult<()> { ... }
+///     async fn handle_http(&self, req: HttpRequest) -> PluginResult<HttpResponse> { ... }
+///     async fn handle_kv(&mut self, op: KvOp) -> PluginResult<Option<String>> { ... }
+/// }
+/// ```
+///
+/// The generated `main` runs `PluginServer::run` with the plugin's `Default`
+/// value, which also answers `--metadata`.
+///
+/// # Arguments
+/// * `id`, `route` - Required
+/// * `name` - Defaults to the ID
+/// * `version` - Defaults to the crate version
+/// * `author`, `icon` - Optional
+/// * `transport` - `"unix"` (default) or `"tcp"`
+/// * `websockets`, `subscriptions`, `capabilities`, `dependencies` - Lists of
+///   strings, e.g. `capabilities = ["call:*"]` or `dependencies = ["metrics>=1.2"]`
+/// * `main = false` - Don't generate `main`, for plugins with their own entry point
+#[proc_macro_attribute]
+pub fn toru_plugin(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = PluginArgs::default();
+    let parser = syn::meta::parser(|meta| args.parse(meta));
+    parse_macro_input!(attr with parser);
+    let mut item = parse_macro_input!(item as ItemImpl);
+
+    match expand(args, &mut item) {
+        Ok(main) => quote!(#item #main).into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct PluginArgs {
+    id: Option<LitStr>,
+    route: Option<LitStr>,
+    name: Option<LitStr>,
+    version: Option<LitStr>,
+    author: Option<LitStr>,
+    icon: Option<LitStr>,
+    transport: Option<LitStr>,
+    websockets: Vec<LitStr>,
+    subscriptions: Vec<LitStr>,
+    capabilities: Vec<LitStr>,
+    dependencies: Vec<LitStr>,
+    main: bool,
+}
+
+impl Default for PluginArgs {
+    fn default() -> Self {
+        Self {
+            id: None,
+            route: None,
+            name: None,
+            version: None,
+            author: None,
+            icon: None,
+            transport: None,
+            websockets: Vec::new(),
+            subscriptions: Vec::new(),
+            capabilities: Vec::new(),
+            dependencies: Vec::new(),
+            main: true,
+        }
+    }
+}
+
+impl PluginArgs {
+    fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        let Some(key) = meta.path.get_ident().map(|ident| ident.to_string()) else {
+            return Err(meta.error("expected an argument name"));
+        };
+        match key.as_str() {
+            "id" => self.id = Some(meta.value()?.parse()?),
+            "route" => self.route = Some(meta.value()?.parse()?),
+            "name" => self.name = Some(meta.value()?.parse()?),
+            "version" => self.version = Some(meta.value()?.parse()?),
+            "author" => self.author = Some(meta.value()?.parse()?),
+            "icon" => self.icon = Some(meta.value()?.parse()?),
+            "transport" => self.transport = Some(meta.value()?.parse()?),
+            "websockets" => self.websockets = parse_list(&meta)?,
+            "subscriptions" => self.subscriptions = parse_list(&meta)?,
+            "capabilities" => self.capabilities = parse_list(&meta)?,
+            "dependencies" => self.dependencies = parse_list(&meta)?,
+            "main" => self.main = meta.value()?.parse::<LitBool>()?.value,
+            _ => return Err(meta.error(format!("unknown toru_plugin argument `{}`", key))),
+        }
+        Ok(())
+    }
+}
+
+/// `key = ["a", "b"]`
+fn parse_list(meta: &ParseNestedMeta) -> syn::Result<Vec<LitStr>> {
+    let value = meta.value()?;
+    let content;
+    syn::bracketed!(content in value);
+    let items = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+    Ok(items.into_iter().collect())
+}
+
+/// Add `metadata()` to the impl block and return the `main` function
+fn expand(args: PluginArgs, item: &mut ItemImpl) -> syn::Result<proc_macro2::TokenStream> {
+    if item.trait_.is_none() {
+        return Err(syn::Error::new_spanned(
+            &item.self_ty,
+            "toru_plugin goes on the `impl ToruPlugin for ...` block",
+        ));
+    }
+    let defines_metadata = item
+        .items
+        .iter()
+        .any(|item| matches!(item, ImplItem::Fn(f) if f.sig.ident == "metadata"));
+    if defines_metadata {
+        return Err(syn::Error::new_spanned(
+            &item.self_ty,
+            "toru_plugin generates metadata(); remove it from the impl block",
+        ));
+    }
+
+    let id = args
+        .id
+        .ok_or_else(|| syn::Error::new(Span::call_site(), "toru_plugin needs an `id`"))?;
+    let route = args
+        .route
+        .ok_or_else(|| syn::Error::new(Span::call_site(), "toru_plugin needs a `route`"))?;
+    if id.value().is_empty() {
+        return Err(syn::Error::new_spanned(id, "Plugin ID must not be empty"));
+    }
+    if !route.value().starts_with('/') {
+        return Err(syn::Error::new_spanned(route, "Route must start with '/'"));
+    }
+
+    let name = args.name.unwrap_or_else(|| id.clone());
+    let version = match args.version {
+        Some(version) => quote!(#version),
+        None => quote!(env!("CARGO_PKG_VERSION")),
+    };
+    let author = match args.author {
+        Some(author) => quote!(Some(#author.to_string())),
+        None => quote!(None),
+    };
+    let icon = args
+        .icon
+        .unwrap_or_else(|| LitStr::new("", Span::call_site()));
+    let transport = match args.transport.as_ref().map(LitStr::value).as_deref() {
+        None | Some("unix") => quote!(::toru_plugin_api::TransportKind::Unix),
+        Some("tcp") => quote!(::toru_plugin_api::TransportKind::Tcp),
+        Some(_) => {
+            return Err(syn::Error::new_spanned(
+                args.transport,
+                "transport must be \"unix\" or \"tcp\"",
+            ))
+        }
+    };
+    let (websockets, subscriptions, capabilities, dependencies) = (
+        args.websockets,
+        args.subscriptions,
+        args.capabilities,
+        args.dependencies,
+    );
+
+    item.items.push(syn::parse_quote! {
+        fn metadata() -> ::toru_plugin_api::PluginMetadata {
+            ::toru_plugin_api::PluginMetadata {
+                id: #id.to_string(),
+                name: #name.to_string(),
+                version: #version.to_string(),
+                author: #author,
+                icon: #icon.to_string(),
+                route: #route.to_string(),
+                transport: #transport,
+                limits: ::core::default::Default::default(),
+                websockets: vec![#(#websockets.to_string()),*],
+                subscriptions: vec![#(#subscriptions.to_string()),*],
+                capabilities: vec![#(#capabilities.to_string()),*],
+                dependencies: vec![#(#dependencies.to_string()),*],
+                protocol_version: ::toru_plugin_api::PROTOCOL_VERSION,
+            }
+        }
+    });
+
+    if !args.main {
+        return Ok(quote!());
+    }
+    let self_ty = &item.self_ty;
+    Ok(quote! {
+        fn main() -> ::toru_plugin_api::PluginResult<()> {
+            ::toru_plugin_api::PluginServer::run_blocking(
+                <#self_ty as ::core::default::Default>::default(),
+            )
+        }
+    })
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use toru_plugin_api::*;
+
+#[derive(Default)]
+struct FullPlugin;
+
+#[toru_plugin(
+    id = "full-plugin",
+    route = "/full",
+    name = "Full Plugin",
+    version = "2.1.0",
+    author = "ToruAI",
+    icon = "🔌",
+    transport = "tcp",
+    websockets = ["/live"],
+    subscriptions = ["system.*", "hello.published"],
+    capabilities = ["call:*"],
+    dependencies = ["metrics>=1.2"],
+    main = false
+)]
+#[async_trait::async_trait]
+impl ToruPlugin for FullPlugin {
+    async fn init(&mut self, _ctx: PluginContext) -> PluginResult<()> {
+        Ok(())
+    }
+
+    async fn handle_http(&self, _req: HttpRequest) -> PluginResult<HttpResponse> {
+        Ok(HttpResponse {
+            status: 204,
+            headers: HashMap::new(),
+            body: None,
+            body_encoding: Default::default(),
+        })
+    }
+
+    async fn handle_kv(&mut self, _op: KvOp) -> PluginResult<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[derive(Default)]
+struct MinimalPlugin;
+
+#[toru_plugin(id = "minimal-plugin", route = "/minimal", main = false)]
+#[async_trait::async_trait]
+impl ToruPlugin for MinimalPlugin {
+    async fn init(&mut self, _ctx: PluginContext) -> PluginResult<()> {
+        Ok(())
+    }
+
+    async fn handle_http(&self, _req: HttpRequest) -> PluginResult<HttpResponse> {
+        Err(PluginError::NotInitialized)
+    }
+
+    async fn handle_kv(&mut self, _op: KvOp) -> PluginResult<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[test]
+fn test_metadata_from_arguments() {
+    let metadata = FullPlugin::metadata();
+    assert_eq!(metadata.id, "full-plugin");
+    assert_eq!(metadata.route, "/full");
+    assert_eq!(metadata.name, "Full Plugin");
+    assert_eq!(metadata.version, "2.1.0");
+    assert_eq!(metadata.author.as_deref(), Some("ToruAI"));
+    assert_eq!(metadata.icon, "🔌");
+    assert_eq!(metadata.transport, TransportKind::Tcp);
+    assert_eq!(metadata.websockets, ["/live"]);
+    assert_eq!(metadata.subscriptions, ["system.*", "hello.published"]);
+    assert_eq!(metadata.capabilities, ["call:*"]);
+    assert_eq!(metadata.dependencies, ["metrics>=1.2"]);
+    assert_eq!(metadata.protocol_version, PROTOCOL_VERSION);
+
+    let metadata = MinimalPlugin::metadata();
+    assert_eq!(metadata.name, "minimal-plugin");
+    assert_eq!(metadata.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(metadata.author, None);
+    assert_eq!(metadata.transport, TransportKind::Unix);
+    assert!(metadata.websockets.is_empty());
+    assert!(metadata.dependencies.is_empty());
+}
@@ -0,0 +1,345 @@
+//! Mock Steering Center host for testing plugins
+//!
+//! `MockHost` talks the plugin protocol to a plugin binary or to a
+//! [`ToruPlugin`] running in the test itself, so plugins can be tested in CI
+//! without the full server:
+//!
+//! ```no_run
+//! # async fn test() -> toru_plugin_api::PluginResult<()> {
+//! use toru_plugin_test::MockHost;
+//!
+//! let mut host = MockHost::spawn("target/release/my-plugin").await?;
+//! host.init().await?;
+//! let response = host.get("/").await?;
+//! assert_eq!(response.status, 200);
+//! host.shutdown().await
+//! # }
+//! ```
+
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+use toru_plugin_api::transport::{ADDRESS_ENV, SOCKET_ENV};
+use toru_plugin_api::{
+    HttpMessageResponse, HttpRequest, KvMessagePayload, KvOp, LifecycleInitPayload, Message,
+    MessagePayload, PluginError, PluginProtocol, PluginResult, PluginServer, PluginTransport,
+    ToruPlugin, WireFormat, PROTOCOL_VERSION,
+};
+
+/// Instance ID the mock host sends in `init`
+pub const TEST_INSTANCE_ID: &str = "test-instance";
+
+/// How long a plugin gets to answer a request
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a spawned plugin gets to start listening
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+trait HostStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> HostStream for S {}
+
+/// Plugin under test
+enum Runner {
+    Process(Child),
+    InProcess(JoinHandle<PluginResult<bool>>),
+}
+
+/// A connection to a plugin, playing the part of the Steering Center
+pub struct MockHost {
+    stream: Box<dyn HostStream>,
+    protocol: PluginProtocol,
+    runner: Runner,
+    /// Messages from the plugin that didn't answer a request
+    received: Vec<Message>,
+    next_request: u64,
+    timeout: Duration,
+    /// Holds the socket of a spawned plugin
+    _socket_dir: Option<tempfile::TempDir>,
+}
+
+impl MockHost {
+    /// Start a plugin binary and connect to it
+    ///
+    /// The plugin listens on a Unix socket in a temporary directory, passed in
+    /// like the supervisor does. It is killed when the host is dropped.
+    pub async fn spawn(binary: impl AsRef<Path>) -> PluginResult<Self> {
+        let socket_dir = tempfile::tempdir()?;
+        let socket_path = socket_dir.path().join("plugin.sock");
+        let mut child = Command::new(binary.as_ref())
+            .env(ADDRESS_ENV, format!("unix://{}", socket_path.display()))
+            .env(SOCKET_ENV, &socket_path)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let transport = PluginTransport::Unix(socket_path);
+        let started = tokio::time::Instant::now();
+        let stream = loop {
+            if let Ok(stream) = transport.connect(None).await {
+                break stream;
+            }
+            if let Some(status) = child.try_wait()? {
+                return Err(PluginError::Socket(format!(
+                    "Plugin exited before listening: {}",
+                    status
+                )));
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                return Err(PluginError::Timeout);
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+
+        Ok(Self::new(
+            Box::new(stream),
+            Runner::Process(child),
+            Some(socket_dir),
+        ))
+    }
+
+    /// Run a plugin in this process with [`PluginServer`], connected through
+    /// an in-memory stream
+    pub fn in_process<P>(plugin: P) -> Self
+    where
+        P: ToruPlugin + Send + Sync + 'static,
+    {
+        Self::in_process_server(PluginServer::new(plugin))
+    }
+
+    /// Like [`in_process`](Self::in_process), for a server set up with its
+    /// `with_*` methods
+    pub fn in_process_server<P>(mut server: PluginServer<P>) -> Self
+    where
+        P: ToruPlugin + Send + Sync + 'static,
+    {
+        let (host, mut plugin_end) = tokio::io::duplex(1024 * 1024);
+        let server = tokio::spawn(async move { server.serve(&mut plugin_end).await });
+        Self::new(Box::new(host), Runner::InProcess(server), None)
+    }
+
+    fn new(
+        stream: Box<dyn HostStream>,
+        runner: Runner,
+        socket_dir: Option<tempfile::TempDir>,
+    ) -> Self {
+        Self {
+            stream,
+            protocol: PluginProtocol::new(),
+            runner,
+            received: Vec::new(),
+            next_request: 0,
+            timeout: DEFAULT_TIMEOUT,
+            _socket_dir: socket_dir,
+        }
+    }
+
+    /// Wait `timeout` instead of [`DEFAULT_TIMEOUT`] for answers
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Messages the plugin sent on its own (events, job registrations, plugin
+    /// calls, ...), oldest first
+    pub fn received(&self) -> &[Message] {
+        &self.received
+    }
+
+    /// Format the host writes in; the plugin may switch it after `init`
+    pub fn wire_format(&self) -> WireFormat {
+        self.protocol.format()
+    }
+
+    /// Send any message to the plugin, e.g. an event or a due job
+    pub async fn send(&mut self, message: &Message) -> PluginResult<()> {
+        self.protocol.write_message(&mut self.stream, message).await
+    }
+
+    /// Send `init` with [`TEST_INSTANCE_ID`], offering every wire format
+    pub async fn init(&mut self) -> PluginResult<()> {
+        let payload = LifecycleInitPayload {
+            instance_id: TEST_INSTANCE_ID.to_string(),
+            plugin_socket: String::new(),
+            log_path: String::new(),
+            protocol_version: PROTOCOL_VERSION,
+            wire_formats: vec![WireFormat::MessagePack, WireFormat::Json],
+            instance_name: None,
+            settings: Default::default(),
+        };
+        self.send(&Message::new_lifecycle("init", Some(payload)))
+            .await
+    }
+
+    /// Send an HTTP request and wait for the response
+    ///
+    /// A streamed body is read to the end and returned in `body`.
+    pub async fn request(&mut self, request: HttpRequest) -> PluginResult<HttpMessageResponse> {
+        let request_id = self.request_id();
+        self.send(&Message::new_http(request_id.clone(), request))
+            .await?;
+
+        let mut response = match self.answer(&request_id).await?.payload {
+            MessagePayload::HttpResponse { payload, .. } => payload,
+            // Plugins older than protocol version 3 wrap the response in an `http` message
+            MessagePayload::Http { payload, .. } => {
+                serde_json::from_str(payload.body.as_deref().unwrap_or("{}"))?
+            }
+            other => {
+                return Err(PluginError::Protocol(format!(
+                    "Expected an HTTP response, got {:?}",
+                    other
+                )))
+            }
+        };
+
+        if response.stream {
+            let mut body = Vec::new();
+            loop {
+                match self.answer(&request_id).await?.payload {
+                    MessagePayload::HttpChunk { payload, .. } => {
+                        body.extend(payload.bytes()?);
+                        if payload.done {
+                            break;
+                        }
+                    }
+                    other => {
+                        return Err(PluginError::Protocol(format!(
+                            "Expected a body chunk, got {:?}",
+                            other
+                        )))
+                    }
+                }
+            }
+            response.set_body_bytes(&body);
+            response.stream = false;
+        }
+        Ok(response)
+    }
+
+    /// `GET path`
+    pub async fn get(&mut self, path: &str) -> PluginResult<HttpMessageResponse> {
+        self.request(http_request("GET", path, None)).await
+    }
+
+    /// `POST path` with a JSON body
+    pub async fn post(&mut self, path: &str, body: &str) -> PluginResult<HttpMessageResponse> {
+        self.request(http_request("POST", path, Some(body))).await
+    }
+
+    /// Send a KV operation and wait for the value the plugin answers with
+    pub async fn kv(&mut self, op: KvOp) -> PluginResult<Option<String>> {
+        let request_id = self.request_id();
+        self.send(&Message::new_kv(request_id.clone(), op)).await?;
+
+        match self.answer(&request_id).await?.payload {
+            MessagePayload::KvResponse { value, .. }
+            | MessagePayload::Kv {
+                payload: KvMessagePayload::Response { value },
+                ..
+            } => Ok(value),
+            other => Err(PluginError::Protocol(format!(
+                "Expected a KV response, got {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Ping the plugin; fails with [`PluginError::Timeout`] if it doesn't pong
+    pub async fn ping(&mut self) -> PluginResult<()> {
+        let request_id = self.request_id();
+        self.send(&Message::new_ping(request_id.clone())).await?;
+        self.answer(&request_id).await.map(|_| ())
+    }
+
+    /// Send `shutdown` and wait for the plugin to stop
+    pub async fn shutdown(mut self) -> PluginResult<()> {
+        self.send(&Message::new_lifecycle("shutdown", None)).await?;
+        match &mut self.runner {
+            Runner::Process(child) => {
+                let status = tokio::time::timeout(self.timeout, child.wait())
+                    .await
+                    .map_err(|_| PluginError::Timeout)??;
+                if !status.success() {
+                    return Err(PluginError::Internal(format!(
+                        "Plugin exited with {}",
+                        status
+                    )));
+                }
+            }
+            Runner::InProcess(server) => {
+                let stopped = tokio::time::timeout(self.timeout, server)
+                    .await
+                    .map_err(|_| PluginError::Timeout)?
+                    .map_err(|e| PluginError::Internal(e.to_string()))??;
+                if !stopped {
+                    return Err(PluginError::Protocol(
+                        "Plugin closed the connection instead of shutting down".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn request_id(&mut self) -> String {
+        self.next_request += 1;
+        format!("test-{}", self.next_request)
+    }
+
+    /// Read messages until one answers `request_id`, keeping the others in
+    /// `received`
+    async fn answer(&mut self, request_id: &str) -> PluginResult<Message> {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let message =
+                tokio::time::timeout_at(deadline, self.protocol.read_message(&mut self.stream))
+                    .await
+                    .map_err(|_| PluginError::Timeout)??;
+            if answered_request(&message) == Some(request_id) {
+                return Ok(message);
+            }
+            if let MessagePayload::Lifecycle {
+                action,
+                format: Some(format),
+                ..
+            } = &message.payload
+            {
+                if action == "wire_format" {
+                    self.protocol.set_format(*format);
+                }
+            }
+            self.received.push(message);
+        }
+    }
+}
+
+/// Request ID a message from the plugin answers
+fn answered_request(message: &Message) -> Option<&str> {
+    match &message.payload {
+        MessagePayload::Http { request_id, .. }
+        | MessagePayload::HttpResponse { request_id, .. }
+        | MessagePayload::HttpChunk { request_id, .. }
+        | MessagePayload::Kv { request_id, .. }
+        | MessagePayload::KvResponse { request_id, .. }
+        | MessagePayload::Pong { request_id } => Some(request_id),
+        _ => None,
+    }
+}
+
+fn http_request(method: &str, path: &str, body: Option<&str>) -> HttpRequest {
+    let mut headers = std::collections::HashMap::new();
+    if body.is_some() {
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+    }
+    HttpRequest {
+        method: method.to_string(),
+        path: path.to_string(),
+        headers,
+        body: body.map(str::to_string),
+        body_encoding: Default::default(),
+    }
+}
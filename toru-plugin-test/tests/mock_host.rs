@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use toru_plugin_api::*;
+use toru_plugin_test::{MockHost, TEST_INSTANCE_ID};
+
+#[derive(Default)]
+struct NotesPlugin {
+    ctx: Option<PluginContext>,
+}
+
+#[toru_plugin(id = "notes", route = "/notes", main = false)]
+#[async_trait::async_trait]
+impl ToruPlugin for NotesPlugin {
+    async fn init(&mut self, ctx: PluginContext) -> PluginResult<()> {
+        self.ctx = Some(ctx);
+        Ok(())
+    }
+
+    async fn handle_http(&self, req: HttpRequest) -> PluginResult<HttpResponse> {
+        let ctx = self.ctx.as_ref().ok_or(PluginError::NotInitialized)?;
+        let body = match req.path.as_str() {
+            "/note" => ctx.kv.get("note").await?,
+            _ => Some(ctx.instance_id.clone()),
+        };
+        Ok(HttpResponse {
+            status: 200,
+            headers: HashMap::new(),
+            body,
+            body_encoding: Default::default(),
+        })
+    }
+
+    async fn handle_kv(&mut self, op: KvOp) -> PluginResult<Option<String>> {
+        let kv = &self.ctx.as_ref().ok_or(PluginError::NotInitialized)?.kv;
+        match op {
+            KvOp::Get { key } => kv.get(&key).await,
+            KvOp::Set {
+                key,
+                value,
+                ttl_secs,
+            } => kv
+                .set_with_ttl(&key, &value, ttl_secs.map(Duration::from_secs))
+                .await
+                .map(|_| None),
+            KvOp::Delete { key } => kv.delete(&key).await.map(|_| None),
+            KvOp::List {
+                prefix,
+                cursor,
+                limit,
+            } => {
+                let page = kv
+                    .list(&prefix, cursor.as_deref(), KvOp::list_limit(limit))
+                    .await?;
+                Ok(Some(serde_json::to_string(&page)?))
+            }
+            KvOp::Incr { key, by } => kv.incr(&key, by).await.map(|v| Some(v.to_string())),
+            KvOp::Cas { key, expected, new } => kv
+                .compare_and_swap(&key, expected.as_deref(), &new)
+                .await
+                .map(|swapped| Some(swapped.to_string())),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_in_process_plugin() {
+    let mut host = MockHost::in_process(NotesPlugin::default());
+
+    // Requests before init reach the plugin, which fails them
+    assert_eq!(host.get("/").await.unwrap().status, 500);
+
+    host.init().await.unwrap();
+    let response = host.get("/").await.unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body.as_deref(), Some(TEST_INSTANCE_ID));
+
+    let set = KvOp::Set {
+        key: "note".to_string(),
+        value: "buy milk".to_string(),
+        ttl_secs: None,
+    };
+    assert_eq!(host.kv(set).await.unwrap(), None);
+    let note = host.get("/note").await.unwrap();
+    assert_eq!(note.body.as_deref(), Some("buy milk"));
+
+    let list = KvOp::List {
+        prefix: "no".to_string(),
+        cursor: None,
+        limit: None,
+    };
+    let page: KvPage = serde_json::from_str(&host.kv(list).await.unwrap().unwrap()).unwrap();
+    assert_eq!(page.keys, ["note"]);
+
+    let incr = KvOp::Incr {
+        key: "visits".to_string(),
+        by: 2,
+    };
+    assert_eq!(host.kv(incr).await.unwrap().as_deref(), Some("2"));
+    let cas = KvOp::Cas {
+        key: "note".to_string(),
+        expected: Some("stale".to_string()),
+        new: "buy bread".to_string(),
+    };
+    assert_eq!(host.kv(cas).await.unwrap().as_deref(), Some("false"));
+
+    host.ping().await.unwrap();
+    host.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_message_pack_plugin() {
+    let server =
+        PluginServer::new(NotesPlugin::default()).with_wire_format(WireFormat::MessagePack);
+    let mut host = MockHost::in_process_server(server);
+    assert_eq!(host.wire_format(), WireFormat::Json);
+
+    host.init().await.unwrap();
+    let response = host.get("/").await.unwrap();
+    assert_eq!(response.body.as_deref(), Some(TEST_INSTANCE_ID));
+    assert_eq!(host.wire_format(), WireFormat::MessagePack);
+
+    let set = KvOp::Set {
+        key: "note".to_string(),
+        value: "packed".to_string(),
+        ttl_secs: Some(60),
+    };
+    host.kv(set).await.unwrap();
+    assert_eq!(
+        host.get("/note").await.unwrap().body.as_deref(),
+        Some("packed")
+    );
+    host.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_spawned_plugin() {
+    // Built from examples/hello-plugin-rust, like the supervisor's integration tests
+    let binary = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../plugins/hello-plugin-rust.binary"
+    );
+    let mut host = MockHost::spawn(binary).await.unwrap();
+    host.init().await.unwrap();
+
+    let response = host.get("/").await.unwrap();
+    assert_eq!(response.status, 200);
+    let json: serde_json::Value = serde_json::from_str(response.body.as_deref().unwrap()).unwrap();
+    assert_eq!(json["instance_id"], TEST_INSTANCE_ID);
+
+    // Streamed bodies are collected
+    let ticks = host.get("/ticks").await.unwrap();
+    assert_eq!(ticks.body.as_deref(), Some("tick 1\ntick 2\ntick 3\n"));
+
+    // Messages the plugin sends on its own are kept for assertions
+    assert_eq!(host.post("/publish", "\"hi\"").await.unwrap().status, 202);
+    let events: Vec<_> = host
+        .received()
+        .iter()
+        .filter_map(|message| match &message.payload {
+            MessagePayload::Event { payload } => Some(payload.name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(events, ["hello.published"]);
+
+    let get = KvOp::Get {
+        key: "counter".to_string(),
+    };
+    assert_eq!(host.kv(get).await.unwrap().as_deref(), Some("0"));
+    host.ping().await.unwrap();
+    host.shutdown().await.unwrap();
+}